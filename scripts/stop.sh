@@ -24,14 +24,16 @@ echo "Stopping services..."
 pkill -f "facto-ingestion" 2>/dev/null || true
 pkill -f "processor" 2>/dev/null || true
 pkill -f "./api" 2>/dev/null || true
+pkill -f "./indexer" 2>/dev/null || true
+pkill -f "./archiver" 2>/dev/null || true
 
 # Stop Docker containers
 echo "Stopping Docker containers..."
-docker-compose down
+docker-compose --profile indexer --profile archiver down
 
 if [[ "$KEEP_DATA" == "false" ]]; then
     echo "Removing volumes..."
-    docker-compose down -v
+    docker-compose --profile indexer --profile archiver down -v
     echo "Data volumes removed."
 else
     echo "Data volumes preserved."
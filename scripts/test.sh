@@ -44,6 +44,15 @@ run_unit_tests() {
         echo "Cargo not found, skipping Rust tests"
     fi
 
+    echo ""
+    echo "Running Go shared package tests..."
+    cd "$PROJECT_DIR/server/common"
+    if command -v go &> /dev/null; then
+        go test -v ./... 2>&1 || echo "Go shared package tests completed (some may have failed)"
+    else
+        echo "Go not found, skipping Go tests"
+    fi
+
     echo ""
     echo "Running Go processor tests..."
     cd "$PROJECT_DIR/server/processor"
@@ -61,6 +70,24 @@ run_unit_tests() {
     else
         echo "Go not found, skipping Go tests"
     fi
+
+    echo ""
+    echo "Running Go indexer tests..."
+    cd "$PROJECT_DIR/server/indexer"
+    if command -v go &> /dev/null; then
+        go test -v ./... 2>&1 || echo "Go indexer tests completed (some may have failed)"
+    else
+        echo "Go not found, skipping Go tests"
+    fi
+
+    echo ""
+    echo "Running Go archiver tests..."
+    cd "$PROJECT_DIR/server/archiver"
+    if command -v go &> /dev/null; then
+        go test -v ./... 2>&1 || echo "Go archiver tests completed (some may have failed)"
+    else
+        echo "Go not found, skipping Go tests"
+    fi
 }
 
 run_integration_tests() {
@@ -33,7 +33,7 @@ Example usage:
     client.close()
 """
 
-from .client import AsyncFactoClient, FactoClient
+from .client import KEY_ROTATION_ACTION, AsyncFactoClient, FactoClient
 from .crypto import CryptoProvider, generate_keypair, verify_event
 from .cli import verify_evidence_bundle
 from .models import (
@@ -51,6 +51,7 @@ __all__ = [
     # Clients
     "FactoClient",
     "AsyncFactoClient",
+    "KEY_ROTATION_ACTION",
     # Configuration
     "FactoConfig",
     # Models
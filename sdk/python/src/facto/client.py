@@ -25,6 +25,87 @@ from .models import (
 F = TypeVar("F", bound=Callable[..., Any])
 
 
+KEY_ROTATION_ACTION = "key_rotation"
+"""Action type of the event attesting to a signing key rotation."""
+
+
+def _build_event(
+    config: FactoConfig,
+    crypto: CryptoProvider,
+    action_type: str,
+    input_data: Dict[str, Any],
+    output_data: Dict[str, Any],
+    status: str = "success",
+    parent_facto_id: Optional[str] = None,
+    execution_meta: Optional[ExecutionMeta] = None,
+    started_at: Optional[int] = None,
+    completed_at: Optional[int] = None,
+) -> FactoEvent:
+    """Build and sign an event chained to the provider's prev_hash."""
+    facto_id = generate_facto_id()
+    now = current_time_ns()
+
+    if execution_meta is None:
+        execution_meta = ExecutionMeta(tags=config.tags.copy())
+    else:
+        # Merge config tags with execution meta tags
+        merged_tags = config.tags.copy()
+        merged_tags.update(execution_meta.tags)
+        execution_meta.tags = merged_tags
+
+    # Build event dict for signing
+    event_dict = {
+        "facto_id": facto_id,
+        "agent_id": config.agent_id,
+        "session_id": config.session_id,
+        "parent_facto_id": parent_facto_id,
+        "action_type": action_type,
+        "status": status,
+        "input_data": input_data,
+        "output_data": output_data,
+        "execution_meta": {
+            "model_id": execution_meta.model_id,
+            "model_hash": execution_meta.model_hash,
+            "temperature": execution_meta.temperature,
+            "seed": execution_meta.seed,
+            "max_tokens": execution_meta.max_tokens,
+            "tool_calls": execution_meta.tool_calls,
+            "sdk_version": execution_meta.sdk_version,
+            "sdk_language": execution_meta.sdk_language,
+            "tags": execution_meta.tags,
+        },
+        "proof": {
+            "prev_hash": crypto.prev_hash,
+        },
+        "started_at": started_at or now,
+        "completed_at": completed_at or now,
+    }
+
+    # Sign the event
+    event_hash, signature = crypto.sign_event(event_dict)
+
+    return FactoEvent(
+        facto_id=facto_id,
+        agent_id=config.agent_id,
+        session_id=config.session_id,
+        parent_facto_id=parent_facto_id,
+        action_type=action_type,
+        status=status,
+        input_data=input_data,
+        output_data=output_data,
+        execution_meta=execution_meta,
+        proof=Proof(
+            signature=signature,
+            public_key=crypto.public_key_base64,
+            prev_hash=crypto.prev_hash,
+            event_hash=event_hash,
+            verification_method=config.verification_method,
+        ),
+        started_at=started_at or now,
+        completed_at=completed_at or now,
+    )
+
+
 class FactoClient:
     """Synchronous client for sending facto events."""
 
@@ -147,6 +228,7 @@ class FactoClient:
                 public_key=self._crypto.public_key_base64,
                 prev_hash=self._crypto.prev_hash,
                 event_hash=event_hash,
+                verification_method=self.config.verification_method,
             ),
             started_at=started_at or now,
             completed_at=completed_at or now,
@@ -252,6 +334,47 @@ class FactoClient:
 
         return decorator
 
+    def rotate_key(
+        self,
+        private_key: Optional[bytes] = None,
+        overlap_secs: Optional[int] = None,
+    ) -> str:
+        """
+        Rotate the signing key without breaking the session's chain.
+
+        Pending events are sent first. A key rotation event naming the new
+        key is then signed with the current key and sent on its own; once
+        the server accepts it, the client signs with the new key. The server
+        keeps accepting the old key for the overlap. The current key must be
+        registered for the agent on the server.
+
+        Args:
+            private_key: Ed25519 private key seed of the new key. If None,
+                generates a new keypair.
+            overlap_secs: How long the old key stays valid, at most the
+                server's limit. Defaults to the server's limit.
+
+        Returns:
+            The facto_id of the key rotation event
+        """
+        new_crypto = CryptoProvider(private_key=private_key)
+        rotation: Dict[str, Any] = {"new_public_key": new_crypto.public_key_base64}
+        if overlap_secs is not None:
+            rotation["overlap_secs"] = overlap_secs
+
+        with self._batch_lock:
+            self._flush_batch()
+            event = _build_event(self.config, self._crypto, KEY_ROTATION_ACTION, rotation, {})
+            response = self._http_client.post("/v1/ingest", json=event.to_dict())
+            response.raise_for_status()
+            self._crypto.update_prev_hash(event.proof.event_hash)
+            # A did:key names the key itself, so it follows the rotation
+            if self.config.verification_method == self._crypto.did_key:
+                self.config.verification_method = new_crypto.did_key
+            self._crypto.replace_key(new_crypto.private_key)
+
+        return event.facto_id
+
     def flush(self) -> None:
         """Flush the current batch of events."""
         with self._batch_lock:
@@ -277,9 +400,13 @@ class FactoClient:
         if not events:
             return
 
-        payload = {
+        payload: Dict[str, Any] = {
             "events": [event.to_dict() for event in events],
         }
+        if self.config.batch_envelope:
+            payload["envelope"] = self._crypto.sign_batch(
+                [event.proof.event_hash for event in events]
+            )
 
         for attempt in range(self.config.max_retries):
             try:
@@ -424,6 +551,7 @@ class AsyncFactoClient:
                 public_key=self._crypto.public_key_base64,
                 prev_hash=self._crypto.prev_hash,
                 event_hash=event_hash,
+                verification_method=self.config.verification_method,
             ),
             started_at=started_at or now,
             completed_at=completed_at or now,
@@ -438,6 +566,41 @@ class AsyncFactoClient:
 
         return facto_id
 
+    async def rotate_key(
+        self,
+        private_key: Optional[bytes] = None,
+        overlap_secs: Optional[int] = None,
+    ) -> str:
+        """
+        Rotate the signing key asynchronously. See `FactoClient.rotate_key`.
+
+        Args:
+            private_key: Ed25519 private key seed of the new key. If None,
+                generates a new keypair.
+            overlap_secs: How long the old key stays valid, at most the
+                server's limit. Defaults to the server's limit.
+
+        Returns:
+            The facto_id of the key rotation event
+        """
+        new_crypto = CryptoProvider(private_key=private_key)
+        rotation: Dict[str, Any] = {"new_public_key": new_crypto.public_key_base64}
+        if overlap_secs is not None:
+            rotation["overlap_secs"] = overlap_secs
+
+        async with self._batch_lock:
+            await self._flush_batch()
+            event = _build_event(self.config, self._crypto, KEY_ROTATION_ACTION, rotation, {})
+            response = await self._http_client.post("/v1/ingest", json=event.to_dict())
+            response.raise_for_status()
+            self._crypto.update_prev_hash(event.proof.event_hash)
+            # A did:key names the key itself, so it follows the rotation
+            if self.config.verification_method == self._crypto.did_key:
+                self.config.verification_method = new_crypto.did_key
+            self._crypto.replace_key(new_crypto.private_key)
+
+        return event.facto_id
+
     async def flush(self) -> None:
         """Flush the current batch of events."""
         async with self._batch_lock:
@@ -462,9 +625,13 @@ class AsyncFactoClient:
         if not events:
             return
 
-        payload = {
+        payload: Dict[str, Any] = {
             "events": [event.to_dict() for event in events],
         }
+        if self.config.batch_envelope:
+            payload["envelope"] = self._crypto.sign_batch(
+                [event.proof.event_hash for event in events]
+            )
 
         for attempt in range(self.config.max_retries):
             try:
@@ -3,11 +3,27 @@
 import base64
 import hashlib
 import json
-from typing import Any, Dict, Optional, Tuple
+from typing import Any, Dict, List, Optional, Tuple
 
 from nacl.signing import SigningKey, VerifyKey
 from nacl.exceptions import BadSignatureError
 
+# Multicodec prefix of Ed25519 public keys in did:key DIDs
+_ED25519_MULTICODEC = b"\xed\x01"
+
+_BASE58_ALPHABET = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz"
+
+
+def _base58_encode(data: bytes) -> str:
+    """Bitcoin base58, the encoding of multibase 'z' strings."""
+    number = int.from_bytes(data, "big")
+    encoded = ""
+    while number:
+        number, digit = divmod(number, 58)
+        encoded = _BASE58_ALPHABET[digit] + encoded
+    zeros = len(data) - len(data.lstrip(b"\0"))
+    return "1" * zeros + encoded
+
 
 class CryptoProvider:
     """Handles cryptographic operations for event signing and verification."""
@@ -46,6 +62,11 @@ class CryptoProvider:
         """Get the public key as base64 string."""
         return base64.b64encode(self.public_key).decode("ascii")
 
+    @property
+    def did_key(self) -> str:
+        """Get the did:key DID of the public key."""
+        return "did:key:z" + _base58_encode(_ED25519_MULTICODEC + self.public_key)
+
     @property
     def private_key(self) -> bytes:
         """Get the private key seed bytes."""
@@ -60,6 +81,18 @@ class CryptoProvider:
         """Update the prev_hash after successfully sending an event."""
         self._prev_hash = event_hash
 
+    def replace_key(self, private_key: bytes) -> None:
+        """
+        Sign with a new key from now on, continuing the same chain.
+
+        Args:
+            private_key: Ed25519 private key seed of the new key (32 bytes)
+        """
+        if len(private_key) != 32:
+            raise ValueError("Private key must be 32 bytes")
+        self._signing_key = SigningKey(private_key)
+        self._verify_key = self._signing_key.verify_key
+
     def build_canonical_form(self, event_dict: Dict[str, Any]) -> str:
         """
         Build the canonical JSON form for hashing/signing.
@@ -142,6 +175,27 @@ class CryptoProvider:
 
         return event_hash, signature
 
+    def sign_batch(self, event_hashes: List[str]) -> Dict[str, str]:
+        """
+        Sign a batch envelope over the event hashes of a batch.
+
+        Args:
+            event_hashes: The events' hashes in the order they are sent
+
+        Returns:
+            The envelope to send as the batch request's `envelope`
+        """
+        hasher = hashlib.sha3_256()
+        for event_hash in event_hashes:
+            hasher.update(event_hash.encode("ascii"))
+        batch_hash = hasher.hexdigest()
+
+        return {
+            "batch_hash": batch_hash,
+            "public_key": self.public_key_base64,
+            "signature": self.sign_base64(batch_hash.encode("utf-8")),
+        }
+
 
 def generate_keypair() -> Tuple[bytes, bytes]:
     """
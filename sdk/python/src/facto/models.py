@@ -20,6 +20,12 @@ class FactoConfig:
     timeout_seconds: float = 30.0
     max_retries: int = 3
     tags: Dict[str, str] = field(default_factory=dict)
+    # Sign each batch once so the server can skip per-event signature
+    # checks for agents it trusts with batch envelopes
+    batch_envelope: bool = False
+    # DID URL of the signing key, named in each proof so consumers can
+    # identify the agent by its DID (e.g. CryptoProvider.did_key)
+    verification_method: Optional[str] = None
 
     def __post_init__(self) -> None:
         if not self.session_id:
@@ -49,6 +55,7 @@ class Proof:
     public_key: str  # Base64-encoded Ed25519 public key
     prev_hash: str  # SHA3-256 hash of previous event (hex)
     event_hash: str  # SHA3-256 hash of this event (hex)
+    verification_method: Optional[str] = None  # DID URL resolving to public_key
 
 
 @dataclass
@@ -70,6 +77,14 @@ class FactoEvent:
 
     def to_dict(self) -> Dict[str, Any]:
         """Convert to dictionary for JSON serialization."""
+        proof = {
+            "signature": self.proof.signature,
+            "public_key": self.proof.public_key,
+            "prev_hash": self.proof.prev_hash,
+            "event_hash": self.proof.event_hash,
+        }
+        if self.proof.verification_method is not None:
+            proof["verification_method"] = self.proof.verification_method
         return {
             "facto_id": self.facto_id,
             "agent_id": self.agent_id,
@@ -90,12 +105,7 @@ class FactoEvent:
                 "sdk_language": self.execution_meta.sdk_language,
                 "tags": self.execution_meta.tags,
             },
-            "proof": {
-                "signature": self.proof.signature,
-                "public_key": self.proof.public_key,
-                "prev_hash": self.proof.prev_hash,
-                "event_hash": self.proof.event_hash,
-            },
+            "proof": proof,
             "started_at": self.started_at,
             "completed_at": self.completed_at,
         }
@@ -8,6 +8,7 @@ from facto import (
     ExecutionMeta,
     FactoClient,
     FactoConfig,
+    KEY_ROTATION_ACTION,
     generate_keypair,
     verify_event,
 )
@@ -113,6 +114,26 @@ class TestCryptoProvider:
         assert len(event_hash) == 64
         assert len(signature) > 0
 
+    def test_sign_batch(self):
+        """Test batch envelope signing."""
+        import base64
+        import hashlib
+
+        crypto = CryptoProvider()
+        hashes = ["a" * 64, "b" * 64]
+        envelope = crypto.sign_batch(hashes)
+        expected = hashlib.sha3_256(("a" * 64 + "b" * 64).encode()).hexdigest()
+        assert envelope["batch_hash"] == expected
+        assert envelope["public_key"] == crypto.public_key_base64
+        signature = base64.b64decode(envelope["signature"])
+        assert crypto.verify(envelope["batch_hash"].encode(), signature, crypto.public_key)
+
+    def test_did_key(self):
+        """Test the did:key DID of the public key (RFC 8032 test vector 1)."""
+        seed = bytes.fromhex("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60")
+        crypto = CryptoProvider(private_key=seed)
+        assert crypto.did_key == "did:key:z6MktwupdmLXVVqTzCw4i46r4uGyosGXRnR3XjN4Zq7oMMsw"
+
 
 class TestFactoConfig:
     """Tests for FactoConfig."""
@@ -270,6 +291,46 @@ class TestFactoClient:
 
         client.close()
 
+    def test_rotate_key(self):
+        """Test that key rotation is signed with the old key and continues the chain."""
+        import base64
+        import json
+
+        import httpx
+
+        sent = []
+
+        def handler(request: httpx.Request) -> httpx.Response:
+            sent.append(json.loads(request.content))
+            return httpx.Response(202, json={"accepted": True})
+
+        config = FactoConfig(endpoint="http://localhost:8080", agent_id="test-agent")
+        client = FactoClient(config)
+        client._http_client = httpx.Client(
+            base_url=config.endpoint, transport=httpx.MockTransport(handler)
+        )
+        old_key = client._crypto.public_key_base64
+        new_private, new_public = generate_keypair()
+
+        facto_id = client.rotate_key(private_key=new_private, overlap_secs=60)
+
+        rotation = sent[-1]
+        assert rotation["facto_id"] == facto_id
+        assert rotation["action_type"] == KEY_ROTATION_ACTION
+        assert rotation["proof"]["public_key"] == old_key
+        assert rotation["input_data"] == {
+            "new_public_key": base64.b64encode(new_public).decode("ascii"),
+            "overlap_secs": 60,
+        }
+
+        client.record(action_type="after", input_data={}, output_data={})
+        event = client._batch[0]
+        assert "verification_method" not in event.to_dict()["proof"]
+        assert event.proof.public_key == base64.b64encode(new_public).decode("ascii")
+        assert event.proof.prev_hash == rotation["proof"]["event_hash"]
+
+        client.close()
+
 
 if __name__ == "__main__":
     pytest.main([__file__, "-v"])
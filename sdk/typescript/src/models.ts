@@ -140,6 +140,16 @@ export interface BatchIngestRequest {
   batch_id?: string;
 }
 
+/**
+ * Why the server refused an event. `code` is stable; branch on it rather
+ * than on `message`.
+ */
+export interface EventError {
+  code: string;
+  message: string;
+  details?: Record<string, unknown>;
+}
+
 /**
  * Batch ingest response.
  */
@@ -148,7 +158,9 @@ export interface BatchIngestResponse {
   rejected_count: number;
   rejected: Array<{
     facto_id: string;
+    /** Same as `error.message` */
     reason: string;
+    error: EventError;
   }>;
 }
 
@@ -0,0 +1,287 @@
+package main
+
+import (
+	"net/http"
+	"sort"
+	"time"
+
+	"github.com/gin-gonic/gin"
+	"github.com/rs/zerolog/log"
+)
+
+// genesisPrevHash is the prev_hash of the first event of a session
+const genesisPrevHash = "0000000000000000000000000000000000000000000000000000000000000000"
+
+// maxAuditEvents bounds the number of events one audit reads
+const maxAuditEvents = 10000
+
+// SessionAuditReport is the verification report of a session's chain
+type SessionAuditReport struct {
+	SessionID  string `json:"session_id"`
+	Valid      bool   `json:"valid"`
+	EventCount int    `json:"event_count"`
+	AuditedAt  int64  `json:"audited_at"`
+
+	// Hash of the last event in chain order
+	ChainHead string `json:"chain_head"`
+
+	// Set when the session has more events than one audit reads
+	Truncated bool `json:"truncated"`
+
+	FirstBreak   *AuditBreak    `json:"first_break"`
+	MissingLinks []MissingLink  `json:"missing_links"`
+	UnsignedGaps []UnsignedGap  `json:"unsigned_gaps"`
+	Forks        []ChainFork    `json:"forks"`
+	Events       []AuditedEvent `json:"events"`
+}
+
+// AuditedEvent is the outcome of the checks on one event, at its position
+// in chain order
+type AuditedEvent struct {
+	Position       int    `json:"position"`
+	FactoID        string `json:"facto_id"`
+	EventHash      string `json:"event_hash"`
+	PrevHash       string `json:"prev_hash"`
+	CompletedAt    int64  `json:"completed_at"`
+	HashValid      bool   `json:"hash_valid"`
+	Signed         bool   `json:"signed"`
+	SignatureValid bool   `json:"signature_valid"`
+
+	// Whether prev_hash is the hash of the event before it, or the genesis
+	// hash for the first event
+	Linked bool `json:"linked"`
+}
+
+// AuditBreak is the first position at which the chain stops verifying
+type AuditBreak struct {
+	Position int      `json:"position"`
+	FactoID  string   `json:"facto_id"`
+	Reasons  []string `json:"reasons"`
+}
+
+// MissingLink is an event whose prev_hash names no event of the session
+type MissingLink struct {
+	Position int    `json:"position"`
+	FactoID  string `json:"facto_id"`
+	PrevHash string `json:"prev_hash"`
+}
+
+// UnsignedGap is a run of consecutive events without a signature
+type UnsignedGap struct {
+	StartPosition int      `json:"start_position"`
+	EndPosition   int      `json:"end_position"`
+	FactoIDs      []string `json:"facto_ids"`
+}
+
+// ChainFork is a hash that more than one event names as its prev_hash
+type ChainFork struct {
+	PrevHash string   `json:"prev_hash"`
+	FactoIDs []string `json:"facto_ids"`
+}
+
+// GetSessionAudit handles GET /v1/sessions/:session_id/audit
+//
+// Events are put in chain order by following prev_hash from the genesis
+// hash, then every hash, signature and link is re-verified.
+func (h *Handlers) GetSessionAudit(c *gin.Context) {
+	start := time.Now()
+	defer func() {
+		apiRequestDuration.WithLabelValues("session_audit").Observe(time.Since(start).Seconds())
+	}()
+
+	sessionID := c.Param("session_id")
+	if sessionID == "" {
+		apiRequestsTotal.WithLabelValues("session_audit", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": "session_id is required"})
+		return
+	}
+
+	ctx := c.Request.Context()
+	summaries, nextCursor, err := h.storage.GetSessionEvents(ctx, sessionID, maxAuditEvents, "")
+	if err != nil {
+		respondFetchError(c, "session_audit", err)
+		return
+	}
+
+	if len(summaries) == 0 {
+		apiRequestsTotal.WithLabelValues("session_audit", "404").Inc()
+		c.JSON(http.StatusNotFound, gin.H{"error": "no events found for session"})
+		return
+	}
+
+	// events_by_session does not hold every signed field, so verify the
+	// full rows
+	factoIDs := make([]string, len(summaries))
+	for i, event := range summaries {
+		factoIDs[i] = event.FactoID
+	}
+	events, err := h.storage.GetEventsByFactoIDs(ctx, factoIDs)
+	if err != nil {
+		apiRequestsTotal.WithLabelValues("session_audit", "500").Inc()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to fetch events"})
+		return
+	}
+	if err := h.blobs.ResolveAll(ctx, events); err != nil {
+		log.Error().Err(err).Msg("Failed to resolve offloaded payload")
+		apiRequestsTotal.WithLabelValues("session_audit", "502").Inc()
+		c.JSON(http.StatusBadGateway, gin.H{"error": "failed to fetch offloaded payload"})
+		return
+	}
+
+	report := auditSession(sessionID, events)
+	report.Truncated = nextCursor != nil
+
+	apiRequestsTotal.WithLabelValues("session_audit", "200").Inc()
+	c.JSON(http.StatusOK, report)
+}
+
+// auditSession verifies a session's events
+func auditSession(sessionID string, events []EventResponse) SessionAuditReport {
+	sort.SliceStable(events, func(i, j int) bool {
+		if events[i].CompletedAt != events[j].CompletedAt {
+			return events[i].CompletedAt < events[j].CompletedAt
+		}
+		return events[i].FactoID < events[j].FactoID
+	})
+	ordered := chainOrder(events)
+
+	report := SessionAuditReport{
+		SessionID:    sessionID,
+		Valid:        true,
+		EventCount:   len(ordered),
+		AuditedAt:    time.Now().UnixNano(),
+		MissingLinks: []MissingLink{},
+		UnsignedGaps: []UnsignedGap{},
+		Forks:        []ChainFork{},
+		Events:       make([]AuditedEvent, 0, len(ordered)),
+	}
+	if len(ordered) > 0 {
+		report.ChainHead = ordered[len(ordered)-1].Proof.EventHash
+	}
+
+	known := make(map[string]bool, len(ordered))
+	successors := make(map[string][]string)
+	for _, event := range ordered {
+		known[event.Proof.EventHash] = true
+		successors[event.Proof.PrevHash] = append(successors[event.Proof.PrevHash], event.FactoID)
+	}
+
+	expectedPrevHash := genesisPrevHash
+	var gap *UnsignedGap
+	for position, event := range ordered {
+		audited := AuditedEvent{
+			Position:    position,
+			FactoID:     event.FactoID,
+			EventHash:   event.Proof.EventHash,
+			PrevHash:    event.Proof.PrevHash,
+			CompletedAt: event.CompletedAt,
+			HashValid:   verifyHash(&event),
+			Signed:      event.Proof.Signature != "" && event.Proof.PublicKey != "",
+			Linked:      event.Proof.PrevHash == expectedPrevHash,
+		}
+		audited.SignatureValid = audited.Signed && verifySignature(&event)
+		expectedPrevHash = event.Proof.EventHash
+
+		if event.Proof.PrevHash != genesisPrevHash && !known[event.Proof.PrevHash] {
+			report.MissingLinks = append(report.MissingLinks, MissingLink{
+				Position: position,
+				FactoID:  event.FactoID,
+				PrevHash: event.Proof.PrevHash,
+			})
+		}
+
+		if !audited.Signed {
+			if gap == nil {
+				report.UnsignedGaps = append(report.UnsignedGaps, UnsignedGap{StartPosition: position})
+				gap = &report.UnsignedGaps[len(report.UnsignedGaps)-1]
+			}
+			gap.EndPosition = position
+			gap.FactoIDs = append(gap.FactoIDs, event.FactoID)
+		} else {
+			gap = nil
+		}
+
+		var reasons []string
+		if !audited.HashValid {
+			reasons = append(reasons, "hash mismatch")
+		}
+		if !audited.Signed {
+			reasons = append(reasons, "unsigned")
+		} else if !audited.SignatureValid {
+			reasons = append(reasons, "invalid signature")
+		}
+		if !audited.Linked {
+			reasons = append(reasons, "prev_hash does not link to the previous event")
+		}
+		if len(reasons) > 0 && report.FirstBreak == nil {
+			report.FirstBreak = &AuditBreak{Position: position, FactoID: event.FactoID, Reasons: reasons}
+			report.Valid = false
+		}
+
+		report.Events = append(report.Events, audited)
+	}
+
+	for _, event := range ordered {
+		if ids := successors[event.Proof.PrevHash]; len(ids) > 1 && ids[0] == event.FactoID {
+			report.Forks = append(report.Forks, ChainFork{PrevHash: event.Proof.PrevHash, FactoIDs: ids})
+		}
+	}
+
+	return report
+}
+
+// chainOrder orders events by following prev_hash links, given oldest
+// first. Each chain segment starts at an event whose predecessor is not in
+// the session, genesis events first; at a fork the oldest branch is walked
+// to its end before the next one. Events no segment reaches, which only a
+// hash cycle produces, keep their time order at the end.
+func chainOrder(events []EventResponse) []EventResponse {
+	known := make(map[string]bool, len(events))
+	next := make(map[string][]int)
+	for i, event := range events {
+		known[event.Proof.EventHash] = true
+		next[event.Proof.PrevHash] = append(next[event.Proof.PrevHash], i)
+	}
+
+	ordered := make([]EventResponse, 0, len(events))
+	placed := make([]bool, len(events))
+
+	walk := func(start int) {
+		stack := []int{start}
+		for len(stack) > 0 {
+			i := stack[len(stack)-1]
+			stack = stack[:len(stack)-1]
+			if placed[i] {
+				continue
+			}
+			placed[i] = true
+			ordered = append(ordered, events[i])
+
+			// Push newest first so the oldest branch is walked next
+			successors := next[events[i].Proof.EventHash]
+			for j := len(successors) - 1; j >= 0; j-- {
+				if !placed[successors[j]] {
+					stack = append(stack, successors[j])
+				}
+			}
+		}
+	}
+
+	for i, event := range events {
+		if event.Proof.PrevHash == genesisPrevHash {
+			walk(i)
+		}
+	}
+	for i, event := range events {
+		if !known[event.Proof.PrevHash] {
+			walk(i)
+		}
+	}
+	for i := range events {
+		if !placed[i] {
+			ordered = append(ordered, events[i])
+		}
+	}
+
+	return ordered
+}
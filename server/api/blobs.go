@@ -0,0 +1,51 @@
+package main
+
+import (
+	"context"
+	"fmt"
+
+	"github.com/facto-ai/facto/server/common/blobs"
+)
+
+// BlobStore resolves the payloads the ingestion service offloaded to blob
+// storage, checking each against its reference before it is used
+type BlobStore struct {
+	*blobs.Store
+}
+
+// NewBlobStore reads the same BLOB_S3_* and AWS_* variables as the
+// ingestion service
+func NewBlobStore() *BlobStore {
+	return &BlobStore{blobs.NewStore()}
+}
+
+// ResolvePayloads puts offloaded payloads back into the event, so its hash
+// and signature can be checked
+func (b *BlobStore) ResolvePayloads(ctx context.Context, event *EventResponse) error {
+	for _, payload := range []*map[string]interface{}{&event.InputData, &event.OutputData} {
+		ref, ok := blobs.RefOf(*payload)
+		if !ok {
+			continue
+		}
+		value, err := b.Resolve(ctx, *payload)
+		if err != nil {
+			return fmt.Errorf("event %s: %w", event.FactoID, err)
+		}
+		resolved, ok := value.(map[string]interface{})
+		if !ok {
+			return fmt.Errorf("event %s: blob %s is not a JSON object", event.FactoID, ref.Hash)
+		}
+		*payload = resolved
+	}
+	return nil
+}
+
+// ResolveAll resolves the offloaded payloads of each event in place
+func (b *BlobStore) ResolveAll(ctx context.Context, events []EventResponse) error {
+	for i := range events {
+		if err := b.ResolvePayloads(ctx, &events[i]); err != nil {
+			return err
+		}
+	}
+	return nil
+}
@@ -0,0 +1,439 @@
+package main
+
+import (
+	"archive/tar"
+	"archive/zip"
+	"bytes"
+	"compress/gzip"
+	"context"
+	"crypto/ed25519"
+	"crypto/rand"
+	"crypto/sha256"
+	"encoding/base64"
+	"encoding/hex"
+	"encoding/json"
+	"fmt"
+	"io"
+	"net/http"
+	"net/url"
+	"os"
+	"sort"
+	"strings"
+	"sync"
+	"time"
+
+	"github.com/gin-gonic/gin"
+	"github.com/rs/zerolog/log"
+)
+
+// bundleFormat names the layout of evidence bundles, so auditors' tools
+// can tell versions apart
+const bundleFormat = "facto-evidence-bundle/1"
+
+// proofFetchConcurrency bounds the inclusion proof requests one bundle
+// makes to the ingestion service
+const proofFetchConcurrency = 8
+
+const bundleReadme = `Facto evidence bundle
+
+manifest.json          what the bundle holds, with the SHA-256 of every file
+manifest.sig           base64 Ed25519 signature of manifest.json by the
+                       key in manifest.signer.public_key
+events.ndjson          the session's events, one per line
+chain-report.json      the chain verification report at export time
+inclusion-proofs.json  Merkle inclusion proofs of the events in the
+                       ingestion service's checkpoints
+checkpoints.json       the signed checkpoints those proofs lead to
+
+To verify this bundle:
+
+1. Verify manifest.sig over the exact bytes of manifest.json, and check
+   that the signer key is one you trust for this deployment.
+2. Check the SHA-256 of every file against manifest.files.
+3. For each event, rebuild the canonical form, compare its SHA3-256 with
+   proof.event_hash and verify proof.signature with proof.public_key
+   (facto verify events.ndjson does both).
+4. Follow prev_hash from the genesis hash (64 zeros) through the events
+   (facto audit-chain --session <id> events.ndjson).
+5. For each inclusion proof, hash the event hash up the audit path and
+   compare with the checkpoint root, then verify the checkpoint signature
+   with its signer_public_key over the sorted-key JSON of the checkpoint
+   without its signature field.
+`
+
+// Bundler builds evidence bundles: signs their manifests and fetches
+// inclusion proofs from the ingestion service
+type Bundler struct {
+	signingKey   ed25519.PrivateKey
+	client       *http.Client
+	ingestionURL string
+	apiKey       string
+}
+
+// NewBundler reads BUNDLE_SIGNING_KEY, a base64 Ed25519 seed, and
+// INGESTION_URL and INGESTION_API_KEY for inclusion proofs. Without a
+// signing key an ephemeral one is generated; without INGESTION_URL bundles
+// carry no inclusion proofs.
+func NewBundler() (*Bundler, error) {
+	var signingKey ed25519.PrivateKey
+	if seed := os.Getenv("BUNDLE_SIGNING_KEY"); seed != "" {
+		decoded, err := base64.StdEncoding.DecodeString(seed)
+		if err != nil || len(decoded) != ed25519.SeedSize {
+			return nil, fmt.Errorf("BUNDLE_SIGNING_KEY must be a base64 %d-byte seed", ed25519.SeedSize)
+		}
+		signingKey = ed25519.NewKeyFromSeed(decoded)
+	} else {
+		log.Warn().Msg("No BUNDLE_SIGNING_KEY configured, signing evidence bundles with an ephemeral key")
+		_, generated, err := ed25519.GenerateKey(rand.Reader)
+		if err != nil {
+			return nil, err
+		}
+		signingKey = generated
+	}
+
+	ingestionURL := strings.TrimRight(os.Getenv("INGESTION_URL"), "/")
+	if ingestionURL == "" {
+		log.Warn().Msg("No INGESTION_URL configured, evidence bundles carry no inclusion proofs")
+	}
+	return &Bundler{
+		signingKey:   signingKey,
+		client:       &http.Client{Timeout: 10 * time.Second},
+		ingestionURL: ingestionURL,
+		apiKey:       os.Getenv("INGESTION_API_KEY"),
+	}, nil
+}
+
+// PublicKey is the base64 key bundle manifests are signed with
+func (b *Bundler) PublicKey() string {
+	return base64.StdEncoding.EncodeToString(b.signingKey.Public().(ed25519.PublicKey))
+}
+
+// MissingProof is an event without an inclusion proof in the bundle
+type MissingProof struct {
+	FactoID string `json:"facto_id"`
+	Reason  string `json:"reason"`
+}
+
+// fetchProof reads the inclusion proof of an event from the ingestion
+// service
+func (b *Bundler) fetchProof(ctx context.Context, factoID string) (json.RawMessage, error) {
+	req, err := http.NewRequestWithContext(ctx, http.MethodGet, b.ingestionURL+"/v1/proof/"+url.PathEscape(factoID), nil)
+	if err != nil {
+		return nil, err
+	}
+	if b.apiKey != "" {
+		req.Header.Set("X-API-Key", b.apiKey)
+	}
+	resp, err := b.client.Do(req)
+	if err != nil {
+		return nil, err
+	}
+	defer resp.Body.Close()
+
+	body, err := io.ReadAll(io.LimitReader(resp.Body, 1<<20))
+	if err != nil {
+		return nil, err
+	}
+	if resp.StatusCode != http.StatusOK {
+		var failure struct {
+			Error string `json:"error"`
+		}
+		if json.Unmarshal(body, &failure) == nil && failure.Error != "" {
+			return nil, fmt.Errorf("%s", failure.Error)
+		}
+		return nil, fmt.Errorf("ingestion service returned %d", resp.StatusCode)
+	}
+	return body, nil
+}
+
+// fetchProofs reads the inclusion proofs of events, returning the proofs,
+// the distinct checkpoints they lead to, and the events without one
+func (b *Bundler) fetchProofs(ctx context.Context, events []EventResponse) ([]json.RawMessage, []json.RawMessage, []MissingProof) {
+	if b.ingestionURL == "" {
+		missing := make([]MissingProof, len(events))
+		for i, event := range events {
+			missing[i] = MissingProof{FactoID: event.FactoID, Reason: "no ingestion service configured"}
+		}
+		return []json.RawMessage{}, []json.RawMessage{}, missing
+	}
+
+	proofs := make([]json.RawMessage, len(events))
+	failures := make([]error, len(events))
+	var wg sync.WaitGroup
+	next := make(chan int)
+	for w := 0; w < proofFetchConcurrency; w++ {
+		wg.Add(1)
+		go func() {
+			defer wg.Done()
+			for i := range next {
+				proofs[i], failures[i] = b.fetchProof(ctx, events[i].FactoID)
+			}
+		}()
+	}
+	for i := range events {
+		next <- i
+	}
+	close(next)
+	wg.Wait()
+
+	included := []json.RawMessage{}
+	missing := []MissingProof{}
+	checkpoints := map[uint64]json.RawMessage{}
+	for i, proof := range proofs {
+		// Only the checkpoint is read; proofs are kept as served
+		var parsed struct {
+			Checkpoint json.RawMessage `json:"checkpoint"`
+		}
+		var checkpoint struct {
+			Sequence uint64 `json:"sequence"`
+		}
+		if failures[i] == nil {
+			failures[i] = json.Unmarshal(proof, &parsed)
+		}
+		if failures[i] == nil {
+			failures[i] = json.Unmarshal(parsed.Checkpoint, &checkpoint)
+		}
+		if failures[i] != nil {
+			missing = append(missing, MissingProof{FactoID: events[i].FactoID, Reason: failures[i].Error()})
+			continue
+		}
+		included = append(included, proof)
+		checkpoints[checkpoint.Sequence] = parsed.Checkpoint
+	}
+
+	sequences := make([]uint64, 0, len(checkpoints))
+	for sequence := range checkpoints {
+		sequences = append(sequences, sequence)
+	}
+	sort.Slice(sequences, func(i, j int) bool { return sequences[i] < sequences[j] })
+	ordered := make([]json.RawMessage, len(sequences))
+	for i, sequence := range sequences {
+		ordered[i] = checkpoints[sequence]
+	}
+	return included, ordered, missing
+}
+
+// BundleFile is a file of a bundle as listed in its manifest
+type BundleFile struct {
+	Name   string `json:"name"`
+	Size   int    `json:"size"`
+	SHA256 string `json:"sha256"`
+}
+
+// BundleSigner identifies the key a manifest is signed with
+type BundleSigner struct {
+	Algorithm string `json:"algorithm"`
+	PublicKey string `json:"public_key"`
+}
+
+// BundleManifest describes an evidence bundle. manifest.sig signs its
+// exact bytes.
+type BundleManifest struct {
+	Format        string         `json:"format"`
+	BundleID      string         `json:"bundle_id"`
+	SessionID     string         `json:"session_id"`
+	CreatedAt     string         `json:"created_at"`
+	EventCount    int            `json:"event_count"`
+	ChainValid    bool           `json:"chain_valid"`
+	ChainHead     string         `json:"chain_head"`
+	Truncated     bool           `json:"truncated"`
+	ProofCount    int            `json:"proof_count"`
+	MissingProofs []MissingProof `json:"missing_proofs"`
+	Files         []BundleFile   `json:"files"`
+	Signer        BundleSigner   `json:"signer"`
+}
+
+// bundleEntry is a named file of a bundle
+type bundleEntry struct {
+	name    string
+	content []byte
+}
+
+// buildBundle assembles the files of a session's bundle, manifest and
+// signature last
+func (b *Bundler) buildBundle(sessionID string, events []EventResponse, report SessionAuditReport, proofs, checkpoints []json.RawMessage, missing []MissingProof) ([]bundleEntry, BundleManifest, error) {
+	var ndjson bytes.Buffer
+	for _, event := range events {
+		line, err := json.Marshal(event)
+		if err != nil {
+			return nil, BundleManifest{}, err
+		}
+		ndjson.Write(line)
+		ndjson.WriteByte('\n')
+	}
+	reportJSON, err := json.MarshalIndent(report, "", "  ")
+	if err != nil {
+		return nil, BundleManifest{}, err
+	}
+	proofsJSON, err := json.MarshalIndent(proofs, "", "  ")
+	if err != nil {
+		return nil, BundleManifest{}, err
+	}
+	checkpointsJSON, err := json.MarshalIndent(checkpoints, "", "  ")
+	if err != nil {
+		return nil, BundleManifest{}, err
+	}
+
+	entries := []bundleEntry{
+		{name: "README.txt", content: []byte(bundleReadme)},
+		{name: "events.ndjson", content: ndjson.Bytes()},
+		{name: "chain-report.json", content: reportJSON},
+		{name: "inclusion-proofs.json", content: proofsJSON},
+		{name: "checkpoints.json", content: checkpointsJSON},
+	}
+
+	createdAt := time.Now().UTC()
+	idHash := sha256.Sum256([]byte(sessionID + createdAt.String()))
+	manifest := BundleManifest{
+		Format:        bundleFormat,
+		BundleID:      "eb-" + hex.EncodeToString(idHash[:8]),
+		SessionID:     sessionID,
+		CreatedAt:     createdAt.Format(time.RFC3339),
+		EventCount:    len(events),
+		ChainValid:    report.Valid,
+		ChainHead:     report.ChainHead,
+		Truncated:     report.Truncated,
+		ProofCount:    len(proofs),
+		MissingProofs: missing,
+		Files:         make([]BundleFile, len(entries)),
+		Signer:        BundleSigner{Algorithm: "ed25519", PublicKey: b.PublicKey()},
+	}
+	for i, entry := range entries {
+		digest := sha256.Sum256(entry.content)
+		manifest.Files[i] = BundleFile{Name: entry.name, Size: len(entry.content), SHA256: hex.EncodeToString(digest[:])}
+	}
+
+	manifestJSON, err := json.MarshalIndent(manifest, "", "  ")
+	if err != nil {
+		return nil, BundleManifest{}, err
+	}
+	signature := ed25519.Sign(b.signingKey, manifestJSON)
+	entries = append(entries,
+		bundleEntry{name: "manifest.json", content: manifestJSON},
+		bundleEntry{name: "manifest.sig", content: []byte(base64.StdEncoding.EncodeToString(signature) + "\n")},
+	)
+	return entries, manifest, nil
+}
+
+// writeTarGz writes bundle files as a gzipped tarball under dir
+func writeTarGz(w io.Writer, dir string, entries []bundleEntry, modTime time.Time) error {
+	gz := gzip.NewWriter(w)
+	tw := tar.NewWriter(gz)
+	for _, entry := range entries {
+		header := &tar.Header{
+			Name:    dir + "/" + entry.name,
+			Mode:    0o644,
+			Size:    int64(len(entry.content)),
+			ModTime: modTime,
+		}
+		if err := tw.WriteHeader(header); err != nil {
+			return err
+		}
+		if _, err := tw.Write(entry.content); err != nil {
+			return err
+		}
+	}
+	if err := tw.Close(); err != nil {
+		return err
+	}
+	return gz.Close()
+}
+
+// writeZip writes bundle files as a zip archive under dir
+func writeZip(w io.Writer, dir string, entries []bundleEntry, modTime time.Time) error {
+	zw := zip.NewWriter(w)
+	for _, entry := range entries {
+		f, err := zw.CreateHeader(&zip.FileHeader{
+			Name:     dir + "/" + entry.name,
+			Method:   zip.Deflate,
+			Modified: modTime,
+		})
+		if err != nil {
+			return err
+		}
+		if _, err := f.Write(entry.content); err != nil {
+			return err
+		}
+	}
+	return zw.Close()
+}
+
+// GetSessionBundle handles GET /v1/sessions/:session_id/bundle
+//
+// The bundle is a tar.gz (or, with format=zip, a zip) of the session's
+// events, the chain verification report, inclusion proofs and the signed
+// checkpoints they lead to, and a signed manifest of all of them.
+func (h *Handlers) GetSessionBundle(c *gin.Context) {
+	start := time.Now()
+	defer func() {
+		apiRequestDuration.WithLabelValues("session_bundle").Observe(time.Since(start).Seconds())
+	}()
+
+	sessionID := c.Param("session_id")
+	format := c.DefaultQuery("format", "tar")
+	if sessionID == "" || (format != "tar" && format != "zip") {
+		apiRequestsTotal.WithLabelValues("session_bundle", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": "session_id is required and format must be tar or zip"})
+		return
+	}
+
+	ctx := c.Request.Context()
+	summaries, nextCursor, err := h.storage.GetSessionEvents(ctx, sessionID, maxAuditEvents, "")
+	if err != nil {
+		respondFetchError(c, "session_bundle", err)
+		return
+	}
+	if len(summaries) == 0 {
+		apiRequestsTotal.WithLabelValues("session_bundle", "404").Inc()
+		c.JSON(http.StatusNotFound, gin.H{"error": "no events found for session"})
+		return
+	}
+
+	factoIDs := make([]string, len(summaries))
+	for i, event := range summaries {
+		factoIDs[i] = event.FactoID
+	}
+	events, err := h.storage.GetEventsByFactoIDs(ctx, factoIDs)
+	if err != nil {
+		apiRequestsTotal.WithLabelValues("session_bundle", "500").Inc()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to fetch events"})
+		return
+	}
+	// The bundle carries payloads so it can be verified on its own
+	if err := h.blobs.ResolveAll(ctx, events); err != nil {
+		log.Error().Err(err).Msg("Failed to resolve offloaded payload")
+		apiRequestsTotal.WithLabelValues("session_bundle", "502").Inc()
+		c.JSON(http.StatusBadGateway, gin.H{"error": "failed to fetch offloaded payload"})
+		return
+	}
+
+	report := auditSession(sessionID, events)
+	report.Truncated = nextCursor != nil
+	ordered := chainOrder(events)
+	proofs, checkpoints, missing := h.bundler.fetchProofs(ctx, ordered)
+
+	entries, manifest, err := h.bundler.buildBundle(sessionID, ordered, report, proofs, checkpoints, missing)
+	if err != nil {
+		apiRequestsTotal.WithLabelValues("session_bundle", "500").Inc()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to build bundle"})
+		return
+	}
+
+	var archive bytes.Buffer
+	contentType, extension := "application/gzip", "tar.gz"
+	write := writeTarGz
+	if format == "zip" {
+		contentType, extension = "application/zip", "zip"
+		write = writeZip
+	}
+	if err := write(&archive, manifest.BundleID, entries, start); err != nil {
+		apiRequestsTotal.WithLabelValues("session_bundle", "500").Inc()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to write bundle"})
+		return
+	}
+
+	apiRequestsTotal.WithLabelValues("session_bundle", "200").Inc()
+	c.Header("Content-Disposition", fmt.Sprintf(`attachment; filename="%s.%s"`, manifest.BundleID, extension))
+	c.Header("X-Facto-Bundle-Signer", manifest.Signer.PublicKey)
+	c.Data(http.StatusOK, contentType, archive.Bytes())
+}
@@ -0,0 +1,443 @@
+package main
+
+import (
+	"context"
+	"encoding/base64"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"net/http"
+	"sort"
+	"strings"
+	"sync"
+	"time"
+
+	"github.com/facto-ai/facto/server/common/archive"
+	"github.com/facto-ai/facto/server/common/signature"
+	"github.com/gin-gonic/gin"
+	"github.com/prometheus/client_golang/prometheus"
+	"github.com/prometheus/client_golang/prometheus/promauto"
+	"github.com/rs/zerolog/log"
+)
+
+// Hydration statuses
+const (
+	hydrationQueued   = "queued"
+	hydrationRunning  = "running"
+	hydrationComplete = "complete"
+	hydrationFailed   = "failed"
+)
+
+var (
+	hydrationsTotal = promauto.NewCounterVec(prometheus.CounterOpts{
+		Name: "facto_api_hydrations_total",
+		Help: "Total number of sessions read back from the archive, by outcome",
+	}, []string{"status"})
+
+	archiveFilesScanned = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_api_archive_files_scanned_total",
+		Help: "Total number of archive files read while hydrating sessions",
+	})
+)
+
+// Hydration reports how far reading a session back from the archive got
+type Hydration struct {
+	SessionID    string     `json:"session_id"`
+	Status       string     `json:"status"`
+	FilesScanned int        `json:"files_scanned"`
+	FilesTotal   int        `json:"files_total"`
+	EventsFound  int        `json:"events_found"`
+	Error        string     `json:"error,omitempty"`
+	StartedAt    time.Time  `json:"started_at"`
+	CompletedAt  *time.Time `json:"completed_at,omitempty"`
+}
+
+// HydratingError is returned for a session still being read back from the
+// archive
+type HydratingError struct {
+	Hydration Hydration
+}
+
+func (e *HydratingError) Error() string {
+	return fmt.Sprintf("session %s is being restored from the archive (%d of %d files)",
+		e.Hydration.SessionID, e.Hydration.FilesScanned, e.Hydration.FilesTotal)
+}
+
+// hydratedSession is a session read back from the archive, kept until it
+// expires
+type hydratedSession struct {
+	progress Hydration
+	events   []EventResponse
+	// byID indexes events by facto_id
+	byID    map[string]int
+	expires time.Time
+}
+
+// ColdTier reads sessions back from the archiver's Parquet files. Files are
+// partitioned by tenant, agent and hour rather than session, so a session
+// is found by scanning every archived file once, in the background; the
+// events found are then kept for a while to answer follow-up requests.
+type ColdTier struct {
+	store       *archive.Store
+	ttl         time.Duration
+	maxSessions int
+	// scans bounds the number of sessions hydrated at once
+	scans chan struct{}
+
+	mu       sync.Mutex
+	sessions map[string]*hydratedSession
+}
+
+// NewColdTier creates a cold tier over an archive, keeping up to
+// maxSessions hydrated sessions for ttl each
+func NewColdTier(store *archive.Store, ttl time.Duration, maxSessions, concurrency int) *ColdTier {
+	return &ColdTier{
+		store:       store,
+		ttl:         ttl,
+		maxSessions: maxSessions,
+		scans:       make(chan struct{}, concurrency),
+		sessions:    make(map[string]*hydratedSession),
+	}
+}
+
+// Hydrate returns the progress of reading a session back from the archive,
+// starting it if it is not under way, and the session's events once it is
+// complete. A failed hydration is reported once, then started again by the
+// next call.
+func (t *ColdTier) Hydrate(sessionID string) (Hydration, []EventResponse) {
+	t.mu.Lock()
+	defer t.mu.Unlock()
+
+	session, ok := t.sessions[sessionID]
+	if ok && session.progress.Status == hydrationComplete && time.Now().After(session.expires) {
+		delete(t.sessions, sessionID)
+		ok = false
+	}
+	if !ok {
+		t.evict()
+		session = &hydratedSession{progress: Hydration{
+			SessionID: sessionID,
+			Status:    hydrationQueued,
+			StartedAt: time.Now().UTC(),
+		}}
+		t.sessions[sessionID] = session
+		go t.hydrate(sessionID, session)
+	}
+
+	switch session.progress.Status {
+	case hydrationComplete:
+		return session.progress, session.events
+	case hydrationFailed:
+		delete(t.sessions, sessionID)
+	}
+	return session.progress, nil
+}
+
+// Progress returns the progress of a session's hydration, if one is known
+func (t *ColdTier) Progress(sessionID string) (Hydration, bool) {
+	t.mu.Lock()
+	defer t.mu.Unlock()
+
+	session, ok := t.sessions[sessionID]
+	if !ok {
+		return Hydration{}, false
+	}
+	return session.progress, true
+}
+
+// Event returns an event of a hydrated session
+func (t *ColdTier) Event(factoID string) (*EventResponse, bool) {
+	t.mu.Lock()
+	defer t.mu.Unlock()
+
+	for _, session := range t.sessions {
+		if session.progress.Status != hydrationComplete {
+			continue
+		}
+		if i, ok := session.byID[factoID]; ok {
+			event := session.events[i]
+			return &event, true
+		}
+	}
+	return nil, false
+}
+
+// evict makes room for one more session, dropping expired sessions, then
+// the oldest complete one. Sessions being hydrated are never dropped.
+func (t *ColdTier) evict() {
+	now := time.Now()
+	for sessionID, session := range t.sessions {
+		if session.progress.Status == hydrationComplete && now.After(session.expires) {
+			delete(t.sessions, sessionID)
+		}
+	}
+	for len(t.sessions) >= t.maxSessions {
+		oldest := ""
+		for sessionID, session := range t.sessions {
+			if session.progress.Status != hydrationComplete {
+				continue
+			}
+			if oldest == "" || session.expires.Before(t.sessions[oldest].expires) {
+				oldest = sessionID
+			}
+		}
+		if oldest == "" {
+			return
+		}
+		delete(t.sessions, oldest)
+	}
+}
+
+// hydrate scans the archive for a session's events
+func (t *ColdTier) hydrate(sessionID string, session *hydratedSession) {
+	t.scans <- struct{}{}
+	defer func() { <-t.scans }()
+
+	t.update(session, func(progress *Hydration) { progress.Status = hydrationRunning })
+	events, err := t.scan(context.Background(), sessionID, session)
+
+	completed := time.Now().UTC()
+	t.mu.Lock()
+	session.progress.CompletedAt = &completed
+	if err != nil {
+		session.progress.Status = hydrationFailed
+		session.progress.Error = err.Error()
+	} else {
+		session.progress.Status = hydrationComplete
+		session.progress.EventsFound = len(events)
+		session.events = events
+		session.byID = make(map[string]int, len(events))
+		for i, event := range events {
+			session.byID[event.FactoID] = i
+		}
+		session.expires = time.Now().Add(t.ttl)
+	}
+	status := session.progress.Status
+	t.mu.Unlock()
+
+	hydrationsTotal.WithLabelValues(status).Inc()
+	if err != nil {
+		log.Error().Err(err).Str("session_id", sessionID).Msg("Failed to restore session from the archive")
+		return
+	}
+	log.Info().Str("session_id", sessionID).Int("events", len(events)).Msg("Restored session from the archive")
+}
+
+// scan reads every archived file and returns the session's events, in the
+// order events_by_session keeps them
+func (t *ColdTier) scan(ctx context.Context, sessionID string, session *hydratedSession) ([]EventResponse, error) {
+	manifests, err := t.store.Manifests(ctx)
+	if err != nil {
+		return nil, err
+	}
+	// A batch archived again after a failure lists the same files again
+	var files []archive.ManifestFile
+	seen := make(map[string]bool)
+	for _, manifest := range manifests {
+		for _, file := range manifest.Files {
+			if !seen[file.URI] {
+				seen[file.URI] = true
+				files = append(files, file)
+			}
+		}
+	}
+	t.update(session, func(progress *Hydration) { progress.FilesTotal = len(files) })
+
+	events := make(map[string]EventResponse)
+	for i := range files {
+		rows, err := t.store.Events(ctx, &files[i])
+		if err != nil {
+			return nil, err
+		}
+		archiveFilesScanned.Inc()
+		for _, row := range rows {
+			if row.SessionID != sessionID {
+				continue
+			}
+			event, err := archivedEventResponse(row.Event, row.Envelope)
+			if err != nil {
+				return nil, fmt.Errorf("archived event %s: %w", row.FactoID, err)
+			}
+			events[event.FactoID] = *event
+		}
+		t.update(session, func(progress *Hydration) {
+			progress.FilesScanned = i + 1
+			progress.EventsFound = len(events)
+		})
+	}
+
+	ordered := make([]EventResponse, 0, len(events))
+	for _, event := range events {
+		ordered = append(ordered, event)
+	}
+	sort.Slice(ordered, func(i, j int) bool {
+		if ordered[i].CompletedAt != ordered[j].CompletedAt {
+			return ordered[i].CompletedAt < ordered[j].CompletedAt
+		}
+		return ordered[i].FactoID < ordered[j].FactoID
+	})
+	return ordered, nil
+}
+
+func (t *ColdTier) update(session *hydratedSession, change func(*Hydration)) {
+	t.mu.Lock()
+	defer t.mu.Unlock()
+	change(&session.progress)
+}
+
+// archivedEventResponse decodes an event as it was published. Numbers in
+// payloads are kept as json.Number so the event still verifies. The trust
+// basis comes from the archived server envelope, when there is one.
+func archivedEventResponse(published, envelope string) (*EventResponse, error) {
+	var event EventResponse
+	decoder := json.NewDecoder(strings.NewReader(published))
+	decoder.UseNumber()
+	if err := decoder.Decode(&event); err != nil {
+		return nil, err
+	}
+	if envelope != "" {
+		var server struct {
+			Verification struct {
+				TrustBasis *signature.TrustBasis `json:"trust_basis"`
+			} `json:"verification"`
+		}
+		if err := json.Unmarshal([]byte(envelope), &server); err != nil {
+			return nil, fmt.Errorf("envelope: %w", err)
+		}
+		if server.Verification.TrustBasis.Enveloped() {
+			event.TrustBasis = server.Verification.TrustBasis
+		}
+	}
+	return &event, nil
+}
+
+// FederatedStorage reads events from the hot tier, ScyllaDB, and falls back
+// to the archive for sessions and events the hot tier no longer holds
+type FederatedStorage struct {
+	*Storage
+	// cold is nil without an ARCHIVE_URI
+	cold *ColdTier
+}
+
+// GetSessionEvents reads a session's events from the hot tier, or once it
+// holds none, from the archive. While the session is being read back it
+// returns a *HydratingError with the progress.
+func (f *FederatedStorage) GetSessionEvents(ctx context.Context, sessionID string, limit int, cursor string) ([]EventResponse, *string, error) {
+	events, nextCursor, err := f.Storage.GetSessionEvents(ctx, sessionID, limit, cursor)
+	if err != nil || len(events) > 0 || f.cold == nil {
+		return events, nextCursor, err
+	}
+
+	progress, archived := f.cold.Hydrate(sessionID)
+	switch progress.Status {
+	case hydrationComplete:
+		return pageSessionEvents(archived, limit, cursor)
+	case hydrationFailed:
+		return nil, nil, fmt.Errorf("failed to restore session %s from the archive: %s", sessionID, progress.Error)
+	default:
+		return nil, nil, &HydratingError{Hydration: progress}
+	}
+}
+
+// GetEventByFactoID reads an event from the hot tier, or from the sessions
+// read back from the archive
+func (f *FederatedStorage) GetEventByFactoID(ctx context.Context, factoID string) (*EventResponse, error) {
+	event, err := f.Storage.GetEventByFactoID(ctx, factoID)
+	if err != nil || event != nil || f.cold == nil {
+		return event, err
+	}
+	if archived, ok := f.cold.Event(factoID); ok {
+		return archived, nil
+	}
+	return nil, nil
+}
+
+// GetEventsByFactoIDs reads events from the hot tier, and those it does not
+// hold from the sessions read back from the archive
+func (f *FederatedStorage) GetEventsByFactoIDs(ctx context.Context, factoIDs []string) ([]EventResponse, error) {
+	events, err := f.Storage.GetEventsByFactoIDs(ctx, factoIDs)
+	if err != nil || len(events) == len(factoIDs) || f.cold == nil {
+		return events, err
+	}
+
+	found := make(map[string]bool, len(events))
+	for _, event := range events {
+		found[event.FactoID] = true
+	}
+	for _, factoID := range factoIDs {
+		if found[factoID] {
+			continue
+		}
+		if archived, ok := f.cold.Event(factoID); ok {
+			events = append(events, *archived)
+			found[factoID] = true
+		}
+	}
+	return events, nil
+}
+
+// pageSessionEvents returns the page of a hydrated session after the event
+// the cursor points at, with cursors like the hot tier's
+func pageSessionEvents(events []EventResponse, limit int, cursor string) ([]EventResponse, *string, error) {
+	start := 0
+	if cursor != "" {
+		after, err := base64.StdEncoding.DecodeString(cursor)
+		if err != nil {
+			return nil, nil, fmt.Errorf("invalid cursor: %w", err)
+		}
+		for i, event := range events {
+			if event.FactoID == string(after) {
+				start = i + 1
+				break
+			}
+		}
+	}
+
+	page := events[start:]
+	var nextCursor *string
+	if len(page) > limit {
+		page = page[:limit]
+		next := base64.StdEncoding.EncodeToString([]byte(page[len(page)-1].FactoID))
+		nextCursor = &next
+	}
+	return append([]EventResponse(nil), page...), nextCursor, nil
+}
+
+// respondFetchError answers a request whose session events could not be
+// read: 202 Accepted with the progress while the session is read back from
+// the archive, 500 otherwise
+func respondFetchError(c *gin.Context, endpoint string, err error) {
+	var hydrating *HydratingError
+	if errors.As(err, &hydrating) {
+		apiRequestsTotal.WithLabelValues(endpoint, "202").Inc()
+		c.Header("Location", "/v1/sessions/"+hydrating.Hydration.SessionID+"/hydration")
+		c.Header("Retry-After", "5")
+		c.JSON(http.StatusAccepted, hydrating.Hydration)
+		return
+	}
+	apiRequestsTotal.WithLabelValues(endpoint, "500").Inc()
+	c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to fetch events"})
+}
+
+// GetSessionHydration handles GET /v1/sessions/:session_id/hydration
+func (h *Handlers) GetSessionHydration(c *gin.Context) {
+	start := time.Now()
+	defer func() {
+		apiRequestDuration.WithLabelValues("session_hydration").Observe(time.Since(start).Seconds())
+	}()
+
+	if h.storage.cold == nil {
+		apiRequestsTotal.WithLabelValues("session_hydration", "404").Inc()
+		c.JSON(http.StatusNotFound, gin.H{"error": "no archive is configured"})
+		return
+	}
+	progress, ok := h.storage.cold.Progress(c.Param("session_id"))
+	if !ok {
+		apiRequestsTotal.WithLabelValues("session_hydration", "404").Inc()
+		c.JSON(http.StatusNotFound, gin.H{"error": "session is not being restored from the archive"})
+		return
+	}
+
+	apiRequestsTotal.WithLabelValues("session_hydration", "200").Inc()
+	c.JSON(http.StatusOK, progress)
+}
@@ -0,0 +1,406 @@
+package main
+
+import (
+	"context"
+	"encoding/xml"
+	"fmt"
+	"net/http"
+	"sort"
+	"strings"
+	"time"
+
+	"github.com/gin-gonic/gin"
+)
+
+// maxGraphEvents bounds the number of events one graph is built from
+const maxGraphEvents = 5000
+
+// delegationTag names the agent an event handed work to
+const delegationTag = "delegate_to"
+
+// Node kinds of an interaction graph
+const (
+	graphNodeAgent   = "agent"
+	graphNodeSession = "session"
+	graphNodeTool    = "tool"
+)
+
+// Edge kinds of an interaction graph
+const (
+	// An agent recorded events in a session
+	graphEdgeParticipates = "participates"
+
+	// An agent called a tool
+	graphEdgeCalls = "calls"
+
+	// An agent's event is the parent of another agent's event, or names it
+	// in the delegate_to tag
+	graphEdgeDelegates = "delegates"
+
+	// An event of one session is the parent of an event of another
+	graphEdgeSpawns = "spawns"
+)
+
+// GraphQuery represents query parameters for a graph export. Either
+// session_id, or start and end with one or more agent_id, select the events.
+type GraphQuery struct {
+	SessionID string   `form:"session_id"`
+	AgentIDs  []string `form:"agent_id"`
+	Start     string   `form:"start"`
+	End       string   `form:"end"`
+	Format    string   `form:"format"`
+}
+
+// GraphNode is an agent, session or tool
+type GraphNode struct {
+	ID         string `json:"id"`
+	Kind       string `json:"kind"`
+	Label      string `json:"label"`
+	EventCount int    `json:"event_count"`
+}
+
+// GraphEdge is an interaction between two nodes, weighted by the number of
+// events it was seen in
+type GraphEdge struct {
+	Source string `json:"source"`
+	Target string `json:"target"`
+	Kind   string `json:"kind"`
+	Weight int    `json:"weight"`
+}
+
+// GraphResponse is the interaction graph of a set of events
+type GraphResponse struct {
+	EventCount int         `json:"event_count"`
+	Nodes      []GraphNode `json:"nodes"`
+	Edges      []GraphEdge `json:"edges"`
+
+	// Set when the event limit cut the graph short
+	Truncated bool `json:"truncated"`
+}
+
+// GetGraph handles GET /v1/graph
+//
+// Exports the agent, session and tool interaction graph of a session, or of
+// agents within a time window, as JSON (the default), DOT or GraphML.
+// Interactions come from tool calls, parent links that cross agents or
+// sessions, and delegate_to tags.
+func (h *Handlers) GetGraph(c *gin.Context) {
+	start := time.Now()
+	defer func() {
+		apiRequestDuration.WithLabelValues("get_graph").Observe(time.Since(start).Seconds())
+	}()
+
+	var query GraphQuery
+	if err := c.ShouldBindQuery(&query); err != nil {
+		apiRequestsTotal.WithLabelValues("get_graph", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	if query.Format == "" {
+		query.Format = "json"
+	}
+	if query.Format != "json" && query.Format != "dot" && query.Format != "graphml" {
+		apiRequestsTotal.WithLabelValues("get_graph", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": "format must be json, dot or graphml"})
+		return
+	}
+
+	ctx := c.Request.Context()
+	var (
+		events    []EventResponse
+		truncated bool
+		err       error
+	)
+	switch {
+	case query.SessionID != "":
+		events, truncated, err = h.sessionGraphEvents(ctx, query.SessionID)
+	case len(query.AgentIDs) > 0 && query.Start != "" && query.End != "":
+		startTime, parseErr := time.Parse(time.RFC3339, query.Start)
+		if parseErr != nil {
+			apiRequestsTotal.WithLabelValues("get_graph", "400").Inc()
+			c.JSON(http.StatusBadRequest, gin.H{"error": "invalid start time format"})
+			return
+		}
+		endTime, parseErr := time.Parse(time.RFC3339, query.End)
+		if parseErr != nil {
+			apiRequestsTotal.WithLabelValues("get_graph", "400").Inc()
+			c.JSON(http.StatusBadRequest, gin.H{"error": "invalid end time format"})
+			return
+		}
+		if endTime.Sub(startTime) > maxAgentEventsRange {
+			apiRequestsTotal.WithLabelValues("get_graph", "400").Inc()
+			c.JSON(http.StatusBadRequest, gin.H{"error": "time range must not exceed 31 days"})
+			return
+		}
+		events, truncated, err = h.windowGraphEvents(ctx, query.AgentIDs, startTime, endTime)
+	default:
+		apiRequestsTotal.WithLabelValues("get_graph", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": "session_id, or agent_id with start and end, is required"})
+		return
+	}
+	if err != nil {
+		respondFetchError(c, "get_graph", err)
+		return
+	}
+
+	// Parents outside the selection still show which agent or session
+	// handed work over
+	parents, err := h.missingParents(ctx, events)
+	if err != nil {
+		apiRequestsTotal.WithLabelValues("get_graph", "500").Inc()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to fetch parent events"})
+		return
+	}
+
+	graph := buildGraph(events, parents)
+	graph.Truncated = truncated
+
+	apiRequestsTotal.WithLabelValues("get_graph", "200").Inc()
+	switch query.Format {
+	case "dot":
+		c.Data(http.StatusOK, "text/vnd.graphviz; charset=utf-8", []byte(graph.dot()))
+	case "graphml":
+		c.Data(http.StatusOK, "application/graphml+xml; charset=utf-8", []byte(graph.graphML()))
+	default:
+		c.JSON(http.StatusOK, graph)
+	}
+}
+
+// sessionGraphEvents reads the full rows of a session's events, since
+// events_by_session holds no tool calls
+func (h *Handlers) sessionGraphEvents(ctx context.Context, sessionID string) ([]EventResponse, bool, error) {
+	summaries, nextCursor, err := h.storage.GetSessionEvents(ctx, sessionID, maxGraphEvents, "")
+	if err != nil {
+		return nil, false, err
+	}
+
+	factoIDs := make([]string, len(summaries))
+	for i, event := range summaries {
+		factoIDs[i] = event.FactoID
+	}
+	events, err := h.storage.GetEventsByFactoIDs(ctx, factoIDs)
+	return events, nextCursor != nil, err
+}
+
+// windowGraphEvents reads the events of each agent within a time window
+func (h *Handlers) windowGraphEvents(ctx context.Context, agentIDs []string, start, end time.Time) ([]EventResponse, bool, error) {
+	var events []EventResponse
+	for _, agentID := range agentIDs {
+		remaining := maxGraphEvents - len(events)
+		if remaining <= 0 {
+			return events, true, nil
+		}
+
+		// One extra event tells whether the agent had more
+		agentEvents, _, err := h.storage.GetEvents(ctx, agentID, start, end, remaining+1, "")
+		if err != nil {
+			return nil, false, err
+		}
+		if len(agentEvents) > remaining {
+			return append(events, agentEvents[:remaining]...), true, nil
+		}
+		events = append(events, agentEvents...)
+	}
+	return events, false, nil
+}
+
+// missingParents fetches the parents of events that are not in the selection
+func (h *Handlers) missingParents(ctx context.Context, events []EventResponse) ([]EventResponse, error) {
+	selected := make(map[string]bool, len(events))
+	for _, event := range events {
+		selected[event.FactoID] = true
+	}
+
+	var missing []string
+	for _, event := range events {
+		if event.ParentFactoID != nil && !selected[*event.ParentFactoID] {
+			selected[*event.ParentFactoID] = true
+			missing = append(missing, *event.ParentFactoID)
+		}
+	}
+	if len(missing) == 0 {
+		return nil, nil
+	}
+	return h.storage.GetEventsByFactoIDs(ctx, missing)
+}
+
+// toolName returns the name of a tool call, either {"name": ...} or
+// {"function": {"name": ...}}
+func toolName(call interface{}) string {
+	fields, ok := call.(map[string]interface{})
+	if !ok {
+		return ""
+	}
+	if name, ok := fields["name"].(string); ok {
+		return name
+	}
+	if function, ok := fields["function"].(map[string]interface{}); ok {
+		if name, ok := function["name"].(string); ok {
+			return name
+		}
+	}
+	if name, ok := fields["function"].(string); ok {
+		return name
+	}
+	return ""
+}
+
+// graphBuilder accumulates nodes and weighted edges
+type graphBuilder struct {
+	nodes map[string]*GraphNode
+	edges map[GraphEdge]int
+}
+
+func (b *graphBuilder) node(kind, label string) string {
+	id := kind + ":" + label
+	if _, ok := b.nodes[id]; !ok {
+		b.nodes[id] = &GraphNode{ID: id, Kind: kind, Label: label}
+	}
+	return id
+}
+
+func (b *graphBuilder) edge(source, target, kind string) {
+	if source == target {
+		return
+	}
+	b.edges[GraphEdge{Source: source, Target: target, Kind: kind}]++
+}
+
+// buildGraph derives the interaction graph of events. parents are events
+// outside the selection that selected events descend from; they contribute
+// edges but are not counted.
+func buildGraph(events, parents []EventResponse) GraphResponse {
+	b := &graphBuilder{
+		nodes: make(map[string]*GraphNode),
+		edges: make(map[GraphEdge]int),
+	}
+
+	byID := make(map[string]*EventResponse, len(events)+len(parents))
+	for i := range parents {
+		byID[parents[i].FactoID] = &parents[i]
+	}
+	for i := range events {
+		byID[events[i].FactoID] = &events[i]
+	}
+
+	for _, event := range events {
+		agent := b.node(graphNodeAgent, event.AgentID)
+		session := b.node(graphNodeSession, event.SessionID)
+		b.nodes[agent].EventCount++
+		b.nodes[session].EventCount++
+		b.edge(agent, session, graphEdgeParticipates)
+
+		for _, call := range event.ExecutionMeta.ToolCalls {
+			if name := toolName(call); name != "" {
+				tool := b.node(graphNodeTool, name)
+				b.nodes[tool].EventCount++
+				b.edge(agent, tool, graphEdgeCalls)
+			}
+		}
+
+		if target := event.ExecutionMeta.Tags[delegationTag]; target != "" {
+			b.edge(agent, b.node(graphNodeAgent, target), graphEdgeDelegates)
+		}
+
+		if event.ParentFactoID == nil {
+			continue
+		}
+		parent, ok := byID[*event.ParentFactoID]
+		if !ok {
+			continue
+		}
+		if parent.AgentID != event.AgentID {
+			b.edge(b.node(graphNodeAgent, parent.AgentID), agent, graphEdgeDelegates)
+		}
+		if parent.SessionID != event.SessionID {
+			b.edge(b.node(graphNodeSession, parent.SessionID), session, graphEdgeSpawns)
+		}
+	}
+
+	graph := GraphResponse{
+		EventCount: len(events),
+		Nodes:      make([]GraphNode, 0, len(b.nodes)),
+		Edges:      make([]GraphEdge, 0, len(b.edges)),
+	}
+	for _, node := range b.nodes {
+		graph.Nodes = append(graph.Nodes, *node)
+	}
+	for edge, weight := range b.edges {
+		edge.Weight = weight
+		graph.Edges = append(graph.Edges, edge)
+	}
+
+	sort.Slice(graph.Nodes, func(i, j int) bool {
+		return graph.Nodes[i].ID < graph.Nodes[j].ID
+	})
+	sort.Slice(graph.Edges, func(i, j int) bool {
+		x, y := graph.Edges[i], graph.Edges[j]
+		if x.Source != y.Source {
+			return x.Source < y.Source
+		}
+		if x.Target != y.Target {
+			return x.Target < y.Target
+		}
+		return x.Kind < y.Kind
+	})
+	return graph
+}
+
+// dotShapes are the Graphviz node shapes of each node kind
+var dotShapes = map[string]string{
+	graphNodeAgent:   "box",
+	graphNodeSession: "ellipse",
+	graphNodeTool:    "diamond",
+}
+
+// dotQuote quotes a Graphviz ID
+func dotQuote(s string) string {
+	return `"` + strings.NewReplacer(`\`, `\\`, `"`, `\"`, "\n", `\n`).Replace(s) + `"`
+}
+
+// dot renders the graph in the Graphviz DOT language
+func (g *GraphResponse) dot() string {
+	var sb strings.Builder
+	sb.WriteString("digraph facto {\n")
+	for _, node := range g.Nodes {
+		fmt.Fprintf(&sb, "  %s [label=%s, shape=%s, kind=%s, event_count=%d];\n",
+			dotQuote(node.ID), dotQuote(node.Label), dotShapes[node.Kind], node.Kind, node.EventCount)
+	}
+	for _, edge := range g.Edges {
+		fmt.Fprintf(&sb, "  %s -> %s [label=%s, kind=%s, weight=%d];\n",
+			dotQuote(edge.Source), dotQuote(edge.Target),
+			dotQuote(fmt.Sprintf("%s (%d)", edge.Kind, edge.Weight)), edge.Kind, edge.Weight)
+	}
+	sb.WriteString("}\n")
+	return sb.String()
+}
+
+// xmlEscape escapes text for an XML attribute or element
+func xmlEscape(s string) string {
+	var sb strings.Builder
+	xml.EscapeText(&sb, []byte(s))
+	return sb.String()
+}
+
+// graphML renders the graph as GraphML
+func (g *GraphResponse) graphML() string {
+	var sb strings.Builder
+	sb.WriteString(xml.Header)
+	sb.WriteString(`<graphml xmlns="http://graphml.graphdrawing.org/xmlns">` + "\n")
+	sb.WriteString(`  <key id="kind" for="all" attr.name="kind" attr.type="string"/>` + "\n")
+	sb.WriteString(`  <key id="label" for="node" attr.name="label" attr.type="string"/>` + "\n")
+	sb.WriteString(`  <key id="event_count" for="node" attr.name="event_count" attr.type="int"/>` + "\n")
+	sb.WriteString(`  <key id="weight" for="edge" attr.name="weight" attr.type="int"/>` + "\n")
+	sb.WriteString(`  <graph id="facto" edgedefault="directed">` + "\n")
+	for _, node := range g.Nodes {
+		fmt.Fprintf(&sb, `    <node id="%s"><data key="kind">%s</data><data key="label">%s</data><data key="event_count">%d</data></node>`+"\n",
+			xmlEscape(node.ID), node.Kind, xmlEscape(node.Label), node.EventCount)
+	}
+	for i, edge := range g.Edges {
+		fmt.Fprintf(&sb, `    <edge id="e%d" source="%s" target="%s"><data key="kind">%s</data><data key="weight">%d</data></edge>`+"\n",
+			i, xmlEscape(edge.Source), xmlEscape(edge.Target), edge.Kind, edge.Weight)
+	}
+	sb.WriteString("  </graph>\n</graphml>\n")
+	return sb.String()
+}
@@ -1,18 +1,21 @@
 package main
 
 import (
-	"crypto/ed25519"
+	"context"
 	"crypto/sha256"
-	"encoding/base64"
 	"encoding/hex"
 	"encoding/json"
 	"net/http"
 	"sort"
+	"strconv"
 	"time"
 
+	"github.com/facto-ai/facto/server/common/jcs"
+	"github.com/facto-ai/facto/server/common/signature"
 	"github.com/gin-gonic/gin"
 	"github.com/prometheus/client_golang/prometheus"
 	"github.com/prometheus/client_golang/prometheus/promauto"
+	"github.com/rs/zerolog/log"
 	"golang.org/x/crypto/sha3"
 )
 
@@ -31,12 +34,14 @@ var (
 
 // Handlers contains the API handlers
 type Handlers struct {
-	storage *Storage
+	storage *FederatedStorage
+	blobs   *BlobStore
+	bundler *Bundler
 }
 
 // NewHandlers creates a new Handlers instance
-func NewHandlers(storage *Storage) *Handlers {
-	return &Handlers{storage: storage}
+func NewHandlers(storage *FederatedStorage, blobs *BlobStore, bundler *Bundler) *Handlers {
+	return &Handlers{storage: storage, blobs: blobs, bundler: bundler}
 }
 
 // EventsQuery represents query parameters for events listing
@@ -68,6 +73,9 @@ type EventResponse struct {
 	Proof         ProofResponse          `json:"proof"`
 	StartedAt     int64                  `json:"started_at"`
 	CompletedAt   int64                  `json:"completed_at"`
+	// TrustBasis is set when a batch envelope stood in for the signature:
+	// the envelope and the event hashes it covers, from the server envelope
+	TrustBasis    *signature.TrustBasis  `json:"trust_basis,omitempty"`
 }
 
 // ExecutionMetaResponse represents execution metadata in API responses
@@ -85,10 +93,12 @@ type ExecutionMetaResponse struct {
 
 // ProofResponse represents cryptographic proof in API responses
 type ProofResponse struct {
-	Signature string `json:"signature"`
-	PublicKey string `json:"public_key"`
-	PrevHash  string `json:"prev_hash"`
-	EventHash string `json:"event_hash"`
+	Signature        string `json:"signature"`
+	PublicKey        string `json:"public_key"`
+	Algorithm        string `json:"algorithm,omitempty"`
+	CanonicalVersion *int   `json:"canonical_version,omitempty"`
+	PrevHash         string `json:"prev_hash"`
+	EventHash        string `json:"event_hash"`
 }
 
 // GetEvents handles GET /v1/events
@@ -164,6 +174,13 @@ func (h *Handlers) GetEventByFactoID(c *gin.Context) {
 		return
 	}
 
+	if err := h.blobs.ResolvePayloads(c.Request.Context(), event); err != nil {
+		log.Error().Err(err).Msg("Failed to resolve offloaded payload")
+		apiRequestsTotal.WithLabelValues("get_event", "502").Inc()
+		c.JSON(http.StatusBadGateway, gin.H{"error": "failed to fetch offloaded payload"})
+		return
+	}
+
 	apiRequestsTotal.WithLabelValues("get_event", "200").Inc()
 	c.JSON(http.StatusOK, event)
 }
@@ -197,8 +214,7 @@ func (h *Handlers) GetSessionEvents(c *gin.Context) {
 
 	events, nextCursor, err := h.storage.GetSessionEvents(c.Request.Context(), sessionID, query.Limit, query.Cursor)
 	if err != nil {
-		apiRequestsTotal.WithLabelValues("get_session_events", "500").Inc()
-		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to fetch events"})
+		respondFetchError(c, "get_session_events", err)
 		return
 	}
 
@@ -209,6 +225,305 @@ func (h *Handlers) GetSessionEvents(c *gin.Context) {
 	})
 }
 
+// maxAgentEventsRange bounds the number of daily partitions one request scans
+const maxAgentEventsRange = 31 * 24 * time.Hour
+
+// AgentEventsQuery represents query parameters for an agent's events
+type AgentEventsQuery struct {
+	From   string `form:"from"`
+	To     string `form:"to"`
+	Status string `form:"status"`
+	Limit  int    `form:"limit"`
+	Cursor string `form:"cursor"`
+}
+
+// GetAgentEvents handles GET /v1/agents/:agent_id/events
+//
+// Events are returned newest first. `from` and `to` are RFC 3339 and default
+// to the last 24 hours; `status` keeps only events with that status.
+func (h *Handlers) GetAgentEvents(c *gin.Context) {
+	start := time.Now()
+	defer func() {
+		apiRequestDuration.WithLabelValues("get_agent_events").Observe(time.Since(start).Seconds())
+	}()
+
+	agentID := c.Param("agent_id")
+	if agentID == "" {
+		apiRequestsTotal.WithLabelValues("get_agent_events", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": "agent_id is required"})
+		return
+	}
+
+	var query AgentEventsQuery
+	if err := c.ShouldBindQuery(&query); err != nil {
+		apiRequestsTotal.WithLabelValues("get_agent_events", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	if query.Limit <= 0 || query.Limit > 1000 {
+		query.Limit = 100
+	}
+
+	to := time.Now().UTC()
+	if query.To != "" {
+		parsed, err := time.Parse(time.RFC3339, query.To)
+		if err != nil {
+			apiRequestsTotal.WithLabelValues("get_agent_events", "400").Inc()
+			c.JSON(http.StatusBadRequest, gin.H{"error": "invalid to time format"})
+			return
+		}
+		to = parsed
+	}
+
+	from := to.Add(-24 * time.Hour)
+	if query.From != "" {
+		parsed, err := time.Parse(time.RFC3339, query.From)
+		if err != nil {
+			apiRequestsTotal.WithLabelValues("get_agent_events", "400").Inc()
+			c.JSON(http.StatusBadRequest, gin.H{"error": "invalid from time format"})
+			return
+		}
+		from = parsed
+	}
+
+	if from.After(to) {
+		apiRequestsTotal.WithLabelValues("get_agent_events", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": "from must not be after to"})
+		return
+	}
+	if to.Sub(from) > maxAgentEventsRange {
+		apiRequestsTotal.WithLabelValues("get_agent_events", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": "time range must not exceed 31 days"})
+		return
+	}
+
+	var cursor *eventCursor
+	if query.Cursor != "" {
+		decoded, err := decodeEventCursor(query.Cursor)
+		if err != nil {
+			apiRequestsTotal.WithLabelValues("get_agent_events", "400").Inc()
+			c.JSON(http.StatusBadRequest, gin.H{"error": "invalid cursor"})
+			return
+		}
+		cursor = decoded
+	}
+
+	events, nextCursor, err := h.storage.GetAgentEvents(c.Request.Context(), agentID, from, to, query.Status, query.Limit, cursor)
+	if err != nil {
+		apiRequestsTotal.WithLabelValues("get_agent_events", "500").Inc()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to fetch events"})
+		return
+	}
+
+	apiRequestsTotal.WithLabelValues("get_agent_events", "200").Inc()
+	c.JSON(http.StatusOK, EventsResponse{
+		Events:     events,
+		NextCursor: nextCursor,
+	})
+}
+
+const (
+	// defaultLineageDepth and maxLineageDepth bound how many hops a lineage
+	// request follows from the requested event
+	defaultLineageDepth = 10
+	maxLineageDepth     = 100
+
+	// maxLineageNodes bounds the size of a returned graph
+	maxLineageNodes = 1000
+)
+
+// LineageQuery represents query parameters for an event's lineage
+type LineageQuery struct {
+	Depth     int    `form:"depth"`
+	Direction string `form:"direction"`
+}
+
+// LineageResponse is the ancestor or descendant graph of an event
+type LineageResponse struct {
+	Root      string        `json:"root"`
+	Direction string        `json:"direction"`
+	Depth     int           `json:"depth"`
+	Nodes     []LineageNode `json:"nodes"`
+	Edges     []LineageEdge `json:"edges"`
+
+	// Parents referenced by an event in the graph but not stored
+	Missing []string `json:"missing"`
+
+	// Set when depth or the node limit cut the graph short
+	Truncated bool `json:"truncated"`
+}
+
+// LineageNode is an event in a lineage graph, with its hop distance from
+// the root
+type LineageNode struct {
+	Distance int           `json:"distance"`
+	Event    EventResponse `json:"event"`
+}
+
+// LineageEdge links an event to its parent
+type LineageEdge struct {
+	Parent string `json:"parent"`
+	Child  string `json:"child"`
+}
+
+// GetLineage handles GET /v1/lineage/:facto_id
+//
+// `direction=up` (the default) follows parent_facto_id from the event to its
+// ancestors, reconstructing the trace that led to it; `direction=down`
+// returns every event descending from it. `depth` limits the hops followed.
+func (h *Handlers) GetLineage(c *gin.Context) {
+	start := time.Now()
+	defer func() {
+		apiRequestDuration.WithLabelValues("get_lineage").Observe(time.Since(start).Seconds())
+	}()
+
+	factoID := c.Param("facto_id")
+	if factoID == "" {
+		apiRequestsTotal.WithLabelValues("get_lineage", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": "facto_id is required"})
+		return
+	}
+
+	var query LineageQuery
+	if err := c.ShouldBindQuery(&query); err != nil {
+		apiRequestsTotal.WithLabelValues("get_lineage", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	if query.Direction == "" {
+		query.Direction = "up"
+	}
+	if query.Direction != "up" && query.Direction != "down" {
+		apiRequestsTotal.WithLabelValues("get_lineage", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": "direction must be up or down"})
+		return
+	}
+	if query.Depth < 0 || query.Depth > maxLineageDepth {
+		apiRequestsTotal.WithLabelValues("get_lineage", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": "depth must be between 1 and 100"})
+		return
+	}
+	if query.Depth == 0 {
+		query.Depth = defaultLineageDepth
+	}
+
+	ctx := c.Request.Context()
+	root, err := h.storage.GetEventByFactoID(ctx, factoID)
+	if err != nil {
+		apiRequestsTotal.WithLabelValues("get_lineage", "500").Inc()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to fetch event"})
+		return
+	}
+	if root == nil {
+		apiRequestsTotal.WithLabelValues("get_lineage", "404").Inc()
+		c.JSON(http.StatusNotFound, gin.H{"error": "event not found"})
+		return
+	}
+
+	response := LineageResponse{
+		Root:      factoID,
+		Direction: query.Direction,
+		Depth:     query.Depth,
+		Nodes:     []LineageNode{{Distance: 0, Event: *root}},
+		Edges:     []LineageEdge{},
+		Missing:   []string{},
+	}
+
+	if query.Direction == "up" {
+		err = h.walkAncestors(ctx, &response, root, query.Depth)
+	} else {
+		err = h.walkDescendants(ctx, &response, query.Depth)
+	}
+	if err != nil {
+		apiRequestsTotal.WithLabelValues("get_lineage", "500").Inc()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to fetch lineage"})
+		return
+	}
+
+	apiRequestsTotal.WithLabelValues("get_lineage", "200").Inc()
+	c.JSON(http.StatusOK, response)
+}
+
+// walkAncestors follows parent_facto_id upwards from the root
+func (h *Handlers) walkAncestors(ctx context.Context, response *LineageResponse, event *EventResponse, depth int) error {
+	seen := map[string]bool{event.FactoID: true}
+
+	for distance := 1; event.ParentFactoID != nil; distance++ {
+		parentID := *event.ParentFactoID
+		if seen[parentID] {
+			// A parent cycle can only come from forged parent ids
+			break
+		}
+		if distance > depth {
+			response.Truncated = true
+			break
+		}
+
+		parent, err := h.storage.GetEventByFactoID(ctx, parentID)
+		if err != nil {
+			return err
+		}
+		response.Edges = append(response.Edges, LineageEdge{Parent: parentID, Child: event.FactoID})
+		if parent == nil {
+			response.Missing = append(response.Missing, parentID)
+			break
+		}
+
+		seen[parentID] = true
+		response.Nodes = append(response.Nodes, LineageNode{Distance: distance, Event: *parent})
+		event = parent
+	}
+	return nil
+}
+
+// walkDescendants visits the root's descendants breadth first, so every
+// event appears at its shortest distance from the root
+func (h *Handlers) walkDescendants(ctx context.Context, response *LineageResponse, depth int) error {
+	seen := map[string]bool{response.Root: true}
+	level := []string{response.Root}
+
+	for distance := 1; len(level) > 0; distance++ {
+		var next []string
+		for _, parentID := range level {
+			children, err := h.storage.GetChildFactoIDs(ctx, parentID, maxLineageNodes+1)
+			if err != nil {
+				return err
+			}
+			if len(children) > 0 && distance > depth {
+				response.Truncated = true
+				return nil
+			}
+
+			for _, childID := range children {
+				if seen[childID] {
+					continue
+				}
+				if len(response.Nodes) >= maxLineageNodes {
+					response.Truncated = true
+					return nil
+				}
+
+				child, err := h.storage.GetEventByFactoID(ctx, childID)
+				if err != nil {
+					return err
+				}
+				if child == nil {
+					continue
+				}
+
+				seen[childID] = true
+				response.Nodes = append(response.Nodes, LineageNode{Distance: distance, Event: *child})
+				response.Edges = append(response.Edges, LineageEdge{Parent: parentID, Child: childID})
+				next = append(next, childID)
+			}
+		}
+		level = next
+	}
+	return nil
+}
+
 // VerifyRequest represents a verification request
 type VerifyRequest struct {
 	Event EventResponse `json:"event" binding:"required"`
@@ -241,6 +556,13 @@ func (h *Handlers) VerifyEvent(c *gin.Context) {
 		return
 	}
 
+	if err := h.blobs.ResolvePayloads(c.Request.Context(), &req.Event); err != nil {
+		log.Error().Err(err).Msg("Failed to resolve offloaded payload")
+		apiRequestsTotal.WithLabelValues("verify", "502").Inc()
+		c.JSON(http.StatusBadGateway, gin.H{"error": "failed to fetch offloaded payload"})
+		return
+	}
+
 	// Verify hash
 	hashValid := verifyHash(&req.Event)
 
@@ -300,8 +622,7 @@ func (h *Handlers) VerifyChain(c *gin.Context) {
 	// Get all events for the session
 	events, _, err := h.storage.GetSessionEvents(c.Request.Context(), query.SessionID, 10000, "")
 	if err != nil {
-		apiRequestsTotal.WithLabelValues("verify_chain", "500").Inc()
-		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to fetch events"})
+		respondFetchError(c, "verify_chain", err)
 		return
 	}
 
@@ -311,6 +632,13 @@ func (h *Handlers) VerifyChain(c *gin.Context) {
 		return
 	}
 
+	if err := h.blobs.ResolveAll(c.Request.Context(), events); err != nil {
+		log.Error().Err(err).Msg("Failed to resolve offloaded payload")
+		apiRequestsTotal.WithLabelValues("verify_chain", "502").Inc()
+		c.JSON(http.StatusBadGateway, gin.H{"error": "failed to fetch offloaded payload"})
+		return
+	}
+
 	// Sort events by completed_at (oldest first for chain verification)
 	sort.Slice(events, func(i, j int) bool {
 		return events[i].CompletedAt < events[j].CompletedAt
@@ -419,8 +747,7 @@ func (h *Handlers) GetEvidencePackage(c *gin.Context) {
 	// Get all events for the session
 	events, _, err := h.storage.GetSessionEvents(c.Request.Context(), query.SessionID, 10000, "")
 	if err != nil {
-		apiRequestsTotal.WithLabelValues("evidence_package", "500").Inc()
-		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to fetch events"})
+		respondFetchError(c, "evidence_package", err)
 		return
 	}
 
@@ -430,6 +757,14 @@ func (h *Handlers) GetEvidencePackage(c *gin.Context) {
 		return
 	}
 
+	// The package carries payloads so it can be verified on its own
+	if err := h.blobs.ResolveAll(c.Request.Context(), events); err != nil {
+		log.Error().Err(err).Msg("Failed to resolve offloaded payload")
+		apiRequestsTotal.WithLabelValues("evidence_package", "502").Inc()
+		c.JSON(http.StatusBadGateway, gin.H{"error": "failed to fetch offloaded payload"})
+		return
+	}
+
 	// Build Merkle tree and proofs
 	hashes := make([]string, len(events))
 	for i, e := range events {
@@ -492,24 +827,28 @@ func verifyHash(event *EventResponse) bool {
 }
 
 func verifySignature(event *EventResponse) bool {
-	// Decode public key
-	pubKeyBytes, err := base64.StdEncoding.DecodeString(event.Proof.PublicKey)
-	if err != nil || len(pubKeyBytes) != ed25519.PublicKeySize {
-		return false
+	proof := &event.Proof
+	// A batch envelope signed with the event's key stands in for the
+	// signature of an event it covers
+	if event.TrustBasis.Enveloped() {
+		return event.TrustBasis.VerifyEnvelope(proof.EventHash, proof.PublicKey) == nil
 	}
 
-	// Decode signature
-	sigBytes, err := base64.StdEncoding.DecodeString(event.Proof.Signature)
-	if err != nil || len(sigBytes) != ed25519.SignatureSize {
-		return false
-	}
-
-	// Build canonical form and verify
+	// Build canonical form and verify with the proof's algorithm
 	canonical := buildCanonicalForm(event)
-	return ed25519.Verify(pubKeyBytes, []byte(canonical), sigBytes)
+	return signature.Verify(proof.Algorithm, proof.PublicKey, proof.Signature, []byte(canonical)) == nil
 }
 
+// buildCanonicalForm builds the form the event hash and signature cover, in
+// the canonical form version the proof names
 func buildCanonicalForm(event *EventResponse) string {
+	if event.Proof.CanonicalVersion != nil && *event.Proof.CanonicalVersion == 2 {
+		return buildCanonicalFormV2(event)
+	}
+	return buildCanonicalFormV1(event)
+}
+
+func buildCanonicalFormV1(event *EventResponse) string {
 	// Build a map with sorted keys
 	canonical := make(map[string]interface{})
 
@@ -544,6 +883,66 @@ func buildCanonicalForm(event *EventResponse) string {
 	return string(bytes)
 }
 
+// buildCanonicalFormV2 is the RFC 8785 serialization of the whole event
+// without the signature and event hash. Every field is present, null when
+// unset, the proof always names its algorithm, and the 64-bit integers are
+// strings.
+func buildCanonicalFormV2(event *EventResponse) string {
+	toolCalls := event.ExecutionMeta.ToolCalls
+	if toolCalls == nil {
+		toolCalls = []interface{}{}
+	}
+	tags := event.ExecutionMeta.Tags
+	if tags == nil {
+		tags = map[string]string{}
+	}
+	var seed interface{}
+	if event.ExecutionMeta.Seed != nil {
+		seed = strconv.FormatInt(*event.ExecutionMeta.Seed, 10)
+	}
+	algorithm := event.Proof.Algorithm
+	if algorithm == "" {
+		algorithm = signature.Ed25519
+	}
+
+	canonical := map[string]interface{}{
+		"action_type":   event.ActionType,
+		"agent_id":      event.AgentID,
+		"completed_at":  strconv.FormatInt(event.CompletedAt, 10),
+		"event_version": 1,
+		"execution_meta": map[string]interface{}{
+			"max_tokens":   event.ExecutionMeta.MaxTokens,
+			"model_hash":   event.ExecutionMeta.ModelHash,
+			"model_id":     event.ExecutionMeta.ModelID,
+			"sdk_language": event.ExecutionMeta.SDKLanguage,
+			"sdk_version":  event.ExecutionMeta.SDKVersion,
+			"seed":         seed,
+			"tags":         tags,
+			"temperature":  event.ExecutionMeta.Temperature,
+			"tool_calls":   toolCalls,
+		},
+		"facto_id":        event.FactoID,
+		"input_data":      event.InputData,
+		"output_data":     event.OutputData,
+		"parent_facto_id": event.ParentFactoID,
+		"proof": map[string]interface{}{
+			"algorithm":         algorithm,
+			"canonical_version": 2,
+			"prev_hash":         event.Proof.PrevHash,
+			"public_key":        event.Proof.PublicKey,
+		},
+		"session_id": event.SessionID,
+		"started_at": strconv.FormatInt(event.StartedAt, 10),
+		"status":     event.Status,
+	}
+
+	canonicalJSON, err := jcs.Marshal(canonical)
+	if err != nil {
+		return ""
+	}
+	return string(canonicalJSON)
+}
+
 func sortedMap(m map[string]interface{}) map[string]interface{} {
 	// Get sorted keys
 	keys := make([]string, 0, len(m))
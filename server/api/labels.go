@@ -0,0 +1,197 @@
+package main
+
+import (
+	"context"
+	"net/http"
+	"time"
+
+	"github.com/gin-gonic/gin"
+	"github.com/rs/zerolog/log"
+)
+
+// EventLabel is a category assigned to an event by a classifier
+type EventLabel struct {
+	Category        string   `json:"category"`
+	Classifier      string   `json:"classifier"`
+	Rule            *string  `json:"rule,omitempty"`
+	Confidence      *float64 `json:"confidence,omitempty"`
+	ClassifiedAt    int64    `json:"classified_at"`
+	SignerPublicKey string   `json:"signer_public_key"`
+	Signature       string   `json:"signature"`
+}
+
+// EventLabelsResponse lists the labels of one event
+type EventLabelsResponse struct {
+	FactoID string       `json:"facto_id"`
+	Labels  []EventLabel `json:"labels"`
+}
+
+// LabeledEventsQuery represents query parameters for events by label
+type LabeledEventsQuery struct {
+	Start    string `form:"start" binding:"required"`
+	End      string `form:"end" binding:"required"`
+	AgentID  string `form:"agent_id"`
+	TenantID string `form:"tenant_id"`
+	Limit    int    `form:"limit"`
+}
+
+// LabeledEvent is an event carrying a label, without its payload
+type LabeledEvent struct {
+	FactoID     string `json:"facto_id"`
+	AgentID     string `json:"agent_id"`
+	SessionID   string `json:"session_id"`
+	TenantID    string `json:"tenant_id,omitempty"`
+	ActionType  string `json:"action_type"`
+	CompletedAt int64  `json:"completed_at"`
+}
+
+// LabeledEventsResponse lists events carrying a label, newest first
+type LabeledEventsResponse struct {
+	Category  string         `json:"category"`
+	Events    []LabeledEvent `json:"events"`
+	Truncated bool           `json:"truncated"`
+}
+
+// GetEventLabels handles GET /v1/events/:facto_id/labels
+func (h *Handlers) GetEventLabels(c *gin.Context) {
+	start := time.Now()
+	defer func() {
+		apiRequestDuration.WithLabelValues("event_labels").Observe(time.Since(start).Seconds())
+	}()
+
+	factoID := c.Param("facto_id")
+	labels, err := h.storage.GetEventLabels(c.Request.Context(), factoID)
+	if err != nil {
+		apiRequestsTotal.WithLabelValues("event_labels", "500").Inc()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to fetch labels"})
+		return
+	}
+
+	apiRequestsTotal.WithLabelValues("event_labels", "200").Inc()
+	c.JSON(http.StatusOK, EventLabelsResponse{FactoID: factoID, Labels: labels})
+}
+
+// GetLabeledEvents handles GET /v1/labels/:category/events
+//
+// Lists the events a classifier labelled with a category between start and
+// end, e.g. every code_execution event of the last week.
+func (h *Handlers) GetLabeledEvents(c *gin.Context) {
+	start := time.Now()
+	defer func() {
+		apiRequestDuration.WithLabelValues("labeled_events").Observe(time.Since(start).Seconds())
+	}()
+
+	var query LabeledEventsQuery
+	if err := c.ShouldBindQuery(&query); err != nil {
+		apiRequestsTotal.WithLabelValues("labeled_events", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	if query.Limit <= 0 || query.Limit > 1000 {
+		query.Limit = 100
+	}
+
+	startTime, err := time.Parse(time.RFC3339, query.Start)
+	if err != nil {
+		apiRequestsTotal.WithLabelValues("labeled_events", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": "invalid start time format"})
+		return
+	}
+
+	endTime, err := time.Parse(time.RFC3339, query.End)
+	if err != nil {
+		apiRequestsTotal.WithLabelValues("labeled_events", "400").Inc()
+		c.JSON(http.StatusBadRequest, gin.H{"error": "invalid end time format"})
+		return
+	}
+
+	category := c.Param("category")
+	events, truncated, err := h.storage.GetLabeledEvents(c.Request.Context(), category, startTime, endTime, query.AgentID, query.TenantID, query.Limit)
+	if err != nil {
+		apiRequestsTotal.WithLabelValues("labeled_events", "500").Inc()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to fetch events"})
+		return
+	}
+
+	apiRequestsTotal.WithLabelValues("labeled_events", "200").Inc()
+	c.JSON(http.StatusOK, LabeledEventsResponse{
+		Category:  category,
+		Events:    events,
+		Truncated: truncated,
+	})
+}
+
+// GetEventLabels retrieves the classifier labels of an event
+func (s *Storage) GetEventLabels(ctx context.Context, factoID string) ([]EventLabel, error) {
+	labels := []EventLabel{}
+
+	iter := s.session.Query(`
+		SELECT category, classifier, rule, confidence,
+		       classified_at, signer_public_key, signature
+		FROM event_labels
+		WHERE facto_id = ?
+	`, factoID).WithContext(ctx).Iter()
+
+	var (
+		label        EventLabel
+		classifiedAt time.Time
+	)
+	for iter.Scan(
+		&label.Category, &label.Classifier, &label.Rule, &label.Confidence,
+		&classifiedAt, &label.SignerPublicKey, &label.Signature,
+	) {
+		label.ClassifiedAt = classifiedAt.UnixNano()
+		labels = append(labels, label)
+		label = EventLabel{}
+	}
+
+	if err := iter.Close(); err != nil {
+		log.Error().Err(err).Msg("Error iterating event labels")
+		return nil, err
+	}
+
+	return labels, nil
+}
+
+// GetLabeledEvents retrieves events labelled with a category within a time
+// range, newest day first. agentID and tenantID filter when set.
+func (s *Storage) GetLabeledEvents(ctx context.Context, category string, start, end time.Time, agentID, tenantID string, limit int) ([]LabeledEvent, bool, error) {
+	events := []LabeledEvent{}
+
+	dates := getDateRange(start, end)
+	for i := len(dates) - 1; i >= 0; i-- {
+		iter := s.session.Query(`
+			SELECT facto_id, agent_id, session_id, tenant_id, action_type, completed_at
+			FROM events_by_label
+			WHERE category = ? AND date = ?
+			  AND completed_at >= ? AND completed_at <= ?
+		`, category, dates[i], start, end).WithContext(ctx).Iter()
+
+		var (
+			event       LabeledEvent
+			completedAt time.Time
+		)
+		for iter.Scan(
+			&event.FactoID, &event.AgentID, &event.SessionID, &event.TenantID,
+			&event.ActionType, &completedAt,
+		) {
+			if (agentID != "" && event.AgentID != agentID) || (tenantID != "" && event.TenantID != tenantID) {
+				continue
+			}
+			if len(events) == limit {
+				iter.Close()
+				return events, true, nil
+			}
+			event.CompletedAt = completedAt.UnixNano()
+			events = append(events, event)
+		}
+
+		if err := iter.Close(); err != nil {
+			log.Error().Err(err).Msg("Error iterating labeled events")
+			return nil, false, err
+		}
+	}
+
+	return events, false, nil
+}
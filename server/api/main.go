@@ -9,6 +9,7 @@ import (
 	"syscall"
 	"time"
 
+	"github.com/facto-ai/facto/server/common/archive"
 	"github.com/gin-gonic/gin"
 	"github.com/prometheus/client_golang/prometheus/promhttp"
 	"github.com/rs/zerolog"
@@ -19,6 +20,11 @@ import (
 type Config struct {
 	Port        int
 	ScyllaHosts []string
+	// ArchiveURI is where the archiver writes; sessions the hot tier no
+	// longer holds are read back from it. Empty disables the cold tier.
+	ArchiveURI           string
+	HydrationTTL         time.Duration
+	HydrationMaxSessions int
 }
 
 func loadConfig() *Config {
@@ -34,9 +40,26 @@ func loadConfig() *Config {
 		scyllaHosts = "localhost:9042"
 	}
 
+	hydrationTTL := time.Hour
+	if v := os.Getenv("HYDRATION_TTL_SECS"); v != "" {
+		if parsed, err := strconv.Atoi(v); err == nil && parsed > 0 {
+			hydrationTTL = time.Duration(parsed) * time.Second
+		}
+	}
+
+	hydrationMaxSessions := 100
+	if v := os.Getenv("HYDRATION_MAX_SESSIONS"); v != "" {
+		if parsed, err := strconv.Atoi(v); err == nil && parsed > 0 {
+			hydrationMaxSessions = parsed
+		}
+	}
+
 	return &Config{
-		Port:        port,
-		ScyllaHosts: []string{scyllaHosts},
+		Port:                 port,
+		ScyllaHosts:          []string{scyllaHosts},
+		ArchiveURI:           os.Getenv("ARCHIVE_URI"),
+		HydrationTTL:         hydrationTTL,
+		HydrationMaxSessions: hydrationMaxSessions,
 	}
 }
 
@@ -52,6 +75,7 @@ func main() {
 	log.Info().
 		Int("port", config.Port).
 		Strs("scylla_hosts", config.ScyllaHosts).
+		Str("archive_uri", config.ArchiveURI).
 		Msg("Configuration loaded")
 
 	// Initialize storage
@@ -62,8 +86,24 @@ func main() {
 	defer storage.Close()
 	log.Info().Msg("Connected to ScyllaDB")
 
+	federated := &FederatedStorage{Storage: storage}
+	if config.ArchiveURI != "" {
+		store, err := archive.NewStore(config.ArchiveURI)
+		if err != nil {
+			log.Fatal().Err(err).Msg("Failed to open the archive")
+		}
+		federated.cold = NewColdTier(store, config.HydrationTTL, config.HydrationMaxSessions, 2)
+		log.Info().Msg("Reading archived sessions back from the archive")
+	}
+
+	bundler, err := NewBundler()
+	if err != nil {
+		log.Fatal().Err(err).Msg("Failed to initialize evidence bundles")
+	}
+	log.Info().Str("public_key", bundler.PublicKey()).Msg("Evidence bundle signing key")
+
 	// Create handlers
-	handlers := NewHandlers(storage)
+	handlers := NewHandlers(federated, NewBlobStore(), bundler)
 
 	// Setup Gin
 	gin.SetMode(gin.ReleaseMode)
@@ -82,7 +122,15 @@ func main() {
 	{
 		v1.GET("/events", handlers.GetEvents)
 		v1.GET("/events/:facto_id", handlers.GetEventByFactoID)
+		v1.GET("/events/:facto_id/labels", handlers.GetEventLabels)
+		v1.GET("/labels/:category/events", handlers.GetLabeledEvents)
 		v1.GET("/sessions/:session_id/events", handlers.GetSessionEvents)
+		v1.GET("/sessions/:session_id/audit", handlers.GetSessionAudit)
+		v1.GET("/sessions/:session_id/bundle", handlers.GetSessionBundle)
+		v1.GET("/sessions/:session_id/hydration", handlers.GetSessionHydration)
+		v1.GET("/agents/:agent_id/events", handlers.GetAgentEvents)
+		v1.GET("/lineage/:facto_id", handlers.GetLineage)
+		v1.GET("/graph", handlers.GetGraph)
 		v1.POST("/verify", handlers.VerifyEvent)
 		v1.GET("/verify/chain", handlers.VerifyChain)
 		v1.GET("/evidence-package", handlers.GetEvidencePackage)
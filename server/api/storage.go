@@ -4,8 +4,12 @@ import (
 	"context"
 	"encoding/base64"
 	"encoding/json"
+	"errors"
+	"strconv"
+	"strings"
 	"time"
 
+	commonsig "github.com/facto-ai/facto/server/common/signature"
 	"github.com/gocql/gocql"
 	"github.com/rs/zerolog/log"
 )
@@ -49,7 +53,7 @@ func (s *Storage) GetEvents(ctx context.Context, agentID string, start, end time
 			       action_type, status, input_data, output_data,
 			       model_id, model_hash, temperature, seed, max_tokens, tool_calls,
 			       sdk_version, sdk_language, tags,
-			       signature, public_key, prev_hash, event_hash,
+			       signature, public_key, signature_algorithm, canonical_version, trust_basis, prev_hash, event_hash,
 			       started_at, completed_at
 			FROM events
 			WHERE agent_id = ? AND date = ?
@@ -72,6 +76,9 @@ func (s *Storage) GetEvents(ctx context.Context, agentID string, start, end time
 			tags                              map[string]string
 			signature, publicKey              []byte
 			prevHash, eventHash               string
+			algorithm                         string
+			canonicalVersion                  *int
+			trustBasis                        *string
 			startedAt, completedAt            time.Time
 		)
 
@@ -80,7 +87,7 @@ func (s *Storage) GetEvents(ctx context.Context, agentID string, start, end time
 			&actionType, &status, &inputData, &outputData,
 			&modelID, &modelHash, &temperature, &seed, &maxTokens, &toolCalls,
 			&sdkVersion, &sdkLanguage, &tags,
-			&signature, &publicKey, &prevHash, &eventHash,
+			&signature, &publicKey, &algorithm, &canonicalVersion, &trustBasis, &prevHash, &eventHash,
 			&startedAt, &completedAt,
 		) {
 			event := buildEventResponse(
@@ -88,7 +95,7 @@ func (s *Storage) GetEvents(ctx context.Context, agentID string, start, end time
 				actionType, status, inputData, outputData,
 				modelID, modelHash, temperature, seed, maxTokens, toolCalls,
 				sdkVersion, sdkLanguage, tags,
-				signature, publicKey, prevHash, eventHash,
+				signature, publicKey, algorithm, canonicalVersion, trustBasis, prevHash, eventHash,
 				startedAt, completedAt,
 			)
 			events = append(events, event)
@@ -127,7 +134,7 @@ func (s *Storage) GetEventByFactoID(ctx context.Context, factoID string) (*Event
 		       action_type, status, input_data, output_data,
 		       model_id, model_hash, temperature, seed, max_tokens, tool_calls,
 		       sdk_version, sdk_language, tags,
-		       signature, public_key, prev_hash, event_hash,
+		       signature, public_key, signature_algorithm, canonical_version, trust_basis, prev_hash, event_hash,
 		       parent_facto_id, started_at
 		FROM events_by_facto_id
 		WHERE facto_id = ?
@@ -148,6 +155,9 @@ func (s *Storage) GetEventByFactoID(ctx context.Context, factoID string) (*Event
 		tags                              map[string]string
 		signature, publicKey              []byte
 		prevHash, eventHash               string
+		algorithm                         string
+		canonicalVersion                  *int
+		trustBasis                        *string
 	)
 
 	if err := query.Scan(
@@ -155,7 +165,7 @@ func (s *Storage) GetEventByFactoID(ctx context.Context, factoID string) (*Event
 		&actionType, &status, &inputData, &outputData,
 		&modelID, &modelHash, &temperature, &seed, &maxTokens, &toolCalls,
 		&sdkVersion, &sdkLanguage, &tags,
-		&signature, &publicKey, &prevHash, &eventHash,
+		&signature, &publicKey, &algorithm, &canonicalVersion, &trustBasis, &prevHash, &eventHash,
 		&parentFactoID, &startedAt,
 	); err != nil {
 		if err == gocql.ErrNotFound {
@@ -169,13 +179,84 @@ func (s *Storage) GetEventByFactoID(ctx context.Context, factoID string) (*Event
 		actionType, status, inputData, outputData,
 		modelID, modelHash, temperature, seed, maxTokens, toolCalls,
 		sdkVersion, sdkLanguage, tags,
-		signature, publicKey, prevHash, eventHash,
+		signature, publicKey, algorithm, canonicalVersion, trustBasis, prevHash, eventHash,
 		startedAt, completedAt,
 	)
 
 	return &event, nil
 }
 
+// factoIDLookupChunk is the number of facto_ids read per IN query
+const factoIDLookupChunk = 100
+
+// GetEventsByFactoIDs retrieves the events with the given facto_ids, in no
+// particular order. Unknown facto_ids are skipped.
+func (s *Storage) GetEventsByFactoIDs(ctx context.Context, factoIDs []string) ([]EventResponse, error) {
+	events := make([]EventResponse, 0, len(factoIDs))
+
+	for i := 0; i < len(factoIDs); i += factoIDLookupChunk {
+		end := i + factoIDLookupChunk
+		if end > len(factoIDs) {
+			end = len(factoIDs)
+		}
+
+		iter := s.session.Query(`
+			SELECT facto_id, agent_id, completed_at, session_id,
+			       action_type, status, input_data, output_data,
+			       model_id, model_hash, temperature, seed, max_tokens, tool_calls,
+			       sdk_version, sdk_language, tags,
+			       signature, public_key, signature_algorithm, canonical_version, trust_basis, prev_hash, event_hash,
+			       parent_facto_id, started_at
+			FROM events_by_facto_id
+			WHERE facto_id IN ?
+		`, factoIDs[i:end]).WithContext(ctx).Iter()
+
+		var (
+			factoID, agentID, sessionID, parentFactoID string
+			completedAt, startedAt                     time.Time
+			actionType, status                         string
+			inputData, outputData                      []byte
+			modelID, modelHash                         string
+			temperature                                float32
+			seed                                       int64
+			maxTokens                                  int32
+			toolCalls                                  string
+			sdkVersion, sdkLanguage                    string
+			tags                                       map[string]string
+			signature, publicKey                       []byte
+			prevHash, eventHash                        string
+			algorithm                                  string
+			canonicalVersion                           *int
+			trustBasis                                 *string
+		)
+
+		for iter.Scan(
+			&factoID, &agentID, &completedAt, &sessionID,
+			&actionType, &status, &inputData, &outputData,
+			&modelID, &modelHash, &temperature, &seed, &maxTokens, &toolCalls,
+			&sdkVersion, &sdkLanguage, &tags,
+			&signature, &publicKey, &algorithm, &canonicalVersion, &trustBasis, &prevHash, &eventHash,
+			&parentFactoID, &startedAt,
+		) {
+			events = append(events, buildEventResponse(
+				factoID, agentID, sessionID, parentFactoID,
+				actionType, status, inputData, outputData,
+				modelID, modelHash, temperature, seed, maxTokens, toolCalls,
+				sdkVersion, sdkLanguage, tags,
+				signature, publicKey, algorithm, canonicalVersion, trustBasis, prevHash, eventHash,
+				startedAt, completedAt,
+			))
+		}
+
+		if err := iter.Close(); err != nil {
+			log.Error().Err(err).Msg("Error iterating events by facto_id")
+			return nil, err
+		}
+	}
+
+	return events, nil
+}
+
 // GetSessionEvents retrieves all events for a session
 func (s *Storage) GetSessionEvents(ctx context.Context, sessionID string, limit int, cursor string) ([]EventResponse, *string, error) {
 	var events []EventResponse
@@ -186,7 +267,7 @@ func (s *Storage) GetSessionEvents(ctx context.Context, sessionID string, limit
 		       input_data, output_data,
 		       model_id, temperature,
 		       sdk_version, sdk_language, tags,
-		       signature, public_key, prev_hash,
+		       signature, public_key, signature_algorithm, canonical_version, trust_basis, prev_hash,
 		       parent_facto_id, started_at
 		FROM events_by_session
 		WHERE session_id = ?
@@ -207,6 +288,9 @@ func (s *Storage) GetSessionEvents(ctx context.Context, sessionID string, limit
 		tags                            map[string]string
 		signature, publicKey            []byte
 		prevHash                        string
+		algorithm                       string
+		canonicalVersion                *int
+		trustBasis                      *string
 	)
 
 	for iter.Scan(
@@ -215,7 +299,7 @@ func (s *Storage) GetSessionEvents(ctx context.Context, sessionID string, limit
 		&inputData, &outputData,
 		&modelID, &temperature,
 		&sdkVersion, &sdkLanguage, &tags,
-		&signature, &publicKey, &prevHash,
+		&signature, &publicKey, &algorithm, &canonicalVersion, &trustBasis, &prevHash,
 		&parentFactoID, &startedAt,
 	) {
 		event := buildEventResponse(
@@ -223,7 +307,7 @@ func (s *Storage) GetSessionEvents(ctx context.Context, sessionID string, limit
 			actionType, status, inputData, outputData,
 			modelID, "", temperature, 0, 0, "[]",
 			sdkVersion, sdkLanguage, tags,
-			signature, publicKey, prevHash, eventHash,
+			signature, publicKey, algorithm, canonicalVersion, trustBasis, prevHash, eventHash,
 			startedAt, completedAt,
 		)
 		events = append(events, event)
@@ -250,6 +334,128 @@ func (s *Storage) GetSessionEvents(ctx context.Context, sessionID string, limit
 	return events, nextCursor, nil
 }
 
+// GetAgentEvents retrieves an agent's events within a time range, newest
+// first, optionally only those with the given status. Pages continue after
+// the event the cursor points at.
+func (s *Storage) GetAgentEvents(ctx context.Context, agentID string, from, to time.Time, status string, limit int, cursor *eventCursor) ([]EventResponse, *string, error) {
+	var events []EventResponse
+
+	if cursor != nil && cursor.CompletedAt.Before(to) {
+		to = cursor.CompletedAt
+	}
+	if from.After(to) {
+		return events, nil, nil
+	}
+
+	// Partitions are per day; walk them newest first
+	dates := getDateRange(from, to)
+
+	for i := len(dates) - 1; i >= 0 && len(events) <= limit; i-- {
+		query := s.session.Query(`
+			SELECT facto_id, agent_id, session_id, parent_facto_id,
+			       action_type, status, input_data, output_data,
+			       model_id, model_hash, temperature, seed, max_tokens, tool_calls,
+			       sdk_version, sdk_language, tags,
+			       signature, public_key, signature_algorithm, canonical_version, trust_basis, prev_hash, event_hash,
+			       started_at, completed_at
+			FROM events
+			WHERE agent_id = ? AND date = ?
+			  AND completed_at >= ? AND completed_at <= ?
+		`, agentID, dates[i], from, to).WithContext(ctx).PageSize(limit + 1)
+
+		iter := query.Iter()
+
+		var (
+			factoID, sessionID, parentFactoID string
+			actionType, eventStatus           string
+			inputData, outputData             []byte
+			modelID, modelHash                string
+			temperature                       float32
+			seed                              int64
+			maxTokens                         int32
+			toolCalls                         string
+			sdkVersion, sdkLanguage           string
+			tags                              map[string]string
+			signature, publicKey              []byte
+			prevHash, eventHash               string
+			algorithm                         string
+			canonicalVersion                  *int
+			trustBasis                        *string
+			startedAt, completedAt            time.Time
+		)
+
+		for len(events) <= limit && iter.Scan(
+			&factoID, &agentID, &sessionID, &parentFactoID,
+			&actionType, &eventStatus, &inputData, &outputData,
+			&modelID, &modelHash, &temperature, &seed, &maxTokens, &toolCalls,
+			&sdkVersion, &sdkLanguage, &tags,
+			&signature, &publicKey, &algorithm, &canonicalVersion, &trustBasis, &prevHash, &eventHash,
+			&startedAt, &completedAt,
+		) {
+			// Rows sharing the cursor's timestamp sort by facto_id
+			if cursor != nil && completedAt.Equal(cursor.CompletedAt) && factoID <= cursor.FactoID {
+				continue
+			}
+			if status != "" && eventStatus != status {
+				continue
+			}
+
+			events = append(events, buildEventResponse(
+				factoID, agentID, sessionID, parentFactoID,
+				actionType, eventStatus, inputData, outputData,
+				modelID, modelHash, temperature, seed, maxTokens, toolCalls,
+				sdkVersion, sdkLanguage, tags,
+				signature, publicKey, algorithm, canonicalVersion, trustBasis, prevHash, eventHash,
+				startedAt, completedAt,
+			))
+		}
+
+		if err := iter.Close(); err != nil {
+			log.Error().Err(err).Msg("Error iterating agent events")
+			return nil, nil, err
+		}
+	}
+
+	// Handle pagination
+	var nextCursor *string
+	if len(events) > limit {
+		events = events[:limit]
+		lastEvent := events[len(events)-1]
+		next := encodeEventCursor(eventCursor{
+			CompletedAt: time.Unix(0, lastEvent.CompletedAt).UTC(),
+			FactoID:     lastEvent.FactoID,
+		})
+		nextCursor = &next
+	}
+
+	return events, nextCursor, nil
+}
+
+// GetChildFactoIDs returns the facto_ids of events whose parent is
+// parentID, oldest first
+func (s *Storage) GetChildFactoIDs(ctx context.Context, parentID string, limit int) ([]string, error) {
+	var children []string
+
+	iter := s.session.Query(`
+		SELECT facto_id
+		FROM events_by_parent
+		WHERE parent_facto_id = ?
+		LIMIT ?
+	`, parentID, limit).WithContext(ctx).Iter()
+
+	var factoID string
+	for iter.Scan(&factoID) {
+		children = append(children, factoID)
+	}
+
+	if err := iter.Close(); err != nil {
+		log.Error().Err(err).Msg("Error iterating child events")
+		return nil, err
+	}
+
+	return children, nil
+}
+
 // Close closes the storage connection
 func (s *Storage) Close() {
 	if s.session != nil {
@@ -259,6 +465,34 @@ func (s *Storage) Close() {
 
 // Helper functions
 
+// eventCursor marks the last event of a page in (completed_at DESC,
+// facto_id ASC) order
+type eventCursor struct {
+	CompletedAt time.Time
+	FactoID     string
+}
+
+func encodeEventCursor(cursor eventCursor) string {
+	raw := strconv.FormatInt(cursor.CompletedAt.UnixMilli(), 10) + ":" + cursor.FactoID
+	return base64.RawURLEncoding.EncodeToString([]byte(raw))
+}
+
+func decodeEventCursor(encoded string) (*eventCursor, error) {
+	raw, err := base64.RawURLEncoding.DecodeString(encoded)
+	if err != nil {
+		return nil, err
+	}
+	millis, factoID, found := strings.Cut(string(raw), ":")
+	if !found || factoID == "" {
+		return nil, errors.New("malformed cursor")
+	}
+	ms, err := strconv.ParseInt(millis, 10, 64)
+	if err != nil {
+		return nil, err
+	}
+	return &eventCursor{CompletedAt: time.UnixMilli(ms).UTC(), FactoID: factoID}, nil
+}
+
 func getDateRange(start, end time.Time) []time.Time {
 	var dates []time.Time
 
@@ -285,6 +519,9 @@ func buildEventResponse(
 	sdkVersion, sdkLanguage string,
 	tags map[string]string,
 	signature, publicKey []byte,
+	algorithm string,
+	canonicalVersion *int,
+	trustBasis *string,
 	prevHash, eventHash string,
 	startedAt, completedAt time.Time,
 ) EventResponse {
@@ -345,6 +582,15 @@ func buildEventResponse(
 		output = make(map[string]interface{})
 	}
 
+	var trust *commonsig.TrustBasis
+	if trustBasis != nil {
+		trust = new(commonsig.TrustBasis)
+		if err := json.Unmarshal([]byte(*trustBasis), trust); err != nil {
+			log.Warn().Err(err).Str("facto_id", factoID).Msg("Ignoring malformed trust basis")
+			trust = nil
+		}
+	}
+
 	return EventResponse{
 		FactoID:       factoID,
 		AgentID:       agentID,
@@ -366,12 +612,15 @@ func buildEventResponse(
 			Tags:        tags,
 		},
 		Proof: ProofResponse{
-			Signature: string(signature), // Stored as base64 bytes, no need to re-encode
-			PublicKey: string(publicKey), // Stored as base64 bytes, no need to re-encode
-			PrevHash:  prevHash,
-			EventHash: eventHash,
+			Signature:        string(signature), // Stored as base64 bytes, no need to re-encode
+			PublicKey:        string(publicKey), // Stored as base64 bytes, no need to re-encode
+			Algorithm:        algorithm,
+			CanonicalVersion: canonicalVersion,
+			PrevHash:         prevHash,
+			EventHash:        eventHash,
 		},
 		StartedAt:   startedAt.UnixNano(),
 		CompletedAt: completedAt.UnixNano(),
+		TrustBasis:  trust,
 	}
 }
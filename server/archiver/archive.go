@@ -0,0 +1,166 @@
+package main
+
+import (
+	"bytes"
+	"context"
+	"crypto/sha256"
+	"encoding/hex"
+	"encoding/json"
+	"fmt"
+	"net/url"
+	"sort"
+	"time"
+
+	"github.com/facto-ai/facto/server/common/archive"
+	"github.com/parquet-go/parquet-go"
+)
+
+// Partition is the tenant, agent and hour an archive file holds events of
+type Partition struct {
+	TenantID string
+	AgentID  string
+	Hour     time.Time
+}
+
+// partitionOf returns the partition of an event by when it was received
+func partitionOf(event *archive.Event) Partition {
+	return Partition{
+		TenantID: event.TenantID,
+		AgentID:  event.AgentID,
+		Hour:     time.Unix(0, event.ReceivedAt).UTC().Truncate(time.Hour),
+	}
+}
+
+// Path is the Hive-style directory of the partition's files
+func (p Partition) Path() string {
+	return fmt.Sprintf("tenant=%s/agent=%s/date=%s/hour=%s",
+		url.PathEscape(p.TenantID), url.PathEscape(p.AgentID),
+		p.Hour.Format("2006-01-02"), p.Hour.Format("15"))
+}
+
+// Archiver writes batches of events as Parquet files, one per partition
+type Archiver struct {
+	store  *archive.Store
+	stream string
+}
+
+// NewArchiver creates an archiver writing to store
+func NewArchiver(store *archive.Store, stream string) *Archiver {
+	return &Archiver{store: store, stream: stream}
+}
+
+// Archive writes the events, in stream order, then the manifest listing
+// their files. Files are named by the stream sequences they hold, so a
+// batch written again after a failure replaces its own files.
+func (a *Archiver) Archive(ctx context.Context, events []archive.Event) (*archive.Manifest, error) {
+	partitions := make(map[Partition][]archive.Event)
+	for _, event := range events {
+		partition := partitionOf(&event)
+		partitions[partition] = append(partitions[partition], event)
+	}
+
+	manifest := &archive.Manifest{
+		Stream:    a.stream,
+		CreatedAt: time.Now().UTC(),
+	}
+	for partition, rows := range partitions {
+		file, err := a.writeFile(ctx, partition, rows)
+		if err != nil {
+			return nil, err
+		}
+		manifest.Files = append(manifest.Files, *file)
+	}
+	sort.Slice(manifest.Files, func(i, j int) bool {
+		return manifest.Files[i].FirstSequence < manifest.Files[j].FirstSequence
+	})
+	manifest.FirstSequence = manifest.Files[0].FirstSequence
+	for _, file := range manifest.Files {
+		if file.LastSequence > manifest.LastSequence {
+			manifest.LastSequence = file.LastSequence
+		}
+	}
+
+	data, err := json.MarshalIndent(manifest, "", "  ")
+	if err != nil {
+		return nil, err
+	}
+	key := fmt.Sprintf("%s/date=%s/%020d-%020d.json", archive.ManifestPrefix,
+		manifest.CreatedAt.Format("2006-01-02"), manifest.FirstSequence, manifest.LastSequence)
+	if err := a.store.Put(ctx, key, data, "application/json"); err != nil {
+		return nil, fmt.Errorf("failed to write manifest: %w", err)
+	}
+	return manifest, nil
+}
+
+// writeFile encodes a partition's events as a Parquet file and stores it
+func (a *Archiver) writeFile(ctx context.Context, partition Partition, rows []archive.Event) (*archive.ManifestFile, error) {
+	var buf bytes.Buffer
+	writer := parquet.NewGenericWriter[archive.Event](&buf, parquet.Compression(&parquet.Zstd))
+	if _, err := writer.Write(rows); err != nil {
+		return nil, fmt.Errorf("failed to encode %s: %w", partition.Path(), err)
+	}
+	if err := writer.Close(); err != nil {
+		return nil, fmt.Errorf("failed to encode %s: %w", partition.Path(), err)
+	}
+
+	file := &archive.ManifestFile{
+		TenantID:      partition.TenantID,
+		AgentID:       partition.AgentID,
+		Hour:          partition.Hour,
+		Events:        len(rows),
+		Bytes:         buf.Len(),
+		FirstSequence: rows[0].Sequence,
+		LastSequence:  rows[len(rows)-1].Sequence,
+		MinReceivedAt: rows[0].ReceivedAt,
+		MaxReceivedAt: rows[0].ReceivedAt,
+	}
+	hashes := make([]string, len(rows))
+	for i, row := range rows {
+		hashes[i] = row.EventHash
+		if row.ReceivedAt < file.MinReceivedAt {
+			file.MinReceivedAt = row.ReceivedAt
+		}
+		if row.ReceivedAt > file.MaxReceivedAt {
+			file.MaxReceivedAt = row.ReceivedAt
+		}
+	}
+	file.MerkleRoot = merkleRoot(hashes)
+	sum := sha256.Sum256(buf.Bytes())
+	file.SHA256 = hex.EncodeToString(sum[:])
+
+	key := fmt.Sprintf("%s/%020d-%020d.parquet", partition.Path(), file.FirstSequence, file.LastSequence)
+	if err := a.store.Put(ctx, key, buf.Bytes(), "application/vnd.apache.parquet"); err != nil {
+		return nil, fmt.Errorf("failed to write %s: %w", key, err)
+	}
+	file.URI = a.store.URI(key)
+	return file, nil
+}
+
+// merkleRoot computes the root the processor's BuildMerkleTree gives for
+// the same hex hashes: SHA256(left || right) of each pair, the last node
+// of an odd level paired with itself
+func merkleRoot(hashes []string) string {
+	if len(hashes) == 0 {
+		return hex.EncodeToString(sha256.New().Sum(nil))
+	}
+
+	level := make([][]byte, len(hashes))
+	for i, h := range hashes {
+		level[i], _ = hex.DecodeString(h)
+	}
+	// Even a single hash is paired, with itself
+	for {
+		if len(level)%2 != 0 {
+			level = append(level, level[len(level)-1])
+		}
+		parents := make([][]byte, 0, len(level)/2)
+		for i := 0; i < len(level); i += 2 {
+			sum := sha256.Sum256(append(append([]byte{}, level[i]...), level[i+1]...))
+			parents = append(parents, sum[:])
+		}
+		level = parents
+		if len(level) == 1 {
+			return hex.EncodeToString(level[0])
+		}
+	}
+}
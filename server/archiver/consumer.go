@@ -0,0 +1,363 @@
+package main
+
+import (
+	"context"
+	"encoding/json"
+	"os"
+	"time"
+
+	"github.com/facto-ai/facto/server/common/archive"
+	"github.com/nats-io/nats.go"
+	"github.com/nats-io/nats.go/jetstream"
+	"github.com/prometheus/client_golang/prometheus"
+	"github.com/prometheus/client_golang/prometheus/promauto"
+	"github.com/rs/zerolog/log"
+)
+
+// envelopeHeader carries the ingestion server's envelope for each event
+const envelopeHeader = "Facto-Envelope"
+
+// defaultTenant is the tenant of events published without one
+const defaultTenant = "default"
+
+var (
+	eventsConsumed = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_archiver_events_consumed_total",
+		Help: "Total number of events consumed from NATS",
+	})
+
+	eventsArchived = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_archiver_events_archived_total",
+		Help: "Total number of events written to archive files",
+	})
+
+	eventsRejected = promauto.NewCounterVec(prometheus.CounterOpts{
+		Name: "facto_archiver_events_rejected_total",
+		Help: "Total number of events terminated without being archived",
+	}, []string{"reason"})
+
+	filesWritten = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_archiver_files_written_total",
+		Help: "Total number of Parquet files written",
+	})
+
+	bytesWritten = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_archiver_bytes_written_total",
+		Help: "Total bytes of Parquet files written",
+	})
+
+	flushesFailed = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_archiver_flushes_failed_total",
+		Help: "Total number of flushes whose files or manifest failed to write",
+	})
+
+	flushLatency = promauto.NewHistogram(prometheus.HistogramOpts{
+		Name:    "facto_archiver_flush_latency_seconds",
+		Help:    "Latency of writing a batch's files and manifest in seconds",
+		Buckets: []float64{0.1, 0.25, 0.5, 1, 2.5, 5, 10, 30, 60},
+	})
+
+	archiveLag = promauto.NewGauge(prometheus.GaugeOpts{
+		Name: "facto_archiver_lag_seconds",
+		Help: "Seconds since the oldest consumed but unarchived event was received, or 0 when none is waiting",
+	})
+
+	lastArchived = promauto.NewGauge(prometheus.GaugeOpts{
+		Name: "facto_archiver_last_archived_timestamp_seconds",
+		Help: "Receive time of the newest archived event",
+	})
+
+	pendingMessages = promauto.NewGauge(prometheus.GaugeOpts{
+		Name: "facto_archiver_pending_messages",
+		Help: "Messages in the stream not yet delivered to or acked by the archiver",
+	})
+)
+
+// FactoEvent is the part of a published event the archiver indexes its
+// rows by; the event itself is archived as published
+type FactoEvent struct {
+	FactoID       string  `json:"facto_id"`
+	AgentID       string  `json:"agent_id"`
+	SessionID     string  `json:"session_id"`
+	ParentFactoID *string `json:"parent_facto_id"`
+	ActionType    string  `json:"action_type"`
+	Status        string  `json:"status"`
+	StartedAt     int64   `json:"started_at"`
+	CompletedAt   int64   `json:"completed_at"`
+	Proof         struct {
+		PrevHash  string `json:"prev_hash"`
+		EventHash string `json:"event_hash"`
+	} `json:"proof"`
+}
+
+// Envelope is the part of the server envelope the archiver partitions by
+type Envelope struct {
+	ReceivedAt int64   `json:"received_at"`
+	TenantID   *string `json:"tenant_id"`
+}
+
+// Consumer pulls events from the event stream and archives them in
+// batches, acking each batch once its manifest is written
+type Consumer struct {
+	nc            *nats.Conn
+	js            jetstream.JetStream
+	stream        string
+	archiver      *Archiver
+	batchSize     int
+	flushInterval time.Duration
+	events        []archive.Event
+	messages      []jetstream.Msg
+}
+
+// NewConsumer creates a new NATS consumer
+func NewConsumer(natsURL, stream string, archiver *Archiver, batchSize int, flushInterval time.Duration) (*Consumer, error) {
+	nc, err := nats.Connect(natsURL,
+		nats.RetryOnFailedConnect(true),
+		nats.MaxReconnects(-1),
+		nats.ReconnectWait(time.Second),
+		nats.DisconnectErrHandler(func(nc *nats.Conn, err error) {
+			log.Warn().Err(err).Msg("NATS disconnected")
+		}),
+		nats.ReconnectHandler(func(nc *nats.Conn) {
+			log.Info().Msg("NATS reconnected")
+		}),
+	)
+	if err != nil {
+		return nil, err
+	}
+
+	js, err := jetstream.New(nc)
+	if err != nil {
+		nc.Close()
+		return nil, err
+	}
+
+	return &Consumer{
+		nc:            nc,
+		js:            js,
+		stream:        stream,
+		archiver:      archiver,
+		batchSize:     batchSize,
+		flushInterval: flushInterval,
+		events:        make([]archive.Event, 0, batchSize),
+		messages:      make([]jetstream.Msg, 0, batchSize),
+	}, nil
+}
+
+// Start begins consuming messages. FACTO_EVENTS is a work queue, so each
+// event is delivered to one consumer only: next to the processor or the
+// indexer, give the archiver a FILTER_SUBJECT of its own or archive
+// FACTO_FEED, the copy of every accepted event the ingestion service keeps
+// when cursors are enabled. Both streams carry the same messages.
+func (c *Consumer) Start(ctx context.Context) error {
+	stream, err := c.js.Stream(ctx, c.stream)
+	if err != nil {
+		return err
+	}
+
+	var subjects []string
+	if filter := os.Getenv("FILTER_SUBJECT"); filter != "" {
+		subjects = []string{filter}
+		log.Info().Str("filter_subject", filter).Msg("Using filtered subject")
+	}
+
+	durableName := os.Getenv("DURABLE_NAME")
+	if durableName == "" {
+		durableName = "archiver"
+	}
+
+	// A batch waits up to a flush interval before it is acked
+	consumer, err := stream.CreateOrUpdateConsumer(ctx, jetstream.ConsumerConfig{
+		Durable:        durableName,
+		FilterSubjects: subjects,
+		AckPolicy:      jetstream.AckExplicitPolicy,
+		MaxAckPending:  c.batchSize * 2,
+		AckWait:        c.flushInterval + 5*time.Minute,
+	})
+	if err != nil {
+		return err
+	}
+
+	log.Info().Str("durable", durableName).Str("stream", c.stream).Msg("Started archiving")
+
+	ticker := time.NewTicker(c.flushInterval)
+	defer ticker.Stop()
+	lagTicker := time.NewTicker(5 * time.Second)
+	defer lagTicker.Stop()
+
+	msgChan := make(chan jetstream.Msg, c.batchSize)
+	go func() {
+		for {
+			select {
+			case <-ctx.Done():
+				close(msgChan)
+				return
+			default:
+				msgs, err := consumer.Fetch(c.batchSize, jetstream.FetchMaxWait(5*time.Second))
+				if err != nil {
+					if err != context.Canceled {
+						log.Debug().Err(err).Msg("Fetch returned")
+					}
+					continue
+				}
+				for msg := range msgs.Messages() {
+					msgChan <- msg
+				}
+			}
+		}
+	}()
+
+	for {
+		select {
+		case <-ctx.Done():
+			// Unarchived messages are redelivered after AckWait
+			return ctx.Err()
+
+		case msg, ok := <-msgChan:
+			if !ok {
+				return nil
+			}
+			c.handleMessage(ctx, msg)
+
+		case <-ticker.C:
+			if len(c.events) > 0 {
+				c.flush(ctx)
+			}
+
+		case <-lagTicker.C:
+			c.updateLag(ctx, consumer)
+		}
+	}
+}
+
+// handleMessage queues an event for the next batch. Messages that are not
+// events are terminated, never retried.
+func (c *Consumer) handleMessage(ctx context.Context, msg jetstream.Msg) {
+	eventsConsumed.Inc()
+
+	meta, err := msg.Metadata()
+	if err != nil {
+		log.Error().Err(err).Msg("Message has no JetStream metadata")
+		msg.Term()
+		eventsRejected.WithLabelValues("malformed").Inc()
+		return
+	}
+
+	var event FactoEvent
+	if err := json.Unmarshal(msg.Data(), &event); err != nil || event.FactoID == "" {
+		log.Error().Err(err).Uint64("sequence", meta.Sequence.Stream).Msg("Failed to unmarshal event")
+		msg.Term()
+		eventsRejected.WithLabelValues("malformed").Inc()
+		return
+	}
+
+	row := archive.Event{
+		Sequence:      meta.Sequence.Stream,
+		FactoID:       event.FactoID,
+		TenantID:      defaultTenant,
+		AgentID:       event.AgentID,
+		SessionID:     event.SessionID,
+		ParentFactoID: event.ParentFactoID,
+		ActionType:    event.ActionType,
+		Status:        event.Status,
+		StartedAt:     event.StartedAt,
+		CompletedAt:   event.CompletedAt,
+		ReceivedAt:    meta.Timestamp.UnixNano(),
+		EventHash:     event.Proof.EventHash,
+		PrevHash:      event.Proof.PrevHash,
+		Event:         string(msg.Data()),
+	}
+	if raw := msg.Headers().Get(envelopeHeader); raw != "" {
+		var envelope Envelope
+		if err := json.Unmarshal([]byte(raw), &envelope); err != nil {
+			log.Warn().Err(err).Str("facto_id", event.FactoID).Msg("Ignoring malformed envelope")
+		} else {
+			row.Envelope = raw
+			if envelope.ReceivedAt > 0 {
+				row.ReceivedAt = envelope.ReceivedAt
+			}
+			if envelope.TenantID != nil {
+				row.TenantID = *envelope.TenantID
+			}
+		}
+	}
+
+	c.events = append(c.events, row)
+	c.messages = append(c.messages, msg)
+
+	if len(c.events) >= c.batchSize {
+		c.flush(ctx)
+	}
+}
+
+// flush writes the batch's files and manifest and acks only after both
+// are stored
+func (c *Consumer) flush(ctx context.Context) {
+	if len(c.events) == 0 {
+		return
+	}
+
+	start := time.Now()
+	manifest, err := c.archiver.Archive(ctx, c.events)
+	flushLatency.Observe(time.Since(start).Seconds())
+
+	if err != nil {
+		log.Error().Err(err).Int("count", len(c.events)).Msg("Failed to archive batch")
+		for _, msg := range c.messages {
+			msg.Nak()
+		}
+		flushesFailed.Inc()
+	} else {
+		for _, msg := range c.messages {
+			msg.Ack()
+		}
+		var newest int64
+		for _, file := range manifest.Files {
+			filesWritten.Inc()
+			bytesWritten.Add(float64(file.Bytes))
+			if file.MaxReceivedAt > newest {
+				newest = file.MaxReceivedAt
+			}
+		}
+		eventsArchived.Add(float64(len(c.events)))
+		lastArchived.Set(float64(newest) / 1e9)
+
+		log.Info().
+			Int("count", len(c.events)).
+			Int("files", len(manifest.Files)).
+			Uint64("first_sequence", manifest.FirstSequence).
+			Uint64("last_sequence", manifest.LastSequence).
+			Dur("duration", time.Since(start)).
+			Msg("Batch archived")
+	}
+
+	c.events = c.events[:0]
+	c.messages = c.messages[:0]
+	archiveLag.Set(0)
+}
+
+// updateLag reports how far the archive is behind: how long ago the
+// oldest waiting event was received, and how many messages the stream
+// holds for the archiver
+func (c *Consumer) updateLag(ctx context.Context, consumer jetstream.Consumer) {
+	if len(c.events) > 0 {
+		archiveLag.Set(time.Since(time.Unix(0, c.events[0].ReceivedAt)).Seconds())
+	} else {
+		archiveLag.Set(0)
+	}
+
+	info, err := consumer.Info(ctx)
+	if err != nil {
+		log.Debug().Err(err).Msg("Failed to read consumer info")
+		return
+	}
+	pendingMessages.Set(float64(info.NumPending) + float64(info.NumAckPending))
+}
+
+// Close closes the consumer
+func (c *Consumer) Close() error {
+	if c.nc != nil {
+		c.nc.Close()
+	}
+	return nil
+}
@@ -0,0 +1,148 @@
+package main
+
+import (
+	"context"
+	"net/http"
+	"os"
+	"os/signal"
+	"strconv"
+	"syscall"
+	"time"
+
+	"github.com/facto-ai/facto/server/common/archive"
+	"github.com/prometheus/client_golang/prometheus/promhttp"
+	"github.com/rs/zerolog"
+	"github.com/rs/zerolog/log"
+)
+
+// Config holds the archiver configuration
+type Config struct {
+	NatsURL       string
+	Stream        string
+	ArchiveURI    string
+	BatchSize     int
+	FlushInterval time.Duration
+	MetricsPort   int
+}
+
+func loadConfig() *Config {
+	natsURL := os.Getenv("NATS_URL")
+	if natsURL == "" {
+		natsURL = "nats://localhost:4222"
+	}
+
+	stream := os.Getenv("STREAM")
+	if stream == "" {
+		stream = "FACTO_EVENTS"
+	}
+
+	archiveURI := os.Getenv("ARCHIVE_URI")
+	if archiveURI == "" {
+		archiveURI = "file:///var/lib/facto/archive"
+	}
+
+	batchSize := 10000
+	if bs := os.Getenv("BATCH_SIZE"); bs != "" {
+		if parsed, err := strconv.Atoi(bs); err == nil {
+			batchSize = parsed
+		}
+	}
+
+	// Events are acked only once archived, so each flush writes at least
+	// one file per partition; a longer interval means fewer, larger files
+	flushIntervalMs := 60000
+	if fi := os.Getenv("FLUSH_INTERVAL_MS"); fi != "" {
+		if parsed, err := strconv.Atoi(fi); err == nil {
+			flushIntervalMs = parsed
+		}
+	}
+
+	metricsPort := 8084
+	if mp := os.Getenv("METRICS_PORT"); mp != "" {
+		if parsed, err := strconv.Atoi(mp); err == nil {
+			metricsPort = parsed
+		}
+	}
+
+	return &Config{
+		NatsURL:       natsURL,
+		Stream:        stream,
+		ArchiveURI:    archiveURI,
+		BatchSize:     batchSize,
+		FlushInterval: time.Duration(flushIntervalMs) * time.Millisecond,
+		MetricsPort:   metricsPort,
+	}
+}
+
+func main() {
+	// Setup logging
+	zerolog.TimeFieldFormat = zerolog.TimeFormatUnix
+	log.Logger = log.Output(zerolog.ConsoleWriter{Out: os.Stderr, TimeFormat: time.RFC3339})
+
+	log.Info().Msg("Starting Facto Archiver Service")
+
+	// Load configuration
+	config := loadConfig()
+	log.Info().
+		Str("nats_url", config.NatsURL).
+		Str("stream", config.Stream).
+		Str("archive_uri", config.ArchiveURI).
+		Int("batch_size", config.BatchSize).
+		Dur("flush_interval", config.FlushInterval).
+		Int("metrics_port", config.MetricsPort).
+		Msg("Configuration loaded")
+
+	// Create context with cancellation
+	ctx, cancel := context.WithCancel(context.Background())
+	defer cancel()
+
+	store, err := archive.NewStore(config.ArchiveURI)
+	if err != nil {
+		log.Fatal().Err(err).Msg("Invalid ARCHIVE_URI")
+	}
+
+	// Initialize consumer
+	consumer, err := NewConsumer(config.NatsURL, config.Stream, NewArchiver(store, config.Stream), config.BatchSize, config.FlushInterval)
+	if err != nil {
+		log.Fatal().Err(err).Msg("Failed to initialize consumer")
+	}
+	defer consumer.Close()
+	log.Info().Msg("Connected to NATS")
+
+	// Start metrics server
+	go func() {
+		http.Handle("/metrics", promhttp.Handler())
+		http.HandleFunc("/health", func(w http.ResponseWriter, r *http.Request) {
+			w.WriteHeader(http.StatusOK)
+			w.Write([]byte(`{"status":"healthy"}`))
+		})
+		addr := ":" + strconv.Itoa(config.MetricsPort)
+		log.Info().Str("addr", addr).Msg("Starting metrics server")
+		if err := http.ListenAndServe(addr, nil); err != nil {
+			log.Error().Err(err).Msg("Metrics server error")
+		}
+	}()
+
+	// Start consuming messages
+	go func() {
+		if err := consumer.Start(ctx); err != nil && err != context.Canceled {
+			log.Error().Err(err).Msg("Consumer error")
+			cancel()
+		}
+	}()
+
+	// Wait for shutdown signal
+	sigChan := make(chan os.Signal, 1)
+	signal.Notify(sigChan, syscall.SIGINT, syscall.SIGTERM)
+	select {
+	case <-sigChan:
+	case <-ctx.Done():
+	}
+
+	log.Info().Msg("Shutting down...")
+	cancel()
+
+	// Give time for graceful shutdown
+	time.Sleep(2 * time.Second)
+	log.Info().Msg("Shutdown complete")
+}
@@ -0,0 +1,58 @@
+// Package archive is the layout of the archiver's Parquet files and
+// manifests, and the store they are written to and read from
+package archive
+
+import "time"
+
+// ManifestPrefix is where manifests are written, beside the partitions
+const ManifestPrefix = "_manifests"
+
+// Event is one row of an archive file. Its Event column holds the event as
+// published, so it can be verified again from the archive alone.
+type Event struct {
+	Sequence      uint64  `parquet:"sequence"`
+	FactoID       string  `parquet:"facto_id"`
+	TenantID      string  `parquet:"tenant_id"`
+	AgentID       string  `parquet:"agent_id"`
+	SessionID     string  `parquet:"session_id"`
+	ParentFactoID *string `parquet:"parent_facto_id,optional"`
+	ActionType    string  `parquet:"action_type"`
+	Status        string  `parquet:"status"`
+	StartedAt     int64   `parquet:"started_at"`
+	CompletedAt   int64   `parquet:"completed_at"`
+	ReceivedAt    int64   `parquet:"received_at"`
+	EventHash     string  `parquet:"event_hash"`
+	PrevHash      string  `parquet:"prev_hash"`
+	Event         string  `parquet:"event,json"`
+	Envelope      string  `parquet:"envelope,optional,json"`
+}
+
+// ManifestFile describes one archive file. MerkleRoot is the root of the
+// Merkle tree over its events' hashes in row order, built as the processor
+// builds batch roots, so a single event can be proven part of the file.
+type ManifestFile struct {
+	URI           string    `json:"uri"`
+	TenantID      string    `json:"tenant_id"`
+	AgentID       string    `json:"agent_id"`
+	Hour          time.Time `json:"hour"`
+	Events        int       `json:"events"`
+	Bytes         int       `json:"bytes"`
+	SHA256        string    `json:"sha256"`
+	MerkleRoot    string    `json:"merkle_root"`
+	FirstSequence uint64    `json:"first_sequence"`
+	LastSequence  uint64    `json:"last_sequence"`
+	MinReceivedAt int64     `json:"min_received_at"`
+	MaxReceivedAt int64     `json:"max_received_at"`
+}
+
+// Manifest records the files written by one flush. A file belongs to the
+// archive once a manifest lists it; files left by a flush that failed
+// before its manifest was written are superseded when the events are
+// redelivered.
+type Manifest struct {
+	Stream        string         `json:"stream"`
+	CreatedAt     time.Time      `json:"created_at"`
+	FirstSequence uint64         `json:"first_sequence"`
+	LastSequence  uint64         `json:"last_sequence"`
+	Files         []ManifestFile `json:"files"`
+}
@@ -0,0 +1,99 @@
+package archive
+
+import (
+	"bytes"
+	"context"
+	"crypto/sha256"
+	"encoding/hex"
+	"encoding/json"
+	"reflect"
+	"testing"
+	"time"
+
+	"github.com/parquet-go/parquet-go"
+)
+
+// TestArchiveRoundTrip writes a file and its manifest as the archiver does,
+// compressed and with every column set, and reads both back unchanged
+func TestArchiveRoundTrip(t *testing.T) {
+	ctx := context.Background()
+	store, err := NewStore("file://" + t.TempDir())
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	parent := "ft-1"
+	rows := []Event{
+		{
+			Sequence: 7, FactoID: "ft-1", TenantID: "acme", AgentID: "agent-1", SessionID: "s-1",
+			ActionType: "llm_call", Status: "success",
+			StartedAt: 1700000000000000000, CompletedAt: 1700000000500000000, ReceivedAt: 1700000001000000000,
+			EventHash: "aa", PrevHash: "00",
+			Event: `{"facto_id":"ft-1","input_data":{"prompt":"hi"}}`,
+		},
+		{
+			Sequence: 8, FactoID: "ft-2", TenantID: "acme", AgentID: "agent-1", SessionID: "s-1",
+			ParentFactoID: &parent, ActionType: "tool_call", Status: "error",
+			StartedAt: 1700000002000000000, CompletedAt: 1700000002500000000, ReceivedAt: 1700000003000000000,
+			EventHash: "bb", PrevHash: "aa",
+			Event:    `{"facto_id":"ft-2","parent_facto_id":"ft-1"}`,
+			Envelope: `{"received_at":1700000003000000000,"verification":{"trust_basis":{"type":"signature"}}}`,
+		},
+	}
+	var buf bytes.Buffer
+	writer := parquet.NewGenericWriter[Event](&buf, parquet.Compression(&parquet.Zstd))
+	if _, err := writer.Write(rows); err != nil {
+		t.Fatal(err)
+	}
+	if err := writer.Close(); err != nil {
+		t.Fatal(err)
+	}
+	key := "tenant=acme/agent=agent-1/date=2023-11-14/hour=22/7-8.parquet"
+	if err := store.Put(ctx, key, buf.Bytes(), "application/vnd.apache.parquet"); err != nil {
+		t.Fatal(err)
+	}
+
+	sum := sha256.Sum256(buf.Bytes())
+	manifest := Manifest{
+		Stream:        "FACTO_EVENTS",
+		CreatedAt:     time.Date(2023, 11, 14, 23, 0, 0, 0, time.UTC),
+		FirstSequence: 7,
+		LastSequence:  8,
+		Files: []ManifestFile{{
+			URI:           store.URI(key),
+			TenantID:      "acme",
+			AgentID:       "agent-1",
+			Hour:          time.Date(2023, 11, 14, 22, 0, 0, 0, time.UTC),
+			Events:        len(rows),
+			Bytes:         buf.Len(),
+			SHA256:        hex.EncodeToString(sum[:]),
+			MerkleRoot:    "cc",
+			FirstSequence: 7,
+			LastSequence:  8,
+			MinReceivedAt: rows[0].ReceivedAt,
+			MaxReceivedAt: rows[1].ReceivedAt,
+		}},
+	}
+	data, err := json.Marshal(manifest)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if err := store.Put(ctx, ManifestPrefix+"/date=2023-11-14/7-8.json", data, "application/json"); err != nil {
+		t.Fatal(err)
+	}
+
+	manifests, err := store.Manifests(ctx)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if len(manifests) != 1 || !reflect.DeepEqual(manifests[0], manifest) {
+		t.Fatalf("manifest changed in the round trip: %+v", manifests)
+	}
+	events, err := store.Events(ctx, &manifests[0].Files[0])
+	if err != nil {
+		t.Fatal(err)
+	}
+	if !reflect.DeepEqual(events, rows) {
+		t.Fatalf("rows changed in the round trip:\n got %+v\nwant %+v", events, rows)
+	}
+}
@@ -0,0 +1,64 @@
+package archive
+
+import (
+	"bytes"
+	"context"
+	"crypto/sha256"
+	"encoding/hex"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"strings"
+
+	"github.com/parquet-go/parquet-go"
+)
+
+// ErrCorrupt marks archive files that do not match their manifest
+var ErrCorrupt = errors.New("archive file does not match its manifest")
+
+// Manifests reads every manifest in the store, oldest flush first
+func (s *Store) Manifests(ctx context.Context) ([]Manifest, error) {
+	keys, err := s.List(ctx, ManifestPrefix+"/")
+	if err != nil {
+		return nil, fmt.Errorf("failed to list manifests: %w", err)
+	}
+
+	manifests := make([]Manifest, 0, len(keys))
+	for _, key := range keys {
+		if !strings.HasSuffix(key, ".json") {
+			continue
+		}
+		data, err := s.Get(ctx, s.URI(key))
+		if err != nil {
+			return nil, fmt.Errorf("failed to read manifest %s: %w", key, err)
+		}
+		var manifest Manifest
+		if err := json.Unmarshal(data, &manifest); err != nil {
+			return nil, fmt.Errorf("malformed manifest %s: %w", key, err)
+		}
+		manifests = append(manifests, manifest)
+	}
+	return manifests, nil
+}
+
+// Events reads the rows of an archive file, after checking its size and
+// SHA-256 against the manifest that lists it
+func (s *Store) Events(ctx context.Context, file *ManifestFile) ([]Event, error) {
+	data, err := s.Get(ctx, file.URI)
+	if err != nil {
+		return nil, fmt.Errorf("failed to read %s: %w", file.URI, err)
+	}
+	if len(data) != file.Bytes {
+		return nil, fmt.Errorf("%w: %s is not %d bytes", ErrCorrupt, file.URI, file.Bytes)
+	}
+	sum := sha256.Sum256(data)
+	if hex.EncodeToString(sum[:]) != file.SHA256 {
+		return nil, fmt.Errorf("%w: %s hash differs", ErrCorrupt, file.URI)
+	}
+
+	rows, err := parquet.Read[Event](bytes.NewReader(data), int64(len(data)))
+	if err != nil {
+		return nil, fmt.Errorf("%w: %s is not readable: %v", ErrCorrupt, file.URI, err)
+	}
+	return rows, nil
+}
@@ -0,0 +1,151 @@
+package archive
+
+import (
+	"context"
+	"fmt"
+	"io"
+	"io/fs"
+	"net/url"
+	"os"
+	"path/filepath"
+	"sort"
+	"strings"
+	"time"
+
+	"github.com/facto-ai/facto/server/common/s3"
+)
+
+// Store holds archive objects under an ARCHIVE_URI: a file:// directory, or
+// a bucket and prefix of an S3-compatible store (s3:// or gs://)
+type Store struct {
+	scheme string
+	bucket string
+	prefix string
+	client *s3.Client
+}
+
+// NewStore reads ARCHIVE_S3_ENDPOINT and ARCHIVE_S3_REGION, and the AWS_*
+// credentials, for S3-compatible stores
+func NewStore(uri string) (*Store, error) {
+	u, err := url.Parse(uri)
+	if err != nil {
+		return nil, err
+	}
+
+	store := &Store{scheme: u.Scheme}
+	switch u.Scheme {
+	case "file":
+		store.prefix = u.Path
+	case "s3", "gs":
+		if u.Host == "" {
+			return nil, fmt.Errorf("%s has no bucket", uri)
+		}
+		store.bucket = u.Host
+		store.prefix = strings.Trim(u.Path, "/")
+	default:
+		return nil, fmt.Errorf("unsupported archive uri scheme %q", u.Scheme)
+	}
+
+	region := os.Getenv("ARCHIVE_S3_REGION")
+	if region == "" {
+		region = os.Getenv("AWS_REGION")
+	}
+	store.client = s3.NewClient(u.Scheme, os.Getenv("ARCHIVE_S3_ENDPOINT"), region, 5*time.Minute)
+	return store, nil
+}
+
+// URI returns where an object is stored
+func (s *Store) URI(key string) string {
+	if s.scheme == "file" {
+		return "file://" + filepath.Join(s.prefix, key)
+	}
+	return s.scheme + "://" + s.bucket + "/" + s.objectKey(key)
+}
+
+func (s *Store) objectKey(key string) string {
+	if s.prefix == "" {
+		return key
+	}
+	return s.prefix + "/" + key
+}
+
+// Put writes an object, replacing any object of the same key
+func (s *Store) Put(ctx context.Context, key string, data []byte, contentType string) error {
+	if s.scheme == "file" {
+		return s.putFile(key, data)
+	}
+	return s.client.Put(ctx, s.bucket, s.objectKey(key), data, contentType)
+}
+
+// putFile writes through a temporary file so readers never see part of one
+func (s *Store) putFile(key string, data []byte) error {
+	path := filepath.Join(s.prefix, filepath.FromSlash(key))
+	if err := os.MkdirAll(filepath.Dir(path), 0o755); err != nil {
+		return err
+	}
+	tmp := path + ".tmp"
+	if err := os.WriteFile(tmp, data, 0o644); err != nil {
+		return err
+	}
+	return os.Rename(tmp, path)
+}
+
+// Get reads an object by the URI the store gave it
+func (s *Store) Get(ctx context.Context, uri string) ([]byte, error) {
+	u, err := url.Parse(uri)
+	if err != nil {
+		return nil, err
+	}
+	if u.Scheme != s.scheme || u.Host != s.bucket {
+		return nil, fmt.Errorf("%s is outside the archive", uri)
+	}
+	if s.scheme == "file" {
+		return os.ReadFile(u.Path)
+	}
+	body, err := s.client.Get(ctx, s.bucket, strings.TrimPrefix(u.Path, "/"))
+	if err != nil {
+		return nil, err
+	}
+	defer body.Close()
+	return io.ReadAll(body)
+}
+
+// List returns the keys of the objects under prefix, in key order
+func (s *Store) List(ctx context.Context, prefix string) ([]string, error) {
+	if s.scheme != "file" {
+		keys, err := s.client.List(ctx, s.bucket, s.objectKey(prefix))
+		if err != nil {
+			return nil, err
+		}
+		for i, key := range keys {
+			keys[i] = strings.TrimPrefix(strings.TrimPrefix(key, s.prefix), "/")
+		}
+		return keys, nil
+	}
+
+	var keys []string
+	root := filepath.Join(s.prefix, filepath.FromSlash(prefix))
+	err := filepath.WalkDir(root, func(path string, entry fs.DirEntry, err error) error {
+		if err != nil {
+			return err
+		}
+		// Temporary files are objects still being written
+		if entry.IsDir() || strings.HasSuffix(path, ".tmp") {
+			return nil
+		}
+		key, err := filepath.Rel(s.prefix, path)
+		if err != nil {
+			return err
+		}
+		keys = append(keys, filepath.ToSlash(key))
+		return nil
+	})
+	if os.IsNotExist(err) {
+		return nil, nil
+	}
+	if err != nil {
+		return nil, err
+	}
+	sort.Strings(keys)
+	return keys, nil
+}
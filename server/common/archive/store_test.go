@@ -0,0 +1,77 @@
+package archive
+
+import (
+	"bytes"
+	"context"
+	"crypto/sha256"
+	"encoding/hex"
+	"errors"
+	"testing"
+
+	"github.com/parquet-go/parquet-go"
+)
+
+func TestFileStoreRoundTrip(t *testing.T) {
+	ctx := context.Background()
+	store, err := NewStore("file://" + t.TempDir())
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	rows := []Event{
+		{Sequence: 1, FactoID: "ft-1", SessionID: "s-1", EventHash: "aa", Event: `{"facto_id":"ft-1"}`},
+		{Sequence: 2, FactoID: "ft-2", SessionID: "s-2", EventHash: "bb", Event: `{"facto_id":"ft-2"}`},
+	}
+	var buf bytes.Buffer
+	writer := parquet.NewGenericWriter[Event](&buf)
+	if _, err := writer.Write(rows); err != nil {
+		t.Fatal(err)
+	}
+	if err := writer.Close(); err != nil {
+		t.Fatal(err)
+	}
+	key := "tenant=default/agent=a/date=2026-01-01/hour=00/1-2.parquet"
+	if err := store.Put(ctx, key, buf.Bytes(), "application/vnd.apache.parquet"); err != nil {
+		t.Fatal(err)
+	}
+	sum := sha256.Sum256(buf.Bytes())
+	file := &ManifestFile{URI: store.URI(key), Events: 2, Bytes: buf.Len(), SHA256: hex.EncodeToString(sum[:])}
+	if err := store.Put(ctx, ManifestPrefix+"/date=2026-01-01/1-2.json", []byte(`{"files":[]}`), "application/json"); err != nil {
+		t.Fatal(err)
+	}
+
+	keys, err := store.List(ctx, "")
+	if err != nil {
+		t.Fatal(err)
+	}
+	if len(keys) != 2 || keys[0] != ManifestPrefix+"/date=2026-01-01/1-2.json" || keys[1] != key {
+		t.Fatalf("unexpected keys %v", keys)
+	}
+	manifests, err := store.Manifests(ctx)
+	if err != nil || len(manifests) != 1 {
+		t.Fatalf("expected one manifest, got %v, %v", manifests, err)
+	}
+
+	events, err := store.Events(ctx, file)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if len(events) != 2 || events[1].SessionID != "s-2" || events[1].Event != rows[1].Event {
+		t.Fatalf("unexpected events %+v", events)
+	}
+
+	file.SHA256 = hex.EncodeToString(make([]byte, 32))
+	if _, err := store.Events(ctx, file); !errors.Is(err, ErrCorrupt) {
+		t.Fatalf("expected ErrCorrupt, got %v", err)
+	}
+}
+
+func TestStoreReadsOnlyItsOwnObjects(t *testing.T) {
+	store, err := NewStore("file://" + t.TempDir())
+	if err != nil {
+		t.Fatal(err)
+	}
+	if _, err := store.Get(context.Background(), "s3://elsewhere/key"); err == nil {
+		t.Fatal("expected an error for an object outside the archive")
+	}
+}
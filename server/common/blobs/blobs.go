@@ -0,0 +1,135 @@
+// Package blobs reads payloads the ingestion service offloaded to blob
+// storage, checking each against the reference published in its place
+package blobs
+
+import (
+	"bytes"
+	"context"
+	"encoding/hex"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io"
+	"net/url"
+	"os"
+	"strings"
+	"time"
+
+	"github.com/facto-ai/facto/server/common/s3"
+	"golang.org/x/crypto/sha3"
+)
+
+// Key is the key of the object the ingestion service publishes in place of
+// a payload it offloaded to blob storage
+const Key = "$facto_blob"
+
+// ErrMismatch marks blobs that do not match their reference; reading them
+// again will not help
+var ErrMismatch = errors.New("blob does not match its reference")
+
+// Ref points at an offloaded payload. Hash is the SHA3-256 of the blob,
+// which holds the payload's JSON encoding.
+type Ref struct {
+	Hash string `json:"hash"`
+	Size int64  `json:"size"`
+	URI  string `json:"uri"`
+}
+
+// Store reads offloaded payloads from file:// URIs and from S3-compatible
+// stores (s3:// and gs://)
+type Store struct {
+	// clients holds the S3 client of each object URI scheme
+	clients map[string]*s3.Client
+}
+
+// NewStore reads the same BLOB_S3_* and AWS_* variables as the ingestion
+// service
+func NewStore() *Store {
+	region := os.Getenv("BLOB_S3_REGION")
+	if region == "" {
+		region = os.Getenv("AWS_REGION")
+	}
+	endpoint := os.Getenv("BLOB_S3_ENDPOINT")
+	clients := make(map[string]*s3.Client)
+	for _, scheme := range []string{"s3", "gs"} {
+		clients[scheme] = s3.NewClient(scheme, endpoint, region, 30*time.Second)
+	}
+	return &Store{clients: clients}
+}
+
+// RefOf returns the reference a payload holds, if it was offloaded
+func RefOf(payload interface{}) (*Ref, bool) {
+	object, ok := payload.(map[string]interface{})
+	if !ok || len(object) != 1 {
+		return nil, false
+	}
+	raw, ok := object[Key]
+	if !ok {
+		return nil, false
+	}
+	encoded, err := json.Marshal(raw)
+	if err != nil {
+		return nil, false
+	}
+	var ref Ref
+	if err := json.Unmarshal(encoded, &ref); err != nil || ref.Hash == "" || ref.URI == "" {
+		return nil, false
+	}
+	return &ref, true
+}
+
+// Resolve returns the payload a reference points at, decoded with numbers
+// kept as json.Number, or the payload itself if it was not offloaded
+func (s *Store) Resolve(ctx context.Context, payload interface{}) (interface{}, error) {
+	ref, ok := RefOf(payload)
+	if !ok {
+		return payload, nil
+	}
+	data, err := s.Fetch(ctx, ref)
+	if err != nil {
+		return nil, err
+	}
+	var value interface{}
+	decoder := json.NewDecoder(bytes.NewReader(data))
+	decoder.UseNumber()
+	if err := decoder.Decode(&value); err != nil {
+		return nil, fmt.Errorf("%w: blob %s is not JSON: %v", ErrMismatch, ref.Hash, err)
+	}
+	return value, nil
+}
+
+// Fetch reads a blob and checks its size and hash against the reference
+func (s *Store) Fetch(ctx context.Context, ref *Ref) ([]byte, error) {
+	u, err := url.Parse(ref.URI)
+	if err != nil {
+		return nil, fmt.Errorf("invalid blob uri %q: %w", ref.URI, err)
+	}
+
+	var body io.ReadCloser
+	switch u.Scheme {
+	case "file":
+		body, err = os.Open(u.Path)
+	case "s3", "gs":
+		body, err = s.clients[u.Scheme].Get(ctx, u.Host, strings.TrimPrefix(u.Path, "/"))
+	default:
+		err = fmt.Errorf("unsupported blob uri scheme %q", u.Scheme)
+	}
+	if err != nil {
+		return nil, err
+	}
+	defer body.Close()
+
+	// Read one byte past the expected size to notice longer blobs
+	data, err := io.ReadAll(io.LimitReader(body, ref.Size+1))
+	if err != nil {
+		return nil, fmt.Errorf("failed to read blob %s: %w", ref.Hash, err)
+	}
+	if int64(len(data)) != ref.Size {
+		return nil, fmt.Errorf("%w: blob %s is not %d bytes", ErrMismatch, ref.Hash, ref.Size)
+	}
+	sum := sha3.Sum256(data)
+	if hex.EncodeToString(sum[:]) != ref.Hash {
+		return nil, fmt.Errorf("%w: blob %s hash differs", ErrMismatch, ref.Hash)
+	}
+	return data, nil
+}
@@ -0,0 +1,69 @@
+package blobs
+
+import (
+	"context"
+	"encoding/hex"
+	"encoding/json"
+	"errors"
+	"os"
+	"path/filepath"
+	"testing"
+
+	"golang.org/x/crypto/sha3"
+)
+
+// writeBlob stores data as a file blob and returns the payload referencing it
+func writeBlob(t *testing.T, data []byte) map[string]interface{} {
+	t.Helper()
+	path := filepath.Join(t.TempDir(), "blob")
+	if err := os.WriteFile(path, data, 0o600); err != nil {
+		t.Fatal(err)
+	}
+	sum := sha3.Sum256(data)
+	return map[string]interface{}{Key: map[string]interface{}{
+		"hash": hex.EncodeToString(sum[:]),
+		"size": len(data),
+		"uri":  "file://" + path,
+	}}
+}
+
+func TestResolveOffloadedPayload(t *testing.T) {
+	payload := writeBlob(t, []byte(`{"tokens":12345678901234567890}`))
+
+	value, err := NewStore().Resolve(context.Background(), payload)
+	if err != nil {
+		t.Fatal(err)
+	}
+	object, ok := value.(map[string]interface{})
+	if !ok || object["tokens"] != json.Number("12345678901234567890") {
+		t.Fatalf("unexpected payload %#v", value)
+	}
+}
+
+func TestResolveLeavesInlinePayloads(t *testing.T) {
+	payload := map[string]interface{}{"prompt": "hello"}
+
+	value, err := NewStore().Resolve(context.Background(), payload)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if value.(map[string]interface{})["prompt"] != "hello" {
+		t.Fatalf("unexpected payload %#v", value)
+	}
+}
+
+func TestFetchRejectsChangedBlob(t *testing.T) {
+	payload := writeBlob(t, []byte(`{"a":1}`))
+	ref, ok := RefOf(payload)
+	if !ok {
+		t.Fatal("expected a blob reference")
+	}
+	path := ref.URI[len("file://"):]
+	if err := os.WriteFile(path, []byte(`{"a":2}`), 0o600); err != nil {
+		t.Fatal(err)
+	}
+
+	if _, err := NewStore().Fetch(context.Background(), ref); !errors.Is(err, ErrMismatch) {
+		t.Fatalf("expected ErrMismatch, got %v", err)
+	}
+}
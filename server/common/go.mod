@@ -0,0 +1,14 @@
+module github.com/facto-ai/facto/server/common
+
+go 1.21
+
+require (
+	github.com/decred/dcrd/dcrec/secp256k1/v4 v4.2.0
+	github.com/parquet-go/parquet-go v0.23.0
+	golang.org/x/crypto v0.17.0
+)
+
+require (
+	github.com/decred/dcrd/crypto/blake256 v1.0.1 // indirect
+	golang.org/x/sys v0.15.0 // indirect
+)
@@ -0,0 +1,221 @@
+// Package jcs writes the RFC 8785 JSON Canonicalization Scheme that event
+// proofs of canonical version 2 cover, for every service that verifies them.
+package jcs
+
+import (
+	"bytes"
+	"encoding/json"
+	"fmt"
+	"math"
+	"sort"
+	"strconv"
+	"strings"
+	"unicode/utf16"
+	"unicode/utf8"
+)
+
+// Marshal returns the canonical form of value
+func Marshal(value interface{}) ([]byte, error) {
+	var buf bytes.Buffer
+	if err := Write(&buf, value); err != nil {
+		return nil, err
+	}
+	return buf.Bytes(), nil
+}
+
+// Write writes value in canonical form: object keys sorted by UTF-16 code
+// units, every number written as the ECMAScript string of the nearest
+// double, and minimal string escaping. Numbers may be decoded either as
+// float64 or, with UseNumber, as json.Number.
+func Write(buf *bytes.Buffer, value interface{}) error {
+	switch v := value.(type) {
+	case nil:
+		buf.WriteString("null")
+	case bool:
+		buf.WriteString(strconv.FormatBool(v))
+	case string:
+		WriteString(buf, v)
+	case *string:
+		if v == nil {
+			buf.WriteString("null")
+		} else {
+			WriteString(buf, *v)
+		}
+	case int:
+		return writeNumber(buf, float64(v))
+	case *int32:
+		if v == nil {
+			buf.WriteString("null")
+			return nil
+		}
+		return writeNumber(buf, float64(*v))
+	case float64:
+		return writeNumber(buf, v)
+	case *float64:
+		if v == nil {
+			buf.WriteString("null")
+			return nil
+		}
+		return writeNumber(buf, *v)
+	case json.Number:
+		f, err := strconv.ParseFloat(v.String(), 64)
+		if err != nil {
+			return fmt.Errorf("invalid number %s in canonical form", v)
+		}
+		return writeNumber(buf, f)
+	case *json.Number:
+		if v == nil {
+			buf.WriteString("null")
+			return nil
+		}
+		return Write(buf, *v)
+	case []interface{}:
+		buf.WriteByte('[')
+		for i, item := range v {
+			if i > 0 {
+				buf.WriteByte(',')
+			}
+			if err := Write(buf, item); err != nil {
+				return err
+			}
+		}
+		buf.WriteByte(']')
+	case map[string]string:
+		members := make(map[string]interface{}, len(v))
+		for k, s := range v {
+			members[k] = s
+		}
+		return Write(buf, members)
+	case map[string]interface{}:
+		keys := make([]string, 0, len(v))
+		for k := range v {
+			keys = append(keys, k)
+		}
+		sort.Slice(keys, func(i, j int) bool {
+			return lessUTF16(keys[i], keys[j])
+		})
+		buf.WriteByte('{')
+		for i, k := range keys {
+			if i > 0 {
+				buf.WriteByte(',')
+			}
+			WriteString(buf, k)
+			buf.WriteByte(':')
+			if err := Write(buf, v[k]); err != nil {
+				return err
+			}
+		}
+		buf.WriteByte('}')
+	default:
+		return fmt.Errorf("unexpected value of type %T in canonical form", value)
+	}
+	return nil
+}
+
+// lessUTF16 orders strings by their UTF-16 code units
+func lessUTF16(a, b string) bool {
+	ua, ub := utf16.Encode([]rune(a)), utf16.Encode([]rune(b))
+	for i := 0; i < len(ua) && i < len(ub); i++ {
+		if ua[i] != ub[i] {
+			return ua[i] < ub[i]
+		}
+	}
+	return len(ua) < len(ub)
+}
+
+// writeNumber writes a double the way ECMAScript's Number.prototype.toString does
+func writeNumber(buf *bytes.Buffer, f float64) error {
+	if math.IsNaN(f) || math.IsInf(f, 0) {
+		return fmt.Errorf("non-finite number in canonical form")
+	}
+	if f == 0 {
+		buf.WriteByte('0')
+		return nil
+	}
+	if f < 0 {
+		buf.WriteByte('-')
+		f = -f
+	}
+
+	// Shortest digits that round-trip, as d.ddde±xx
+	scientific := strconv.FormatFloat(f, 'e', -1, 64)
+	mantissa, exponent, _ := strings.Cut(scientific, "e")
+	digits := strings.Replace(mantissa, ".", "", 1)
+	exp, _ := strconv.Atoi(exponent)
+	k, n := len(digits), exp+1
+
+	switch {
+	case k <= n && n <= 21:
+		buf.WriteString(digits)
+		buf.WriteString(strings.Repeat("0", n-k))
+	case 0 < n && n <= 21:
+		buf.WriteString(digits[:n])
+		buf.WriteByte('.')
+		buf.WriteString(digits[n:])
+	case -6 < n && n <= 0:
+		buf.WriteString("0.")
+		buf.WriteString(strings.Repeat("0", -n))
+		buf.WriteString(digits)
+	default:
+		buf.WriteString(digits[:1])
+		if k > 1 {
+			buf.WriteByte('.')
+			buf.WriteString(digits[1:])
+		}
+		sign := "+"
+		if n <= 0 {
+			sign = "-"
+		}
+		buf.WriteString("e" + sign + strconv.Itoa(abs(n-1)))
+	}
+	return nil
+}
+
+func abs(n int) int {
+	if n < 0 {
+		return -n
+	}
+	return n
+}
+
+// WriteString writes a JSON string escaping only quotes, backslashes and
+// control characters. Canonical version 1 escapes strings the same way.
+func WriteString(buf *bytes.Buffer, s string) {
+	const hexDigits = "0123456789abcdef"
+	buf.WriteByte('"')
+	for i := 0; i < len(s); {
+		c := s[i]
+		if c >= utf8.RuneSelf {
+			r, size := utf8.DecodeRuneInString(s[i:])
+			buf.WriteRune(r)
+			i += size
+			continue
+		}
+		switch c {
+		case '"':
+			buf.WriteString(`\"`)
+		case '\\':
+			buf.WriteString(`\\`)
+		case '\b':
+			buf.WriteString(`\b`)
+		case '\f':
+			buf.WriteString(`\f`)
+		case '\n':
+			buf.WriteString(`\n`)
+		case '\r':
+			buf.WriteString(`\r`)
+		case '\t':
+			buf.WriteString(`\t`)
+		default:
+			if c < 0x20 {
+				buf.WriteString(`\u00`)
+				buf.WriteByte(hexDigits[c>>4])
+				buf.WriteByte(hexDigits[c&0xf])
+			} else {
+				buf.WriteByte(c)
+			}
+		}
+		i++
+	}
+	buf.WriteByte('"')
+}
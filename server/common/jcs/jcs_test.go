@@ -0,0 +1,55 @@
+package jcs
+
+import (
+	"encoding/json"
+	"os"
+	"strings"
+	"testing"
+)
+
+// vector is a case of tests/vectors/jcs.json, which the ingestion service
+// is tested against as well
+type vector struct {
+	Name      string `json:"name"`
+	Input     string `json:"input"`
+	Canonical string `json:"canonical"`
+}
+
+func loadVectors(t *testing.T) []vector {
+	data, err := os.ReadFile("../../../tests/vectors/jcs.json")
+	if err != nil {
+		t.Fatal(err)
+	}
+	var vectors []vector
+	if err := json.Unmarshal(data, &vectors); err != nil {
+		t.Fatal(err)
+	}
+	return vectors
+}
+
+// The API decodes events with float64 numbers and the indexer with
+// json.Number; both must give the same canonical form
+func TestVectors(t *testing.T) {
+	for _, v := range loadVectors(t) {
+		var floats interface{}
+		if err := json.Unmarshal([]byte(v.Input), &floats); err != nil {
+			t.Fatalf("%s: %v", v.Name, err)
+		}
+		var numbers interface{}
+		decoder := json.NewDecoder(strings.NewReader(v.Input))
+		decoder.UseNumber()
+		if err := decoder.Decode(&numbers); err != nil {
+			t.Fatalf("%s: %v", v.Name, err)
+		}
+
+		for _, value := range []interface{}{floats, numbers} {
+			got, err := Marshal(value)
+			if err != nil {
+				t.Fatalf("%s: %v", v.Name, err)
+			}
+			if string(got) != v.Canonical {
+				t.Errorf("%s: got %s, want %s", v.Name, got, v.Canonical)
+			}
+		}
+	}
+}
@@ -0,0 +1,248 @@
+// Package s3 makes path-style requests to S3-compatible stores, signed with
+// Signature Version 4
+package s3
+
+import (
+	"bytes"
+	"context"
+	"crypto/hmac"
+	"crypto/sha256"
+	"encoding/hex"
+	"encoding/xml"
+	"errors"
+	"fmt"
+	"io"
+	"net/http"
+	"net/url"
+	"os"
+	"sort"
+	"strings"
+	"time"
+)
+
+// ErrNotFound is returned for objects the store does not hold
+var ErrNotFound = errors.New("object not found")
+
+// emptyPayloadHash is the SHA-256 of an empty request body
+const emptyPayloadHash = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+
+// Client talks to one S3-compatible endpoint with the AWS_* credentials
+type Client struct {
+	client          *http.Client
+	endpoint        string
+	region          string
+	accessKeyID     string
+	secretAccessKey string
+	sessionToken    string
+}
+
+// NewClient creates a client for s3:// or gs:// objects. An empty region
+// or endpoint defaults to the scheme's: AWS in us-east-1, or Google Cloud
+// Storage's interoperability endpoint.
+func NewClient(scheme, endpoint, region string, timeout time.Duration) *Client {
+	if region == "" {
+		region = "us-east-1"
+		if scheme == "gs" {
+			region = "auto"
+		}
+	}
+	if endpoint == "" {
+		endpoint = "https://s3." + region + ".amazonaws.com"
+		if scheme == "gs" {
+			endpoint = "https://storage.googleapis.com"
+		}
+	}
+	return &Client{
+		client:          &http.Client{Timeout: timeout},
+		endpoint:        endpoint,
+		region:          region,
+		accessKeyID:     os.Getenv("AWS_ACCESS_KEY_ID"),
+		secretAccessKey: os.Getenv("AWS_SECRET_ACCESS_KEY"),
+		sessionToken:    os.Getenv("AWS_SESSION_TOKEN"),
+	}
+}
+
+// Get fetches an object. The caller closes the body.
+func (c *Client) Get(ctx context.Context, bucket, key string) (io.ReadCloser, error) {
+	resp, err := c.do(ctx, http.MethodGet, bucket, key, nil, nil, "")
+	if err != nil {
+		return nil, err
+	}
+	return resp.Body, nil
+}
+
+// Put writes an object, replacing any object of the same key
+func (c *Client) Put(ctx context.Context, bucket, key string, data []byte, contentType string) error {
+	resp, err := c.do(ctx, http.MethodPut, bucket, key, nil, data, contentType)
+	if err != nil {
+		return err
+	}
+	defer resp.Body.Close()
+	io.Copy(io.Discard, resp.Body)
+	return nil
+}
+
+// listResult is the part of a ListObjectsV2 response that is read
+type listResult struct {
+	Contents []struct {
+		Key string `xml:"Key"`
+	} `xml:"Contents"`
+	IsTruncated           bool   `xml:"IsTruncated"`
+	NextContinuationToken string `xml:"NextContinuationToken"`
+}
+
+// List returns the keys of the bucket's objects under prefix, in key order
+func (c *Client) List(ctx context.Context, bucket, prefix string) ([]string, error) {
+	var keys []string
+	query := map[string]string{"list-type": "2", "prefix": prefix}
+	for {
+		resp, err := c.do(ctx, http.MethodGet, bucket, "", query, nil, "")
+		if err != nil {
+			return nil, err
+		}
+		var result listResult
+		err = xml.NewDecoder(resp.Body).Decode(&result)
+		resp.Body.Close()
+		if err != nil {
+			return nil, fmt.Errorf("malformed list response for %s: %w", bucket, err)
+		}
+		for _, object := range result.Contents {
+			keys = append(keys, object.Key)
+		}
+		if !result.IsTruncated || result.NextContinuationToken == "" {
+			return keys, nil
+		}
+		query["continuation-token"] = result.NextContinuationToken
+	}
+}
+
+// do sends a signed request and returns the response of a 200, whose body
+// the caller closes
+func (c *Client) do(ctx context.Context, method, bucket, key string, query map[string]string, body []byte, contentType string) (*http.Response, error) {
+	path := "/" + uriEncode(bucket)
+	if key != "" {
+		segments := strings.Split(key, "/")
+		for i, segment := range segments {
+			segments[i] = uriEncode(segment)
+		}
+		path += "/" + strings.Join(segments, "/")
+	}
+	names := make([]string, 0, len(query))
+	for name := range query {
+		names = append(names, name)
+	}
+	sort.Strings(names)
+	params := make([]string, len(names))
+	for i, name := range names {
+		params[i] = uriEncode(name) + "=" + uriEncode(query[name])
+	}
+	rawQuery := strings.Join(params, "&")
+
+	base, err := url.Parse(c.endpoint)
+	if err != nil {
+		return nil, fmt.Errorf("invalid S3 endpoint %q: %w", c.endpoint, err)
+	}
+	target := base.Scheme + "://" + base.Host + path
+	if rawQuery != "" {
+		target += "?" + rawQuery
+	}
+	var reader io.Reader
+	if body != nil {
+		reader = bytes.NewReader(body)
+	}
+	req, err := http.NewRequestWithContext(ctx, method, target, reader)
+	if err != nil {
+		return nil, err
+	}
+	req.ContentLength = int64(len(body))
+
+	payloadHash := emptyPayloadHash
+	if body != nil {
+		sum := sha256.Sum256(body)
+		payloadHash = hex.EncodeToString(sum[:])
+	}
+	amzDate := time.Now().UTC().Format("20060102T150405Z")
+	var headers [][2]string
+	if contentType != "" {
+		headers = append(headers, [2]string{"content-type", contentType})
+	}
+	headers = append(headers,
+		[2]string{"host", base.Host},
+		[2]string{"x-amz-content-sha256", payloadHash},
+		[2]string{"x-amz-date", amzDate},
+	)
+	if c.sessionToken != "" {
+		headers = append(headers, [2]string{"x-amz-security-token", c.sessionToken})
+	}
+	for _, header := range headers {
+		if header[0] != "host" {
+			req.Header.Set(header[0], header[1])
+		}
+	}
+	req.Header.Set("Authorization", c.signV4(method, path, rawQuery, headers, payloadHash, amzDate))
+
+	resp, err := c.client.Do(req)
+	if err != nil {
+		return nil, fmt.Errorf("S3 request failed: %w", err)
+	}
+	if resp.StatusCode != http.StatusOK {
+		resp.Body.Close()
+		if resp.StatusCode == http.StatusNotFound {
+			return nil, fmt.Errorf("%w: %s/%s", ErrNotFound, bucket, key)
+		}
+		return nil, fmt.Errorf("S3 returned %s for %s/%s", resp.Status, bucket, key)
+	}
+	return resp, nil
+}
+
+// signV4 builds the Authorization header of a request; headers must have
+// lowercase names in sorted order and the query must be canonical
+func (c *Client) signV4(method, path, query string, headers [][2]string, payloadHash, amzDate string) string {
+	date := amzDate[:8]
+	scope := date + "/" + c.region + "/s3/aws4_request"
+
+	names := make([]string, len(headers))
+	var canonicalHeaders strings.Builder
+	for i, header := range headers {
+		names[i] = header[0]
+		canonicalHeaders.WriteString(header[0] + ":" + strings.TrimSpace(header[1]) + "\n")
+	}
+	signedHeaders := strings.Join(names, ";")
+
+	canonicalRequest := strings.Join([]string{
+		method, path, query, canonicalHeaders.String(), signedHeaders, payloadHash,
+	}, "\n")
+	requestHash := sha256.Sum256([]byte(canonicalRequest))
+	stringToSign := "AWS4-HMAC-SHA256\n" + amzDate + "\n" + scope + "\n" + hex.EncodeToString(requestHash[:])
+
+	key := hmacSHA256([]byte("AWS4"+c.secretAccessKey), date)
+	key = hmacSHA256(key, c.region)
+	key = hmacSHA256(key, "s3")
+	key = hmacSHA256(key, "aws4_request")
+	signature := hex.EncodeToString(hmacSHA256(key, stringToSign))
+
+	return "AWS4-HMAC-SHA256 Credential=" + c.accessKeyID + "/" + scope +
+		", SignedHeaders=" + signedHeaders + ", Signature=" + signature
+}
+
+func hmacSHA256(key []byte, data string) []byte {
+	mac := hmac.New(sha256.New, key)
+	mac.Write([]byte(data))
+	return mac.Sum(nil)
+}
+
+// uriEncode percent-encodes a path segment or query component as Signature
+// Version 4 requires
+func uriEncode(segment string) string {
+	var encoded strings.Builder
+	for i := 0; i < len(segment); i++ {
+		c := segment[i]
+		if 'A' <= c && c <= 'Z' || 'a' <= c && c <= 'z' || '0' <= c && c <= '9' ||
+			c == '-' || c == '.' || c == '_' || c == '~' {
+			encoded.WriteByte(c)
+		} else {
+			fmt.Fprintf(&encoded, "%%%02X", c)
+		}
+	}
+	return encoded.String()
+}
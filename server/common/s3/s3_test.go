@@ -0,0 +1,70 @@
+package s3
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"net/http"
+	"net/http/httptest"
+	"strings"
+	"testing"
+	"time"
+)
+
+func TestListFollowsContinuationTokens(t *testing.T) {
+	server := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if !strings.HasPrefix(r.Header.Get("Authorization"), "AWS4-HMAC-SHA256 Credential=") {
+			t.Errorf("unsigned request %s", r.URL)
+		}
+		if r.URL.Path != "/bucket" || r.URL.Query().Get("prefix") != "a b/" {
+			t.Errorf("unexpected request %s", r.URL)
+		}
+		switch r.URL.Query().Get("continuation-token") {
+		case "":
+			fmt.Fprint(w, `<ListBucketResult><Contents><Key>a b/1</Key></Contents>`+
+				`<IsTruncated>true</IsTruncated><NextContinuationToken>next+1</NextContinuationToken></ListBucketResult>`)
+		case "next+1":
+			fmt.Fprint(w, `<ListBucketResult><Contents><Key>a b/2</Key></Contents>`+
+				`<IsTruncated>false</IsTruncated></ListBucketResult>`)
+		default:
+			t.Errorf("unexpected token in %s", r.URL)
+		}
+	}))
+	defer server.Close()
+
+	client := NewClient("s3", server.URL, "", 5*time.Second)
+	keys, err := client.List(context.Background(), "bucket", "a b/")
+	if err != nil {
+		t.Fatal(err)
+	}
+	if len(keys) != 2 || keys[0] != "a b/1" || keys[1] != "a b/2" {
+		t.Fatalf("unexpected keys %v", keys)
+	}
+}
+
+func TestGetReportsMissingObjects(t *testing.T) {
+	server := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if r.URL.EscapedPath() == "/bucket/dir/a%20b" {
+			io.WriteString(w, "data")
+			return
+		}
+		http.NotFound(w, r)
+	}))
+	defer server.Close()
+
+	client := NewClient("s3", server.URL, "", 5*time.Second)
+	body, err := client.Get(context.Background(), "bucket", "dir/a b")
+	if err != nil {
+		t.Fatal(err)
+	}
+	data, _ := io.ReadAll(body)
+	body.Close()
+	if string(data) != "data" {
+		t.Fatalf("unexpected body %q", data)
+	}
+
+	if _, err := client.Get(context.Background(), "bucket", "missing"); !errors.Is(err, ErrNotFound) {
+		t.Fatalf("expected ErrNotFound, got %v", err)
+	}
+}
@@ -0,0 +1,74 @@
+package signature
+
+import (
+	"encoding/hex"
+	"errors"
+
+	"golang.org/x/crypto/sha3"
+)
+
+// BatchEnvelopeTrust is the trust basis type of an event whose signature a
+// batch envelope stood in for
+const BatchEnvelopeTrust = "batch_envelope"
+
+var (
+	ErrNotEnveloped = errors.New("event is not covered by the batch envelope")
+	ErrEnvelopeKey  = errors.New("batch envelope is signed with a different key")
+	ErrBatchHash    = errors.New("batch envelope hash does not cover its event hashes")
+)
+
+// TrustBasis is the trust basis of the verification assertion in a server
+// envelope. Envelope and EventHashes are set for BatchEnvelopeTrust.
+type TrustBasis struct {
+	Type        string         `json:"type"`
+	Envelope    *BatchEnvelope `json:"envelope"`
+	EventHashes []string       `json:"event_hashes"`
+}
+
+// Enveloped reports whether a batch envelope stood in for the signature
+func (b *TrustBasis) Enveloped() bool {
+	return b != nil && b.Type == BatchEnvelopeTrust
+}
+
+// BatchEnvelope is a batch signed once, as the ingestion service publishes
+// it with each event it stood in for the signature of
+type BatchEnvelope struct {
+	// Hex SHA3-256 of the events' proof.event_hash values concatenated in
+	// batch order
+	BatchHash string `json:"batch_hash"`
+	PublicKey string `json:"public_key"`
+	// Signature over the UTF-8 bytes of BatchHash
+	Signature string `json:"signature"`
+	Algorithm string `json:"algorithm,omitempty"`
+}
+
+// VerifyEnvelope checks that a batch envelope vouches for an event: eventHash
+// is one of eventHashes, they hash to the envelope's batch hash, and the
+// envelope is signed with publicKey. The event hash itself still has to be
+// checked against the event's canonical form.
+func VerifyEnvelope(envelope BatchEnvelope, eventHashes []string, eventHash, publicKey string) error {
+	covered := false
+	hasher := sha3.New256()
+	for _, hash := range eventHashes {
+		covered = covered || hash == eventHash
+		hasher.Write([]byte(hash))
+	}
+	if !covered {
+		return ErrNotEnveloped
+	}
+	if envelope.PublicKey != publicKey {
+		return ErrEnvelopeKey
+	}
+	if hex.EncodeToString(hasher.Sum(nil)) != envelope.BatchHash {
+		return ErrBatchHash
+	}
+	return Verify(envelope.Algorithm, envelope.PublicKey, envelope.Signature, []byte(envelope.BatchHash))
+}
+
+// VerifyEnvelope checks an enveloped trust basis with VerifyEnvelope
+func (b *TrustBasis) VerifyEnvelope(eventHash, publicKey string) error {
+	if !b.Enveloped() || b.Envelope == nil {
+		return ErrNotEnveloped
+	}
+	return VerifyEnvelope(*b.Envelope, b.EventHashes, eventHash, publicKey)
+}
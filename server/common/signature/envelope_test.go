@@ -0,0 +1,43 @@
+package signature
+
+import (
+	"crypto/ed25519"
+	"encoding/hex"
+	"errors"
+	"testing"
+
+	"golang.org/x/crypto/sha3"
+)
+
+func TestVerifyEnvelope(t *testing.T) {
+	pub, priv, err := ed25519.GenerateKey(nil)
+	if err != nil {
+		t.Fatal(err)
+	}
+	hashes := []string{"aa", "bb"}
+	sum := sha3.Sum256([]byte("aabb"))
+	batchHash := hex.EncodeToString(sum[:])
+	envelope := BatchEnvelope{
+		BatchHash: batchHash,
+		PublicKey: encode(pub),
+		Signature: encode(ed25519.Sign(priv, []byte(batchHash))),
+	}
+
+	if err := VerifyEnvelope(envelope, hashes, "bb", encode(pub)); err != nil {
+		t.Fatalf("member: %v", err)
+	}
+	if err := VerifyEnvelope(envelope, hashes, "cc", encode(pub)); !errors.Is(err, ErrNotEnveloped) {
+		t.Fatalf("non-member: got %v", err)
+	}
+	if err := VerifyEnvelope(envelope, hashes[1:], "bb", encode(pub)); !errors.Is(err, ErrBatchHash) {
+		t.Fatalf("dropped member: got %v", err)
+	}
+	other, _, _ := ed25519.GenerateKey(nil)
+	if err := VerifyEnvelope(envelope, hashes, "bb", encode(other)); !errors.Is(err, ErrEnvelopeKey) {
+		t.Fatalf("other key: got %v", err)
+	}
+	envelope.Signature = encode(make([]byte, ed25519.SignatureSize))
+	if err := VerifyEnvelope(envelope, hashes, "bb", encode(pub)); !errors.Is(err, ErrMismatch) {
+		t.Fatalf("forged signature: got %v", err)
+	}
+}
@@ -0,0 +1,123 @@
+// Package signature verifies event proof signatures with the algorithm the
+// proof names
+package signature
+
+import (
+	"crypto/ecdsa"
+	"crypto/ed25519"
+	"crypto/elliptic"
+	"crypto/sha256"
+	"encoding/base64"
+	"errors"
+	"fmt"
+	"math/big"
+
+	"github.com/decred/dcrd/dcrec/secp256k1/v4"
+	secpecdsa "github.com/decred/dcrd/dcrec/secp256k1/v4/ecdsa"
+)
+
+// Signature algorithms an event proof may name. Proofs without one are Ed25519.
+const (
+	Ed25519   = "ed25519"
+	ES256     = "es256"
+	Secp256k1 = "secp256k1"
+)
+
+var (
+	ErrInvalidPublicKey = errors.New("invalid public key")
+	ErrInvalidSignature = errors.New("invalid signature encoding")
+	ErrMismatch         = errors.New("signature verification failed")
+)
+
+// Verify checks a base64 signature over message against a base64 public key
+// with algorithm, Ed25519 when it is empty. ES256 and secp256k1 keys are
+// SEC1 encoded and signatures are a 64-byte r || s over the SHA-256 digest
+// of message.
+func Verify(algorithm, publicKey, signature string, message []byte) error {
+	key, err := base64.StdEncoding.DecodeString(publicKey)
+	if err != nil {
+		return ErrInvalidPublicKey
+	}
+	sig, err := base64.StdEncoding.DecodeString(signature)
+	if err != nil {
+		return ErrInvalidSignature
+	}
+
+	switch algorithm {
+	case "", Ed25519:
+		return verifyEd25519(key, sig, message)
+	case ES256:
+		return verifyES256(key, sig, message)
+	case Secp256k1:
+		return verifySecp256k1(key, sig, message)
+	default:
+		return fmt.Errorf("unsupported signature algorithm %q", algorithm)
+	}
+}
+
+func verifyEd25519(key, sig, message []byte) error {
+	if len(key) != ed25519.PublicKeySize {
+		return ErrInvalidPublicKey
+	}
+	if len(sig) != ed25519.SignatureSize {
+		return ErrInvalidSignature
+	}
+	if !ed25519.Verify(key, message, sig) {
+		return ErrMismatch
+	}
+	return nil
+}
+
+func verifyES256(key, sig, message []byte) error {
+	curve := elliptic.P256()
+
+	var x, y *big.Int
+	if len(key) == 33 {
+		x, y = elliptic.UnmarshalCompressed(curve, key)
+	} else {
+		x, y = elliptic.Unmarshal(curve, key)
+	}
+	if x == nil {
+		return ErrInvalidPublicKey
+	}
+	if len(sig) != 64 {
+		return ErrInvalidSignature
+	}
+
+	r, s := new(big.Int).SetBytes(sig[:32]), new(big.Int).SetBytes(sig[32:])
+	digest := sha256.Sum256(message)
+	if !ecdsa.Verify(&ecdsa.PublicKey{Curve: curve, X: x, Y: y}, digest[:], r, s) {
+		return ErrMismatch
+	}
+	return nil
+}
+
+// verifySecp256k1 checks an ECDSA signature over secp256k1. Like the
+// ingestion service, it only accepts compressed or uncompressed keys and
+// signatures normalized to low S.
+func verifySecp256k1(key, sig, message []byte) error {
+	if len(key) == 0 || (key[0] != 0x02 && key[0] != 0x03 && key[0] != 0x04) {
+		return ErrInvalidPublicKey
+	}
+	pubKey, err := secp256k1.ParsePubKey(key)
+	if err != nil {
+		return ErrInvalidPublicKey
+	}
+	if len(sig) != 64 {
+		return ErrInvalidSignature
+	}
+
+	var r, s secp256k1.ModNScalar
+	if r.SetByteSlice(sig[:32]) || s.SetByteSlice(sig[32:]) {
+		return ErrInvalidSignature
+	}
+	if r.IsZero() || s.IsZero() || s.IsOverHalfOrder() {
+		return ErrInvalidSignature
+	}
+
+	digest := sha256.Sum256(message)
+	if !secpecdsa.NewSignature(&r, &s).Verify(digest[:], pubKey) {
+		return ErrMismatch
+	}
+	return nil
+}
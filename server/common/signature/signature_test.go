@@ -0,0 +1,69 @@
+package signature
+
+import (
+	"crypto/ed25519"
+	"crypto/sha256"
+	"encoding/base64"
+	"errors"
+	"testing"
+
+	"github.com/decred/dcrd/dcrec/secp256k1/v4"
+	secpecdsa "github.com/decred/dcrd/dcrec/secp256k1/v4/ecdsa"
+)
+
+func encode(b []byte) string {
+	return base64.StdEncoding.EncodeToString(b)
+}
+
+func TestVerifyEd25519(t *testing.T) {
+	pub, priv, err := ed25519.GenerateKey(nil)
+	if err != nil {
+		t.Fatal(err)
+	}
+	message := []byte(`{"action":"test"}`)
+	sig := ed25519.Sign(priv, message)
+
+	if err := Verify("", encode(pub), encode(sig), message); err != nil {
+		t.Fatalf("unnamed algorithm: %v", err)
+	}
+	if err := Verify(Ed25519, encode(pub), encode(sig), []byte("tampered")); !errors.Is(err, ErrMismatch) {
+		t.Fatalf("tampered message: got %v", err)
+	}
+}
+
+func TestVerifySecp256k1(t *testing.T) {
+	priv, err := secp256k1.GeneratePrivateKey()
+	if err != nil {
+		t.Fatal(err)
+	}
+	message := []byte(`{"action":"test"}`)
+	digest := sha256.Sum256(message)
+	// Compact signatures are a recovery byte then r || s, with s low
+	sig := secpecdsa.SignCompact(priv, digest[:], true)[1:]
+
+	for _, key := range [][]byte{priv.PubKey().SerializeCompressed(), priv.PubKey().SerializeUncompressed()} {
+		if err := Verify(Secp256k1, encode(key), encode(sig), message); err != nil {
+			t.Fatalf("key of %d bytes: %v", len(key), err)
+		}
+	}
+
+	key := encode(priv.PubKey().SerializeCompressed())
+	if err := Verify(Secp256k1, key, encode(sig), []byte("tampered")); !errors.Is(err, ErrMismatch) {
+		t.Fatalf("tampered message: got %v", err)
+	}
+
+	// The high-S twin of a valid signature verifies mathematically but is rejected
+	var s secp256k1.ModNScalar
+	s.SetByteSlice(sig[32:])
+	s.Negate()
+	highS := append(append([]byte{}, sig[:32]...), s.Bytes()[:]...)
+	if err := Verify(Secp256k1, key, encode(highS), message); !errors.Is(err, ErrInvalidSignature) {
+		t.Fatalf("high S: got %v", err)
+	}
+}
+
+func TestVerifyUnsupportedAlgorithm(t *testing.T) {
+	if err := Verify("rsa", "", "", nil); err == nil {
+		t.Fatal("expected an error for an unsupported algorithm")
+	}
+}
@@ -0,0 +1,33 @@
+package main
+
+import (
+	"context"
+
+	"github.com/facto-ai/facto/server/common/blobs"
+)
+
+// BlobStore resolves the payloads the ingestion service offloaded to blob
+// storage, checking each against its reference before it is used
+type BlobStore struct {
+	*blobs.Store
+}
+
+// NewBlobStore reads the same BLOB_S3_* and AWS_* variables as the
+// ingestion service
+func NewBlobStore() *BlobStore {
+	return &BlobStore{blobs.NewStore()}
+}
+
+// ResolvePayloads returns a copy of the event with offloaded payloads put
+// back, for verification. The event itself is indexed with its references.
+func (b *BlobStore) ResolvePayloads(ctx context.Context, event *FactoEvent) (*FactoEvent, error) {
+	resolved := *event
+	for _, payload := range []*interface{}{&resolved.InputData, &resolved.OutputData} {
+		value, err := b.Resolve(ctx, *payload)
+		if err != nil {
+			return nil, err
+		}
+		*payload = value
+	}
+	return &resolved, nil
+}
@@ -0,0 +1,388 @@
+package main
+
+import (
+	"bytes"
+	"context"
+	"encoding/json"
+	"errors"
+	"os"
+	"time"
+
+	"github.com/facto-ai/facto/server/common/blobs"
+	"github.com/facto-ai/facto/server/common/signature"
+	"github.com/nats-io/nats.go"
+	"github.com/nats-io/nats.go/jetstream"
+	"github.com/prometheus/client_golang/prometheus"
+	"github.com/prometheus/client_golang/prometheus/promauto"
+	"github.com/rs/zerolog/log"
+)
+
+// envelopeHeader carries the ingestion server's envelope for each event
+const envelopeHeader = "Facto-Envelope"
+
+var (
+	eventsConsumed = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_indexer_events_consumed_total",
+		Help: "Total number of events consumed from NATS",
+	})
+
+	eventsIndexed = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_indexer_events_indexed_total",
+		Help: "Total number of events written to Postgres",
+	})
+
+	eventsDuplicate = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_indexer_events_duplicate_total",
+		Help: "Total number of redelivered events that were already indexed",
+	})
+
+	eventsRedacted = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_indexer_events_redacted_total",
+		Help: "Total number of redacted events indexed without re-verification",
+	})
+
+	eventsUnverified = promauto.NewCounterVec(prometheus.CounterOpts{
+		Name: "facto_indexer_events_unverified_total",
+		Help: "Total number of events accepted unverified under the audit or off enforcement mode",
+	}, []string{"status"})
+
+	eventsRejected = promauto.NewCounterVec(prometheus.CounterOpts{
+		Name: "facto_indexer_events_rejected_total",
+		Help: "Total number of events terminated without being indexed",
+	}, []string{"reason"})
+
+	batchesFailed = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_indexer_batches_failed_total",
+		Help: "Total number of batches whose transaction failed",
+	})
+
+	commitLatency = promauto.NewHistogram(prometheus.HistogramOpts{
+		Name:    "facto_indexer_commit_latency_seconds",
+		Help:    "Latency of batch transactions in seconds",
+		Buckets: []float64{0.01, 0.05, 0.1, 0.25, 0.5, 1, 2.5, 5},
+	})
+)
+
+// FactoEvent is an event as published on FACTO_EVENTS. Free-form JSON is
+// decoded with json.Number so numbers are re-encoded exactly as received.
+type FactoEvent struct {
+	EventVersion  int           `json:"event_version"`
+	FactoID       string        `json:"facto_id"`
+	AgentID       string        `json:"agent_id"`
+	SessionID     string        `json:"session_id"`
+	ParentFactoID *string       `json:"parent_facto_id"`
+	ActionType    string        `json:"action_type"`
+	Status        string        `json:"status"`
+	InputData     interface{}   `json:"input_data"`
+	OutputData    interface{}   `json:"output_data"`
+	ExecutionMeta ExecutionMeta `json:"execution_meta"`
+	Proof         Proof         `json:"proof"`
+	StartedAt     int64         `json:"started_at"`
+	CompletedAt   int64         `json:"completed_at"`
+}
+
+// ExecutionMeta contains execution metadata
+type ExecutionMeta struct {
+	ModelID     *string           `json:"model_id"`
+	ModelHash   *string           `json:"model_hash"`
+	Temperature *json.Number      `json:"temperature"`
+	Seed        *int64            `json:"seed"`
+	MaxTokens   *int32            `json:"max_tokens"`
+	ToolCalls   []interface{}     `json:"tool_calls"`
+	SDKVersion  string            `json:"sdk_version"`
+	SDKLanguage string            `json:"sdk_language"`
+	Tags        map[string]string `json:"tags"`
+}
+
+// Proof contains cryptographic proof
+type Proof struct {
+	Signature        string  `json:"signature"`
+	PublicKey        string  `json:"public_key"`
+	// Algorithm is nil for Ed25519, or "es256" or "secp256k1"
+	Algorithm        *string `json:"algorithm"`
+	// CanonicalVersion is nil for canonical form version 1
+	CanonicalVersion *int    `json:"canonical_version"`
+	PrevHash         string  `json:"prev_hash"`
+	EventHash        string  `json:"event_hash"`
+}
+
+// Envelope is the part of the server envelope the indexer stores
+type Envelope struct {
+	ReceivedAt   int64   `json:"received_at"`
+	TenantID     *string `json:"tenant_id"`
+	Verification struct {
+		EventHash  string                `json:"event_hash"`
+		// TrustBasis names the batch envelope that stood in for the
+		// event's signature, when one did
+		TrustBasis *signature.TrustBasis `json:"trust_basis"`
+	} `json:"verification"`
+	// VerificationStatus is set when the ingestion service accepted the
+	// event without verifying it, under the audit or off enforcement mode
+	VerificationStatus *struct {
+		Status string `json:"status"`
+		Code   string `json:"code"`
+	} `json:"verification_status"`
+	Redaction *json.RawMessage `json:"redaction"`
+}
+
+// indexedEvent is a verified event ready to be written
+type indexedEvent struct {
+	event    FactoEvent
+	envelope json.RawMessage
+	tenantID string
+}
+
+// Consumer pulls events from FACTO_EVENTS and indexes them in Postgres
+type Consumer struct {
+	nc            *nats.Conn
+	js            jetstream.JetStream
+	storage       *Storage
+	blobs         *BlobStore
+	batchSize     int
+	flushInterval time.Duration
+	events        []indexedEvent
+	messages      []jetstream.Msg
+}
+
+// NewConsumer creates a new NATS consumer
+func NewConsumer(natsURL string, storage *Storage, blobs *BlobStore, batchSize int, flushInterval time.Duration) (*Consumer, error) {
+	nc, err := nats.Connect(natsURL,
+		nats.RetryOnFailedConnect(true),
+		nats.MaxReconnects(-1),
+		nats.ReconnectWait(time.Second),
+		nats.DisconnectErrHandler(func(nc *nats.Conn, err error) {
+			log.Warn().Err(err).Msg("NATS disconnected")
+		}),
+		nats.ReconnectHandler(func(nc *nats.Conn) {
+			log.Info().Msg("NATS reconnected")
+		}),
+	)
+	if err != nil {
+		return nil, err
+	}
+
+	js, err := jetstream.New(nc)
+	if err != nil {
+		nc.Close()
+		return nil, err
+	}
+
+	return &Consumer{
+		nc:            nc,
+		js:            js,
+		storage:       storage,
+		blobs:         blobs,
+		batchSize:     batchSize,
+		flushInterval: flushInterval,
+		events:        make([]indexedEvent, 0, batchSize),
+		messages:      make([]jetstream.Msg, 0, batchSize),
+	}, nil
+}
+
+// Start begins consuming messages. FACTO_EVENTS is a work queue, so each
+// event is delivered to one consumer only: run the indexer in place of the
+// ScyllaDB processor, not next to it.
+func (c *Consumer) Start(ctx context.Context) error {
+	stream, err := c.js.Stream(ctx, "FACTO_EVENTS")
+	if err != nil {
+		stream, err = c.js.CreateStream(ctx, jetstream.StreamConfig{
+			Name:      "FACTO_EVENTS",
+			Subjects:  []string{"facto.events.>", "facto.tenants.>"},
+			Retention: jetstream.WorkQueuePolicy,
+			Storage:   jetstream.FileStorage,
+		})
+		if err != nil {
+			return err
+		}
+	}
+
+	subjects := []string{"facto.events.>", "facto.tenants.>"}
+	if filter := os.Getenv("FILTER_SUBJECT"); filter != "" {
+		subjects = []string{filter}
+		log.Info().Str("filter_subject", filter).Msg("Using filtered subject")
+	}
+
+	durableName := os.Getenv("DURABLE_NAME")
+	if durableName == "" {
+		durableName = "indexer"
+	}
+
+	consumer, err := stream.CreateOrUpdateConsumer(ctx, jetstream.ConsumerConfig{
+		Durable:        durableName,
+		FilterSubjects: subjects,
+		AckPolicy:      jetstream.AckExplicitPolicy,
+		MaxAckPending:  c.batchSize * 2,
+		AckWait:        30 * time.Second,
+	})
+	if err != nil {
+		return err
+	}
+
+	log.Info().Str("durable", durableName).Msg("Started indexing from FACTO_EVENTS stream")
+
+	ticker := time.NewTicker(c.flushInterval)
+	defer ticker.Stop()
+
+	msgChan := make(chan jetstream.Msg, c.batchSize)
+	go func() {
+		for {
+			select {
+			case <-ctx.Done():
+				close(msgChan)
+				return
+			default:
+				msgs, err := consumer.Fetch(c.batchSize, jetstream.FetchMaxWait(c.flushInterval))
+				if err != nil {
+					if err != context.Canceled {
+						log.Debug().Err(err).Msg("Fetch returned")
+					}
+					continue
+				}
+				for msg := range msgs.Messages() {
+					msgChan <- msg
+				}
+			}
+		}
+	}()
+
+	for {
+		select {
+		case <-ctx.Done():
+			// Unflushed messages are redelivered after AckWait
+			return ctx.Err()
+
+		case msg, ok := <-msgChan:
+			if !ok {
+				return nil
+			}
+			c.handleMessage(ctx, msg)
+
+		case <-ticker.C:
+			if len(c.events) > 0 {
+				c.flush(ctx)
+			}
+		}
+	}
+}
+
+// handleMessage re-verifies an event and queues it for the next batch.
+// Events that cannot be read or verified are terminated, never retried.
+func (c *Consumer) handleMessage(ctx context.Context, msg jetstream.Msg) {
+	eventsConsumed.Inc()
+
+	var event FactoEvent
+	decoder := json.NewDecoder(bytes.NewReader(msg.Data()))
+	decoder.UseNumber()
+	if err := decoder.Decode(&event); err != nil {
+		log.Error().Err(err).Msg("Failed to unmarshal event")
+		msg.Term()
+		eventsRejected.WithLabelValues("malformed").Inc()
+		return
+	}
+
+	indexed := indexedEvent{event: event}
+	var envelope *Envelope
+	if raw := msg.Headers().Get(envelopeHeader); raw != "" {
+		var parsed Envelope
+		if err := json.Unmarshal([]byte(raw), &parsed); err != nil {
+			log.Warn().Err(err).Str("facto_id", event.FactoID).Msg("Ignoring malformed envelope")
+		} else {
+			envelope = &parsed
+			indexed.envelope = json.RawMessage(raw)
+			if parsed.TenantID != nil {
+				indexed.tenantID = *parsed.TenantID
+			}
+		}
+	}
+
+	if envelope != nil && envelope.VerificationStatus != nil {
+		// Re-verifying would terminate an event the tenant chose to accept;
+		// it is indexed as published, its status kept in the stored envelope
+		eventsUnverified.WithLabelValues(envelope.VerificationStatus.Status).Inc()
+	} else if envelope != nil && envelope.Redaction != nil {
+		// Redacted payloads no longer match the event hash, so the event is
+		// indexed on the ingestion service's verification of the original
+		if envelope.Verification.EventHash != event.Proof.EventHash {
+			log.Error().Str("facto_id", event.FactoID).Msg("Redacted event does not match its envelope")
+			msg.Term()
+			eventsRejected.WithLabelValues("verification").Inc()
+			return
+		}
+		eventsRedacted.Inc()
+	} else {
+		// Offloaded payloads are verified against their blobs, then indexed
+		// as references
+		resolved, err := c.blobs.ResolvePayloads(ctx, &event)
+		if errors.Is(err, blobs.ErrMismatch) {
+			log.Error().Err(err).Str("facto_id", event.FactoID).Msg("Offloaded payload does not match its reference")
+			msg.Term()
+			eventsRejected.WithLabelValues("blob_mismatch").Inc()
+			return
+		}
+		if err != nil {
+			log.Warn().Err(err).Str("facto_id", event.FactoID).Msg("Failed to fetch offloaded payload, retrying")
+			msg.Nak()
+			return
+		}
+
+		var trust *signature.TrustBasis
+		if envelope != nil {
+			trust = envelope.Verification.TrustBasis
+		}
+		if _, err := verifyEvent(resolved, trust); err != nil {
+			log.Error().Err(err).Str("facto_id", event.FactoID).Msg("Event failed re-verification")
+			msg.Term()
+			eventsRejected.WithLabelValues("verification").Inc()
+			return
+		}
+	}
+
+	c.events = append(c.events, indexed)
+	c.messages = append(c.messages, msg)
+
+	if len(c.events) >= c.batchSize {
+		c.flush(ctx)
+	}
+}
+
+// flush writes the batch in one transaction and acks only after it commits
+func (c *Consumer) flush(ctx context.Context) {
+	if len(c.events) == 0 {
+		return
+	}
+
+	start := time.Now()
+	inserted, err := c.storage.IndexBatch(ctx, c.events)
+	commitLatency.Observe(time.Since(start).Seconds())
+
+	if err != nil {
+		log.Error().Err(err).Int("count", len(c.events)).Msg("Failed to index batch")
+		for _, msg := range c.messages {
+			msg.Nak()
+		}
+		batchesFailed.Inc()
+	} else {
+		for _, msg := range c.messages {
+			msg.Ack()
+		}
+		eventsIndexed.Add(float64(inserted))
+		eventsDuplicate.Add(float64(len(c.events) - inserted))
+
+		log.Info().
+			Int("count", len(c.events)).
+			Int("inserted", inserted).
+			Dur("duration", time.Since(start)).
+			Msg("Batch indexed")
+	}
+
+	c.events = c.events[:0]
+	c.messages = c.messages[:0]
+}
+
+// Close closes the consumer
+func (c *Consumer) Close() error {
+	if c.nc != nil {
+		c.nc.Close()
+	}
+	return nil
+}
@@ -0,0 +1,46 @@
+-- Facto Postgres index
+-- Events consumed from FACTO_EVENTS, re-verified by the indexer
+
+-- facto_ids are unique per tenant; events without a tenant use ''
+CREATE TABLE events (
+    tenant_id       TEXT NOT NULL DEFAULT '',
+    facto_id        TEXT NOT NULL,
+    agent_id        TEXT NOT NULL,
+    session_id      TEXT NOT NULL,
+    parent_facto_id TEXT,
+    event_version   INTEGER NOT NULL,
+    action_type     TEXT NOT NULL,
+    status          TEXT NOT NULL,
+    input_data      JSONB NOT NULL,
+    output_data     JSONB NOT NULL,
+    execution_meta  JSONB NOT NULL,
+    signature       TEXT NOT NULL,
+    public_key      TEXT NOT NULL,
+    prev_hash       TEXT NOT NULL,
+    event_hash      TEXT NOT NULL,
+    started_at      BIGINT NOT NULL,
+    completed_at    BIGINT NOT NULL,
+    -- Server envelope from the Facto-Envelope header
+    received_at     BIGINT,
+    envelope        JSONB,
+    -- Whether prev_hash matched the session's chain head when indexed
+    chain_linked    BOOLEAN NOT NULL,
+    indexed_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (tenant_id, facto_id)
+);
+
+CREATE INDEX events_agent_completed_idx ON events (tenant_id, agent_id, completed_at);
+CREATE INDEX events_session_completed_idx ON events (tenant_id, session_id, completed_at);
+CREATE INDEX events_completed_idx ON events (completed_at);
+
+-- Latest indexed event of each session
+CREATE TABLE session_chain_heads (
+    tenant_id   TEXT NOT NULL DEFAULT '',
+    session_id  TEXT NOT NULL,
+    agent_id    TEXT NOT NULL,
+    facto_id    TEXT NOT NULL,
+    event_hash  TEXT NOT NULL,
+    event_count BIGINT NOT NULL,
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (tenant_id, session_id)
+);
@@ -0,0 +1,2 @@
+-- Signature algorithm of the event proof; NULL for Ed25519
+ALTER TABLE events ADD COLUMN signature_algorithm TEXT;
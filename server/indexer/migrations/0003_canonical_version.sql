@@ -0,0 +1,2 @@
+-- Canonical form version of the event proof; NULL for version 1
+ALTER TABLE events ADD COLUMN canonical_version INTEGER;
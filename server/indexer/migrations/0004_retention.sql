@@ -0,0 +1,42 @@
+-- Retention policies and legal holds set through the ingestion admin API,
+-- and what expiry deleted
+
+-- Days events are kept after they complete; agent_id '' is the tenant's
+-- retention for agents without their own
+CREATE TABLE retention_policies (
+    tenant_id  TEXT NOT NULL,
+    agent_id   TEXT NOT NULL DEFAULT '',
+    days       INTEGER NOT NULL,
+    updated_at BIGINT NOT NULL,
+    PRIMARY KEY (tenant_id, agent_id)
+);
+
+-- Sessions whose events are never expired while held
+CREATE TABLE legal_holds (
+    tenant_id  TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    reason     TEXT NOT NULL,
+    placed_by  TEXT NOT NULL,
+    placed_at  BIGINT NOT NULL,
+    PRIMARY KEY (tenant_id, session_id)
+);
+
+-- Merkle checkpoint of each run of a session's events deleted by expiry, so
+-- the remaining chain can still be tied to what came before it
+CREATE TABLE expired_ranges (
+    id                 BIGSERIAL PRIMARY KEY,
+    tenant_id          TEXT NOT NULL,
+    session_id         TEXT NOT NULL,
+    events             INTEGER NOT NULL,
+    first_facto_id     TEXT NOT NULL,
+    last_facto_id      TEXT NOT NULL,
+    first_event_hash   TEXT NOT NULL,
+    last_event_hash    TEXT NOT NULL,
+    first_completed_at BIGINT NOT NULL,
+    last_completed_at  BIGINT NOT NULL,
+    -- Root over the event hashes in completed_at order
+    merkle_root        TEXT NOT NULL,
+    expired_at         TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX expired_ranges_session_idx ON expired_ranges (tenant_id, session_id);
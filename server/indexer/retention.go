@@ -0,0 +1,343 @@
+package main
+
+import (
+	"context"
+	"crypto/sha256"
+	"encoding/hex"
+	"encoding/json"
+	"fmt"
+	"time"
+
+	"github.com/jackc/pgx/v5"
+	"github.com/nats-io/nats.go/jetstream"
+	"github.com/prometheus/client_golang/prometheus"
+	"github.com/prometheus/client_golang/prometheus/promauto"
+	"github.com/rs/zerolog/log"
+)
+
+// retentionSubject is where the ingestion service publishes retention
+// policy and legal hold changes
+const retentionSubject = "facto.control.retention"
+
+// defaultTenant is how the admin API names events indexed without a tenant
+const defaultTenant = "default"
+
+const nanosPerDay = int64(24 * time.Hour)
+
+var (
+	retentionChanges = promauto.NewCounterVec(prometheus.CounterOpts{
+		Name: "facto_indexer_retention_changes_total",
+		Help: "Total number of retention policy and legal hold changes applied",
+	}, []string{"type"})
+
+	eventsExpired = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_indexer_events_expired_total",
+		Help: "Total number of events deleted past their retention",
+	})
+
+	expiredRanges = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_indexer_expired_ranges_total",
+		Help: "Total number of Merkle checkpoints recorded for expired events",
+	})
+
+	retentionRunsFailed = promauto.NewCounter(prometheus.CounterOpts{
+		Name: "facto_indexer_retention_runs_failed_total",
+		Help: "Total number of expiry runs whose transaction failed",
+	})
+)
+
+// RetentionControl is a retention policy or legal hold change. Policies
+// carry the tenant's full retention, so applying one replaces the last.
+type RetentionControl struct {
+	Type      string         `json:"type"`
+	TenantID  string         `json:"tenant_id"`
+	Days      *int           `json:"days"`
+	Agents    map[string]int `json:"agents"`
+	UpdatedAt int64          `json:"updated_at"`
+	SessionID string         `json:"session_id"`
+	Reason    string         `json:"reason"`
+	PlacedBy  string         `json:"placed_by"`
+	PlacedAt  int64          `json:"placed_at"`
+}
+
+// indexTenant maps a tenant of the admin API to the one events are indexed under
+func indexTenant(tenantID string) string {
+	if tenantID == defaultTenant {
+		return ""
+	}
+	return tenantID
+}
+
+// StartRetention consumes retention changes from the FACTO_CONTROL stream
+// and applies them, one message at a time
+func (c *Consumer) StartRetention(ctx context.Context) error {
+	stream, err := c.js.Stream(ctx, "FACTO_CONTROL")
+	if err != nil {
+		return err
+	}
+
+	consumer, err := stream.CreateOrUpdateConsumer(ctx, jetstream.ConsumerConfig{
+		Durable:       "indexer-retention",
+		FilterSubject: retentionSubject,
+		AckPolicy:     jetstream.AckExplicitPolicy,
+		AckWait:       30 * time.Second,
+	})
+	if err != nil {
+		return err
+	}
+
+	log.Info().Msg("Started consuming retention changes from FACTO_CONTROL stream")
+
+	consumeCtx, err := consumer.Consume(func(msg jetstream.Msg) {
+		var control RetentionControl
+		if err := json.Unmarshal(msg.Data(), &control); err != nil {
+			log.Error().Err(err).Msg("Failed to unmarshal retention change")
+			msg.Term()
+			return
+		}
+
+		if err := c.storage.ApplyRetentionControl(ctx, &control); err != nil {
+			log.Error().Err(err).Str("type", control.Type).Str("tenant_id", control.TenantID).Msg("Failed to apply retention change")
+			msg.Nak()
+			return
+		}
+
+		msg.Ack()
+		retentionChanges.WithLabelValues(control.Type).Inc()
+	})
+	if err != nil {
+		return err
+	}
+	defer consumeCtx.Stop()
+
+	<-ctx.Done()
+	return ctx.Err()
+}
+
+// ApplyRetentionControl stores a retention policy or legal hold change
+func (s *Storage) ApplyRetentionControl(ctx context.Context, control *RetentionControl) error {
+	tenantID := indexTenant(control.TenantID)
+
+	tx, err := s.pool.Begin(ctx)
+	if err != nil {
+		return err
+	}
+	defer tx.Rollback(ctx)
+
+	switch control.Type {
+	case "policy", "policy_removed":
+		if _, err := tx.Exec(ctx, "DELETE FROM retention_policies WHERE tenant_id = $1", tenantID); err != nil {
+			return err
+		}
+		if control.Type == "policy_removed" {
+			break
+		}
+		days := make(map[string]int, len(control.Agents)+1)
+		for agentID, agentDays := range control.Agents {
+			days[agentID] = agentDays
+		}
+		if control.Days != nil {
+			days[""] = *control.Days
+		}
+		for agentID, agentDays := range days {
+			if _, err := tx.Exec(ctx, `
+				INSERT INTO retention_policies (tenant_id, agent_id, days, updated_at)
+				VALUES ($1, $2, $3, $4)
+			`, tenantID, agentID, agentDays, control.UpdatedAt); err != nil {
+				return err
+			}
+		}
+	case "legal_hold":
+		if _, err := tx.Exec(ctx, `
+			INSERT INTO legal_holds (tenant_id, session_id, reason, placed_by, placed_at)
+			VALUES ($1, $2, $3, $4, $5)
+			ON CONFLICT (tenant_id, session_id) DO UPDATE SET
+				reason = EXCLUDED.reason,
+				placed_by = EXCLUDED.placed_by,
+				placed_at = EXCLUDED.placed_at
+		`, tenantID, control.SessionID, control.Reason, control.PlacedBy, control.PlacedAt); err != nil {
+			return err
+		}
+	case "legal_hold_released":
+		if _, err := tx.Exec(ctx,
+			"DELETE FROM legal_holds WHERE tenant_id = $1 AND session_id = $2",
+			tenantID, control.SessionID,
+		); err != nil {
+			return err
+		}
+	default:
+		return fmt.Errorf("unknown retention change %q", control.Type)
+	}
+
+	return tx.Commit(ctx)
+}
+
+// expiredEvent is an event past its retention
+type expiredEvent struct {
+	tenantID    string
+	sessionID   string
+	factoID     string
+	eventHash   string
+	completedAt int64
+}
+
+// ExpireEvents deletes up to limit events that completed longer ago than
+// their agent's retention, or else their tenant's, outside sessions under a
+// legal hold. Each session's deleted events are recorded in expired_ranges
+// with the Merkle root of their hashes, in the same transaction. Returns
+// how many events were deleted.
+//
+// Only the Postgres index is expired; archive files are kept as written.
+func (s *Storage) ExpireEvents(ctx context.Context, now int64, limit int) (int, error) {
+	tx, err := s.pool.Begin(ctx)
+	if err != nil {
+		return 0, err
+	}
+	defer tx.Rollback(ctx)
+
+	rows, err := tx.Query(ctx, `
+		SELECT e.tenant_id, e.session_id, e.facto_id, e.event_hash, e.completed_at
+		FROM events e
+		CROSS JOIN LATERAL (
+			SELECT p.days FROM retention_policies p
+			WHERE p.tenant_id = e.tenant_id AND p.agent_id IN (e.agent_id, '')
+			ORDER BY p.agent_id DESC
+			LIMIT 1
+		) policy
+		WHERE e.completed_at < $1 - policy.days::BIGINT * $2
+		  AND NOT EXISTS (
+			SELECT 1 FROM legal_holds h
+			WHERE h.tenant_id = e.tenant_id AND h.session_id = e.session_id
+		  )
+		ORDER BY e.tenant_id, e.session_id, e.completed_at, e.facto_id
+		LIMIT $3
+		FOR UPDATE OF e SKIP LOCKED
+	`, now, nanosPerDay, limit)
+	if err != nil {
+		return 0, err
+	}
+	expired, err := pgx.CollectRows(rows, func(row pgx.CollectableRow) (expiredEvent, error) {
+		var e expiredEvent
+		err := row.Scan(&e.tenantID, &e.sessionID, &e.factoID, &e.eventHash, &e.completedAt)
+		return e, err
+	})
+	if err != nil {
+		return 0, err
+	}
+
+	ranges := 0
+	for start := 0; start < len(expired); {
+		end := start + 1
+		for end < len(expired) &&
+			expired[end].tenantID == expired[start].tenantID &&
+			expired[end].sessionID == expired[start].sessionID {
+			end++
+		}
+		if err := recordExpiredRange(ctx, tx, expired[start:end]); err != nil {
+			return 0, err
+		}
+		ranges++
+		start = end
+	}
+
+	for _, e := range expired {
+		if _, err := tx.Exec(ctx,
+			"DELETE FROM events WHERE tenant_id = $1 AND facto_id = $2",
+			e.tenantID, e.factoID,
+		); err != nil {
+			return 0, err
+		}
+	}
+
+	if err := tx.Commit(ctx); err != nil {
+		return 0, err
+	}
+	eventsExpired.Add(float64(len(expired)))
+	expiredRanges.Add(float64(ranges))
+	return len(expired), nil
+}
+
+// recordExpiredRange writes the Merkle checkpoint of one session's expired
+// events, given in completed_at order
+func recordExpiredRange(ctx context.Context, tx pgx.Tx, events []expiredEvent) error {
+	hashes := make([]string, len(events))
+	for i, e := range events {
+		hashes[i] = e.eventHash
+	}
+	first, last := events[0], events[len(events)-1]
+	_, err := tx.Exec(ctx, `
+		INSERT INTO expired_ranges (
+			tenant_id, session_id, events,
+			first_facto_id, last_facto_id, first_event_hash, last_event_hash,
+			first_completed_at, last_completed_at, merkle_root
+		) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+	`,
+		first.tenantID, first.sessionID, len(events),
+		first.factoID, last.factoID, first.eventHash, last.eventHash,
+		first.completedAt, last.completedAt, merkleRoot(hashes),
+	)
+	return err
+}
+
+// RunExpiry expires events every interval, in batches of batchSize until
+// none are left
+func RunExpiry(ctx context.Context, storage *Storage, interval time.Duration, batchSize int) {
+	ticker := time.NewTicker(interval)
+	defer ticker.Stop()
+
+	for {
+		select {
+		case <-ctx.Done():
+			return
+		case <-ticker.C:
+		}
+
+		total := 0
+		for {
+			expired, err := storage.ExpireEvents(ctx, time.Now().UnixNano(), batchSize)
+			if err != nil {
+				if ctx.Err() == nil {
+					retentionRunsFailed.Inc()
+					log.Error().Err(err).Msg("Failed to expire events")
+				}
+				break
+			}
+			total += expired
+			if expired < batchSize {
+				break
+			}
+		}
+		if total > 0 {
+			log.Info().Int("events", total).Msg("Expired events past retention")
+		}
+	}
+}
+
+// merkleRoot computes the root the processor's BuildMerkleTree gives for
+// the same hex hashes: SHA256(left || right) of each pair, the last node
+// of an odd level paired with itself
+func merkleRoot(hashes []string) string {
+	if len(hashes) == 0 {
+		return hex.EncodeToString(sha256.New().Sum(nil))
+	}
+
+	level := make([][]byte, len(hashes))
+	for i, h := range hashes {
+		level[i], _ = hex.DecodeString(h)
+	}
+	// Even a single hash is paired, with itself
+	for {
+		if len(level)%2 != 0 {
+			level = append(level, level[len(level)-1])
+		}
+		parents := make([][]byte, 0, len(level)/2)
+		for i := 0; i < len(level); i += 2 {
+			sum := sha256.Sum256(append(append([]byte{}, level[i]...), level[i+1]...))
+			parents = append(parents, sum[:])
+		}
+		level = parents
+		if len(level) == 1 {
+			return hex.EncodeToString(level[0])
+		}
+	}
+}
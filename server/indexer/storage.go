@@ -0,0 +1,238 @@
+package main
+
+import (
+	"context"
+	"embed"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io/fs"
+	"sort"
+	"strings"
+
+	"github.com/jackc/pgx/v5"
+	"github.com/jackc/pgx/v5/pgxpool"
+	"github.com/rs/zerolog/log"
+)
+
+//go:embed migrations/*.sql
+var migrations embed.FS
+
+// migrationLockID serializes migrations between indexer replicas
+const migrationLockID = 0x66616374
+
+// Storage handles Postgres operations
+type Storage struct {
+	pool *pgxpool.Pool
+}
+
+// NewStorage connects to Postgres and applies pending migrations
+func NewStorage(ctx context.Context, databaseURL string) (*Storage, error) {
+	pool, err := pgxpool.New(ctx, databaseURL)
+	if err != nil {
+		return nil, err
+	}
+	if err := pool.Ping(ctx); err != nil {
+		pool.Close()
+		return nil, err
+	}
+
+	s := &Storage{pool: pool}
+	if err := s.migrate(ctx); err != nil {
+		pool.Close()
+		return nil, fmt.Errorf("migrations failed: %w", err)
+	}
+	return s, nil
+}
+
+// migrate applies the embedded migrations in file name order, each in its
+// own transaction, recording them in schema_migrations
+func (s *Storage) migrate(ctx context.Context) error {
+	conn, err := s.pool.Acquire(ctx)
+	if err != nil {
+		return err
+	}
+	defer conn.Release()
+
+	if _, err := conn.Exec(ctx, "SELECT pg_advisory_lock($1)", migrationLockID); err != nil {
+		return err
+	}
+	defer conn.Exec(context.Background(), "SELECT pg_advisory_unlock($1)", migrationLockID)
+
+	if _, err := conn.Exec(ctx, `
+		CREATE TABLE IF NOT EXISTS schema_migrations (
+			version    TEXT PRIMARY KEY,
+			applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+		)
+	`); err != nil {
+		return err
+	}
+
+	names, err := fs.Glob(migrations, "migrations/*.sql")
+	if err != nil {
+		return err
+	}
+	sort.Strings(names)
+
+	for _, name := range names {
+		version := strings.TrimSuffix(strings.TrimPrefix(name, "migrations/"), ".sql")
+
+		var applied bool
+		if err := conn.QueryRow(ctx,
+			"SELECT EXISTS (SELECT 1 FROM schema_migrations WHERE version = $1)", version,
+		).Scan(&applied); err != nil {
+			return err
+		}
+		if applied {
+			continue
+		}
+
+		sql, err := migrations.ReadFile(name)
+		if err != nil {
+			return err
+		}
+
+		tx, err := conn.Begin(ctx)
+		if err != nil {
+			return err
+		}
+		if _, err := tx.Exec(ctx, string(sql)); err != nil {
+			tx.Rollback(ctx)
+			return fmt.Errorf("%s: %w", version, err)
+		}
+		if _, err := tx.Exec(ctx, "INSERT INTO schema_migrations (version) VALUES ($1)", version); err != nil {
+			tx.Rollback(ctx)
+			return err
+		}
+		if err := tx.Commit(ctx); err != nil {
+			return err
+		}
+		log.Info().Str("version", version).Msg("Applied migration")
+	}
+
+	return nil
+}
+
+// IndexBatch writes a batch of verified events in one transaction and
+// returns how many were new. Events already indexed under the same tenant
+// and facto_id are skipped, so redeliveries are harmless. Chain heads
+// advance in batch order, which is the order events were published in.
+func (s *Storage) IndexBatch(ctx context.Context, events []indexedEvent) (int, error) {
+	tx, err := s.pool.Begin(ctx)
+	if err != nil {
+		return 0, err
+	}
+	defer tx.Rollback(ctx)
+
+	inserted := 0
+	for _, indexed := range events {
+		event := indexed.event
+
+		var headHash string
+		var linked bool
+		err := tx.QueryRow(ctx, `
+			SELECT event_hash FROM session_chain_heads
+			WHERE tenant_id = $1 AND session_id = $2
+			FOR UPDATE
+		`, indexed.tenantID, event.SessionID).Scan(&headHash)
+		switch {
+		case errors.Is(err, pgx.ErrNoRows):
+			// The first event seen for a session starts the chain
+			linked = true
+		case err != nil:
+			return 0, err
+		default:
+			linked = event.Proof.PrevHash == headHash
+		}
+
+		inputData, err := json.Marshal(event.InputData)
+		if err != nil {
+			return 0, err
+		}
+		outputData, err := json.Marshal(event.OutputData)
+		if err != nil {
+			return 0, err
+		}
+		execMeta, err := json.Marshal(event.ExecutionMeta)
+		if err != nil {
+			return 0, err
+		}
+
+		var receivedAt *int64
+		var envelope []byte
+		if indexed.envelope != nil {
+			var e Envelope
+			if err := json.Unmarshal(indexed.envelope, &e); err == nil {
+				receivedAt = &e.ReceivedAt
+			}
+			envelope = indexed.envelope
+		}
+
+		eventVersion := event.EventVersion
+		if eventVersion == 0 {
+			eventVersion = 1
+		}
+
+		tag, err := tx.Exec(ctx, `
+			INSERT INTO events (
+				tenant_id, facto_id, agent_id, session_id, parent_facto_id,
+				event_version, action_type, status,
+				input_data, output_data, execution_meta,
+				signature, public_key, signature_algorithm, canonical_version, prev_hash, event_hash,
+				started_at, completed_at, received_at, envelope, chain_linked
+			) VALUES (
+				$1, $2, $3, $4, $5,
+				$6, $7, $8,
+				$9, $10, $11,
+				$12, $13, $14, $15, $16, $17,
+				$18, $19, $20, $21, $22
+			)
+			ON CONFLICT (tenant_id, facto_id) DO NOTHING
+		`,
+			indexed.tenantID, event.FactoID, event.AgentID, event.SessionID, event.ParentFactoID,
+			eventVersion, event.ActionType, event.Status,
+			inputData, outputData, execMeta,
+			event.Proof.Signature, event.Proof.PublicKey, event.Proof.Algorithm, event.Proof.CanonicalVersion, event.Proof.PrevHash, event.Proof.EventHash,
+			event.StartedAt, event.CompletedAt, receivedAt, envelope, linked,
+		)
+		if err != nil {
+			return 0, err
+		}
+		if tag.RowsAffected() == 0 {
+			continue
+		}
+		inserted++
+
+		if !linked {
+			log.Warn().
+				Str("facto_id", event.FactoID).
+				Str("session_id", event.SessionID).
+				Msg("prev_hash does not match the session chain head")
+		}
+
+		if _, err := tx.Exec(ctx, `
+			INSERT INTO session_chain_heads (tenant_id, session_id, agent_id, facto_id, event_hash, event_count)
+			VALUES ($1, $2, $3, $4, $5, 1)
+			ON CONFLICT (tenant_id, session_id) DO UPDATE SET
+				agent_id = EXCLUDED.agent_id,
+				facto_id = EXCLUDED.facto_id,
+				event_hash = EXCLUDED.event_hash,
+				event_count = session_chain_heads.event_count + 1,
+				updated_at = now()
+		`, indexed.tenantID, event.SessionID, event.AgentID, event.FactoID, event.Proof.EventHash); err != nil {
+			return 0, err
+		}
+	}
+
+	if err := tx.Commit(ctx); err != nil {
+		return 0, err
+	}
+	return inserted, nil
+}
+
+// Close closes the connection pool
+func (s *Storage) Close() {
+	if s.pool != nil {
+		s.pool.Close()
+	}
+}
@@ -0,0 +1,228 @@
+package main
+
+import (
+	"bytes"
+	"encoding/hex"
+	"encoding/json"
+	"fmt"
+	"sort"
+	"strconv"
+
+	"github.com/facto-ai/facto/server/common/jcs"
+	"github.com/facto-ai/facto/server/common/signature"
+	"golang.org/x/crypto/sha3"
+)
+
+// supportedEventVersion is the newest event wire version the indexer can verify
+const supportedEventVersion = 1
+
+// verifyEvent recomputes the event hash from the canonical form and checks
+// the signature against the embedded public key, or the batch envelope that
+// stood in for it when trust names one. It returns the hash.
+func verifyEvent(event *FactoEvent, trust *signature.TrustBasis) (string, error) {
+	canonical, err := canonicalForm(event)
+	if err != nil {
+		return "", err
+	}
+
+	sum := sha3.Sum256(canonical)
+	hash := hex.EncodeToString(sum[:])
+	if hash != event.Proof.EventHash {
+		return "", fmt.Errorf("hash mismatch: computed %s, event carries %s", hash, event.Proof.EventHash)
+	}
+
+	if trust.Enveloped() {
+		if err := trust.VerifyEnvelope(hash, event.Proof.PublicKey); err != nil {
+			return "", err
+		}
+		return hash, nil
+	}
+
+	algorithm := ""
+	if event.Proof.Algorithm != nil {
+		algorithm = *event.Proof.Algorithm
+	}
+	if err := signature.Verify(algorithm, event.Proof.PublicKey, event.Proof.Signature, canonical); err != nil {
+		return "", err
+	}
+
+	return hash, nil
+}
+
+// canonicalForm builds the bytes the event hash and signature cover, in the
+// canonical form version the proof names
+func canonicalForm(event *FactoEvent) ([]byte, error) {
+	version := event.EventVersion
+	if version == 0 {
+		version = 1 // Deployed SDKs send no version
+	}
+	if version > supportedEventVersion {
+		return nil, fmt.Errorf("unsupported event_version %d", version)
+	}
+
+	switch canonicalVersion := event.Proof.CanonicalVersion; {
+	case canonicalVersion == nil || *canonicalVersion == 1:
+		return canonicalFormV1(event)
+	case *canonicalVersion == 2:
+		return canonicalFormV2(event, version)
+	default:
+		return nil, fmt.Errorf("unsupported canonical_version %d", *canonicalVersion)
+	}
+}
+
+// canonicalFormV1 must match the ingestion service byte for byte: sorted
+// keys, no whitespace, numbers as received, and serde_json string escaping.
+func canonicalFormV1(event *FactoEvent) ([]byte, error) {
+	toolCalls := event.ExecutionMeta.ToolCalls
+	if toolCalls == nil {
+		toolCalls = []interface{}{}
+	}
+	execMeta := map[string]interface{}{
+		"sdk_version": event.ExecutionMeta.SDKVersion,
+		"seed":        event.ExecutionMeta.Seed,
+		"tool_calls":  toolCalls,
+	}
+	if event.ExecutionMeta.ModelID != nil {
+		execMeta["model_id"] = *event.ExecutionMeta.ModelID
+	}
+	if event.ExecutionMeta.Temperature != nil {
+		execMeta["temperature"] = *event.ExecutionMeta.Temperature
+	}
+
+	canonical := map[string]interface{}{
+		"action_type":     event.ActionType,
+		"agent_id":        event.AgentID,
+		"completed_at":    event.CompletedAt,
+		"execution_meta":  execMeta,
+		"facto_id":        event.FactoID,
+		"input_data":      event.InputData,
+		"output_data":     event.OutputData,
+		"parent_facto_id": event.ParentFactoID,
+		"prev_hash":       event.Proof.PrevHash,
+		"session_id":      event.SessionID,
+		"started_at":      event.StartedAt,
+		"status":          event.Status,
+	}
+
+	var buf bytes.Buffer
+	if err := writeCanonical(&buf, canonical); err != nil {
+		return nil, err
+	}
+	return buf.Bytes(), nil
+}
+
+// canonicalFormV2 is the RFC 8785 serialization of the whole event without
+// the signature and event hash. Every field is present, null when unset, the
+// proof always names its algorithm, and the 64-bit integers are strings.
+func canonicalFormV2(event *FactoEvent, version int) ([]byte, error) {
+	toolCalls := event.ExecutionMeta.ToolCalls
+	if toolCalls == nil {
+		toolCalls = []interface{}{}
+	}
+	tags := event.ExecutionMeta.Tags
+	if tags == nil {
+		tags = map[string]string{}
+	}
+	var seed interface{}
+	if event.ExecutionMeta.Seed != nil {
+		seed = strconv.FormatInt(*event.ExecutionMeta.Seed, 10)
+	}
+	algorithm := "ed25519"
+	if event.Proof.Algorithm != nil {
+		algorithm = *event.Proof.Algorithm
+	}
+
+	canonical := map[string]interface{}{
+		"action_type":   event.ActionType,
+		"agent_id":      event.AgentID,
+		"completed_at":  strconv.FormatInt(event.CompletedAt, 10),
+		"event_version": version,
+		"execution_meta": map[string]interface{}{
+			"max_tokens":   event.ExecutionMeta.MaxTokens,
+			"model_hash":   event.ExecutionMeta.ModelHash,
+			"model_id":     event.ExecutionMeta.ModelID,
+			"sdk_language": event.ExecutionMeta.SDKLanguage,
+			"sdk_version":  event.ExecutionMeta.SDKVersion,
+			"seed":         seed,
+			"tags":         tags,
+			"temperature":  event.ExecutionMeta.Temperature,
+			"tool_calls":   toolCalls,
+		},
+		"facto_id":        event.FactoID,
+		"input_data":      event.InputData,
+		"output_data":     event.OutputData,
+		"parent_facto_id": event.ParentFactoID,
+		"proof": map[string]interface{}{
+			"algorithm":         algorithm,
+			"canonical_version": 2,
+			"prev_hash":         event.Proof.PrevHash,
+			"public_key":        event.Proof.PublicKey,
+		},
+		"session_id": event.SessionID,
+		"started_at": strconv.FormatInt(event.StartedAt, 10),
+		"status":     event.Status,
+	}
+
+	return jcs.Marshal(canonical)
+}
+
+// writeCanonical writes compact JSON with sorted object keys. Unlike
+// encoding/json it does not escape HTML characters or U+2028/U+2029.
+func writeCanonical(buf *bytes.Buffer, value interface{}) error {
+	switch v := value.(type) {
+	case nil:
+		buf.WriteString("null")
+	case bool:
+		buf.WriteString(strconv.FormatBool(v))
+	case string:
+		jcs.WriteString(buf, v)
+	case *string:
+		if v == nil {
+			buf.WriteString("null")
+		} else {
+			jcs.WriteString(buf, *v)
+		}
+	case *int64:
+		if v == nil {
+			buf.WriteString("null")
+		} else {
+			buf.WriteString(strconv.FormatInt(*v, 10))
+		}
+	case int64:
+		buf.WriteString(strconv.FormatInt(v, 10))
+	case json.Number:
+		buf.WriteString(v.String())
+	case []interface{}:
+		buf.WriteByte('[')
+		for i, item := range v {
+			if i > 0 {
+				buf.WriteByte(',')
+			}
+			if err := writeCanonical(buf, item); err != nil {
+				return err
+			}
+		}
+		buf.WriteByte(']')
+	case map[string]interface{}:
+		keys := make([]string, 0, len(v))
+		for k := range v {
+			keys = append(keys, k)
+		}
+		sort.Strings(keys)
+		buf.WriteByte('{')
+		for i, k := range keys {
+			if i > 0 {
+				buf.WriteByte(',')
+			}
+			jcs.WriteString(buf, k)
+			buf.WriteByte(':')
+			if err := writeCanonical(buf, v[k]); err != nil {
+				return err
+			}
+		}
+		buf.WriteByte('}')
+	default:
+		return fmt.Errorf("unexpected value of type %T in canonical form", value)
+	}
+	return nil
+}
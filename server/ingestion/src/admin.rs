@@ -0,0 +1,1617 @@
+use crate::protocol::ErrorResponse;
+use axum::{
+    extract::{Extension, Json, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::anchor::Anchor;
+use crate::annotations::{Annotation, AnnotationError, AnnotationRequest, ANNOTATION_SUBJECT};
+use crate::auth::{AdminPrincipal, Principal, Scope};
+use crate::checkpoint::Checkpoint;
+use crate::controls::{
+    AgentControl, AgentPause, ByteQuotaOverride, ControlError, EnforcementModeOverride,
+    LimitSource, PauseRequest, RateLimitOverride,
+};
+use crate::freeze::{
+    notification_subject, FreezeError, FreezeRequest, SessionFreeze, SessionNotification,
+    SessionSeal, UnfreezeOutcome, REQUIRED_UNFREEZE_APPROVALS, SESSION_CONTROL_SUBJECT,
+};
+use crate::headers::TenantHeaders;
+use crate::keyfile::{self, KeyFileFormat, MAX_IMPORT_ROWS};
+use crate::models::{ModelError, ModelList, ModelListRequest};
+use crate::ratelimit::{ByteUsage, LimiterState};
+use crate::redaction::{RedactionError, RedactionPolicy, RedactionRequest};
+use crate::registry::{
+    KeyEntry, KeyImportReport, RegisterKeyRequest, RegistryError, RegistryRef, RegistrySnapshot,
+    RejectedKeyRow, RevokeKeyRequest, RotateKeyRequest,
+};
+use crate::retention::{
+    LegalHold, LegalHoldRequest, RetentionControl, RetentionError, RetentionPolicy,
+    RetentionRequest, RETENTION_SUBJECT,
+};
+use crate::schemas::{ActionSchema, SchemaError, SchemaRequest};
+use crate::tenants::{validate_tenant_id, TenantConfig, TenantError, TenantLimits, TenantUsage};
+use crate::verification::{now_nanos, EnforcementMode};
+use crate::webhooks::{WebhookError, WebhookRequest};
+use crate::{scoped_id, AppState};
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+pub fn error_response(status: StatusCode, error: impl ToString) -> Response {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: None,
+        }),
+    )
+        .into_response()
+}
+
+// ============================================================================
+// Key Registry
+// ============================================================================
+
+fn registry_error_response(e: RegistryError) -> Response {
+    let status = match e {
+        RegistryError::UnknownVersion(_) | RegistryError::UnknownKey(_) => StatusCode::NOT_FOUND,
+        RegistryError::KeyExists(_)
+        | RegistryError::AlreadyRevoked(_)
+        | RegistryError::AlreadyRotated(_) => StatusCode::CONFLICT,
+        RegistryError::Persistence(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        RegistryError::InvalidKey(_)
+        | RegistryError::StateRootMismatch
+        | RegistryError::InvalidSignature(_) => StatusCode::BAD_REQUEST,
+        RegistryError::UntrustedSigner(_) => StatusCode::FORBIDDEN,
+    };
+    error_response(status, e)
+}
+
+pub async fn list_keys_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    (
+        StatusCode::OK,
+        Json(state.key_registry.keys(tenant_id, &agent_id)),
+    )
+        .into_response()
+}
+
+pub async fn register_key_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+    Json(request): Json<RegisterKeyRequest>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    match state.key_registry.register(tenant_id, &agent_id, request) {
+        Ok(entry) => {
+            info!("Admin {} registered a key for agent {}", admin, agent_id);
+            (StatusCode::CREATED, Json::<KeyEntry>(entry)).into_response()
+        }
+        Err(e) => registry_error_response(e),
+    }
+}
+
+pub async fn revoke_key_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+    Json(request): Json<RevokeKeyRequest>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    match state.key_registry.revoke(tenant_id, &agent_id, request) {
+        Ok(entry) => {
+            info!("Admin {} revoked a key of agent {}", admin, agent_id);
+            counter!("facto_key_revocations_total").increment(1);
+            (StatusCode::OK, Json(entry)).into_response()
+        }
+        Err(e) => registry_error_response(e),
+    }
+}
+
+pub async fn rotate_key_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+    Json(request): Json<RotateKeyRequest>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    match state.key_registry.rotate(tenant_id, &agent_id, request) {
+        Ok(entry) => {
+            info!("Admin {} rotated a key of agent {}", admin, agent_id);
+            (StatusCode::CREATED, Json(entry)).into_response()
+        }
+        Err(e) => registry_error_response(e),
+    }
+}
+
+// ============================================================================
+// Key Registry Snapshots
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshotQuery {
+    /// Registry version to export
+    pub version: Option<u64>,
+    /// Export the registry as it was at this time (nanoseconds since epoch)
+    pub at: Option<i64>,
+}
+
+pub async fn export_snapshot_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Query(query): Query<SnapshotQuery>,
+) -> Response {
+    let version = query
+        .version
+        .or_else(|| query.at.map(|at| state.key_registry.version_at(at)));
+
+    match state
+        .key_registry
+        .snapshot(version, state.verifier.signer())
+    {
+        Ok(snapshot) => {
+            info!(
+                "Admin {} exported key registry snapshot version {}",
+                admin, snapshot.version
+            );
+            (StatusCode::OK, Json(snapshot)).into_response()
+        }
+        Err(e) => registry_error_response(e),
+    }
+}
+
+pub async fn import_snapshot_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Json(snapshot): Json<RegistrySnapshot>,
+) -> Response {
+    match state
+        .key_registry
+        .restore(&snapshot, &state.snapshot_signers)
+    {
+        Ok(restored) => {
+            info!(
+                "Admin {} restored key registry from snapshot version {}",
+                admin, snapshot.version
+            );
+            (StatusCode::OK, Json::<RegistryRef>(restored)).into_response()
+        }
+        Err(e) => {
+            warn!("Rejected key registry snapshot import: {}", e);
+            registry_error_response(e)
+        }
+    }
+}
+
+// ============================================================================
+// Bulk Key Import/Export
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct KeyFileQuery {
+    /// Defaults to the request Content-Type on import, then to JSONL
+    pub format: Option<KeyFileFormat>,
+    /// Validate an import without applying it
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+pub async fn export_keys_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Query(query): Query<KeyFileQuery>,
+) -> Response {
+    let format = query.format.unwrap_or_default();
+    let entries = state.key_registry.entries();
+    match keyfile::write_entries(format, &entries) {
+        Ok(body) => {
+            info!("Admin {} exported {} keys", admin, entries.len());
+            (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, format.content_type())],
+                body,
+            )
+                .into_response()
+        }
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+pub async fn import_keys_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Query(query): Query<KeyFileQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let format = query
+        .format
+        .or_else(|| {
+            headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(KeyFileFormat::from_content_type)
+        })
+        .unwrap_or_default();
+
+    let parsed = keyfile::parse_rows(format, &body);
+    if parsed.len() > MAX_IMPORT_ROWS {
+        return error_response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("at most {} rows can be imported at once", MAX_IMPORT_ROWS),
+        );
+    }
+
+    let mut rows = Vec::with_capacity(parsed.len());
+    let mut unparsed = Vec::new();
+    for (line, row) in parsed {
+        match row {
+            Ok(row) => rows.push((line, row)),
+            Err(error) => unparsed.push(RejectedKeyRow {
+                line,
+                agent_id: None,
+                error,
+            }),
+        }
+    }
+
+    match state.key_registry.import(rows, query.dry_run) {
+        Ok(mut report) => {
+            report.rejected.extend(unparsed);
+            report.rejected.sort_by_key(|r| r.line);
+            info!(
+                "Admin {} imported {} keys ({} rejected, dry run: {})",
+                admin,
+                report.imported,
+                report.rejected.len(),
+                report.dry_run
+            );
+            if !report.dry_run {
+                counter!("facto_keys_imported_total").increment(report.imported as u64);
+            }
+            (StatusCode::OK, Json::<KeyImportReport>(report)).into_response()
+        }
+        Err(e) => registry_error_response(e),
+    }
+}
+
+// ============================================================================
+// Session Freezes
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct FreezeResponse {
+    pub freeze: SessionFreeze,
+    /// Whether the seal and notification reached NATS
+    pub published: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnfreezeResponse {
+    pub unfrozen: bool,
+    pub approvals: Vec<String>,
+    pub required_approvals: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seal: Option<SessionSeal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published: Option<bool>,
+}
+
+fn freeze_error_response(e: FreezeError) -> Response {
+    let status = match e {
+        FreezeError::AlreadyFrozen | FreezeError::AlreadyApproved(_) => StatusCode::CONFLICT,
+        FreezeError::NotFrozen => StatusCode::NOT_FOUND,
+        FreezeError::Persistence(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error_response(status, e)
+}
+
+/// Publish a seal on the control subject and notify the agent's owner
+async fn publish_seal(state: &AppState, seal: &SessionSeal) -> bool {
+    let nats_client = state.nats_client.read().await;
+    let Some(ref client) = *nats_client else {
+        warn!(
+            "NATS not connected, session seal for {} not published",
+            seal.session_id
+        );
+        return false;
+    };
+
+    let mut published = client
+        .publish(
+            SESSION_CONTROL_SUBJECT,
+            serde_json::to_vec(seal).unwrap().into(),
+        )
+        .await
+        .is_ok();
+
+    if let Some(ref agent_id) = seal.agent_id {
+        let notification = SessionNotification::for_seal(seal);
+        published &= client
+            .publish(
+                notification_subject(agent_id),
+                serde_json::to_vec(&notification).unwrap().into(),
+            )
+            .await
+            .is_ok();
+    }
+
+    if !published {
+        warn!("Failed to publish session seal for {}", seal.session_id);
+    }
+    published
+}
+
+pub async fn get_freeze_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+    Path(session_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    match state.freezes.get(tenant_id, &session_id) {
+        Some(freeze) => (StatusCode::OK, Json(freeze)).into_response(),
+        None => freeze_error_response(FreezeError::NotFrozen),
+    }
+}
+
+pub async fn freeze_session_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(session_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+    Json(request): Json<FreezeRequest>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    let head = state
+        .chain_heads
+        .get(&scoped_id(tenant_id, &session_id))
+        .await;
+    let freeze = match state.freezes.freeze(
+        tenant_id,
+        &session_id,
+        request,
+        &admin,
+        head,
+        state.verifier.signer(),
+    ) {
+        Ok(freeze) => freeze,
+        Err(e) => return freeze_error_response(e),
+    };
+
+    info!(
+        "Admin {} froze session {}: {}",
+        admin, session_id, freeze.reason
+    );
+    counter!("facto_session_freezes_total").increment(1);
+
+    let published = publish_seal(&state, &freeze.seal).await;
+    (StatusCode::OK, Json(FreezeResponse { freeze, published })).into_response()
+}
+
+pub async fn approve_unfreeze_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(session_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    let outcome = match state.freezes.approve_unfreeze(
+        tenant_id,
+        &session_id,
+        &admin,
+        state.verifier.signer(),
+    ) {
+        Ok(outcome) => outcome,
+        Err(e) => return freeze_error_response(e),
+    };
+
+    let response = match outcome {
+        UnfreezeOutcome::Pending(approvals) => {
+            info!("Admin {} approved unfreezing session {}", admin, session_id);
+            UnfreezeResponse {
+                unfrozen: false,
+                approvals,
+                required_approvals: REQUIRED_UNFREEZE_APPROVALS,
+                seal: None,
+                published: None,
+            }
+        }
+        UnfreezeOutcome::Unfrozen(seal) => {
+            info!(
+                "Session {} unfrozen with approval of {:?}",
+                session_id, seal.actors
+            );
+            let published = publish_seal(&state, &seal).await;
+            UnfreezeResponse {
+                unfrozen: true,
+                approvals: seal.actors.clone(),
+                required_approvals: REQUIRED_UNFREEZE_APPROVALS,
+                seal: Some(seal),
+                published: Some(published),
+            }
+        }
+    };
+
+    (StatusCode::OK, Json(response)).into_response()
+}
+
+// ============================================================================
+// Session Annotations
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct AnnotationResponse {
+    pub annotation: Annotation,
+    /// Whether the annotation reached NATS
+    pub published: bool,
+}
+
+pub async fn list_annotations_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+    Path(session_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    (
+        StatusCode::OK,
+        Json(state.annotations.list(tenant_id, &session_id)),
+    )
+        .into_response()
+}
+
+pub async fn annotate_session_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(author): AdminPrincipal,
+    Path(session_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+    Json(request): Json<AnnotationRequest>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    let head = state
+        .chain_heads
+        .get(&scoped_id(tenant_id, &session_id))
+        .await;
+    let annotation = match state.annotations.add(
+        tenant_id,
+        &session_id,
+        request,
+        &author,
+        head,
+        state.verifier.signer(),
+    ) {
+        Ok(annotation) => annotation,
+        Err(e @ AnnotationError::EmptyFinding) => {
+            return error_response(StatusCode::BAD_REQUEST, e)
+        }
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+
+    info!(
+        "{} annotated session {} ({:?})",
+        author, session_id, annotation.severity
+    );
+    counter!("facto_session_annotations_total").increment(1);
+
+    let published = {
+        let nats_client = state.nats_client.read().await;
+        match *nats_client {
+            Some(ref client) => client
+                .publish(
+                    ANNOTATION_SUBJECT,
+                    serde_json::to_vec(&annotation).unwrap().into(),
+                )
+                .await
+                .is_ok(),
+            None => false,
+        }
+    };
+    if !published {
+        warn!("Annotation {} not published", annotation.annotation_id);
+    }
+
+    (
+        StatusCode::CREATED,
+        Json(AnnotationResponse {
+            annotation,
+            published,
+        }),
+    )
+        .into_response()
+}
+
+// ============================================================================
+// Tenants
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct TenantResponse {
+    #[serde(flatten)]
+    pub config: TenantConfig,
+    pub usage: TenantUsage,
+}
+
+fn tenant_error_response(e: TenantError) -> Response {
+    let status = match e {
+        TenantError::InvalidId(_) | TenantError::InvalidHeaders(_) => StatusCode::BAD_REQUEST,
+        TenantError::Unknown(_) => StatusCode::NOT_FOUND,
+        TenantError::Persistence(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error_response(status, e)
+}
+
+fn tenant_response(state: &AppState, config: TenantConfig) -> TenantResponse {
+    TenantResponse {
+        usage: state.tenants.usage(&config.tenant_id),
+        config,
+    }
+}
+
+pub async fn list_tenants_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+) -> Response {
+    let tenants: Vec<TenantResponse> = state
+        .tenants
+        .list()
+        .into_iter()
+        .map(|config| tenant_response(&state, config))
+        .collect();
+    (StatusCode::OK, Json(tenants)).into_response()
+}
+
+pub async fn get_tenant_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+) -> Response {
+    match state.tenants.get(&tenant_id) {
+        Some(config) => (StatusCode::OK, Json(tenant_response(&state, config))).into_response(),
+        None => tenant_error_response(TenantError::Unknown(tenant_id)),
+    }
+}
+
+pub async fn put_tenant_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+    Json(limits): Json<TenantLimits>,
+) -> Response {
+    match state.tenants.upsert(&tenant_id, limits) {
+        Ok(config) => {
+            info!(
+                "Admin {} set limits for tenant {}: {:?}",
+                admin, tenant_id, config.limits
+            );
+            (StatusCode::OK, Json(tenant_response(&state, config))).into_response()
+        }
+        Err(e) => tenant_error_response(e),
+    }
+}
+
+pub async fn put_tenant_headers_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+    Json(headers): Json<TenantHeaders>,
+) -> Response {
+    match state.tenants.set_headers(&tenant_id, headers) {
+        Ok(config) => {
+            info!(
+                "Admin {} set message headers for tenant {}: {:?}",
+                admin, tenant_id, config.headers
+            );
+            (StatusCode::OK, Json(tenant_response(&state, config))).into_response()
+        }
+        Err(e) => tenant_error_response(e),
+    }
+}
+
+pub async fn put_tenant_enforcement_mode_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+    Json(request): Json<EnforcementModeOverride>,
+) -> Response {
+    match state
+        .tenants
+        .set_enforcement_mode(&tenant_id, Some(request.enforcement_mode))
+    {
+        Ok(config) => {
+            info!(
+                "Admin {} set the enforcement mode of tenant {} to {}",
+                admin,
+                tenant_id,
+                request.enforcement_mode.code()
+            );
+            (StatusCode::OK, Json(tenant_response(&state, config))).into_response()
+        }
+        Err(e) => tenant_error_response(e),
+    }
+}
+
+pub async fn delete_tenant_enforcement_mode_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+) -> Response {
+    if state.tenants.get(&tenant_id).is_none() {
+        return tenant_error_response(TenantError::Unknown(tenant_id));
+    }
+    match state.tenants.set_enforcement_mode(&tenant_id, None) {
+        Ok(config) => {
+            info!(
+                "Admin {} removed the enforcement mode of tenant {}",
+                admin, tenant_id
+            );
+            (StatusCode::OK, Json(tenant_response(&state, config))).into_response()
+        }
+        Err(e) => tenant_error_response(e),
+    }
+}
+
+pub async fn delete_tenant_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+) -> Response {
+    match state.tenants.remove(&tenant_id) {
+        Ok(config) => {
+            info!("Admin {} removed tenant {}", admin, tenant_id);
+            (StatusCode::OK, Json(config)).into_response()
+        }
+        Err(e) => tenant_error_response(e),
+    }
+}
+
+// ============================================================================
+// Payload Schemas
+// ============================================================================
+
+fn schema_error_response(e: SchemaError) -> Response {
+    let status = match e {
+        SchemaError::InvalidSchema(_) => StatusCode::BAD_REQUEST,
+        SchemaError::Unknown(_) => StatusCode::NOT_FOUND,
+        SchemaError::Persistence(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error_response(status, e)
+}
+
+pub async fn list_schemas_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+) -> Response {
+    (StatusCode::OK, Json(state.schemas.list())).into_response()
+}
+
+pub async fn get_schema_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+    Path(action_type): Path<String>,
+) -> Response {
+    match state.schemas.get(&action_type) {
+        Some(schema) => (StatusCode::OK, Json(schema)).into_response(),
+        None => schema_error_response(SchemaError::Unknown(action_type)),
+    }
+}
+
+pub async fn put_schema_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(action_type): Path<String>,
+    Json(request): Json<SchemaRequest>,
+) -> Response {
+    match state.schemas.upsert(&action_type, request) {
+        Ok(schema) => {
+            info!(
+                "Admin {} set the {} schema ({} mode)",
+                admin,
+                action_type,
+                schema.schemas.mode.code()
+            );
+            (StatusCode::OK, Json::<ActionSchema>(schema)).into_response()
+        }
+        Err(e) => schema_error_response(e),
+    }
+}
+
+pub async fn delete_schema_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(action_type): Path<String>,
+) -> Response {
+    match state.schemas.remove(&action_type) {
+        Ok(schema) => {
+            info!("Admin {} removed the {} schema", admin, action_type);
+            (StatusCode::OK, Json(schema)).into_response()
+        }
+        Err(e) => schema_error_response(e),
+    }
+}
+
+// ============================================================================
+// Agent Rate Limits and Pauses
+// ============================================================================
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AgentQuery {
+    /// Tenant of the agent or session; omitted for those accepted without one
+    pub tenant_id: Option<String>,
+}
+
+impl AgentQuery {
+    fn tenant(&self) -> Result<Option<&str>, TenantError> {
+        if let Some(ref tenant_id) = self.tenant_id {
+            validate_tenant_id(tenant_id)?;
+        }
+        Ok(self.tenant_id.as_deref())
+    }
+}
+
+/// The limits in force for an agent and its current limiter state
+#[derive(Debug, Serialize)]
+pub struct AgentStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub agent_id: String,
+    pub rate_limit_source: LimitSource,
+    pub limiter: LimiterState,
+    pub enforcement_mode: EnforcementMode,
+    pub enforcement_mode_source: LimitSource,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub envelope_trusted: bool,
+    /// Set when the agent has a byte quota
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub byte_quota: Option<ByteQuotaStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused: Option<AgentPause>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ByteQuotaStatus {
+    pub byte_quota: u64,
+    pub source: LimitSource,
+    pub window_secs: u64,
+    #[serde(flatten)]
+    pub usage: ByteUsage,
+}
+
+fn control_error_response(e: ControlError) -> Response {
+    let status = match e {
+        ControlError::Unknown(_) => StatusCode::NOT_FOUND,
+        ControlError::Persistence(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error_response(status, e)
+}
+
+fn control_response(result: Result<AgentControl, ControlError>) -> Response {
+    match result {
+        Ok(control) => (StatusCode::OK, Json(control)).into_response(),
+        Err(e) => control_error_response(e),
+    }
+}
+
+/// Agents with a rate limit, byte quota or enforcement mode override,
+/// envelope trust or a pause
+pub async fn list_agent_controls_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+) -> Response {
+    (StatusCode::OK, Json(state.agent_controls.list())).into_response()
+}
+
+pub async fn agent_status_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    let (rate, source) = state.agent_rate_limit(tenant_id, &agent_id);
+    let arrival = state
+        .rate_limiter
+        .arrival(&scoped_id(tenant_id, &agent_id))
+        .await;
+    let now = now_nanos();
+    let byte_quota = state
+        .agent_byte_quota(tenant_id, &agent_id)
+        .map(|(byte_quota, source)| ByteQuotaStatus {
+            byte_quota,
+            source,
+            window_secs: state.byte_quotas.window().as_secs(),
+            usage: state
+                .byte_quotas
+                .usage(&scoped_id(tenant_id, &agent_id), now),
+        });
+    let (enforcement_mode, enforcement_mode_source) = state.enforcement_mode(tenant_id, &agent_id);
+    let status = AgentStatus {
+        tenant_id: tenant_id.map(String::from),
+        paused: state.agent_controls.pause(tenant_id, &agent_id),
+        envelope_trusted: state.agent_controls.envelope_trusted(tenant_id, &agent_id),
+        agent_id,
+        rate_limit_source: source,
+        limiter: LimiterState::new(arrival, now, rate),
+        enforcement_mode,
+        enforcement_mode_source,
+        byte_quota,
+    };
+    (StatusCode::OK, Json(status)).into_response()
+}
+
+pub async fn put_agent_rate_limit_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+    Json(request): Json<RateLimitOverride>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    let result =
+        state
+            .agent_controls
+            .set_rate_limit(tenant_id, &agent_id, Some(request.rate_limit_per_sec));
+    if result.is_ok() {
+        info!(
+            "Admin {} set the rate limit of agent {} to {}/s",
+            admin,
+            scoped_id(tenant_id, &agent_id),
+            request.rate_limit_per_sec
+        );
+    }
+    control_response(result)
+}
+
+pub async fn delete_agent_rate_limit_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    let result = state
+        .agent_controls
+        .set_rate_limit(tenant_id, &agent_id, None);
+    if result.is_ok() {
+        info!(
+            "Admin {} removed the rate limit override of agent {}",
+            admin,
+            scoped_id(tenant_id, &agent_id)
+        );
+    }
+    control_response(result)
+}
+
+pub async fn put_agent_byte_quota_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+    Json(request): Json<ByteQuotaOverride>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    let result =
+        state
+            .agent_controls
+            .set_byte_quota(tenant_id, &agent_id, Some(request.byte_quota));
+    if result.is_ok() {
+        info!(
+            "Admin {} set the byte quota of agent {} to {} bytes",
+            admin,
+            scoped_id(tenant_id, &agent_id),
+            request.byte_quota
+        );
+    }
+    control_response(result)
+}
+
+pub async fn delete_agent_byte_quota_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    let result = state
+        .agent_controls
+        .set_byte_quota(tenant_id, &agent_id, None);
+    if result.is_ok() {
+        info!(
+            "Admin {} removed the byte quota override of agent {}",
+            admin,
+            scoped_id(tenant_id, &agent_id)
+        );
+    }
+    control_response(result)
+}
+
+pub async fn put_agent_enforcement_mode_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+    Json(request): Json<EnforcementModeOverride>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    let result = state.agent_controls.set_enforcement_mode(
+        tenant_id,
+        &agent_id,
+        Some(request.enforcement_mode),
+    );
+    if result.is_ok() {
+        info!(
+            "Admin {} set the enforcement mode of agent {} to {}",
+            admin,
+            scoped_id(tenant_id, &agent_id),
+            request.enforcement_mode.code()
+        );
+    }
+    control_response(result)
+}
+
+pub async fn delete_agent_enforcement_mode_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    let result = state
+        .agent_controls
+        .set_enforcement_mode(tenant_id, &agent_id, None);
+    if result.is_ok() {
+        info!(
+            "Admin {} removed the enforcement mode override of agent {}",
+            admin,
+            scoped_id(tenant_id, &agent_id)
+        );
+    }
+    control_response(result)
+}
+
+pub async fn put_agent_envelope_trust_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    let result = state
+        .agent_controls
+        .set_envelope_trusted(tenant_id, &agent_id, true);
+    if result.is_ok() {
+        info!(
+            "Admin {} trusted batch envelopes of agent {}",
+            admin,
+            scoped_id(tenant_id, &agent_id)
+        );
+    }
+    control_response(result)
+}
+
+pub async fn delete_agent_envelope_trust_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    let result = state
+        .agent_controls
+        .set_envelope_trusted(tenant_id, &agent_id, false);
+    if result.is_ok() {
+        info!(
+            "Admin {} stopped trusting batch envelopes of agent {}",
+            admin,
+            scoped_id(tenant_id, &agent_id)
+        );
+    }
+    control_response(result)
+}
+
+pub async fn pause_agent_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+    request: Option<Json<PauseRequest>>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    let Json(request) = request.unwrap_or_default();
+    let pause = AgentPause {
+        reason: request.reason,
+        paused_by: admin.clone(),
+        paused_at: now_nanos(),
+    };
+    let result = state
+        .agent_controls
+        .set_paused(tenant_id, &agent_id, Some(pause));
+    if result.is_ok() {
+        warn!(
+            "Admin {} paused ingestion for agent {}",
+            admin,
+            scoped_id(tenant_id, &agent_id)
+        );
+        counter!("facto_agent_pauses_total").increment(1);
+    }
+    control_response(result)
+}
+
+pub async fn resume_agent_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(agent_id): Path<String>,
+    Query(query): Query<AgentQuery>,
+) -> Response {
+    let tenant_id = match query.tenant() {
+        Ok(tenant_id) => tenant_id,
+        Err(e) => return tenant_error_response(e),
+    };
+    if state.agent_controls.pause(tenant_id, &agent_id).is_none() {
+        return error_response(StatusCode::CONFLICT, "Agent is not paused");
+    }
+    let result = state.agent_controls.set_paused(tenant_id, &agent_id, None);
+    if result.is_ok() {
+        info!(
+            "Admin {} resumed ingestion for agent {}",
+            admin,
+            scoped_id(tenant_id, &agent_id)
+        );
+    }
+    control_response(result)
+}
+
+// ============================================================================
+// Approved Models
+// ============================================================================
+
+fn model_error_response(e: ModelError) -> Response {
+    let status = match e {
+        ModelError::InvalidList(_) => StatusCode::BAD_REQUEST,
+        ModelError::Unknown(_) => StatusCode::NOT_FOUND,
+        ModelError::Persistence(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error_response(status, e)
+}
+
+pub async fn list_models_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+) -> Response {
+    (StatusCode::OK, Json(state.models.list())).into_response()
+}
+
+pub async fn get_models_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+) -> Response {
+    match state.models.get(&tenant_id) {
+        Some(list) => (StatusCode::OK, Json(list)).into_response(),
+        None => model_error_response(ModelError::Unknown(tenant_id)),
+    }
+}
+
+pub async fn put_models_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+    Json(request): Json<ModelListRequest>,
+) -> Response {
+    match state.models.upsert(&tenant_id, request) {
+        Ok(list) => {
+            info!(
+                "Admin {} set the approved models of tenant {} ({} models, {} mode)",
+                admin,
+                tenant_id,
+                list.list.models.len(),
+                list.list.mode.code()
+            );
+            (StatusCode::OK, Json::<ModelList>(list)).into_response()
+        }
+        Err(e) => model_error_response(e),
+    }
+}
+
+pub async fn delete_models_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+) -> Response {
+    match state.models.remove(&tenant_id) {
+        Ok(list) => {
+            info!(
+                "Admin {} removed the approved models of tenant {}",
+                admin, tenant_id
+            );
+            (StatusCode::OK, Json(list)).into_response()
+        }
+        Err(e) => model_error_response(e),
+    }
+}
+
+// ============================================================================
+// Redaction Policies
+// ============================================================================
+
+fn redaction_error_response(e: RedactionError) -> Response {
+    let status = match e {
+        RedactionError::InvalidPolicy(_) | RedactionError::EncryptionUnavailable => {
+            StatusCode::BAD_REQUEST
+        }
+        RedactionError::Unknown(_) => StatusCode::NOT_FOUND,
+        RedactionError::Persistence(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error_response(status, e)
+}
+
+pub async fn list_redactions_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+) -> Response {
+    (StatusCode::OK, Json(state.redactions.list())).into_response()
+}
+
+pub async fn get_redaction_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+) -> Response {
+    match state.redactions.get(&tenant_id) {
+        Some(policy) => (StatusCode::OK, Json(policy)).into_response(),
+        None => redaction_error_response(RedactionError::Unknown(tenant_id)),
+    }
+}
+
+pub async fn put_redaction_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+    Json(request): Json<RedactionRequest>,
+) -> Response {
+    match state.redactions.upsert(&tenant_id, request) {
+        Ok(policy) => {
+            info!(
+                "Admin {} set the redaction policy of tenant {} ({} rules, {} agents)",
+                admin,
+                tenant_id,
+                policy.policy.rules.len(),
+                policy.policy.agents.len()
+            );
+            (StatusCode::OK, Json::<RedactionPolicy>(policy)).into_response()
+        }
+        Err(e) => redaction_error_response(e),
+    }
+}
+
+pub async fn delete_redaction_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+) -> Response {
+    match state.redactions.remove(&tenant_id) {
+        Ok(policy) => {
+            info!(
+                "Admin {} removed the redaction policy of tenant {}",
+                admin, tenant_id
+            );
+            (StatusCode::OK, Json(policy)).into_response()
+        }
+        Err(e) => redaction_error_response(e),
+    }
+}
+
+// ============================================================================
+// Retention and Legal Holds
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct RetentionResponse {
+    pub policy: RetentionPolicy,
+    /// Whether the change reached NATS
+    pub published: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LegalHoldResponse {
+    pub hold: LegalHold,
+    /// Whether the change reached NATS
+    pub published: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LegalHoldQuery {
+    pub tenant: Option<String>,
+}
+
+fn retention_error_response(e: RetentionError) -> Response {
+    let status = match e {
+        RetentionError::InvalidPolicy(_) | RetentionError::EmptyReason => StatusCode::BAD_REQUEST,
+        RetentionError::Unknown(_) | RetentionError::NotHeld(_) => StatusCode::NOT_FOUND,
+        RetentionError::Persistence(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error_response(status, e)
+}
+
+/// Publish a retention change on the control subject for the indexer
+async fn publish_retention(state: &AppState, control: &RetentionControl) -> bool {
+    let nats_client = state.nats_client.read().await;
+    let Some(ref client) = *nats_client else {
+        warn!("NATS not connected, retention change not published");
+        return false;
+    };
+    let published = client
+        .publish(
+            RETENTION_SUBJECT,
+            serde_json::to_vec(control).unwrap().into(),
+        )
+        .await
+        .is_ok();
+    if !published {
+        warn!("Failed to publish retention change");
+    }
+    published
+}
+
+pub async fn list_retention_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+) -> Response {
+    (StatusCode::OK, Json(state.retention.policies())).into_response()
+}
+
+pub async fn get_retention_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+) -> Response {
+    match state.retention.policy(&tenant_id) {
+        Some(policy) => (StatusCode::OK, Json(policy)).into_response(),
+        None => retention_error_response(RetentionError::Unknown(tenant_id)),
+    }
+}
+
+pub async fn put_retention_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+    Json(request): Json<RetentionRequest>,
+) -> Response {
+    let policy = match state.retention.set_policy(&tenant_id, request) {
+        Ok(policy) => policy,
+        Err(e) => return retention_error_response(e),
+    };
+    info!(
+        "Admin {} set the retention of tenant {} ({:?} days, {} agents)",
+        admin,
+        tenant_id,
+        policy.policy.days,
+        policy.policy.agents.len()
+    );
+    let published = publish_retention(&state, &RetentionControl::Policy(policy.clone())).await;
+    (
+        StatusCode::OK,
+        Json(RetentionResponse { policy, published }),
+    )
+        .into_response()
+}
+
+pub async fn delete_retention_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(tenant_id): Path<String>,
+) -> Response {
+    let policy = match state.retention.remove_policy(&tenant_id) {
+        Ok(policy) => policy,
+        Err(e) => return retention_error_response(e),
+    };
+    info!(
+        "Admin {} removed the retention of tenant {}",
+        admin, tenant_id
+    );
+    let control = RetentionControl::PolicyRemoved {
+        tenant_id,
+        at: now_nanos(),
+    };
+    let published = publish_retention(&state, &control).await;
+    (
+        StatusCode::OK,
+        Json(RetentionResponse { policy, published }),
+    )
+        .into_response()
+}
+
+pub async fn list_legal_holds_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+    Query(query): Query<LegalHoldQuery>,
+) -> Response {
+    (
+        StatusCode::OK,
+        Json(state.retention.holds(query.tenant.as_deref())),
+    )
+        .into_response()
+}
+
+pub async fn put_legal_hold_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path((tenant_id, session_id)): Path<(String, String)>,
+    Json(request): Json<LegalHoldRequest>,
+) -> Response {
+    let hold = match state
+        .retention
+        .hold(&tenant_id, &session_id, request, &admin)
+    {
+        Ok(hold) => hold,
+        Err(e) => return retention_error_response(e),
+    };
+    info!(
+        "Admin {} placed a legal hold on session {} of tenant {}: {}",
+        admin, session_id, tenant_id, hold.reason
+    );
+    counter!("facto_legal_holds_placed_total").increment(1);
+    let published = publish_retention(&state, &RetentionControl::LegalHold(hold.clone())).await;
+    (StatusCode::OK, Json(LegalHoldResponse { hold, published })).into_response()
+}
+
+pub async fn delete_legal_hold_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path((tenant_id, session_id)): Path<(String, String)>,
+) -> Response {
+    let hold = match state.retention.release(&tenant_id, &session_id) {
+        Ok(hold) => hold,
+        Err(e) => return retention_error_response(e),
+    };
+    info!(
+        "Admin {} released the legal hold on session {} of tenant {}",
+        admin, session_id, tenant_id
+    );
+    let control = RetentionControl::LegalHoldReleased {
+        tenant_id,
+        session_id,
+        released_by: admin,
+        at: now_nanos(),
+    };
+    let published = publish_retention(&state, &control).await;
+    (StatusCode::OK, Json(LegalHoldResponse { hold, published })).into_response()
+}
+
+// ============================================================================
+// Webhooks
+// ============================================================================
+
+fn webhook_error_response(e: WebhookError) -> Response {
+    let status = match e {
+        WebhookError::Invalid(_) => StatusCode::BAD_REQUEST,
+        WebhookError::Unknown(_) => StatusCode::NOT_FOUND,
+        WebhookError::Persistence(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error_response(status, e)
+}
+
+fn webhooks_disabled() -> Response {
+    error_response(StatusCode::NOT_FOUND, "Webhooks are not enabled")
+}
+
+pub async fn list_webhooks_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+) -> Response {
+    match state.webhooks {
+        Some(ref webhooks) => (StatusCode::OK, Json(webhooks.list())).into_response(),
+        None => webhooks_disabled(),
+    }
+}
+
+pub async fn get_webhook_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+    Path(webhook_id): Path<String>,
+) -> Response {
+    let Some(ref webhooks) = state.webhooks else {
+        return webhooks_disabled();
+    };
+    match webhooks.get(&webhook_id) {
+        Some(webhook) => (StatusCode::OK, Json(webhook)).into_response(),
+        None => webhook_error_response(WebhookError::Unknown(webhook_id)),
+    }
+}
+
+/// Register a webhook. The response is the only time its signing secret
+/// is shown.
+pub async fn register_webhook_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Json(request): Json<WebhookRequest>,
+) -> Response {
+    let Some(ref webhooks) = state.webhooks else {
+        return webhooks_disabled();
+    };
+    match webhooks.register(request) {
+        Ok(registered) => {
+            info!(
+                "Admin {} registered webhook {} for {}",
+                admin, registered.webhook.id, registered.webhook.url
+            );
+            (StatusCode::CREATED, Json(registered)).into_response()
+        }
+        Err(e) => webhook_error_response(e),
+    }
+}
+
+pub async fn delete_webhook_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(admin): AdminPrincipal,
+    Path(webhook_id): Path<String>,
+) -> Response {
+    let Some(ref webhooks) = state.webhooks else {
+        return webhooks_disabled();
+    };
+    match webhooks.remove(&webhook_id) {
+        Ok(webhook) => {
+            info!("Admin {} removed webhook {}", admin, webhook_id);
+            (StatusCode::OK, Json(webhook)).into_response()
+        }
+        Err(e) => webhook_error_response(e),
+    }
+}
+
+// ============================================================================
+// Shadow Evaluation
+// ============================================================================
+
+pub async fn shadow_report_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+) -> Response {
+    match state.shadow {
+        Some(ref shadow) => (StatusCode::OK, Json(shadow.report())).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, "Shadow evaluation is not enabled"),
+    }
+}
+
+// ============================================================================
+// Checkpoints
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct CheckpointQuery {
+    pub limit: Option<usize>,
+}
+
+pub async fn list_checkpoints_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CheckpointQuery>,
+) -> Response {
+    let checkpoints: Vec<Checkpoint> = state.checkpoints.list(query.limit.unwrap_or(100));
+    (StatusCode::OK, Json(checkpoints)).into_response()
+}
+
+pub async fn proof_handler(
+    State(state): State<Arc<AppState>>,
+    principal: Option<Extension<Principal>>,
+    Path(facto_id): Path<String>,
+) -> Response {
+    let key = match principal.and_then(|Extension(p)| p.tenant_id) {
+        Some(tenant_id) => format!("{}/{}", tenant_id, facto_id),
+        None => facto_id,
+    };
+    match state.checkpoints.proof(&key) {
+        Ok(proof) => (StatusCode::OK, Json(proof)).into_response(),
+        Err(e) => error_response(StatusCode::NOT_FOUND, e),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnchorQuery {
+    pub root: Option<String>,
+}
+
+pub async fn list_anchors_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AnchorQuery>,
+) -> Response {
+    let anchors: Vec<Anchor> = state.anchors.list(query.root.as_deref());
+    (StatusCode::OK, Json(anchors)).into_response()
+}
+
+// ============================================================================
+// Debug Bundles
+// ============================================================================
+
+/// Read a captured debug bundle. Tenant-bound callers only see their own
+/// tenant's bundles; operators see all of them.
+pub async fn get_debug_bundle_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(debug_id): Path<String>,
+) -> Response {
+    let principal = match state.auth.authorize(&headers, Scope::Debug).await {
+        Ok(principal) => principal,
+        Err(e) => return e.into_response(),
+    };
+
+    match state.debug_bundles.get(&debug_id) {
+        Some(bundle)
+            if principal.tenant_id.is_none() || principal.tenant_id == bundle.tenant_id =>
+        {
+            (StatusCode::OK, Json(bundle)).into_response()
+        }
+        _ => error_response(StatusCode::NOT_FOUND, "Debug bundle not found or expired"),
+    }
+}
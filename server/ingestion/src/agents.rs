@@ -0,0 +1,478 @@
+//! Inventory of the agents that register with the server.
+//!
+//! Agents register their SDK, language, declared models and public keys on
+//! startup and send heartbeats while idle. Together with their accepted
+//! events, heartbeats tell when an agent was last seen; an agent unseen for
+//! longer than `AGENT_SILENCE_SECS` is reported silent, once, until it is
+//! seen again. Declared public keys are inventory only: keys are trusted
+//! through the key registry, never through registration.
+//!
+//! Activity is tracked by each replica for the requests it serves.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use dashmap::DashMap;
+use metrics::{counter, gauge};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use crate::admin::error_response;
+use crate::auth::{AdminPrincipal, Principal};
+use crate::scoped_id;
+use crate::store::JsonFile;
+use crate::verification::now_nanos;
+use crate::AppState;
+
+/// NATS subject agents found silent are reported on
+pub const AGENT_SILENCE_SUBJECT: &str = "facto.control.agents.silent";
+
+/// Default for `AGENT_SILENCE_SECS`
+pub const DEFAULT_AGENT_SILENCE_SECS: u64 = 300;
+
+// ============================================================================
+// Records
+// ============================================================================
+
+/// Agent request to register, or to update its registration
+#[derive(Debug, Clone, Deserialize)]
+pub struct AgentRegistration {
+    pub agent_id: String,
+    pub sdk_version: Option<String>,
+    pub language: Option<String>,
+    /// Models the agent declares it calls
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// Base64 Ed25519 public keys the agent declares it signs with
+    #[serde(default)]
+    pub public_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Heartbeat {
+    /// Set when the agent was upgraded since it registered
+    pub sdk_version: Option<String>,
+}
+
+/// A registered agent and when it was last seen
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub agent_id: String,
+    pub sdk_version: Option<String>,
+    pub language: Option<String>,
+    pub models: Vec<String>,
+    pub public_keys: Vec<String>,
+    pub registered_at: i64,
+    pub last_heartbeat_at: Option<i64>,
+    pub last_event_at: Option<i64>,
+    /// Set once the agent was unseen for longer than the silence threshold
+    #[serde(default)]
+    pub silent: bool,
+}
+
+impl AgentRecord {
+    /// Latest of registration, heartbeat and accepted event
+    pub fn last_seen_at(&self) -> i64 {
+        self.registered_at
+            .max(self.last_heartbeat_at.unwrap_or_default())
+            .max(self.last_event_at.unwrap_or_default())
+    }
+
+    /// Clear the silent flag of an agent seen again
+    fn seen(&mut self) {
+        if std::mem::take(&mut self.silent) {
+            info!("Agent {} is sending again", self.agent_id);
+        }
+    }
+}
+
+/// Published on [`AGENT_SILENCE_SUBJECT`] when an agent falls silent
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentSilence {
+    pub tenant_id: Option<String>,
+    pub agent_id: String,
+    pub last_seen_at: i64,
+    pub detected_at: i64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InventoryError {
+    #[error("invalid agent_id: {0}")]
+    InvalidAgentId(String),
+    #[error("agent is not registered: {0}")]
+    Unregistered(String),
+    #[error("agent_id {0} is not the agent of these credentials")]
+    NotAuthorized(String),
+    #[error("failed to persist the agent inventory: {0}")]
+    Persistence(String),
+}
+
+impl InventoryError {
+    fn status(&self) -> StatusCode {
+        match self {
+            InventoryError::InvalidAgentId(_) => StatusCode::BAD_REQUEST,
+            InventoryError::Unregistered(_) => StatusCode::NOT_FOUND,
+            InventoryError::NotAuthorized(_) => StatusCode::FORBIDDEN,
+            InventoryError::Persistence(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl IntoResponse for InventoryError {
+    fn into_response(self) -> Response {
+        error_response(self.status(), self)
+    }
+}
+
+// ============================================================================
+// Inventory
+// ============================================================================
+
+/// Registered agents, persisted to `AGENT_INVENTORY_PATH` when set.
+/// Registrations are saved as they happen; heartbeats and events only
+/// update when agents were last seen, saved with the next silence check.
+pub struct AgentInventory {
+    /// Keyed by tenant-scoped agent id
+    agents: DashMap<String, AgentRecord>,
+    store: JsonFile,
+    silence: i64,
+    /// Last-seen times changed since the inventory was saved
+    dirty: AtomicBool,
+}
+
+impl AgentInventory {
+    /// Report agents unseen for longer than `silence`
+    pub fn new(path: Option<PathBuf>, silence: Duration) -> anyhow::Result<Self> {
+        let store = JsonFile::new(path);
+        let agents: BTreeMap<String, AgentRecord> = store.load()?;
+        info!("Loaded {} registered agents", agents.len());
+        Ok(Self {
+            agents: agents.into_iter().collect(),
+            store,
+            silence: silence.as_nanos() as i64,
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    /// Registered agents of every tenant, or of one, by agent id
+    pub fn list(&self, tenant_id: Option<Option<&str>>) -> Vec<AgentRecord> {
+        let mut agents: Vec<AgentRecord> = self
+            .agents
+            .iter()
+            .filter(|entry| tenant_id.is_none_or(|t| entry.tenant_id.as_deref() == t))
+            .map(|entry| entry.value().clone())
+            .collect();
+        agents.sort_by(|a, b| (&a.tenant_id, &a.agent_id).cmp(&(&b.tenant_id, &b.agent_id)));
+        agents
+    }
+
+    /// Register an agent, or replace its registered metadata
+    pub fn register(
+        &self,
+        tenant_id: Option<&str>,
+        registration: AgentRegistration,
+        now: i64,
+    ) -> Result<AgentRecord, InventoryError> {
+        let agent_id = registration.agent_id;
+        if agent_id.is_empty() || agent_id.contains(['.', '*', '>', ' ']) {
+            return Err(InventoryError::InvalidAgentId(agent_id));
+        }
+        let key = scoped_id(tenant_id, &agent_id);
+        let previous = self.agents.get(&key).map(|entry| entry.value().clone());
+        let record = AgentRecord {
+            tenant_id: tenant_id.map(String::from),
+            sdk_version: registration.sdk_version,
+            language: registration.language,
+            models: registration.models,
+            public_keys: registration.public_keys,
+            registered_at: now,
+            last_heartbeat_at: previous.as_ref().and_then(|p| p.last_heartbeat_at),
+            last_event_at: previous.as_ref().and_then(|p| p.last_event_at),
+            silent: false,
+            agent_id,
+        };
+        if previous.as_ref().is_some_and(|p| p.silent) {
+            info!("Agent {} registered again", record.agent_id);
+        }
+        let previous = self.agents.insert(key.clone(), record.clone());
+        if let Err(e) = self.save() {
+            match previous {
+                Some(previous) => self.agents.insert(key, previous),
+                None => self.agents.remove(&key).map(|(_, record)| record),
+            };
+            return Err(InventoryError::Persistence(e.to_string()));
+        }
+        Ok(record)
+    }
+
+    /// Record a heartbeat of a registered agent
+    pub fn heartbeat(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+        heartbeat: Heartbeat,
+        now: i64,
+    ) -> Result<AgentRecord, InventoryError> {
+        let mut record = self
+            .agents
+            .get_mut(&scoped_id(tenant_id, agent_id))
+            .ok_or_else(|| InventoryError::Unregistered(agent_id.to_string()))?;
+        record.last_heartbeat_at = Some(now);
+        if heartbeat.sdk_version.is_some() {
+            record.sdk_version = heartbeat.sdk_version;
+        }
+        record.seen();
+        self.dirty.store(true, Ordering::Relaxed);
+        Ok(record.clone())
+    }
+
+    /// Record an accepted event; events of unregistered agents are ignored
+    pub fn saw_event(&self, tenant_id: Option<&str>, agent_id: &str, at: i64) {
+        if let Some(mut record) = self.agents.get_mut(&scoped_id(tenant_id, agent_id)) {
+            if record.last_event_at < Some(at) {
+                record.last_event_at = Some(at);
+            }
+            record.seen();
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Flag the agents unseen for longer than the silence threshold and
+    /// return the ones that just fell silent
+    pub fn check_silence(&self, now: i64) -> Vec<AgentSilence> {
+        let mut silenced = Vec::new();
+        for mut record in self.agents.iter_mut() {
+            let last_seen_at = record.last_seen_at();
+            if record.silent || now.saturating_sub(last_seen_at) <= self.silence {
+                continue;
+            }
+            record.silent = true;
+            silenced.push(AgentSilence {
+                tenant_id: record.tenant_id.clone(),
+                agent_id: record.agent_id.clone(),
+                last_seen_at,
+                detected_at: now,
+            });
+        }
+        if !silenced.is_empty() {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        silenced
+    }
+
+    /// Number of registered agents, and of those silent
+    pub fn counts(&self) -> (usize, usize) {
+        let silent = self.agents.iter().filter(|entry| entry.silent).count();
+        (self.agents.len(), silent)
+    }
+
+    /// Save last-seen times changed since the last save
+    pub fn flush(&self) -> std::io::Result<()> {
+        match self.dirty.swap(false, Ordering::Relaxed) {
+            true => self.save().inspect_err(|_| {
+                self.dirty.store(true, Ordering::Relaxed);
+            }),
+            false => Ok(()),
+        }
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let agents: BTreeMap<String, AgentRecord> = self
+            .agents
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        self.store.save(&agents)
+    }
+}
+
+/// Check for silent agents every `interval`, report them, and save when
+/// agents were last seen
+pub async fn run_silence_checks(state: Arc<AppState>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        for silence in state.agents.check_silence(now_nanos()) {
+            warn!(
+                "Agent {} has sent no events or heartbeats since {}",
+                scoped_id(silence.tenant_id.as_deref(), &silence.agent_id),
+                silence.last_seen_at
+            );
+            counter!("facto_agent_silences_total").increment(1);
+            publish_silence(&state, &silence).await;
+        }
+        let (registered, silent) = state.agents.counts();
+        gauge!("facto_agents_registered").set(registered as f64);
+        gauge!("facto_agents_silent").set(silent as f64);
+        if let Err(e) = state.agents.flush() {
+            warn!("Failed to save the agent inventory: {}", e);
+        }
+    }
+}
+
+async fn publish_silence(state: &AppState, silence: &AgentSilence) {
+    let Some(client) = state.connected_client().await else {
+        return;
+    };
+    let mut headers = async_nats::HeaderMap::new();
+    headers.insert(
+        async_nats::header::NATS_MESSAGE_ID,
+        format!(
+            "agent-silent-{}-{}",
+            scoped_id(silence.tenant_id.as_deref(), &silence.agent_id),
+            silence.last_seen_at
+        )
+        .as_str(),
+    );
+    if let Err(e) = client
+        .publish_with_headers(
+            AGENT_SILENCE_SUBJECT,
+            headers,
+            serde_json::to_vec(silence).unwrap().into(),
+        )
+        .await
+    {
+        warn!(
+            "Failed to publish the silence of agent {}: {}",
+            silence.agent_id, e
+        );
+    }
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct InventoryQuery {
+    /// Only agents of this tenant; `default` for agents without one
+    pub tenant_id: Option<String>,
+}
+
+/// The tenant and bound agent of an agent's credentials
+fn caller(principal: Option<Extension<Principal>>) -> (Option<String>, Option<String>) {
+    principal
+        .map(|Extension(p)| (p.tenant_id, p.agent_id))
+        .unwrap_or_default()
+}
+
+/// `POST /v1/agents/register`
+pub async fn register_agent_handler(
+    State(state): State<Arc<AppState>>,
+    principal: Option<Extension<Principal>>,
+    Json(registration): Json<AgentRegistration>,
+) -> Response {
+    let (tenant_id, bound_agent) = caller(principal);
+    if bound_agent.is_some_and(|agent_id| agent_id != registration.agent_id) {
+        return InventoryError::NotAuthorized(registration.agent_id).into_response();
+    }
+    match state
+        .agents
+        .register(tenant_id.as_deref(), registration, now_nanos())
+    {
+        Ok(record) => {
+            info!(
+                "Agent {} registered ({} {})",
+                scoped_id(tenant_id.as_deref(), &record.agent_id),
+                record.language.as_deref().unwrap_or("unknown language"),
+                record.sdk_version.as_deref().unwrap_or("unknown version"),
+            );
+            counter!("facto_agent_registrations_total").increment(1);
+            (StatusCode::OK, Json(record)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// `POST /v1/agents/{agent_id}/heartbeat`, with an optional body
+pub async fn heartbeat_handler(
+    State(state): State<Arc<AppState>>,
+    principal: Option<Extension<Principal>>,
+    Path(agent_id): Path<String>,
+    heartbeat: Option<Json<Heartbeat>>,
+) -> Response {
+    let (tenant_id, bound_agent) = caller(principal);
+    if bound_agent.is_some_and(|bound| bound != agent_id) {
+        return InventoryError::NotAuthorized(agent_id).into_response();
+    }
+    let heartbeat = heartbeat.map(|Json(h)| h).unwrap_or_default();
+    match state
+        .agents
+        .heartbeat(tenant_id.as_deref(), &agent_id, heartbeat, now_nanos())
+    {
+        Ok(record) => (StatusCode::OK, Json(record)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// `GET /v1/agents`: the inventory of registered agents
+pub async fn list_agents_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+    Query(query): Query<InventoryQuery>,
+) -> Response {
+    let tenant_id = query
+        .tenant_id
+        .as_deref()
+        .map(|t| Some(t).filter(|t| *t != crate::tenants::DEFAULT_TENANT));
+    (StatusCode::OK, Json(state.agents.list(tenant_id))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(agent_id: &str) -> AgentRegistration {
+        AgentRegistration {
+            agent_id: agent_id.to_string(),
+            sdk_version: Some("0.4.0".to_string()),
+            language: Some("python".to_string()),
+            models: vec!["gpt-4o".to_string()],
+            public_keys: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_agents_fall_silent_once_until_seen() {
+        let second = 1_000_000_000;
+        let inventory = AgentInventory::new(None, Duration::from_secs(60)).unwrap();
+        inventory.register(None, registration("a"), 0).unwrap();
+        inventory
+            .register(Some("acme"), registration("a"), 0)
+            .unwrap();
+        assert!(inventory.register(None, registration("a.b"), 0).is_err());
+        assert!(matches!(
+            inventory.heartbeat(None, "b", Heartbeat::default(), 0),
+            Err(InventoryError::Unregistered(_))
+        ));
+
+        inventory.saw_event(Some("acme"), "a", 50 * second);
+        let silenced = inventory.check_silence(90 * second);
+        assert_eq!(silenced.len(), 1);
+        assert_eq!(silenced[0].tenant_id, None);
+        assert!(inventory.check_silence(100 * second).is_empty());
+        assert_eq!(inventory.counts(), (2, 1));
+
+        let record = inventory
+            .heartbeat(None, "a", Heartbeat::default(), 120 * second)
+            .unwrap();
+        assert!(!record.silent);
+        assert_eq!(record.last_seen_at(), 120 * second);
+        assert_eq!(inventory.check_silence(150 * second).len(), 1);
+        assert_eq!(inventory.list(Some(Some("acme")))[0].agent_id, "a");
+        assert_eq!(inventory.list(None).len(), 2);
+    }
+}
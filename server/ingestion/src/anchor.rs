@@ -0,0 +1,343 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{path::PathBuf, sync::RwLock, time::Duration};
+use tracing::{info, warn};
+
+use crate::checkpoint::Checkpoint;
+use crate::store::JsonFile;
+use crate::verification::now_nanos;
+
+// ============================================================================
+// Anchor Records
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnchorMethod {
+    /// RFC 3161 timestamp authority; the attestation is the DER TimeStampResp
+    Rfc3161,
+    /// OpenTimestamps calendar; the attestation is a detached `.ots` file
+    OpenTimestamps,
+}
+
+/// External proof that a checkpoint root existed at a point in time.
+///
+/// Both services timestamp `digest`, the SHA-256 of the 32 raw root bytes.
+/// For OpenTimestamps those bytes are the stamped file: write them out and
+/// run `ots upgrade` / `ots verify` against the attestation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Anchor {
+    pub root: String,
+    pub checkpoint_sequence: u64,
+    pub signer_id: String,
+    pub method: AnchorMethod,
+    pub service: String,
+    pub digest: String,
+    pub attestation: String,
+    pub anchored_at: i64,
+}
+
+/// Anchors obtained so far, persisted across restarts
+pub struct Anchors {
+    anchors: RwLock<Vec<Anchor>>,
+    store: JsonFile,
+}
+
+impl Anchors {
+    pub fn new(path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let store = JsonFile::new(path);
+        let anchors: Vec<Anchor> = store.load()?;
+        info!("Loaded {} checkpoint anchors", anchors.len());
+
+        Ok(Self {
+            anchors: RwLock::new(anchors),
+            store,
+        })
+    }
+
+    /// Anchors, optionally only those of one root
+    pub fn list(&self, root: Option<&str>) -> Vec<Anchor> {
+        self.anchors
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|a| root.is_none_or(|r| a.root == r))
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_anchored(&self, root: &str) -> bool {
+        self.anchors.read().unwrap().iter().any(|a| a.root == root)
+    }
+
+    pub fn add(&self, new: Vec<Anchor>) -> std::io::Result<()> {
+        let mut anchors = self.anchors.write().unwrap();
+        let mut updated = anchors.clone();
+        updated.extend(new);
+        self.store.save(&updated)?;
+        *anchors = updated;
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Anchoring Services
+// ============================================================================
+
+/// Submits checkpoint roots to the configured timestamping services
+pub struct Anchorer {
+    client: reqwest::Client,
+    tsa_url: Option<String>,
+    ots_calendars: Vec<String>,
+}
+
+impl Anchorer {
+    /// None when no service is configured
+    pub fn new(tsa_url: Option<String>, ots_calendars: Vec<String>) -> Option<Self> {
+        if tsa_url.is_none() && ots_calendars.is_empty() {
+            return None;
+        }
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .expect("Failed to build HTTP client");
+        Some(Self {
+            client,
+            tsa_url,
+            ots_calendars,
+        })
+    }
+
+    /// Anchor a checkpoint with every service; failures are logged and skipped
+    pub async fn anchor(&self, checkpoint: &Checkpoint) -> Vec<Anchor> {
+        let root = hex::decode(&checkpoint.root).unwrap_or_default();
+        let digest: [u8; 32] = Sha256::digest(&root).into();
+
+        let mut requests = Vec::new();
+        if let Some(ref url) = self.tsa_url {
+            requests.push((AnchorMethod::Rfc3161, url.clone()));
+        }
+        for calendar in &self.ots_calendars {
+            requests.push((AnchorMethod::OpenTimestamps, calendar.clone()));
+        }
+
+        let mut anchors = Vec::new();
+        for (method, service) in requests {
+            let result = match method {
+                AnchorMethod::Rfc3161 => self.rfc3161(&service, &digest).await,
+                AnchorMethod::OpenTimestamps => self.opentimestamps(&service, &digest).await,
+            };
+            let label = match method {
+                AnchorMethod::Rfc3161 => "rfc3161",
+                AnchorMethod::OpenTimestamps => "opentimestamps",
+            };
+            match result {
+                Ok(attestation) => {
+                    counter!("facto_anchors_total", "method" => label, "outcome" => "ok")
+                        .increment(1);
+                    anchors.push(Anchor {
+                        root: checkpoint.root.clone(),
+                        checkpoint_sequence: checkpoint.sequence,
+                        signer_id: checkpoint.signer_id.clone(),
+                        method,
+                        service,
+                        digest: hex::encode(digest),
+                        attestation: BASE64.encode(attestation),
+                        anchored_at: now_nanos(),
+                    });
+                }
+                Err(e) => {
+                    counter!("facto_anchors_total", "method" => label, "outcome" => "failed")
+                        .increment(1);
+                    warn!("Failed to anchor checkpoint with {}: {}", service, e);
+                }
+            }
+        }
+        anchors
+    }
+
+    async fn rfc3161(&self, url: &str, digest: &[u8; 32]) -> anyhow::Result<Vec<u8>> {
+        let nonce: u64 = rand::random();
+        let response = self
+            .client
+            .post(url)
+            .header("Content-Type", "application/timestamp-query")
+            .header("Accept", "application/timestamp-reply")
+            .body(timestamp_request(digest, nonce))
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        match timestamp_response_status(&response) {
+            Some(0 | 1) => Ok(response.to_vec()),
+            Some(status) => {
+                anyhow::bail!("timestamp authority refused request (status {})", status)
+            }
+            None => anyhow::bail!("malformed timestamp response"),
+        }
+    }
+
+    async fn opentimestamps(&self, calendar: &str, digest: &[u8; 32]) -> anyhow::Result<Vec<u8>> {
+        let timestamp = self
+            .client
+            .post(format!("{}/digest", calendar.trim_end_matches('/')))
+            .header("Accept", "application/vnd.opentimestamps.v1")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(digest.to_vec())
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        Ok(detached_ots_file(digest, &timestamp))
+    }
+}
+
+// ============================================================================
+// Wire Formats
+// ============================================================================
+
+/// DER id-sha256 AlgorithmIdentifier (2.16.840.1.101.3.4.2.1, NULL params)
+const SHA256_ALGORITHM: [u8; 15] = [
+    0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00,
+];
+
+/// Header of a detached OpenTimestamps proof, major version 1
+const OTS_HEADER_MAGIC: &[u8] =
+    b"\x00OpenTimestamps\x00\x00Proof\x00\xbf\x89\xe2\xe8\x84\xe8\x92\x94";
+const OTS_MAJOR_VERSION: u8 = 0x01;
+const OTS_OP_SHA256: u8 = 0x08;
+
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend(bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+/// DER INTEGER for a non-negative value
+fn der_uint(value: u64) -> Vec<u8> {
+    let mut bytes: Vec<u8> = value
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|b| *b == 0)
+        .collect();
+    if bytes.first().is_none_or(|b| b & 0x80 != 0) {
+        bytes.insert(0, 0);
+    }
+    der(0x02, &bytes)
+}
+
+/// Split the first DER element off `input`: (tag, content, rest)
+fn der_read(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, input) = input.split_first()?;
+    let (&first, mut input) = input.split_first()?;
+    let len = if first < 0x80 {
+        first as usize
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || input.len() < count {
+            return None;
+        }
+        let len = input[..count]
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        input = &input[count..];
+        len
+    };
+    if input.len() < len {
+        return None;
+    }
+    Some((tag, &input[..len], &input[len..]))
+}
+
+/// RFC 3161 TimeStampReq for a SHA-256 digest, asking for the TSA certificate
+fn timestamp_request(digest: &[u8; 32], nonce: u64) -> Vec<u8> {
+    let mut imprint = SHA256_ALGORITHM.to_vec();
+    imprint.extend(der(0x04, digest));
+
+    let mut request = der_uint(1);
+    request.extend(der(0x30, &imprint));
+    request.extend(der_uint(nonce));
+    request.extend([0x01, 0x01, 0xff]); // certReq TRUE
+    der(0x30, &request)
+}
+
+/// PKIStatus of an RFC 3161 TimeStampResp
+fn timestamp_response_status(response: &[u8]) -> Option<u64> {
+    let (0x30, response, _) = der_read(response)? else {
+        return None;
+    };
+    let (0x30, status_info, _) = der_read(response)? else {
+        return None;
+    };
+    let (0x02, status, _) = der_read(status_info)? else {
+        return None;
+    };
+    if status.is_empty() || status.len() > 8 {
+        return None;
+    }
+    Some(status.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64))
+}
+
+/// A detached `.ots` proof for a file whose SHA-256 is `digest`, completed
+/// with the calendar's pending timestamp
+fn detached_ots_file(digest: &[u8; 32], timestamp: &[u8]) -> Vec<u8> {
+    let mut file = OTS_HEADER_MAGIC.to_vec();
+    file.push(OTS_MAJOR_VERSION);
+    file.push(OTS_OP_SHA256);
+    file.extend_from_slice(digest);
+    file.extend_from_slice(timestamp);
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_request_encoding() {
+        let digest = [0xab; 32];
+        let request = timestamp_request(&digest, 0x80);
+
+        let (tag, body, rest) = der_read(&request).unwrap();
+        assert_eq!((tag, rest.len()), (0x30, 0));
+        let (tag, version, body) = der_read(body).unwrap();
+        assert_eq!((tag, version), (0x02, &[1u8][..]));
+        let (tag, imprint, body) = der_read(body).unwrap();
+        assert_eq!(tag, 0x30);
+        assert_eq!(&imprint[..SHA256_ALGORITHM.len()], &SHA256_ALGORITHM);
+        let (tag, hashed, _) = der_read(&imprint[SHA256_ALGORITHM.len()..]).unwrap();
+        assert_eq!((tag, hashed), (0x04, &digest[..]));
+        // A nonce with the high bit set gets a leading zero to stay positive
+        let (tag, nonce, body) = der_read(body).unwrap();
+        assert_eq!((tag, nonce), (0x02, &[0x00, 0x80][..]));
+        assert_eq!(body, &[0x01, 0x01, 0xff]);
+    }
+
+    #[test]
+    fn test_timestamp_response_status() {
+        let granted = der(0x30, &der(0x30, &der_uint(0)));
+        assert_eq!(timestamp_response_status(&granted), Some(0));
+
+        let rejection = der(0x30, &der(0x30, &der_uint(2)));
+        assert_eq!(timestamp_response_status(&rejection), Some(2));
+
+        assert_eq!(timestamp_response_status(&[0x30, 0x05, 0x30]), None);
+    }
+}
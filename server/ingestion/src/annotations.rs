@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::{collections::HashMap, path::PathBuf, sync::RwLock};
+use tracing::info;
+
+use crate::chain::ChainHead;
+use crate::scoped_id;
+use crate::store::JsonFile;
+use crate::verification::{now_nanos, ServerSigner};
+
+/// NATS subject signed annotations are published on
+pub const ANNOTATION_SUBJECT: &str = "facto.control.annotations";
+
+// ============================================================================
+// Records
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    #[default]
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+/// Investigator request to annotate a session
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnnotationRequest {
+    pub finding: String,
+    #[serde(default)]
+    pub severity: Severity,
+    pub ticket_url: Option<String>,
+    /// Specific event of the session the annotation refers to
+    pub facto_id: Option<String>,
+}
+
+/// A server-signed investigator note about a session.
+///
+/// Annotations never enter the agent's own hash chain. They form a separate
+/// chain per session through `prev_hash`, and record the agent chain head
+/// they were written against in `head_hash`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    pub annotation_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub session_id: String,
+    pub facto_id: Option<String>,
+    pub author: String,
+    pub finding: String,
+    pub severity: Severity,
+    pub ticket_url: Option<String>,
+    pub created_at: i64,
+    pub head_hash: Option<String>,
+    /// Hash of the previous annotation of the session, empty for the first
+    pub prev_hash: String,
+    pub signer_public_key: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub signature: String,
+}
+
+impl Annotation {
+    /// The bytes covered by the annotation signature: the sorted-key JSON of
+    /// the annotation with `signature` omitted
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let unsigned = Self {
+            signature: String::new(),
+            ..self.clone()
+        };
+        let value = serde_json::to_value(&unsigned).unwrap_or_default();
+        serde_json::to_vec(&value).unwrap_or_default()
+    }
+
+    /// SHA3-256 of the signing payload, referenced by the next annotation
+    pub fn hash(&self) -> String {
+        hex::encode(Sha3_256::digest(self.signing_payload()))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AnnotationError {
+    #[error("finding must not be empty")]
+    EmptyFinding,
+    #[error("failed to persist annotations: {0}")]
+    Persistence(String),
+}
+
+// ============================================================================
+// Annotation Store
+// ============================================================================
+
+/// Investigator annotations by tenant-scoped session id, persisted across
+/// restarts
+pub struct SessionAnnotations {
+    annotations: RwLock<HashMap<String, Vec<Annotation>>>,
+    store: JsonFile,
+}
+
+impl SessionAnnotations {
+    pub fn new(path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let store = JsonFile::new(path);
+        let annotations: HashMap<String, Vec<Annotation>> = store.load()?;
+        info!("Loaded annotations for {} sessions", annotations.len());
+
+        Ok(Self {
+            annotations: RwLock::new(annotations),
+            store,
+        })
+    }
+
+    /// Annotations of a session, oldest first
+    pub fn list(&self, tenant_id: Option<&str>, session_id: &str) -> Vec<Annotation> {
+        self.annotations
+            .read()
+            .unwrap()
+            .get(&scoped_id(tenant_id, session_id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Sign and append an annotation to a session
+    pub fn add(
+        &self,
+        tenant_id: Option<&str>,
+        session_id: &str,
+        request: AnnotationRequest,
+        author: &str,
+        head: Option<ChainHead>,
+        signer: &ServerSigner,
+    ) -> Result<Annotation, AnnotationError> {
+        if request.finding.trim().is_empty() {
+            return Err(AnnotationError::EmptyFinding);
+        }
+
+        let key = scoped_id(tenant_id, session_id);
+        let mut annotations = self.annotations.write().unwrap();
+        let prev_hash = annotations
+            .get(&key)
+            .and_then(|a| a.last())
+            .map(Annotation::hash)
+            .unwrap_or_default();
+
+        let mut annotation = Annotation {
+            annotation_id: uuid::Uuid::new_v4().to_string(),
+            tenant_id: tenant_id.map(str::to_string),
+            session_id: session_id.to_string(),
+            facto_id: request.facto_id,
+            author: author.to_string(),
+            finding: request.finding,
+            severity: request.severity,
+            ticket_url: request.ticket_url,
+            created_at: now_nanos(),
+            head_hash: head.map(|h| h.event_hash),
+            prev_hash,
+            signer_public_key: signer.public_key_base64(),
+            signature: String::new(),
+        };
+        annotation.signature = signer.sign_base64(&annotation.signing_payload());
+
+        let mut updated = annotations.clone();
+        updated.entry(key).or_default().push(annotation.clone());
+        self.store
+            .save(&updated)
+            .map_err(|e| AnnotationError::Persistence(e.to_string()))?;
+        *annotations = updated;
+
+        Ok(annotation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+
+    fn request(finding: &str) -> AnnotationRequest {
+        AnnotationRequest {
+            finding: finding.to_string(),
+            severity: Severity::High,
+            ticket_url: Some("https://tickets.example.com/INC-7".to_string()),
+            facto_id: None,
+        }
+    }
+
+    #[test]
+    fn test_annotations_chain_per_session() {
+        let annotations = SessionAnnotations::new(None).unwrap();
+        let signer = ServerSigner::new("test".to_string(), SigningKey::from_bytes(&[5u8; 32]));
+
+        let first = annotations
+            .add(
+                None,
+                "session-1",
+                request("prompt injection"),
+                "alice",
+                None,
+                &signer,
+            )
+            .unwrap();
+        let second = annotations
+            .add(
+                None,
+                "session-1",
+                request("data exfiltration"),
+                "bob",
+                None,
+                &signer,
+            )
+            .unwrap();
+        let other = annotations
+            .add(
+                Some("acme"),
+                "session-1",
+                request("unrelated"),
+                "alice",
+                None,
+                &signer,
+            )
+            .unwrap();
+
+        assert!(first.prev_hash.is_empty());
+        assert_eq!(second.prev_hash, first.hash());
+        assert!(other.prev_hash.is_empty());
+        assert_eq!(annotations.list(None, "session-1").len(), 2);
+        assert_eq!(annotations.list(Some("acme"), "session-1").len(), 1);
+        assert!(annotations.list(None, "session-3").is_empty());
+    }
+
+    #[test]
+    fn test_empty_finding_is_rejected() {
+        let annotations = SessionAnnotations::new(None).unwrap();
+        let signer = ServerSigner::new("test".to_string(), SigningKey::from_bytes(&[5u8; 32]));
+        assert!(matches!(
+            annotations.add(None, "session-1", request("  "), "alice", None, &signer),
+            Err(AnnotationError::EmptyFinding)
+        ));
+    }
+}
@@ -0,0 +1,633 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use jsonwebtoken::{
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::admin::error_response;
+use crate::tenants::validate_tenant_id;
+use crate::tls::ClientIdentity;
+use crate::AppState;
+
+/// Header carrying a static API key, as an alternative to `Authorization: Bearer`
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Minimum time between JWKS refetches triggered by an unknown key id
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(10);
+
+// ============================================================================
+// Principals and Scopes
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Submit events
+    Ingest,
+    /// Use the admin API
+    Admin,
+    /// Capture and read request debug bundles
+    Debug,
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("facto:").unwrap_or(s) {
+            "ingest" => Ok(Scope::Ingest),
+            "admin" => Ok(Scope::Admin),
+            "debug" => Ok(Scope::Debug),
+            other => Err(anyhow::anyhow!("unknown scope: {}", other)),
+        }
+    }
+}
+
+/// An authenticated caller: an API key name or a JWT subject
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// Tenant the caller acts for; `None` for operators and single-tenant use
+    pub tenant_id: Option<String>,
+    /// The only agent the caller may submit events for, for client
+    /// certificates
+    pub agent_id: Option<String>,
+    /// Events are accepted in sandbox mode (see `sandbox`)
+    pub sandbox: bool,
+}
+
+impl Principal {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("Missing credentials")]
+    Missing,
+    #[error("Invalid credentials")]
+    Invalid,
+    #[error("Credentials lack the {0:?} scope")]
+    InsufficientScope(Scope),
+    #[error("Token keys unavailable: {0}")]
+    KeysUnavailable(String),
+}
+
+impl AuthError {
+    fn reason(&self) -> &'static str {
+        match self {
+            AuthError::Missing => "missing",
+            AuthError::Invalid => "invalid",
+            AuthError::InsufficientScope(_) => "scope",
+            AuthError::KeysUnavailable(_) => "jwks_unavailable",
+        }
+    }
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        counter!("facto_auth_rejections_total", "reason" => self.reason()).increment(1);
+        let status = match self {
+            AuthError::Missing | AuthError::Invalid => StatusCode::UNAUTHORIZED,
+            AuthError::InsufficientScope(_) => StatusCode::FORBIDDEN,
+            AuthError::KeysUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        error_response(status, self)
+    }
+}
+
+// ============================================================================
+// Static API Keys
+// ============================================================================
+
+/// Named static API keys with scopes, stored by SHA3-256 digest
+#[derive(Default)]
+pub struct ApiKeys {
+    by_digest: HashMap<String, Principal>,
+}
+
+impl ApiKeys {
+    /// Parse `name:key[:scope+scope[:tenant[:sandbox]]]` entries separated
+    /// by commas. Entries without scopes get `default_scopes`; the tenant may
+    /// be left empty for tenantless sandbox keys.
+    pub fn parse(spec: &str, default_scopes: &[Scope]) -> anyhow::Result<Self> {
+        let mut by_digest = HashMap::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let mut parts = entry.splitn(5, ':');
+            let (Some(name), Some(key)) = (parts.next(), parts.next()) else {
+                anyhow::bail!("API key must be name:key[:scopes[:tenant[:sandbox]]]");
+            };
+            let scopes = match parts.next() {
+                Some(scopes) => scopes
+                    .split('+')
+                    .map(Scope::from_str)
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+                None => default_scopes.to_vec(),
+            };
+            let tenant = parts.next();
+            let sandbox = match parts.next() {
+                Some("sandbox") => true,
+                Some(other) => anyhow::bail!("unknown API key mode: {}", other),
+                None => false,
+            };
+            let tenant_id = match tenant {
+                Some("") if sandbox => None,
+                tenant => tenant.map(str::to_string),
+            };
+            if let Some(ref tenant_id) = tenant_id {
+                validate_tenant_id(tenant_id)?;
+            }
+            by_digest.insert(
+                digest(key),
+                Principal {
+                    name: name.to_string(),
+                    scopes,
+                    tenant_id,
+                    agent_id: None,
+                    sandbox,
+                },
+            );
+        }
+        Ok(Self { by_digest })
+    }
+
+    /// Add the keys of `other`, which take precedence on collisions
+    pub fn extend(&mut self, other: ApiKeys) {
+        self.by_digest.extend(other.by_digest);
+    }
+
+    fn grants(&self, scope: Scope) -> bool {
+        self.by_digest.values().any(|p| p.has_scope(scope))
+    }
+
+    fn authenticate(&self, key: &str) -> Option<&Principal> {
+        self.by_digest.get(&digest(key))
+    }
+}
+
+fn digest(token: &str) -> String {
+    hex::encode(Sha3_256::digest(token.as_bytes()))
+}
+
+// ============================================================================
+// JWT Bearer Tokens
+// ============================================================================
+
+pub struct JwtConfig {
+    pub issuer: String,
+    pub jwks_url: String,
+    pub audience: Option<String>,
+    /// Algorithm of the signing keys whose JWK does not name one
+    pub algorithm: Option<Algorithm>,
+    /// How long fetched signing keys are trusted before refetching
+    pub jwks_ttl: Duration,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    /// Space-separated scopes, e.g. `facto:ingest facto:admin`
+    #[serde(default)]
+    scope: String,
+    tenant_id: Option<String>,
+    #[serde(default)]
+    sandbox: bool,
+}
+
+/// Validates JWTs against the issuer's published JWKS
+pub struct JwtValidator {
+    config: JwtConfig,
+    http: reqwest::Client,
+    jwks: RwLock<Option<(Instant, JwkSet)>>,
+}
+
+impl JwtValidator {
+    pub fn new(config: JwtConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            jwks: RwLock::new(None),
+        }
+    }
+
+    async fn validate(&self, token: &str) -> Result<Principal, AuthError> {
+        let header = jsonwebtoken::decode_header(token).map_err(|_| AuthError::Invalid)?;
+        let kid = header.kid.ok_or(AuthError::Invalid)?;
+        let (key, algorithm) = self.decoding_key(&kid).await?;
+        // The key decides the algorithm, never the token
+        if header.alg != algorithm {
+            return Err(AuthError::Invalid);
+        }
+
+        let mut validation = Validation::new(algorithm);
+        validation.set_issuer(&[&self.config.issuer]);
+        match self.config.audience {
+            Some(ref audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        let claims = jsonwebtoken::decode::<Claims>(token, &key, &validation)
+            .map_err(|_| AuthError::Invalid)?
+            .claims;
+        if let Some(ref tenant_id) = claims.tenant_id {
+            validate_tenant_id(tenant_id).map_err(|_| AuthError::Invalid)?;
+        }
+        Ok(Principal {
+            name: claims.sub,
+            scopes: claims
+                .scope
+                .split_whitespace()
+                .filter_map(|s| s.parse().ok())
+                .collect(),
+            tenant_id: claims.tenant_id,
+            agent_id: None,
+            sandbox: claims.sandbox,
+        })
+    }
+
+    /// Find the signing key for `kid` and its algorithm, refetching the JWKS
+    /// when it is stale or does not know the key
+    async fn decoding_key(&self, kid: &str) -> Result<(DecodingKey, Algorithm), AuthError> {
+        let refetch = {
+            let jwks = self.jwks.read().await;
+            match *jwks {
+                Some((fetched_at, ref set)) => {
+                    if fetched_at.elapsed() < self.config.jwks_ttl {
+                        if let Some(jwk) = set.find(kid) {
+                            return self.key_of(jwk);
+                        }
+                    }
+                    fetched_at.elapsed() >= JWKS_MIN_REFRESH
+                }
+                None => true,
+            }
+        };
+
+        if refetch {
+            self.refresh().await?;
+        }
+
+        let jwks = self.jwks.read().await;
+        let jwk = jwks
+            .as_ref()
+            .and_then(|(_, set)| set.find(kid))
+            .ok_or(AuthError::Invalid)?;
+        self.key_of(jwk)
+    }
+
+    /// The decoding key of a JWK and the algorithm it is pinned to: the
+    /// JWK's `alg`, or else the configured algorithm
+    fn key_of(&self, jwk: &Jwk) -> Result<(DecodingKey, Algorithm), AuthError> {
+        let algorithm = match jwk.common.key_algorithm {
+            Some(alg) => alg.to_string().parse().map_err(|_| AuthError::Invalid)?,
+            None => self.config.algorithm.ok_or(AuthError::Invalid)?,
+        };
+        let key = DecodingKey::from_jwk(jwk).map_err(|_| AuthError::Invalid)?;
+        Ok((key, algorithm))
+    }
+
+    async fn refresh(&self) -> Result<(), AuthError> {
+        let set: JwkSet = self
+            .http
+            .get(&self.config.jwks_url)
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AuthError::KeysUnavailable(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| AuthError::KeysUnavailable(e.to_string()))?;
+
+        info!("Fetched {} JWT signing keys", set.keys.len());
+        *self.jwks.write().await = Some((Instant::now(), set));
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Authenticator
+// ============================================================================
+
+/// Authenticates callers by static API key or JWT bearer token.
+///
+/// Ingestion is only authenticated when some credential source can grant the
+/// ingest scope, so deployments without API keys or JWT keep working as
+/// before on a trusted network.
+pub struct Authenticator {
+    api_keys: ApiKeys,
+    jwt: Option<JwtValidator>,
+}
+
+impl Authenticator {
+    pub fn new(api_keys: ApiKeys, jwt: Option<JwtValidator>) -> Self {
+        Self { api_keys, jwt }
+    }
+
+    /// Whether ingestion requires credentials
+    pub fn requires_ingest_auth(&self) -> bool {
+        self.jwt.is_some() || self.api_keys.grants(Scope::Ingest)
+    }
+
+    /// Whether any credential can reach the admin API
+    pub fn admin_enabled(&self) -> bool {
+        self.jwt.is_some() || self.api_keys.grants(Scope::Admin)
+    }
+
+    /// Whether any credential can capture debug bundles
+    pub fn debug_enabled(&self) -> bool {
+        self.jwt.is_some() || self.api_keys.grants(Scope::Debug)
+    }
+
+    /// Ways ingestion clients can authenticate; empty when ingestion is open
+    pub fn ingest_methods(&self) -> Vec<&'static str> {
+        let mut methods = Vec::new();
+        if self.api_keys.grants(Scope::Ingest) {
+            methods.push("api_key");
+        }
+        if self.jwt.is_some() {
+            methods.push("jwt");
+        }
+        methods
+    }
+
+    /// Authenticate the request credentials and check they carry `scope`
+    pub async fn authorize(
+        &self,
+        headers: &HeaderMap,
+        scope: Scope,
+    ) -> Result<Principal, AuthError> {
+        let bearer = headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        let api_key = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok());
+
+        let principal = match (bearer, api_key) {
+            (_, Some(key)) => self.api_keys.authenticate(key).cloned(),
+            (Some(token), None) => match self.api_keys.authenticate(token) {
+                Some(principal) => Some(principal.clone()),
+                None => match self.jwt {
+                    // JWTs are three dot-separated segments
+                    Some(ref jwt) if token.matches('.').count() == 2 => {
+                        Some(jwt.validate(token).await?)
+                    }
+                    _ => None,
+                },
+            },
+            (None, None) => return Err(AuthError::Missing),
+        };
+
+        let principal = principal.ok_or(AuthError::Invalid)?;
+        if !principal.has_scope(scope) {
+            warn!("{} lacks the {:?} scope", principal.name, scope);
+            return Err(AuthError::InsufficientScope(scope));
+        }
+        Ok(principal)
+    }
+}
+
+/// Middleware authenticating ingestion requests when ingestion auth is enabled.
+/// The principal is made available to handlers as a request extension.
+///
+/// Requests over a connection with a verified client certificate and no
+/// other credentials act for the certificate's agent, whether or not
+/// ingestion auth is enabled.
+pub async fn require_ingest_scope(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let has_credentials = request.headers().contains_key(AUTHORIZATION)
+        || request.headers().contains_key(API_KEY_HEADER);
+    if let (Some(identity), false) = (
+        request.extensions().get::<ClientIdentity>(),
+        has_credentials,
+    ) {
+        let principal = identity.principal();
+        request.extensions_mut().insert(principal);
+        return next.run(request).await;
+    }
+    if !state.auth.requires_ingest_auth() {
+        return next.run(request).await;
+    }
+
+    match state.auth.authorize(request.headers(), Scope::Ingest).await {
+        Ok(principal) if principal.sandbox && state.sandbox.is_none() => {
+            error_response(StatusCode::FORBIDDEN, "Sandbox mode is disabled")
+        }
+        Ok(principal) => {
+            request.extensions_mut().insert(principal);
+            next.run(request).await
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// An authenticated operator with the admin scope. Admin resources are not
+/// partitioned by tenant, so tenant-bound credentials are refused.
+pub struct AdminPrincipal(pub String);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AdminPrincipal {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        if !state.auth.admin_enabled() {
+            return Err(error_response(
+                StatusCode::FORBIDDEN,
+                "Admin API is disabled",
+            ));
+        }
+
+        match state.auth.authorize(&parts.headers, Scope::Admin).await {
+            Ok(principal) if principal.tenant_id.is_some() => {
+                Err(AuthError::InsufficientScope(Scope::Admin).into_response())
+            }
+            Ok(principal) => Ok(AdminPrincipal(principal.name)),
+            Err(e) => Err(e.into_response()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use jsonwebtoken::{EncodingKey, Header};
+
+    fn headers(name: &str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            HeaderValue::from_str(value).unwrap(),
+        );
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_api_key_scopes() {
+        let mut keys = ApiKeys::parse(
+            "sdk:k1,ops:k2:ingest+admin,acme:k4:ingest:acme",
+            &[Scope::Ingest],
+        )
+        .unwrap();
+        keys.extend(ApiKeys::parse("alice:k3", &[Scope::Admin]).unwrap());
+        let auth = Authenticator::new(keys, None);
+        assert!(auth.requires_ingest_auth());
+        assert!(auth.admin_enabled());
+
+        let sdk = headers("x-api-key", "k1");
+        assert!(auth.authorize(&sdk, Scope::Ingest).await.is_ok());
+        assert!(matches!(
+            auth.authorize(&sdk, Scope::Admin).await,
+            Err(AuthError::InsufficientScope(Scope::Admin))
+        ));
+
+        let acme = auth
+            .authorize(&headers("x-api-key", "k4"), Scope::Ingest)
+            .await
+            .unwrap();
+        assert_eq!(acme.tenant_id.as_deref(), Some("acme"));
+
+        let ops = headers("authorization", "Bearer k2");
+        assert_eq!(
+            auth.authorize(&ops, Scope::Admin).await.unwrap().name,
+            "ops"
+        );
+        assert!(matches!(
+            auth.authorize(&headers("x-api-key", "nope"), Scope::Ingest)
+                .await,
+            Err(AuthError::Invalid)
+        ));
+        assert!(matches!(
+            auth.authorize(&HeaderMap::new(), Scope::Ingest).await,
+            Err(AuthError::Missing)
+        ));
+    }
+
+    #[test]
+    fn test_admin_only_keys_leave_ingest_open() {
+        let auth = Authenticator::new(ApiKeys::parse("alice:k", &[Scope::Admin]).unwrap(), None);
+        assert!(!auth.requires_ingest_auth());
+        assert!(ApiKeys::parse("bad", &[Scope::Ingest]).is_err());
+        assert!(ApiKeys::parse("a:k:root", &[Scope::Ingest]).is_err());
+        assert!(ApiKeys::parse("a:k:ingest:Not.Valid", &[Scope::Ingest]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sandbox_keys() {
+        let keys = ApiKeys::parse(
+            "dev:k1:ingest:acme:sandbox,solo:k2:ingest::sandbox,prod:k3:ingest:acme",
+            &[Scope::Ingest],
+        )
+        .unwrap();
+        let auth = Authenticator::new(keys, None);
+
+        let dev = auth
+            .authorize(&headers("x-api-key", "k1"), Scope::Ingest)
+            .await
+            .unwrap();
+        assert!(dev.sandbox);
+        assert_eq!(dev.tenant_id.as_deref(), Some("acme"));
+
+        let solo = auth
+            .authorize(&headers("x-api-key", "k2"), Scope::Ingest)
+            .await
+            .unwrap();
+        assert!(solo.sandbox);
+        assert_eq!(solo.tenant_id, None);
+
+        let prod = auth
+            .authorize(&headers("x-api-key", "k3"), Scope::Ingest)
+            .await
+            .unwrap();
+        assert!(!prod.sandbox);
+
+        assert!(ApiKeys::parse("a:k:ingest:acme:staging", &[Scope::Ingest]).is_err());
+        assert!(ApiKeys::parse("a:k:ingest:", &[Scope::Ingest]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_jwt_bearer_tokens() {
+        let jwt = JwtValidator::new(JwtConfig {
+            issuer: "https://idp.example.com".to_string(),
+            jwks_url: "http://127.0.0.1:9/jwks".to_string(),
+            audience: None,
+            algorithm: None,
+            jwks_ttl: Duration::from_secs(300),
+        });
+        let jwks: JwkSet = serde_json::from_value(serde_json::json!({
+            "keys": [{"kty": "oct", "kid": "k1", "alg": "HS256", "k": "c2VjcmV0LXNlY3JldC1zZWNyZXQ"}]
+        }))
+        .unwrap();
+        *jwt.jwks.write().await = Some((Instant::now(), jwks));
+        let auth = Authenticator::new(ApiKeys::default(), Some(jwt));
+
+        let token_with = |alg: Algorithm, iss: &str| {
+            let mut header = Header::new(alg);
+            header.kid = Some("k1".to_string());
+            let claims = serde_json::json!({
+                "sub": "agent-runner",
+                "iss": iss,
+                "exp": chrono::Utc::now().timestamp() + 60,
+                "scope": "facto:ingest",
+            });
+            let encoded = jsonwebtoken::encode(
+                &header,
+                &claims,
+                &EncodingKey::from_secret(b"secret-secret-secret"),
+            )
+            .unwrap();
+            headers("authorization", &format!("Bearer {}", encoded))
+        };
+        let token = |iss: &str| token_with(Algorithm::HS256, iss);
+
+        let principal = auth
+            .authorize(&token("https://idp.example.com"), Scope::Ingest)
+            .await
+            .unwrap();
+        assert_eq!(principal.name, "agent-runner");
+        assert!(matches!(
+            auth.authorize(&token("https://idp.example.com"), Scope::Admin)
+                .await,
+            Err(AuthError::InsufficientScope(_))
+        ));
+        assert!(matches!(
+            auth.authorize(&token("https://evil.example.com"), Scope::Ingest)
+                .await,
+            Err(AuthError::Invalid)
+        ));
+        // The key is pinned to HS256, whatever the token header claims
+        assert!(matches!(
+            auth.authorize(
+                &token_with(Algorithm::HS512, "https://idp.example.com"),
+                Scope::Ingest
+            )
+            .await,
+            Err(AuthError::Invalid)
+        ));
+    }
+}
@@ -0,0 +1,363 @@
+//! Holding area for events ingested with `backfill=true`.
+//!
+//! Agents that buffer events offline submit them hours later and in any
+//! order. Backfilled events are verified like any other, then held per
+//! session instead of being published. An event is released once its
+//! `prev_hash` is the hash of the session's latest released event, or the
+//! genesis hash for a new session, so each session reaches the stream in
+//! chain order. Once a session's held events have waited the backfill
+//! timeout without any of them being released, the rest are released
+//! anyway, chain segment by chain segment, and the first event of each
+//! segment is marked as following a gap.
+//!
+//! Held events live in memory only; they are released with their gaps on
+//! shutdown rather than lost.
+
+use crate::protocol::{KeyRotation, ReplayRejection};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use crate::ordering::{SessionGuard, SessionLocks};
+use crate::verification::ServerEnvelope;
+use crate::FactoEvent;
+
+/// `prev_hash` of the first event of a session
+pub const GENESIS_PREV_HASH: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// How a released event's `prev_hash` relates to the events released
+/// before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChainLink {
+    /// `prev_hash` is the hash of the session's previous event, or the
+    /// genesis hash
+    Linked,
+    /// The event's predecessor had not arrived when the backfill timeout
+    /// expired
+    Gap,
+}
+
+/// How a backfilled event was held, recorded in its server envelope
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackfillRecord {
+    pub held_at: i64,
+    pub released_at: i64,
+    pub chain: ChainLink,
+}
+
+/// A verified event waiting for its predecessor
+pub struct HeldEvent {
+    pub event: FactoEvent,
+    pub envelope: ServerEnvelope,
+    /// Attestation of a key rotation event, applied once it is published
+    pub rotation: Option<KeyRotation>,
+}
+
+/// The holding area is full; the event is refused
+#[derive(Debug)]
+pub struct BackfillFull;
+
+/// Held events of one session, and since when they have waited without any
+/// being released
+struct HeldSession {
+    session_id: String,
+    events: Vec<HeldEvent>,
+    since: i64,
+}
+
+pub struct BackfillBuffer {
+    sessions: Mutex<HashMap<String, HeldSession>>,
+    held: AtomicUsize,
+    max_events: usize,
+    max_age: i64,
+    timeout: i64,
+    /// Serializes the releases of each session, from taking events out to
+    /// advancing the chain head
+    releasing: SessionLocks,
+}
+
+impl BackfillBuffer {
+    /// Hold at most `max_events` events of at most `max_age`, giving up on a
+    /// session's gaps once none of its events was released for `timeout`
+    pub fn new(max_events: usize, max_age: Duration, timeout: Duration) -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            held: AtomicUsize::new(0),
+            max_events,
+            max_age: max_age.as_nanos() as i64,
+            timeout: timeout.as_nanos() as i64,
+            releasing: SessionLocks::new(),
+        }
+    }
+
+    /// Check that a backfilled event completed within the backfill window,
+    /// which stands in for the replay freshness window's age limit
+    pub fn check_age(&self, completed_at: i64, now: i64) -> Result<(), ReplayRejection> {
+        match now.saturating_sub(completed_at) > self.max_age {
+            true => Err(ReplayRejection::Stale),
+            false => Ok(()),
+        }
+    }
+
+    /// Number of events currently held
+    pub fn len(&self) -> usize {
+        self.held.load(Ordering::Relaxed)
+    }
+
+    /// Hold an event under its tenant-scoped session
+    pub fn hold(&self, session: &str, held: HeldEvent, now: i64) -> Result<(), BackfillFull> {
+        if self.held.fetch_add(1, Ordering::Relaxed) >= self.max_events {
+            self.held.fetch_sub(1, Ordering::Relaxed);
+            return Err(BackfillFull);
+        }
+        let mut held = held;
+        held.envelope.backfill = Some(BackfillRecord {
+            held_at: now,
+            released_at: 0,
+            chain: ChainLink::Linked,
+        });
+        self.sessions
+            .lock()
+            .unwrap()
+            .entry(session.to_string())
+            .or_insert_with(|| HeldSession {
+                session_id: held.event.session_id.clone(),
+                events: Vec::new(),
+                since: now,
+            })
+            .events
+            .push(held);
+        Ok(())
+    }
+
+    /// Put back events whose release failed, to be released again later
+    pub fn restore(&self, session: &str, events: Vec<HeldEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        self.held.fetch_add(events.len(), Ordering::Relaxed);
+        let mut sessions = self.sessions.lock().unwrap();
+        let since = events
+            .iter()
+            .filter_map(|held| held.envelope.backfill.as_ref())
+            .map(|record| record.held_at)
+            .min()
+            .unwrap_or_default();
+        let entry = sessions
+            .entry(session.to_string())
+            .or_insert_with(|| HeldSession {
+                session_id: events[0].event.session_id.clone(),
+                events: Vec::new(),
+                since,
+            });
+        entry.since = entry.since.min(since);
+        entry.events.extend(events);
+    }
+
+    /// Tenant-scoped sessions with held events, with their session ids
+    pub fn sessions(&self) -> Vec<(String, String)> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(session, held)| (session.clone(), held.session_id.clone()))
+            .collect()
+    }
+
+    /// Lock a session's releases
+    pub async fn lock(&self, session: &str) -> SessionGuard {
+        self.releasing.lock_all([session]).await
+    }
+
+    /// Take the events that continue the session's chain from `head`, the
+    /// hash of its latest published event, in chain order. Once the
+    /// session has waited out the timeout, or with `all`, every held event
+    /// is taken. Callers hold the session's release lock.
+    pub fn take(&self, session: &str, head: Option<&str>, now: i64, all: bool) -> Vec<HeldEvent> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some(held) = sessions.get_mut(session) else {
+            return Vec::new();
+        };
+        let expired = all || now.saturating_sub(held.since) >= self.timeout;
+        let events = std::mem::take(&mut held.events);
+        let (mut released, rest) = chain_order(events, head.unwrap_or(GENESIS_PREV_HASH), expired);
+        match rest.is_empty() {
+            true => {
+                sessions.remove(session);
+            }
+            false => {
+                let held = sessions.get_mut(session).unwrap();
+                held.events = rest;
+                if !released.is_empty() {
+                    held.since = now;
+                }
+            }
+        }
+        drop(sessions);
+
+        self.held.fetch_sub(released.len(), Ordering::Relaxed);
+        for held in released.iter_mut() {
+            if let Some(ref mut record) = held.envelope.backfill {
+                record.released_at = now;
+            }
+        }
+        released
+    }
+}
+
+/// Split held events into those that continue the chain from `head`, in
+/// chain order, and the rest. With `all`, the rest follow segment by
+/// segment, oldest first, each segment's first event marked as a gap.
+fn chain_order(events: Vec<HeldEvent>, head: &str, all: bool) -> (Vec<HeldEvent>, Vec<HeldEvent>) {
+    let mut rest = events;
+    // Forks are resolved towards the event that completed first
+    rest.sort_by_key(|held| held.event.completed_at);
+    let mut released = Vec::with_capacity(rest.len());
+    let mut tip = head.to_string();
+    let mut link = ChainLink::Linked;
+    loop {
+        let next = rest
+            .iter()
+            .position(|held| held.event.proof.prev_hash == tip)
+            .or_else(|| match all {
+                true => segment_start(&rest),
+                false => None,
+            });
+        let Some(index) = next else {
+            break;
+        };
+        let mut held = rest.remove(index);
+        if held.event.proof.prev_hash != tip {
+            link = ChainLink::Gap;
+        }
+        if let Some(ref mut record) = held.envelope.backfill {
+            record.chain = link;
+        }
+        link = ChainLink::Linked;
+        tip = held.event.proof.event_hash.clone();
+        released.push(held);
+    }
+    (released, rest)
+}
+
+/// The oldest held event whose predecessor is not held
+fn segment_start(events: &[HeldEvent]) -> Option<usize> {
+    let hashes: HashSet<&str> = events
+        .iter()
+        .map(|held| held.event.proof.event_hash.as_str())
+        .collect();
+    events
+        .iter()
+        .position(|held| !hashes.contains(held.event.proof.prev_hash.as_str()))
+        .or_else(|| (!events.is_empty()).then_some(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_event;
+    use crate::verification::{TrustBasis, VerificationAssertion};
+
+    fn held(n: u8, prev: &str) -> HeldEvent {
+        let mut event = test_event();
+        event.facto_id = format!("f{}", n);
+        event.proof.prev_hash = prev.to_string();
+        event.proof.event_hash = format!("{:064}", n);
+        event.completed_at = n as i64;
+        HeldEvent {
+            envelope: ServerEnvelope {
+                received_at: 0,
+                verification: VerificationAssertion {
+                    facto_id: event.facto_id.clone(),
+                    event_hash: event.proof.event_hash.clone(),
+                    algorithm: "ed25519".to_string(),
+                    signer_public_key: String::new(),
+                    trust_basis: TrustBasis::EmbeddedKey,
+                    verifier_id: "test".to_string(),
+                    verifier_public_key: String::new(),
+                    verified_at: 0,
+                    signature: String::new(),
+                },
+                verification_status: Default::default(),
+                tenant_id: None,
+                sandbox: false,
+                schema_violations: Vec::new(),
+                policy_findings: Vec::new(),
+                model_attestation: None,
+                headers: Default::default(),
+                redaction: None,
+                data_integrity: None,
+                backfill: None,
+                translation: None,
+            },
+            event,
+            rotation: None,
+        }
+    }
+
+    fn ids(events: &[HeldEvent]) -> Vec<(&str, ChainLink)> {
+        events
+            .iter()
+            .map(|held| {
+                let record = held.envelope.backfill.as_ref().unwrap();
+                (held.event.facto_id.as_str(), record.chain)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_events_are_released_in_chain_order_once_linked() {
+        let buffer = BackfillBuffer::new(10, Duration::from_secs(3600), Duration::from_secs(60));
+        buffer.hold("s", held(3, &format!("{:064}", 2)), 0).unwrap();
+        buffer.hold("s", held(2, &format!("{:064}", 1)), 0).unwrap();
+        assert!(buffer.take("s", None, 1, false).is_empty());
+
+        buffer.hold("s", held(1, GENESIS_PREV_HASH), 2).unwrap();
+        let released = buffer.take("s", None, 3, false);
+        assert_eq!(
+            ids(&released),
+            [
+                ("f1", ChainLink::Linked),
+                ("f2", ChainLink::Linked),
+                ("f3", ChainLink::Linked)
+            ]
+        );
+        assert_eq!(buffer.len(), 0);
+        assert!(buffer.sessions().is_empty());
+
+        buffer.hold("s", held(5, "x"), 4).unwrap();
+        assert_eq!(
+            ids(&buffer.take("s", None, 5, true)),
+            [("f5", ChainLink::Gap)]
+        );
+    }
+
+    #[test]
+    fn test_gaps_are_given_up_on_after_the_timeout() {
+        let second = 1_000_000_000;
+        let buffer = BackfillBuffer::new(2, Duration::from_secs(3600), Duration::from_secs(60));
+        buffer.hold("s", held(4, &format!("{:064}", 3)), 0).unwrap();
+        buffer.hold("s", held(2, &format!("{:064}", 1)), 0).unwrap();
+        assert!(buffer.hold("s", held(5, "x"), 0).is_err());
+
+        let head = format!("{:064}", 1);
+        let released = buffer.take("s", Some(&head), 10 * second, false);
+        assert_eq!(ids(&released), [("f2", ChainLink::Linked)]);
+        assert!(buffer.take("s", Some(&head), 65 * second, false).is_empty());
+
+        let released = buffer.take("s", Some(&format!("{:064}", 2)), 70 * second, false);
+        assert_eq!(ids(&released), [("f4", ChainLink::Gap)]);
+        assert_eq!(
+            released[0].envelope.backfill.as_ref().unwrap().released_at,
+            70 * second
+        );
+    }
+}
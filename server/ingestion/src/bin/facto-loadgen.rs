@@ -0,0 +1,541 @@
+//! Load generator for capacity and soak testing of the ingestion service.
+//!
+//! Simulates `--agents` agents, each with its own Ed25519 key and
+//! `--sessions` sessions, emitting signed events in chain order with
+//! payloads of about `--event-bytes` bytes. A share `--invalid-ratio` of
+//! events is corrupted, half with a wrong hash and half with a wrong
+//! signature, and left out of their session's chain. Events are sent to
+//! `/v1/ingest/batch` in batches of `--batch-size` at `--rate` events per
+//! second for `--duration` seconds, with at most `--concurrency` requests
+//! in flight; when the server falls behind, the achieved rate drops below
+//! the target.
+//!
+//! ```text
+//! facto-loadgen --url http://localhost:8080 --rate 5000 --duration 300
+//! ```
+//!
+//! Progress is printed every `--report-secs`, and a summary at the end:
+//! request latency percentiles, HTTP statuses, and per-event outcomes
+//! including spooled, held and rejected events by reason. Events should be
+//! trusted by their embedded keys, as no keys are registered.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ed25519_dalek::{Signer, SigningKey};
+use facto_ingestion::crypto::{build_canonical_form, compute_event_hash};
+use facto_ingestion::protocol::EventError;
+use facto_ingestion::{ExecutionMeta, FactoEvent, Proof};
+use rand::{rngs::OsRng, Rng};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    process::ExitCode,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::Semaphore;
+
+/// `prev_hash` of the first event of a session
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Approximate JSON size of a generated event besides its prompt padding
+const EVENT_OVERHEAD: usize = 880;
+
+const ACTION_TYPES: &[&str] = &["llm_call", "tool_call", "retrieval", "llm_call"];
+
+const USAGE: &str = "\
+Usage:
+  facto-loadgen [--url URL] [--api-key KEY] [--agents N] [--sessions N]
+                [--rate EVENTS_PER_SEC] [--batch-size N] [--duration SECS]
+                [--event-bytes N] [--invalid-ratio R] [--concurrency N]
+                [--report-secs SECS]
+
+The API key may also be set in FACTO_API_KEY.";
+
+// ============================================================================
+// Options
+// ============================================================================
+
+struct Options {
+    url: String,
+    api_key: Option<String>,
+    agents: usize,
+    sessions: usize,
+    rate: f64,
+    batch_size: usize,
+    duration: Duration,
+    event_bytes: usize,
+    invalid_ratio: f64,
+    concurrency: usize,
+    report_every: Duration,
+}
+
+/// The value of `--name VALUE` or `--name=VALUE`, removed from `args`
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let prefix = format!("{}=", name);
+    let Some(i) = args
+        .iter()
+        .position(|a| a == name || a.starts_with(&prefix))
+    else {
+        return Ok(None);
+    };
+    let arg = args.remove(i);
+    match arg.strip_prefix(&prefix) {
+        Some(value) => Ok(Some(value.to_string())),
+        None if i < args.len() => Ok(Some(args.remove(i))),
+        None => Err(format!("{} needs a value", name)),
+    }
+}
+
+/// A parsed `--name` option, or `default`
+fn parse_option<T: std::str::FromStr>(
+    args: &mut Vec<String>,
+    name: &str,
+    default: T,
+) -> Result<T, String> {
+    match take_option(args, name)? {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("invalid {} {}", name, value)),
+        None => Ok(default),
+    }
+}
+
+impl Options {
+    fn parse(mut args: Vec<String>) -> Result<Self, String> {
+        if args.iter().any(|a| a == "-h" || a == "--help") {
+            return Err(USAGE.to_string());
+        }
+        let options = Options {
+            url: parse_option(&mut args, "--url", "http://localhost:8080".to_string())?,
+            api_key: take_option(&mut args, "--api-key")?
+                .or_else(|| std::env::var("FACTO_API_KEY").ok()),
+            agents: parse_option(&mut args, "--agents", 10)?,
+            sessions: parse_option(&mut args, "--sessions", 4)?,
+            rate: parse_option(&mut args, "--rate", 1000.0)?,
+            batch_size: parse_option(&mut args, "--batch-size", 50)?,
+            duration: Duration::from_secs(parse_option(&mut args, "--duration", 60)?),
+            event_bytes: parse_option(&mut args, "--event-bytes", 512)?,
+            invalid_ratio: parse_option(&mut args, "--invalid-ratio", 0.0)?,
+            concurrency: parse_option(&mut args, "--concurrency", 16)?,
+            report_every: Duration::from_secs(parse_option(&mut args, "--report-secs", 5)?),
+        };
+        if let Some(arg) = args.first() {
+            return Err(format!("unknown argument {}\n\n{}", arg, USAGE));
+        }
+        if options.agents == 0 || options.sessions == 0 || options.batch_size == 0 {
+            return Err("--agents, --sessions and --batch-size must be positive".to_string());
+        }
+        if options.rate.is_nan()
+            || options.rate <= 0.0
+            || !(0.0..=1.0).contains(&options.invalid_ratio)
+        {
+            return Err("--rate must be positive and --invalid-ratio within 0..=1".to_string());
+        }
+        Ok(options)
+    }
+}
+
+// ============================================================================
+// Event Generation
+// ============================================================================
+
+fn now_nanos() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as i64
+}
+
+/// How a generated event is corrupted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Corruption {
+    Hash,
+    Signature,
+}
+
+struct Session {
+    session_id: String,
+    prev_hash: String,
+}
+
+struct Agent {
+    agent_id: String,
+    key: SigningKey,
+    public_key: String,
+    sessions: Vec<Session>,
+}
+
+/// Simulated agents, each emitting its sessions' events in chain order
+struct Generator {
+    agents: Vec<Agent>,
+    event_bytes: usize,
+    invalid_ratio: f64,
+    next_agent: usize,
+}
+
+impl Generator {
+    fn new(agents: usize, sessions: usize, event_bytes: usize, invalid_ratio: f64) -> Self {
+        let run = uuid::Uuid::new_v4().simple().to_string();
+        let agents = (0..agents)
+            .map(|a| {
+                let key = SigningKey::generate(&mut OsRng);
+                Agent {
+                    agent_id: format!("loadgen-{}-{}", &run[..8], a),
+                    public_key: BASE64.encode(key.verifying_key().as_bytes()),
+                    key,
+                    sessions: (0..sessions)
+                        .map(|_| Session {
+                            session_id: uuid::Uuid::now_v7().to_string(),
+                            prev_hash: GENESIS_HASH.to_string(),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        Self {
+            agents,
+            event_bytes,
+            invalid_ratio,
+            next_agent: 0,
+        }
+    }
+
+    /// The next event, round-robin over agents and at random over their
+    /// sessions, with how it was corrupted if it was
+    fn next(&mut self, rng: &mut impl Rng) -> (FactoEvent, Option<Corruption>) {
+        let index = self.next_agent;
+        self.next_agent = (index + 1) % self.agents.len();
+        let agent = &mut self.agents[index];
+        let session = rng.gen_range(0..agent.sessions.len());
+        let session = &mut agent.sessions[session];
+
+        let completed_at = now_nanos();
+        let padding: String = (0..self.event_bytes.saturating_sub(EVENT_OVERHEAD))
+            .map(|_| rng.sample(rand::distributions::Alphanumeric) as char)
+            .collect();
+        let mut event = FactoEvent {
+            event_version: 1,
+            facto_id: uuid::Uuid::now_v7().to_string(),
+            agent_id: agent.agent_id.clone(),
+            session_id: session.session_id.clone(),
+            parent_facto_id: None,
+            action_type: ACTION_TYPES[rng.gen_range(0..ACTION_TYPES.len())].to_string(),
+            status: "success".to_string(),
+            input_data: serde_json::json!({"prompt": padding}),
+            output_data: serde_json::json!({"response": "ok", "tokens": rng.gen_range(1..4096)}),
+            execution_meta: ExecutionMeta {
+                model_id: Some("loadgen-model".to_string()),
+                model_hash: None,
+                temperature: Some(0.7),
+                seed: None,
+                max_tokens: Some(4096),
+                tool_calls: vec![],
+                sdk_version: env!("CARGO_PKG_VERSION").to_string(),
+                sdk_language: "rust".to_string(),
+                tags: BTreeMap::new(),
+            },
+            proof: Proof {
+                signature: String::new(),
+                public_key: agent.public_key.clone(),
+                prev_hash: session.prev_hash.clone(),
+                event_hash: String::new(),
+                algorithm: None,
+                canonical_version: None,
+                verification_method: None,
+            },
+            started_at: completed_at - rng.gen_range(1_000_000..2_000_000_000),
+            completed_at,
+            raw: None,
+        };
+        let canonical = build_canonical_form(&event).expect("generated events canonicalize");
+        event.proof.event_hash = compute_event_hash(&canonical);
+        event.proof.signature = BASE64.encode(agent.key.sign(canonical.as_bytes()).to_bytes());
+
+        if !rng.gen_bool(self.invalid_ratio) {
+            session.prev_hash = event.proof.event_hash.clone();
+            return (event, None);
+        }
+        // Corrupted events are rejected, so the chain continues without them
+        let corruption = match rng.gen_bool(0.5) {
+            true => {
+                event.proof.event_hash = "f".repeat(64);
+                Corruption::Hash
+            }
+            false => {
+                let other = SigningKey::generate(&mut OsRng);
+                event.proof.signature = BASE64.encode(other.sign(canonical.as_bytes()).to_bytes());
+                Corruption::Signature
+            }
+        };
+        (event, Some(corruption))
+    }
+}
+
+// ============================================================================
+// Statistics
+// ============================================================================
+
+/// The parts of a batch response the statistics need
+#[derive(Debug, Deserialize)]
+struct BatchOutcome {
+    accepted_count: usize,
+    #[serde(default)]
+    rejected: Vec<Rejection>,
+    #[serde(default)]
+    duplicates: Vec<String>,
+    #[serde(default)]
+    spooled_count: usize,
+    #[serde(default)]
+    held_count: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct Rejection {
+    error: EventError,
+}
+
+#[derive(Default)]
+struct Stats {
+    sent_events: u64,
+    invalid_events: u64,
+    /// Request latencies in microseconds
+    latencies: Vec<u64>,
+    statuses: BTreeMap<String, u64>,
+    accepted: u64,
+    duplicates: u64,
+    spooled: u64,
+    held: u64,
+    rejected: BTreeMap<String, u64>,
+}
+
+impl Stats {
+    fn record(&mut self, latency: Duration, status: String, response: Option<BatchOutcome>) {
+        self.latencies.push(latency.as_micros() as u64);
+        *self.statuses.entry(status).or_default() += 1;
+        let Some(response) = response else {
+            return;
+        };
+        self.accepted += (response.accepted_count - response.duplicates.len()) as u64;
+        self.duplicates += response.duplicates.len() as u64;
+        self.spooled += response.spooled_count as u64;
+        self.held += response.held_count as u64;
+        for rejection in response.rejected {
+            *self
+                .rejected
+                .entry(rejection.error.code.as_str().to_string())
+                .or_default() += 1;
+        }
+    }
+}
+
+/// The `p`th percentile of sorted values, by nearest rank
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn print_summary(stats: &mut Stats, elapsed: Duration, target_rate: f64) {
+    stats.latencies.sort_unstable();
+    let ms = |p: f64| percentile(&stats.latencies, p) as f64 / 1000.0;
+    let rejected: u64 = stats.rejected.values().sum();
+    let valid = stats.sent_events - stats.invalid_events;
+    println!("\n== facto-loadgen summary ==");
+    println!(
+        "elapsed {:.1}s, {} events in {} requests, {:.0} events/s (target {:.0})",
+        elapsed.as_secs_f64(),
+        stats.sent_events,
+        stats.latencies.len(),
+        stats.sent_events as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        target_rate
+    );
+    println!(
+        "latency ms: p50 {:.1}  p90 {:.1}  p99 {:.1}  p99.9 {:.1}  max {:.1}",
+        ms(50.0),
+        ms(90.0),
+        ms(99.0),
+        ms(99.9),
+        ms(100.0)
+    );
+    println!("statuses: {:?}", stats.statuses);
+    println!(
+        "accepted {} ({:.2}% of {} valid), duplicates {}, spooled {}, held {}",
+        stats.accepted,
+        stats.accepted as f64 * 100.0 / valid.max(1) as f64,
+        valid,
+        stats.duplicates,
+        stats.spooled,
+        stats.held
+    );
+    println!(
+        "rejected {} ({} sent invalid): {:?}",
+        rejected, stats.invalid_events, stats.rejected
+    );
+}
+
+// ============================================================================
+// Driving Load
+// ============================================================================
+
+async fn send_batch(
+    client: &reqwest::Client,
+    options: &Options,
+    events: Vec<FactoEvent>,
+) -> (String, Option<BatchOutcome>) {
+    let mut builder = client
+        .post(format!(
+            "{}/v1/ingest/batch",
+            options.url.trim_end_matches('/')
+        ))
+        .json(&serde_json::json!({"events": events}));
+    if let Some(ref key) = options.api_key {
+        builder = builder.header("x-api-key", key);
+    }
+    match builder.send().await {
+        Ok(response) => {
+            let status = response.status().as_u16().to_string();
+            (status, response.json().await.ok())
+        }
+        Err(e) if e.is_timeout() => ("timeout".to_string(), None),
+        Err(_) => ("connection error".to_string(), None),
+    }
+}
+
+async fn run(options: Options) -> Result<bool, String> {
+    let options = Arc::new(options);
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut generator = Generator::new(
+        options.agents,
+        options.sessions,
+        options.event_bytes,
+        options.invalid_ratio,
+    );
+    let stats = Arc::new(Mutex::new(Stats::default()));
+    let permits = Arc::new(Semaphore::new(options.concurrency.max(1)));
+
+    let batches_per_sec = options.rate / options.batch_size as f64;
+    let mut ticks = tokio::time::interval(Duration::from_secs_f64(1.0 / batches_per_sec));
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let start = Instant::now();
+    let mut reported = start;
+    let mut tasks = tokio::task::JoinSet::new();
+    let mut rng = rand::thread_rng();
+
+    while start.elapsed() < options.duration {
+        ticks.tick().await;
+        // Waiting for a permit is the backpressure the server applies
+        let permit = permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut invalid = 0;
+        let events = (0..options.batch_size)
+            .map(|_| {
+                let (event, corruption) = generator.next(&mut rng);
+                invalid += corruption.is_some() as u64;
+                event
+            })
+            .collect();
+        {
+            let mut stats = stats.lock().unwrap();
+            stats.sent_events += options.batch_size as u64;
+            stats.invalid_events += invalid;
+        }
+        let (client, task_options, task_stats) = (client.clone(), options.clone(), stats.clone());
+        tasks.spawn(async move {
+            let _permit = permit;
+            let sent = Instant::now();
+            let (status, response) = send_batch(&client, &task_options, events).await;
+            task_stats
+                .lock()
+                .unwrap()
+                .record(sent.elapsed(), status, response);
+        });
+        while tasks.try_join_next().is_some() {}
+
+        if reported.elapsed() >= options.report_every {
+            reported = Instant::now();
+            let stats = stats.lock().unwrap();
+            println!(
+                "{:>6.0}s  sent {}  accepted {}  rejected {}  in flight {}",
+                start.elapsed().as_secs_f64(),
+                stats.sent_events,
+                stats.accepted,
+                stats.rejected.values().sum::<u64>(),
+                options.concurrency - permits.available_permits()
+            );
+        }
+    }
+    while tasks.join_next().await.is_some() {}
+
+    let mut stats = stats.lock().unwrap();
+    print_summary(&mut stats, start.elapsed(), options.rate);
+    Ok(stats.statuses.keys().all(|status| status.starts_with('2')))
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("facto-loadgen: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    match run(options).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("facto-loadgen: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use facto_ingestion::crypto::{check_required_fields, verify_hash, verify_signature};
+
+    #[test]
+    fn test_generated_events_chain_and_corrupt_as_configured() {
+        let mut rng = rand::thread_rng();
+        let mut generator = Generator::new(1, 1, 2048, 0.0);
+        let (first, _) = generator.next(&mut rng);
+        let (second, corruption) = generator.next(&mut rng);
+        assert_eq!(corruption, None);
+        assert_eq!(first.proof.prev_hash, GENESIS_HASH);
+        assert_eq!(second.proof.prev_hash, first.proof.event_hash);
+        for event in [&first, &second] {
+            check_required_fields(event).unwrap();
+            let canonical = build_canonical_form(event).unwrap();
+            verify_hash(event, &canonical).unwrap();
+            verify_signature(event, &canonical).unwrap();
+        }
+        let size = serde_json::to_vec(&first).unwrap().len();
+        assert!((1900..2200).contains(&size), "{} bytes", size);
+
+        generator.invalid_ratio = 1.0;
+        let (invalid, corruption) = generator.next(&mut rng);
+        let canonical = build_canonical_form(&invalid).unwrap();
+        match corruption.unwrap() {
+            Corruption::Hash => assert!(verify_hash(&invalid, &canonical).is_err()),
+            Corruption::Signature => assert!(verify_signature(&invalid, &canonical).is_err()),
+        }
+        // The chain continues from the last valid event
+        generator.invalid_ratio = 0.0;
+        assert_eq!(
+            generator.next(&mut rng).0.proof.prev_hash,
+            second.proof.event_hash
+        );
+
+        assert_eq!(percentile(&[1, 2, 3, 4], 50.0), 2);
+        assert_eq!(percentile(&[1, 2, 3, 4], 100.0), 4);
+    }
+}
@@ -0,0 +1,597 @@
+//! Conformance server for SDK test suites.
+//!
+//! Serves the ingestion API with the real event model, canonical forms and
+//! response types, but answers with canned behaviors instead of publishing
+//! anything. Behaviors are picked, most specific first, by:
+//!
+//! - the `facto.mock.behavior` tag of an event (tags are not signed, so
+//!   SDKs can set them without touching the proof)
+//! - the `X-Facto-Mock-Behavior` request header
+//! - `MOCK_BEHAVIOR` (default `verify`)
+//!
+//! `X-Facto-Mock-Latency-Ms` or `MOCK_LATENCY_MS` delay every response.
+//! `POST /mock/reset` forgets accepted events between test cases.
+
+use axum::{
+    extract::{Json, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use facto_ingestion::crypto::{self, VerificationError};
+use facto_ingestion::did::{self, DidUrl};
+use facto_ingestion::openapi;
+use facto_ingestion::protocol::{
+    BatchIngestRequest, BatchIngestResponse, Capabilities, ErrorCode, ErrorResponse, EventError,
+    FactoEvent, HealthResponse, OrderingGuarantee, ReadyResponse, RejectedEvent, ReplayRejection,
+    SingleIngestResponse, AGENT_BYTE_QUOTA_EXCEEDED, AGENT_PAUSED, FACTO_ID_CONFLICT, QUEUE_FAILED,
+    RATE_LIMITED, SERVICE_NOT_READY, SESSION_FROZEN, SPOOL_FULL, TENANT_BYTE_QUOTA_EXCEEDED,
+    TENANT_RATE_LIMITED,
+};
+use facto_ingestion::versions;
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::info;
+
+const BEHAVIOR_HEADER: &str = "x-facto-mock-behavior";
+const LATENCY_HEADER: &str = "x-facto-mock-latency-ms";
+const BEHAVIOR_TAG: &str = "facto.mock.behavior";
+
+// ============================================================================
+// Behaviors
+// ============================================================================
+
+/// How the mock answers an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Behavior {
+    /// Verify hash and signature against the embedded key and deduplicate
+    /// by facto_id, like a server without key registration
+    Verify,
+    Accept,
+    Duplicate,
+    Spooled,
+    RateLimited,
+    TenantRateLimited,
+    TenantByteQuota,
+    SessionFrozen,
+    AgentPaused,
+    AgentByteQuota,
+    Stale,
+    FromFuture,
+    Replayed,
+    Conflict,
+    /// Fail verification with a hash mismatch
+    Invalid,
+    SpoolFull,
+    QueueFailed,
+    NotReady,
+}
+
+const BEHAVIORS: &[(&str, Behavior)] = &[
+    ("verify", Behavior::Verify),
+    ("accept", Behavior::Accept),
+    ("duplicate", Behavior::Duplicate),
+    ("spooled", Behavior::Spooled),
+    ("rate_limit", Behavior::RateLimited),
+    ("tenant_rate_limit", Behavior::TenantRateLimited),
+    ("tenant_byte_quota", Behavior::TenantByteQuota),
+    ("session_frozen", Behavior::SessionFrozen),
+    ("agent_paused", Behavior::AgentPaused),
+    ("agent_byte_quota", Behavior::AgentByteQuota),
+    ("stale", Behavior::Stale),
+    ("future", Behavior::FromFuture),
+    ("replay", Behavior::Replayed),
+    ("conflict", Behavior::Conflict),
+    ("invalid", Behavior::Invalid),
+    ("spool_full", Behavior::SpoolFull),
+    ("queue_failed", Behavior::QueueFailed),
+    ("not_ready", Behavior::NotReady),
+];
+
+impl FromStr for Behavior {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BEHAVIORS
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, behavior)| *behavior)
+            .ok_or_else(|| format!("Unknown mock behavior: {}", s))
+    }
+}
+
+/// What became of an event
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Accepted {
+        duplicate: bool,
+        spooled: bool,
+    },
+    /// Status the single-event endpoint answers with, and the error
+    Rejected(StatusCode, EventError),
+}
+
+fn rejected(status: StatusCode, error: impl Into<EventError>) -> Outcome {
+    Outcome::Rejected(status, error.into())
+}
+
+// ============================================================================
+// Mock State
+// ============================================================================
+
+struct MockState {
+    default_behavior: Behavior,
+    default_latency: Duration,
+    capabilities: Capabilities,
+    /// facto_id -> event hash of events accepted under `verify`
+    accepted: Mutex<HashMap<String, String>>,
+}
+
+impl MockState {
+    fn outcome(&self, event: &FactoEvent, behavior: Behavior) -> Outcome {
+        match behavior {
+            Behavior::Verify => match verify(event) {
+                Ok(event_hash) => self.claim(&event.facto_id, event_hash),
+                Err(e) => rejected(StatusCode::BAD_REQUEST, e),
+            },
+            Behavior::Accept => Outcome::Accepted {
+                duplicate: false,
+                spooled: false,
+            },
+            Behavior::Duplicate => Outcome::Accepted {
+                duplicate: true,
+                spooled: false,
+            },
+            Behavior::Spooled => Outcome::Accepted {
+                duplicate: false,
+                spooled: true,
+            },
+            Behavior::RateLimited => rejected(
+                StatusCode::TOO_MANY_REQUESTS,
+                EventError::new(ErrorCode::RateLimited, RATE_LIMITED),
+            ),
+            Behavior::TenantRateLimited => rejected(
+                StatusCode::TOO_MANY_REQUESTS,
+                EventError::new(ErrorCode::TenantRateLimited, TENANT_RATE_LIMITED),
+            ),
+            Behavior::TenantByteQuota => rejected(
+                StatusCode::TOO_MANY_REQUESTS,
+                EventError::new(
+                    ErrorCode::TenantByteQuotaExceeded,
+                    TENANT_BYTE_QUOTA_EXCEEDED,
+                ),
+            ),
+            Behavior::SessionFrozen => rejected(
+                StatusCode::LOCKED,
+                EventError::new(ErrorCode::SessionFrozen, SESSION_FROZEN),
+            ),
+            Behavior::AgentPaused => rejected(
+                StatusCode::LOCKED,
+                EventError::new(ErrorCode::AgentPaused, AGENT_PAUSED),
+            ),
+            Behavior::AgentByteQuota => rejected(
+                StatusCode::TOO_MANY_REQUESTS,
+                EventError::new(ErrorCode::AgentByteQuotaExceeded, AGENT_BYTE_QUOTA_EXCEEDED),
+            ),
+            Behavior::Stale => rejected(StatusCode::BAD_REQUEST, ReplayRejection::Stale),
+            Behavior::FromFuture => rejected(StatusCode::BAD_REQUEST, ReplayRejection::FromFuture),
+            Behavior::Replayed => rejected(StatusCode::CONFLICT, ReplayRejection::Replayed),
+            Behavior::Conflict => rejected(
+                StatusCode::CONFLICT,
+                EventError::new(ErrorCode::FactoIdConflict, FACTO_ID_CONFLICT),
+            ),
+            Behavior::Invalid => rejected(StatusCode::BAD_REQUEST, hash_mismatch(event)),
+            Behavior::SpoolFull => rejected(
+                StatusCode::SERVICE_UNAVAILABLE,
+                EventError::new(ErrorCode::SpoolFull, SPOOL_FULL),
+            ),
+            Behavior::QueueFailed => rejected(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                EventError::new(ErrorCode::QueueFailed, QUEUE_FAILED),
+            ),
+            Behavior::NotReady => rejected(
+                StatusCode::SERVICE_UNAVAILABLE,
+                EventError::new(ErrorCode::ServiceNotReady, SERVICE_NOT_READY),
+            ),
+        }
+    }
+
+    /// Deduplicate by facto_id the way the dedup cache does
+    fn claim(&self, facto_id: &str, event_hash: String) -> Outcome {
+        let mut accepted = self.accepted.lock().unwrap();
+        match accepted.get(facto_id) {
+            Some(seen) if *seen == event_hash => Outcome::Accepted {
+                duplicate: true,
+                spooled: false,
+            },
+            Some(_) => rejected(
+                StatusCode::CONFLICT,
+                EventError::new(ErrorCode::FactoIdConflict, FACTO_ID_CONFLICT),
+            ),
+            None => {
+                accepted.insert(facto_id.to_string(), event_hash);
+                Outcome::Accepted {
+                    duplicate: false,
+                    spooled: false,
+                }
+            }
+        }
+    }
+
+    /// Behavior for a request, from its header or the default
+    fn request_behavior(&self, headers: &HeaderMap) -> Result<Behavior, String> {
+        match headers.get(BEHAVIOR_HEADER) {
+            Some(value) => value
+                .to_str()
+                .map_err(|_| format!("Invalid {} header", BEHAVIOR_HEADER))?
+                .parse(),
+            None => Ok(self.default_behavior),
+        }
+    }
+
+    /// Sleep for the latency asked for by the request, or the default
+    async fn delay(&self, headers: &HeaderMap) -> Result<(), String> {
+        let latency = match headers.get(LATENCY_HEADER) {
+            Some(value) => value
+                .to_str()
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis)
+                .ok_or_else(|| format!("Invalid {} header", LATENCY_HEADER))?,
+            None => self.default_latency,
+        };
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+        Ok(())
+    }
+}
+
+/// Behavior for one event: its tag overrides the request's behavior
+fn event_behavior(event: &FactoEvent, request: Behavior) -> Result<Behavior, String> {
+    match event.execution_meta.tags.get(BEHAVIOR_TAG) {
+        Some(name) => name.parse(),
+        None => Ok(request),
+    }
+}
+
+/// Check the proof against the event's embedded key, in the same order as
+/// the server's verifier
+fn verify(event: &FactoEvent) -> Result<String, VerificationError> {
+    crypto::check_required_fields(event)?;
+    // Only did:key verification methods resolve without the network
+    if let Some(ref verification_method) = event.proof.verification_method {
+        let key = DidUrl::parse(verification_method).and_then(|url| url.did_key())?;
+        did::check_binding(event, &key)?;
+    }
+    let canonical = crypto::build_canonical_form(event)?;
+    let event_hash = crypto::verify_hash(event, &canonical)?;
+    crypto::verify_signature(event, &canonical)?;
+    Ok(event_hash)
+}
+
+/// The error the server gives an event whose hash does not match
+fn hash_mismatch(event: &FactoEvent) -> VerificationError {
+    match crypto::build_canonical_form(event) {
+        Ok(canonical) => VerificationError::HashMismatch {
+            computed: crypto::compute_event_hash(&canonical),
+            provided: event.proof.event_hash.clone(),
+        },
+        Err(e) => e,
+    }
+}
+
+fn bad_request(error: String) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse { error, code: None }),
+    )
+        .into_response()
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+async fn health_handler() -> impl IntoResponse {
+    Json(HealthResponse {
+        status: "healthy".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    })
+}
+
+async fn openapi_handler() -> impl IntoResponse {
+    Json(openapi::document(env!("CARGO_PKG_VERSION")))
+}
+
+async fn ready_handler() -> impl IntoResponse {
+    Json(ReadyResponse {
+        ready: true,
+        nats_connected: true,
+        kafka_connected: None,
+        spool_depth: None,
+        checks: BTreeMap::new(),
+    })
+}
+
+async fn capabilities_handler(State(state): State<Arc<MockState>>) -> impl IntoResponse {
+    Json(state.capabilities.clone())
+}
+
+async fn reset_handler(State(state): State<Arc<MockState>>) -> impl IntoResponse {
+    state.accepted.lock().unwrap().clear();
+    StatusCode::NO_CONTENT
+}
+
+async fn ingest_single_handler(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Json(event): Json<FactoEvent>,
+) -> Response {
+    let behavior = match state
+        .request_behavior(&headers)
+        .and_then(|b| event_behavior(&event, b))
+    {
+        Ok(behavior) => behavior,
+        Err(e) => return bad_request(e),
+    };
+    if let Err(e) = state.delay(&headers).await {
+        return bad_request(e);
+    }
+
+    let (status, response) = match state.outcome(&event, behavior) {
+        Outcome::Accepted { duplicate, spooled } => (
+            if duplicate {
+                StatusCode::OK
+            } else {
+                StatusCode::ACCEPTED
+            },
+            SingleIngestResponse {
+                accepted: true,
+                facto_id: event.facto_id,
+                duplicate,
+                spooled,
+                held: false,
+                reason: None,
+                error: None,
+                receipt: None,
+            },
+        ),
+        Outcome::Rejected(status, error) => (
+            status,
+            SingleIngestResponse::rejected(event.facto_id, error),
+        ),
+    };
+    (status, Json(response)).into_response()
+}
+
+async fn ingest_batch_handler(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Json(request): Json<BatchIngestRequest>,
+) -> Response {
+    let behaviors = match state.request_behavior(&headers).and_then(|b| {
+        request
+            .events
+            .iter()
+            .map(|event| event_behavior(event, b))
+            .collect::<Result<Vec<_>, _>>()
+    }) {
+        Ok(behaviors) => behaviors,
+        Err(e) => return bad_request(e),
+    };
+    if let Err(e) = state.delay(&headers).await {
+        return bad_request(e);
+    }
+
+    let mut response = BatchIngestResponse {
+        accepted_count: 0,
+        rejected_count: 0,
+        rejected: Vec::new(),
+        duplicates: Vec::new(),
+        spooled_count: 0,
+        held_count: 0,
+        ordering: request.ordered.then(OrderingGuarantee::session),
+        receipts: Vec::new(),
+    };
+    for (event, behavior) in request.events.into_iter().zip(behaviors) {
+        match state.outcome(&event, behavior) {
+            Outcome::Accepted { duplicate, spooled } => {
+                response.accepted_count += 1;
+                if duplicate {
+                    response.duplicates.push(event.facto_id);
+                } else if spooled {
+                    response.spooled_count += 1;
+                }
+            }
+            Outcome::Rejected(_, error) => response
+                .rejected
+                .push(RejectedEvent::new(event.facto_id, error)),
+        }
+    }
+    response.rejected_count = response.rejected.len();
+
+    (StatusCode::ACCEPTED, Json(response)).into_response()
+}
+
+// ============================================================================
+// Main Entry Point
+// ============================================================================
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::from_default_env()
+                .add_directive("facto_mock_server=info".parse()?),
+        )
+        .init();
+
+    let port: u16 = std::env::var("PORT")
+        .unwrap_or_else(|_| "8090".to_string())
+        .parse()
+        .expect("Invalid PORT");
+
+    let default_behavior: Behavior = std::env::var("MOCK_BEHAVIOR")
+        .unwrap_or_else(|_| "verify".to_string())
+        .parse()
+        .expect("Invalid MOCK_BEHAVIOR");
+
+    let default_latency_ms: u64 = std::env::var("MOCK_LATENCY_MS")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse()
+        .expect("Invalid MOCK_LATENCY_MS");
+
+    let state = Arc::new(MockState {
+        default_behavior,
+        default_latency: Duration::from_millis(default_latency_ms),
+        capabilities: Capabilities {
+            server_version: env!("CARGO_PKG_VERSION"),
+            event_versions: versions::SUPPORTED_EVENT_VERSIONS,
+            signature_algorithms: crypto::SIGNATURE_ALGORITHMS,
+            hash_algorithm: crypto::HASH_ALGORITHM,
+            canonical_versions: crypto::CANONICAL_VERSIONS,
+            canonical_v1_until: None,
+            did_methods: &did::DID_METHODS[..1],
+            max_body_bytes: 2 * 1024 * 1024,
+            max_batch_events: None,
+            max_event_bytes: 1024 * 1024,
+            max_tool_calls: None,
+            offload_threshold_bytes: None,
+            rate_limit_per_agent: 10000,
+            replay_window_secs: None,
+            replay_max_skew_secs: 30,
+            auth_methods: Vec::new(),
+            content_types: &["application/json"],
+            content_encodings: &[],
+            endpoints: vec!["/v1/ingest", "/v1/ingest/batch"],
+        },
+        accepted: Mutex::new(HashMap::new()),
+    });
+
+    let app = Router::new()
+        .route("/health", get(health_handler))
+        .route("/ready", get(ready_handler))
+        .route("/v1/capabilities", get(capabilities_handler))
+        .route("/openapi.json", get(openapi_handler))
+        .route("/v1/ingest", post(ingest_single_handler))
+        .route("/v1/ingest/batch", post(ingest_batch_handler))
+        .route("/mock/reset", post(reset_handler))
+        .with_state(state);
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    info!(
+        "Facto mock server v{} listening on {} (default behavior: {:?})",
+        env!("CARGO_PKG_VERSION"),
+        addr,
+        default_behavior
+    );
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use facto_ingestion::testing::{sign_test_event, test_event};
+
+    fn mock_state() -> MockState {
+        MockState {
+            default_behavior: Behavior::Verify,
+            default_latency: Duration::ZERO,
+            capabilities: Capabilities {
+                server_version: "test",
+                event_versions: versions::SUPPORTED_EVENT_VERSIONS,
+                signature_algorithms: crypto::SIGNATURE_ALGORITHMS,
+                hash_algorithm: crypto::HASH_ALGORITHM,
+                canonical_versions: crypto::CANONICAL_VERSIONS,
+                canonical_v1_until: None,
+                did_methods: &did::DID_METHODS[..1],
+                max_body_bytes: 1024,
+                max_batch_events: None,
+                max_event_bytes: 1024 * 1024,
+                max_tool_calls: None,
+                offload_threshold_bytes: None,
+                rate_limit_per_agent: 1,
+                replay_window_secs: None,
+                replay_max_skew_secs: 30,
+                auth_methods: Vec::new(),
+                content_types: &["application/json"],
+                content_encodings: &[],
+                endpoints: Vec::new(),
+            },
+            accepted: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[test]
+    fn test_behavior_names_round_trip() {
+        for (name, behavior) in BEHAVIORS {
+            assert_eq!(name.parse::<Behavior>(), Ok(*behavior));
+        }
+        assert!("explode".parse::<Behavior>().is_err());
+    }
+
+    #[test]
+    fn test_verify_deduplicates_like_the_server() {
+        let state = mock_state();
+        let event = sign_test_event(test_event(), &SigningKey::from_bytes(&[7u8; 32]));
+
+        let accepted = Outcome::Accepted {
+            duplicate: false,
+            spooled: false,
+        };
+        assert_eq!(state.outcome(&event, Behavior::Verify), accepted);
+        assert_eq!(
+            state.outcome(&event, Behavior::Verify),
+            Outcome::Accepted {
+                duplicate: true,
+                spooled: false,
+            }
+        );
+
+        let mut other = test_event();
+        other.output_data = serde_json::json!({"response": "other"});
+        let other = sign_test_event(other, &SigningKey::from_bytes(&[7u8; 32]));
+        assert_eq!(
+            state.outcome(&other, Behavior::Verify),
+            rejected(
+                StatusCode::CONFLICT,
+                EventError::new(ErrorCode::FactoIdConflict, FACTO_ID_CONFLICT)
+            )
+        );
+
+        let mut tampered = event.clone();
+        tampered.facto_id = "tr-tampered".to_string();
+        assert!(matches!(
+            state.outcome(&tampered, Behavior::Verify),
+            Outcome::Rejected(StatusCode::BAD_REQUEST, error) if error.code == ErrorCode::HashMismatch
+        ));
+    }
+
+    #[test]
+    fn test_event_tag_overrides_request_behavior() {
+        let mut event = test_event();
+        assert_eq!(
+            event_behavior(&event, Behavior::Accept),
+            Ok(Behavior::Accept)
+        );
+        event
+            .execution_meta
+            .tags
+            .insert(BEHAVIOR_TAG.to_string(), "session_frozen".to_string());
+        assert_eq!(
+            event_behavior(&event, Behavior::Accept),
+            Ok(Behavior::SessionFrozen)
+        );
+    }
+}
@@ -0,0 +1,496 @@
+//! Offline verification of exported events, for audits without network
+//! access to a Facto deployment.
+//!
+//! Reads events from JSON or NDJSON exports: a single event, an array of
+//! events, an object with an `events` array (evidence packages, session
+//! listings), or one event per line. Lines of the warehouse export, cursor
+//! pages and dead letters wrap the event in an `event` field, which is
+//! unwrapped. Files named `-` or no files at all read standard input.
+//!
+//! ```text
+//! facto verify [FILE...]
+//! facto audit-chain --session SESSION_ID [FILE...]
+//! facto canonicalize [--facto-id FACTO_ID] [FILE...]
+//! ```
+//!
+//! Hashes, signatures and canonical forms come from the same library the
+//! ingestion server verifies with. An event whose exported server envelope
+//! says a batch envelope stood in for its signature is checked against that
+//! batch envelope instead. Exit status is 0 when every check
+//! passes, 1 when one fails and 2 when the input cannot be read.
+
+use facto_ingestion::crypto::{
+    build_canonical_form, check_required_fields, verify_envelope_membership, verify_hash,
+    verify_signature, VerificationError,
+};
+use facto_ingestion::protocol::BatchEnvelope;
+use facto_ingestion::FactoEvent;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::process::ExitCode;
+
+/// `prev_hash` of the first event of a session
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+const USAGE: &str = "\
+Usage:
+  facto verify [FILE...]
+  facto audit-chain --session SESSION_ID [FILE...]
+  facto canonicalize [--facto-id FACTO_ID] [FILE...]
+
+Reads JSON or NDJSON exports of events; `-` or no FILE reads stdin.";
+
+// ============================================================================
+// Reading Exports
+// ============================================================================
+
+/// An event read from an export, with where it was found
+struct Exported {
+    source: String,
+    event: FactoEvent,
+    /// Batch envelope that stood in for the event's signature
+    trust: Option<EnvelopeTrust>,
+}
+
+/// A `batch_envelope` trust basis of an exported server envelope
+#[derive(Deserialize)]
+struct EnvelopeTrust {
+    envelope: BatchEnvelope,
+    event_hashes: Vec<String>,
+}
+
+/// The batch envelope trust an export line's server envelope records, if any
+fn envelope_trust(item: &Value) -> Result<Option<EnvelopeTrust>, serde_json::Error> {
+    match item.pointer("/envelope/verification/trust_basis") {
+        Some(basis) if basis.get("type").and_then(Value::as_str) == Some("batch_envelope") => {
+            EnvelopeTrust::deserialize(basis).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The events of one export. JSON documents are tried first; anything that
+/// is not one document is read as NDJSON.
+fn parse_export(name: &str, contents: &str) -> Result<Vec<Exported>, String> {
+    let items: Vec<(String, Value)> = match serde_json::from_str::<Value>(contents) {
+        Ok(Value::Array(items)) => items
+            .into_iter()
+            .enumerate()
+            .map(|(i, item)| (format!("{}[{}]", name, i), item))
+            .collect(),
+        Ok(Value::Object(mut object)) => match object.remove("events") {
+            Some(Value::Array(items)) => items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| (format!("{}:events[{}]", name, i), item))
+                .collect(),
+            _ => vec![(name.to_string(), Value::Object(object))],
+        },
+        Ok(_) => return Err(format!("{}: expected events", name)),
+        Err(_) => contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let source = format!("{}:{}", name, i + 1);
+                serde_json::from_str(line)
+                    .map(|item| (source.clone(), item))
+                    .map_err(|e| format!("{}: {}", source, e))
+            })
+            .collect::<Result<_, _>>()?,
+    };
+
+    items
+        .into_iter()
+        .map(|(source, mut item)| {
+            let trust = envelope_trust(&item).map_err(|e| format!("{}: {}", source, e))?;
+            if let Some(event) = item.get_mut("event").filter(|e| e.is_object()) {
+                item = event.take();
+            }
+            serde_json::from_value(item)
+                .map(|event| Exported {
+                    source: source.clone(),
+                    event,
+                    trust,
+                })
+                .map_err(|e| format!("{}: {}", source, e))
+        })
+        .collect()
+}
+
+fn read_exports(files: &[String]) -> Result<Vec<Exported>, String> {
+    let stdin = ["-".to_string()];
+    let files = match files.is_empty() {
+        true => &stdin[..],
+        false => files,
+    };
+
+    let mut events = Vec::new();
+    for file in files {
+        let contents = match file.as_str() {
+            "-" => {
+                let mut contents = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut contents)
+                    .map_err(|e| format!("stdin: {}", e))?;
+                contents
+            }
+            path => std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?,
+        };
+        let name = match file.as_str() {
+            "-" => "stdin",
+            path => path,
+        };
+        events.extend(parse_export(name, &contents)?);
+    }
+    Ok(events)
+}
+
+// ============================================================================
+// Checks
+// ============================================================================
+
+/// Check an event's hash and signature, as the server does before it
+/// consults its key registry. With `trust`, the batch envelope is checked
+/// in place of the signature.
+fn verify_event(
+    event: &FactoEvent,
+    trust: Option<&EnvelopeTrust>,
+) -> Result<(), VerificationError> {
+    check_required_fields(event)?;
+    let canonical = build_canonical_form(event)?;
+    let event_hash = verify_hash(event, &canonical)?;
+    match trust {
+        Some(trust) => verify_envelope_membership(
+            &trust.envelope,
+            &trust.event_hashes,
+            &event_hash,
+            &event.proof.public_key,
+        ),
+        None => verify_signature(event, &canonical),
+    }
+}
+
+/// A break in a session's `prev_hash` linkage
+#[derive(Debug, PartialEq)]
+enum ChainBreak {
+    /// The event names a predecessor that is not in the export
+    MissingPredecessor { facto_id: String, prev_hash: String },
+    /// More than one event names the same predecessor
+    Fork {
+        prev_hash: String,
+        facto_ids: Vec<String>,
+    },
+    /// The session has no event starting the chain
+    NoGenesis,
+}
+
+impl std::fmt::Display for ChainBreak {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChainBreak::MissingPredecessor {
+                facto_id,
+                prev_hash,
+            } => write!(
+                f,
+                "{} follows {}, which is not in the export",
+                facto_id, prev_hash
+            ),
+            ChainBreak::Fork {
+                prev_hash,
+                facto_ids,
+            } => write!(f, "{} all follow {}", facto_ids.join(", "), prev_hash),
+            ChainBreak::NoGenesis => write!(f, "no event starts the chain"),
+        }
+    }
+}
+
+/// Follow a session's events from the genesis hash, returning them in chain
+/// order and every break found. Events are linked by their claimed
+/// `event_hash`; `verify` checks that the claims hold.
+fn audit_chain<'a>(events: &[&'a FactoEvent]) -> (Vec<&'a FactoEvent>, Vec<ChainBreak>) {
+    let mut successors: HashMap<&str, Vec<&'a FactoEvent>> = HashMap::new();
+    for event in events {
+        successors
+            .entry(event.proof.prev_hash.as_str())
+            .or_default()
+            .push(event);
+    }
+    let hashes: HashSet<&str> = events.iter().map(|e| e.proof.event_hash.as_str()).collect();
+
+    let mut breaks = Vec::new();
+    let mut forks: Vec<_> = successors
+        .iter()
+        .filter(|(_, next)| next.len() > 1)
+        .collect();
+    forks.sort_by_key(|(prev_hash, _)| *prev_hash);
+    for (prev_hash, next) in forks {
+        breaks.push(ChainBreak::Fork {
+            prev_hash: prev_hash.to_string(),
+            facto_ids: next.iter().map(|e| e.facto_id.clone()).collect(),
+        });
+    }
+    for event in events {
+        let prev_hash = event.proof.prev_hash.as_str();
+        if prev_hash != GENESIS_HASH && !hashes.contains(prev_hash) {
+            breaks.push(ChainBreak::MissingPredecessor {
+                facto_id: event.facto_id.clone(),
+                prev_hash: prev_hash.to_string(),
+            });
+        }
+    }
+    if !events.is_empty() && !successors.contains_key(GENESIS_HASH) {
+        breaks.push(ChainBreak::NoGenesis);
+    }
+
+    // Walk the first successor at each step, stopping at cycles
+    let mut ordered = Vec::new();
+    let mut seen = HashSet::new();
+    let mut head = GENESIS_HASH;
+    while let Some(event) = successors.get(head).and_then(|next| next.first()) {
+        if !seen.insert(event.facto_id.as_str()) {
+            break;
+        }
+        ordered.push(*event);
+        head = event.proof.event_hash.as_str();
+    }
+    (ordered, breaks)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+fn verify(files: &[String]) -> Result<bool, String> {
+    let events = read_exports(files)?;
+    let mut failed = 0;
+    for Exported {
+        source,
+        event,
+        trust,
+    } in &events
+    {
+        match verify_event(event, trust.as_ref()) {
+            Ok(()) => println!("ok    {}", event.facto_id),
+            Err(e) => {
+                failed += 1;
+                println!("FAIL  {} ({}): {}", event.facto_id, source, e);
+            }
+        }
+    }
+    println!(
+        "{} events: {} verified, {} failed",
+        events.len(),
+        events.len() - failed,
+        failed
+    );
+    Ok(failed == 0)
+}
+
+fn audit(session_id: &str, files: &[String]) -> Result<bool, String> {
+    let events = read_exports(files)?;
+    let session: Vec<&FactoEvent> = events
+        .iter()
+        .map(|exported| &exported.event)
+        .filter(|event| event.session_id == session_id)
+        .collect();
+    if session.is_empty() {
+        return Err(format!("no events of session {}", session_id));
+    }
+
+    let (ordered, breaks) = audit_chain(&session);
+    for (i, event) in ordered.iter().enumerate() {
+        println!(
+            "{:>5}  {}  {}",
+            i + 1,
+            event.proof.event_hash,
+            event.facto_id
+        );
+    }
+    for chain_break in &breaks {
+        println!("BREAK {}", chain_break);
+    }
+    let unreached = session.len() - ordered.len();
+    if unreached > 0 {
+        println!(
+            "{} events are not reachable from the start of the chain",
+            unreached
+        );
+    }
+    println!(
+        "session {}: {} events, {} linked, {} breaks",
+        session_id,
+        session.len(),
+        ordered.len(),
+        breaks.len()
+    );
+    Ok(breaks.is_empty() && unreached == 0)
+}
+
+fn canonicalize(facto_id: Option<&str>, files: &[String]) -> Result<bool, String> {
+    let mut found = false;
+    for Exported { source, event, .. } in read_exports(files)? {
+        if facto_id.is_some_and(|id| id != event.facto_id) {
+            continue;
+        }
+        found = true;
+        let canonical = build_canonical_form(&event).map_err(|e| format!("{}: {}", source, e))?;
+        println!("{}", canonical);
+    }
+    match (found, facto_id) {
+        (false, Some(facto_id)) => Err(format!("no event {}", facto_id)),
+        _ => Ok(true),
+    }
+}
+
+/// The value of `--name VALUE` or `--name=VALUE`, removed from `args`
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let prefix = format!("{}=", name);
+    let Some(i) = args
+        .iter()
+        .position(|a| a == name || a.starts_with(&prefix))
+    else {
+        return Ok(None);
+    };
+    let arg = args.remove(i);
+    match arg.strip_prefix(&prefix) {
+        Some(value) => Ok(Some(value.to_string())),
+        None if i < args.len() => Ok(Some(args.remove(i))),
+        None => Err(format!("{} needs a value", name)),
+    }
+}
+
+fn run(mut args: Vec<String>) -> Result<bool, String> {
+    if args.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let command = args.remove(0);
+    let session_id = take_option(&mut args, "--session")?;
+    let facto_id = take_option(&mut args, "--facto-id")?;
+    if let Some(flag) = args.iter().find(|a| a.starts_with("--")) {
+        return Err(format!("unknown option {}\n\n{}", flag, USAGE));
+    }
+
+    match command.as_str() {
+        "verify" => verify(&args),
+        "audit-chain" => match session_id {
+            Some(session_id) => audit(&session_id, &args),
+            None => Err("audit-chain needs --session".to_string()),
+        },
+        "canonicalize" => canonicalize(facto_id.as_deref(), &args),
+        "-h" | "--help" | "help" => {
+            println!("{}", USAGE);
+            Ok(true)
+        }
+        other => Err(format!("unknown command {}\n\n{}", other, USAGE)),
+    }
+}
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("facto: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use facto_ingestion::testing::{sign_test_event, test_event};
+
+    #[test]
+    fn test_exports_are_verified_and_chained() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let first = sign_test_event(test_event(), &key);
+        let mut second = test_event();
+        second.facto_id = "tr-test-456".to_string();
+        second.proof.prev_hash = first.proof.event_hash.clone();
+        let second = sign_test_event(second, &key);
+
+        // NDJSON of export lines and a JSON array read the same
+        let ndjson = [&first, &second]
+            .iter()
+            .map(|e| serde_json::json!({"event": e, "envelope": {}}).to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let array = serde_json::to_string(&[&second, &first]).unwrap();
+        for contents in [ndjson, array] {
+            let events = parse_export("export", &contents).unwrap();
+            assert_eq!(events.len(), 2);
+            assert!(events.iter().all(|e| verify_event(&e.event, None).is_ok()));
+        }
+
+        let (ordered, breaks) = audit_chain(&[&second, &first]);
+        assert_eq!(ordered.len(), 2);
+        assert_eq!(ordered[0].facto_id, first.facto_id);
+        assert!(breaks.is_empty());
+
+        let (ordered, breaks) = audit_chain(&[&second]);
+        assert!(ordered.is_empty());
+        assert_eq!(
+            breaks,
+            vec![
+                ChainBreak::MissingPredecessor {
+                    facto_id: second.facto_id.clone(),
+                    prev_hash: first.proof.event_hash.clone(),
+                },
+                ChainBreak::NoGenesis,
+            ]
+        );
+
+        let mut tampered = second.clone();
+        tampered.status = "error".to_string();
+        assert!(matches!(
+            verify_event(&tampered, None),
+            Err(VerificationError::HashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_batch_envelope_trust_stands_in_for_signature() {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+        use ed25519_dalek::Signer as _;
+        use facto_ingestion::crypto::compute_batch_hash;
+
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let mut event = sign_test_event(test_event(), &key);
+        event.proof.signature = BASE64.encode([0u8; 64]);
+        let batch_hash = compute_batch_hash(std::slice::from_ref(&event));
+        let trust_basis = serde_json::json!({
+            "type": "batch_envelope",
+            "envelope": {
+                "batch_hash": batch_hash,
+                "public_key": event.proof.public_key,
+                "signature": BASE64.encode(key.sign(batch_hash.as_bytes()).to_bytes()),
+            },
+            "event_hashes": [event.proof.event_hash],
+        });
+        let line = |trust_basis: &Value| {
+            serde_json::json!({
+                "event": event,
+                "envelope": {"verification": {"trust_basis": trust_basis}},
+            })
+            .to_string()
+        };
+
+        let exported = parse_export("export", &line(&trust_basis)).unwrap();
+        assert!(verify_event(&exported[0].event, exported[0].trust.as_ref()).is_ok());
+        assert!(matches!(
+            verify_event(&exported[0].event, None),
+            Err(VerificationError::SignatureMismatch(_))
+        ));
+
+        // An envelope that does not cover the event vouches for nothing
+        let mut other = trust_basis.clone();
+        other["event_hashes"] = serde_json::json!([batch_hash]);
+        let exported = parse_export("export", &line(&other)).unwrap();
+        assert!(verify_event(&exported[0].event, exported[0].trust.as_ref()).is_err());
+    }
+}
@@ -0,0 +1,355 @@
+//! Session chain heads: the latest published event of each session.
+//!
+//! Replicas publishing events of the same session race to move its head. In
+//! the shared bucket every move is a compare-and-swap decided on the head
+//! it replaces, so a replica that lost the race to a later event never puts
+//! the head back. Deployments without the shared bucket can instead route
+//! each session to a single replica by session hash, with
+//! `CHAIN_HEAD_COORDINATION=routed`; each replica then refuses the events of
+//! sessions it does not own.
+
+use crate::protocol::FactoEvent;
+use anyhow::Context;
+use axum::async_trait;
+use dashmap::{mapref::entry::Entry, DashMap};
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{sync::Arc, time::Duration};
+
+use crate::shared::{self, KvBucket, Swap};
+use crate::verification::now_nanos;
+
+/// Bucket holding the chain head of each session
+pub const CHAIN_HEAD_BUCKET: &str = "facto_chain_heads";
+
+/// The most recent accepted event of a session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainHead {
+    pub agent_id: String,
+    pub facto_id: String,
+    pub event_hash: String,
+    /// When the event completed, by the agent's clock
+    #[serde(default)]
+    pub completed_at: i64,
+    pub updated_at: i64,
+}
+
+impl ChainHead {
+    fn of(event: &FactoEvent, event_hash: &str) -> Self {
+        Self {
+            agent_id: event.agent_id.clone(),
+            facto_id: event.facto_id.clone(),
+            event_hash: event_hash.to_string(),
+            completed_at: event.completed_at,
+            updated_at: now_nanos(),
+        }
+    }
+}
+
+/// How publishing an event moved its session's chain head
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadUpdate {
+    /// The event continues the head, or the session had none; it is the
+    /// head now
+    Linked,
+    /// The event is the head already
+    Current,
+    /// The event does not continue the head and completed before it, so a
+    /// later event was published first; the head stays
+    Stale,
+    /// The event does not continue the head and completed after it, past a
+    /// gap or on a fork; it is the head now
+    Forked,
+}
+
+impl HeadUpdate {
+    /// Decide on the head an event would replace
+    fn of(head: Option<&ChainHead>, event: &FactoEvent, event_hash: &str) -> Self {
+        match head {
+            None => HeadUpdate::Linked,
+            Some(head) if head.event_hash == event_hash => HeadUpdate::Current,
+            Some(head) if head.event_hash == event.proof.prev_hash => HeadUpdate::Linked,
+            Some(head) if event.completed_at < head.completed_at => HeadUpdate::Stale,
+            Some(_) => HeadUpdate::Forked,
+        }
+    }
+
+    fn moves_head(&self) -> bool {
+        matches!(self, HeadUpdate::Linked | HeadUpdate::Forked)
+    }
+
+    /// Count updates that did not extend the chain
+    fn count(self) -> Self {
+        let outcome = match self {
+            HeadUpdate::Stale => "stale",
+            HeadUpdate::Forked => "forked",
+            HeadUpdate::Linked | HeadUpdate::Current => return self,
+        };
+        counter!("facto_chain_head_conflicts_total", "outcome" => outcome).increment(1);
+        self
+    }
+}
+
+/// The latest accepted event hash of each session, keyed by tenant-scoped
+/// session so tenants reusing a session id keep separate chains
+#[async_trait]
+pub trait ChainHeadStore: Send + Sync {
+    async fn get(&self, session: &str) -> Option<ChainHead>;
+
+    /// Record an event that was just published for its session
+    async fn advance(&self, session: &str, event: &FactoEvent, event_hash: &str) -> HeadUpdate;
+}
+
+/// Tracks the latest accepted event hash of each session seen by this replica
+#[derive(Default)]
+pub struct ChainHeads {
+    heads: DashMap<String, ChainHead>,
+}
+
+impl ChainHeads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn apply(&self, session: &str, event: &FactoEvent, event_hash: &str) -> HeadUpdate {
+        match self.heads.entry(session.to_string()) {
+            Entry::Occupied(mut entry) => {
+                let update = HeadUpdate::of(Some(entry.get()), event, event_hash);
+                if update.moves_head() {
+                    entry.insert(ChainHead::of(event, event_hash));
+                }
+                update
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(ChainHead::of(event, event_hash));
+                HeadUpdate::Linked
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ChainHeadStore for ChainHeads {
+    async fn get(&self, session: &str) -> Option<ChainHead> {
+        self.heads.get(session).map(|h| h.clone())
+    }
+
+    async fn advance(&self, session: &str, event: &FactoEvent, event_hash: &str) -> HeadUpdate {
+        self.apply(session, event, event_hash).count()
+    }
+}
+
+/// Chain heads shared by all replicas, so freezes and annotations record
+/// the session's latest event whichever replica accepted it
+pub struct KvChainHeads {
+    bucket: Arc<KvBucket>,
+    local: ChainHeads,
+}
+
+impl KvChainHeads {
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            bucket: Arc::new(KvBucket::new(CHAIN_HEAD_BUCKET, max_age)),
+            local: ChainHeads::new(),
+        }
+    }
+
+    pub fn bucket(&self) -> Arc<KvBucket> {
+        self.bucket.clone()
+    }
+}
+
+#[async_trait]
+impl ChainHeadStore for KvChainHeads {
+    async fn get(&self, session: &str) -> Option<ChainHead> {
+        match self.bucket.get(session).await {
+            Ok(head) => head.and_then(|h| serde_json::from_slice(&h).ok()),
+            Err(e) => {
+                shared::fallback(e);
+                self.local.get(session).await
+            }
+        }
+    }
+
+    async fn advance(&self, session: &str, event: &FactoEvent, event_hash: &str) -> HeadUpdate {
+        let head = ChainHead::of(event, event_hash);
+        let value = serde_json::to_vec(&head).expect("chain heads serialize");
+        let swapped = self
+            .bucket
+            .swap(session, |current| {
+                let current: Option<ChainHead> =
+                    current.and_then(|c| serde_json::from_slice(c).ok());
+                let update = HeadUpdate::of(current.as_ref(), event, event_hash);
+                match update.moves_head() {
+                    true => Swap::Write(value.clone(), update),
+                    false => Swap::Keep(update),
+                }
+            })
+            .await;
+        let update = match swapped {
+            Ok(update) => {
+                if update.moves_head() {
+                    self.local.heads.insert(session.to_string(), head);
+                }
+                update
+            }
+            Err(e) => {
+                shared::fallback(e);
+                self.local.apply(session, event, event_hash)
+            }
+        };
+        update.count()
+    }
+}
+
+// ============================================================================
+// Session Routing
+// ============================================================================
+
+/// Sessions assigned to replicas by the hash of their id, so each session's
+/// head has a single writer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainRouting {
+    index: u64,
+    count: u64,
+}
+
+impl ChainRouting {
+    /// Read `CHAIN_HEAD_COORDINATION`: `cas`, the default, or `routed`, for
+    /// which `REPLICA_INDEX` and `REPLICA_COUNT` place this replica
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        match std::env::var("CHAIN_HEAD_COORDINATION").as_deref() {
+            Err(_) | Ok("cas") => return Ok(None),
+            Ok("routed") => {}
+            Ok(other) => anyhow::bail!(
+                "CHAIN_HEAD_COORDINATION must be cas or routed, not {}",
+                other
+            ),
+        }
+        let index: u64 = std::env::var("REPLICA_INDEX")
+            .map_err(|_| anyhow::anyhow!("CHAIN_HEAD_COORDINATION=routed needs REPLICA_INDEX"))?
+            .parse()
+            .context("Invalid REPLICA_INDEX")?;
+        let count: u64 = std::env::var("REPLICA_COUNT")
+            .map_err(|_| anyhow::anyhow!("CHAIN_HEAD_COORDINATION=routed needs REPLICA_COUNT"))?
+            .parse()
+            .context("Invalid REPLICA_COUNT")?;
+        Self::new(index, count).map(Some)
+    }
+
+    pub fn new(index: u64, count: u64) -> anyhow::Result<Self> {
+        if count == 0 {
+            anyhow::bail!("REPLICA_COUNT must be at least 1");
+        }
+        if index >= count {
+            anyhow::bail!(
+                "REPLICA_INDEX must be below REPLICA_COUNT, got {} of {}",
+                index,
+                count
+            );
+        }
+        Ok(Self { index, count })
+    }
+
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The replica a session belongs to: the first 8 bytes of the SHA-256
+    /// of its id, big-endian, modulo the replica count
+    pub fn owner(&self, session_id: &str) -> u64 {
+        let digest = Sha256::digest(session_id.as_bytes());
+        let prefix: [u8; 8] = digest[..8].try_into().expect("digest is 32 bytes");
+        u64::from_be_bytes(prefix) % self.count
+    }
+
+    /// The owner of a session that belongs to another replica
+    pub fn misrouted(&self, session_id: &str) -> Option<u64> {
+        let owner = self.owner(session_id);
+        (owner != self.index).then_some(owner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoped_id;
+    use crate::testing::test_event;
+
+    fn event(facto_id: &str, prev_hash: &str, completed_at: i64) -> FactoEvent {
+        let mut event = test_event();
+        event.facto_id = facto_id.to_string();
+        event.proof.prev_hash = prev_hash.to_string();
+        event.completed_at = completed_at;
+        event
+    }
+
+    #[tokio::test]
+    async fn test_late_events_do_not_move_the_head_back() {
+        let heads = ChainHeads::new();
+        let first = event("f1", "genesis", 1);
+        let second = event("f2", "h1", 2);
+        let third = event("f3", "h2", 3);
+        let session = first.session_id.clone();
+
+        assert_eq!(
+            heads.advance(&session, &first, "h1").await,
+            HeadUpdate::Linked
+        );
+        assert_eq!(
+            heads.advance(&session, &third, "h3").await,
+            HeadUpdate::Forked
+        );
+        // The replica that published the second event lost the race
+        assert_eq!(
+            heads.advance(&session, &second, "h2").await,
+            HeadUpdate::Stale
+        );
+        assert_eq!(
+            heads.advance(&session, &third, "h3").await,
+            HeadUpdate::Current
+        );
+        assert_eq!(heads.get(&session).await.unwrap().event_hash, "h3");
+    }
+
+    #[tokio::test]
+    async fn test_tenants_reusing_a_session_id_have_separate_heads() {
+        let heads = ChainHeads::new();
+        let acme = event("f1", "genesis", 1);
+        let globex = event("f2", "genesis", 2);
+        let acme_session = scoped_id(Some("acme"), &acme.session_id);
+        let globex_session = scoped_id(Some("globex"), &globex.session_id);
+
+        assert_eq!(
+            heads.advance(&acme_session, &acme, "h1").await,
+            HeadUpdate::Linked
+        );
+        // Not a fork of the other tenant's chain
+        assert_eq!(
+            heads.advance(&globex_session, &globex, "h2").await,
+            HeadUpdate::Linked
+        );
+        assert_eq!(heads.get(&acme_session).await.unwrap().event_hash, "h1");
+        assert_eq!(heads.get(&globex_session).await.unwrap().event_hash, "h2");
+        assert!(heads.get(&acme.session_id).await.is_none());
+    }
+
+    #[test]
+    fn test_sessions_route_to_one_replica() {
+        let routing: Vec<ChainRouting> = (0..3).map(|i| ChainRouting::new(i, 3).unwrap()).collect();
+        for session in ["s1", "s2", "s3", "s4"] {
+            let owners: Vec<bool> = routing
+                .iter()
+                .map(|r| r.misrouted(session).is_none())
+                .collect();
+            assert_eq!(owners.iter().filter(|owns| **owns).count(), 1);
+        }
+        assert!(ChainRouting::new(3, 3).is_err());
+        assert!(ChainRouting::new(0, 0).is_err());
+    }
+}
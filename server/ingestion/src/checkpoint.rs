@@ -0,0 +1,370 @@
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, RwLock},
+};
+use tokio::sync::Notify;
+
+use crate::verification::{now_nanos, ServerSigner};
+
+/// NATS subject signed checkpoints are published on
+pub const CHECKPOINT_SUBJECT: &str = "facto.checkpoints";
+
+type Hash = [u8; 32];
+
+// ============================================================================
+// Merkle Tree
+// ============================================================================
+//
+// The tree follows RFC 6962 with SHA3-256: leaves are H(0x00 || event_hash),
+// interior nodes H(0x01 || left || right), and a tree of n leaves splits at
+// the largest power of two below n.
+
+fn leaf_hash(event_hash: &str) -> Hash {
+    let bytes = hex::decode(event_hash).unwrap_or_else(|_| event_hash.as_bytes().to_vec());
+    let mut hasher = Sha3_256::new();
+    hasher.update([0x00]);
+    hasher.update(bytes);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha3_256::new();
+    hasher.update([0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Largest power of two strictly below `n` (n > 1)
+fn split_point(n: usize) -> usize {
+    let mut k = 1;
+    while k << 1 < n {
+        k <<= 1;
+    }
+    k
+}
+
+fn merkle_root(leaves: &[Hash]) -> Hash {
+    match leaves.len() {
+        0 => Sha3_256::digest([]).into(),
+        1 => leaves[0],
+        n => {
+            let k = split_point(n);
+            node_hash(&merkle_root(&leaves[..k]), &merkle_root(&leaves[k..]))
+        }
+    }
+}
+
+/// Sibling hashes from leaf `index` up to the root
+fn audit_path(index: usize, leaves: &[Hash]) -> Vec<Hash> {
+    let n = leaves.len();
+    if n <= 1 {
+        return Vec::new();
+    }
+    let k = split_point(n);
+    if index < k {
+        let mut path = audit_path(index, &leaves[..k]);
+        path.push(merkle_root(&leaves[k..]));
+        path
+    } else {
+        let mut path = audit_path(index - k, &leaves[k..]);
+        path.push(merkle_root(&leaves[..k]));
+        path
+    }
+}
+
+// ============================================================================
+// Checkpoints
+// ============================================================================
+
+/// A server-signed Merkle root over the event hashes accepted since the
+/// previous checkpoint. Checkpoints chain through `prev_root`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub sequence: u64,
+    pub root: String,
+    /// Root of the previous checkpoint, empty for the first
+    pub prev_root: String,
+    pub tree_size: u64,
+    pub created_at: i64,
+    pub signer_id: String,
+    pub signer_public_key: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub signature: String,
+}
+
+impl Checkpoint {
+    /// The bytes covered by the checkpoint signature: the sorted-key JSON of
+    /// the checkpoint with `signature` omitted
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let unsigned = Self {
+            signature: String::new(),
+            ..self.clone()
+        };
+        let value = serde_json::to_value(&unsigned).unwrap_or_default();
+        serde_json::to_vec(&value).unwrap_or_default()
+    }
+}
+
+/// Proof that an event hash is a leaf of a checkpoint's tree
+#[derive(Debug, Clone, Serialize)]
+pub struct InclusionProof {
+    pub facto_id: String,
+    pub event_hash: String,
+    pub leaf_index: u64,
+    pub tree_size: u64,
+    /// Sibling hashes from the leaf up to the root
+    pub audit_path: Vec<String>,
+    pub checkpoint: Checkpoint,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ProofError {
+    #[error("facto_id is not checkpointed yet")]
+    Pending,
+    #[error("facto_id is not in a retained checkpoint")]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    Pending,
+    Sealed { sequence: u64, index: usize },
+}
+
+struct PendingLeaf {
+    key: String,
+    facto_id: String,
+    event_hash: String,
+}
+
+struct Sealed {
+    checkpoint: Checkpoint,
+    keys: Vec<String>,
+    facto_ids: Vec<String>,
+    event_hashes: Vec<String>,
+    leaves: Vec<Hash>,
+}
+
+/// Collects accepted event hashes and seals them into signed checkpoints.
+///
+/// The last `retention` checkpoints and their leaves are kept in memory to
+/// serve inclusion proofs; older checkpoints remain verifiable from the
+/// copies published on [`CHECKPOINT_SUBJECT`]. Sequences and the
+/// `prev_root` chain start over when the process restarts.
+pub struct Checkpoints {
+    pending: Mutex<Vec<PendingLeaf>>,
+    sealed: RwLock<VecDeque<Sealed>>,
+    index: DashMap<String, Location>,
+    max_events: usize,
+    retention: usize,
+    wake: Notify,
+}
+
+impl Checkpoints {
+    pub fn new(max_events: usize, retention: usize) -> Self {
+        Self {
+            pending: Mutex::new(Vec::new()),
+            sealed: RwLock::new(VecDeque::new()),
+            index: DashMap::new(),
+            max_events: max_events.max(1),
+            retention: retention.max(1),
+            wake: Notify::new(),
+        }
+    }
+
+    /// Add an accepted event to the next checkpoint. `key` is the
+    /// tenant-scoped facto_id proofs are looked up by.
+    pub fn record(&self, key: String, facto_id: &str, event_hash: &str) {
+        let mut pending = self.pending.lock().unwrap();
+        self.index.insert(key.clone(), Location::Pending);
+        pending.push(PendingLeaf {
+            key,
+            facto_id: facto_id.to_string(),
+            event_hash: event_hash.to_string(),
+        });
+        if pending.len() >= self.max_events {
+            self.wake.notify_one();
+        }
+    }
+
+    /// Wait until a checkpoint is full or `timeout` elapses
+    pub async fn wait(&self, timeout: std::time::Duration) {
+        let _ = tokio::time::timeout(timeout, self.wake.notified()).await;
+    }
+
+    /// Seal pending events into a signed checkpoint, if there are any
+    pub fn seal(&self, signer: &ServerSigner) -> Option<Checkpoint> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if pending.is_empty() {
+            return None;
+        }
+
+        let mut sealed = self.sealed.write().unwrap();
+        let (sequence, prev_root) = sealed.back().map_or((1, String::new()), |s| {
+            (s.checkpoint.sequence + 1, s.checkpoint.root.clone())
+        });
+
+        let mut keys = Vec::with_capacity(pending.len());
+        let mut facto_ids = Vec::with_capacity(pending.len());
+        let mut event_hashes = Vec::with_capacity(pending.len());
+        for leaf in pending {
+            keys.push(leaf.key);
+            facto_ids.push(leaf.facto_id);
+            event_hashes.push(leaf.event_hash);
+        }
+        let leaves: Vec<Hash> = event_hashes.iter().map(|h| leaf_hash(h)).collect();
+
+        let mut checkpoint = Checkpoint {
+            sequence,
+            root: hex::encode(merkle_root(&leaves)),
+            prev_root,
+            tree_size: leaves.len() as u64,
+            created_at: now_nanos(),
+            signer_id: signer.instance_id().to_string(),
+            signer_public_key: signer.public_key_base64(),
+            signature: String::new(),
+        };
+        checkpoint.signature = signer.sign_base64(&checkpoint.signing_payload());
+
+        for (index, key) in keys.iter().enumerate() {
+            self.index
+                .insert(key.clone(), Location::Sealed { sequence, index });
+        }
+        sealed.push_back(Sealed {
+            checkpoint: checkpoint.clone(),
+            keys,
+            facto_ids,
+            event_hashes,
+            leaves,
+        });
+
+        while sealed.len() > self.retention {
+            if let Some(expired) = sealed.pop_front() {
+                for key in expired.keys {
+                    self.index.remove_if(&key, |_, location| {
+                        matches!(location, Location::Sealed { sequence, .. } if *sequence == expired.checkpoint.sequence)
+                    });
+                }
+            }
+        }
+
+        Some(checkpoint)
+    }
+
+    /// Retained checkpoints, newest first
+    pub fn list(&self, limit: usize) -> Vec<Checkpoint> {
+        self.sealed
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .take(limit)
+            .map(|s| s.checkpoint.clone())
+            .collect()
+    }
+
+    /// Inclusion proof for the event recorded under `key`
+    pub fn proof(&self, key: &str) -> Result<InclusionProof, ProofError> {
+        let (sequence, index) = match self.index.get(key).map(|l| *l) {
+            Some(Location::Sealed { sequence, index }) => (sequence, index),
+            Some(Location::Pending) => return Err(ProofError::Pending),
+            None => return Err(ProofError::Unknown),
+        };
+
+        let sealed = self.sealed.read().unwrap();
+        let first = sealed
+            .front()
+            .ok_or(ProofError::Unknown)?
+            .checkpoint
+            .sequence;
+        let checkpoint = sealed
+            .get((sequence - first) as usize)
+            .ok_or(ProofError::Unknown)?;
+
+        Ok(InclusionProof {
+            facto_id: checkpoint.facto_ids[index].clone(),
+            event_hash: checkpoint.event_hashes[index].clone(),
+            leaf_index: index as u64,
+            tree_size: checkpoint.leaves.len() as u64,
+            audit_path: audit_path(index, &checkpoint.leaves)
+                .iter()
+                .map(hex::encode)
+                .collect(),
+            checkpoint: checkpoint.checkpoint.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+
+    /// Recompute the root from an inclusion proof (RFC 9162, 2.1.3.2)
+    fn root_from_proof(proof: &InclusionProof) -> String {
+        let mut index = proof.leaf_index;
+        let mut last = proof.tree_size - 1;
+        let mut hash = leaf_hash(&proof.event_hash);
+        for sibling in &proof.audit_path {
+            let sibling: Hash = hex::decode(sibling).unwrap().try_into().unwrap();
+            if index % 2 == 1 || index == last {
+                hash = node_hash(&sibling, &hash);
+                while index.is_multiple_of(2) && index != 0 {
+                    index >>= 1;
+                    last >>= 1;
+                }
+            } else {
+                hash = node_hash(&hash, &sibling);
+            }
+            index >>= 1;
+            last >>= 1;
+        }
+        hex::encode(hash)
+    }
+
+    fn signer() -> ServerSigner {
+        ServerSigner::new("test".to_string(), SigningKey::from_bytes(&[9u8; 32]))
+    }
+
+    #[test]
+    fn test_inclusion_proofs_verify() {
+        let checkpoints = Checkpoints::new(100, 10);
+        for i in 0..7 {
+            let hash = hex::encode(Sha3_256::digest(format!("event-{}", i)));
+            checkpoints.record(format!("tr-{}", i), &format!("tr-{}", i), &hash);
+        }
+        assert_eq!(checkpoints.proof("tr-3").unwrap_err(), ProofError::Pending);
+
+        let checkpoint = checkpoints.seal(&signer()).unwrap();
+        assert_eq!(checkpoint.tree_size, 7);
+        for i in 0..7 {
+            let proof = checkpoints.proof(&format!("tr-{}", i)).unwrap();
+            assert_eq!(root_from_proof(&proof), checkpoint.root);
+        }
+        assert_eq!(checkpoints.proof("tr-9").unwrap_err(), ProofError::Unknown);
+    }
+
+    #[test]
+    fn test_checkpoints_chain_and_expire() {
+        let checkpoints = Checkpoints::new(100, 2);
+        let signer = signer();
+        assert!(checkpoints.seal(&signer).is_none());
+
+        let mut roots = Vec::new();
+        for i in 0..3 {
+            checkpoints.record(format!("tr-{}", i), "tr", &"ab".repeat(32));
+            roots.push(checkpoints.seal(&signer).unwrap());
+        }
+        assert_eq!(roots[1].prev_root, roots[0].root);
+        assert_eq!(roots[2].sequence, 3);
+
+        // Only the last two checkpoints are retained
+        assert_eq!(checkpoints.list(10).len(), 2);
+        assert_eq!(checkpoints.proof("tr-0").unwrap_err(), ProofError::Unknown);
+        assert!(checkpoints.proof("tr-2").is_ok());
+    }
+}
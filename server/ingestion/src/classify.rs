@@ -0,0 +1,470 @@
+use axum::async_trait;
+use metrics::counter;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::Arc, time::Duration};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::sinks::{AcceptedEvent, FanoutSink};
+use crate::verification::{now_nanos, ServerSigner};
+use crate::FactoEvent;
+
+/// NATS subject signed event classifications are published on
+pub const CLASSIFICATION_SUBJECT: &str = "facto.control.classifications";
+
+/// Input or output data that looks like personal data
+pub const PII_RISK: &str = "pii_risk";
+/// The action ran code or shell commands
+pub const CODE_EXECUTION: &str = "code_execution";
+/// The action called a service outside the agent
+pub const EXTERNAL_API_CALL: &str = "external_api_call";
+
+// ============================================================================
+// Records
+// ============================================================================
+
+/// A category assigned to an event by a classifier
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    pub category: String,
+    pub classifier: String,
+    /// Rule that matched, for rule-based classifiers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+}
+
+/// The labels of one event, signed by the server.
+///
+/// Like investigator annotations, labels never enter the agent's hash chain;
+/// they are published on [`CLASSIFICATION_SUBJECT`] and stored beside the
+/// event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Classification {
+    pub facto_id: String,
+    pub agent_id: String,
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub action_type: String,
+    pub completed_at: i64,
+    pub labels: Vec<Label>,
+    pub classified_at: i64,
+    #[serde(default)]
+    pub signer_public_key: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub signature: String,
+}
+
+impl Classification {
+    /// The bytes covered by the classification signature: the sorted-key
+    /// JSON of the classification with `signature` omitted
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let unsigned = Self {
+            signature: String::new(),
+            ..self.clone()
+        };
+        let value = serde_json::to_value(&unsigned).unwrap_or_default();
+        serde_json::to_vec(&value).unwrap_or_default()
+    }
+
+    pub fn sign(&mut self, signer: &ServerSigner) {
+        self.signer_public_key = signer.public_key_base64();
+        self.signature = signer.sign_base64(&self.signing_payload());
+    }
+}
+
+// ============================================================================
+// Classifiers
+// ============================================================================
+
+/// Labels accepted events after ingestion
+#[async_trait]
+pub trait Classifier: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
+    async fn classify(&self, event: &FactoEvent) -> anyhow::Result<Vec<Label>>;
+}
+
+/// A rule as written in a rules file. Every condition given must match;
+/// patterns are regular expressions.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RuleConfig {
+    pub name: String,
+    pub category: String,
+    pub action_type: Option<String>,
+    /// Tag that must be present
+    pub tag: Option<String>,
+    /// Pattern for the value of `tag`
+    pub tag_value: Option<String>,
+    /// Pattern for the JSON of the input and output data
+    pub content: Option<String>,
+    /// Pattern for the JSON of the tool calls
+    pub tool_calls: Option<String>,
+}
+
+struct Rule {
+    name: String,
+    category: String,
+    action_type: Option<Regex>,
+    tag: Option<(String, Option<Regex>)>,
+    content: Option<Regex>,
+    tool_calls: Option<Regex>,
+}
+
+impl Rule {
+    fn compile(config: RuleConfig) -> anyhow::Result<Self> {
+        let pattern = |p: Option<String>| -> anyhow::Result<Option<Regex>> {
+            p.map(|p| Regex::new(&p)).transpose().map_err(|e| {
+                anyhow::anyhow!("invalid pattern in classifier rule {}: {}", config.name, e)
+            })
+        };
+        let rule = Self {
+            action_type: pattern(config.action_type.clone())?,
+            tag: match config.tag.clone() {
+                Some(tag) => Some((tag, pattern(config.tag_value.clone())?)),
+                None => None,
+            },
+            content: pattern(config.content.clone())?,
+            tool_calls: pattern(config.tool_calls.clone())?,
+            name: config.name,
+            category: config.category,
+        };
+        if rule.action_type.is_none()
+            && rule.tag.is_none()
+            && rule.content.is_none()
+            && rule.tool_calls.is_none()
+        {
+            anyhow::bail!("classifier rule {} has no condition", rule.name);
+        }
+        Ok(rule)
+    }
+
+    fn matches(&self, event: &FactoEvent, content: &str, tool_calls: &str) -> bool {
+        self.action_type
+            .as_ref()
+            .is_none_or(|p| p.is_match(&event.action_type))
+            && self.tag.as_ref().is_none_or(|(tag, value)| {
+                event
+                    .execution_meta
+                    .tags
+                    .get(tag)
+                    .is_some_and(|v| value.as_ref().is_none_or(|p| p.is_match(v)))
+            })
+            && self.content.as_ref().is_none_or(|p| p.is_match(content))
+            && self
+                .tool_calls
+                .as_ref()
+                .is_none_or(|p| p.is_match(tool_calls))
+    }
+}
+
+/// Classifies events with regular expression and tag rules. Each category is
+/// labelled once, by the first rule of that category that matches.
+pub struct RuleClassifier {
+    rules: Vec<Rule>,
+}
+
+impl RuleClassifier {
+    pub fn new(rules: Vec<RuleConfig>) -> anyhow::Result<Self> {
+        Ok(Self {
+            rules: rules
+                .into_iter()
+                .map(Rule::compile)
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+
+    /// Rules for the standard categories
+    pub fn builtin() -> Self {
+        let rule = |name: &str, category: &str| RuleConfig {
+            name: name.to_string(),
+            category: category.to_string(),
+            action_type: None,
+            tag: None,
+            tag_value: None,
+            content: None,
+            tool_calls: None,
+        };
+        let rules = vec![
+            RuleConfig {
+                content: Some(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}".to_string()),
+                ..rule("email_address", PII_RISK)
+            },
+            RuleConfig {
+                content: Some(r"\b\d{3}-\d{2}-\d{4}\b".to_string()),
+                ..rule("us_ssn", PII_RISK)
+            },
+            RuleConfig {
+                content: Some(r"\b(?:\d{4}[ -]?){3}\d{4}\b".to_string()),
+                ..rule("card_number", PII_RISK)
+            },
+            RuleConfig {
+                action_type: Some(r"(?i)code|exec|shell|bash|python|terminal|command".to_string()),
+                ..rule("code_action", CODE_EXECUTION)
+            },
+            RuleConfig {
+                tool_calls: Some(
+                    r#"(?i)"(name|function)"\s*:\s*"[^"]*(exec|shell|bash|python|run_code|terminal)"#
+                        .to_string(),
+                ),
+                ..rule("code_tool", CODE_EXECUTION)
+            },
+            RuleConfig {
+                action_type: Some(r"(?i)http|api_call|fetch|request|webhook".to_string()),
+                ..rule("api_action", EXTERNAL_API_CALL)
+            },
+            RuleConfig {
+                tool_calls: Some(
+                    r#"(?i)"(name|function)"\s*:\s*"[^"]*(http|fetch|request|browse|search)"#
+                        .to_string(),
+                ),
+                ..rule("api_tool", EXTERNAL_API_CALL)
+            },
+        ];
+        Self::new(rules).expect("builtin classifier rules compile")
+    }
+
+    /// Rules from a JSON file holding an array of rules
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let rules: Vec<RuleConfig> = serde_json::from_slice(&std::fs::read(path)?)?;
+        info!(
+            "Loaded {} classifier rules from {}",
+            rules.len(),
+            path.display()
+        );
+        Self::new(rules)
+    }
+}
+
+#[async_trait]
+impl Classifier for RuleClassifier {
+    fn name(&self) -> &'static str {
+        "rules"
+    }
+
+    async fn classify(&self, event: &FactoEvent) -> anyhow::Result<Vec<Label>> {
+        let content = format!("{} {}", event.input_data, event.output_data);
+        let tool_calls = serde_json::to_string(&event.execution_meta.tool_calls)?;
+
+        let mut labels: Vec<Label> = Vec::new();
+        for rule in &self.rules {
+            if labels.iter().any(|l| l.category == rule.category) {
+                continue;
+            }
+            if rule.matches(event, &content, &tool_calls) {
+                labels.push(Label {
+                    category: rule.category.clone(),
+                    classifier: self.name().to_string(),
+                    rule: Some(rule.name.clone()),
+                    confidence: None,
+                });
+            }
+        }
+        Ok(labels)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelLabel {
+    category: String,
+    confidence: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelResponse {
+    labels: Vec<ModelLabel>,
+}
+
+/// Classifies events with an external model endpoint. The event is POSTed as
+/// JSON and the endpoint answers `{"labels": [{"category", "confidence"}]}`.
+pub struct HttpClassifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl HttpClassifier {
+    pub fn new(url: String, timeout: Duration) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("Failed to build HTTP client");
+        Self { client, url }
+    }
+}
+
+#[async_trait]
+impl Classifier for HttpClassifier {
+    fn name(&self) -> &'static str {
+        "model"
+    }
+
+    async fn classify(&self, event: &FactoEvent) -> anyhow::Result<Vec<Label>> {
+        let response: ModelResponse = self
+            .client
+            .post(&self.url)
+            .json(event)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(response
+            .labels
+            .into_iter()
+            .map(|l| Label {
+                category: l.category,
+                classifier: self.name().to_string(),
+                rule: None,
+                confidence: l.confidence,
+            })
+            .collect())
+    }
+}
+
+// ============================================================================
+// Classifier Sink
+// ============================================================================
+
+/// Fan-out sink running every classifier on accepted events. Events with at
+/// least one label are handed on for signing and publishing.
+pub struct ClassifierSink {
+    classifiers: Vec<Arc<dyn Classifier>>,
+    sender: mpsc::Sender<Classification>,
+}
+
+impl ClassifierSink {
+    pub fn new(
+        classifiers: Vec<Arc<dyn Classifier>>,
+        sender: mpsc::Sender<Classification>,
+    ) -> Self {
+        Self {
+            classifiers,
+            sender,
+        }
+    }
+}
+
+#[async_trait]
+impl FanoutSink for ClassifierSink {
+    fn name(&self) -> &'static str {
+        "classifier"
+    }
+
+    async fn deliver(&self, accepted: &AcceptedEvent) -> anyhow::Result<()> {
+        let (event, envelope) = accepted.as_ref();
+
+        let mut labels = Vec::new();
+        let mut failed = None;
+        for classifier in &self.classifiers {
+            match classifier.classify(event).await {
+                Ok(found) => labels.extend(found),
+                Err(e) => {
+                    warn!(
+                        "Classifier {} failed on {}: {}",
+                        classifier.name(),
+                        event.facto_id,
+                        e
+                    );
+                    counter!("facto_classifier_failed_total", "classifier" => classifier.name())
+                        .increment(1);
+                    failed = Some(e);
+                }
+            }
+        }
+        if labels.is_empty() {
+            return failed.map_or(Ok(()), Err);
+        }
+
+        for label in &labels {
+            counter!("facto_events_labelled_total", "category" => label.category.clone())
+                .increment(1);
+        }
+        let classification = Classification {
+            facto_id: event.facto_id.clone(),
+            agent_id: event.agent_id.clone(),
+            session_id: event.session_id.clone(),
+            tenant_id: envelope.tenant_id.clone(),
+            action_type: event.action_type.clone(),
+            completed_at: event.completed_at,
+            labels,
+            classified_at: now_nanos(),
+            signer_public_key: String::new(),
+            signature: String::new(),
+        };
+        self.sender
+            .send(classification)
+            .await
+            .map_err(|_| anyhow::anyhow!("classification publisher stopped"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_event;
+
+    fn categories(labels: &[Label]) -> Vec<&str> {
+        labels.iter().map(|l| l.category.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_builtin_rules() {
+        let classifier = RuleClassifier::builtin();
+
+        let mut event = test_event();
+        event.action_type = "shell_exec".to_string();
+        event.input_data = serde_json::json!({"to": "jane@example.com"});
+        let labels = classifier.classify(&event).await.unwrap();
+        assert_eq!(categories(&labels), vec![PII_RISK, CODE_EXECUTION]);
+        assert_eq!(labels[0].rule.as_deref(), Some("email_address"));
+
+        let mut event = test_event();
+        event.action_type = "llm_call".to_string();
+        event.execution_meta.tool_calls = vec![serde_json::json!({"name": "http_get"})];
+        let labels = classifier.classify(&event).await.unwrap();
+        assert_eq!(categories(&labels), vec![EXTERNAL_API_CALL]);
+    }
+
+    #[tokio::test]
+    async fn test_tag_rules() {
+        let classifier = RuleClassifier::new(vec![RuleConfig {
+            name: "prod".to_string(),
+            category: "production".to_string(),
+            action_type: None,
+            tag: Some("env".to_string()),
+            tag_value: Some("^prod".to_string()),
+            content: None,
+            tool_calls: None,
+        }])
+        .unwrap();
+
+        let mut event = test_event();
+        assert!(classifier.classify(&event).await.unwrap().is_empty());
+        event
+            .execution_meta
+            .tags
+            .insert("env".to_string(), "production".to_string());
+        assert_eq!(
+            categories(&classifier.classify(&event).await.unwrap()),
+            vec!["production"]
+        );
+    }
+
+    #[test]
+    fn test_rules_need_a_condition() {
+        let rule = RuleConfig {
+            name: "empty".to_string(),
+            category: PII_RISK.to_string(),
+            action_type: None,
+            tag: None,
+            tag_value: None,
+            content: None,
+            tool_calls: None,
+        };
+        assert!(RuleClassifier::new(vec![rule]).is_err());
+    }
+}
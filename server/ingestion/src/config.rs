@@ -0,0 +1,755 @@
+//! Layered configuration.
+//!
+//! Every setting is an environment variable. A TOML or YAML file named by
+//! `FACTO_CONFIG` can provide them too. The file is deserialized into
+//! [`File`], whose sections prefix the variables of their keys:
+//!
+//! ```toml
+//! rate_limit_per_agent = 100
+//!
+//! [nats]
+//! url = "nats://nats:4222"
+//!
+//! [kafka]
+//! brokers = ["kafka-1:9092", "kafka-2:9092"]
+//! ```
+//!
+//! sets `RATE_LIMIT_PER_AGENT`, `NATS_URL` and `KAFKA_BROKERS`; lists are
+//! joined with commas. Variables set in the environment override the file.
+//! Unknown keys and values of the wrong type are rejected at startup.
+//!
+//! On SIGHUP, or when the file changes, the file is read again and the
+//! [`RELOADABLE`] settings apply to the next request. Other settings that
+//! changed are reported and take effect after a restart.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::RwLock,
+    time::SystemTime,
+};
+
+/// Names the config file
+pub const CONFIG_VAR: &str = "FACTO_CONFIG";
+
+/// Settings applied without a restart. Redaction policies are re-read from
+/// `REDACTIONS_PATH` on every reload as well.
+pub const RELOADABLE: &[&str] = &["RATE_LIMIT_PER_AGENT", "AGENT_BYTE_QUOTA"];
+
+// ============================================================================
+// File
+// ============================================================================
+
+/// A config file. Each key sets the variable named by its section path and
+/// itself, so `[nats.tls] ca_path` sets `NATS_TLS_CA_PATH`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct File {
+    pub port: Option<u16>,
+    pub transport: Option<String>,
+    pub shared_state: Option<String>,
+    pub admin_tokens: Option<Vec<String>>,
+    pub api_keys: Option<Vec<String>>,
+    pub canonical_v1_until: Option<String>,
+    pub config_watch_secs: Option<u64>,
+    pub health_check_timeout_ms: Option<u64>,
+    pub shutdown_timeout_secs: Option<u64>,
+    pub outbox_enabled: Option<bool>,
+    pub cursors_enabled: Option<bool>,
+    pub rate_limit_per_agent: Option<u32>,
+    pub receipt_retention: Option<usize>,
+    pub registry_snapshot_signers: Option<Vec<String>>,
+    pub require_key_registration: Option<bool>,
+    pub key_registry_path: Option<String>,
+    pub key_rotation_overlap_secs: Option<u64>,
+    pub max_batch_events: Option<usize>,
+    pub max_body_bytes: Option<usize>,
+    pub max_event_bytes: Option<usize>,
+    pub max_tool_calls: Option<usize>,
+
+    pub anchors_path: Option<String>,
+    pub annotations_path: Option<String>,
+    pub models_path: Option<String>,
+    pub redactions_path: Option<String>,
+    pub retention_path: Option<String>,
+    pub schemas_path: Option<String>,
+    pub session_freezes_path: Option<String>,
+    pub tenants_path: Option<String>,
+
+    pub agent: Option<Agent>,
+    pub anchor: Option<Anchor>,
+    pub aws: Option<Aws>,
+    pub backfill: Option<Backfill>,
+    pub blob: Option<Blob>,
+    pub chain_head: Option<ChainHead>,
+    pub checkpoint: Option<Checkpoint>,
+    pub classifier: Option<Classifier>,
+    pub cursor: Option<Cursor>,
+    pub debug: Option<DebugBundles>,
+    pub dedup: Option<Dedup>,
+    pub did: Option<Did>,
+    pub export: Option<Export>,
+    pub facto: Option<Facto>,
+    pub jwt: Option<Jwt>,
+    pub kafka: Option<Kafka>,
+    pub metrics: Option<Metrics>,
+    pub metric_dimensions: Option<MetricDimensions>,
+    pub nats: Option<Nats>,
+    pub otel: Option<Otel>,
+    pub policy: Option<Policy>,
+    pub raw_ingest: Option<RawIngest>,
+    pub redaction: Option<Redaction>,
+    pub rejects: Option<Rejects>,
+    pub replay: Option<Replay>,
+    pub replica: Option<Replica>,
+    pub sandbox: Option<Sandbox>,
+    pub shadow: Option<Shadow>,
+    pub spool: Option<Spool>,
+    pub tls: Option<Tls>,
+    pub usage: Option<Usage>,
+    pub validation: Option<Validation>,
+    pub verification: Option<Verification>,
+    pub verify: Option<Verify>,
+    pub webhook: Option<Webhook>,
+    pub webhooks: Option<Webhooks>,
+}
+
+/// Rate, concurrency and queue of a sink
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sink {
+    pub rate: Option<f64>,
+    pub concurrency: Option<usize>,
+    pub queue: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Agent {
+    pub byte_quota: Option<u64>,
+    pub byte_quota_window_secs: Option<u64>,
+    pub controls_path: Option<String>,
+    pub inventory_path: Option<String>,
+    pub silence_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Anchor {
+    pub interval_secs: Option<u64>,
+    pub ots_calendars: Option<Vec<String>>,
+    pub tsa_url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Aws {
+    pub access_key_id: Option<String>,
+    pub region: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub session_token: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Backfill {
+    pub max_age_secs: Option<u64>,
+    pub max_events: Option<usize>,
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Blob {
+    pub offload_threshold_bytes: Option<usize>,
+    pub store_url: Option<String>,
+    pub s3: Option<BlobS3>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlobS3 {
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainHead {
+    pub coordination: Option<String>,
+    pub ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Checkpoint {
+    pub interval_secs: Option<u64>,
+    pub max_events: Option<usize>,
+    pub retention: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Classifier {
+    pub rules: Option<String>,
+    pub timeout_ms: Option<u64>,
+    pub url: Option<String>,
+    pub rate: Option<f64>,
+    pub concurrency: Option<usize>,
+    pub queue: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cursor {
+    pub retention_hours: Option<u64>,
+    pub feed: Option<Sink>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DebugBundles {
+    pub bundle_ttl_secs: Option<u64>,
+    pub max_bundles: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Dedup {
+    pub cache_size: Option<usize>,
+    pub ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Did {
+    pub cache_ttl_secs: Option<u64>,
+    pub web_hosts: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Export {
+    pub dir: Option<String>,
+    pub rate: Option<f64>,
+    pub concurrency: Option<usize>,
+    pub queue: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Facto {
+    pub instance_id: Option<String>,
+    pub server_signing_key: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Jwt {
+    pub algorithm: Option<String>,
+    pub audience: Option<String>,
+    pub issuer: Option<String>,
+    pub jwks_ttl_secs: Option<u64>,
+    pub jwks_url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Kafka {
+    pub acks: Option<String>,
+    pub brokers: Option<Vec<String>>,
+    pub client_id: Option<String>,
+    pub partitioning: Option<String>,
+    pub timeout_ms: Option<u64>,
+    pub topic: Option<String>,
+    pub publish: Option<Sink>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Metrics {
+    pub port: Option<u16>,
+    pub push: Option<MetricsPush>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsPush {
+    pub interval_secs: Option<u64>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricDimensions {
+    pub enabled: Option<bool>,
+    pub top_action_types: Option<usize>,
+    pub top_agents: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Nats {
+    pub url: Option<String>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub token: Option<String>,
+    pub creds_path: Option<String>,
+    pub nkey_seed: Option<String>,
+    pub nkey_seed_path: Option<String>,
+    pub propagate_headers: Option<Vec<String>>,
+    pub session_partitions: Option<usize>,
+    pub tls: Option<NatsTls>,
+    pub publish: Option<Sink>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct NatsTls {
+    pub ca_path: Option<String>,
+    pub cert_path: Option<String>,
+    pub key_path: Option<String>,
+    pub required: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Otel {
+    pub exporter_otlp_endpoint: Option<String>,
+    pub exporter_otlp_headers: Option<Vec<String>>,
+    pub exporter_otlp_protocol: Option<String>,
+    pub exporter_otlp_timeout: Option<u64>,
+    pub metric_export_interval: Option<u64>,
+    pub metrics_exporter: Option<String>,
+    pub resource_attributes: Option<Vec<String>>,
+    pub service_name: Option<String>,
+    pub traces_exporter: Option<String>,
+    pub traces_sampler: Option<String>,
+    pub traces_sampler_arg: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    pub rules: Option<String>,
+    pub alert: Option<PolicyAlert>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyAlert {
+    pub timeout_ms: Option<u64>,
+    pub webhook_url: Option<String>,
+    pub rate: Option<f64>,
+    pub concurrency: Option<usize>,
+    pub queue: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawIngest {
+    pub concurrency: Option<usize>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Redaction {
+    pub hash_key: Option<String>,
+    pub kek: Option<String>,
+    pub kek_id: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rejects {
+    pub enabled: Option<bool>,
+    pub max_bytes: Option<i64>,
+    pub retention_hours: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Replay {
+    pub max_skew_secs: Option<u64>,
+    pub window_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Replica {
+    pub count: Option<u64>,
+    pub index: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sandbox {
+    pub enabled: Option<bool>,
+    pub retention_days: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Shadow {
+    pub hasher: Option<String>,
+    pub sample_rate: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Spool {
+    pub dir: Option<String>,
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tls {
+    pub cert_path: Option<String>,
+    pub key_path: Option<String>,
+    pub client_auth: Option<String>,
+    pub client_ca_path: Option<String>,
+    pub reload_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Usage {
+    pub flush_secs: Option<u64>,
+    pub path: Option<String>,
+    pub retention_days: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Validation {
+    pub concurrency: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Verification {
+    pub cache_size: Option<usize>,
+    pub cache_ttl_secs: Option<u64>,
+    pub mode: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Verify {
+    pub chunk_size: Option<usize>,
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    pub allow_http: Option<bool>,
+    pub max_attempts: Option<u64>,
+    pub retry_base_ms: Option<u64>,
+    pub retry_max_ms: Option<u64>,
+    pub timeout_secs: Option<u64>,
+    pub rate: Option<f64>,
+    pub concurrency: Option<usize>,
+    pub queue: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webhooks {
+    pub enabled: Option<bool>,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("failed to read {0}: {1}")]
+    Read(String, std::io::Error),
+    #[error("{0}: {1}")]
+    Parse(String, String),
+    #[error("unsupported config file {0}, expected .toml, .yaml or .yml")]
+    Format(String),
+    #[error("invalid {0}: {1}")]
+    Invalid(&'static str, String),
+}
+
+impl File {
+    /// Read and deserialize a config file, by its extension
+    pub fn read(path: &Path) -> Result<Self, ConfigError> {
+        let display = path.display().to_string();
+        let text =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Read(display.clone(), e))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&text).map_err(|e| e.to_string()),
+            Some("yaml" | "yml") => serde_yaml::from_str(&text).map_err(|e| e.to_string()),
+            _ => return Err(ConfigError::Format(display)),
+        }
+        .map_err(|message| ConfigError::Parse(display, message))
+    }
+
+    /// The variables the file sets, with their values
+    pub fn settings(&self) -> BTreeMap<String, String> {
+        let mut settings = BTreeMap::new();
+        let value = serde_json::to_value(self).expect("config files serialize");
+        flatten(String::new(), value, &mut settings);
+        settings
+    }
+}
+
+/// Collect the variable of each leaf under `name`
+fn flatten(name: String, value: Value, settings: &mut BTreeMap<String, String>) {
+    let text = |value: Value| match value {
+        Value::String(s) => s,
+        other => other.to_string(),
+    };
+    match value {
+        Value::Null => {}
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                let key = match name.is_empty() {
+                    true => key.to_ascii_uppercase(),
+                    false => format!("{}_{}", name, key.to_ascii_uppercase()),
+                };
+                flatten(key, value, settings);
+            }
+        }
+        Value::Array(items) => {
+            let items = items.into_iter().map(text).collect::<Vec<_>>();
+            settings.insert(name, items.join(","));
+        }
+        value => {
+            settings.insert(name, text(value));
+        }
+    }
+}
+
+// ============================================================================
+// Layering and Reload
+// ============================================================================
+
+/// The settings that apply without a restart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reloadable {
+    /// Requests per second per agent; 0 means unlimited
+    pub rate_limit_per_agent: u32,
+    /// Event bytes per agent per window; 0 means unlimited
+    pub agent_byte_quota: u64,
+}
+
+impl Reloadable {
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        Ok(Self {
+            rate_limit_per_agent: lookup("RATE_LIMIT_PER_AGENT")
+                .unwrap_or_else(|| "10000".to_string())
+                .parse()
+                .map_err(|e| ConfigError::Invalid("RATE_LIMIT_PER_AGENT", format!("{}", e)))?,
+            agent_byte_quota: lookup("AGENT_BYTE_QUOTA")
+                .unwrap_or_else(|| "0".to_string())
+                .parse()
+                .map_err(|e| ConfigError::Invalid("AGENT_BYTE_QUOTA", format!("{}", e)))?,
+        })
+    }
+}
+
+/// What a reload changed
+#[derive(Debug)]
+pub struct Reload {
+    pub reloadable: Reloadable,
+    /// Settings that changed but only apply after a restart
+    pub needs_restart: Vec<String>,
+}
+
+/// The environment layered over the config file
+pub struct Config {
+    path: Option<PathBuf>,
+    /// Variables set in the environment at startup, which always win
+    environment: BTreeSet<String>,
+    file: RwLock<(BTreeMap<String, String>, Option<SystemTime>)>,
+}
+
+impl Config {
+    /// Read the file named by `FACTO_CONFIG`, if any, and export its settings
+    /// that the environment does not set.
+    ///
+    /// Call from `main` before the runtime or any other thread starts:
+    /// changing the environment is only sound while the process has a
+    /// single thread.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = std::env::var(CONFIG_VAR).ok().map(PathBuf::from);
+        let environment = std::env::vars_os()
+            .filter_map(|(name, _)| name.into_string().ok())
+            .collect::<BTreeSet<_>>();
+
+        let (settings, modified) = match &path {
+            Some(path) => (File::read(path)?.settings(), modified(path)),
+            None => (BTreeMap::new(), None),
+        };
+        for (name, value) in &settings {
+            if !environment.contains(name) {
+                std::env::set_var(name, value);
+            }
+        }
+
+        let config = Self {
+            path,
+            environment,
+            file: RwLock::new((settings, modified)),
+        };
+        config.reloadable()?;
+        Ok(config)
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The value of a setting: the environment's, else the file's
+    fn get(&self, name: &str) -> Option<String> {
+        match self.environment.contains(name) {
+            true => std::env::var(name).ok(),
+            false => self.file.read().unwrap().0.get(name).cloned(),
+        }
+    }
+
+    pub fn reloadable(&self) -> Result<Reloadable, ConfigError> {
+        Reloadable::from_lookup(|name| self.get(name))
+    }
+
+    /// Whether the file changed since it was last read
+    pub fn modified(&self) -> bool {
+        let Some(path) = &self.path else {
+            return false;
+        };
+        modified(path) != self.file.read().unwrap().1
+    }
+
+    /// Read the file again. Nothing changes if it is invalid.
+    pub fn reload(&self) -> Result<Reload, ConfigError> {
+        let (settings, modified) = match &self.path {
+            Some(path) => (File::read(path)?.settings(), modified(path)),
+            None => (BTreeMap::new(), None),
+        };
+        let reloadable = Reloadable::from_lookup(|name| match self.environment.contains(name) {
+            true => std::env::var(name).ok(),
+            false => settings.get(name).cloned(),
+        })?;
+
+        let mut file = self.file.write().unwrap();
+        let needs_restart = file
+            .0
+            .keys()
+            .chain(settings.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter(|name| {
+                !self.environment.contains(*name)
+                    && !RELOADABLE.contains(&name.as_str())
+                    && file.0.get(*name) != settings.get(*name)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        *file = (settings, modified);
+        Ok(Reload {
+            reloadable,
+            needs_restart,
+        })
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_sections_prefix_settings() {
+        let file: File = toml::from_str(
+            r#"
+# Limits
+rate_limit_per_agent = 1_000
+max_body_bytes = 10485760
+
+[nats]
+url = "nats://nats:4222" # in-cluster
+publish = { rate = 250.5, queue = 100 }
+
+[nats.tls]
+required = true
+
+[kafka]
+brokers = [
+    "kafka-1:9092",
+    "kafka-2:9092",
+]
+topic = 'facto#events'
+"#,
+        )
+        .unwrap();
+        let settings = file.settings();
+        assert_eq!(settings["RATE_LIMIT_PER_AGENT"], "1000");
+        assert_eq!(settings["MAX_BODY_BYTES"], "10485760");
+        assert_eq!(settings["NATS_URL"], "nats://nats:4222");
+        assert_eq!(settings["NATS_PUBLISH_RATE"], "250.5");
+        assert_eq!(settings["NATS_PUBLISH_QUEUE"], "100");
+        assert_eq!(settings["NATS_TLS_REQUIRED"], "true");
+        assert_eq!(settings["KAFKA_BROKERS"], "kafka-1:9092,kafka-2:9092");
+        assert_eq!(settings["KAFKA_TOPIC"], "facto#events");
+        assert_eq!(settings.len(), 8);
+    }
+
+    #[test]
+    fn test_yaml_nesting_and_sequences() {
+        let file: File = serde_yaml::from_str(
+            r#"
+rate_limit_per_agent: 50
+nats:
+  url: "nats://nats:4222"
+  propagate_headers: [traceparent, baggage]
+kafka:
+  brokers:
+    - kafka-1:9092
+    - kafka-2:9092
+  topic: facto.events
+port: 8081
+"#,
+        )
+        .unwrap();
+        let settings = file.settings();
+        assert_eq!(settings["RATE_LIMIT_PER_AGENT"], "50");
+        assert_eq!(settings["NATS_URL"], "nats://nats:4222");
+        assert_eq!(settings["NATS_PROPAGATE_HEADERS"], "traceparent,baggage");
+        assert_eq!(settings["KAFKA_BROKERS"], "kafka-1:9092,kafka-2:9092");
+        assert_eq!(settings["KAFKA_TOPIC"], "facto.events");
+        assert_eq!(settings["PORT"], "8081");
+    }
+
+    #[test]
+    fn test_rejects_unknown_keys_and_wrong_types() {
+        let dir = std::env::temp_dir().join(format!("facto-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, text: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, text).unwrap();
+            File::read(&path)
+        };
+
+        assert!(write("ok.toml", "[nats]\nurl = \"nats://nats:4222\"").is_ok());
+        let err = write("typo.toml", "[nats]\nusrl = \"nats://nats:4222\"").unwrap_err();
+        assert!(err.to_string().contains("usrl"), "{}", err);
+        let err = write("type.yaml", "port: eighty").unwrap_err();
+        assert!(matches!(err, ConfigError::Parse(..)), "{}", err);
+        assert!(write("negative.toml", "max_body_bytes = -1").is_err());
+        assert!(matches!(
+            write("settings.ini", "port = 1"),
+            Err(ConfigError::Format(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
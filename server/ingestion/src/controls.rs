@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, num::NonZeroU32, path::PathBuf, sync::RwLock};
+use tracing::info;
+
+use crate::scoped_id;
+use crate::store::JsonFile;
+use crate::verification::{now_nanos, EnforcementMode};
+
+/// Where an agent's effective limit or enforcement mode comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitSource {
+    /// `RATE_LIMIT_PER_AGENT`, `AGENT_BYTE_QUOTA` or `VERIFICATION_MODE`
+    Default,
+    /// The tenant's `agent_rate_limit_per_sec`, `agent_byte_quota` or
+    /// `enforcement_mode`
+    Tenant,
+    /// An override for the agent
+    Agent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentPause {
+    pub reason: String,
+    pub paused_by: String,
+    pub paused_at: i64,
+}
+
+/// Operator controls of one agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentControl {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub agent_id: String,
+    /// Overrides the tenant's and the default per-agent limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit_per_sec: Option<NonZeroU32>,
+    /// Bytes per quota window; overrides the tenant's and the default quota
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub byte_quota: Option<u64>,
+    /// Overrides the tenant's and the default enforcement mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enforcement_mode: Option<EnforcementMode>,
+    /// Event signatures covered by a verified batch envelope are not
+    /// checked individually
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub envelope_trusted: bool,
+    /// Set while ingestion for the agent is paused
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused: Option<AgentPause>,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimitOverride {
+    pub rate_limit_per_sec: NonZeroU32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ByteQuotaOverride {
+    pub byte_quota: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnforcementModeOverride {
+    pub enforcement_mode: EnforcementMode,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PauseRequest {
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ControlError {
+    #[error("no controls for agent: {0}")]
+    Unknown(String),
+    #[error("failed to persist agent controls: {0}")]
+    Persistence(String),
+}
+
+/// Per-agent rate limit, byte quota and enforcement mode overrides,
+/// envelope trust and pauses set through the admin API, persisted to `AGENT_CONTROLS_PATH` when set. Changes apply to
+/// the next request.
+pub struct AgentControls {
+    /// Keyed by tenant-scoped agent id
+    controls: RwLock<BTreeMap<String, AgentControl>>,
+    store: JsonFile,
+}
+
+impl AgentControls {
+    pub fn new(path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let store = JsonFile::new(path);
+        let controls: BTreeMap<String, AgentControl> = store.load()?;
+        info!("Loaded controls for {} agents", controls.len());
+        Ok(Self {
+            controls: RwLock::new(controls),
+            store,
+        })
+    }
+
+    pub fn list(&self) -> Vec<AgentControl> {
+        self.controls.read().unwrap().values().cloned().collect()
+    }
+
+    pub fn rate_override(&self, tenant_id: Option<&str>, agent_id: &str) -> Option<NonZeroU32> {
+        self.controls
+            .read()
+            .unwrap()
+            .get(&scoped_id(tenant_id, agent_id))
+            .and_then(|c| c.rate_limit_per_sec)
+    }
+
+    pub fn byte_quota_override(&self, tenant_id: Option<&str>, agent_id: &str) -> Option<u64> {
+        self.controls
+            .read()
+            .unwrap()
+            .get(&scoped_id(tenant_id, agent_id))
+            .and_then(|c| c.byte_quota)
+    }
+
+    pub fn enforcement_override(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+    ) -> Option<EnforcementMode> {
+        self.controls
+            .read()
+            .unwrap()
+            .get(&scoped_id(tenant_id, agent_id))
+            .and_then(|c| c.enforcement_mode)
+    }
+
+    pub fn envelope_trusted(&self, tenant_id: Option<&str>, agent_id: &str) -> bool {
+        self.controls
+            .read()
+            .unwrap()
+            .get(&scoped_id(tenant_id, agent_id))
+            .is_some_and(|c| c.envelope_trusted)
+    }
+
+    pub fn pause(&self, tenant_id: Option<&str>, agent_id: &str) -> Option<AgentPause> {
+        self.controls
+            .read()
+            .unwrap()
+            .get(&scoped_id(tenant_id, agent_id))
+            .and_then(|c| c.paused.clone())
+    }
+
+    pub fn set_rate_limit(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+        rate: Option<NonZeroU32>,
+    ) -> Result<AgentControl, ControlError> {
+        self.update(tenant_id, agent_id, |control| {
+            control.rate_limit_per_sec = rate
+        })
+    }
+
+    pub fn set_byte_quota(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+        quota: Option<u64>,
+    ) -> Result<AgentControl, ControlError> {
+        self.update(tenant_id, agent_id, |control| control.byte_quota = quota)
+    }
+
+    pub fn set_enforcement_mode(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+        mode: Option<EnforcementMode>,
+    ) -> Result<AgentControl, ControlError> {
+        self.update(tenant_id, agent_id, |control| {
+            control.enforcement_mode = mode
+        })
+    }
+
+    pub fn set_envelope_trusted(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+        trusted: bool,
+    ) -> Result<AgentControl, ControlError> {
+        self.update(tenant_id, agent_id, |control| {
+            control.envelope_trusted = trusted
+        })
+    }
+
+    pub fn set_paused(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+        pause: Option<AgentPause>,
+    ) -> Result<AgentControl, ControlError> {
+        self.update(tenant_id, agent_id, |control| control.paused = pause)
+    }
+
+    /// Apply `change` to an agent's controls, dropping them once nothing
+    /// is overridden
+    fn update(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+        change: impl FnOnce(&mut AgentControl),
+    ) -> Result<AgentControl, ControlError> {
+        let key = scoped_id(tenant_id, agent_id);
+        let mut controls = self.controls.write().unwrap();
+        let mut control = controls.get(&key).cloned().unwrap_or_else(|| AgentControl {
+            tenant_id: tenant_id.map(String::from),
+            agent_id: agent_id.to_string(),
+            rate_limit_per_sec: None,
+            byte_quota: None,
+            enforcement_mode: None,
+            envelope_trusted: false,
+            paused: None,
+            updated_at: 0,
+        });
+        change(&mut control);
+        control.updated_at = now_nanos();
+
+        let mut updated = controls.clone();
+        match (
+            control.rate_limit_per_sec,
+            control.byte_quota,
+            control.enforcement_mode,
+            control.envelope_trusted,
+            &control.paused,
+        ) {
+            (None, None, None, false, None) => {
+                if updated.remove(&key).is_none() {
+                    return Err(ControlError::Unknown(key));
+                }
+            }
+            _ => {
+                updated.insert(key, control.clone());
+            }
+        }
+        self.store
+            .save(&updated)
+            .map_err(|e| ControlError::Persistence(e.to_string()))?;
+        *controls = updated;
+        Ok(control)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_controls_are_scoped_and_cleared() {
+        let controls = AgentControls::new(None).unwrap();
+        let rate = NonZeroU32::new(3);
+
+        controls
+            .set_rate_limit(Some("acme"), "agent-1", rate)
+            .unwrap();
+        assert_eq!(controls.rate_override(Some("acme"), "agent-1"), rate);
+        assert_eq!(controls.rate_override(None, "agent-1"), None);
+
+        let pause = AgentPause {
+            reason: "runaway loop".to_string(),
+            paused_by: "ops".to_string(),
+            paused_at: 0,
+        };
+        controls
+            .set_paused(Some("acme"), "agent-1", Some(pause))
+            .unwrap();
+        controls
+            .set_rate_limit(Some("acme"), "agent-1", None)
+            .unwrap();
+        assert!(controls.pause(Some("acme"), "agent-1").is_some());
+
+        // Resuming the last override removes the agent's controls
+        controls.set_paused(Some("acme"), "agent-1", None).unwrap();
+        assert!(controls.list().is_empty());
+        assert!(matches!(
+            controls.set_paused(Some("acme"), "agent-1", None),
+            Err(ControlError::Unknown(_))
+        ));
+    }
+}
@@ -0,0 +1,671 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha3::{Digest, Sha3_256};
+
+use crate::protocol::{BatchEnvelope, ErrorCode, EventError};
+use crate::{jcs, FactoEvent, Proof};
+
+/// Signature algorithms accepted on event proofs
+pub const SIGNATURE_ALGORITHMS: &[&str] = &["ed25519", "es256", "secp256k1"];
+
+/// Hash algorithm of event hashes
+pub const HASH_ALGORITHM: &str = "sha3-256";
+
+/// Canonical form versions a proof may name
+pub const CANONICAL_VERSIONS: &[u32] = &[1, 2];
+
+/// Canonical form of proofs that name none
+const DEFAULT_CANONICAL_VERSION: u32 = 1;
+
+// ============================================================================
+// Verification Errors
+// ============================================================================
+
+/// Reasons an event's proof can fail verification.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum VerificationError {
+    #[error("Missing {0}")]
+    MissingField(&'static str),
+
+    #[error("Failed to serialize canonical form: {0}")]
+    Canonicalization(String),
+
+    #[error("Hash mismatch: computed={computed}, provided={provided}")]
+    HashMismatch { computed: String, provided: String },
+
+    #[error("Invalid public key: {0}")]
+    InvalidPublicKey(String),
+
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+
+    #[error("Signature verification failed: {0}")]
+    SignatureMismatch(String),
+
+    #[error("Public key is not registered for agent {0}")]
+    UnregisteredKey(String),
+
+    #[error("Public key has been revoked for agent {0}")]
+    RevokedKey(String),
+
+    #[error("Public key is not yet valid for agent {0}")]
+    KeyNotYetValid(String),
+
+    #[error("Public key has expired after rotation for agent {0}")]
+    ExpiredKey(String),
+
+    #[error("Invalid verification method: {0}")]
+    InvalidVerificationMethod(String),
+
+    #[error("Unsupported signature algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+
+    #[error("Canonical form version {0} is no longer accepted")]
+    RetiredCanonicalVersion(u32),
+}
+
+impl VerificationError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            VerificationError::MissingField(_) => ErrorCode::MissingField,
+            VerificationError::Canonicalization(_) => ErrorCode::InvalidEvent,
+            VerificationError::HashMismatch { .. } => ErrorCode::HashMismatch,
+            VerificationError::InvalidPublicKey(_) => ErrorCode::PublicKeyInvalid,
+            VerificationError::InvalidSignature(_) | VerificationError::SignatureMismatch(_) => {
+                ErrorCode::SignatureInvalid
+            }
+            VerificationError::UnregisteredKey(_) => ErrorCode::KeyNotRegistered,
+            VerificationError::RevokedKey(_) => ErrorCode::KeyRevoked,
+            VerificationError::KeyNotYetValid(_) => ErrorCode::KeyNotYetValid,
+            VerificationError::ExpiredKey(_) => ErrorCode::KeyExpired,
+            VerificationError::InvalidVerificationMethod(_) => ErrorCode::VerificationMethodInvalid,
+            VerificationError::UnsupportedAlgorithm(_) => ErrorCode::UnsupportedAlgorithm,
+            VerificationError::RetiredCanonicalVersion(_) => ErrorCode::CanonicalVersionRetired,
+        }
+    }
+}
+
+impl From<VerificationError> for EventError {
+    fn from(e: VerificationError) -> Self {
+        let details = match &e {
+            VerificationError::MissingField(field) => Some(serde_json::json!({"field": field})),
+            VerificationError::HashMismatch { computed, provided } => {
+                Some(serde_json::json!({"computed": computed, "provided": provided}))
+            }
+            VerificationError::UnregisteredKey(agent_id)
+            | VerificationError::RevokedKey(agent_id)
+            | VerificationError::KeyNotYetValid(agent_id)
+            | VerificationError::ExpiredKey(agent_id) => {
+                Some(serde_json::json!({"agent_id": agent_id}))
+            }
+            VerificationError::UnsupportedAlgorithm(algorithm) => {
+                Some(serde_json::json!({"algorithm": algorithm}))
+            }
+            VerificationError::RetiredCanonicalVersion(version) => {
+                Some(serde_json::json!({"canonical_version": version}))
+            }
+            _ => None,
+        };
+        EventError {
+            code: e.code(),
+            message: e.to_string(),
+            details,
+        }
+    }
+}
+
+// ============================================================================
+// Canonical Form and Hashing
+// ============================================================================
+
+/// Canonical form version named by an event's proof
+pub fn canonical_version(event: &FactoEvent) -> u32 {
+    event
+        .proof
+        .canonical_version
+        .unwrap_or(DEFAULT_CANONICAL_VERSION)
+}
+
+/// Build the canonical form of an event for hashing/signing
+/// The canonical form has sorted keys and no extra whitespace
+pub fn build_canonical_form(event: &FactoEvent) -> Result<String, VerificationError> {
+    match (event.event_version, canonical_version(event)) {
+        (1, 1) => build_canonical_form_v1(event),
+        (1, 2) => build_canonical_form_v2(event),
+        (1, c) => Err(VerificationError::Canonicalization(format!(
+            "unsupported canonical_version {}",
+            c
+        ))),
+        (v, _) => Err(VerificationError::Canonicalization(format!(
+            "unsupported event_version {}",
+            v
+        ))),
+    }
+}
+
+/// Canonical form version 1 of wire version 1, which does not cover
+/// `event_version` and leaves out several fields of `execution_meta`
+fn build_canonical_form_v1(event: &FactoEvent) -> Result<String, VerificationError> {
+    // Build a sorted map with the fields that should be included in the hash
+    let mut canonical = serde_json::Map::new();
+
+    canonical.insert(
+        "action_type".to_string(),
+        serde_json::json!(event.action_type),
+    );
+    canonical.insert("agent_id".to_string(), serde_json::json!(event.agent_id));
+    canonical.insert(
+        "completed_at".to_string(),
+        serde_json::json!(event.completed_at),
+    );
+
+    // Build execution_meta in sorted order
+    let mut exec_meta = serde_json::Map::new();
+    if let Some(ref model_id) = event.execution_meta.model_id {
+        exec_meta.insert("model_id".to_string(), serde_json::json!(model_id));
+    }
+    exec_meta.insert(
+        "sdk_version".to_string(),
+        serde_json::json!(event.execution_meta.sdk_version),
+    );
+    exec_meta.insert(
+        "seed".to_string(),
+        serde_json::json!(event.execution_meta.seed),
+    );
+    if let Some(temp) = event.execution_meta.temperature {
+        exec_meta.insert("temperature".to_string(), serde_json::json!(temp));
+    }
+    exec_meta.insert(
+        "tool_calls".to_string(),
+        serde_json::json!(event.execution_meta.tool_calls),
+    );
+    canonical.insert(
+        "execution_meta".to_string(),
+        serde_json::Value::Object(exec_meta),
+    );
+
+    canonical.insert("input_data".to_string(), event.input_data.clone());
+    canonical.insert("output_data".to_string(), event.output_data.clone());
+    canonical.insert(
+        "parent_facto_id".to_string(),
+        serde_json::json!(event.parent_facto_id),
+    );
+    canonical.insert(
+        "prev_hash".to_string(),
+        serde_json::json!(event.proof.prev_hash),
+    );
+    canonical.insert(
+        "session_id".to_string(),
+        serde_json::json!(event.session_id),
+    );
+    canonical.insert(
+        "started_at".to_string(),
+        serde_json::json!(event.started_at),
+    );
+    canonical.insert("status".to_string(), serde_json::json!(event.status));
+    canonical.insert("facto_id".to_string(), serde_json::json!(event.facto_id));
+
+    // Serialize to JSON with sorted keys (serde_json::Map maintains insertion order,
+    // and we inserted in sorted order)
+    serde_json::to_string(&serde_json::Value::Object(canonical))
+        .map_err(|e| VerificationError::Canonicalization(e.to_string()))
+}
+
+/// Canonical form version 2: the RFC 8785 (JCS) serialization of the whole
+/// event without `proof.signature` and `proof.event_hash`. Every field of the
+/// event model is present, null when unset, and `proof.algorithm` is always
+/// named so a proof cannot be relabelled; `proof.verification_method` is
+/// covered when set. The 64-bit integers `started_at`, `completed_at` and
+/// `execution_meta.seed` are written as strings, since JCS numbers are
+/// doubles and nanosecond timestamps do not fit in one.
+fn build_canonical_form_v2(event: &FactoEvent) -> Result<String, VerificationError> {
+    let algorithm = SignatureAlgorithm::of(&event.proof)?;
+    let mut value = serde_json::to_value(event)
+        .map_err(|e| VerificationError::Canonicalization(e.to_string()))?;
+
+    value["started_at"] = serde_json::json!(event.started_at.to_string());
+    value["completed_at"] = serde_json::json!(event.completed_at.to_string());
+    value["execution_meta"]["seed"] =
+        serde_json::json!(event.execution_meta.seed.map(|s| s.to_string()));
+
+    let proof = value
+        .get_mut("proof")
+        .and_then(serde_json::Value::as_object_mut)
+        .ok_or_else(|| VerificationError::Canonicalization("proof is not an object".into()))?;
+    proof.remove("signature");
+    proof.remove("event_hash");
+    proof.insert("algorithm".to_string(), serde_json::json!(algorithm.name()));
+
+    Ok(jcs::to_string(&value))
+}
+
+/// Compute SHA3-256 hash of the canonical form
+pub fn compute_event_hash(canonical: &str) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(canonical.as_bytes());
+    let result = hasher.finalize();
+    hex::encode(result)
+}
+
+// ============================================================================
+// Proof Verification
+// ============================================================================
+
+/// Check that all fields required for verification are present
+pub fn check_required_fields(event: &FactoEvent) -> Result<(), VerificationError> {
+    let required = [
+        ("facto_id", &event.facto_id),
+        ("agent_id", &event.agent_id),
+        ("session_id", &event.session_id),
+        ("action_type", &event.action_type),
+        ("status", &event.status),
+        ("event_hash", &event.proof.event_hash),
+        ("signature", &event.proof.signature),
+        ("public_key", &event.proof.public_key),
+    ];
+
+    for (name, value) in required {
+        if value.is_empty() {
+            return Err(VerificationError::MissingField(name));
+        }
+    }
+
+    Ok(())
+}
+
+/// Verify the event hash matches the hash of the canonical form.
+/// Returns the computed hash.
+pub fn verify_hash(event: &FactoEvent, canonical: &str) -> Result<String, VerificationError> {
+    let computed_hash = compute_event_hash(canonical);
+
+    if computed_hash != event.proof.event_hash {
+        return Err(VerificationError::HashMismatch {
+            computed: computed_hash,
+            provided: event.proof.event_hash.clone(),
+        });
+    }
+
+    Ok(computed_hash)
+}
+
+/// Decode a base64 Ed25519 public key
+pub fn decode_public_key(encoded: &str) -> Result<VerifyingKey, VerificationError> {
+    let public_key_bytes = BASE64
+        .decode(encoded)
+        .map_err(|e| VerificationError::InvalidPublicKey(format!("bad encoding: {}", e)))?;
+
+    let public_key_array: [u8; 32] = public_key_bytes.try_into().map_err(|b: Vec<u8>| {
+        VerificationError::InvalidPublicKey(format!("expected 32 bytes, got {}", b.len()))
+    })?;
+
+    VerifyingKey::from_bytes(&public_key_array)
+        .map_err(|e| VerificationError::InvalidPublicKey(e.to_string()))
+}
+
+/// Decode a base64 Ed25519 signature
+pub fn decode_signature(encoded: &str) -> Result<Signature, VerificationError> {
+    let signature_bytes = BASE64
+        .decode(encoded)
+        .map_err(|e| VerificationError::InvalidSignature(format!("bad encoding: {}", e)))?;
+
+    let signature_array: [u8; 64] = signature_bytes.try_into().map_err(|b: Vec<u8>| {
+        VerificationError::InvalidSignature(format!("expected 64 bytes, got {}", b.len()))
+    })?;
+
+    Ok(Signature::from_bytes(&signature_array))
+}
+
+// ============================================================================
+// Signature Algorithms
+// ============================================================================
+
+/// Algorithm an event proof is signed with.
+///
+/// ES256 (P-256) and secp256k1 proofs carry a SEC1 public key, compressed or
+/// not, and a 64-byte `r || s` signature over the SHA-256 digest of the
+/// canonical form. secp256k1 signatures must be normalized to low S.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SignatureAlgorithm {
+    #[default]
+    Ed25519,
+    Es256,
+    Secp256k1,
+}
+
+impl SignatureAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            SignatureAlgorithm::Ed25519 => "ed25519",
+            SignatureAlgorithm::Es256 => "es256",
+            SignatureAlgorithm::Secp256k1 => "secp256k1",
+        }
+    }
+
+    /// Algorithm named in a proof; proofs without one are Ed25519
+    pub fn of(proof: &Proof) -> Result<Self, VerificationError> {
+        Self::named(proof.algorithm.as_deref())
+    }
+
+    /// Algorithm of a name, Ed25519 when unnamed
+    pub fn named(name: Option<&str>) -> Result<Self, VerificationError> {
+        match name {
+            None | Some("ed25519") => Ok(SignatureAlgorithm::Ed25519),
+            Some("es256") => Ok(SignatureAlgorithm::Es256),
+            Some("secp256k1") => Ok(SignatureAlgorithm::Secp256k1),
+            Some(other) => Err(VerificationError::UnsupportedAlgorithm(other.to_string())),
+        }
+    }
+}
+
+/// A decoded event signing key
+#[derive(Debug, Clone)]
+pub enum PublicKey {
+    Ed25519(VerifyingKey),
+    Es256(p256::ecdsa::VerifyingKey),
+    Secp256k1(k256::ecdsa::VerifyingKey),
+}
+
+/// A decoded event signature
+#[derive(Debug, Clone)]
+pub enum ProofSignature {
+    Ed25519(Signature),
+    Es256(p256::ecdsa::Signature),
+    Secp256k1(k256::ecdsa::Signature),
+}
+
+impl PublicKey {
+    /// Decode a base64 public key of `algorithm`
+    pub fn decode(algorithm: SignatureAlgorithm, encoded: &str) -> Result<Self, VerificationError> {
+        if algorithm == SignatureAlgorithm::Ed25519 {
+            return decode_public_key(encoded).map(PublicKey::Ed25519);
+        }
+
+        let bytes = BASE64
+            .decode(encoded)
+            .map_err(|e| VerificationError::InvalidPublicKey(format!("bad encoding: {}", e)))?;
+        let invalid = |e: p256::ecdsa::Error| VerificationError::InvalidPublicKey(e.to_string());
+        match algorithm {
+            SignatureAlgorithm::Es256 => p256::ecdsa::VerifyingKey::from_sec1_bytes(&bytes)
+                .map(PublicKey::Es256)
+                .map_err(invalid),
+            _ => k256::ecdsa::VerifyingKey::from_sec1_bytes(&bytes)
+                .map(PublicKey::Secp256k1)
+                .map_err(invalid),
+        }
+    }
+
+    /// Decode a base64 public key of any supported algorithm. 32-byte keys
+    /// are Ed25519; SEC1 keys are tried as P-256, then secp256k1.
+    pub fn decode_any(encoded: &str) -> Result<Self, VerificationError> {
+        let ed25519 = Self::decode(SignatureAlgorithm::Ed25519, encoded);
+        if ed25519.is_ok() || BASE64.decode(encoded).is_ok_and(|b| b.len() == 32) {
+            return ed25519;
+        }
+        Self::decode(SignatureAlgorithm::Es256, encoded)
+            .or_else(|_| Self::decode(SignatureAlgorithm::Secp256k1, encoded))
+    }
+
+    /// Check `signature` over `message`. Ed25519 uses strict verification.
+    pub fn verify(
+        &self,
+        message: &[u8],
+        signature: &ProofSignature,
+    ) -> Result<(), VerificationError> {
+        use p256::ecdsa::signature::Verifier as _;
+
+        let mismatch =
+            |e: ed25519_dalek::SignatureError| VerificationError::SignatureMismatch(e.to_string());
+        match (self, signature) {
+            (PublicKey::Ed25519(key), ProofSignature::Ed25519(signature)) => {
+                key.verify_strict(message, signature).map_err(mismatch)
+            }
+            (PublicKey::Es256(key), ProofSignature::Es256(signature)) => {
+                key.verify(message, signature).map_err(mismatch)
+            }
+            (PublicKey::Secp256k1(key), ProofSignature::Secp256k1(signature)) => {
+                key.verify(message, signature).map_err(mismatch)
+            }
+            _ => Err(VerificationError::InvalidSignature(
+                "signature and public key algorithms differ".to_string(),
+            )),
+        }
+    }
+}
+
+impl ProofSignature {
+    /// Decode a base64 signature of `algorithm`
+    pub fn decode(algorithm: SignatureAlgorithm, encoded: &str) -> Result<Self, VerificationError> {
+        if algorithm == SignatureAlgorithm::Ed25519 {
+            return decode_signature(encoded).map(ProofSignature::Ed25519);
+        }
+
+        let bytes = BASE64
+            .decode(encoded)
+            .map_err(|e| VerificationError::InvalidSignature(format!("bad encoding: {}", e)))?;
+        let invalid = |e: p256::ecdsa::Error| VerificationError::InvalidSignature(e.to_string());
+        match algorithm {
+            SignatureAlgorithm::Es256 => p256::ecdsa::Signature::from_slice(&bytes)
+                .map(ProofSignature::Es256)
+                .map_err(invalid),
+            _ => k256::ecdsa::Signature::from_slice(&bytes)
+                .map(ProofSignature::Secp256k1)
+                .map_err(invalid),
+        }
+    }
+}
+
+/// Verify an event's signature over its canonical form with the algorithm
+/// its proof names
+pub fn verify_signature(event: &FactoEvent, canonical: &str) -> Result<(), VerificationError> {
+    let algorithm = SignatureAlgorithm::of(&event.proof)?;
+    let public_key = PublicKey::decode(algorithm, &event.proof.public_key)?;
+    let signature = ProofSignature::decode(algorithm, &event.proof.signature)?;
+    public_key.verify(canonical.as_bytes(), &signature)
+}
+
+/// Whether the R component of a signature is a small-order point, which
+/// `verify_strict` rejects but batch verification does not
+pub fn has_small_order_r(signature: &Signature) -> bool {
+    match CompressedEdwardsY(*signature.r_bytes()).decompress() {
+        Some(r) => r.is_small_order(),
+        None => true,
+    }
+}
+
+// ============================================================================
+// Batch Envelopes
+// ============================================================================
+
+/// Hash a batch envelope covers: SHA3-256 of the events' claimed event
+/// hashes concatenated in array order
+pub fn compute_batch_hash(events: &[FactoEvent]) -> String {
+    hash_event_hashes(events.iter().map(|event| event.proof.event_hash.as_str()))
+}
+
+fn hash_event_hashes<'a>(hashes: impl IntoIterator<Item = &'a str>) -> String {
+    let mut hasher = Sha3_256::new();
+    for hash in hashes {
+        hasher.update(hash.as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Verify a batch envelope: its hash covers the events' claimed hashes and
+/// its signature covers its hash. Each event's own hash still has to be
+/// checked against its canonical form.
+pub fn verify_batch_envelope(
+    envelope: &BatchEnvelope,
+    events: &[FactoEvent],
+) -> Result<(), VerificationError> {
+    check_batch_hash(envelope, compute_batch_hash(events))?;
+    verify_envelope_signature(envelope)
+}
+
+/// Verify the trust a published batch envelope lends an event in place of
+/// its signature: the event's hash is one of `event_hashes`, they hash to
+/// the envelope's batch hash, and the envelope is signed with the event's
+/// key. The event's hash still has to be checked against its canonical form.
+pub fn verify_envelope_membership(
+    envelope: &BatchEnvelope,
+    event_hashes: &[String],
+    event_hash: &str,
+    public_key: &str,
+) -> Result<(), VerificationError> {
+    if !event_hashes.iter().any(|hash| hash == event_hash) {
+        return Err(VerificationError::SignatureMismatch(
+            "event is not covered by the batch envelope".to_string(),
+        ));
+    }
+    if envelope.public_key != public_key {
+        return Err(VerificationError::SignatureMismatch(
+            "batch envelope is signed with a different key".to_string(),
+        ));
+    }
+    check_batch_hash(
+        envelope,
+        hash_event_hashes(event_hashes.iter().map(String::as_str)),
+    )?;
+    verify_envelope_signature(envelope)
+}
+
+fn check_batch_hash(envelope: &BatchEnvelope, computed: String) -> Result<(), VerificationError> {
+    if computed != envelope.batch_hash {
+        return Err(VerificationError::HashMismatch {
+            computed,
+            provided: envelope.batch_hash.clone(),
+        });
+    }
+    Ok(())
+}
+
+fn verify_envelope_signature(envelope: &BatchEnvelope) -> Result<(), VerificationError> {
+    let algorithm = SignatureAlgorithm::named(envelope.algorithm.as_deref())?;
+    let public_key = PublicKey::decode(algorithm, &envelope.public_key)?;
+    let signature = ProofSignature::decode(algorithm, &envelope.signature)?;
+    public_key.verify(envelope.batch_hash.as_bytes(), &signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{sign_test_event, test_event};
+    use ed25519_dalek::SigningKey;
+
+    #[test]
+    fn test_canonical_form() {
+        let event = test_event();
+
+        let canonical = build_canonical_form(&event).unwrap();
+        assert!(canonical.contains("action_type"));
+        assert!(canonical.contains("agent_id"));
+    }
+
+    #[test]
+    fn test_compute_hash() {
+        let data = r#"{"test":"data"}"#;
+        let hash = compute_event_hash(data);
+        assert_eq!(hash.len(), 64); // SHA3-256 produces 32 bytes = 64 hex chars
+    }
+
+    #[test]
+    fn test_missing_field_is_typed() {
+        let event = test_event();
+        assert_eq!(
+            check_required_fields(&event),
+            Err(VerificationError::MissingField("event_hash"))
+        );
+    }
+
+    #[test]
+    fn test_signed_event_verifies() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let event = sign_test_event(test_event(), &key);
+        let canonical = build_canonical_form(&event).unwrap();
+
+        assert!(verify_hash(&event, &canonical).is_ok());
+        let key = decode_public_key(&event.proof.public_key).unwrap();
+        let signature = decode_signature(&event.proof.signature).unwrap();
+        assert!(key.verify_strict(canonical.as_bytes(), &signature).is_ok());
+
+        let mut tampered = event.clone();
+        tampered.output_data = serde_json::json!({"response": "tampered"});
+        let canonical = build_canonical_form(&tampered).unwrap();
+        assert!(matches!(
+            verify_hash(&tampered, &canonical),
+            Err(VerificationError::HashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_canonical_form_v2() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut event = test_event();
+        event.proof.canonical_version = Some(2);
+        let event = sign_test_event(event, &key);
+        let canonical = build_canonical_form(&event).unwrap();
+        assert!(canonical.starts_with(
+            r#"{"action_type":"llm_call","agent_id":"agent-test","completed_at":"1000000001""#
+        ));
+        assert!(canonical
+            .contains(r#""proof":{"algorithm":"ed25519","canonical_version":2,"prev_hash":"#));
+        assert!(verify_hash(&event, &canonical).is_ok());
+        assert!(verify_signature(&event, &canonical).is_ok());
+
+        // Fields version 1 leaves out are covered
+        let mut tampered = event.clone();
+        tampered.execution_meta.max_tokens = Some(1);
+        tampered
+            .execution_meta
+            .tags
+            .insert("env".to_string(), "prod".to_string());
+        let canonical = build_canonical_form(&tampered).unwrap();
+        assert!(verify_hash(&tampered, &canonical).is_err());
+
+        let mut v1 = event.clone();
+        v1.proof.canonical_version = None;
+        assert_ne!(build_canonical_form(&v1).unwrap(), canonical);
+
+        let mut unknown = event;
+        unknown.proof.canonical_version = Some(9);
+        assert!(build_canonical_form(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_ecdsa_algorithms_verify() {
+        use p256::ecdsa::signature::Signer as _;
+
+        let mut es256 = test_event();
+        es256.proof.algorithm = Some("es256".to_string());
+        let key = p256::ecdsa::SigningKey::from_slice(&[7u8; 32]).unwrap();
+        es256.proof.public_key =
+            BASE64.encode(key.verifying_key().to_encoded_point(true).as_bytes());
+        let canonical = build_canonical_form(&es256).unwrap();
+        let signature: p256::ecdsa::Signature = key.sign(canonical.as_bytes());
+        es256.proof.signature = BASE64.encode(signature.to_bytes());
+        assert!(verify_signature(&es256, &canonical).is_ok());
+
+        let mut secp256k1 = test_event();
+        secp256k1.proof.algorithm = Some("secp256k1".to_string());
+        let key = k256::ecdsa::SigningKey::from_slice(&[7u8; 32]).unwrap();
+        secp256k1.proof.public_key =
+            BASE64.encode(key.verifying_key().to_encoded_point(false).as_bytes());
+        let canonical = build_canonical_form(&secp256k1).unwrap();
+        let signature: k256::ecdsa::Signature = key.sign(canonical.as_bytes());
+        secp256k1.proof.signature = BASE64.encode(signature.to_bytes());
+        assert!(verify_signature(&secp256k1, &canonical).is_ok());
+
+        // A proof naming the wrong algorithm does not verify
+        let mut mislabelled = secp256k1.clone();
+        mislabelled.proof.algorithm = Some("es256".to_string());
+        assert!(verify_signature(&mislabelled, &canonical).is_err());
+        assert!(PublicKey::decode_any(&secp256k1.proof.public_key).is_ok());
+
+        let mut unknown = es256;
+        unknown.proof.algorithm = Some("rsa".to_string());
+        assert_eq!(
+            verify_signature(&unknown, &canonical),
+            Err(VerificationError::UnsupportedAlgorithm("rsa".to_string()))
+        );
+    }
+}
@@ -0,0 +1,614 @@
+use async_nats::jetstream::{
+    self,
+    consumer::{pull, AckPolicy, DeliverPolicy, PullConsumer},
+    message::Acker,
+};
+use axum::{
+    async_trait,
+    extract::{Json, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc, sync::RwLock, time::Duration};
+use tracing::info;
+
+use crate::admin::error_response;
+use crate::auth::{Principal, Scope};
+use crate::headers::nats_headers;
+use crate::sinks::{AcceptedEvent, FanoutSink};
+use crate::tenants::{validate_tenant_id, DEFAULT_TENANT};
+use crate::verification::{ServerEnvelope, ENVELOPE_HEADER};
+use crate::{AppState, FactoEvent};
+
+/// Stream holding a copy of every accepted event for cursors. The primary
+/// event stream is a work queue, so it cannot serve additional consumers.
+pub const FEED_STREAM: &str = "FACTO_FEED";
+
+const FEED_SUBJECT_PREFIX: &str = "facto.feed";
+
+/// Events returned by one fetch when the client does not ask for fewer
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Largest page a client may ask for
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Longest a fetch may wait for new events
+pub const MAX_FETCH_WAIT: Duration = Duration::from_secs(30);
+
+/// Fetched but uncommitted events per cursor; fetches return nothing more
+/// until older events are committed or redelivered
+pub const MAX_UNCOMMITTED: i64 = 10_000;
+
+/// Subject an accepted event is copied to in the feed stream
+pub fn feed_subject(tenant_id: Option<&str>, agent_id: &str) -> String {
+    format!(
+        "{}.{}.{}",
+        FEED_SUBJECT_PREFIX,
+        tenant_id.unwrap_or(DEFAULT_TENANT),
+        agent_id
+    )
+}
+
+/// Cursor names are a subject token and part of the consumer name:
+/// lowercase alphanumerics and `-`
+fn validate_name(name: &str) -> Result<(), CursorError> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(CursorError::InvalidName(name.to_string()))
+    }
+}
+
+/// Durable consumer backing a cursor. Cursors live in the namespace of the
+/// caller's tenant; cursor names hold no `_`, so names never collide.
+fn durable_name(tenant_id: Option<&str>, name: &str) -> String {
+    format!("cursor_{}_{}", tenant_id.unwrap_or(DEFAULT_TENANT), name)
+}
+
+// ============================================================================
+// Requests and Responses
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CursorStart {
+    /// Every event still retained in the feed
+    #[default]
+    All,
+    /// Only events accepted after the cursor is created
+    New,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCursorRequest {
+    pub name: String,
+    /// Only events of this tenant, for operators not bound to one
+    pub tenant_id: Option<String>,
+    /// Only events of this agent
+    pub agent_id: Option<String>,
+    #[serde(default)]
+    pub start: CursorStart,
+    /// Fetched events that are not committed within this time are returned
+    /// again by a later fetch
+    #[serde(default = "default_ack_wait_secs")]
+    pub ack_wait_secs: u64,
+}
+
+fn default_ack_wait_secs() -> u64 {
+    300
+}
+
+#[derive(Debug, Serialize)]
+pub struct CursorInfo {
+    pub name: String,
+    pub filter_subject: String,
+    /// Events not yet fetched
+    pub pending: u64,
+    /// Events fetched but not yet committed
+    pub uncommitted: usize,
+    /// Feed sequence up to which all events are committed
+    pub committed_sequence: u64,
+    pub ack_wait_secs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FetchQuery {
+    /// Events to return, defaults to [`DEFAULT_PAGE_SIZE`]
+    pub limit: Option<usize>,
+    /// Wait up to this long for events when none are pending
+    pub wait_ms: Option<u64>,
+}
+
+/// An accepted event with its position in the feed
+#[derive(Debug, Serialize)]
+pub struct CursorEvent {
+    pub sequence: u64,
+    pub event: FactoEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub envelope: Option<ServerEnvelope>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CursorPage {
+    pub events: Vec<CursorEvent>,
+    /// Sequence to commit once the page is processed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_sequence: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommitRequest {
+    /// Commits this event and every event fetched before it
+    pub sequence: u64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CursorError {
+    #[error("invalid cursor name: {0}")]
+    InvalidName(String),
+    #[error("invalid cursor filter: {0}")]
+    InvalidFilter(String),
+    #[error("cursor already exists: {0}")]
+    Exists(String),
+    #[error("unknown cursor: {0}")]
+    Unknown(String),
+    #[error("sequence {0} was not fetched through this server or was already committed")]
+    UnknownSequence(u64),
+    #[error("NATS is not connected")]
+    Unavailable,
+    #[error("NATS error: {0}")]
+    Nats(String),
+}
+
+impl IntoResponse for CursorError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            CursorError::InvalidName(_)
+            | CursorError::InvalidFilter(_)
+            | CursorError::UnknownSequence(_) => StatusCode::BAD_REQUEST,
+            CursorError::Exists(_) => StatusCode::CONFLICT,
+            CursorError::Unknown(_) => StatusCode::NOT_FOUND,
+            CursorError::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            CursorError::Nats(_) => StatusCode::BAD_GATEWAY,
+        };
+        error_response(status, self)
+    }
+}
+
+fn nats_error(e: impl ToString) -> CursorError {
+    CursorError::Nats(e.to_string())
+}
+
+// ============================================================================
+// Cursors
+// ============================================================================
+
+/// Named cursors over the feed stream for consumers without NATS access.
+///
+/// Each cursor is a durable pull consumer acknowledging cumulatively:
+/// committing a sequence commits everything fetched before it. Fetched
+/// events stay uncommitted until then and are fetched again after the
+/// cursor's ack wait, so delivery is at-least-once. Commits must reach the
+/// server that served the fetch; elsewhere they fail and the events are
+/// redelivered.
+pub struct Cursors {
+    retention: Duration,
+    jetstream: RwLock<Option<jetstream::Context>>,
+    /// Acknowledgement handles of fetched events by consumer and sequence
+    uncommitted: DashMap<String, BTreeMap<u64, Acker>>,
+}
+
+impl Cursors {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            retention,
+            jetstream: RwLock::new(None),
+            uncommitted: DashMap::new(),
+        }
+    }
+
+    pub fn stream_config(&self) -> jetstream::stream::Config {
+        jetstream::stream::Config {
+            name: FEED_STREAM.to_string(),
+            subjects: vec![format!("{}.>", FEED_SUBJECT_PREFIX)],
+            storage: jetstream::stream::StorageType::File,
+            max_age: self.retention,
+            ..Default::default()
+        }
+    }
+
+    /// Use a newly connected JetStream context
+    pub fn connect(&self, jetstream: jetstream::Context) {
+        *self.jetstream.write().unwrap() = Some(jetstream);
+    }
+
+    fn context(&self) -> Result<jetstream::Context, CursorError> {
+        self.jetstream
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or(CursorError::Unavailable)
+    }
+
+    async fn stream(&self) -> Result<jetstream::stream::Stream, CursorError> {
+        self.context()?
+            .get_stream(FEED_STREAM)
+            .await
+            .map_err(nats_error)
+    }
+
+    async fn consumer(&self, durable: &str) -> Result<PullConsumer, CursorError> {
+        let stream = self.stream().await?;
+        if stream.consumer_info(durable).await.is_err() {
+            return Err(CursorError::Unknown(durable.to_string()));
+        }
+        stream.get_consumer(durable).await.map_err(nats_error)
+    }
+
+    pub async fn create(
+        &self,
+        tenant_id: Option<&str>,
+        request: CreateCursorRequest,
+    ) -> Result<CursorInfo, CursorError> {
+        validate_name(&request.name)?;
+        let tenant = match (tenant_id, request.tenant_id.as_deref()) {
+            (Some(own), Some(asked)) if own != asked => {
+                return Err(CursorError::InvalidFilter(format!(
+                    "tenant {} is not the caller's tenant",
+                    asked
+                )))
+            }
+            (Some(own), _) => Some(own),
+            (None, Some(asked)) => {
+                validate_tenant_id(asked).map_err(|e| CursorError::InvalidFilter(e.to_string()))?;
+                Some(asked)
+            }
+            (None, None) => None,
+        };
+        let agent = match request.agent_id.as_deref() {
+            Some(agent_id) if agent_id.is_empty() || agent_id.contains(['.', '*', '>', ' ']) => {
+                return Err(CursorError::InvalidFilter(format!(
+                    "invalid agent_id: {}",
+                    agent_id
+                )))
+            }
+            Some(agent_id) => agent_id,
+            None => ">",
+        };
+        let filter_subject = format!(
+            "{}.{}.{}",
+            FEED_SUBJECT_PREFIX,
+            tenant.unwrap_or("*"),
+            agent
+        );
+
+        let durable = durable_name(tenant_id, &request.name);
+        let stream = self.stream().await?;
+        if stream.consumer_info(&durable).await.is_ok() {
+            return Err(CursorError::Exists(request.name));
+        }
+        let consumer: PullConsumer = stream
+            .create_consumer(pull::Config {
+                durable_name: Some(durable.clone()),
+                filter_subject,
+                deliver_policy: match request.start {
+                    CursorStart::All => DeliverPolicy::All,
+                    CursorStart::New => DeliverPolicy::New,
+                },
+                ack_policy: AckPolicy::All,
+                ack_wait: Duration::from_secs(request.ack_wait_secs.max(1)),
+                max_ack_pending: MAX_UNCOMMITTED,
+                ..Default::default()
+            })
+            .await
+            .map_err(nats_error)?;
+        info!("Created cursor {}", durable);
+        Ok(cursor_info(&request.name, consumer.cached_info()))
+    }
+
+    pub async fn info(
+        &self,
+        tenant_id: Option<&str>,
+        name: &str,
+    ) -> Result<CursorInfo, CursorError> {
+        validate_name(name)?;
+        let consumer = self.consumer(&durable_name(tenant_id, name)).await?;
+        Ok(cursor_info(name, consumer.cached_info()))
+    }
+
+    pub async fn delete(&self, tenant_id: Option<&str>, name: &str) -> Result<(), CursorError> {
+        validate_name(name)?;
+        let durable = durable_name(tenant_id, name);
+        self.consumer(&durable).await?;
+        self.stream()
+            .await?
+            .delete_consumer(&durable)
+            .await
+            .map_err(nats_error)?;
+        self.uncommitted.remove(&durable);
+        info!("Deleted cursor {}", durable);
+        Ok(())
+    }
+
+    /// The next page of events after the cursor's uncommitted ones
+    pub async fn fetch(
+        &self,
+        tenant_id: Option<&str>,
+        name: &str,
+        limit: usize,
+        wait: Duration,
+    ) -> Result<CursorPage, CursorError> {
+        validate_name(name)?;
+        let durable = durable_name(tenant_id, name);
+        let consumer = self.consumer(&durable).await?;
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        let mut messages = match wait.is_zero() {
+            true => consumer.fetch().max_messages(limit).messages().await,
+            false => {
+                consumer
+                    .batch()
+                    .max_messages(limit)
+                    .expires(wait.min(MAX_FETCH_WAIT))
+                    .messages()
+                    .await
+            }
+        }
+        .map_err(nats_error)?;
+
+        let mut events = Vec::new();
+        let mut ackers = Vec::new();
+        while let Some(message) = messages.next().await {
+            let message = message.map_err(nats_error)?;
+            let sequence = message.info().map_err(nats_error)?.stream_sequence;
+            let (message, acker) = message.split();
+            ackers.push((sequence, acker));
+
+            // Events the feed cannot decode are skipped once the page is committed
+            let Ok(event) = serde_json::from_slice::<FactoEvent>(&message.payload) else {
+                continue;
+            };
+            let envelope = message
+                .headers
+                .as_ref()
+                .and_then(|h| h.get(ENVELOPE_HEADER))
+                .and_then(|v| serde_json::from_str(v.as_str()).ok());
+            events.push(CursorEvent {
+                sequence,
+                event,
+                envelope,
+            });
+        }
+
+        let commit_sequence = ackers.iter().map(|(sequence, _)| *sequence).max();
+        self.uncommitted.entry(durable).or_default().extend(ackers);
+        Ok(CursorPage {
+            events,
+            commit_sequence,
+        })
+    }
+
+    /// Commit `sequence` and every event fetched before it
+    pub async fn commit(
+        &self,
+        tenant_id: Option<&str>,
+        name: &str,
+        sequence: u64,
+    ) -> Result<CursorInfo, CursorError> {
+        validate_name(name)?;
+        let durable = durable_name(tenant_id, name);
+        let acker = {
+            let mut uncommitted = self
+                .uncommitted
+                .get_mut(&durable)
+                .ok_or(CursorError::UnknownSequence(sequence))?;
+            let acker = uncommitted
+                .remove(&sequence)
+                .ok_or(CursorError::UnknownSequence(sequence))?;
+            *uncommitted = uncommitted.split_off(&sequence);
+            acker
+        };
+        acker.double_ack().await.map_err(nats_error)?;
+        self.info(tenant_id, name).await
+    }
+}
+
+fn cursor_info(name: &str, info: &jetstream::consumer::Info) -> CursorInfo {
+    CursorInfo {
+        name: name.to_string(),
+        filter_subject: info.config.filter_subject.clone(),
+        pending: info.num_pending,
+        uncommitted: info.num_ack_pending,
+        committed_sequence: info.ack_floor.stream_sequence,
+        ack_wait_secs: info.config.ack_wait.as_secs(),
+    }
+}
+
+// ============================================================================
+// Feed Sink
+// ============================================================================
+
+/// Copies accepted events to the feed stream, waiting for JetStream to
+/// store each one. Run with the outbox so failed copies are retried.
+pub struct FeedSink {
+    cursors: Arc<Cursors>,
+}
+
+impl FeedSink {
+    pub fn new(cursors: Arc<Cursors>) -> Self {
+        Self { cursors }
+    }
+}
+
+#[async_trait]
+impl FanoutSink for FeedSink {
+    fn name(&self) -> &'static str {
+        "cursor_feed"
+    }
+
+    async fn deliver(&self, accepted: &AcceptedEvent) -> anyhow::Result<()> {
+        let (event, envelope) = accepted.as_ref();
+        let jetstream = self.cursors.context()?;
+
+        let headers = nats_headers(event, envelope)?;
+        jetstream
+            .publish_with_headers(
+                feed_subject(envelope.tenant_id.as_deref(), &event.agent_id),
+                headers,
+                event.to_json(),
+            )
+            .await?
+            .await?;
+        Ok(())
+    }
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// Cursors read every accepted event of their scope, so they need the
+/// admin scope; tenant-bound operators only reach their own tenant's cursors
+async fn authorize(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<(Arc<Cursors>, Principal), Response> {
+    let Some(ref cursors) = state.cursors else {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            "Cursors are not enabled",
+        ));
+    };
+    if !state.auth.admin_enabled() {
+        return Err(error_response(
+            StatusCode::FORBIDDEN,
+            "Admin API is disabled",
+        ));
+    }
+    let principal = state
+        .auth
+        .authorize(headers, Scope::Admin)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    Ok((cursors.clone(), principal))
+}
+
+pub async fn create_cursor_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<CreateCursorRequest>,
+) -> Response {
+    let (cursors, principal) = match authorize(&state, &headers).await {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    match cursors
+        .create(principal.tenant_id.as_deref(), request)
+        .await
+    {
+        Ok(cursor) => (StatusCode::CREATED, Json(cursor)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn get_cursor_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Response {
+    let (cursors, principal) = match authorize(&state, &headers).await {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    match cursors.info(principal.tenant_id.as_deref(), &name).await {
+        Ok(cursor) => (StatusCode::OK, Json(cursor)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn delete_cursor_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Response {
+    let (cursors, principal) = match authorize(&state, &headers).await {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    match cursors.delete(principal.tenant_id.as_deref(), &name).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn fetch_cursor_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Query(query): Query<FetchQuery>,
+) -> Response {
+    let (cursors, principal) = match authorize(&state, &headers).await {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let wait = Duration::from_millis(query.wait_ms.unwrap_or(0));
+    match cursors
+        .fetch(principal.tenant_id.as_deref(), &name, limit, wait)
+        .await
+    {
+        Ok(page) => (StatusCode::OK, Json(page)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn commit_cursor_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Json(request): Json<CommitRequest>,
+) -> Response {
+    let (cursors, principal) = match authorize(&state, &headers).await {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    match cursors
+        .commit(principal.tenant_id.as_deref(), &name, request.sequence)
+        .await
+    {
+        Ok(cursor) => (StatusCode::OK, Json(cursor)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_names_do_not_collide() {
+        assert!(validate_name("warehouse-1").is_ok());
+        assert!(validate_name("a_b").is_err());
+        assert!(validate_name("Upper").is_err());
+        assert!(validate_name("").is_err());
+
+        assert_eq!(durable_name(None, "etl"), "cursor_default_etl");
+        assert_eq!(durable_name(Some("acme_eu"), "etl"), "cursor_acme_eu_etl");
+        assert_ne!(
+            durable_name(Some("a_b"), "c"),
+            durable_name(Some("a"), "b-c")
+        );
+        assert_eq!(
+            feed_subject(Some("acme"), "agent-1"),
+            "facto.feed.acme.agent-1"
+        );
+        assert_eq!(feed_subject(None, "agent-1"), "facto.feed.default.agent-1");
+    }
+}
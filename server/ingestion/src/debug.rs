@@ -0,0 +1,442 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+use metrics::counter;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::info;
+
+use crate::auth::Scope;
+use crate::crypto::{build_canonical_form, compute_event_hash};
+use crate::encoding::Encoding;
+use crate::limits::LimitError;
+use crate::verification::now_nanos;
+use crate::{AppState, FactoEvent};
+
+/// Request header asking for a debug bundle (`1` or `true`)
+pub const DEBUG_HEADER: &str = "x-facto-debug";
+
+/// Response header carrying the id of the captured bundle
+pub const DEBUG_ID_HEADER: &str = "x-facto-debug-id";
+
+/// Request id set or echoed by the request id layer
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// ============================================================================
+// Stage Timings
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageTiming {
+    pub stage: &'static str,
+    /// Time spent since the previous stage ended
+    pub elapsed_us: u64,
+}
+
+struct TraceState {
+    started: Instant,
+    last: Instant,
+    stages: Vec<StageTiming>,
+}
+
+/// Stage timings of a request being debugged. Handlers receive it as a
+/// request extension; the default trace records nothing.
+#[derive(Clone, Default)]
+pub struct DebugTrace(Option<Arc<Mutex<TraceState>>>);
+
+impl DebugTrace {
+    fn start() -> Self {
+        let now = Instant::now();
+        Self(Some(Arc::new(Mutex::new(TraceState {
+            started: now,
+            last: now,
+            stages: Vec::new(),
+        }))))
+    }
+
+    /// Mark the end of `stage`
+    pub fn stage(&self, stage: &'static str) {
+        if let Some(ref state) = self.0 {
+            let mut state = state.lock().unwrap();
+            let now = Instant::now();
+            let elapsed_us = now.duration_since(state.last).as_micros() as u64;
+            state.last = now;
+            state.stages.push(StageTiming { stage, elapsed_us });
+        }
+    }
+
+    fn finish(&self) -> (Vec<StageTiming>, u64) {
+        match self.0 {
+            Some(ref state) => {
+                let state = state.lock().unwrap();
+                (
+                    state.stages.clone(),
+                    state.started.elapsed().as_micros() as u64,
+                )
+            }
+            None => (Vec::new(), 0),
+        }
+    }
+}
+
+// ============================================================================
+// Debug Bundles
+// ============================================================================
+
+/// How the server read one submitted event
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EventTrace {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facto_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_version: Option<u32>,
+    /// The exact string the server hashes and verifies the signature over
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canonical_form: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub computed_event_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provided_event_hash: Option<String>,
+    /// Why the event could not be read or canonicalized
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What the server decided for one event
+#[derive(Debug, Clone, Serialize)]
+pub struct Decision {
+    pub facto_id: String,
+    /// `accepted`, `spooled`, `duplicate` or `rejected`
+    pub outcome: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Everything the server saw and decided while handling one request
+#[derive(Debug, Clone, Serialize)]
+pub struct DebugBundle {
+    pub debug_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub principal: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub captured_at: i64,
+    pub expires_at: i64,
+    pub events: Vec<EventTrace>,
+    pub stages: Vec<StageTiming>,
+    pub total_us: u64,
+    pub decisions: Vec<Decision>,
+    pub status: u16,
+    pub response: serde_json::Value,
+}
+
+/// Recently captured bundles, kept in memory for a short TTL
+pub struct DebugBundles {
+    bundles: DashMap<String, DebugBundle>,
+    ttl: Duration,
+    max_bundles: usize,
+}
+
+impl DebugBundles {
+    pub fn new(ttl: Duration, max_bundles: usize) -> Self {
+        Self {
+            bundles: DashMap::new(),
+            ttl,
+            max_bundles: max_bundles.max(1),
+        }
+    }
+
+    /// Store a bundle, evicting expired bundles and then the oldest ones
+    pub fn insert(&self, bundle: DebugBundle) {
+        let now = now_nanos();
+        self.bundles.retain(|_, b| b.expires_at > now);
+        while self.bundles.len() >= self.max_bundles {
+            let oldest = self
+                .bundles
+                .iter()
+                .min_by_key(|b| b.captured_at)
+                .map(|b| b.key().clone());
+            match oldest {
+                Some(id) => self.bundles.remove(&id),
+                None => break,
+            };
+        }
+        self.bundles.insert(bundle.debug_id.clone(), bundle);
+    }
+
+    pub fn get(&self, debug_id: &str) -> Option<DebugBundle> {
+        self.bundles
+            .get(debug_id)
+            .filter(|b| b.expires_at > now_nanos())
+            .map(|b| b.clone())
+    }
+}
+
+/// A request or response body decoded by its content type, JSON if unset
+fn decode_body(headers: &HeaderMap, body: &[u8]) -> serde_json::Value {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(Encoding::from_media_type)
+        .unwrap_or(Encoding::Json)
+        .decode(body)
+        .unwrap_or_default()
+}
+
+/// Canonical forms and hashes of the events in an ingest request body
+fn trace_events(value: serde_json::Value) -> Vec<EventTrace> {
+    let values = match value.get("events").and_then(|e| e.as_array()) {
+        Some(events) => events.clone(),
+        None if value.is_object() => vec![value],
+        None => return Vec::new(),
+    };
+
+    values
+        .into_iter()
+        .map(|value| {
+            let mut trace = EventTrace {
+                facto_id: value["facto_id"].as_str().map(String::from),
+                provided_event_hash: value["proof"]["event_hash"].as_str().map(String::from),
+                ..Default::default()
+            };
+            let canonical = FactoEvent::try_from(value)
+                .map_err(|e| e.to_string())
+                .and_then(|event| {
+                    trace.event_version = Some(event.event_version);
+                    build_canonical_form(&event).map_err(|e| e.to_string())
+                });
+            match canonical {
+                Ok(canonical) => {
+                    trace.computed_event_hash = Some(compute_event_hash(&canonical));
+                    trace.canonical_form = Some(canonical);
+                }
+                Err(e) => trace.error = Some(e),
+            }
+            trace
+        })
+        .collect()
+}
+
+/// Per-event decisions, read back from a single or batch ingest response
+fn decisions(events: &[EventTrace], response: &serde_json::Value) -> Vec<Decision> {
+    if let Some(rejected) = response["rejected"].as_array() {
+        let reasons: HashMap<&str, &str> = rejected
+            .iter()
+            .filter_map(|r| Some((r["facto_id"].as_str()?, r["reason"].as_str()?)))
+            .collect();
+        let duplicates: Vec<&str> = response["duplicates"]
+            .as_array()
+            .map(|d| d.iter().filter_map(|id| id.as_str()).collect())
+            .unwrap_or_default();
+
+        return events
+            .iter()
+            .filter_map(|e| e.facto_id.as_deref())
+            .map(|facto_id| {
+                let (outcome, reason) = match reasons.get(facto_id) {
+                    Some(reason) => ("rejected", Some(reason.to_string())),
+                    None if duplicates.contains(&facto_id) => ("duplicate", None),
+                    None => ("accepted", None),
+                };
+                Decision {
+                    facto_id: facto_id.to_string(),
+                    outcome,
+                    reason,
+                }
+            })
+            .collect();
+    }
+
+    let Some(facto_id) = response["facto_id"].as_str() else {
+        return Vec::new();
+    };
+    let outcome = if response["accepted"] != true {
+        "rejected"
+    } else if response["duplicate"] == true {
+        "duplicate"
+    } else if response["spooled"] == true {
+        "spooled"
+    } else {
+        "accepted"
+    };
+    vec![Decision {
+        facto_id: facto_id.to_string(),
+        outcome,
+        reason: response["reason"].as_str().map(String::from),
+    }]
+}
+
+// ============================================================================
+// Capture Middleware
+// ============================================================================
+
+/// Middleware capturing a debug bundle for requests sent with
+/// `X-Facto-Debug: 1`. Capturing requires credentials with the debug scope,
+/// whether or not ingestion itself is authenticated. The bundle id is
+/// returned in `X-Facto-Debug-Id`.
+pub async fn capture_debug_bundle(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let requested = request
+        .headers()
+        .get(DEBUG_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+    if !requested {
+        return next.run(request).await;
+    }
+
+    let principal = match state.auth.authorize(request.headers(), Scope::Debug).await {
+        Ok(principal) => principal,
+        Err(e) => return e.into_response(),
+    };
+
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+
+    // Buffer the body to trace the events; the handler reads the copy
+    let trace = DebugTrace::start();
+    let (mut parts, body) = request.into_parts();
+    let body = match to_bytes(body, state.capabilities.max_body_bytes).await {
+        Ok(body) => body,
+        Err(_) => {
+            return LimitError::BodyTooLarge {
+                limit: state.capabilities.max_body_bytes,
+            }
+            .into_response();
+        }
+    };
+    let events = trace_events(decode_body(&parts.headers, &body));
+    parts.extensions.insert(trace.clone());
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    let (mut parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX).await.unwrap_or_default();
+    let response = decode_body(&parts.headers, &body);
+    let (stages, total_us) = trace.finish();
+
+    let captured_at = now_nanos();
+    let bundle = DebugBundle {
+        debug_id: uuid::Uuid::now_v7().to_string(),
+        request_id,
+        principal: principal.name,
+        tenant_id: principal.tenant_id,
+        method,
+        path,
+        captured_at,
+        expires_at: captured_at.saturating_add(state.debug_bundles.ttl.as_nanos() as i64),
+        decisions: decisions(&events, &response),
+        events,
+        stages,
+        total_us,
+        status: parts.status.as_u16(),
+        response,
+    };
+    info!(
+        "Captured debug bundle {} for {} {}",
+        bundle.debug_id, bundle.principal, bundle.path
+    );
+    counter!("facto_debug_bundles_total").increment(1);
+
+    if let Ok(value) = HeaderValue::from_str(&bundle.debug_id) {
+        parts.headers.insert(DEBUG_ID_HEADER, value);
+    }
+    state.debug_bundles.insert(bundle);
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{sign_test_event, test_event};
+    use ed25519_dalek::SigningKey;
+
+    fn bundle(debug_id: &str, captured_at: i64) -> DebugBundle {
+        DebugBundle {
+            debug_id: debug_id.to_string(),
+            request_id: None,
+            principal: "sdk-dev".to_string(),
+            tenant_id: None,
+            method: "POST".to_string(),
+            path: "/v1/ingest".to_string(),
+            captured_at,
+            expires_at: captured_at + 60_000_000_000,
+            events: Vec::new(),
+            stages: Vec::new(),
+            total_us: 0,
+            decisions: Vec::new(),
+            status: 202,
+            response: serde_json::Value::Null,
+        }
+    }
+
+    #[test]
+    fn test_bundles_expire_and_evict_oldest() {
+        let bundles = DebugBundles::new(Duration::from_secs(60), 2);
+        let now = now_nanos();
+
+        let mut expired = bundle("expired", now - 120_000_000_000);
+        expired.expires_at = now - 60_000_000_000;
+        bundles.insert(expired);
+        assert!(bundles.get("expired").is_none());
+
+        bundles.insert(bundle("a", now));
+        bundles.insert(bundle("b", now + 1));
+        bundles.insert(bundle("c", now + 2));
+        assert!(bundles.get("a").is_none());
+        assert!(bundles.get("b").is_some());
+        assert!(bundles.get("c").is_some());
+    }
+
+    #[test]
+    fn test_trace_events_and_decisions() {
+        let event = sign_test_event(test_event(), &SigningKey::from_bytes(&[7u8; 32]));
+        let mut malformed = serde_json::to_value(&event).unwrap();
+        malformed["facto_id"] = serde_json::json!("ft-bad");
+        malformed["event_version"] = serde_json::json!(7);
+        let body = serde_json::json!({ "events": [event, malformed] });
+
+        let events = trace_events(body);
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0].computed_event_hash.as_deref(),
+            Some(event.proof.event_hash.as_str())
+        );
+        assert!(events[1]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("event_version 7"));
+
+        let response = serde_json::json!({
+            "accepted_count": 1,
+            "rejected_count": 1,
+            "rejected": [{ "facto_id": "ft-bad", "reason": "Invalid" }],
+        });
+        let decisions = decisions(&events, &response);
+        assert_eq!(decisions[0].outcome, "accepted");
+        assert_eq!(decisions[1].outcome, "rejected");
+        assert_eq!(decisions[1].reason.as_deref(), Some("Invalid"));
+    }
+}
@@ -0,0 +1,214 @@
+//! Compressed ingest request bodies.
+//!
+//! Edge agents may send batches with `Content-Encoding: gzip`, `deflate` or
+//! `zstd`. The compressed body counts against `MAX_BODY_BYTES` on the wire,
+//! and decompression stops as soon as the output passes the same limit, so a
+//! small body that inflates enormously costs at most one limit of memory.
+
+use crate::protocol::{ErrorCode, ErrorResponse};
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use metrics::counter;
+use std::io::Read;
+use std::sync::Arc;
+
+use crate::limits::LimitError;
+use crate::AppState;
+
+/// Content codings accepted on request bodies, for `Accept-Encoding` and
+/// capabilities
+pub const CONTENT_ENCODINGS: &[&str] = &["gzip", "deflate", "zstd"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Gzip,
+    /// zlib-wrapped deflate, as HTTP defines it
+    Deflate,
+    Zstd,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecompressError {
+    #[error("Unsupported Content-Encoding: {0}")]
+    Unsupported(String),
+    #[error("Invalid {encoding} body: {source}")]
+    Invalid {
+        encoding: &'static str,
+        source: std::io::Error,
+    },
+    #[error(transparent)]
+    TooLarge(LimitError),
+}
+
+impl ContentEncoding {
+    /// The coding a `Content-Encoding` header names; `None` for `identity`
+    pub fn parse(value: &str) -> Result<Option<Self>, DecompressError> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "identity" => Ok(None),
+            "gzip" | "x-gzip" => Ok(Some(ContentEncoding::Gzip)),
+            "deflate" => Ok(Some(ContentEncoding::Deflate)),
+            "zstd" => Ok(Some(ContentEncoding::Zstd)),
+            _ => Err(DecompressError::Unsupported(value.to_string())),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+            ContentEncoding::Zstd => "zstd",
+        }
+    }
+
+    /// Decompress `body`, failing once the output exceeds `limit` bytes
+    pub fn decompress(self, body: &[u8], limit: usize) -> Result<Vec<u8>, DecompressError> {
+        let invalid = |source| DecompressError::Invalid {
+            encoding: self.as_str(),
+            source,
+        };
+        let reader: Box<dyn Read + '_> = match self {
+            ContentEncoding::Gzip => Box::new(flate2::read::MultiGzDecoder::new(body)),
+            ContentEncoding::Deflate => Box::new(flate2::read::ZlibDecoder::new(body)),
+            ContentEncoding::Zstd => Box::new(zstd::Decoder::with_buffer(body).map_err(invalid)?),
+        };
+
+        let mut decompressed = Vec::new();
+        reader
+            .take(limit as u64 + 1)
+            .read_to_end(&mut decompressed)
+            .map_err(invalid)?;
+        if decompressed.len() > limit {
+            return Err(DecompressError::TooLarge(
+                LimitError::DecompressedTooLarge { limit },
+            ));
+        }
+        Ok(decompressed)
+    }
+}
+
+impl IntoResponse for DecompressError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            DecompressError::TooLarge(e) => return e.into_response(),
+            DecompressError::Unsupported(_) => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                ErrorCode::UnsupportedContentEncoding,
+            ),
+            DecompressError::Invalid { .. } => {
+                (StatusCode::BAD_REQUEST, ErrorCode::InvalidCompressedBody)
+            }
+        };
+        let mut response = (
+            status,
+            Json(ErrorResponse {
+                error: self.to_string(),
+                code: Some(code),
+            }),
+        )
+            .into_response();
+        if status == StatusCode::UNSUPPORTED_MEDIA_TYPE {
+            // RFC 7694: name the codings that would have been accepted
+            response.headers_mut().insert(
+                header::ACCEPT_ENCODING,
+                HeaderValue::from_str(&CONTENT_ENCODINGS.join(", ")).expect("valid header"),
+            );
+        }
+        response
+    }
+}
+
+/// Replace a compressed request body with its decompressed form, so later
+/// middleware and the handlers see a plain body
+pub async fn decompress_request(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let encoding = request
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .map(|v| v.to_str().unwrap_or("invalid"))
+        .map(ContentEncoding::parse)
+        .transpose();
+    let encoding = match encoding {
+        Ok(Some(Some(encoding))) => encoding,
+        Ok(_) => return next.run(request).await,
+        Err(e) => return e.into_response(),
+    };
+
+    let limit = state.limits.max_body_bytes;
+    let (mut parts, body) = request.into_parts();
+    let compressed = match to_bytes(body, limit).await {
+        Ok(body) => body,
+        Err(_) => return LimitError::BodyTooLarge { limit }.into_response(),
+    };
+    let compressed_len = compressed.len();
+    let decompressed =
+        tokio::task::spawn_blocking(move || encoding.decompress(&compressed, limit)).await;
+    let body = match decompressed {
+        Ok(Ok(body)) => body,
+        Ok(Err(e)) => return e.into_response(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Decompression failed: {}", e),
+                    code: None,
+                }),
+            )
+                .into_response()
+        }
+    };
+
+    counter!("facto_request_bytes_compressed_total", "encoding" => encoding.as_str())
+        .increment(compressed_len as u64);
+    counter!("facto_request_bytes_decompressed_total", "encoding" => encoding.as_str())
+        .increment(body.len() as u64);
+
+    parts.headers.remove(header::CONTENT_ENCODING);
+    parts
+        .headers
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_decompression_stops_at_limit() {
+        let body = vec![b'a'; 64 * 1024];
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        gzip.write_all(&body).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let zstd = zstd::encode_all(&body[..], 19).unwrap();
+
+        for (encoding, compressed) in [
+            (ContentEncoding::Gzip, &gzip),
+            (ContentEncoding::Zstd, &zstd),
+        ] {
+            assert!(compressed.len() < 1024);
+            assert_eq!(encoding.decompress(compressed, body.len()).unwrap(), body);
+            assert!(matches!(
+                encoding.decompress(compressed, body.len() - 1),
+                Err(DecompressError::TooLarge(_))
+            ));
+        }
+        assert!(matches!(
+            ContentEncoding::Deflate.decompress(&gzip, body.len()),
+            Err(DecompressError::Invalid { .. })
+        ));
+        assert!(matches!(
+            ContentEncoding::parse("br"),
+            Err(DecompressError::Unsupported(_))
+        ));
+        assert_eq!(ContentEncoding::parse("identity").unwrap(), None);
+    }
+}
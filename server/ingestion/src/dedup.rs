@@ -0,0 +1,178 @@
+use axum::async_trait;
+use dashmap::{mapref::entry::Entry, DashMap};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::shared::{self, KvBucket, Swap};
+
+/// Bucket holding the event hash of each recently accepted facto_id
+pub const DEDUP_BUCKET: &str = "facto_dedup";
+
+/// Result of claiming a facto_id for publication
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupOutcome {
+    /// First time this facto_id is seen within the window
+    New,
+    /// The same event was already accepted
+    Duplicate,
+    /// The facto_id was already accepted with a different event hash
+    Conflict,
+}
+
+/// Record of recently accepted facto_ids
+#[async_trait]
+pub trait DedupStore: Send + Sync {
+    /// Claim `facto_id` for an event with the given verified hash. A `New`
+    /// claim must be released with [`DedupStore::release`] if publishing fails.
+    async fn claim(&self, facto_id: &str, event_hash: &str) -> DedupOutcome;
+
+    /// Forget a claim whose event could not be published
+    async fn release(&self, facto_id: &str);
+}
+
+/// Short-lived in-process record of accepted facto_ids.
+///
+/// This catches agent retries before they reach NATS; JetStream's own
+/// `Nats-Msg-Id` deduplication remains the backstop across replicas.
+pub struct DedupCache {
+    entries: DashMap<String, (Instant, String)>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl DedupCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: DashMap::new(),
+            capacity,
+            ttl,
+        }
+    }
+
+    /// Claim `facto_id` for an event with the given verified hash. A `New`
+    /// claim must be released with [`DedupCache::release`] if publishing fails.
+    pub fn claim(&self, facto_id: &str, event_hash: &str) -> DedupOutcome {
+        if self.capacity == 0 {
+            return DedupOutcome::New;
+        }
+        if self.entries.len() >= self.capacity {
+            self.evict_expired();
+        }
+
+        match self.entries.entry(facto_id.to_string()) {
+            Entry::Occupied(mut entry) => {
+                let (seen_at, seen_hash) = entry.get();
+                if seen_at.elapsed() > self.ttl {
+                    entry.insert((Instant::now(), event_hash.to_string()));
+                    DedupOutcome::New
+                } else if seen_hash == event_hash {
+                    DedupOutcome::Duplicate
+                } else {
+                    DedupOutcome::Conflict
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert((Instant::now(), event_hash.to_string()));
+                DedupOutcome::New
+            }
+        }
+    }
+
+    /// Forget a claim whose event could not be published
+    pub fn release(&self, facto_id: &str) {
+        self.entries.remove(facto_id);
+    }
+
+    fn evict_expired(&self) {
+        let ttl = self.ttl;
+        self.entries
+            .retain(|_, (seen_at, _)| seen_at.elapsed() <= ttl);
+    }
+}
+
+#[async_trait]
+impl DedupStore for DedupCache {
+    async fn claim(&self, facto_id: &str, event_hash: &str) -> DedupOutcome {
+        DedupCache::claim(self, facto_id, event_hash)
+    }
+
+    async fn release(&self, facto_id: &str) {
+        DedupCache::release(self, facto_id)
+    }
+}
+
+/// Dedup window shared by all replicas, so a retry that lands on another
+/// replica is still answered as a duplicate
+pub struct KvDedup {
+    bucket: Arc<KvBucket>,
+    local: DedupCache,
+}
+
+impl KvDedup {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            bucket: Arc::new(KvBucket::new(DEDUP_BUCKET, ttl)),
+            local: DedupCache::new(capacity, ttl),
+        }
+    }
+
+    pub fn bucket(&self) -> Arc<KvBucket> {
+        self.bucket.clone()
+    }
+}
+
+#[async_trait]
+impl DedupStore for KvDedup {
+    async fn claim(&self, facto_id: &str, event_hash: &str) -> DedupOutcome {
+        let outcome = self
+            .bucket
+            .swap(facto_id, |current| match current {
+                None => Swap::Write(event_hash.as_bytes().to_vec(), DedupOutcome::New),
+                Some(seen) if seen == event_hash.as_bytes() => Swap::Keep(DedupOutcome::Duplicate),
+                Some(_) => Swap::Keep(DedupOutcome::Conflict),
+            })
+            .await;
+        match outcome {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                shared::fallback(e);
+                self.local.claim(facto_id, event_hash)
+            }
+        }
+    }
+
+    async fn release(&self, facto_id: &str) {
+        self.local.release(facto_id);
+        if let Err(e) = self.bucket.delete(facto_id).await {
+            shared::fallback(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claims() {
+        let cache = DedupCache::new(16, Duration::from_secs(60));
+
+        assert_eq!(cache.claim("tr-1", "hash-a"), DedupOutcome::New);
+        assert_eq!(cache.claim("tr-1", "hash-a"), DedupOutcome::Duplicate);
+        assert_eq!(cache.claim("tr-1", "hash-b"), DedupOutcome::Conflict);
+
+        cache.release("tr-1");
+        assert_eq!(cache.claim("tr-1", "hash-b"), DedupOutcome::New);
+    }
+
+    #[test]
+    fn test_expired_claims_are_new() {
+        let cache = DedupCache::new(16, Duration::ZERO);
+
+        assert_eq!(cache.claim("tr-1", "hash-a"), DedupOutcome::New);
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(cache.claim("tr-1", "hash-a"), DedupOutcome::New);
+    }
+}
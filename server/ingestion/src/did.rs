@@ -0,0 +1,489 @@
+//! Decentralized identifiers (DIDs) for event signers.
+//!
+//! A proof may name a `verification_method`: a DID URL such as
+//! `did:key:z6Mk...` or `did:web:example.com:agents:a1#key-1`. The key the
+//! DID resolves to must be the proof's public key, so consumers can identify
+//! the signer by its DID instead of the raw key. `did:key` DIDs carry their
+//! key; `did:web` DIDs name a DID document the server fetches over HTTPS.
+//!
+//! Events signed under a verification method are published with
+//! [`DataIntegrityProof`] metadata, the proof shape of W3C Data Integrity,
+//! so tooling built for verifiable credentials can read who signed them.
+
+use base64::{
+    engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64URL},
+    Engine,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{self, PublicKey, SignatureAlgorithm, VerificationError};
+use crate::FactoEvent;
+
+/// DID methods a verification method may use
+pub const DID_METHODS: &[&str] = &["key", "web"];
+
+/// Multicodec prefixes of the public key types `did:key` and `Multikey`
+/// encode
+const ED25519_PUB: [u8; 2] = [0xed, 0x01];
+const SECP256K1_PUB: [u8; 2] = [0xe7, 0x01];
+const P256_PUB: [u8; 2] = [0x80, 0x24];
+
+#[derive(Debug, thiserror::Error)]
+pub enum DidError {
+    #[error("malformed DID URL {0}")]
+    Malformed(String),
+    #[error("unsupported DID method {0}")]
+    UnsupportedMethod(String),
+    #[error("unsupported key encoding: {0}")]
+    UnsupportedKey(String),
+    #[error("{0} is not a verification method of its DID document")]
+    UnknownMethod(String),
+    #[error("failed to resolve {did}: {reason}")]
+    Resolution { did: String, reason: String },
+}
+
+impl From<DidError> for VerificationError {
+    fn from(e: DidError) -> Self {
+        VerificationError::InvalidVerificationMethod(e.to_string())
+    }
+}
+
+// ============================================================================
+// Base58
+// ============================================================================
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Bitcoin base58, the encoding of multibase `z` strings
+pub fn base58_encode(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    // Base 58 digits, least significant first
+    let mut digits: Vec<u8> = Vec::with_capacity(bytes.len() * 138 / 100 + 1);
+    for &byte in &bytes[zeros..] {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    std::iter::repeat_n('1', zeros)
+        .chain(
+            digits
+                .iter()
+                .rev()
+                .map(|d| BASE58_ALPHABET[*d as usize] as char),
+        )
+        .collect()
+}
+
+pub fn base58_decode(encoded: &str) -> Option<Vec<u8>> {
+    let zeros = encoded.bytes().take_while(|c| *c == b'1').count();
+    // Bytes, least significant first
+    let mut bytes: Vec<u8> = Vec::with_capacity(encoded.len() * 733 / 1000 + 1);
+    for c in encoded.bytes().skip(zeros) {
+        let mut carry = BASE58_ALPHABET.iter().position(|a| *a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    bytes.extend(std::iter::repeat_n(0, zeros));
+    bytes.reverse();
+    Some(bytes)
+}
+
+// ============================================================================
+// Keys
+// ============================================================================
+
+/// A public key a DID resolves to. ECDSA keys are held as compressed SEC1
+/// points so keys can be compared whatever encoding they came in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DidKey {
+    pub algorithm: SignatureAlgorithm,
+    pub bytes: Vec<u8>,
+}
+
+impl DidKey {
+    fn new(algorithm: SignatureAlgorithm, bytes: &[u8]) -> Result<Self, DidError> {
+        let key = PublicKey::decode(algorithm, &BASE64.encode(bytes))
+            .map_err(|e| DidError::UnsupportedKey(e.to_string()))?;
+        Ok(Self {
+            algorithm,
+            bytes: key_bytes(&key),
+        })
+    }
+
+    /// Decode a multibase key with a multicodec prefix, as in `did:key`
+    /// DIDs and `Multikey` verification methods
+    pub fn from_multikey(multikey: &str) -> Result<Self, DidError> {
+        let bytes = multikey
+            .strip_prefix('z')
+            .and_then(base58_decode)
+            .ok_or_else(|| DidError::UnsupportedKey("not a base58btc multibase key".into()))?;
+        let algorithm = match bytes.get(..2) {
+            Some(prefix) if prefix == ED25519_PUB => SignatureAlgorithm::Ed25519,
+            Some(prefix) if prefix == P256_PUB => SignatureAlgorithm::Es256,
+            Some(prefix) if prefix == SECP256K1_PUB => SignatureAlgorithm::Secp256k1,
+            _ => return Err(DidError::UnsupportedKey("unknown multicodec".into())),
+        };
+        Self::new(algorithm, &bytes[2..])
+    }
+
+    fn from_jwk(jwk: &Jwk) -> Result<Self, DidError> {
+        let coordinate = |c: &str| {
+            BASE64URL
+                .decode(c)
+                .map_err(|e| DidError::UnsupportedKey(format!("bad JWK coordinate: {}", e)))
+        };
+        let algorithm = match (jwk.kty.as_str(), jwk.crv.as_str()) {
+            ("OKP", "Ed25519") => {
+                return Self::new(SignatureAlgorithm::Ed25519, &coordinate(&jwk.x)?)
+            }
+            ("EC", "P-256") => SignatureAlgorithm::Es256,
+            ("EC", "secp256k1") => SignatureAlgorithm::Secp256k1,
+            (kty, crv) => {
+                return Err(DidError::UnsupportedKey(format!("JWK {} {}", kty, crv)));
+            }
+        };
+        let y = jwk
+            .y
+            .as_deref()
+            .ok_or_else(|| DidError::UnsupportedKey("EC JWK without y".into()))?;
+        let mut point = vec![0x04];
+        point.extend(coordinate(&jwk.x)?);
+        point.extend(coordinate(y)?);
+        Self::new(algorithm, &point)
+    }
+
+    /// The key as a multibase `Multikey`, the method-specific id of its
+    /// `did:key` DID
+    pub fn multikey(&self) -> String {
+        let prefix = match self.algorithm {
+            SignatureAlgorithm::Ed25519 => ED25519_PUB,
+            SignatureAlgorithm::Es256 => P256_PUB,
+            SignatureAlgorithm::Secp256k1 => SECP256K1_PUB,
+        };
+        format!("z{}", base58_encode(&[&prefix[..], &self.bytes].concat()))
+    }
+
+    /// Whether a proof's base64 `public_key` of `algorithm` is this key
+    pub fn matches(&self, algorithm: SignatureAlgorithm, public_key: &str) -> bool {
+        algorithm == self.algorithm
+            && PublicKey::decode(algorithm, public_key)
+                .is_ok_and(|key| key_bytes(&key) == self.bytes)
+    }
+}
+
+fn key_bytes(key: &PublicKey) -> Vec<u8> {
+    match key {
+        PublicKey::Ed25519(key) => key.to_bytes().to_vec(),
+        PublicKey::Es256(key) => key.to_encoded_point(true).as_bytes().to_vec(),
+        PublicKey::Secp256k1(key) => key.to_encoded_point(true).as_bytes().to_vec(),
+    }
+}
+
+/// The `did:key` DID of a key
+pub fn did_key(key: &DidKey) -> String {
+    format!("did:key:{}", key.multikey())
+}
+
+/// Check that `key`, resolved from the event's verification method, is the
+/// key its proof is signed with
+pub fn check_binding(event: &FactoEvent, key: &DidKey) -> Result<(), VerificationError> {
+    let algorithm = SignatureAlgorithm::of(&event.proof)?;
+    if !key.matches(algorithm, &event.proof.public_key) {
+        return Err(VerificationError::InvalidVerificationMethod(format!(
+            "{} does not resolve to the proof's public key",
+            event
+                .proof
+                .verification_method
+                .as_deref()
+                .unwrap_or_default()
+        )));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// DID URLs and Documents
+// ============================================================================
+
+/// A parsed DID URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DidUrl {
+    /// The DID, without fragment
+    pub did: String,
+    pub method: String,
+    pub method_id: String,
+    pub fragment: Option<String>,
+}
+
+impl DidUrl {
+    pub fn parse(url: &str) -> Result<Self, DidError> {
+        let malformed = || DidError::Malformed(url.to_string());
+        let (did, fragment) = match url.split_once('#') {
+            Some((did, fragment)) if !fragment.is_empty() => (did, Some(fragment.to_string())),
+            Some(_) => return Err(malformed()),
+            None => (url, None),
+        };
+        let (method, method_id) = did
+            .strip_prefix("did:")
+            .and_then(|rest| rest.split_once(':'))
+            .ok_or_else(malformed)?;
+        if method.is_empty()
+            || !method
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+            || method_id.is_empty()
+            || did.contains(['/', '?'])
+        {
+            return Err(malformed());
+        }
+        Ok(Self {
+            did: did.to_string(),
+            method: method.to_string(),
+            method_id: method_id.to_string(),
+            fragment,
+        })
+    }
+
+    /// The key of a `did:key` DID. A fragment, if any, must be the key
+    /// itself.
+    pub fn did_key(&self) -> Result<DidKey, DidError> {
+        if self.method != "key" {
+            return Err(DidError::UnsupportedMethod(self.method.clone()));
+        }
+        if self
+            .fragment
+            .as_ref()
+            .is_some_and(|fragment| *fragment != self.method_id)
+        {
+            return Err(DidError::UnknownMethod(self.to_string()));
+        }
+        DidKey::from_multikey(&self.method_id)
+    }
+
+    /// Domain a `did:web` DID is hosted on, with its port if any
+    pub fn web_host(&self) -> Result<String, DidError> {
+        if self.method != "web" {
+            return Err(DidError::UnsupportedMethod(self.method.clone()));
+        }
+        let host = self.method_id.split(':').next().unwrap_or_default();
+        Ok(host.replace("%3A", ":").replace("%3a", ":"))
+    }
+
+    /// Where a `did:web` DID's document is published
+    pub fn web_document_url(&self) -> Result<String, DidError> {
+        let host = self.web_host()?;
+        let path: Vec<&str> = self.method_id.split(':').skip(1).collect();
+        if host.is_empty() || path.iter().any(|segment| segment.is_empty()) {
+            return Err(DidError::Malformed(self.to_string()));
+        }
+        Ok(match path.is_empty() {
+            true => format!("https://{}/.well-known/did.json", host),
+            false => format!("https://{}/{}/did.json", host, path.join("/")),
+        })
+    }
+}
+
+impl std::fmt::Display for DidUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.fragment {
+            Some(ref fragment) => write!(f, "{}#{}", self.did, fragment),
+            None => f.write_str(&self.did),
+        }
+    }
+}
+
+/// The parts of a DID document used to find a verification method's key
+#[derive(Debug, Clone, Deserialize)]
+pub struct DidDocument {
+    pub id: String,
+    #[serde(default, rename = "verificationMethod")]
+    pub verification_method: Vec<VerificationMethod>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerificationMethod {
+    pub id: String,
+    #[serde(rename = "publicKeyMultibase")]
+    pub public_key_multibase: Option<String>,
+    #[serde(rename = "publicKeyJwk")]
+    pub public_key_jwk: Option<Jwk>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    pub x: String,
+    pub y: Option<String>,
+}
+
+impl DidDocument {
+    /// The key of the verification method `url` names. Methods are matched
+    /// by absolute id or by fragment relative to the document.
+    pub fn key(&self, url: &DidUrl) -> Result<DidKey, DidError> {
+        if self.id != url.did {
+            return Err(DidError::Resolution {
+                did: url.did.clone(),
+                reason: format!("document is for {}", self.id),
+            });
+        }
+        let fragment = url
+            .fragment
+            .as_deref()
+            .ok_or_else(|| DidError::UnknownMethod(url.to_string()))?;
+        let absolute = url.to_string();
+        let method = self
+            .verification_method
+            .iter()
+            .find(|m| m.id == absolute || m.id.strip_prefix('#') == Some(fragment))
+            .ok_or(DidError::UnknownMethod(absolute))?;
+        match (&method.public_key_multibase, &method.public_key_jwk) {
+            (Some(multikey), _) => DidKey::from_multikey(multikey),
+            (None, Some(jwk)) => DidKey::from_jwk(jwk),
+            (None, None) => Err(DidError::UnsupportedKey(format!(
+                "{} has no publicKeyMultibase or publicKeyJwk",
+                method.id
+            ))),
+        }
+    }
+}
+
+// ============================================================================
+// Data Integrity Proofs
+// ============================================================================
+
+/// Proof metadata in the shape of a W3C Data Integrity proof. The
+/// `cryptosuite` is `facto-{algorithm}-v{canonical_version}`: `proofValue`
+/// is the event signature over the event's canonical form in that version,
+/// multibase encoded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataIntegrityProof {
+    #[serde(rename = "type")]
+    pub proof_type: String,
+    pub cryptosuite: String,
+    /// The event's `completed_at`, as an XML Schema dateTime
+    pub created: String,
+    pub verification_method: String,
+    pub proof_purpose: String,
+    pub proof_value: String,
+}
+
+impl DataIntegrityProof {
+    /// Metadata for an event whose proof names a verification method
+    pub fn of(event: &FactoEvent) -> Option<Self> {
+        let verification_method = event.proof.verification_method.clone()?;
+        let algorithm = SignatureAlgorithm::of(&event.proof).ok()?;
+        let signature = BASE64.decode(&event.proof.signature).ok()?;
+        let created = chrono::DateTime::from_timestamp_nanos(event.completed_at)
+            .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true);
+        Some(Self {
+            proof_type: "DataIntegrityProof".to_string(),
+            cryptosuite: format!(
+                "facto-{}-v{}",
+                algorithm.name(),
+                crypto::canonical_version(event)
+            ),
+            created,
+            verification_method,
+            proof_purpose: "assertionMethod".to_string(),
+            proof_value: format!("z{}", base58_encode(&signature)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{sign_test_event, test_event};
+    use ed25519_dalek::SigningKey;
+
+    #[test]
+    fn test_did_key_round_trip() {
+        // The Ed25519 test vector of the did:key specification
+        let url = DidUrl::parse(
+            "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp#z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+        )
+        .unwrap();
+        let key = url.did_key().unwrap();
+        assert_eq!(key.algorithm, SignatureAlgorithm::Ed25519);
+        assert_eq!(did_key(&key), url.did);
+        assert_eq!(
+            base58_decode(&base58_encode(&[0, 0, 1, 2])).unwrap(),
+            [0, 0, 1, 2]
+        );
+
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut event = test_event();
+        let key = DidKey::new(
+            SignatureAlgorithm::Ed25519,
+            signing_key.verifying_key().as_bytes(),
+        )
+        .unwrap();
+        event.proof.verification_method = Some(did_key(&key));
+        let event = sign_test_event(event, &signing_key);
+        assert!(key.matches(SignatureAlgorithm::Ed25519, &event.proof.public_key));
+        assert!(!key.matches(SignatureAlgorithm::Es256, &event.proof.public_key));
+
+        let proof = DataIntegrityProof::of(&event).unwrap();
+        assert_eq!(proof.cryptosuite, "facto-ed25519-v1");
+        let signature = base58_decode(proof.proof_value.strip_prefix('z').unwrap()).unwrap();
+        assert_eq!(BASE64.encode(signature), event.proof.signature);
+    }
+
+    #[test]
+    fn test_did_web_documents() {
+        let url = DidUrl::parse("did:web:example.com%3A8443:agents:a1#key-1").unwrap();
+        assert_eq!(
+            url.web_document_url().unwrap(),
+            "https://example.com:8443/agents/a1/did.json"
+        );
+        assert_eq!(
+            DidUrl::parse("did:web:example.com")
+                .unwrap()
+                .web_document_url()
+                .unwrap(),
+            "https://example.com/.well-known/did.json"
+        );
+        assert!(DidUrl::parse("did:web:example.com#").is_err());
+        assert!(DidUrl::parse("key:z6Mk").is_err());
+
+        let x = BASE64URL.encode(
+            SigningKey::from_bytes(&[7u8; 32])
+                .verifying_key()
+                .as_bytes(),
+        );
+        let document: DidDocument = serde_json::from_value(serde_json::json!({
+            "id": "did:web:example.com%3A8443:agents:a1",
+            "verificationMethod": [{
+                "id": "#key-1",
+                "type": "JsonWebKey2020",
+                "publicKeyJwk": {"kty": "OKP", "crv": "Ed25519", "x": x},
+            }],
+        }))
+        .unwrap();
+        assert_eq!(
+            document.key(&url).unwrap().algorithm,
+            SignatureAlgorithm::Ed25519
+        );
+        let other = DidUrl::parse("did:web:example.com%3A8443:agents:a1#key-2").unwrap();
+        assert!(matches!(
+            document.key(&other),
+            Err(DidError::UnknownMethod(_))
+        ));
+    }
+}
@@ -0,0 +1,288 @@
+//! Per-agent and per-action type ingest metrics.
+//!
+//! The ingest counters are labeled by tenant only. With
+//! METRIC_DIMENSIONS_ENABLED, each event's outcome is also counted under its
+//! agent and action type. To keep the number of series bounded, only the
+//! busiest agents and action types are named; the rest are counted as
+//! `other`. Volumes are tracked with the space-saving algorithm and ranked
+//! every minute, halving the counts at each ranking so the ranking follows
+//! recent traffic. An agent that falls out of the ranking keeps its series,
+//! which stop growing.
+
+use metrics::{counter, histogram};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::protocol::{BatchIngestResponse, SingleIngestResponse};
+
+use crate::FactoEvent;
+
+/// Label of the values outside the ranking
+pub const OTHER: &str = "other";
+
+/// How often the named values are re-ranked
+const RANK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Values counted per named slot, so a newcomer can climb the ranking
+const CANDIDATES_PER_SLOT: usize = 4;
+
+/// The busiest values of one label, by approximate recent volume
+struct Ranking {
+    slots: usize,
+    counts: HashMap<String, u64>,
+    named: HashSet<String>,
+    ranked_at: Instant,
+}
+
+impl Ranking {
+    fn new(slots: usize) -> Self {
+        Self {
+            slots,
+            counts: HashMap::new(),
+            named: HashSet::new(),
+            ranked_at: Instant::now(),
+        }
+    }
+
+    /// Count an occurrence of `value` and return the label to use for it.
+    /// Free slots are handed out on first sight; once full, they change
+    /// hands only when the values are re-ranked.
+    fn label(&mut self, value: &str, now: Instant) -> String {
+        if now.duration_since(self.ranked_at) >= RANK_INTERVAL {
+            self.rank(now);
+        }
+        self.observe(value);
+        if self.named.contains(value) {
+            return value.to_string();
+        }
+        if self.named.len() < self.slots {
+            self.named.insert(value.to_string());
+            return value.to_string();
+        }
+        OTHER.to_string()
+    }
+
+    fn observe(&mut self, value: &str) {
+        if let Some(count) = self.counts.get_mut(value) {
+            *count += 1;
+            return;
+        }
+        let mut count = 1;
+        if self.counts.len() >= self.slots.max(1) * CANDIDATES_PER_SLOT {
+            // The newcomer takes over the least counted value's count
+            if let Some((evicted, least)) = self
+                .counts
+                .iter()
+                .min_by_key(|(_, count)| **count)
+                .map(|(value, count)| (value.clone(), *count))
+            {
+                self.counts.remove(&evicted);
+                count += least;
+            }
+        }
+        self.counts.insert(value.to_string(), count);
+    }
+
+    /// Name the busiest values and decay the counts
+    fn rank(&mut self, now: Instant) {
+        let mut ranked: Vec<(&String, &u64)> = self.counts.iter().collect();
+        ranked.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        self.named = ranked
+            .into_iter()
+            .take(self.slots)
+            .map(|(value, _)| value.clone())
+            .collect();
+        self.counts.retain(|_, count| {
+            *count /= 2;
+            *count > 0
+        });
+        self.ranked_at = now;
+    }
+}
+
+/// What identifies an event in the dimensioned metrics and usage, taken
+/// before it is ingested
+pub struct EventLabels {
+    facto_id: String,
+    pub agent_id: String,
+    action_type: String,
+    /// Size of the event as published
+    pub bytes: u64,
+}
+
+impl EventLabels {
+    pub fn of(event: &FactoEvent) -> Self {
+        Self {
+            facto_id: event.facto_id.clone(),
+            agent_id: event.agent_id.clone(),
+            action_type: event.action_type.clone(),
+            bytes: crate::event_size(event),
+        }
+    }
+}
+
+/// What became of an event of an ingest request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Accepted,
+    Duplicate,
+    /// Refused, with the code of the reason
+    Rejected(&'static str),
+}
+
+impl Outcome {
+    pub fn of_single(response: &SingleIngestResponse) -> Self {
+        match (&response.error, response.duplicate) {
+            (Some(error), _) => Outcome::Rejected(error.code.as_str()),
+            (None, true) => Outcome::Duplicate,
+            (None, false) => Outcome::Accepted,
+        }
+    }
+
+    /// Outcomes of a batch's events, in order
+    pub fn of_batch(events: &[EventLabels], response: &BatchIngestResponse) -> Vec<Self> {
+        // A facto_id repeated in the batch has one outcome per occurrence
+        let mut rejected: HashMap<&str, Vec<&'static str>> = HashMap::new();
+        for rejection in response.rejected.iter().rev() {
+            rejected
+                .entry(rejection.facto_id.as_str())
+                .or_default()
+                .push(rejection.error.code.as_str());
+        }
+        let mut duplicates: HashMap<&str, usize> = HashMap::new();
+        for facto_id in &response.duplicates {
+            *duplicates.entry(facto_id.as_str()).or_default() += 1;
+        }
+
+        events
+            .iter()
+            .map(|event| {
+                let facto_id = event.facto_id.as_str();
+                if let Some(reason) = rejected.get_mut(facto_id).and_then(Vec::pop) {
+                    Outcome::Rejected(reason)
+                } else if let Some(remaining) = duplicates.get_mut(facto_id).filter(|n| **n > 0) {
+                    *remaining -= 1;
+                    Outcome::Duplicate
+                } else {
+                    Outcome::Accepted
+                }
+            })
+            .collect()
+    }
+}
+
+/// Counts ingest outcomes per agent and action type, naming only the top
+/// agents and action types
+pub struct MetricDimensions {
+    agents: Mutex<Ranking>,
+    action_types: Mutex<Ranking>,
+}
+
+impl MetricDimensions {
+    pub fn new(top_agents: usize, top_action_types: usize) -> Self {
+        Self {
+            agents: Mutex::new(Ranking::new(top_agents)),
+            action_types: Mutex::new(Ranking::new(top_action_types)),
+        }
+    }
+
+    /// Count one event's outcome, returning its agent label
+    fn count(&self, tenant: &str, event: &EventLabels, outcome: Outcome) -> String {
+        let now = Instant::now();
+        let agent_id = self.agents.lock().unwrap().label(&event.agent_id, now);
+        let action_type = self
+            .action_types
+            .lock()
+            .unwrap()
+            .label(&event.action_type, now);
+
+        let outcome = match outcome {
+            Outcome::Accepted => "accepted",
+            Outcome::Duplicate => "duplicate",
+            Outcome::Rejected(reason) => {
+                counter!(
+                    "facto_agent_rejected_total",
+                    "tenant" => tenant.to_string(),
+                    "agent_id" => agent_id.clone(),
+                    "action_type" => action_type.clone(),
+                    "reason" => reason
+                )
+                .increment(1);
+                "rejected"
+            }
+        };
+        counter!(
+            "facto_agent_events_total",
+            "tenant" => tenant.to_string(),
+            "agent_id" => agent_id.clone(),
+            "action_type" => action_type,
+            "outcome" => outcome
+        )
+        .increment(1);
+        agent_id
+    }
+
+    fn record_duration(&self, tenant: &str, agent_id: String, elapsed: Duration) {
+        histogram!(
+            "facto_agent_ingest_duration_seconds",
+            "tenant" => tenant.to_string(),
+            "agent_id" => agent_id
+        )
+        .record(elapsed.as_secs_f64());
+    }
+
+    /// Count the outcome of a single-event request
+    pub fn record_single(
+        &self,
+        tenant: &str,
+        event: &EventLabels,
+        outcome: Outcome,
+        elapsed: Duration,
+    ) {
+        let agent_id = self.count(tenant, event, outcome);
+        self.record_duration(tenant, agent_id, elapsed);
+    }
+
+    /// Count the outcome of each event of a batch request, and the request
+    /// duration once for each agent in it
+    pub fn record_batch(
+        &self,
+        tenant: &str,
+        events: &[EventLabels],
+        outcomes: &[Outcome],
+        elapsed: Duration,
+    ) {
+        let mut agents = HashSet::new();
+        for (event, outcome) in events.iter().zip(outcomes) {
+            agents.insert(self.count(tenant, event, *outcome));
+        }
+        for agent_id in agents {
+            self.record_duration(tenant, agent_id, elapsed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranking_names_busiest_values() {
+        let mut ranking = Ranking::new(2);
+        let start = ranking.ranked_at;
+
+        assert_eq!(ranking.label("a", start), "a");
+        assert_eq!(ranking.label("b", start), "b");
+        for _ in 0..10 {
+            assert_eq!(ranking.label("c", start), OTHER);
+        }
+        assert_eq!(ranking.label("a", start), "a");
+
+        // Re-ranking hands b's slot to the busier c
+        let later = start + RANK_INTERVAL;
+        assert_eq!(ranking.label("c", later), "c");
+        assert_eq!(ranking.label("a", later), "a");
+        assert_eq!(ranking.label("b", later), OTHER);
+    }
+}
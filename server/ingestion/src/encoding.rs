@@ -0,0 +1,273 @@
+//! CBOR and MessagePack encodings of request and response bodies.
+//!
+//! Binary bodies are deserialized with `ciborium` and `rmp-serde` into the
+//! same types the JSON endpoints parse into, so an event's canonical form,
+//! hash and signature do not depend on how it was sent. Values the JSON
+//! data model cannot hold, such as byte strings, CBOR tags and MessagePack
+//! extension types, are refused by the target types.
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Nesting limit of decoded bodies, the same as `serde_json`'s
+const MAX_DEPTH: usize = 128;
+
+/// Tag a CBOR document may start with to mark itself as CBOR (55799)
+const CBOR_SELF_DESCRIBED: &[u8] = &[0xd9, 0xd9, 0xf7];
+
+/// A body encoding the ingest endpoints accept and answer in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Json,
+    Cbor,
+    MsgPack,
+}
+
+/// Content types of the supported encodings, JSON first
+pub const CONTENT_TYPES: &[&str] = &[
+    "application/json",
+    "application/cbor",
+    "application/msgpack",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DecodeError {
+    /// The body is not a well-formed value of its encoding
+    #[error("{0}")]
+    Malformed(String),
+    /// The body is well formed but does not have the expected shape
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0} bytes after the end of the value")]
+    TrailingBytes(usize),
+}
+
+impl DecodeError {
+    fn truncated_or(e: &std::io::Error) -> Self {
+        if e.kind() == std::io::ErrorKind::UnexpectedEof {
+            DecodeError::Malformed("body ends in the middle of a value".to_string())
+        } else {
+            DecodeError::Malformed(e.to_string())
+        }
+    }
+
+    fn too_deep() -> Self {
+        DecodeError::Malformed(format!("values nest more than {} levels deep", MAX_DEPTH))
+    }
+}
+
+impl From<serde_json::Error> for DecodeError {
+    fn from(e: serde_json::Error) -> Self {
+        match e.classify() {
+            serde_json::error::Category::Data => DecodeError::Invalid(e.to_string()),
+            _ => DecodeError::Malformed(e.to_string()),
+        }
+    }
+}
+
+impl From<ciborium::de::Error<std::io::Error>> for DecodeError {
+    fn from(e: ciborium::de::Error<std::io::Error>) -> Self {
+        use ciborium::de::Error;
+        match e {
+            Error::Io(e) => DecodeError::truncated_or(&e),
+            Error::Syntax(offset) => {
+                DecodeError::Malformed(format!("malformed CBOR at byte {}", offset))
+            }
+            Error::Semantic(_, message) => DecodeError::Invalid(message),
+            Error::RecursionLimitExceeded => DecodeError::too_deep(),
+        }
+    }
+}
+
+impl From<rmp_serde::decode::Error> for DecodeError {
+    fn from(e: rmp_serde::decode::Error) -> Self {
+        use rmp_serde::decode::Error;
+        match e {
+            Error::InvalidMarkerRead(e) | Error::InvalidDataRead(e) => {
+                DecodeError::truncated_or(&e)
+            }
+            Error::DepthLimitExceeded => DecodeError::too_deep(),
+            Error::Utf8Error(e) => DecodeError::Malformed(e.to_string()),
+            e => DecodeError::Invalid(e.to_string()),
+        }
+    }
+}
+
+impl Encoding {
+    /// The encoding named by a `Content-Type` or `Accept` media type,
+    /// ignoring parameters
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        let essence = media_type.split(';').next()?.trim().to_ascii_lowercase();
+        match essence.as_str() {
+            "application/json" => Some(Encoding::Json),
+            "application/cbor" => Some(Encoding::Cbor),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Encoding::MsgPack)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Json => "application/json",
+            Encoding::Cbor => "application/cbor",
+            Encoding::MsgPack => "application/msgpack",
+        }
+    }
+
+    /// Deserialize a body holding exactly one value
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, DecodeError> {
+        let mut rest = bytes;
+        let value = match self {
+            Encoding::Json => return Ok(serde_json::from_slice(bytes)?),
+            Encoding::Cbor => {
+                rest = rest.strip_prefix(CBOR_SELF_DESCRIBED).unwrap_or(rest);
+                ciborium::de::from_reader_with_recursion_limit(&mut rest, MAX_DEPTH)?
+            }
+            Encoding::MsgPack => {
+                let mut deserializer = rmp_serde::Deserializer::new(&mut rest);
+                deserializer.set_max_depth(MAX_DEPTH);
+                T::deserialize(&mut deserializer)?
+            }
+        };
+        match rest.len() {
+            0 => Ok(value),
+            trailing => Err(DecodeError::TrailingBytes(trailing)),
+        }
+    }
+
+    pub fn encode(self, value: &Value) -> Vec<u8> {
+        match self {
+            Encoding::Json => serde_json::to_vec(value),
+            Encoding::Cbor => {
+                let mut out = Vec::new();
+                ciborium::ser::into_writer(value, &mut out)
+                    .map(|()| out)
+                    .map_err(serde::ser::Error::custom)
+            }
+            Encoding::MsgPack => rmp_serde::to_vec_named(value).map_err(serde::ser::Error::custom),
+        }
+        .expect("JSON values serialize")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::build_canonical_form;
+    use crate::testing::test_event;
+    use crate::FactoEvent;
+
+    fn event_value() -> Value {
+        let mut event = serde_json::to_value(test_event()).unwrap();
+        event["input_data"] = serde_json::json!({
+            "text": "x".repeat(300),
+            "values": [0, 1, 255, 65_536, -1, -33, -129, -40_000, i64::MIN, u64::MAX, 0.5, -2.25],
+            "nested": {"empty": [], "null": null, "flag": true},
+        });
+        event
+    }
+
+    #[test]
+    fn test_events_from_the_crates_encoders() {
+        let value = event_value();
+        let json: FactoEvent = serde_json::from_value(value.clone()).unwrap();
+
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(&value, &mut cbor).unwrap();
+        let msgpack = rmp_serde::to_vec_named(&value).unwrap();
+
+        for (encoding, bytes) in [(Encoding::Cbor, cbor), (Encoding::MsgPack, msgpack)] {
+            let event: FactoEvent = encoding.decode(&bytes).unwrap();
+            assert_eq!(
+                serde_json::to_value(&event).unwrap(),
+                serde_json::to_value(&json).unwrap(),
+                "{:?}",
+                encoding
+            );
+            assert_eq!(
+                build_canonical_form(&event).unwrap(),
+                build_canonical_form(&json).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_values_round_trip_unchanged() {
+        let value = event_value();
+        for encoding in [Encoding::Cbor, Encoding::MsgPack, Encoding::Json] {
+            let bytes = encoding.encode(&value);
+            assert_eq!(
+                encoding.decode::<Value>(&bytes).unwrap(),
+                value,
+                "{:?}",
+                encoding
+            );
+        }
+    }
+
+    #[test]
+    fn test_cbor_edge_cases() {
+        // RFC 8949 examples: half-float 1.5, indefinite ["a"] and {"a": 1},
+        // a text string in chunks, and a self-described document
+        let cases: &[(&[u8], Value)] = &[
+            (&[0xf9, 0x3e, 0x00], serde_json::json!(1.5)),
+            (&[0x9f, 0x61, 0x61, 0xff], serde_json::json!(["a"])),
+            (&[0xbf, 0x61, 0x61, 0x01, 0xff], serde_json::json!({"a": 1})),
+            (
+                &[0x7f, 0x61, 0x61, 0x61, 0x62, 0xff],
+                serde_json::json!("ab"),
+            ),
+            (&[0xd9, 0xd9, 0xf7, 0x01], serde_json::json!(1)),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(&Encoding::Cbor.decode::<Value>(bytes).unwrap(), expected);
+        }
+
+        // Byte strings, tags and non-string keys have no JSON equivalent
+        assert!(matches!(
+            Encoding::Cbor.decode::<Value>(&[0x41, 0x00]),
+            Err(DecodeError::Invalid(_))
+        ));
+        assert!(matches!(
+            Encoding::Cbor.decode::<Value>(&[0xc1, 0x1a, 0x51, 0x4b, 0x67, 0xb0]),
+            Err(DecodeError::Invalid(_))
+        ));
+        assert!(matches!(
+            Encoding::Cbor.decode::<Value>(&[0xa1, 0x01, 0x02]),
+            Err(DecodeError::Invalid(_))
+        ));
+        assert!(matches!(
+            Encoding::Cbor.decode::<Value>(&[0x81; MAX_DEPTH + 2]),
+            Err(DecodeError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_msgpack_edge_cases() {
+        // fixext 1 and bin 8 have no JSON equivalent
+        assert!(matches!(
+            Encoding::MsgPack.decode::<Value>(&[0xd4, 0x01, 0x00]),
+            Err(DecodeError::Invalid(_))
+        ));
+        assert!(matches!(
+            Encoding::MsgPack.decode::<Value>(&[0xc4, 0x01, 0x00]),
+            Err(DecodeError::Invalid(_))
+        ));
+        assert_eq!(
+            Encoding::MsgPack.decode::<Value>(&[0x92, 0x01]),
+            Err(DecodeError::Malformed(
+                "body ends in the middle of a value".to_string()
+            ))
+        );
+        assert_eq!(
+            Encoding::MsgPack.decode::<Value>(&[0x01, 0x02]),
+            Err(DecodeError::TrailingBytes(1))
+        );
+        assert!(matches!(
+            Encoding::MsgPack.decode::<Value>(&[0x91; MAX_DEPTH + 2]),
+            Err(DecodeError::Malformed(_))
+        ));
+    }
+}
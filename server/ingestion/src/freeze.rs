@@ -0,0 +1,412 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::PathBuf, sync::RwLock};
+use tracing::info;
+
+use crate::chain::ChainHead;
+use crate::scoped_id;
+use crate::store::JsonFile;
+use crate::verification::{now_nanos, ServerSigner};
+
+/// Number of distinct admins that must approve lifting a freeze
+pub const REQUIRED_UNFREEZE_APPROVALS: usize = 2;
+
+/// NATS subject for signed session control records
+pub const SESSION_CONTROL_SUBJECT: &str = "facto.control.sessions";
+
+/// NATS subject notifications for an agent's owner are published on
+pub fn notification_subject(agent_id: &str) -> String {
+    format!("facto.notifications.{}", agent_id)
+}
+
+// ============================================================================
+// Records
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SealKind {
+    Freeze,
+    Unfreeze,
+}
+
+/// Server-signed record closing (or reopening) a session's chain. A freeze
+/// seal names the last accepted event hash, so any later event claiming to
+/// extend the chain past the seal is evidently out of band.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSeal {
+    pub kind: SealKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub session_id: String,
+    pub agent_id: Option<String>,
+    pub head_hash: Option<String>,
+    pub reason: String,
+    pub actors: Vec<String>,
+    pub at: i64,
+    pub expires_at: Option<i64>,
+    pub signer_public_key: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub signature: String,
+}
+
+impl SessionSeal {
+    /// The bytes covered by the seal signature: the sorted-key JSON of the
+    /// seal with `signature` omitted
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let unsigned = Self {
+            signature: String::new(),
+            ..self.clone()
+        };
+        let value = serde_json::to_value(&unsigned).unwrap_or_default();
+        serde_json::to_vec(&value).unwrap_or_default()
+    }
+
+    fn sign(mut self, signer: &ServerSigner) -> Self {
+        self.signer_public_key = signer.public_key_base64();
+        self.signature = signer.sign_base64(&self.signing_payload());
+        self
+    }
+}
+
+/// An active or expired freeze of a session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionFreeze {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub session_id: String,
+    pub agent_id: Option<String>,
+    pub reason: String,
+    pub frozen_by: String,
+    pub frozen_at: i64,
+    pub expires_at: Option<i64>,
+    pub seal: SessionSeal,
+    #[serde(default)]
+    pub unfreeze_approvals: Vec<String>,
+}
+
+impl SessionFreeze {
+    pub fn is_active(&self, now: i64) -> bool {
+        self.expires_at.is_none_or(|expires_at| now < expires_at)
+    }
+}
+
+/// Notification sent to an agent's owner when its session is frozen or unfrozen
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionNotification<'a> {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub session_id: &'a str,
+    pub reason: &'a str,
+    pub actors: &'a [String],
+    pub at: i64,
+    pub expires_at: Option<i64>,
+}
+
+impl<'a> SessionNotification<'a> {
+    pub fn for_seal(seal: &'a SessionSeal) -> Self {
+        Self {
+            kind: match seal.kind {
+                SealKind::Freeze => "session_frozen",
+                SealKind::Unfreeze => "session_unfrozen",
+            },
+            session_id: &seal.session_id,
+            reason: &seal.reason,
+            actors: &seal.actors,
+            at: seal.at,
+            expires_at: seal.expires_at,
+        }
+    }
+}
+
+/// Admin request to freeze a session
+#[derive(Debug, Clone, Deserialize)]
+pub struct FreezeRequest {
+    pub reason: String,
+    /// Agent to notify; defaults to the agent of the session's latest event
+    pub agent_id: Option<String>,
+    /// Lift the freeze automatically after this many seconds
+    pub duration_secs: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum UnfreezeOutcome {
+    /// More approvals are needed; lists the admins who approved so far
+    Pending(Vec<String>),
+    /// The freeze was lifted
+    Unfrozen(SessionSeal),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FreezeError {
+    #[error("session is already frozen")]
+    AlreadyFrozen,
+    #[error("session is not frozen")]
+    NotFrozen,
+    #[error("{0} has already approved this unfreeze")]
+    AlreadyApproved(String),
+    #[error("failed to persist session freezes: {0}")]
+    Persistence(String),
+}
+
+// ============================================================================
+// Freeze Registry
+// ============================================================================
+
+/// Sessions currently closed to ingestion, persisted across restarts and
+/// keyed by the tenant-scoped session id
+pub struct SessionFreezes {
+    freezes: RwLock<HashMap<String, SessionFreeze>>,
+    store: JsonFile,
+}
+
+impl SessionFreezes {
+    pub fn new(path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let store = JsonFile::new(path);
+        let freezes: HashMap<String, SessionFreeze> = store.load()?;
+        info!("Loaded {} session freezes", freezes.len());
+
+        Ok(Self {
+            freezes: RwLock::new(freezes),
+            store,
+        })
+    }
+
+    pub fn is_frozen(&self, tenant_id: Option<&str>, session_id: &str) -> bool {
+        let freezes = self.freezes.read().unwrap();
+        freezes
+            .get(&scoped_id(tenant_id, session_id))
+            .is_some_and(|f| f.is_active(now_nanos()))
+    }
+
+    pub fn get(&self, tenant_id: Option<&str>, session_id: &str) -> Option<SessionFreeze> {
+        self.freezes
+            .read()
+            .unwrap()
+            .get(&scoped_id(tenant_id, session_id))
+            .cloned()
+    }
+
+    /// Freeze a session, sealing its chain at `head`
+    pub fn freeze(
+        &self,
+        tenant_id: Option<&str>,
+        session_id: &str,
+        request: FreezeRequest,
+        admin: &str,
+        head: Option<ChainHead>,
+        signer: &ServerSigner,
+    ) -> Result<SessionFreeze, FreezeError> {
+        let key = scoped_id(tenant_id, session_id);
+        let mut freezes = self.freezes.write().unwrap();
+        let now = now_nanos();
+        if freezes.get(&key).is_some_and(|f| f.is_active(now)) {
+            return Err(FreezeError::AlreadyFrozen);
+        }
+
+        let FreezeRequest {
+            reason,
+            agent_id,
+            duration_secs,
+        } = request;
+        let agent_id = agent_id.or_else(|| head.as_ref().map(|h| h.agent_id.clone()));
+        let expires_at = duration_secs.map(|secs| now + (secs as i64) * 1_000_000_000);
+        let seal = SessionSeal {
+            kind: SealKind::Freeze,
+            tenant_id: tenant_id.map(str::to_string),
+            session_id: session_id.to_string(),
+            agent_id: agent_id.clone(),
+            head_hash: head.map(|h| h.event_hash),
+            reason: reason.clone(),
+            actors: vec![admin.to_string()],
+            at: now,
+            expires_at,
+            signer_public_key: String::new(),
+            signature: String::new(),
+        }
+        .sign(signer);
+
+        let freeze = SessionFreeze {
+            tenant_id: tenant_id.map(str::to_string),
+            session_id: session_id.to_string(),
+            agent_id,
+            reason,
+            frozen_by: admin.to_string(),
+            frozen_at: now,
+            expires_at,
+            seal,
+            unfreeze_approvals: Vec::new(),
+        };
+
+        let mut updated = freezes.clone();
+        updated.insert(key, freeze.clone());
+        self.commit(&mut freezes, updated)?;
+
+        Ok(freeze)
+    }
+
+    /// Record an admin's approval to lift a freeze. The freeze is lifted once
+    /// [`REQUIRED_UNFREEZE_APPROVALS`] distinct admins have approved.
+    pub fn approve_unfreeze(
+        &self,
+        tenant_id: Option<&str>,
+        session_id: &str,
+        admin: &str,
+        signer: &ServerSigner,
+    ) -> Result<UnfreezeOutcome, FreezeError> {
+        let key = scoped_id(tenant_id, session_id);
+        let mut freezes = self.freezes.write().unwrap();
+        let now = now_nanos();
+        let freeze = match freezes.get(&key) {
+            Some(freeze) if freeze.is_active(now) => freeze.clone(),
+            _ => return Err(FreezeError::NotFrozen),
+        };
+        if freeze.unfreeze_approvals.iter().any(|a| a == admin) {
+            return Err(FreezeError::AlreadyApproved(admin.to_string()));
+        }
+
+        let mut approvals = freeze.unfreeze_approvals.clone();
+        approvals.push(admin.to_string());
+
+        let mut updated = freezes.clone();
+        if approvals.len() < REQUIRED_UNFREEZE_APPROVALS {
+            updated.insert(
+                key,
+                SessionFreeze {
+                    unfreeze_approvals: approvals.clone(),
+                    ..freeze
+                },
+            );
+            self.commit(&mut freezes, updated)?;
+            return Ok(UnfreezeOutcome::Pending(approvals));
+        }
+
+        let seal = SessionSeal {
+            kind: SealKind::Unfreeze,
+            tenant_id: freeze.tenant_id.clone(),
+            session_id: session_id.to_string(),
+            agent_id: freeze.agent_id.clone(),
+            head_hash: freeze.seal.head_hash.clone(),
+            reason: freeze.reason.clone(),
+            actors: approvals,
+            at: now,
+            expires_at: None,
+            signer_public_key: String::new(),
+            signature: String::new(),
+        }
+        .sign(signer);
+
+        updated.remove(&key);
+        self.commit(&mut freezes, updated)?;
+        Ok(UnfreezeOutcome::Unfrozen(seal))
+    }
+
+    fn commit(
+        &self,
+        freezes: &mut HashMap<String, SessionFreeze>,
+        updated: HashMap<String, SessionFreeze>,
+    ) -> Result<(), FreezeError> {
+        self.store
+            .save(&updated)
+            .map_err(|e| FreezeError::Persistence(e.to_string()))?;
+        *freezes = updated;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+
+    fn signer() -> ServerSigner {
+        ServerSigner::new("test".to_string(), SigningKey::from_bytes(&[4u8; 32]))
+    }
+
+    fn freeze(
+        freezes: &SessionFreezes,
+        tenant_id: Option<&str>,
+        duration_secs: Option<u64>,
+    ) -> SessionFreeze {
+        freezes
+            .freeze(
+                tenant_id,
+                "session-1",
+                FreezeRequest {
+                    reason: "dispute #42".to_string(),
+                    agent_id: Some("agent-1".to_string()),
+                    duration_secs,
+                },
+                "alice",
+                None,
+                &signer(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_unfreeze_requires_two_admins() {
+        let freezes = SessionFreezes::new(None).unwrap();
+        let signer = signer();
+        freeze(&freezes, None, None);
+        assert!(freezes.is_frozen(None, "session-1"));
+
+        assert!(matches!(
+            freezes.approve_unfreeze(None, "session-1", "alice", &signer),
+            Ok(UnfreezeOutcome::Pending(_))
+        ));
+        assert!(matches!(
+            freezes.approve_unfreeze(None, "session-1", "alice", &signer),
+            Err(FreezeError::AlreadyApproved(_))
+        ));
+        assert!(freezes.is_frozen(None, "session-1"));
+
+        match freezes.approve_unfreeze(None, "session-1", "bob", &signer) {
+            Ok(UnfreezeOutcome::Unfrozen(seal)) => {
+                assert_eq!(seal.kind, SealKind::Unfreeze);
+                assert_eq!(seal.actors, vec!["alice", "bob"]);
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        assert!(!freezes.is_frozen(None, "session-1"));
+    }
+
+    #[test]
+    fn test_freeze_expires() {
+        let freezes = SessionFreezes::new(None).unwrap();
+        freeze(&freezes, None, Some(0));
+        assert!(!freezes.is_frozen(None, "session-1"));
+    }
+
+    #[test]
+    fn test_double_freeze_is_rejected() {
+        let freezes = SessionFreezes::new(None).unwrap();
+        freeze(&freezes, None, None);
+        assert!(matches!(
+            freezes.freeze(
+                None,
+                "session-1",
+                FreezeRequest {
+                    reason: "again".to_string(),
+                    agent_id: None,
+                    duration_secs: None,
+                },
+                "bob",
+                None,
+                &signer()
+            ),
+            Err(FreezeError::AlreadyFrozen)
+        ));
+    }
+
+    #[test]
+    fn test_freeze_is_scoped_to_tenant() {
+        let freezes = SessionFreezes::new(None).unwrap();
+        let frozen = freeze(&freezes, Some("acme"), None);
+        assert_eq!(frozen.seal.tenant_id.as_deref(), Some("acme"));
+
+        assert!(freezes.is_frozen(Some("acme"), "session-1"));
+        assert!(!freezes.is_frozen(Some("globex"), "session-1"));
+        assert!(!freezes.is_frozen(None, "session-1"));
+        assert!(freezes.get(Some("globex"), "session-1").is_none());
+    }
+}
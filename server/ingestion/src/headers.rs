@@ -0,0 +1,276 @@
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::verification::{ServerEnvelope, ENVELOPE_HEADER};
+use crate::FactoEvent;
+
+/// Default for `NATS_PROPAGATE_HEADERS`
+pub const DEFAULT_PROPAGATED_HEADERS: &str = "traceparent,tracestate,x-request-id";
+
+/// Most headers one tenant may configure
+pub const MAX_TENANT_HEADERS: usize = 32;
+
+/// Header prefixes NATS and the server set themselves
+const RESERVED_PREFIXES: [&str; 2] = ["nats-", "facto-"];
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum HeaderError {
+    #[error("invalid header name: {0}")]
+    InvalidName(String),
+    #[error("invalid value for header {0}")]
+    InvalidValue(String),
+    #[error("unknown header source: {0}")]
+    UnknownSource(String),
+    #[error("at most {MAX_TENANT_HEADERS} headers can be configured")]
+    TooMany,
+}
+
+// ============================================================================
+// Tenant Headers
+// ============================================================================
+
+/// The event field a derived header is taken from. Written as the field
+/// name, or `tag:<name>` for an execution tag.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum HeaderSource {
+    TenantId,
+    AgentId,
+    SessionId,
+    ActionType,
+    Status,
+    ModelId,
+    Tag(String),
+}
+
+impl TryFrom<String> for HeaderSource {
+    type Error = HeaderError;
+
+    fn try_from(source: String) -> Result<Self, HeaderError> {
+        Ok(match source.as_str() {
+            "tenant_id" => HeaderSource::TenantId,
+            "agent_id" => HeaderSource::AgentId,
+            "session_id" => HeaderSource::SessionId,
+            "action_type" => HeaderSource::ActionType,
+            "status" => HeaderSource::Status,
+            "model_id" => HeaderSource::ModelId,
+            _ => match source.strip_prefix("tag:") {
+                Some(tag) if !tag.is_empty() => HeaderSource::Tag(tag.to_string()),
+                _ => return Err(HeaderError::UnknownSource(source)),
+            },
+        })
+    }
+}
+
+impl From<HeaderSource> for String {
+    fn from(source: HeaderSource) -> String {
+        match source {
+            HeaderSource::TenantId => "tenant_id".to_string(),
+            HeaderSource::AgentId => "agent_id".to_string(),
+            HeaderSource::SessionId => "session_id".to_string(),
+            HeaderSource::ActionType => "action_type".to_string(),
+            HeaderSource::Status => "status".to_string(),
+            HeaderSource::ModelId => "model_id".to_string(),
+            HeaderSource::Tag(tag) => format!("tag:{}", tag),
+        }
+    }
+}
+
+impl HeaderSource {
+    fn value<'a>(&self, event: &'a FactoEvent, tenant_id: &'a str) -> Option<&'a str> {
+        match self {
+            HeaderSource::TenantId => Some(tenant_id),
+            HeaderSource::AgentId => Some(&event.agent_id),
+            HeaderSource::SessionId => Some(&event.session_id),
+            HeaderSource::ActionType => Some(&event.action_type),
+            HeaderSource::Status => Some(&event.status),
+            HeaderSource::ModelId => event.execution_meta.model_id.as_deref(),
+            HeaderSource::Tag(tag) => event.execution_meta.tags.get(tag).map(String::as_str),
+        }
+    }
+}
+
+/// Headers a tenant attaches to the messages of its events, such as a data
+/// classification or routing hints for downstream consumers
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TenantHeaders {
+    /// Set on every message
+    #[serde(default, rename = "static", skip_serializing_if = "BTreeMap::is_empty")]
+    pub fixed: BTreeMap<String, String>,
+    /// Taken from each event; left unset when the event has no such value
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub derived: BTreeMap<String, HeaderSource>,
+}
+
+impl TenantHeaders {
+    pub fn is_empty(&self) -> bool {
+        self.fixed.is_empty() && self.derived.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), HeaderError> {
+        if self.fixed.len() + self.derived.len() > MAX_TENANT_HEADERS {
+            return Err(HeaderError::TooMany);
+        }
+        for name in self.fixed.keys().chain(self.derived.keys()) {
+            let lowercase = name.to_ascii_lowercase();
+            let valid = !name.is_empty()
+                && name.len() <= 64
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                && !RESERVED_PREFIXES.iter().any(|p| lowercase.starts_with(p));
+            if !valid {
+                return Err(HeaderError::InvalidName(name.clone()));
+            }
+        }
+        for (name, value) in &self.fixed {
+            if !valid_value(value) {
+                return Err(HeaderError::InvalidValue(name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Add this tenant's headers for an event. Derived values that cannot be
+    /// sent as a header value are skipped.
+    pub fn apply(
+        &self,
+        event: &FactoEvent,
+        tenant_id: &str,
+        headers: &mut BTreeMap<String, String>,
+    ) {
+        for (name, value) in &self.fixed {
+            headers.insert(name.clone(), value.clone());
+        }
+        for (name, source) in &self.derived {
+            if let Some(value) = source.value(event, tenant_id).filter(|v| valid_value(v)) {
+                headers.insert(name.clone(), value.to_string());
+            }
+        }
+    }
+}
+
+/// Header values travel on a single line
+fn valid_value(value: &str) -> bool {
+    value.len() <= 1024 && !value.chars().any(char::is_control)
+}
+
+// ============================================================================
+// Propagation
+// ============================================================================
+
+/// HTTP request headers copied onto the messages of the request's events
+pub struct HeaderPropagation {
+    names: Vec<String>,
+}
+
+impl HeaderPropagation {
+    /// Read `NATS_PROPAGATE_HEADERS`, a comma-separated list; empty turns
+    /// propagation off
+    pub fn from_env() -> Self {
+        let names = std::env::var("NATS_PROPAGATE_HEADERS")
+            .unwrap_or_else(|_| DEFAULT_PROPAGATED_HEADERS.to_string());
+        Self::new(names.split(','))
+    }
+
+    pub fn new<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            names: names
+                .into_iter()
+                .map(|name| name.trim().to_ascii_lowercase())
+                .filter(|name| !name.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn extract(&self, request: &HeaderMap) -> BTreeMap<String, String> {
+        self.names
+            .iter()
+            .filter_map(|name| {
+                let value = request.get(name.as_str())?.to_str().ok()?;
+                valid_value(value).then(|| (name.clone(), value.to_string()))
+            })
+            .collect()
+    }
+}
+
+/// The NATS headers of an accepted event: its message headers, the facto_id
+/// as JetStream message id for stream-side dedup, and the server envelope
+pub fn nats_headers(
+    event: &FactoEvent,
+    envelope: &ServerEnvelope,
+) -> Result<async_nats::HeaderMap, serde_json::Error> {
+    let mut headers = async_nats::HeaderMap::new();
+    for (name, value) in &envelope.headers {
+        headers.insert(name.as_str(), value.as_str());
+    }
+    headers.insert(async_nats::header::NATS_MESSAGE_ID, event.facto_id.as_str());
+    headers.insert(ENVELOPE_HEADER, serde_json::to_string(envelope)?.as_str());
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_event;
+
+    #[test]
+    fn test_tenant_headers_applied() {
+        let config: TenantHeaders = serde_json::from_value(serde_json::json!({
+            "static": {"X-Data-Classification": "confidential"},
+            "derived": {"X-Tenant": "tenant_id", "X-Region": "tag:region"}
+        }))
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let mut event = test_event();
+        event
+            .execution_meta
+            .tags
+            .insert("region".to_string(), "eu-west-1".to_string());
+        let mut headers = BTreeMap::new();
+        config.apply(&event, "acme", &mut headers);
+        assert_eq!(headers["X-Data-Classification"], "confidential");
+        assert_eq!(headers["X-Tenant"], "acme");
+        assert_eq!(headers["X-Region"], "eu-west-1");
+
+        // Derived values are skipped when missing or not sendable
+        event
+            .execution_meta
+            .tags
+            .insert("region".to_string(), "eu\r\nX-Injected: 1".to_string());
+        let mut headers = BTreeMap::new();
+        config.apply(&event, "acme", &mut headers);
+        assert!(!headers.contains_key("X-Region"));
+
+        let reserved = TenantHeaders {
+            fixed: BTreeMap::from([("Nats-Msg-Id".to_string(), "x".to_string())]),
+            ..Default::default()
+        };
+        assert_eq!(
+            reserved.validate(),
+            Err(HeaderError::InvalidName("Nats-Msg-Id".to_string()))
+        );
+        assert!(serde_json::from_value::<HeaderSource>(serde_json::json!("payload")).is_err());
+    }
+
+    #[test]
+    fn test_request_headers_propagated() {
+        let propagation = HeaderPropagation::new(" traceparent, X-Request-Id ,".split(','));
+        let mut request = HeaderMap::new();
+        request.insert(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+        request.insert("x-request-id", "req-1".parse().unwrap());
+        request.insert("authorization", "Bearer secret".parse().unwrap());
+
+        let headers = propagation.extract(&request);
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers["x-request-id"], "req-1");
+        assert!(headers["traceparent"].starts_with("00-4bf9"));
+    }
+}
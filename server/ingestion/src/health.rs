@@ -0,0 +1,329 @@
+//! Dependency health checks behind `/ready`.
+//!
+//! Each check probes its dependency for real: the JetStream account and
+//! events stream, the Kafka producer, the spool's disk, the shared state
+//! buckets and the key registry's store. Checks run concurrently, each
+//! bounded by HEALTH_CHECK_TIMEOUT_MS, and a report is reused for a second
+//! so frequent probes do not load the broker. The service is ready when
+//! every required check passes and, if there are delivery checks, at least
+//! one of them does.
+
+use crate::protocol::DependencyHealth;
+use axum::async_trait;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::tenants::EVENT_STREAM;
+use crate::AppState;
+
+/// A report is reused for this long
+const REPORT_TTL: Duration = Duration::from_secs(1);
+
+/// How readiness depends on a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Requirement {
+    /// Not ready while the check fails
+    Required,
+    /// A path accepted events can take; one of them must pass
+    Delivery,
+    /// Reported only
+    Informational,
+}
+
+impl Requirement {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Requirement::Required => "required",
+            Requirement::Delivery => "delivery",
+            Requirement::Informational => "informational",
+        }
+    }
+}
+
+/// A probe of one dependency
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn requirement(&self) -> Requirement;
+
+    /// Probe the dependency, returning what was observed
+    async fn check(&self, state: &AppState) -> Result<Option<Value>, String>;
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub checks: BTreeMap<String, DependencyHealth>,
+}
+
+/// Whether checks with these outcomes make the service ready
+fn is_healthy(outcomes: &[(Requirement, bool)]) -> bool {
+    let required = outcomes
+        .iter()
+        .filter(|(requirement, _)| *requirement == Requirement::Required)
+        .all(|(_, healthy)| *healthy);
+    let mut delivery = outcomes
+        .iter()
+        .filter(|(requirement, _)| *requirement == Requirement::Delivery)
+        .peekable();
+    let delivers = delivery.peek().is_none() || delivery.any(|(_, healthy)| *healthy);
+    required && delivers
+}
+
+/// The registered health checks
+pub struct HealthChecks {
+    checks: Vec<Box<dyn HealthCheck>>,
+    timeout: Duration,
+    last: Mutex<Option<(Instant, HealthReport)>>,
+}
+
+impl HealthChecks {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            checks: Vec::new(),
+            timeout,
+            last: Mutex::new(None),
+        }
+    }
+
+    pub fn register(&mut self, check: impl HealthCheck + 'static) {
+        self.checks.push(Box::new(check));
+    }
+
+    /// Run every check, or return the report of a run in the last second.
+    /// Concurrent callers wait for the same run.
+    pub async fn run(&self, state: &AppState) -> HealthReport {
+        let mut last = self.last.lock().await;
+        if let Some((at, ref report)) = *last {
+            if at.elapsed() < REPORT_TTL {
+                return report.clone();
+            }
+        }
+
+        let outcomes = futures::future::join_all(self.checks.iter().map(|check| async move {
+            let start = Instant::now();
+            let outcome = match tokio::time::timeout(self.timeout, check.check(state)).await {
+                Ok(outcome) => outcome,
+                Err(_) => Err(format!("timed out after {:?}", self.timeout)),
+            };
+            (check, start.elapsed(), outcome)
+        }))
+        .await;
+
+        let mut checks = BTreeMap::new();
+        let mut results = Vec::with_capacity(outcomes.len());
+        for (check, elapsed, outcome) in outcomes {
+            results.push((check.requirement(), outcome.is_ok()));
+            let (details, error) = match outcome {
+                Ok(details) => (details, None),
+                Err(error) => (None, Some(error)),
+            };
+            checks.insert(
+                check.name().to_string(),
+                DependencyHealth {
+                    healthy: error.is_none(),
+                    requirement: check.requirement().code(),
+                    latency_ms: elapsed.as_millis() as u64,
+                    error,
+                    details,
+                },
+            );
+        }
+        let report = HealthReport {
+            healthy: is_healthy(&results),
+            checks,
+        };
+        *last = Some((Instant::now(), report.clone()));
+        report
+    }
+}
+
+// ============================================================================
+// Checks
+// ============================================================================
+
+/// Queries the JetStream account and the events stream: the stream must
+/// have a leader and room for new events
+pub struct JetStreamCheck {
+    pub requirement: Requirement,
+}
+
+#[async_trait]
+impl HealthCheck for JetStreamCheck {
+    fn name(&self) -> &'static str {
+        "jetstream"
+    }
+
+    fn requirement(&self) -> Requirement {
+        self.requirement
+    }
+
+    async fn check(&self, state: &AppState) -> Result<Option<Value>, String> {
+        let client = state
+            .nats_client
+            .read()
+            .await
+            .clone()
+            .filter(|c| c.connection_state() == async_nats::connection::State::Connected)
+            .ok_or("NATS is not connected")?;
+        let jetstream = async_nats::jetstream::new(client);
+
+        let account = jetstream
+            .query_account()
+            .await
+            .map_err(|e| format!("account query failed: {}", e))?;
+        if let Some(max_storage) = account.limits.max_storage.filter(|max| *max > 0) {
+            if account.storage >= max_storage as u64 {
+                return Err(format!(
+                    "account storage is exhausted ({} of {} bytes)",
+                    account.storage, max_storage
+                ));
+            }
+        }
+
+        let mut stream = jetstream
+            .get_stream(EVENT_STREAM)
+            .await
+            .map_err(|e| format!("stream {} unavailable: {}", EVENT_STREAM, e))?;
+        let info = stream
+            .info()
+            .await
+            .map_err(|e| format!("stream {} info failed: {}", EVENT_STREAM, e))?;
+        if info.cluster.as_ref().is_some_and(|c| c.leader.is_none()) {
+            return Err(format!("stream {} has no leader", EVENT_STREAM));
+        }
+        let full = (info.config.max_bytes > 0 && info.state.bytes >= info.config.max_bytes as u64)
+            || (info.config.max_messages > 0
+                && info.state.messages >= info.config.max_messages as u64);
+        if full && info.config.discard == async_nats::jetstream::stream::DiscardPolicy::New {
+            return Err(format!("stream {} is full", EVENT_STREAM));
+        }
+
+        Ok(Some(json!({
+            "stream": EVENT_STREAM,
+            "messages": info.state.messages,
+            "bytes": info.state.bytes,
+            "max_bytes": info.config.max_bytes,
+            "full": full,
+            "account_storage_bytes": account.storage,
+        })))
+    }
+}
+
+/// Whether the Kafka producer is connected
+pub struct KafkaCheck;
+
+#[async_trait]
+impl HealthCheck for KafkaCheck {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    fn requirement(&self) -> Requirement {
+        Requirement::Delivery
+    }
+
+    async fn check(&self, state: &AppState) -> Result<Option<Value>, String> {
+        match state.sink.is_connected().await {
+            true => Ok(None),
+            false => Err("Kafka is not connected".to_string()),
+        }
+    }
+}
+
+/// Writes a probe file to the spool directory and checks the spool has room
+pub struct SpoolCheck {
+    pub requirement: Requirement,
+}
+
+#[async_trait]
+impl HealthCheck for SpoolCheck {
+    fn name(&self) -> &'static str {
+        "spool"
+    }
+
+    fn requirement(&self) -> Requirement {
+        self.requirement
+    }
+
+    async fn check(&self, state: &AppState) -> Result<Option<Value>, String> {
+        let spool = state.spool.as_ref().ok_or("no spool is configured")?;
+        let used = spool.probe().await.map_err(|e| e.to_string())?;
+        Ok(Some(json!({
+            "depth": spool.depth(),
+            "used_bytes": used,
+            "max_bytes": spool.max_bytes(),
+        })))
+    }
+}
+
+/// Queries each shared state bucket. Stores fall back to local state
+/// while their bucket is unavailable.
+pub struct SharedStateCheck;
+
+#[async_trait]
+impl HealthCheck for SharedStateCheck {
+    fn name(&self) -> &'static str {
+        "shared_state"
+    }
+
+    fn requirement(&self) -> Requirement {
+        Requirement::Informational
+    }
+
+    async fn check(&self, state: &AppState) -> Result<Option<Value>, String> {
+        for bucket in &state.shared_buckets {
+            bucket.ping().await.map_err(|e| e.to_string())?;
+        }
+        let buckets: Vec<&str> = state.shared_buckets.iter().map(|b| b.name()).collect();
+        Ok(Some(json!({ "buckets": buckets })))
+    }
+}
+
+/// Checks the key registry can persist changes and, when registration is
+/// required, has keys to accept events with
+pub struct KeyRegistryCheck;
+
+#[async_trait]
+impl HealthCheck for KeyRegistryCheck {
+    fn name(&self) -> &'static str {
+        "key_registry"
+    }
+
+    fn requirement(&self) -> Requirement {
+        Requirement::Informational
+    }
+
+    async fn check(&self, state: &AppState) -> Result<Option<Value>, String> {
+        let registry = &state.key_registry;
+        registry
+            .check_store()
+            .map_err(|e| format!("registry store is not writable: {}", e))?;
+        let agents = registry.agent_count();
+        if registry.requires_registration() && agents == 0 {
+            return Err("registration is required but no keys are registered".to_string());
+        }
+        Ok(Some(json!({
+            "version": registry.current().version,
+            "agents": agents,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_delivery_path_suffices() {
+        use Requirement::*;
+
+        assert!(is_healthy(&[(Delivery, false), (Delivery, true)]));
+        assert!(!is_healthy(&[(Delivery, false), (Informational, true)]));
+        assert!(!is_healthy(&[(Delivery, true), (Required, false)]));
+        assert!(is_healthy(&[(Informational, false)]));
+    }
+}
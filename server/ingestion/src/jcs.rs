@@ -0,0 +1,175 @@
+//! RFC 8785 JSON Canonicalization Scheme (JCS).
+//!
+//! Object members are sorted by their UTF-16 code units, numbers are
+//! written the way ECMAScript's `Number.prototype.toString` writes them and
+//! strings use the minimal JSON escaping, so any language with a JCS library
+//! produces the same bytes.
+
+use serde_json::{Number, Value};
+use std::fmt::Write as _;
+
+/// Largest integer an IEEE 754 double holds exactly
+const MAX_SAFE_INTEGER: u64 = 1 << 53;
+
+/// The canonical serialization of `value`
+pub fn to_string(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(value, &mut out);
+    out
+}
+
+fn write_value(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => write_number(n, out),
+        Value::String(s) => write_string(s, out),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(members) => {
+            let mut members: Vec<(&String, &Value)> = members.iter().collect();
+            members.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+
+            out.push('{');
+            for (i, (key, value)) in members.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(key, out);
+                out.push(':');
+                write_value(value, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// serde_json escapes only `"`, `\` and control characters, with the short
+/// forms and lowercase `\u00xx` that RFC 8785 prescribes
+fn write_string(s: &str, out: &mut String) {
+    out.push_str(&serde_json::to_string(s).unwrap_or_default());
+}
+
+/// Integers a double holds exactly are written as is; every other number is
+/// treated as the double nearest to it, as I-JSON requires
+fn write_number(n: &Number, out: &mut String) {
+    if let Some(u) = n.as_u64().filter(|u| *u <= MAX_SAFE_INTEGER) {
+        let _ = write!(out, "{}", u);
+    } else if let Some(i) = n.as_i64().filter(|i| i.unsigned_abs() <= MAX_SAFE_INTEGER) {
+        let _ = write!(out, "{}", i);
+    } else {
+        write_double(n.as_f64().unwrap_or_default(), out);
+    }
+}
+
+/// ECMAScript Number-to-String (ECMA-262 §7.1.12.1) of a finite double
+fn write_double(value: f64, out: &mut String) {
+    if value == 0.0 {
+        out.push('0');
+        return;
+    }
+    if value < 0.0 {
+        out.push('-');
+    }
+
+    // `{:e}` gives the shortest digits that round-trip, as ECMAScript does
+    let scientific = format!("{:e}", value.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    let n = exponent.parse::<i32>().unwrap_or_default() + 1;
+
+    if k <= n && n <= 21 {
+        out.push_str(&digits);
+        out.extend(std::iter::repeat_n('0', (n - k) as usize));
+    } else if 0 < n && n <= 21 {
+        out.push_str(&digits[..n as usize]);
+        out.push('.');
+        out.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        out.push_str("0.");
+        out.extend(std::iter::repeat_n('0', -n as usize));
+        out.push_str(&digits);
+    } else {
+        out.push_str(&digits[..1]);
+        if k > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        let _ = write!(out, "e{}{}", if n > 0 { "+" } else { "-" }, (n - 1).abs());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(json: &str) -> String {
+        to_string(&serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn test_rfc8785_examples() {
+        assert_eq!(
+            canonical(
+                r#"{"numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+                    "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+                    "literals": [null, true, false]}"#
+            ),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+
+        // Sorted by UTF-16 code units, which puts the emoji's surrogates
+        // before U+FB33
+        assert_eq!(
+            canonical(
+                r#"{"\u20ac": 1, "\r": 2, "\ufb33": 3, "1": 4, "\ud83d\ude00": 5, "\u0080": 6, "\u00f6": 7}"#
+            ),
+            "{\"\\r\":2,\"1\":4,\"\u{80}\":6,\"ö\":7,\"€\":1,\"😀\":5,\"\u{fb33}\":3}"
+        );
+    }
+
+    /// The vectors the Go services are tested against too
+    #[test]
+    fn test_shared_vectors() {
+        let vectors: Vec<Value> =
+            serde_json::from_str(include_str!("../../../tests/vectors/jcs.json")).unwrap();
+        for vector in vectors {
+            assert_eq!(
+                canonical(vector["input"].as_str().unwrap()),
+                vector["canonical"].as_str().unwrap(),
+                "{}",
+                vector["name"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_numbers() {
+        for (input, expected) in [
+            ("0", "0"),
+            ("-0.0", "0"),
+            ("1.0", "1"),
+            ("-1.5", "-1.5"),
+            ("1e20", "100000000000000000000"),
+            ("1e21", "1e+21"),
+            ("0.000001", "0.000001"),
+            ("1e-7", "1e-7"),
+            ("123456789012", "123456789012"),
+            ("9007199254740993", "9007199254740992"),
+            ("295147905179352830000", "295147905179352830000"),
+            ("5e-324", "5e-324"),
+            ("1.7976931348623157e308", "1.7976931348623157e+308"),
+        ] {
+            assert_eq!(canonical(input), expected, "{}", input);
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use axum::async_trait;
+use futures::FutureExt;
+use metrics::gauge;
+use rdkafka::{
+    config::ClientConfig,
+    error::KafkaError,
+    message::{Header, OwnedHeaders},
+    producer::{FutureProducer, FutureRecord, Producer},
+    util::Timeout,
+};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    sync::Arc,
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use crate::transport::{PendingAck, Sink, SinkError};
+use crate::verification::{ServerEnvelope, ENVELOPE_HEADER};
+use crate::{sandbox, tenants, FactoEvent};
+
+/// Default for `KAFKA_TOPIC`
+pub const DEFAULT_KAFKA_TOPIC: &str = "facto.events";
+
+/// Longest Kafka topic name
+const MAX_TOPIC_CHARS: usize = 249;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partitioning {
+    /// One topic, with each session's events in one partition
+    Session,
+    /// One topic per agent, named after the agent's NATS subject
+    Agent,
+}
+
+#[derive(Debug, Clone)]
+pub struct KafkaConfig {
+    /// Bootstrap brokers as `host:port`
+    pub brokers: Vec<String>,
+    pub topic: String,
+    pub partitioning: Partitioning,
+    /// -1 waits for all in-sync replicas, 1 for the partition leader only
+    pub acks: i16,
+    pub timeout: Duration,
+    pub client_id: String,
+}
+
+impl KafkaConfig {
+    /// Read `KAFKA_BROKERS`, `KAFKA_TOPIC`, `KAFKA_PARTITIONING` (`session`
+    /// or `agent`), `KAFKA_ACKS` (`all` or `1`), `KAFKA_TIMEOUT_MS` and
+    /// `KAFKA_CLIENT_ID`
+    pub fn from_env() -> anyhow::Result<Self> {
+        let brokers: Vec<String> = std::env::var("KAFKA_BROKERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|b| !b.is_empty())
+            .map(String::from)
+            .collect();
+        if brokers.is_empty() {
+            anyhow::bail!("TRANSPORT=kafka requires KAFKA_BROKERS");
+        }
+        let partitioning = match std::env::var("KAFKA_PARTITIONING").as_deref() {
+            Err(_) | Ok("session") => Partitioning::Session,
+            Ok("agent") => Partitioning::Agent,
+            Ok(other) => {
+                anyhow::bail!("KAFKA_PARTITIONING must be session or agent, not {}", other)
+            }
+        };
+        // Without acks nothing confirms an event was persisted, so 0 is not offered
+        let acks = match std::env::var("KAFKA_ACKS").as_deref() {
+            Err(_) | Ok("all") | Ok("-1") => -1,
+            Ok("1") => 1,
+            Ok(other) => anyhow::bail!("KAFKA_ACKS must be all or 1, not {}", other),
+        };
+        let timeout_ms: u64 = std::env::var("KAFKA_TIMEOUT_MS")
+            .map_or(30_000, |v| v.parse().expect("Invalid KAFKA_TIMEOUT_MS"));
+
+        Ok(Self {
+            brokers,
+            topic: std::env::var("KAFKA_TOPIC").unwrap_or_else(|_| DEFAULT_KAFKA_TOPIC.to_string()),
+            partitioning,
+            acks,
+            timeout: Duration::from_millis(timeout_ms),
+            client_id: std::env::var("KAFKA_CLIENT_ID")
+                .unwrap_or_else(|_| "facto-ingestion".to_string()),
+        })
+    }
+}
+
+// ============================================================================
+// Kafka Sink
+// ============================================================================
+
+/// Publishes events to Kafka through librdkafka.
+///
+/// Records are keyed by session_id and assigned to partitions with the Java
+/// client's default partitioner, so each session stays in one partition and
+/// in order. Every publish waits for the broker's acknowledgement. The
+/// facto_id travels in the payload for consumers to deduplicate retries.
+pub struct KafkaSink {
+    config: KafkaConfig,
+    producer: FutureProducer,
+    connected: AtomicBool,
+}
+
+impl KafkaSink {
+    pub fn new(config: KafkaConfig) -> Result<Self, KafkaError> {
+        let mut client = ClientConfig::new();
+        client
+            .set("bootstrap.servers", config.brokers.join(","))
+            .set("client.id", &config.client_id)
+            .set("acks", config.acks.to_string())
+            .set("message.timeout.ms", config.timeout.as_millis().to_string())
+            // murmur2 of the key, as sessions are split across NATS partitions
+            .set("partitioner", "murmur2_random");
+        // Retries must not reorder a session's events
+        match config.acks {
+            -1 => client.set("enable.idempotence", "true"),
+            _ => client.set("max.in.flight.requests.per.connection", "1"),
+        };
+
+        Ok(Self {
+            producer: client.create()?,
+            config,
+            connected: AtomicBool::new(false),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let partitioning = match self.config.partitioning {
+            Partitioning::Session => format!("topic {} keyed by session", self.config.topic),
+            Partitioning::Agent => "a topic per agent".to_string(),
+        };
+        format!(
+            "Kafka at {} ({})",
+            self.config.brokers.join(","),
+            partitioning
+        )
+    }
+
+    /// Track whether a broker is reachable, like the NATS connection monitor
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            let producer = self.producer.clone();
+            let topic = self.config.topic.clone();
+            let timeout = self.config.timeout;
+            let fetched = tokio::task::spawn_blocking(move || {
+                producer
+                    .client()
+                    .fetch_metadata(Some(&topic), timeout)
+                    .map(|_| ())
+            })
+            .await;
+            match fetched {
+                Ok(Ok(())) => self.set_connected(true),
+                Ok(Err(e)) => {
+                    warn!("Failed to fetch Kafka metadata: {}", e);
+                    self.set_connected(false);
+                }
+                Err(e) => warn!("Kafka metadata task failed: {}", e),
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+
+    fn set_connected(&self, connected: bool) {
+        if self.connected.swap(connected, Ordering::Relaxed) != connected {
+            match connected {
+                true => info!("Connected to Kafka"),
+                false => warn!("Kafka connection lost"),
+            }
+        }
+        gauge!("facto_kafka_connected").set(if connected { 1.0 } else { 0.0 });
+    }
+
+    fn topic(&self, event: &FactoEvent, envelope: &ServerEnvelope) -> String {
+        match (self.config.partitioning, envelope.sandbox) {
+            (Partitioning::Session, false) => self.config.topic.clone(),
+            (Partitioning::Session, true) => format!("{}.sandbox", self.config.topic),
+            (Partitioning::Agent, false) => topic_name(&tenants::event_subject(
+                envelope.tenant_id.as_deref(),
+                &event.agent_id,
+            )),
+            (Partitioning::Agent, true) => topic_name(&sandbox::sandbox_subject(
+                envelope.tenant_id.as_deref(),
+                &event.agent_id,
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for KafkaSink {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    async fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Waits for the produce acknowledgement before returning, so events
+    /// stay in order within their partition
+    async fn send(
+        &self,
+        event: &FactoEvent,
+        envelope: &ServerEnvelope,
+    ) -> Result<PendingAck, SinkError> {
+        if !self.connected.load(Ordering::Relaxed) {
+            return Err(SinkError::NotConnected("Kafka"));
+        }
+        let topic = self.topic(event, envelope);
+        let value = event.to_json();
+        let envelope_json = serde_json::to_string(envelope).unwrap();
+        let mut headers = OwnedHeaders::new_with_capacity(envelope.headers.len() + 1);
+        for (name, value) in &envelope.headers {
+            headers = headers.insert(Header {
+                key: name,
+                value: Some(value.as_str()),
+            });
+        }
+        headers = headers.insert(Header {
+            key: ENVELOPE_HEADER,
+            value: Some(envelope_json.as_str()),
+        });
+
+        let record = FutureRecord::to(&topic)
+            .key(event.session_id.as_str())
+            .payload(value.as_ref())
+            .headers(headers);
+        self.producer
+            .send(record, Timeout::After(self.config.timeout))
+            .await
+            .map_err(|(e, _)| SinkError::Kafka(e))?;
+        Ok(futures::future::ready(Ok(None)).boxed())
+    }
+
+    /// Every publish already waited for its acknowledgement
+    async fn flush(&self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+/// Kafka topic names allow fewer characters than NATS subjects
+fn topic_name(subject: &str) -> String {
+    subject
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                true => c,
+                false => '_',
+            },
+        )
+        .take(MAX_TOPIC_CHARS)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_names_replace_subject_characters() {
+        assert_eq!(
+            topic_name("facto.events.agent:1/x"),
+            "facto.events.agent_1_x"
+        );
+        assert_eq!(topic_name(&"a".repeat(300)).len(), MAX_TOPIC_CHARS);
+    }
+}
@@ -0,0 +1,180 @@
+use serde::Deserialize;
+
+use crate::registry::{KeyEntry, KeyImportRow};
+
+/// Most rows accepted by one bulk key import
+pub const MAX_IMPORT_ROWS: usize = 100_000;
+
+/// Columns of an exported CSV key file. Imports read `tenant_id`,
+/// `agent_id`, `public_key`, `valid_from`, `revoked_at` and `expires_at` and
+/// ignore the rest, so an export can be imported as is.
+const CSV_COLUMNS: [&str; 7] = [
+    "tenant_id",
+    "agent_id",
+    "public_key",
+    "registered_at",
+    "valid_from",
+    "revoked_at",
+    "expires_at",
+];
+
+/// Encoding of a bulk key import or export
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyFileFormat {
+    /// One JSON object per line
+    #[default]
+    Jsonl,
+    /// Comma-separated values with a header row
+    Csv,
+}
+
+impl KeyFileFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            KeyFileFormat::Jsonl => "application/x-ndjson",
+            KeyFileFormat::Csv => "text/csv",
+        }
+    }
+
+    /// Format named by a Content-Type header
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+        match mime {
+            "text/csv" => Some(KeyFileFormat::Csv),
+            "application/x-ndjson" | "application/jsonl" | "application/json-lines" => {
+                Some(KeyFileFormat::Jsonl)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A row of an imported key file, by its line in the file
+pub type ParsedRow = (usize, Result<KeyImportRow, String>);
+
+/// Parse every row of a key file. Rows that fail to parse are kept with
+/// their error so they can be reported alongside validation failures.
+pub fn parse_rows(format: KeyFileFormat, body: &str) -> Vec<ParsedRow> {
+    match format {
+        KeyFileFormat::Jsonl => body
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| (i + 1, serde_json::from_str(line).map_err(|e| e.to_string())))
+            .collect(),
+        KeyFileFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(body.as_bytes());
+            let mut rows = Vec::new();
+            let mut record = csv::StringRecord::new();
+            loop {
+                match reader.read_record(&mut record) {
+                    Ok(false) => break,
+                    Ok(true) => {
+                        let line = record.position().map_or(0, |p| p.line() as usize);
+                        let headers = reader.headers().ok().cloned();
+                        rows.push((
+                            line,
+                            record
+                                .deserialize(headers.as_ref())
+                                .map_err(|e| e.to_string()),
+                        ));
+                    }
+                    // A malformed record leaves the reader at the next line
+                    Err(e) => {
+                        let line = e.position().map_or(0, |p| p.line() as usize);
+                        rows.push((line, Err(e.to_string())));
+                        if !matches!(e.kind(), csv::ErrorKind::UnequalLengths { .. }) {
+                            break;
+                        }
+                    }
+                }
+            }
+            rows
+        }
+    }
+}
+
+/// Encode registry entries as a key file
+pub fn write_entries(format: KeyFileFormat, entries: &[KeyEntry]) -> anyhow::Result<Vec<u8>> {
+    match format {
+        KeyFileFormat::Jsonl => {
+            let mut body = Vec::new();
+            for entry in entries {
+                serde_json::to_writer(&mut body, entry)?;
+                body.push(b'\n');
+            }
+            Ok(body)
+        }
+        KeyFileFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            writer.write_record(CSV_COLUMNS)?;
+            for entry in entries {
+                writer.write_record([
+                    entry.tenant_id.clone().unwrap_or_default(),
+                    entry.agent_id.clone(),
+                    entry.public_key.clone(),
+                    entry.registered_at.to_string(),
+                    entry.valid_from.to_string(),
+                    entry.revoked_at.map(|r| r.to_string()).unwrap_or_default(),
+                    entry.expires_at.map(|e| e.to_string()).unwrap_or_default(),
+                ])?;
+            }
+            Ok(writer.into_inner()?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(tenant_id: Option<&str>, agent_id: &str, revoked_at: Option<i64>) -> KeyEntry {
+        KeyEntry {
+            tenant_id: tenant_id.map(str::to_string),
+            agent_id: agent_id.to_string(),
+            public_key: format!("{}-key", agent_id),
+            registered_at: 1,
+            valid_from: 2,
+            revoked_at,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_export_round_trips_through_import() {
+        let entries = vec![
+            entry(None, "agent-a", None),
+            entry(Some("acme"), "agent-b", Some(3)),
+        ];
+        for format in [KeyFileFormat::Jsonl, KeyFileFormat::Csv] {
+            let body = write_entries(format, &entries).unwrap();
+            let rows = parse_rows(format, std::str::from_utf8(&body).unwrap());
+            assert_eq!(rows.len(), 2);
+
+            let (_, second) = &rows[1];
+            let second = second.as_ref().unwrap();
+            assert_eq!(second.tenant_id.as_deref(), Some("acme"));
+            assert_eq!(second.agent_id, "agent-b");
+            assert_eq!(second.valid_from, Some(2));
+            assert_eq!(second.revoked_at, Some(3));
+            assert_eq!(rows[0].1.as_ref().unwrap().revoked_at, None);
+            assert_eq!(rows[0].1.as_ref().unwrap().tenant_id, None);
+        }
+    }
+
+    #[test]
+    fn test_bad_rows_keep_their_line() {
+        let csv = "agent_id,public_key,valid_from\na,key-a,\nb,key-b,soon\nc\nd,key-d,5\n";
+        let rows = parse_rows(KeyFileFormat::Csv, csv);
+        let lines: Vec<(usize, bool)> = rows.iter().map(|(l, r)| (*l, r.is_ok())).collect();
+        assert_eq!(lines, vec![(2, true), (3, false), (4, false), (5, true)]);
+
+        let jsonl = "{\"agent_id\":\"a\",\"public_key\":\"key-a\"}\n\nnot json\n";
+        let rows = parse_rows(KeyFileFormat::Jsonl, jsonl);
+        let lines: Vec<(usize, bool)> = rows.iter().map(|(l, r)| (*l, r.is_ok())).collect();
+        assert_eq!(lines, vec![(1, true), (3, false)]);
+    }
+}
@@ -0,0 +1,201 @@
+//! Compatibility shim for SDKs pinned before 1.0.
+//!
+//! Pre-1.0 SDKs send one event per request in a flat shape: the event is
+//! identified by `trace_id` and `parent_trace_id`, its payloads are `input`
+//! and `output`, and the execution metadata and proof fields sit beside
+//! them instead of in `execution_meta` and `proof`:
+//!
+//! ```text
+//! {"trace_id": "tr-...", "agent_id": "...", "session_id": "...",
+//!  "parent_trace_id": null, "action_type": "llm_call", "status": "success",
+//!  "input": {...}, "output": {...},
+//!  "model_id": "...", "temperature": 0.7, "seed": 42, "tool_calls": [],
+//!  "sdk_version": "0.9.2",
+//!  "signature": "...", "public_key": "...", "prev_hash": "...",
+//!  "event_hash": "...", "started_at": 1700000000000000000,
+//!  "completed_at": 1700000000500000000}
+//! ```
+//!
+//! `POST /v1/ingest/legacy` maps it into a version 1 [`FactoEvent`] and
+//! ingests it like `POST /v1/ingest`. Only names change: pre-1.0 events are
+//! hashed and signed over the version 1 canonical form of the event they
+//! map to, so the translated event verifies with the signature it was sent
+//! with. Its server envelope records the translation in `translation`, and
+//! it is published as the translated event.
+
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use bytes::Bytes;
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::auth::Principal;
+use crate::debug::DebugTrace;
+use crate::limits::{JsonBody, LimitedBody};
+use crate::negotiate::ResponseEncoding;
+use crate::protocol::IngestQuery;
+use crate::{
+    ingest_single, reject_origin, tenant_label, AppState, ExecutionMeta, FactoEvent, Proof,
+};
+
+/// Shape `translation.from` names for events sent to `/v1/ingest/legacy`
+pub const LEGACY_FORMAT: &str = "pre-1.0";
+
+/// An event in the pre-1.0 SDK shape
+#[derive(Debug, Deserialize)]
+pub struct LegacyEvent {
+    trace_id: String,
+    agent_id: String,
+    session_id: String,
+    #[serde(default)]
+    parent_trace_id: Option<String>,
+    action_type: String,
+    status: String,
+    #[serde(default)]
+    input: serde_json::Value,
+    #[serde(default)]
+    output: serde_json::Value,
+    #[serde(default)]
+    model_id: Option<String>,
+    #[serde(default)]
+    temperature: Option<f64>,
+    #[serde(default)]
+    seed: Option<i64>,
+    #[serde(default)]
+    tool_calls: Vec<serde_json::Value>,
+    sdk_version: String,
+    signature: String,
+    public_key: String,
+    prev_hash: String,
+    event_hash: String,
+    started_at: i64,
+    completed_at: i64,
+}
+
+impl JsonBody for LegacyEvent {
+    fn from_json(body: &Bytes) -> serde_json::Result<Self> {
+        serde_json::from_slice(body)
+    }
+}
+
+impl From<LegacyEvent> for FactoEvent {
+    fn from(legacy: LegacyEvent) -> Self {
+        FactoEvent {
+            event_version: 1,
+            facto_id: legacy.trace_id,
+            agent_id: legacy.agent_id,
+            session_id: legacy.session_id,
+            parent_facto_id: legacy.parent_trace_id,
+            action_type: legacy.action_type,
+            status: legacy.status,
+            input_data: legacy.input,
+            output_data: legacy.output,
+            execution_meta: ExecutionMeta {
+                model_id: legacy.model_id,
+                model_hash: None,
+                temperature: legacy.temperature,
+                seed: legacy.seed,
+                max_tokens: None,
+                tool_calls: legacy.tool_calls,
+                sdk_version: legacy.sdk_version,
+                // Pre-1.0 SDKs do not say which language they are
+                sdk_language: String::new(),
+                tags: BTreeMap::new(),
+            },
+            proof: Proof {
+                signature: legacy.signature,
+                public_key: legacy.public_key,
+                prev_hash: legacy.prev_hash,
+                event_hash: legacy.event_hash,
+                algorithm: None,
+                canonical_version: None,
+                verification_method: None,
+            },
+            started_at: legacy.started_at,
+            completed_at: legacy.completed_at,
+            raw: None,
+        }
+    }
+}
+
+/// How an event sent in an older shape was mapped into the current model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranslationRecord {
+    /// Shape the event was sent in
+    pub from: String,
+    /// Wire version it was mapped into
+    pub event_version: u32,
+}
+
+impl TranslationRecord {
+    fn legacy() -> Self {
+        Self {
+            from: LEGACY_FORMAT.to_string(),
+            event_version: 1,
+        }
+    }
+}
+
+/// `POST /v1/ingest/legacy`: ingest a pre-1.0 event as its translation
+pub async fn ingest_legacy_handler(
+    State(state): State<Arc<AppState>>,
+    principal: Option<Extension<Principal>>,
+    debug: Option<Extension<DebugTrace>>,
+    encoding: ResponseEncoding,
+    headers: HeaderMap,
+    Query(query): Query<IngestQuery>,
+    LimitedBody(legacy): LimitedBody<LegacyEvent>,
+) -> Response {
+    let tenant = tenant_label(&principal);
+    counter!("facto_legacy_ingest_events_total", "tenant" => tenant.clone()).increment(1);
+    let event = FactoEvent::from(legacy);
+    if let Err(e) = state.limits.check_event(&event) {
+        counter!("facto_ingest_rejected_total", "reason" => e.code().as_str(), "tenant" => tenant)
+            .increment(1);
+        return e.into_response();
+    }
+    let propagated = state.propagation.extract(&headers);
+    let origin = reject_origin(&principal);
+    let received = state.rejects.is_some().then(|| event.clone());
+    let (status, axum::Json(response)) = ingest_single(
+        state.clone(),
+        principal,
+        debug,
+        propagated,
+        event,
+        query.backfill,
+        Some(TranslationRecord::legacy()),
+    )
+    .await;
+    if let (Some(rejects), Some(event), Some(error)) = (&state.rejects, received, &response.error) {
+        rejects.record(origin.0.as_deref(), origin.1, event, error);
+    }
+    encoding.reply(status, &response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{build_canonical_form, verify_hash, verify_signature};
+    use crate::testing::{legacy_test_event, sign_test_event, test_event};
+    use ed25519_dalek::SigningKey;
+
+    #[test]
+    fn test_translation_keeps_the_signed_form() {
+        let event = sign_test_event(test_event(), &SigningKey::from_bytes(&[7; 32]));
+        let legacy: LegacyEvent = serde_json::from_value(legacy_test_event(&event)).unwrap();
+        let translated = FactoEvent::from(legacy);
+
+        assert_eq!(translated.facto_id, event.facto_id);
+        assert_eq!(translated.input_data, event.input_data);
+        let canonical = build_canonical_form(&translated).unwrap();
+        assert_eq!(canonical, build_canonical_form(&event).unwrap());
+        assert!(verify_hash(&translated, &canonical).is_ok());
+        assert!(verify_signature(&translated, &canonical).is_ok());
+    }
+}
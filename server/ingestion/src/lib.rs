@@ -0,0 +1,4573 @@
+//! The ingestion server: event model, canonical forms and wire types of
+//! the ingestion API, and the handlers and background tasks serving it.
+//!
+//! `facto-mock-server` builds on the same event model and wire types, so
+//! the mock accepts, hashes and answers exactly like the real handlers.
+
+use crate::crypto::VerificationError;
+use crate::did::DataIntegrityProof;
+use crate::protocol::{
+    BatchIngestRequest, BatchIngestResponse, Capabilities, ErrorCode, EventError, HealthResponse,
+    IngestQuery, KeyRotation, OrderingGuarantee, ReadyResponse, RejectedEvent, ReplayRejection,
+    SingleIngestResponse, AGENT_BYTE_QUOTA_EXCEEDED, AGENT_NOT_AUTHORIZED, AGENT_PAUSED,
+    BACKFILL_FULL, BLOB_STORE_FAILED, FACTO_ID_CONFLICT, KEY_ROTATION_ACTION, QUEUE_FAILED,
+    RATE_LIMITED, SERVICE_NOT_READY, SESSION_FROZEN, SPOOL_FULL, TENANT_BYTE_QUOTA_EXCEEDED,
+    TENANT_RATE_LIMITED, VALIDATION_TIMEOUT, WRONG_REPLICA,
+};
+use axum::{
+    extract::{DefaultBodyLimit, Extension, Json, Query, State},
+    http::{HeaderMap, StatusCode},
+    middleware,
+    response::{Html, IntoResponse, Response},
+    routing::{get, post, put},
+    Router,
+};
+use futures::{
+    future::BoxFuture,
+    stream::{FuturesUnordered, StreamExt},
+    FutureExt,
+};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use metrics_util::layers::FanoutBuilder;
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::IntoFuture,
+    net::SocketAddr,
+    num::NonZeroU32,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{mpsc, RwLock};
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{Any, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use tracing::{error, info, info_span, warn, Instrument};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+mod admin;
+mod agents;
+mod anchor;
+mod annotations;
+mod auth;
+mod backfill;
+mod chain;
+mod checkpoint;
+mod classify;
+pub mod config;
+mod controls;
+pub mod crypto;
+mod cursors;
+mod debug;
+mod decompress;
+mod dedup;
+pub mod did;
+mod dimensions;
+pub mod encoding;
+mod freeze;
+mod headers;
+mod health;
+pub mod jcs;
+mod kafka;
+mod keyfile;
+mod legacy;
+mod limits;
+mod metrics_push;
+mod models;
+mod nats;
+mod negotiate;
+mod offload;
+pub mod openapi;
+mod ordering;
+mod policy;
+pub mod protocol;
+mod ratelimit;
+mod raw;
+mod receipts;
+mod redaction;
+mod registry;
+mod rejects;
+mod replay;
+mod resolver;
+mod retention;
+mod sandbox;
+mod schemas;
+mod shadow;
+mod shared;
+mod shutdown;
+mod sinks;
+mod spool;
+mod store;
+mod tail;
+mod telemetry;
+mod tenants;
+mod tls;
+mod transport;
+mod usage;
+mod validation;
+mod verification;
+pub mod versions;
+mod webhooks;
+
+#[cfg(any(test, feature = "test-util"))]
+#[doc(hidden)]
+pub mod testing;
+
+pub use protocol::{ExecutionMeta, FactoEvent, Proof};
+
+use agents::{AgentInventory, DEFAULT_AGENT_SILENCE_SECS};
+use anchor::{Anchorer, Anchors};
+use annotations::SessionAnnotations;
+use auth::{ApiKeys, Authenticator, JwtConfig, JwtValidator, Principal, Scope};
+use backfill::{BackfillBuffer, ChainLink, HeldEvent};
+use chain::{ChainHeadStore, ChainHeads, ChainRouting, KvChainHeads};
+use checkpoint::{Checkpoints, CHECKPOINT_SUBJECT};
+use classify::{
+    Classification, Classifier, ClassifierSink, HttpClassifier, RuleClassifier,
+    CLASSIFICATION_SUBJECT,
+};
+use config::{Config, Reloadable};
+use controls::{AgentControls, LimitSource};
+use cursors::{Cursors, FeedSink};
+use debug::{DebugBundles, DebugTrace};
+use dedup::{DedupCache, DedupOutcome, DedupStore, KvDedup};
+use dimensions::{EventLabels, MetricDimensions, Outcome};
+use freeze::SessionFreezes;
+use headers::HeaderPropagation;
+use health::{
+    HealthChecks, JetStreamCheck, KafkaCheck, KeyRegistryCheck, Requirement, SharedStateCheck,
+    SpoolCheck,
+};
+use kafka::{KafkaConfig, KafkaSink};
+use legacy::TranslationRecord;
+use limits::{LimitedBody, RequestLimits};
+use metrics_push::{MetricsPush, OtelRecorder};
+use models::{AttestationMode, ModelAttestation, ModelRegistry};
+use nats::NatsConfig;
+use negotiate::ResponseEncoding;
+use offload::Offloader;
+use ordering::{SessionGuard, SessionLocks};
+use policy::{
+    PolicyAction, PolicyAlert, PolicyAlertSink, PolicyEngine, PolicyFinding, RulePolicy,
+    POLICY_ALERT_SUBJECT,
+};
+use ratelimit::{default_agent_rate, AgentRateLimiter, ByteQuotas, KvRateLimiter, RateLimitStore};
+use raw::RawIngest;
+use receipts::{receipt_key, sign_receipt, Receipts, DEFAULT_RECEIPT_RETENTION};
+use redaction::{RedactionKeys, Redactions};
+use registry::KeyRegistry;
+use rejects::Rejects;
+use replay::{KvReplayGuard, ReplayGuard, ReplayStore};
+use resolver::DidResolver;
+use retention::Retention;
+use sandbox::{sandbox_scope, Sandbox};
+use schemas::{SchemaMode, SchemaRegistry};
+use shadow::ShadowEvaluator;
+use shared::{KvBucket, SharedStateBackend};
+use shutdown::Shutdown;
+use sinks::{ExportSink, Fanout, FanoutSink, Shaper, SinkLimits};
+use spool::{Spool, SpoolError, SpooledEvent, NATS_CURSOR};
+use tenants::{TenantRejection, Tenants, DEFAULT_TENANT};
+use tls::{Tls, TlsConfig};
+use transport::{NatsSink, Sink, Transport};
+use usage::UsageLedger;
+use validation::{Validation, ValidationTimeout};
+use verification::{
+    now_nanos, EnforcementMode, ServerEnvelope, ServerSigner, VerificationAssertion,
+    VerificationCache, VerificationStatus, Verifier,
+};
+use webhooks::{RetryPolicy, WebhookSink, Webhooks};
+
+// ============================================================================
+// Application State
+// ============================================================================
+
+pub struct AppState {
+    nats_client: Arc<RwLock<Option<async_nats::Client>>>,
+    /// Where accepted events are published
+    sink: Arc<dyn Sink>,
+    /// `NATS_SESSION_PARTITIONS`: subjects each agent's events are spread
+    /// over by session, 0 for the agent's subject alone
+    session_partitions: usize,
+    rate_limiter: Box<dyn RateLimitStore>,
+    /// `RATE_LIMIT_PER_AGENT`, unless the tenant or an override sets
+    /// another. Reloadable.
+    rate_limit_per_agent: AtomicU32,
+    /// `AGENT_BYTE_QUOTA`, 0 for none, unless the tenant or an override sets
+    /// another. Reloadable.
+    agent_byte_quota: AtomicU64,
+    byte_quotas: ByteQuotas,
+    agent_controls: AgentControls,
+    /// `VERIFICATION_MODE`, unless the tenant or an override sets another
+    enforcement_mode: EnforcementMode,
+    verifier: Verifier,
+    key_registry: Arc<KeyRegistry>,
+    /// Public keys whose registry snapshots may be restored: this server's
+    /// and `REGISTRY_SNAPSHOT_SIGNERS`
+    snapshot_signers: Vec<String>,
+    /// `KEY_ROTATION_OVERLAP_SECS`: how long a key rotated out by a key
+    /// rotation event stays valid, unless the event asks for less
+    key_rotation_overlap_secs: u64,
+    auth: Authenticator,
+    dedup: Box<dyn DedupStore>,
+    replay: Box<dyn ReplayStore>,
+    freezes: SessionFreezes,
+    annotations: SessionAnnotations,
+    agents: AgentInventory,
+    usage: UsageLedger,
+    chain_heads: Box<dyn ChainHeadStore>,
+    /// Sessions this replica owns, when chain heads are not shared
+    chain_routing: Option<ChainRouting>,
+    /// Buckets of the stores shared with other replicas, opened on connect
+    shared_buckets: Vec<Arc<KvBucket>>,
+    health: HealthChecks,
+    checkpoints: Checkpoints,
+    /// Signed receipts of recently accepted events
+    receipts: Receipts,
+    anchors: Anchors,
+    tenants: Tenants,
+    schemas: SchemaRegistry,
+    /// Approved models by tenant
+    models: ModelRegistry,
+    policies: PolicyEngine,
+    redactions: Redactions,
+    /// Retention policies and legal holds, applied by the indexer
+    retention: Retention,
+    spool: Option<Spool>,
+    /// Every accepted event is appended to the spool and delivered from it
+    outbox: bool,
+    sink_shaper: Shaper,
+    fanout: Fanout,
+    capabilities: Capabilities,
+    limits: RequestLimits,
+    debug_bundles: DebugBundles,
+    session_locks: SessionLocks,
+    /// Events ingested with `backfill=true`, held until their sessions'
+    /// chains reach them
+    backfill: BackfillBuffer,
+    /// Set when sandbox credentials are accepted
+    sandbox: Option<Sandbox>,
+    /// Set when the cursor API is enabled
+    cursors: Option<Arc<Cursors>>,
+    /// Set when rejected events are dead-lettered to FACTO_REJECTS
+    rejects: Option<Arc<Rejects>>,
+    /// Set when events published to `facto.raw.>` are ingested
+    raw_ingest: Option<RawIngest>,
+    /// Set when ingest outcomes are counted per agent and action type
+    dimensions: Option<MetricDimensions>,
+    /// Set when candidate canonicalizers and hashers are evaluated
+    shadow: Option<Arc<ShadowEvaluator>>,
+    /// Set when large payloads are moved to a blob store
+    offloader: Option<Offloader>,
+    /// Set when accepted events are posted to registered webhooks
+    webhooks: Option<Arc<Webhooks>>,
+    propagation: HeaderPropagation,
+    shutdown: Shutdown,
+    /// Renders the metrics recorded by this process
+    metrics: PrometheusHandle,
+    /// Bounds the payload checks of batch events
+    validation: Validation,
+}
+
+impl AppState {
+    async fn is_nats_connected(&self) -> bool {
+        let client = self.nats_client.read().await;
+        client.is_some()
+    }
+
+    /// The NATS client, if it is currently connected
+    async fn connected_client(&self) -> Option<async_nats::Client> {
+        let client = self.nats_client.read().await;
+        client
+            .as_ref()
+            .filter(|c| c.connection_state() == async_nats::connection::State::Connected)
+            .cloned()
+    }
+
+    /// Verifier for production or sandbox events
+    fn verifier(&self, sandbox: bool) -> &Verifier {
+        match (sandbox, &self.sandbox) {
+            (true, Some(sandbox)) => &sandbox.verifier,
+            _ => &self.verifier,
+        }
+    }
+
+    /// An agent's rate limit and where it is configured
+    fn agent_rate_limit(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+    ) -> (NonZeroU32, LimitSource) {
+        if let Some(rate) = self.agent_controls.rate_override(tenant_id, agent_id) {
+            return (rate, LimitSource::Agent);
+        }
+        match tenant_id.and_then(|t| self.tenants.agent_rate_limit(t)) {
+            Some(rate) => (rate, LimitSource::Tenant),
+            None => (
+                default_agent_rate(self.rate_limit_per_agent.load(Ordering::Relaxed)),
+                LimitSource::Default,
+            ),
+        }
+    }
+
+    /// An agent's enforcement mode and where it is configured
+    fn enforcement_mode(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+    ) -> (EnforcementMode, LimitSource) {
+        if let Some(mode) = self
+            .agent_controls
+            .enforcement_override(tenant_id, agent_id)
+        {
+            return (mode, LimitSource::Agent);
+        }
+        match tenant_id.and_then(|t| self.tenants.enforcement_mode(t)) {
+            Some(mode) => (mode, LimitSource::Tenant),
+            None => (self.enforcement_mode, LimitSource::Default),
+        }
+    }
+
+    /// An agent's byte quota per window and where it is configured, if it
+    /// has one
+    fn agent_byte_quota(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+    ) -> Option<(u64, LimitSource)> {
+        if let Some(quota) = self.agent_controls.byte_quota_override(tenant_id, agent_id) {
+            return Some((quota, LimitSource::Agent));
+        }
+        match tenant_id.and_then(|t| self.tenants.agent_byte_quota(t)) {
+            Some(quota) => Some((quota, LimitSource::Tenant)),
+            None => match self.agent_byte_quota.load(Ordering::Relaxed) {
+                0 => None,
+                quota => Some((quota, LimitSource::Default)),
+            },
+        }
+    }
+
+    /// Count an event's bytes against the agent's byte quota
+    fn admit_agent_bytes(
+        &self,
+        tenant_id: Option<&str>,
+        key_scope: Option<&str>,
+        agent_id: &str,
+        bytes: u64,
+    ) -> bool {
+        let Some((quota, _)) = self.agent_byte_quota(tenant_id, agent_id) else {
+            return true;
+        };
+        self.byte_quotas
+            .admit(&scoped_id(key_scope, agent_id), bytes, quota, now_nanos())
+    }
+
+    /// Count a request against the agent's limit. Sandbox requests are
+    /// limited apart from production under `key_scope`.
+    async fn check_rate_limit(
+        &self,
+        tenant_id: Option<&str>,
+        key_scope: Option<&str>,
+        agent_id: &str,
+    ) -> bool {
+        let (rate, _) = self.agent_rate_limit(tenant_id, agent_id);
+        self.rate_limiter
+            .check(&scoped_id(key_scope, agent_id), rate)
+            .await
+    }
+}
+
+// ============================================================================
+// Validation and Publishing
+// ============================================================================
+
+/// Validate a single event of a tenant under its enforcement mode,
+/// returning the server's verification assertion and whether the proof was
+/// verified
+async fn validate_event(
+    state: &AppState,
+    tenant_id: Option<&str>,
+    event: &FactoEvent,
+    mode: EnforcementMode,
+    sandbox: bool,
+) -> Result<(VerificationAssertion, VerificationStatus), VerificationError> {
+    state
+        .verifier(sandbox)
+        .verify_under(tenant_id, std::slice::from_ref(event), &[mode])
+        .instrument(info_span!("verify_signatures", events = 1))
+        .await
+        .remove(0)
+}
+
+/// Count an event accepted without a verified proof
+fn count_unverified(status: &VerificationStatus, mode: EnforcementMode, tenant: &str) {
+    let reason = match status {
+        VerificationStatus::Verified => return,
+        VerificationStatus::Failed { code, .. } => code.clone(),
+        VerificationStatus::Skipped => "skipped".to_string(),
+    };
+    counter!(
+        "facto_unverified_events_total",
+        "mode" => mode.code(),
+        "reason" => reason,
+        "tenant" => tenant.to_string()
+    )
+    .increment(1);
+}
+
+/// Qualify an agent or facto_id with the tenant, so tenants never share
+/// rate limits or deduplication state
+fn scoped_id(tenant_id: Option<&str>, id: &str) -> String {
+    match tenant_id {
+        Some(tenant_id) => format!("{}/{}", tenant_id, id),
+        None => id.to_string(),
+    }
+}
+
+/// Refuse an event whose session is routed to another replica, naming the
+/// replica. Sandbox events do not move chain heads and are taken anywhere.
+fn misrouted(state: &AppState, event: &FactoEvent, sandbox: bool) -> Option<EventError> {
+    if sandbox {
+        return None;
+    }
+    let owner = state.chain_routing.as_ref()?.misrouted(&event.session_id)?;
+    Some(
+        EventError::new(ErrorCode::WrongReplica, WRONG_REPLICA)
+            .with_details(serde_json::json!({ "replica": owner })),
+    )
+}
+
+/// Check an event's payloads against the schema of its action type. Events
+/// violating an audit-mode schema pass with the violations to flag.
+fn check_schema(
+    state: &AppState,
+    event: &FactoEvent,
+    tenant: &str,
+) -> Result<Vec<String>, EventError> {
+    let span = info_span!(
+        "validate",
+        action_type = %event.action_type,
+        otel.status_code = tracing::field::Empty,
+        otel.status_message = tracing::field::Empty,
+    );
+    let _span = span.enter();
+    match state.schemas.check(event) {
+        Ok(()) => Ok(Vec::new()),
+        Err(violations) => {
+            counter!(
+                "facto_schema_violations_total",
+                "action_type" => violations.action_type.clone(),
+                "mode" => violations.mode.code(),
+                "tenant" => tenant.to_string()
+            )
+            .increment(1);
+            match violations.mode {
+                SchemaMode::Enforce => {
+                    let message = violations.to_string();
+                    span.record("otel.status_code", "error");
+                    span.record("otel.status_message", message.as_str());
+                    Err(
+                        EventError::new(ErrorCode::SchemaViolation, message).with_details(
+                            serde_json::json!({
+                                "action_type": violations.action_type,
+                                "violations": violations.violations,
+                                "total": violations.total,
+                            }),
+                        ),
+                    )
+                }
+                SchemaMode::Audit => Ok(violations.violations),
+            }
+        }
+    }
+}
+
+/// Check the model an event claims against its tenant's approved models.
+/// Events failing an audit-mode list pass with the failure to flag.
+fn check_model(
+    state: &AppState,
+    event: &FactoEvent,
+    tenant: &str,
+) -> Result<Option<ModelAttestation>, EventError> {
+    let Err(violation) = state.models.check(tenant, event) else {
+        return Ok(None);
+    };
+    counter!(
+        "facto_model_attestation_failures_total",
+        "reason" => violation.attestation.failure.code(),
+        "mode" => violation.mode.code(),
+        "tenant" => tenant.to_string()
+    )
+    .increment(1);
+    match violation.mode {
+        AttestationMode::Enforce => Err(EventError::new(
+            ErrorCode::ModelNotApproved,
+            violation.to_string(),
+        )
+        .with_details(serde_json::json!(violation.attestation))),
+        AttestationMode::Audit => Ok(Some(violation.attestation)),
+    }
+}
+
+/// Check that an event completed within the freshness window. Backfilled
+/// events may be as old as the backfill window instead.
+fn check_fresh(
+    state: &AppState,
+    event: &FactoEvent,
+    backfill: bool,
+    now: i64,
+) -> Result<(), ReplayRejection> {
+    if !backfill {
+        return state.replay.check_fresh(event.completed_at, now);
+    }
+    state.backfill.check_age(event.completed_at, now)?;
+    match state.replay.check_fresh(event.completed_at, now) {
+        Err(ReplayRejection::Stale) => Ok(()),
+        fresh => fresh,
+    }
+}
+
+/// Evaluate the policies against an event. Events with a rejecting finding
+/// are refused; the other findings are recorded in the server envelope.
+fn check_policy(
+    state: &AppState,
+    event: &FactoEvent,
+    tenant: &str,
+) -> Result<Vec<PolicyFinding>, EventError> {
+    let findings = state.policies.evaluate(event, tenant);
+    let Some(rejecting) = findings.iter().find(|f| f.action == PolicyAction::Reject) else {
+        return Ok(findings);
+    };
+    Err(EventError::new(
+        ErrorCode::PolicyViolation,
+        format!(
+            "Event violates policy rule {}: {}",
+            rejecting.rule,
+            rejecting.reasons.join("; ")
+        ),
+    )
+    .with_details(serde_json::json!({ "findings": findings })))
+}
+
+/// What the payload checks found in an event that passed them
+struct PayloadChecks {
+    schema_violations: Vec<String>,
+    model_attestation: Option<ModelAttestation>,
+    policy_findings: Vec<PolicyFinding>,
+    rotation: Option<KeyRotation>,
+}
+
+/// Check a batch event's payloads against schemas, approved models,
+/// policies and, for key rotations, the rotation attestation
+fn check_payloads(
+    state: &AppState,
+    event: &FactoEvent,
+    tenant_id: Option<&str>,
+    tenant: &str,
+) -> Result<PayloadChecks, EventError> {
+    Ok(PayloadChecks {
+        schema_violations: check_schema(state, event, tenant)?,
+        model_attestation: check_model(state, event, tenant)?,
+        policy_findings: check_policy(state, event, tenant)?,
+        rotation: check_key_rotation(state, tenant_id, event)?,
+    })
+}
+
+/// Parse the attestation of a key rotation event. The signing key must be
+/// pinned for the agent and the new key usable before the event is
+/// accepted, since the rotation is applied once it is.
+fn check_key_rotation(
+    state: &AppState,
+    tenant_id: Option<&str>,
+    event: &FactoEvent,
+) -> Result<Option<KeyRotation>, EventError> {
+    if event.action_type != KEY_ROTATION_ACTION {
+        return Ok(None);
+    }
+    let rotation: KeyRotation = serde_json::from_value(event.input_data.clone()).map_err(|e| {
+        EventError::new(
+            ErrorCode::InvalidEvent,
+            format!("Invalid key rotation: {}", e),
+        )
+    })?;
+    crypto::PublicKey::decode_any(&rotation.new_public_key).map_err(|e| {
+        EventError::new(
+            ErrorCode::PublicKeyInvalid,
+            format!("Invalid key rotation: {}", e),
+        )
+    })?;
+    if rotation.new_public_key == event.proof.public_key {
+        return Err(EventError::new(
+            ErrorCode::InvalidEvent,
+            "Invalid key rotation: the new key is the signing key",
+        ));
+    }
+    if !state
+        .key_registry
+        .is_pinned(tenant_id, &event.agent_id, &event.proof.public_key)
+    {
+        return Err(EventError::new(
+            ErrorCode::KeyNotRegistered,
+            "Invalid key rotation: the signing key is not registered for the agent",
+        ));
+    }
+    Ok(Some(rotation))
+}
+
+/// Key rotations change which keys may sign for the agent, so they are
+/// only accepted under a verified signature of the key being rotated out
+fn check_rotation_verified(
+    rotation: &Option<KeyRotation>,
+    status: &VerificationStatus,
+) -> Result<(), EventError> {
+    match (rotation, status) {
+        (None, _) | (Some(_), VerificationStatus::Verified) => Ok(()),
+        (Some(_), _) => Err(EventError::new(
+            ErrorCode::SignatureInvalid,
+            "Key rotation events must carry a verified signature",
+        )),
+    }
+}
+
+/// Trust the key attested to by an accepted key rotation event
+fn apply_key_rotation(
+    state: &AppState,
+    envelope: &ServerEnvelope,
+    event: &FactoEvent,
+    rotation: &KeyRotation,
+) {
+    let outcome = match state.key_registry.rotate_attested(
+        envelope.tenant_id.as_deref(),
+        &event.agent_id,
+        &event.proof.public_key,
+        rotation,
+        state.key_rotation_overlap_secs,
+    ) {
+        Ok(entry) => {
+            info!(
+                "Agent {} rotated its key with event {}, the new key is valid from {}",
+                event.agent_id, event.facto_id, entry.valid_from
+            );
+            "applied"
+        }
+        Err(e) => {
+            warn!(
+                "Failed to apply key rotation event {} of agent {}: {}",
+                event.facto_id, event.agent_id, e
+            );
+            "failed"
+        }
+    };
+    counter!("facto_key_rotations_total", "outcome" => outcome).increment(1);
+}
+
+fn tenant_rejection_error(rejection: TenantRejection) -> EventError {
+    match rejection {
+        TenantRejection::RateLimited => {
+            EventError::new(ErrorCode::TenantRateLimited, TENANT_RATE_LIMITED)
+        }
+        TenantRejection::ByteQuotaExceeded => EventError::new(
+            ErrorCode::TenantByteQuotaExceeded,
+            TENANT_BYTE_QUOTA_EXCEEDED,
+        ),
+    }
+}
+
+/// Tenant and sandbox flag of rejected events, for dead-lettering
+fn reject_origin(principal: &Option<Extension<Principal>>) -> (Option<String>, bool) {
+    principal
+        .as_ref()
+        .map(|Extension(p)| (p.tenant_id.clone(), p.sandbox))
+        .unwrap_or_default()
+}
+
+/// Refuse a single event, counting the rejection under its code
+fn reject_event(
+    status: StatusCode,
+    facto_id: String,
+    error: EventError,
+    tenant: &str,
+) -> (StatusCode, Json<SingleIngestResponse>) {
+    counter!("facto_ingest_rejected_total", "reason" => error.code.as_str(), "tenant" => tenant.to_string())
+        .increment(1);
+    (
+        status,
+        Json(SingleIngestResponse::rejected(facto_id, error)),
+    )
+}
+
+/// Size of an event as published: the bytes it was received as, or its
+/// JSON encoding
+fn event_size(event: &FactoEvent) -> u64 {
+    event.json_len().unwrap_or_else(|| payload_size(event))
+}
+
+/// Size of a value's JSON encoding, counted without allocating it
+fn payload_size<T: serde::Serialize + ?Sized>(value: &T) -> u64 {
+    struct Counter(u64);
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len() as u64;
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    let _ = serde_json::to_writer(&mut counter, value);
+    counter.0
+}
+
+/// Message headers of an accepted event: propagated request headers, the
+/// trace context of the request, then the tenant's configured headers
+fn message_headers(
+    state: &AppState,
+    propagated: &BTreeMap<String, String>,
+    event: &FactoEvent,
+    tenant_id: Option<&str>,
+) -> BTreeMap<String, String> {
+    let mut headers = propagated.clone();
+    headers.extend(telemetry::context_headers(&tracing::Span::current()));
+    if let Some(tenant_id) = tenant_id {
+        state.tenants.apply_headers(tenant_id, event, &mut headers);
+    }
+    headers
+}
+
+/// What became of an accepted event handed to [`deliver_all`]
+#[derive(Debug, Clone, Copy)]
+enum Delivery {
+    /// Stored by the broker, at this stream sequence when it reports one
+    Published(Option<u64>),
+    /// Held in the spool because the sink is unavailable
+    Spooled,
+    /// Appended to the outbox, delivered asynchronously
+    Queued,
+    Rejected(StatusCode, ErrorCode, &'static str),
+}
+
+/// Durably append events to the spool. All events share the outcome:
+/// `appended` on success, a rejection otherwise.
+async fn append_all(
+    spool: &Spool,
+    events: Vec<SpooledEvent>,
+    appended: Delivery,
+    delivered: &mut Vec<(FactoEvent, ServerEnvelope, Delivery)>,
+) {
+    let delivery = match spool.append(&events).await {
+        Ok(()) => appended,
+        Err(SpoolError::Full) => Delivery::Rejected(
+            StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::SpoolFull,
+            SPOOL_FULL,
+        ),
+        Err(e) => {
+            error!("Failed to spool events: {}", e);
+            Delivery::Rejected(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorCode::QueueFailed,
+                QUEUE_FAILED,
+            )
+        }
+    };
+    for SpooledEvent { event, envelope } in events {
+        delivered.push((event, envelope, delivery));
+    }
+}
+
+/// Tenant-scoped session of an accepted event, for the session locks
+fn session_key(event: &FactoEvent, envelope: &ServerEnvelope) -> String {
+    scoped_id(envelope.tenant_id.as_deref(), &event.session_id)
+}
+
+/// Lock the sessions of `events`, unless the caller already holds them
+async fn lock_sessions<'a>(
+    state: &AppState,
+    already_held: bool,
+    events: impl Iterator<Item = (&'a FactoEvent, &'a ServerEnvelope)>,
+) -> Option<SessionGuard> {
+    if already_held {
+        return None;
+    }
+    let keys: Vec<String> = events.map(|(e, env)| session_key(e, env)).collect();
+    Some(
+        state
+            .session_locks
+            .lock_all(keys.iter().map(String::as_str))
+            .await,
+    )
+}
+
+/// An event handed to the sink with the index it was sent at, once the
+/// broker has answered
+type Acked = (
+    usize,
+    FactoEvent,
+    ServerEnvelope,
+    Result<Option<u64>, transport::SinkError>,
+);
+
+/// Wait for the acknowledgements of events handed to the sink and record
+/// them in the order they were sent. Events the broker did not acknowledge
+/// are spooled when there is a spool; should one have been stored after all,
+/// JetStream drops the redelivery as a duplicate of its message id.
+async fn settle_acks(
+    state: &AppState,
+    in_flight: &mut FuturesUnordered<BoxFuture<'static, Acked>>,
+    settled: &mut Vec<Acked>,
+    delivered: &mut Vec<(FactoEvent, ServerEnvelope, Delivery)>,
+) {
+    while let Some(acked) = in_flight.next().await {
+        settled.push(acked);
+    }
+    settled.sort_by_key(|(index, ..)| *index);
+
+    for (_, event, envelope, result) in settled.drain(..) {
+        let e = match result {
+            Ok(sequence) => {
+                delivered.push((event, envelope, Delivery::Published(sequence)));
+                continue;
+            }
+            Err(e) => e,
+        };
+        counter!("facto_publish_ack_failures_total", "sink" => state.sink.name()).increment(1);
+        match state.spool {
+            Some(ref spool) => {
+                warn!(
+                    "{} did not acknowledge {}, spooling: {}",
+                    state.sink.name(),
+                    event.facto_id,
+                    e
+                );
+                let _session =
+                    lock_sessions(state, false, std::iter::once((&event, &envelope))).await;
+                let events = vec![SpooledEvent { event, envelope }];
+                append_all(spool, events, Delivery::Spooled, delivered).await;
+            }
+            None => {
+                error!("Failed to publish to {}: {}", state.sink.name(), e);
+                let delivery = Delivery::Rejected(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::QueueFailed,
+                    QUEUE_FAILED,
+                );
+                delivered.push((event, envelope, delivery));
+            }
+        }
+    }
+}
+
+/// Publish accepted events in order. An event only counts as published once
+/// the broker has acknowledged it. With a spool configured, events are
+/// spooled instead while the sink is unreachable or the spool still holds older
+/// events, so the stream sees events in the order they were accepted. With
+/// the outbox, all events are appended and the client is answered before
+/// any broker is involved.
+///
+/// `ordered` deliveries hold the locks of all their sessions throughout, so
+/// no other request's events for those sessions land in between, and wait
+/// for each acknowledgement before sending the next event. All other
+/// deliveries lock each event's session only while handing it over and
+/// await their acknowledgements together, bounded by the sink's concurrency.
+async fn deliver_all(
+    state: &AppState,
+    events: Vec<(FactoEvent, ServerEnvelope)>,
+    ordered: bool,
+) -> Vec<(FactoEvent, ServerEnvelope, Delivery)> {
+    let mut delivered = Vec::with_capacity(events.len());
+
+    let _ordered_sessions = match ordered {
+        true => lock_sessions(state, false, events.iter().map(|(e, env)| (e, env))).await,
+        false => None,
+    };
+
+    if let (true, Some(ref spool)) = (state.outbox, &state.spool) {
+        let _sessions = lock_sessions(state, ordered, events.iter().map(|(e, env)| (e, env))).await;
+        let events = events
+            .into_iter()
+            .map(|(event, envelope)| SpooledEvent { event, envelope })
+            .collect();
+        append_all(spool, events, Delivery::Queued, &mut delivered).await;
+        return delivered;
+    }
+
+    let connected = state.sink.is_connected().await;
+    let mut pending = events.into_iter().enumerate();
+    let mut in_flight = FuturesUnordered::new();
+    let mut settled = Vec::new();
+
+    while let Some((index, (event, envelope))) = pending.next() {
+        let publish_error = match (connected, &state.spool) {
+            (true, spool) if spool.as_ref().is_none_or(Spool::is_empty) => {
+                // Permits are released as acknowledgements arrive, so keep
+                // collecting them while waiting for one
+                let permit = loop {
+                    tokio::select! {
+                        permit = state.sink_shaper.acquire() => break permit,
+                        Some(acked) = in_flight.next() => settled.push(acked),
+                    }
+                };
+                let _session =
+                    lock_sessions(state, ordered, std::iter::once((&event, &envelope))).await;
+                match state.sink.send(&event, &envelope).await {
+                    Ok(ack) if ordered => match ack.await {
+                        Ok(sequence) => {
+                            delivered.push((event, envelope, Delivery::Published(sequence)));
+                            continue;
+                        }
+                        Err(e) => Some(e),
+                    },
+                    Ok(ack) => {
+                        in_flight.push(
+                            async move {
+                                let result = ack.await;
+                                drop(permit);
+                                (index, event, envelope, result)
+                            }
+                            .boxed(),
+                        );
+                        continue;
+                    }
+                    Err(e) => Some(e),
+                }
+            }
+            _ => None,
+        };
+        // Earlier events are recorded first, and any the broker refused
+        // are spooled ahead of this one
+        settle_acks(state, &mut in_flight, &mut settled, &mut delivered).await;
+
+        let Some(ref spool) = state.spool else {
+            let delivery = match publish_error {
+                Some(e) => {
+                    error!("Failed to publish to {}: {}", state.sink.name(), e);
+                    Delivery::Rejected(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        ErrorCode::QueueFailed,
+                        QUEUE_FAILED,
+                    )
+                }
+                None => Delivery::Rejected(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    ErrorCode::ServiceNotReady,
+                    SERVICE_NOT_READY,
+                ),
+            };
+            delivered.push((event, envelope, delivery));
+            continue;
+        };
+
+        if let Some(e) = publish_error {
+            warn!(
+                "Failed to publish to {}, spooling: {}",
+                state.sink.name(),
+                e
+            );
+        }
+
+        // Spool this event and everything after it to preserve order
+        let rest: Vec<SpooledEvent> = std::iter::once((event, envelope))
+            .chain(pending.by_ref().map(|(_, event)| event))
+            .map(|(event, envelope)| SpooledEvent { event, envelope })
+            .collect();
+        let _sessions =
+            lock_sessions(state, ordered, rest.iter().map(|s| (&s.event, &s.envelope))).await;
+        append_all(spool, rest, Delivery::Spooled, &mut delivered).await;
+    }
+    settle_acks(state, &mut in_flight, &mut settled, &mut delivered).await;
+
+    delivered
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+async fn health_handler() -> impl IntoResponse {
+    Json(HealthResponse {
+        status: "healthy".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    })
+}
+
+async fn openapi_handler() -> impl IntoResponse {
+    Json(openapi::document(env!("CARGO_PKG_VERSION")))
+}
+
+async fn docs_handler() -> impl IntoResponse {
+    Html(openapi::SWAGGER_UI)
+}
+
+async fn ready_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let nats_connected = state.is_nats_connected().await;
+    let kafka_connected = match state.sink.name() {
+        "kafka" => Some(state.sink.is_connected().await),
+        _ => None,
+    };
+    // Ready while a delivery path is healthy: with a spool, events are
+    // still accepted while the sink is down
+    let report = state.health.run(&state).await;
+    let ready = report.healthy && !state.shutdown.is_draining();
+
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(ReadyResponse {
+            ready,
+            nats_connected,
+            kafka_connected,
+            spool_depth: state.spool.as_ref().map(Spool::depth),
+            checks: report.checks,
+        }),
+    )
+}
+
+async fn capabilities_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut capabilities = state.capabilities.clone();
+    capabilities.rate_limit_per_agent = state.rate_limit_per_agent.load(Ordering::Relaxed);
+    Json(capabilities)
+}
+
+async fn metrics_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (StatusCode::OK, state.metrics.render())
+}
+
+async fn ingest_single_handler(
+    State(state): State<Arc<AppState>>,
+    principal: Option<Extension<Principal>>,
+    debug: Option<Extension<DebugTrace>>,
+    encoding: ResponseEncoding,
+    headers: HeaderMap,
+    Query(query): Query<IngestQuery>,
+    LimitedBody(event): LimitedBody<FactoEvent>,
+) -> Response {
+    if let Err(e) = state.limits.check_event(&event) {
+        let tenant = tenant_label(&principal);
+        counter!("facto_ingest_rejected_total", "reason" => e.code().as_str(), "tenant" => tenant)
+            .increment(1);
+        return e.into_response();
+    }
+    let propagated = state.propagation.extract(&headers);
+    let origin = reject_origin(&principal);
+    let received = state.rejects.is_some().then(|| event.clone());
+    let (status, Json(response)) = ingest_single(
+        state.clone(),
+        principal,
+        debug,
+        propagated,
+        event,
+        query.backfill,
+        None,
+    )
+    .await;
+    if let (Some(rejects), Some(event), Some(error)) = (&state.rejects, received, &response.error) {
+        rejects.record(origin.0.as_deref(), origin.1, event, error);
+    }
+    encoding.reply(status, &response)
+}
+
+/// Tenant label of a caller's metrics
+fn tenant_label(principal: &Option<Extension<Principal>>) -> String {
+    principal
+        .as_ref()
+        .and_then(|Extension(p)| p.tenant_id.clone())
+        .unwrap_or_else(|| DEFAULT_TENANT.to_string())
+}
+
+/// Ingest one event, counting its outcome in usage and, when metric
+/// dimensions are enabled, under its agent and action type
+async fn ingest_single(
+    state: Arc<AppState>,
+    principal: Option<Extension<Principal>>,
+    debug: Option<Extension<DebugTrace>>,
+    propagated: BTreeMap<String, String>,
+    event: FactoEvent,
+    backfill: bool,
+    translation: Option<TranslationRecord>,
+) -> (StatusCode, Json<SingleIngestResponse>) {
+    let start = Instant::now();
+    let tenant = tenant_label(&principal);
+    let sandbox = principal.as_ref().is_some_and(|Extension(p)| p.sandbox);
+    let labels = EventLabels::of(&event);
+    let (status, response) = ingest_single_event(
+        state.clone(),
+        principal,
+        debug,
+        propagated,
+        event,
+        backfill,
+        translation,
+    )
+    .await;
+    let outcome = Outcome::of_single(&response);
+    if !sandbox {
+        let now = now_nanos();
+        state
+            .usage
+            .record(&tenant, &labels.agent_id, outcome, labels.bytes, now);
+    }
+    if let Some(ref dimensions) = state.dimensions {
+        dimensions.record_single(&tenant, &labels, outcome, start.elapsed());
+    }
+    (status, response)
+}
+
+async fn ingest_single_event(
+    state: Arc<AppState>,
+    principal: Option<Extension<Principal>>,
+    debug: Option<Extension<DebugTrace>>,
+    propagated: BTreeMap<String, String>,
+    event: FactoEvent,
+    backfill: bool,
+    translation: Option<TranslationRecord>,
+) -> (StatusCode, Json<SingleIngestResponse>) {
+    let start = Instant::now();
+    let debug = debug.map(|Extension(d)| d).unwrap_or_default();
+    debug.stage("parse");
+    let principal = principal.map(|Extension(p)| p);
+    let sandbox = principal.as_ref().is_some_and(|p| p.sandbox);
+    let bound_agent = principal.as_ref().and_then(|p| p.agent_id.clone());
+    let tenant_id = principal.and_then(|p| p.tenant_id);
+    let tenant = tenant_id
+        .clone()
+        .unwrap_or_else(|| DEFAULT_TENANT.to_string());
+    // Sandbox events are rate limited and deduplicated apart from production
+    let key_scope = match sandbox {
+        true => Some(sandbox_scope(tenant_id.as_deref())),
+        false => tenant_id.clone(),
+    };
+    counter!("facto_ingest_requests_total", "type" => "single", "tenant" => tenant.clone())
+        .increment(1);
+
+    // Client certificates only submit events for their own agent
+    if bound_agent.is_some_and(|agent_id| agent_id != event.agent_id) {
+        return reject_event(
+            StatusCode::FORBIDDEN,
+            event.facto_id,
+            EventError::new(ErrorCode::AgentNotAuthorized, AGENT_NOT_AUTHORIZED),
+            &tenant,
+        );
+    }
+
+    // Reject events of paused agents
+    if state
+        .agent_controls
+        .pause(tenant_id.as_deref(), &event.agent_id)
+        .is_some()
+    {
+        return reject_event(
+            StatusCode::LOCKED,
+            event.facto_id,
+            EventError::new(ErrorCode::AgentPaused, AGENT_PAUSED),
+            &tenant,
+        );
+    }
+
+    // Check rate limit
+    if !state
+        .check_rate_limit(tenant_id.as_deref(), key_scope.as_deref(), &event.agent_id)
+        .await
+    {
+        return reject_event(
+            StatusCode::TOO_MANY_REQUESTS,
+            event.facto_id,
+            EventError::new(ErrorCode::RateLimited, RATE_LIMITED),
+            &tenant,
+        );
+    }
+
+    // Check the agent's byte quota
+    let bytes = event_size(&event);
+    if !state.admit_agent_bytes(
+        tenant_id.as_deref(),
+        key_scope.as_deref(),
+        &event.agent_id,
+        bytes,
+    ) {
+        return reject_event(
+            StatusCode::TOO_MANY_REQUESTS,
+            event.facto_id,
+            EventError::new(ErrorCode::AgentByteQuotaExceeded, AGENT_BYTE_QUOTA_EXCEEDED),
+            &tenant,
+        );
+    }
+    counter!("facto_ingest_bytes_total", "tenant" => tenant.clone()).increment(bytes);
+
+    // Check tenant rate limit and byte quota
+    if let Some(ref tenant_id) = tenant_id {
+        if let Err(rejection) = state.tenants.admit(tenant_id, bytes) {
+            return reject_event(
+                StatusCode::TOO_MANY_REQUESTS,
+                event.facto_id,
+                tenant_rejection_error(rejection),
+                &tenant,
+            );
+        }
+    }
+
+    // Reject events for frozen sessions
+    if !sandbox
+        && state
+            .freezes
+            .is_frozen(tenant_id.as_deref(), &event.session_id)
+    {
+        return reject_event(
+            StatusCode::LOCKED,
+            event.facto_id,
+            EventError::new(ErrorCode::SessionFrozen, SESSION_FROZEN),
+            &tenant,
+        );
+    }
+
+    // Reject events of sessions routed to another replica
+    if let Some(error) = misrouted(&state, &event, sandbox) {
+        return reject_event(
+            StatusCode::MISDIRECTED_REQUEST,
+            event.facto_id,
+            error,
+            &tenant,
+        );
+    }
+
+    // Reject events outside the freshness window before verifying them
+    let fresh = match sandbox {
+        true => Ok(()),
+        false => check_fresh(&state, &event, backfill, now_nanos()),
+    };
+    if let Err(rejection) = fresh {
+        return reject_event(
+            StatusCode::BAD_REQUEST,
+            event.facto_id,
+            rejection.into(),
+            &tenant,
+        );
+    }
+
+    // Check payloads against the schema registered for the action type
+    let schema_violations = match check_schema(&state, &event, &tenant) {
+        Ok(violations) => violations,
+        Err(error) => {
+            return reject_event(StatusCode::BAD_REQUEST, event.facto_id, error, &tenant);
+        }
+    };
+    let model_attestation = match check_model(&state, &event, &tenant) {
+        Ok(attestation) => attestation,
+        Err(error) => {
+            return reject_event(StatusCode::BAD_REQUEST, event.facto_id, error, &tenant);
+        }
+    };
+    let policy_findings = match check_policy(&state, &event, &tenant) {
+        Ok(findings) => findings,
+        Err(error) => {
+            return reject_event(StatusCode::BAD_REQUEST, event.facto_id, error, &tenant);
+        }
+    };
+    let rotation = match check_key_rotation(&state, tenant_id.as_deref(), &event) {
+        Ok(rotation) => rotation,
+        Err(error) => {
+            return reject_event(StatusCode::BAD_REQUEST, event.facto_id, error, &tenant);
+        }
+    };
+
+    debug.stage("admission");
+
+    // Validate event
+    let (mode, _) = state.enforcement_mode(tenant_id.as_deref(), &event.agent_id);
+    let (verification, verification_status) =
+        match validate_event(&state, tenant_id.as_deref(), &event, mode, sandbox).await {
+            Ok(verified) => verified,
+            Err(error) => {
+                return reject_event(
+                    StatusCode::BAD_REQUEST,
+                    event.facto_id,
+                    error.into(),
+                    &tenant,
+                );
+            }
+        };
+
+    debug.stage("verification");
+    count_unverified(&verification_status, mode, &tenant);
+    if let Err(error) = check_rotation_verified(&rotation, &verification_status) {
+        return reject_event(StatusCode::BAD_REQUEST, event.facto_id, error, &tenant);
+    }
+
+    if let Some(ref shadow) = state.shadow {
+        shadow.observe(&event, &verification.event_hash);
+    }
+
+    // Skip events that were already accepted
+    let dedup_key = scoped_id(key_scope.as_deref(), &event.facto_id);
+    match state
+        .dedup
+        .claim(&dedup_key, &verification.event_hash)
+        .await
+    {
+        DedupOutcome::New => {}
+        DedupOutcome::Duplicate => {
+            counter!("facto_ingest_duplicates_total", "tenant" => tenant.clone()).increment(1);
+            return (
+                StatusCode::OK,
+                Json(SingleIngestResponse {
+                    accepted: true,
+                    facto_id: event.facto_id,
+                    duplicate: true,
+                    spooled: false,
+                    held: false,
+                    reason: None,
+                    error: None,
+                    receipt: state.receipts.get(&dedup_key),
+                }),
+            );
+        }
+        DedupOutcome::Conflict => {
+            return reject_event(
+                StatusCode::CONFLICT,
+                event.facto_id,
+                EventError::new(ErrorCode::FactoIdConflict, FACTO_ID_CONFLICT),
+                &tenant,
+            );
+        }
+    }
+
+    // Reject replays of events accepted before the dedup window. Backfilled
+    // events are remembered for the replay window from when they arrive.
+    let replay_key = scoped_id(key_scope.as_deref(), &verification.event_hash);
+    let now = now_nanos();
+    let claimed = match sandbox {
+        true => Ok(()),
+        false => {
+            let completed_at = match backfill {
+                true => now,
+                false => event.completed_at,
+            };
+            state.replay.claim(&replay_key, completed_at, now).await
+        }
+    };
+    if let Err(rejection) = claimed {
+        state.dedup.release(&dedup_key).await;
+        return reject_event(
+            StatusCode::CONFLICT,
+            event.facto_id,
+            rejection.into(),
+            &tenant,
+        );
+    }
+
+    debug.stage("dedup");
+
+    // Redact payloads under the tenant's policy, then move large ones to the
+    // blob store before publishing
+    let mut event = event;
+    let redaction = state.redactions.redact(&tenant, &mut event);
+    if let Some(ref offloader) = state.offloader {
+        if offloader.offload(&mut event).await.is_err() {
+            state.dedup.release(&dedup_key).await;
+            state.replay.release(&replay_key).await;
+            return reject_event(
+                StatusCode::SERVICE_UNAVAILABLE,
+                event.facto_id,
+                EventError::new(ErrorCode::BlobStoreFailed, BLOB_STORE_FAILED),
+                &tenant,
+            );
+        }
+        debug.stage("offload");
+    }
+
+    let envelope = ServerEnvelope {
+        received_at: now_nanos(),
+        headers: message_headers(&state, &propagated, &event, tenant_id.as_deref()),
+        redaction,
+        verification,
+        data_integrity: verification_status
+            .is_verified()
+            .then(|| DataIntegrityProof::of(&event))
+            .flatten(),
+        verification_status,
+        tenant_id,
+        sandbox,
+        schema_violations,
+        policy_findings,
+        model_attestation,
+        backfill: None,
+        translation,
+    };
+
+    // Hold backfilled events until their session's chain reaches them
+    if backfill && !sandbox {
+        let session = session_key(&event, &envelope);
+        let (facto_id, session_id) = (event.facto_id.clone(), event.session_id.clone());
+        let receipt = sign_receipt(&facto_id, &envelope, None, state.verifier.signer());
+        let held = HeldEvent {
+            event,
+            envelope,
+            rotation,
+        };
+        if state.backfill.hold(&session, held, now_nanos()).is_err() {
+            state.dedup.release(&dedup_key).await;
+            state.replay.release(&replay_key).await;
+            return reject_event(
+                StatusCode::SERVICE_UNAVAILABLE,
+                facto_id,
+                EventError::new(ErrorCode::BackfillFull, BACKFILL_FULL),
+                &tenant,
+            );
+        }
+        state.receipts.keep(dedup_key, receipt.clone());
+        release_backfill(&state, &session, &session_id, false).await;
+        debug.stage("backfill");
+
+        counter!("facto_ingest_accepted_total", "tenant" => tenant.clone()).increment(1);
+        counter!("facto_ingest_held_total", "tenant" => tenant.clone()).increment(1);
+        histogram!("facto_ingest_duration_seconds").record(start.elapsed().as_secs_f64());
+        return (
+            StatusCode::ACCEPTED,
+            Json(SingleIngestResponse {
+                accepted: true,
+                facto_id,
+                duplicate: false,
+                spooled: false,
+                held: true,
+                reason: None,
+                error: None,
+                receipt: Some(receipt),
+            }),
+        );
+    }
+
+    // Publish and wait for the broker's acknowledgement, or spool while it
+    // is unavailable
+    let (event, envelope, delivery) = deliver_all(&state, vec![(event, envelope)], false)
+        .await
+        .remove(0);
+    debug.stage("delivery");
+    let (spooled, sequence) = match delivery {
+        Delivery::Published(sequence) => (false, sequence),
+        Delivery::Queued => (false, None),
+        Delivery::Spooled => {
+            counter!("facto_ingest_spooled_total", "tenant" => tenant.clone()).increment(1);
+            (true, None)
+        }
+        Delivery::Rejected(status, code, reason) => {
+            state.dedup.release(&dedup_key).await;
+            state.replay.release(&replay_key).await;
+            return reject_event(
+                status,
+                event.facto_id,
+                EventError::new(code, reason),
+                &tenant,
+            );
+        }
+    };
+
+    if !sandbox {
+        state
+            .chain_heads
+            .advance(
+                &session_key(&event, &envelope),
+                &event,
+                &envelope.verification.event_hash,
+            )
+            .await;
+        state.agents.saw_event(
+            envelope.tenant_id.as_deref(),
+            &event.agent_id,
+            envelope.received_at,
+        );
+        state.fanout.dispatch(&event, &envelope);
+        state.checkpoints.record(
+            scoped_id(envelope.tenant_id.as_deref(), &event.facto_id),
+            &event.facto_id,
+            &envelope.verification.event_hash,
+        );
+        if let Some(ref rotation) = rotation {
+            apply_key_rotation(&state, &envelope, &event, rotation);
+        }
+    }
+
+    let receipt = state.receipts.issue(
+        dedup_key,
+        &event.facto_id,
+        &envelope,
+        sequence,
+        state.verifier.signer(),
+    );
+    counter!("facto_ingest_accepted_total", "tenant" => tenant.clone()).increment(1);
+    histogram!("facto_ingest_duration_seconds").record(start.elapsed().as_secs_f64());
+
+    (
+        StatusCode::ACCEPTED,
+        Json(SingleIngestResponse {
+            accepted: true,
+            facto_id: event.facto_id,
+            duplicate: false,
+            spooled,
+            held: false,
+            reason: None,
+            error: None,
+            receipt: Some(receipt),
+        }),
+    )
+}
+
+async fn ingest_batch_handler(
+    State(state): State<Arc<AppState>>,
+    principal: Option<Extension<Principal>>,
+    debug: Option<Extension<DebugTrace>>,
+    encoding: ResponseEncoding,
+    headers: HeaderMap,
+    Query(query): Query<IngestQuery>,
+    LimitedBody(request): LimitedBody<BatchIngestRequest>,
+) -> Response {
+    if let Err(e) = state.limits.check_batch(&request.events) {
+        let tenant = tenant_label(&principal);
+        counter!("facto_ingest_rejected_total", "reason" => e.code().as_str(), "tenant" => tenant)
+            .increment(1);
+        return e.into_response();
+    }
+    let propagated = state.propagation.extract(&headers);
+    let origin = reject_origin(&principal);
+    let received = state.rejects.is_some().then(|| request.events.clone());
+    let (status, Json(response)) = ingest_batch(
+        state.clone(),
+        principal,
+        debug,
+        propagated,
+        request,
+        query.backfill,
+    )
+    .await;
+    if let (Some(rejects), Some(events)) = (&state.rejects, received) {
+        let mut events: HashMap<String, FactoEvent> = events
+            .into_iter()
+            .rev()
+            .map(|event| (event.facto_id.clone(), event))
+            .collect();
+        for rejection in &response.rejected {
+            if let Some(event) = events.remove(&rejection.facto_id) {
+                rejects.record(origin.0.as_deref(), origin.1, event, &rejection.error);
+            }
+        }
+    }
+    encoding.reply(status, &response)
+}
+
+/// Ingest a batch, counting each event's outcome in usage and, when metric
+/// dimensions are enabled, under its agent and action type
+async fn ingest_batch(
+    state: Arc<AppState>,
+    principal: Option<Extension<Principal>>,
+    debug: Option<Extension<DebugTrace>>,
+    propagated: BTreeMap<String, String>,
+    request: BatchIngestRequest,
+    backfill: bool,
+) -> (StatusCode, Json<BatchIngestResponse>) {
+    let start = Instant::now();
+    let tenant = tenant_label(&principal);
+    let sandbox = principal.as_ref().is_some_and(|Extension(p)| p.sandbox);
+    let labels: Vec<EventLabels> = request.events.iter().map(EventLabels::of).collect();
+    let (status, response) = ingest_batch_events(
+        state.clone(),
+        principal,
+        debug,
+        propagated,
+        request,
+        backfill,
+    )
+    .await;
+    let outcomes = Outcome::of_batch(&labels, &response);
+    if !sandbox {
+        let now = now_nanos();
+        for (event, outcome) in labels.iter().zip(&outcomes) {
+            state
+                .usage
+                .record(&tenant, &event.agent_id, *outcome, event.bytes, now);
+        }
+    }
+    if let Some(ref dimensions) = state.dimensions {
+        dimensions.record_batch(&tenant, &labels, &outcomes, start.elapsed());
+    }
+    (status, response)
+}
+
+async fn ingest_batch_events(
+    state: Arc<AppState>,
+    principal: Option<Extension<Principal>>,
+    debug: Option<Extension<DebugTrace>>,
+    propagated: BTreeMap<String, String>,
+    request: BatchIngestRequest,
+    backfill: bool,
+) -> (StatusCode, Json<BatchIngestResponse>) {
+    let start = Instant::now();
+    let debug = debug.map(|Extension(d)| d).unwrap_or_default();
+    debug.stage("parse");
+    let total_events = request.events.len();
+    let ordered = request.ordered;
+    let principal = principal.map(|Extension(p)| p);
+    let sandbox = principal.as_ref().is_some_and(|p| p.sandbox);
+    let bound_agent = principal.as_ref().and_then(|p| p.agent_id.clone());
+    let tenant_id = principal.and_then(|p| p.tenant_id);
+    let tenant = tenant_id
+        .clone()
+        .unwrap_or_else(|| DEFAULT_TENANT.to_string());
+    // Sandbox events are rate limited and deduplicated apart from production
+    let key_scope = match sandbox {
+        true => Some(sandbox_scope(tenant_id.as_deref())),
+        false => tenant_id.clone(),
+    };
+    counter!("facto_ingest_requests_total", "type" => "batch", "tenant" => tenant.clone())
+        .increment(1);
+    counter!("facto_ingest_events_received_total", "tenant" => tenant.clone())
+        .increment(total_events as u64);
+
+    let mut accepted_count = 0;
+    let mut rejected: Vec<RejectedEvent> = Vec::new();
+    let mut duplicates: Vec<String> = Vec::new();
+    let mut accepted_events: Vec<(FactoEvent, ServerEnvelope)> = Vec::new();
+    let received_at = now_nanos();
+
+    // A batch envelope that fails verification rejects the whole batch; a
+    // valid one stands in for the event signatures of envelope-trusted
+    // agents made with its key
+    let (envelope, envelope_error) = match request.envelope {
+        None => (None, None),
+        Some(envelope) => match crypto::verify_batch_envelope(&envelope, &request.events) {
+            Ok(()) => {
+                counter!("facto_batch_envelopes_total", "outcome" => "valid").increment(1);
+                // Published with each event it vouches for
+                let event_hashes: Vec<String> = request
+                    .events
+                    .iter()
+                    .map(|event| event.proof.event_hash.clone())
+                    .collect();
+                (Some((envelope, event_hashes)), None)
+            }
+            Err(e) => {
+                counter!("facto_batch_envelopes_total", "outcome" => "invalid").increment(1);
+                let mut error = EventError::from(e);
+                error.message = format!("Invalid batch envelope: {}", error.message);
+                (None, Some(error))
+            }
+        },
+    };
+
+    // Check rate limits and freezes first so those events are not validated
+    let mut admitted: Vec<FactoEvent> = Vec::with_capacity(total_events);
+    for event in request.events {
+        if let Some(ref error) = envelope_error {
+            rejected.push(RejectedEvent::new(event.facto_id, error.clone()));
+            continue;
+        }
+        if bound_agent
+            .as_ref()
+            .is_some_and(|agent_id| *agent_id != event.agent_id)
+        {
+            rejected.push(RejectedEvent::new(
+                event.facto_id,
+                EventError::new(ErrorCode::AgentNotAuthorized, AGENT_NOT_AUTHORIZED),
+            ));
+            continue;
+        }
+        if state
+            .agent_controls
+            .pause(tenant_id.as_deref(), &event.agent_id)
+            .is_some()
+        {
+            rejected.push(RejectedEvent::new(
+                event.facto_id,
+                EventError::new(ErrorCode::AgentPaused, AGENT_PAUSED),
+            ));
+            continue;
+        }
+        if !state
+            .check_rate_limit(tenant_id.as_deref(), key_scope.as_deref(), &event.agent_id)
+            .await
+        {
+            rejected.push(RejectedEvent::new(
+                event.facto_id,
+                EventError::new(ErrorCode::RateLimited, RATE_LIMITED),
+            ));
+            continue;
+        }
+        let bytes = event_size(&event);
+        if !state.admit_agent_bytes(
+            tenant_id.as_deref(),
+            key_scope.as_deref(),
+            &event.agent_id,
+            bytes,
+        ) {
+            rejected.push(RejectedEvent::new(
+                event.facto_id,
+                EventError::new(ErrorCode::AgentByteQuotaExceeded, AGENT_BYTE_QUOTA_EXCEEDED),
+            ));
+            continue;
+        }
+        counter!("facto_ingest_bytes_total", "tenant" => tenant.clone()).increment(bytes);
+        if let Some(ref tenant_id) = tenant_id {
+            if let Err(rejection) = state.tenants.admit(tenant_id, bytes) {
+                rejected.push(RejectedEvent::new(
+                    event.facto_id,
+                    tenant_rejection_error(rejection),
+                ));
+                continue;
+            }
+        }
+        if sandbox {
+            admitted.push(event);
+            continue;
+        }
+        if state
+            .freezes
+            .is_frozen(tenant_id.as_deref(), &event.session_id)
+        {
+            rejected.push(RejectedEvent::new(
+                event.facto_id,
+                EventError::new(ErrorCode::SessionFrozen, SESSION_FROZEN),
+            ));
+            continue;
+        }
+        if let Some(error) = misrouted(&state, &event, sandbox) {
+            rejected.push(RejectedEvent::new(event.facto_id, error));
+            continue;
+        }
+        if let Err(rejection) = check_fresh(&state, &event, backfill, received_at) {
+            rejected.push(RejectedEvent::new(event.facto_id, rejection.into()));
+            continue;
+        }
+        admitted.push(event);
+    }
+
+    debug.stage("admission");
+
+    // Validate payloads side by side, so a slow event holds up no other
+    let facto_ids: Vec<String> = admitted
+        .iter()
+        .map(|event| event.facto_id.clone())
+        .collect();
+    let checked = state.clone();
+    let checked_tenant_id = tenant_id.clone();
+    let checked_tenant = tenant.clone();
+    let validated = state
+        .validation
+        .run(admitted, move |event| {
+            let checks = check_payloads(
+                &checked,
+                &event,
+                checked_tenant_id.as_deref(),
+                &checked_tenant,
+            );
+            (event, checks)
+        })
+        .await;
+    let mut to_verify: Vec<FactoEvent> = Vec::with_capacity(validated.len());
+    let mut payload_checks: Vec<PayloadChecks> = Vec::with_capacity(validated.len());
+    // Attestations of key rotation events, by facto_id
+    let mut rotations: HashMap<String, KeyRotation> = HashMap::new();
+    for (facto_id, outcome) in facto_ids.into_iter().zip(validated) {
+        match outcome {
+            Ok((event, Ok(mut checks))) => {
+                if let Some(rotation) = checks.rotation.take() {
+                    rotations.insert(event.facto_id.clone(), rotation);
+                }
+                to_verify.push(event);
+                payload_checks.push(checks);
+            }
+            Ok((event, Err(error))) => rejected.push(RejectedEvent::new(event.facto_id, error)),
+            Err(ValidationTimeout) => rejected.push(RejectedEvent::new(
+                facto_id,
+                EventError::new(ErrorCode::Timeout, VALIDATION_TIMEOUT).with_details(
+                    serde_json::json!({
+                        "timeout_ms": state.validation.timeout().as_millis() as u64
+                    }),
+                ),
+            )),
+        }
+    }
+
+    debug.stage("validation");
+
+    // Validate all remaining events under their agents' enforcement modes
+    let modes: Vec<EnforcementMode> = to_verify
+        .iter()
+        .map(|event| {
+            state
+                .enforcement_mode(tenant_id.as_deref(), &event.agent_id)
+                .0
+        })
+        .collect();
+    let enveloped: Vec<bool> = to_verify
+        .iter()
+        .map(|event| {
+            envelope.as_ref().is_some_and(|(envelope, _)| {
+                envelope.public_key == event.proof.public_key
+                    && state
+                        .agent_controls
+                        .envelope_trusted(tenant_id.as_deref(), &event.agent_id)
+            })
+        })
+        .collect();
+    let outcomes = state
+        .verifier(sandbox)
+        .verify_enveloped(
+            tenant_id.as_deref(),
+            &to_verify,
+            &modes,
+            envelope.as_ref().map(|(envelope, event_hashes)| {
+                (envelope, event_hashes.as_slice(), enveloped.as_slice())
+            }),
+        )
+        .instrument(info_span!("verify_signatures", events = to_verify.len()))
+        .await;
+    debug.stage("verification");
+    for (((event, outcome), mode), checks) in to_verify
+        .into_iter()
+        .zip(outcomes)
+        .zip(modes)
+        .zip(payload_checks)
+    {
+        match outcome {
+            Ok((verification, verification_status)) => {
+                count_unverified(&verification_status, mode, &tenant);
+                let rotation = rotations.get(&event.facto_id).cloned();
+                if let Err(error) = check_rotation_verified(&rotation, &verification_status) {
+                    rejected.push(RejectedEvent::new(event.facto_id, error));
+                    continue;
+                }
+                if let Some(ref shadow) = state.shadow {
+                    shadow.observe(&event, &verification.event_hash);
+                }
+                let dedup_key = scoped_id(key_scope.as_deref(), &event.facto_id);
+                match state
+                    .dedup
+                    .claim(&dedup_key, &verification.event_hash)
+                    .await
+                {
+                    DedupOutcome::New => {
+                        let replay_key = scoped_id(key_scope.as_deref(), &verification.event_hash);
+                        let claimed = match sandbox {
+                            true => Ok(()),
+                            false => {
+                                let completed_at = match backfill {
+                                    true => received_at,
+                                    false => event.completed_at,
+                                };
+                                state
+                                    .replay
+                                    .claim(&replay_key, completed_at, received_at)
+                                    .await
+                            }
+                        };
+                        if let Err(rejection) = claimed {
+                            state.dedup.release(&dedup_key).await;
+                            rejected.push(RejectedEvent::new(event.facto_id, rejection.into()));
+                            continue;
+                        }
+                        let envelope = ServerEnvelope {
+                            received_at,
+                            verification,
+                            data_integrity: verification_status
+                                .is_verified()
+                                .then(|| DataIntegrityProof::of(&event))
+                                .flatten(),
+                            verification_status,
+                            tenant_id: tenant_id.clone(),
+                            sandbox,
+                            schema_violations: checks.schema_violations,
+                            policy_findings: checks.policy_findings,
+                            model_attestation: checks.model_attestation,
+                            headers: message_headers(
+                                &state,
+                                &propagated,
+                                &event,
+                                tenant_id.as_deref(),
+                            ),
+                            redaction: None,
+                            backfill: None,
+                            translation: None,
+                        };
+                        accepted_events.push((event, envelope));
+                    }
+                    DedupOutcome::Duplicate => duplicates.push(event.facto_id),
+                    DedupOutcome::Conflict => rejected.push(RejectedEvent::new(
+                        event.facto_id,
+                        EventError::new(ErrorCode::FactoIdConflict, FACTO_ID_CONFLICT),
+                    )),
+                }
+            }
+            Err(error) => {
+                rejected.push(RejectedEvent::new(event.facto_id, error.into()));
+            }
+        }
+    }
+
+    debug.stage("dedup");
+
+    // Redact payloads under the tenant's policy before they leave the server
+    for (event, envelope) in accepted_events.iter_mut() {
+        envelope.redaction = state.redactions.redact(&tenant, event);
+    }
+
+    // Move large payloads to the blob store before publishing
+    if let Some(ref offloader) = state.offloader {
+        let outcomes = futures::future::join_all(
+            accepted_events
+                .iter_mut()
+                .map(|(event, _)| offloader.offload(event)),
+        )
+        .await;
+        let mut offloaded = Vec::with_capacity(accepted_events.len());
+        for ((event, envelope), outcome) in accepted_events.into_iter().zip(outcomes) {
+            if outcome.is_ok() {
+                offloaded.push((event, envelope));
+                continue;
+            }
+            state
+                .dedup
+                .release(&scoped_id(key_scope.as_deref(), &event.facto_id))
+                .await;
+            state
+                .replay
+                .release(&scoped_id(
+                    key_scope.as_deref(),
+                    &envelope.verification.event_hash,
+                ))
+                .await;
+            rejected.push(RejectedEvent::new(
+                event.facto_id,
+                EventError::new(ErrorCode::BlobStoreFailed, BLOB_STORE_FAILED),
+            ));
+        }
+        accepted_events = offloaded;
+        debug.stage("offload");
+    }
+
+    // Hold backfilled events until their sessions' chains reach them
+    let mut held_count = 0;
+    let mut receipts = Vec::new();
+    if backfill && !sandbox {
+        let mut sessions = BTreeMap::new();
+        for (event, envelope) in std::mem::take(&mut accepted_events) {
+            let session = session_key(&event, &envelope);
+            let (facto_id, session_id) = (event.facto_id.clone(), event.session_id.clone());
+            let replay_key = scoped_id(key_scope.as_deref(), &envelope.verification.event_hash);
+            let receipt = sign_receipt(&facto_id, &envelope, None, state.verifier.signer());
+            let held = HeldEvent {
+                rotation: rotations.remove(&event.facto_id),
+                event,
+                envelope,
+            };
+            if state.backfill.hold(&session, held, received_at).is_err() {
+                state
+                    .dedup
+                    .release(&scoped_id(key_scope.as_deref(), &facto_id))
+                    .await;
+                state.replay.release(&replay_key).await;
+                rejected.push(RejectedEvent::new(
+                    facto_id,
+                    EventError::new(ErrorCode::BackfillFull, BACKFILL_FULL),
+                ));
+                continue;
+            }
+            held_count += 1;
+            state
+                .receipts
+                .keep(scoped_id(key_scope.as_deref(), &facto_id), receipt.clone());
+            receipts.push(receipt);
+            sessions.insert(session, session_id);
+        }
+        for (session, session_id) in sessions {
+            release_backfill(&state, &session, &session_id, false).await;
+        }
+        accepted_count += held_count;
+        debug.stage("backfill");
+    }
+
+    // Publish accepted events and wait for their acknowledgements, or spool
+    // them while the broker is unavailable
+    let mut spooled_count = 0;
+    let delivered = deliver_all(&state, accepted_events, ordered).await;
+    debug.stage("delivery");
+    for (event, envelope, delivery) in delivered {
+        match delivery {
+            Delivery::Published(_) | Delivery::Spooled | Delivery::Queued => {
+                if matches!(delivery, Delivery::Spooled) {
+                    spooled_count += 1;
+                }
+                let sequence = match delivery {
+                    Delivery::Published(sequence) => sequence,
+                    _ => None,
+                };
+                receipts.push(state.receipts.issue(
+                    scoped_id(key_scope.as_deref(), &event.facto_id),
+                    &event.facto_id,
+                    &envelope,
+                    sequence,
+                    state.verifier.signer(),
+                ));
+                if !sandbox {
+                    state
+                        .chain_heads
+                        .advance(
+                            &session_key(&event, &envelope),
+                            &event,
+                            &envelope.verification.event_hash,
+                        )
+                        .await;
+                    state.agents.saw_event(
+                        envelope.tenant_id.as_deref(),
+                        &event.agent_id,
+                        envelope.received_at,
+                    );
+                    state.fanout.dispatch(&event, &envelope);
+                    state.checkpoints.record(
+                        scoped_id(envelope.tenant_id.as_deref(), &event.facto_id),
+                        &event.facto_id,
+                        &envelope.verification.event_hash,
+                    );
+                    if let Some(rotation) = rotations.remove(&event.facto_id) {
+                        apply_key_rotation(&state, &envelope, &event, &rotation);
+                    }
+                }
+                accepted_count += 1;
+            }
+            Delivery::Rejected(_, code, reason) => {
+                state
+                    .dedup
+                    .release(&scoped_id(key_scope.as_deref(), &event.facto_id))
+                    .await;
+                state
+                    .replay
+                    .release(&scoped_id(
+                        key_scope.as_deref(),
+                        &envelope.verification.event_hash,
+                    ))
+                    .await;
+                rejected.push(RejectedEvent::new(
+                    event.facto_id,
+                    EventError::new(code, reason),
+                ));
+            }
+        }
+    }
+
+    let rejected_count = rejected.len();
+    accepted_count += duplicates.len();
+
+    counter!("facto_ingest_accepted_total", "tenant" => tenant.clone())
+        .increment((accepted_count - duplicates.len()) as u64);
+    counter!("facto_ingest_duplicates_total", "tenant" => tenant.clone())
+        .increment(duplicates.len() as u64);
+    counter!("facto_ingest_spooled_total", "tenant" => tenant.clone())
+        .increment(spooled_count as u64);
+    counter!("facto_ingest_held_total", "tenant" => tenant.clone()).increment(held_count as u64);
+    for rejection in &rejected {
+        counter!("facto_ingest_rejected_total", "reason" => rejection.error.code.as_str(), "tenant" => tenant.clone())
+            .increment(1);
+    }
+    histogram!("facto_ingest_duration_seconds").record(start.elapsed().as_secs_f64());
+    histogram!("facto_ingest_batch_size").record(total_events as f64);
+
+    (
+        StatusCode::ACCEPTED,
+        Json(BatchIngestResponse {
+            accepted_count,
+            rejected_count,
+            rejected,
+            duplicates,
+            spooled_count,
+            held_count,
+            ordering: ordered.then(OrderingGuarantee::session),
+            receipts,
+        }),
+    )
+}
+
+/// Publish the held events of a session that continue its chain, or all of
+/// them with `all`. Events the sink refused are held again.
+async fn release_backfill(state: &AppState, session: &str, session_id: &str, all: bool) {
+    let _releasing = state.backfill.lock(session).await;
+    let head = state.chain_heads.get(session).await;
+    let released = state.backfill.take(
+        session,
+        head.as_ref().map(|head| head.event_hash.as_str()),
+        now_nanos(),
+        all,
+    );
+    if released.is_empty() {
+        return;
+    }
+
+    let mut rotations = HashMap::new();
+    let events = released
+        .into_iter()
+        .map(|held| {
+            if let Some(rotation) = held.rotation {
+                rotations.insert(held.event.facto_id.clone(), rotation);
+            }
+            (held.event, held.envelope)
+        })
+        .collect();
+    let mut refused = Vec::new();
+    for (event, envelope, delivery) in deliver_all(state, events, true).await {
+        let rotation = rotations.remove(&event.facto_id);
+        if let Delivery::Rejected(..) = delivery {
+            refused.push(HeldEvent {
+                event,
+                envelope,
+                rotation,
+            });
+            continue;
+        }
+        let chain = match envelope.backfill.as_ref().map(|record| record.chain) {
+            Some(ChainLink::Gap) => "gap",
+            _ => "linked",
+        };
+        counter!("facto_backfill_released_total", "chain" => chain).increment(1);
+        if let Delivery::Published(Some(sequence)) = delivery {
+            state.receipts.issue(
+                receipt_key(&envelope, &event.facto_id),
+                &event.facto_id,
+                &envelope,
+                Some(sequence),
+                state.verifier.signer(),
+            );
+        }
+        state
+            .chain_heads
+            .advance(
+                &session_key(&event, &envelope),
+                &event,
+                &envelope.verification.event_hash,
+            )
+            .await;
+        state.agents.saw_event(
+            envelope.tenant_id.as_deref(),
+            &event.agent_id,
+            envelope.received_at,
+        );
+        state.fanout.dispatch(&event, &envelope);
+        state.checkpoints.record(
+            scoped_id(envelope.tenant_id.as_deref(), &event.facto_id),
+            &event.facto_id,
+            &envelope.verification.event_hash,
+        );
+        if let Some(ref rotation) = rotation {
+            apply_key_rotation(state, &envelope, &event, rotation);
+        }
+    }
+    if !refused.is_empty() {
+        warn!(
+            "{} backfilled events of session {} were not delivered, holding them again",
+            refused.len(),
+            session_id
+        );
+        state.backfill.restore(session, refused);
+    }
+}
+
+/// Release held events whose predecessors were published since, from any
+/// request or replica, and give up on the gaps of sessions that waited out
+/// the backfill timeout
+async fn run_backfill(state: Arc<AppState>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        for (session, session_id) in state.backfill.sessions() {
+            release_backfill(&state, &session, &session_id, false).await;
+        }
+        gauge!("facto_backfill_held_events").set(state.backfill.len() as f64);
+    }
+}
+
+// ============================================================================
+// NATS Connection
+// ============================================================================
+
+/// Create a stream, or add subjects missing from an existing one
+async fn ensure_stream(
+    jetstream: &async_nats::jetstream::Context,
+    config: async_nats::jetstream::stream::Config,
+) {
+    let name = config.name.clone();
+    let wanted = config.subjects.clone();
+    let stream = match jetstream.get_or_create_stream(config).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            error!("Failed to create stream {}: {}", name, e);
+            return;
+        }
+    };
+
+    let mut existing = stream.cached_info().config.clone();
+    let missing: Vec<String> = wanted
+        .into_iter()
+        .filter(|s| !existing.subjects.contains(s))
+        .collect();
+    if missing.is_empty() {
+        info!("{} stream ready", name);
+        return;
+    }
+
+    info!("Adding subjects {:?} to stream {}", missing, name);
+    existing.subjects.extend(missing);
+    if let Err(e) = jetstream.update_stream(&existing).await {
+        error!("Failed to update stream {} subjects: {}", name, e);
+    }
+}
+
+async fn connect_to_nats(state: Arc<AppState>, nats: NatsConfig) {
+    loop {
+        info!("Connecting to NATS at {}", nats.describe());
+
+        // Options are rebuilt on every attempt so a rotated credentials file
+        // is read again
+        let connected = match nats.options().await {
+            Ok(options) => options
+                .connect(&nats.url)
+                .await
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(anyhow::anyhow!("Failed to read NATS credentials: {}", e)),
+        };
+        match connected {
+            Ok(client) => {
+                info!("Connected to NATS successfully");
+
+                // Create JetStream context and ensure stream exists
+                let jetstream = async_nats::jetstream::new(client.clone());
+
+                // Create or update the FACTO_EVENTS stream
+                ensure_stream(
+                    &jetstream,
+                    async_nats::jetstream::stream::Config {
+                        name: tenants::EVENT_STREAM.to_string(),
+                        subjects: tenants::EVENT_STREAM_SUBJECTS
+                            .iter()
+                            .map(|s| s.to_string())
+                            .collect(),
+                        retention: async_nats::jetstream::stream::RetentionPolicy::WorkQueue,
+                        storage: async_nats::jetstream::stream::StorageType::File,
+                        max_messages: 10_000_000,
+                        max_bytes: 10 * 1024 * 1024 * 1024, // 10GB
+                        ..Default::default()
+                    },
+                )
+                .await;
+
+                // Create or update the FACTO_CONTROL stream for session seals,
+                // owner notifications and checkpoints
+                ensure_stream(
+                    &jetstream,
+                    async_nats::jetstream::stream::Config {
+                        name: "FACTO_CONTROL".to_string(),
+                        subjects: vec![
+                            "facto.control.>".to_string(),
+                            "facto.notifications.>".to_string(),
+                            CHECKPOINT_SUBJECT.to_string(),
+                        ],
+                        storage: async_nats::jetstream::stream::StorageType::File,
+                        max_bytes: 1024 * 1024 * 1024, // 1GB
+                        ..Default::default()
+                    },
+                )
+                .await;
+
+                // Create or update the FACTO_SANDBOX stream, which expires
+                // sandbox events after their retention
+                if let Some(ref sandbox) = state.sandbox {
+                    ensure_stream(&jetstream, sandbox.stream_config()).await;
+                }
+
+                // Create or update the FACTO_FEED stream cursors read from
+                if let Some(ref cursors) = state.cursors {
+                    ensure_stream(&jetstream, cursors.stream_config()).await;
+                    cursors.connect(jetstream.clone());
+                }
+
+                // Create or update the FACTO_REJECTS dead-letter stream
+                if let Some(ref rejects) = state.rejects {
+                    ensure_stream(&jetstream, rejects.stream_config()).await;
+                    rejects.connect(jetstream.clone());
+                }
+
+                // Open the buckets of state shared with other replicas
+                for bucket in &state.shared_buckets {
+                    if let Err(e) = bucket.open(&jetstream).await {
+                        error!(
+                            "Failed to open shared state bucket {}: {}",
+                            bucket.name(),
+                            e
+                        );
+                    }
+                }
+
+                // Ingest raw events over this connection until it is replaced
+                let raw_worker = state.raw_ingest.map(|settings| {
+                    tokio::spawn(raw::run(state.clone(), settings, client.clone()))
+                });
+
+                {
+                    let mut nats_client = state.nats_client.write().await;
+                    *nats_client = Some(client);
+                    gauge!("facto_nats_connected").set(1.0);
+                }
+
+                // Monitor connection
+                loop {
+                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                    let client = state.nats_client.read().await;
+                    if let Some(ref c) = *client {
+                        if c.connection_state() == async_nats::connection::State::Disconnected {
+                            warn!("NATS connection lost");
+                            gauge!("facto_nats_connected").set(0.0);
+                            break;
+                        }
+                    }
+                }
+                if let Some(raw_worker) = raw_worker {
+                    raw_worker.abort();
+                }
+            }
+            Err(e) => {
+                error!("Failed to connect to NATS: {}", e);
+                gauge!("facto_nats_connected").set(0.0);
+            }
+        }
+
+        // Wait before reconnecting
+        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+    }
+}
+
+/// Drain spooled events to the sink, in order, whenever it is connected
+async fn drain_spool(state: Arc<AppState>) {
+    let Some(ref spool) = state.spool else {
+        return;
+    };
+
+    loop {
+        spool.wait(NATS_CURSOR, Duration::from_secs(5)).await;
+        drain_spool_once(&state, spool).await;
+    }
+}
+
+/// Publish the events spooled for the sink if it is connected, returning how
+/// many were drained
+async fn drain_spool_once(state: &AppState, spool: &Spool) -> u64 {
+    if spool.cursor_depth(NATS_CURSOR) == 0 || !state.sink.is_connected().await {
+        return 0;
+    }
+
+    let (shaper, sink) = (&state.sink_shaper, &state.sink);
+    let result = spool
+        .drain(NATS_CURSOR, |item| async move {
+            let _permit = shaper.acquire().await;
+            let sequence = sink.publish(&item.event, &item.envelope).await?;
+            // Receipts of spooled events gain their stream sequence
+            if let Some(sequence) = sequence {
+                state.receipts.issue(
+                    receipt_key(&item.envelope, &item.event.facto_id),
+                    &item.event.facto_id,
+                    &item.envelope,
+                    Some(sequence),
+                    state.verifier.signer(),
+                );
+            }
+            Ok::<(), transport::SinkError>(())
+        })
+        .await;
+    match result {
+        Ok(0) => 0,
+        Ok(drained) => {
+            if let Err(e) = sink.flush().await {
+                warn!("Failed to flush drained events: {}", e);
+            }
+            info!("Drained {} spooled events to {}", drained, sink.name());
+            counter!("facto_spool_drained_total").increment(drained);
+            drained
+        }
+        Err(e) => {
+            error!("Failed to drain spool: {}", e);
+            0
+        }
+    }
+}
+
+/// Hand what was accepted before shutdown to the broker within the time left:
+/// drain the spool while the sink is connected and flush outstanding
+/// publishes. Events still spooled are delivered after the next start.
+async fn flush_on_shutdown(state: &AppState) {
+    let flush = async {
+        // Held backfill events are published with their gaps rather than lost
+        for (session, session_id) in state.backfill.sessions() {
+            release_backfill(state, &session, &session_id, true).await;
+        }
+        if let Some(ref spool) = state.spool {
+            if !state.outbox {
+                let drained = drain_spool_once(state, spool).await;
+                counter!("facto_shutdown_flushed_events_total").increment(drained);
+            }
+        }
+        if let Err(e) = state.sink.flush().await {
+            warn!("Failed to flush {} on shutdown: {}", state.sink.name(), e);
+        }
+        if let Some(client) = state.connected_client().await {
+            if let Err(e) = client.flush().await {
+                warn!("Failed to flush NATS on shutdown: {}", e);
+            }
+        }
+    };
+    if tokio::time::timeout(state.shutdown.remaining(), flush)
+        .await
+        .is_err()
+    {
+        warn!("Shutdown deadline passed before publishes were flushed");
+    }
+    if let Err(e) = state.agents.flush() {
+        warn!("Failed to save the agent inventory on shutdown: {}", e);
+    }
+    if let Err(e) = state.usage.flush(now_nanos()) {
+        warn!("Failed to save usage on shutdown: {}", e);
+    }
+    if let Some(ref spool) = state.spool {
+        gauge!("facto_shutdown_spooled_events").set(spool.depth() as f64);
+        if spool.depth() > 0 {
+            info!("{} events remain spooled for the next start", spool.depth());
+        }
+    }
+}
+
+/// Apply configuration changes on SIGHUP, and when the config file changed,
+/// checking every `interval`
+async fn watch_config(state: Arc<AppState>, config: Arc<Config>, interval: Duration) {
+    #[cfg(unix)]
+    let mut hangups = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+
+    loop {
+        #[cfg(unix)]
+        let hangup = async {
+            match hangups.as_mut() {
+                Some(hangups) => {
+                    hangups.recv().await;
+                }
+                None => std::future::pending().await,
+            }
+        };
+        #[cfg(not(unix))]
+        let hangup = std::future::pending::<()>();
+        let changed = async {
+            if interval.is_zero() || config.path().is_none() {
+                return std::future::pending().await;
+            }
+            loop {
+                tokio::time::sleep(interval).await;
+                if config.modified() {
+                    break;
+                }
+            }
+        };
+
+        tokio::select! {
+            _ = hangup => info!("Received SIGHUP, reloading configuration"),
+            _ = changed => info!("Config file changed, reloading configuration"),
+        }
+        reload_config(&state, &config);
+    }
+}
+
+/// Apply the reloadable settings and redaction policies. An invalid config
+/// file or policy leaves the current ones in place.
+fn reload_config(state: &AppState, config: &Config) {
+    match config.reload() {
+        Ok(reload) => {
+            let Reloadable {
+                rate_limit_per_agent,
+                agent_byte_quota,
+            } = reload.reloadable;
+            if state
+                .rate_limit_per_agent
+                .swap(rate_limit_per_agent, Ordering::Relaxed)
+                != rate_limit_per_agent
+            {
+                info!("Rate limit per agent: {} req/sec", rate_limit_per_agent);
+            }
+            if state
+                .agent_byte_quota
+                .swap(agent_byte_quota, Ordering::Relaxed)
+                != agent_byte_quota
+            {
+                info!("Agent byte quota: {} bytes per window", agent_byte_quota);
+            }
+            for name in &reload.needs_restart {
+                warn!("{} changed in the config file; restart to apply it", name);
+            }
+            counter!("facto_config_reloads_total", "result" => "ok").increment(1);
+        }
+        Err(e) => {
+            error!(
+                "Failed to reload configuration, keeping the current one: {}",
+                e
+            );
+            counter!("facto_config_reloads_total", "result" => "error").increment(1);
+        }
+    }
+
+    match state.redactions.reload() {
+        Ok(Some(count)) => info!("Reloaded {} redaction policies", count),
+        Ok(None) => {}
+        Err(e) => error!(
+            "Failed to reload redaction policies, keeping the current ones: {}",
+            e
+        ),
+    }
+}
+
+/// Seal pending events into signed checkpoints every `interval`, or sooner
+/// when a checkpoint fills up, and publish them
+async fn run_checkpointer(state: Arc<AppState>, interval: Duration) {
+    loop {
+        state.checkpoints.wait(interval).await;
+        let Some(checkpoint) = state.checkpoints.seal(state.verifier.signer()) else {
+            continue;
+        };
+        counter!("facto_checkpoints_total").increment(1);
+        gauge!("facto_checkpoint_sequence").set(checkpoint.sequence as f64);
+
+        let Some(client) = state.connected_client().await else {
+            warn!(
+                "Checkpoint {} not published, NATS unavailable",
+                checkpoint.sequence
+            );
+            continue;
+        };
+        let mut headers = async_nats::HeaderMap::new();
+        headers.insert(
+            async_nats::header::NATS_MESSAGE_ID,
+            format!(
+                "checkpoint-{}-{}",
+                checkpoint.signer_id, checkpoint.sequence
+            )
+            .as_str(),
+        );
+        if let Err(e) = client
+            .publish_with_headers(
+                CHECKPOINT_SUBJECT,
+                headers,
+                serde_json::to_vec(&checkpoint).unwrap().into(),
+            )
+            .await
+        {
+            warn!(
+                "Failed to publish checkpoint {}: {}",
+                checkpoint.sequence, e
+            );
+        }
+    }
+}
+
+/// Sign classifier labels and publish them to be stored beside their events
+async fn publish_classifications(
+    state: Arc<AppState>,
+    mut classifications: mpsc::Receiver<Classification>,
+) {
+    while let Some(mut classification) = classifications.recv().await {
+        classification.sign(state.verifier.signer());
+
+        let Some(client) = state.connected_client().await else {
+            warn!(
+                "Classification of {} not published, NATS unavailable",
+                classification.facto_id
+            );
+            counter!("facto_classifications_dropped_total").increment(1);
+            continue;
+        };
+        let mut headers = async_nats::HeaderMap::new();
+        headers.insert(
+            async_nats::header::NATS_MESSAGE_ID,
+            format!("classification-{}", classification.facto_id).as_str(),
+        );
+        if let Err(e) = client
+            .publish_with_headers(
+                CLASSIFICATION_SUBJECT,
+                headers,
+                serde_json::to_vec(&classification).unwrap().into(),
+            )
+            .await
+        {
+            warn!(
+                "Failed to publish classification of {}: {}",
+                classification.facto_id, e
+            );
+            counter!("facto_classifications_dropped_total").increment(1);
+        }
+    }
+}
+
+/// Sign policy alerts and publish them, and post them to the alert webhook
+/// when one is configured
+async fn publish_policy_alerts(
+    state: Arc<AppState>,
+    mut alerts: mpsc::Receiver<PolicyAlert>,
+    webhook: Option<(reqwest::Client, String)>,
+) {
+    while let Some(mut alert) = alerts.recv().await {
+        alert.sign(state.verifier.signer());
+
+        if let Some((http, url)) = &webhook {
+            let delivered = http
+                .post(url)
+                .json(&alert)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = delivered {
+                warn!(
+                    "Failed to post policy alert of {} to the webhook: {}",
+                    alert.facto_id, e
+                );
+                counter!("facto_policy_alerts_dropped_total", "target" => "webhook").increment(1);
+            }
+        }
+
+        let Some(client) = state.connected_client().await else {
+            warn!(
+                "Policy alert of {} not published, NATS unavailable",
+                alert.facto_id
+            );
+            counter!("facto_policy_alerts_dropped_total", "target" => "nats").increment(1);
+            continue;
+        };
+        let mut headers = async_nats::HeaderMap::new();
+        headers.insert(
+            async_nats::header::NATS_MESSAGE_ID,
+            format!("policy-alert-{}", alert.facto_id).as_str(),
+        );
+        if let Err(e) = client
+            .publish_with_headers(
+                POLICY_ALERT_SUBJECT,
+                headers,
+                serde_json::to_vec(&alert).unwrap().into(),
+            )
+            .await
+        {
+            warn!(
+                "Failed to publish policy alert of {}: {}",
+                alert.facto_id, e
+            );
+            counter!("facto_policy_alerts_dropped_total", "target" => "nats").increment(1);
+        }
+    }
+}
+
+/// Anchor the latest checkpoint with external timestamping services every
+/// `interval`. Checkpoints chain through `prev_root`, so anchoring the latest
+/// root also fixes every earlier one in time.
+async fn run_anchoring(state: Arc<AppState>, anchorer: Anchorer, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let Some(checkpoint) = state.checkpoints.list(1).pop() else {
+            continue;
+        };
+        if state.anchors.is_anchored(&checkpoint.root) {
+            continue;
+        }
+
+        let anchors = anchorer.anchor(&checkpoint).await;
+        if anchors.is_empty() {
+            continue;
+        }
+        info!(
+            "Anchored checkpoint {} with {} services",
+            checkpoint.sequence,
+            anchors.len()
+        );
+        if let Err(e) = state.anchors.add(anchors) {
+            error!("Failed to persist checkpoint anchors: {}", e);
+        }
+    }
+}
+
+/// Pause before retrying a fan-out sink that failed a delivery
+const SINK_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Deliver outbox events to a fan-out sink through its own cursor, in order,
+/// retrying failed deliveries until the sink accepts them
+async fn drain_to_sink(state: Arc<AppState>, sink: Arc<dyn FanoutSink>, limits: SinkLimits) {
+    let Some(ref spool) = state.spool else {
+        return;
+    };
+    let shaper = Shaper::new(&limits);
+    info!("Fan-out sink {} delivering from the outbox", sink.name());
+
+    loop {
+        spool.wait(sink.name(), Duration::from_secs(5)).await;
+        if spool.cursor_depth(sink.name()) == 0 {
+            continue;
+        }
+
+        let result = spool
+            .drain(sink.name(), |item| {
+                let sink = sink.clone();
+                let shaper = &shaper;
+                async move {
+                    // Sandbox events never reach fan-out sinks
+                    if item.envelope.sandbox {
+                        return Ok(());
+                    }
+                    let _permit = shaper.acquire().await;
+                    let start = Instant::now();
+                    let accepted = Arc::new((item.event, item.envelope));
+                    let result = sink.deliver(&accepted).await;
+                    histogram!("facto_sink_delivery_seconds", "sink" => sink.name())
+                        .record(start.elapsed().as_secs_f64());
+                    match result {
+                        Ok(()) => {
+                            counter!("facto_sink_delivered_total", "sink" => sink.name())
+                                .increment(1);
+                            Ok(())
+                        }
+                        Err(e) => {
+                            counter!("facto_sink_failed_total", "sink" => sink.name()).increment(1);
+                            tokio::time::sleep(SINK_RETRY_DELAY).await;
+                            Err(e)
+                        }
+                    }
+                }
+            })
+            .await;
+        if let Err(e) = result {
+            error!("Failed to drain outbox to {}: {}", sink.name(), e);
+        }
+    }
+}
+
+// ============================================================================
+// Router
+// ============================================================================
+
+/// The routes of the API, with their middleware
+pub fn router(state: Arc<AppState>) -> Router {
+    let ingest_routes = Router::new()
+        .route("/v1/ingest", post(ingest_single_handler))
+        .route("/v1/ingest/batch", post(ingest_batch_handler))
+        .route("/v1/ingest/legacy", post(legacy::ingest_legacy_handler))
+        .route("/v1/agents/register", post(agents::register_agent_handler))
+        .route(
+            "/v1/agents/:agent_id/heartbeat",
+            post(agents::heartbeat_handler),
+        )
+        .route("/v1/receipts/:facto_id", get(receipts::get_receipt_handler))
+        .route("/v1/checkpoints", get(admin::list_checkpoints_handler))
+        .route("/v1/proof/:facto_id", get(admin::proof_handler))
+        .route("/v1/anchors", get(admin::list_anchors_handler))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            debug::capture_debug_bundle,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            decompress::decompress_request,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_ingest_scope,
+        ));
+
+    Router::new()
+        .route("/health", get(health_handler))
+        .route("/ready", get(ready_handler))
+        .route("/metrics", get(metrics_handler))
+        .route("/v1/capabilities", get(capabilities_handler))
+        .route(receipts::SERVER_KEY_PATH, get(receipts::server_key_handler))
+        .route("/openapi.json", get(openapi_handler))
+        .route("/docs", get(docs_handler))
+        .route("/v1/debug/:debug_id", get(admin::get_debug_bundle_handler))
+        .route("/v1/rejects", get(rejects::list_rejects_handler))
+        .route("/v1/stream", get(tail::stream_handler))
+        .route("/v1/cursors", post(cursors::create_cursor_handler))
+        .route(
+            "/v1/cursors/:name",
+            get(cursors::get_cursor_handler).delete(cursors::delete_cursor_handler),
+        )
+        .route(
+            "/v1/cursors/:name/events",
+            get(cursors::fetch_cursor_handler),
+        )
+        .route(
+            "/v1/cursors/:name/commit",
+            post(cursors::commit_cursor_handler),
+        )
+        .merge(ingest_routes)
+        .route("/v1/admin/keys/export", get(admin::export_keys_handler))
+        .route("/v1/admin/keys/import", post(admin::import_keys_handler))
+        .route(
+            "/v1/admin/keys/snapshot",
+            get(admin::export_snapshot_handler).post(admin::import_snapshot_handler),
+        )
+        .route(
+            "/v1/admin/keys/:agent_id",
+            get(admin::list_keys_handler).post(admin::register_key_handler),
+        )
+        .route(
+            "/v1/admin/keys/:agent_id/revoke",
+            post(admin::revoke_key_handler),
+        )
+        .route(
+            "/v1/admin/keys/:agent_id/rotate",
+            post(admin::rotate_key_handler),
+        )
+        .route(
+            "/v1/admin/sessions/:session_id/freeze",
+            get(admin::get_freeze_handler).post(admin::freeze_session_handler),
+        )
+        .route(
+            "/v1/admin/sessions/:session_id/unfreeze",
+            post(admin::approve_unfreeze_handler),
+        )
+        .route("/v1/admin/tenants", get(admin::list_tenants_handler))
+        .route(
+            "/v1/admin/tenants/:tenant_id",
+            get(admin::get_tenant_handler)
+                .put(admin::put_tenant_handler)
+                .delete(admin::delete_tenant_handler),
+        )
+        .route(
+            "/v1/admin/tenants/:tenant_id/headers",
+            put(admin::put_tenant_headers_handler),
+        )
+        .route(
+            "/v1/admin/tenants/:tenant_id/enforcement-mode",
+            put(admin::put_tenant_enforcement_mode_handler)
+                .delete(admin::delete_tenant_enforcement_mode_handler),
+        )
+        .route("/v1/admin/shadow", get(admin::shadow_report_handler))
+        .route("/v1/agents", get(agents::list_agents_handler))
+        .route("/v1/usage", get(usage::usage_handler))
+        .route("/v1/admin/agents", get(admin::list_agent_controls_handler))
+        .route(
+            "/v1/admin/agents/:agent_id",
+            get(admin::agent_status_handler),
+        )
+        .route(
+            "/v1/admin/agents/:agent_id/rate-limit",
+            put(admin::put_agent_rate_limit_handler).delete(admin::delete_agent_rate_limit_handler),
+        )
+        .route(
+            "/v1/admin/agents/:agent_id/byte-quota",
+            put(admin::put_agent_byte_quota_handler).delete(admin::delete_agent_byte_quota_handler),
+        )
+        .route(
+            "/v1/admin/agents/:agent_id/enforcement-mode",
+            put(admin::put_agent_enforcement_mode_handler)
+                .delete(admin::delete_agent_enforcement_mode_handler),
+        )
+        .route(
+            "/v1/admin/agents/:agent_id/envelope-trust",
+            put(admin::put_agent_envelope_trust_handler)
+                .delete(admin::delete_agent_envelope_trust_handler),
+        )
+        .route(
+            "/v1/admin/agents/:agent_id/pause",
+            post(admin::pause_agent_handler),
+        )
+        .route(
+            "/v1/admin/agents/:agent_id/resume",
+            post(admin::resume_agent_handler),
+        )
+        .route(
+            "/v1/admin/webhooks",
+            get(admin::list_webhooks_handler).post(admin::register_webhook_handler),
+        )
+        .route(
+            "/v1/admin/webhooks/:webhook_id",
+            get(admin::get_webhook_handler).delete(admin::delete_webhook_handler),
+        )
+        .route("/v1/admin/redactions", get(admin::list_redactions_handler))
+        .route(
+            "/v1/admin/redactions/:tenant_id",
+            get(admin::get_redaction_handler)
+                .put(admin::put_redaction_handler)
+                .delete(admin::delete_redaction_handler),
+        )
+        .route("/v1/admin/models", get(admin::list_models_handler))
+        .route(
+            "/v1/admin/models/:tenant_id",
+            get(admin::get_models_handler)
+                .put(admin::put_models_handler)
+                .delete(admin::delete_models_handler),
+        )
+        .route("/v1/admin/retention", get(admin::list_retention_handler))
+        .route(
+            "/v1/admin/retention/:tenant_id",
+            get(admin::get_retention_handler)
+                .put(admin::put_retention_handler)
+                .delete(admin::delete_retention_handler),
+        )
+        .route(
+            "/v1/admin/legal-holds",
+            get(admin::list_legal_holds_handler),
+        )
+        .route(
+            "/v1/admin/legal-holds/:tenant_id/:session_id",
+            put(admin::put_legal_hold_handler).delete(admin::delete_legal_hold_handler),
+        )
+        .route("/v1/admin/schemas", get(admin::list_schemas_handler))
+        .route(
+            "/v1/admin/schemas/:action_type",
+            get(admin::get_schema_handler)
+                .put(admin::put_schema_handler)
+                .delete(admin::delete_schema_handler),
+        )
+        .route(
+            "/v1/sessions/:session_id/annotations",
+            get(admin::list_annotations_handler).post(admin::annotate_session_handler),
+        )
+        .layer(DefaultBodyLimit::max(state.capabilities.max_body_bytes))
+        .layer(CompressionLayer::new())
+        .layer(
+            CorsLayer::new()
+                .allow_origin(Any)
+                .allow_methods(Any)
+                .allow_headers(Any),
+        )
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::request_span))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(middleware::from_fn_with_state(
+            state.shutdown.clone(),
+            shutdown::track_requests,
+        ))
+        .with_state(state)
+}
+
+// ============================================================================
+// Server
+// ============================================================================
+
+/// Configure the service from the environment and `config`, then serve
+/// until shut down
+pub async fn run(config: Config) -> anyhow::Result<()> {
+    let instance_id = std::env::var("FACTO_INSTANCE_ID")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| uuid::Uuid::new_v4().to_string());
+
+    // Initialize tracing, with spans exported over OTLP when configured
+    let tracing = telemetry::Tracing::from_env(&instance_id)?;
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::EnvFilter::from_default_env()
+                .add_directive("facto_ingestion=info".parse()?)
+                .add_directive("tower_http=info".parse()?),
+        )
+        .with(tracing_subscriber::fmt::layer().json())
+        .with(tracing.layer())
+        .init();
+    if tracing.exporting() {
+        info!("Exporting spans over OTLP");
+    }
+
+    let config = Arc::new(config);
+    if let Some(path) = config.path() {
+        info!("Loaded settings from {}", path.display());
+    }
+
+    // Initialize metrics, served on their own port and exported over OTLP
+    // when configured
+    let metrics_port: u16 = std::env::var("METRICS_PORT")
+        .unwrap_or_else(|_| "9000".to_string())
+        .parse()
+        .expect("Invalid METRICS_PORT");
+    let (recorder, metrics_listener) = PrometheusBuilder::new()
+        .with_http_listener(SocketAddr::from(([0, 0, 0, 0], metrics_port)))
+        .build()
+        .expect("Failed to build Prometheus recorder");
+    let metrics_handle = recorder.handle();
+    let meter_provider = telemetry::meter_provider(&instance_id)?;
+    match meter_provider {
+        Some(ref provider) => {
+            info!("Exporting metrics over OTLP");
+            let fanout = FanoutBuilder::default()
+                .add_recorder(recorder)
+                .add_recorder(OtelRecorder::new(provider))
+                .build();
+            metrics::set_global_recorder(fanout).expect("Failed to install metrics recorders");
+        }
+        None => {
+            metrics::set_global_recorder(recorder).expect("Failed to install Prometheus recorder")
+        }
+    }
+    tokio::spawn(metrics_listener);
+
+    // Configuration from environment
+    let port: u16 = std::env::var("PORT")
+        .unwrap_or_else(|_| "8080".to_string())
+        .parse()
+        .expect("Invalid PORT");
+
+    // TLS termination, with client certificates when a client CA is set
+    let tls = match TlsConfig::from_env()? {
+        Some(config) => Some(Arc::new(Tls::new(config)?)),
+        None => None,
+    };
+
+    // NATS address, credentials and TLS
+    let nats = NatsConfig::from_env()?;
+    nats.options().await?;
+
+    // Requests per second and event bytes per window each agent may send,
+    // where 0 means unlimited
+    let Reloadable {
+        rate_limit_per_agent,
+        agent_byte_quota,
+    } = config.reloadable()?;
+    let agent_byte_quota_window_secs: u64 = std::env::var("AGENT_BYTE_QUOTA_WINDOW_SECS")
+        .unwrap_or_else(|_| "60".to_string())
+        .parse()
+        .expect("Invalid AGENT_BYTE_QUOTA_WINDOW_SECS");
+
+    info!(
+        "Starting Facto Ingestion Service v{}",
+        env!("CARGO_PKG_VERSION")
+    );
+    info!("Port: {}", port);
+    info!("NATS: {}", nats.describe());
+
+    // Metrics pushed to a push gateway too
+    let metrics_push = MetricsPush::from_env(&instance_id)?.map(Arc::new);
+    if let Some(ref push) = metrics_push {
+        info!("Metrics push: {}", push.describe());
+    }
+
+    let verification_cache_size: usize = std::env::var("VERIFICATION_CACHE_SIZE")
+        .unwrap_or_else(|_| "100000".to_string())
+        .parse()
+        .expect("Invalid VERIFICATION_CACHE_SIZE");
+
+    let verification_cache_ttl_secs: u64 = std::env::var("VERIFICATION_CACHE_TTL_SECS")
+        .unwrap_or_else(|_| "300".to_string())
+        .parse()
+        .expect("Invalid VERIFICATION_CACHE_TTL_SECS");
+
+    let did_cache_ttl_secs: u64 = std::env::var("DID_CACHE_TTL_SECS")
+        .unwrap_or_else(|_| "300".to_string())
+        .parse()
+        .expect("Invalid DID_CACHE_TTL_SECS");
+    let dids = Arc::new(DidResolver::new(
+        std::env::var("DID_WEB_HOSTS")
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|h| !h.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        Duration::from_secs(did_cache_ttl_secs),
+    ));
+
+    let dedup_cache_size: usize = std::env::var("DEDUP_CACHE_SIZE")
+        .unwrap_or_else(|_| "1000000".to_string())
+        .parse()
+        .expect("Invalid DEDUP_CACHE_SIZE");
+
+    let dedup_ttl_secs: u64 = std::env::var("DEDUP_TTL_SECS")
+        .unwrap_or_else(|_| "120".to_string())
+        .parse()
+        .expect("Invalid DEDUP_TTL_SECS");
+
+    // Freshness window for completed_at; replay protection is off if unset
+    let replay_window = std::env::var("REPLAY_WINDOW_SECS")
+        .ok()
+        .map(|v| Duration::from_secs(v.parse().expect("Invalid REPLAY_WINDOW_SECS")));
+    if replay_window.is_none() {
+        warn!("REPLAY_WINDOW_SECS not set, replayed events outside the dedup window are accepted");
+    }
+
+    let replay_max_skew_secs: u64 = std::env::var("REPLAY_MAX_SKEW_SECS")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse()
+        .expect("Invalid REPLAY_MAX_SKEW_SECS");
+
+    // Events ingested with `backfill=true` may be as old as
+    // BACKFILL_MAX_AGE_SECS; a session's held events are released with gaps
+    // once none was released for BACKFILL_TIMEOUT_SECS
+    let backfill = BackfillBuffer::new(
+        std::env::var("BACKFILL_MAX_EVENTS")
+            .unwrap_or_else(|_| "100000".to_string())
+            .parse()
+            .expect("Invalid BACKFILL_MAX_EVENTS"),
+        Duration::from_secs(
+            std::env::var("BACKFILL_MAX_AGE_SECS")
+                .unwrap_or_else(|_| "604800".to_string())
+                .parse()
+                .expect("Invalid BACKFILL_MAX_AGE_SECS"),
+        ),
+        Duration::from_secs(
+            std::env::var("BACKFILL_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "900".to_string())
+                .parse()
+                .expect("Invalid BACKFILL_TIMEOUT_SECS"),
+        ),
+    );
+
+    // How long a session's chain head is kept in the shared bucket after its
+    // last event; zero keeps heads indefinitely
+    let chain_head_ttl_secs: u64 = std::env::var("CHAIN_HEAD_TTL_SECS")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse()
+        .expect("Invalid CHAIN_HEAD_TTL_SECS");
+
+    // Rate limits, dedup, replay protection and chain heads are kept per
+    // replica, or shared by all replicas through NATS
+    let shared_state = SharedStateBackend::from_env()?;
+
+    // Chain heads are moved by compare-and-swap in the shared bucket, or
+    // kept by the one replica each session is routed to
+    let chain_routing = ChainRouting::from_env()?;
+
+    // Matches axum's default JSON body limit
+    let max_body_bytes: usize = std::env::var("MAX_BODY_BYTES")
+        .unwrap_or_else(|_| "2097152".to_string())
+        .parse()
+        .expect("Invalid MAX_BODY_BYTES");
+    let mut limits = RequestLimits::from_env(max_body_bytes);
+
+    // Move payloads over the threshold to a blob store
+    let offloader = Offloader::from_env()?;
+    if let Some(ref offloader) = offloader {
+        info!(
+            "Offloading payloads over {} bytes to the blob store",
+            offloader.threshold()
+        );
+        limits.offload_threshold = Some(offloader.threshold());
+    }
+
+    // Count ingest outcomes per agent and action type, naming only the
+    // busiest to bound the number of series
+    let dimensions_enabled: bool = std::env::var("METRIC_DIMENSIONS_ENABLED")
+        .unwrap_or_else(|_| "false".to_string())
+        .parse()
+        .expect("Invalid METRIC_DIMENSIONS_ENABLED");
+    let dimensions = match dimensions_enabled {
+        true => {
+            let top_agents: usize = std::env::var("METRIC_DIMENSIONS_TOP_AGENTS")
+                .unwrap_or_else(|_| "100".to_string())
+                .parse()
+                .expect("Invalid METRIC_DIMENSIONS_TOP_AGENTS");
+            let top_action_types: usize = std::env::var("METRIC_DIMENSIONS_TOP_ACTION_TYPES")
+                .unwrap_or_else(|_| "50".to_string())
+                .parse()
+                .expect("Invalid METRIC_DIMENSIONS_TOP_ACTION_TYPES");
+            info!(
+                "Counting ingest outcomes for the top {} agents and {} action types",
+                top_agents, top_action_types
+            );
+            Some(MetricDimensions::new(top_agents, top_action_types))
+        }
+        false => None,
+    };
+
+    // Evaluate candidate canonicalizers and hashers on a sample of traffic
+    let shadow = match std::env::var("SHADOW_SAMPLE_RATE") {
+        Ok(rate) => {
+            let rate: f64 = rate.parse().expect("Invalid SHADOW_SAMPLE_RATE");
+            let hasher = std::env::var("SHADOW_HASHER")
+                .ok()
+                .map(|name| shadow::candidate_hasher(&name))
+                .transpose()?;
+            info!("Shadow evaluation enabled for {} of events", rate);
+            Some(Arc::new(ShadowEvaluator::new(rate, hasher)))
+        }
+        Err(_) => None,
+    };
+
+    let verify_concurrency: usize = match std::env::var("VERIFY_CONCURRENCY") {
+        Ok(value) => value.parse().expect("Invalid VERIFY_CONCURRENCY"),
+        Err(_) => std::thread::available_parallelism().map_or(4, |n| n.get()),
+    };
+
+    // Payload checks of batch events, side by side across all requests
+    let validation_concurrency: usize = match std::env::var("VALIDATION_CONCURRENCY") {
+        Ok(value) => value.parse().expect("Invalid VALIDATION_CONCURRENCY"),
+        Err(_) => std::thread::available_parallelism().map_or(4, |n| n.get()),
+    };
+    let validation_timeout_ms: u64 = std::env::var("VALIDATION_TIMEOUT_MS")
+        .unwrap_or_else(|_| "2000".to_string())
+        .parse()
+        .expect("Invalid VALIDATION_TIMEOUT_MS");
+
+    let verify_chunk_size: usize = std::env::var("VERIFY_CHUNK_SIZE")
+        .unwrap_or_else(|_| "64".to_string())
+        .parse()
+        .expect("Invalid VERIFY_CHUNK_SIZE");
+
+    // End of the migration window for canonical form version 1; it is
+    // accepted indefinitely if unset
+    let canonical_v1_until = std::env::var("CANONICAL_V1_UNTIL").ok().map(|v| {
+        chrono::DateTime::parse_from_rfc3339(&v)
+            .expect("Invalid CANONICAL_V1_UNTIL")
+            .timestamp()
+    });
+
+    let signer = ServerSigner::from_seed(
+        instance_id,
+        std::env::var("FACTO_SERVER_SIGNING_KEY").ok().as_deref(),
+    )?;
+
+    let require_key_registration: bool = std::env::var("REQUIRE_KEY_REGISTRATION")
+        .unwrap_or_else(|_| "false".to_string())
+        .parse()
+        .expect("Invalid REQUIRE_KEY_REGISTRATION");
+
+    let key_registry = Arc::new(KeyRegistry::new(
+        std::env::var("KEY_REGISTRY_PATH").ok().map(Into::into),
+        require_key_registration,
+    )?);
+    let mut snapshot_signers = vec![signer.public_key_base64()];
+    for key in std::env::var("REGISTRY_SNAPSHOT_SIGNERS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+    {
+        crypto::decode_public_key(key)
+            .map_err(|e| anyhow::anyhow!("Invalid REGISTRY_SNAPSHOT_SIGNERS key {}: {}", key, e))?;
+        snapshot_signers.push(key.to_string());
+    }
+    if !require_key_registration {
+        warn!("REQUIRE_KEY_REGISTRATION not set, agents without registered keys are trusted on their embedded key");
+    }
+
+    let freezes = SessionFreezes::new(std::env::var("SESSION_FREEZES_PATH").ok().map(Into::into))?;
+
+    // Events are published to NATS or Kafka (TRANSPORT). Primary publishes
+    // are shaped in the request path; fan-out sinks get their own queues
+    let nats_client = Arc::new(RwLock::new(None));
+    let session_partitions: usize = std::env::var("NATS_SESSION_PARTITIONS")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse()
+        .expect("Invalid NATS_SESSION_PARTITIONS");
+    if session_partitions > 0 {
+        info!(
+            "Publishing each agent's events on {} session partitions",
+            session_partitions
+        );
+    }
+    let (sink, sink_limits, kafka): (Arc<dyn Sink>, _, _) = match Transport::from_env()? {
+        Transport::Nats => (
+            Arc::new(NatsSink::new(nats_client.clone(), session_partitions)),
+            SinkLimits::from_env("NATS_PUBLISH", 1024),
+            None,
+        ),
+        Transport::Kafka => {
+            let kafka = Arc::new(KafkaSink::new(KafkaConfig::from_env()?)?);
+            info!("Publishing events to {}", kafka.describe());
+            (
+                kafka.clone(),
+                SinkLimits::from_env("KAFKA_PUBLISH", 1024),
+                Some(kafka),
+            )
+        }
+    };
+
+    let mut sinks: Vec<(Arc<dyn FanoutSink>, SinkLimits)> = Vec::new();
+    if let Ok(dir) = std::env::var("EXPORT_DIR") {
+        info!("Exporting accepted events to {}", dir);
+        sinks.push((
+            Arc::new(ExportSink::new(dir.into()).await?),
+            SinkLimits::from_env("EXPORT", 1),
+        ));
+    }
+
+    // Classifiers label accepted events after ingestion: CLASSIFIER_RULES is
+    // `builtin` or a rules file, CLASSIFIER_URL an external model endpoint
+    let mut classifiers: Vec<Arc<dyn Classifier>> = Vec::new();
+    match std::env::var("CLASSIFIER_RULES") {
+        Ok(rules) if rules == "builtin" => {
+            info!("Classifying events with the builtin rules");
+            classifiers.push(Arc::new(RuleClassifier::builtin()));
+        }
+        Ok(path) => classifiers.push(Arc::new(RuleClassifier::load(path.as_ref())?)),
+        Err(_) => {}
+    }
+    if let Ok(url) = std::env::var("CLASSIFIER_URL") {
+        let timeout_ms: u64 = std::env::var("CLASSIFIER_TIMEOUT_MS")
+            .unwrap_or_else(|_| "2000".to_string())
+            .parse()
+            .expect("Invalid CLASSIFIER_TIMEOUT_MS");
+        info!("Classifying events with the model at {}", url);
+        classifiers.push(Arc::new(HttpClassifier::new(
+            url,
+            Duration::from_millis(timeout_ms),
+        )));
+    }
+    let classifications = match classifiers.is_empty() {
+        true => None,
+        false => {
+            let (sender, receiver) = mpsc::channel(1024);
+            sinks.push((
+                Arc::new(ClassifierSink::new(classifiers, sender)),
+                SinkLimits::from_env("CLASSIFIER", 4),
+            ));
+            Some(receiver)
+        }
+    };
+
+    // Policies are evaluated on admission: POLICY_RULES is a rules file
+    // whose findings annotate, alert on or reject events. Alerts are
+    // published on POLICY_ALERT_SUBJECT and posted to
+    // POLICY_ALERT_WEBHOOK_URL when it is set.
+    let mut policies = PolicyEngine::new();
+    if let Ok(path) = std::env::var("POLICY_RULES") {
+        info!("Evaluating the policy rules in {}", path);
+        policies.register(RulePolicy::load(path.as_ref())?);
+    }
+    let policy_alerts = match policies.is_empty() {
+        true => None,
+        false => {
+            let (sender, receiver) = mpsc::channel(1024);
+            sinks.push((
+                Arc::new(PolicyAlertSink::new(sender)),
+                SinkLimits::from_env("POLICY_ALERT", 4),
+            ));
+            let webhook = match std::env::var("POLICY_ALERT_WEBHOOK_URL") {
+                Ok(url) => {
+                    let timeout_ms: u64 = std::env::var("POLICY_ALERT_TIMEOUT_MS")
+                        .unwrap_or_else(|_| "2000".to_string())
+                        .parse()
+                        .expect("Invalid POLICY_ALERT_TIMEOUT_MS");
+                    let http = reqwest::Client::builder()
+                        .timeout(Duration::from_millis(timeout_ms))
+                        .build()?;
+                    Some((http, url))
+                }
+                Err(_) => None,
+            };
+            Some((receiver, webhook))
+        }
+    };
+
+    // Webhooks registered through the admin API receive matching accepted
+    // events, signed with a per-webhook secret
+    let webhooks_enabled: bool = std::env::var("WEBHOOKS_ENABLED")
+        .unwrap_or_else(|_| "false".to_string())
+        .parse()
+        .expect("Invalid WEBHOOKS_ENABLED");
+    let webhooks = match webhooks_enabled {
+        true => {
+            let allow_http: bool = std::env::var("WEBHOOK_ALLOW_HTTP")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .expect("Invalid WEBHOOK_ALLOW_HTTP");
+            let timeout_secs: u64 = std::env::var("WEBHOOK_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .expect("Invalid WEBHOOK_TIMEOUT_SECS");
+            let webhooks = Arc::new(Webhooks::new(
+                std::env::var("WEBHOOKS_PATH").ok().map(Into::into),
+                allow_http,
+            )?);
+            sinks.push((
+                Arc::new(WebhookSink::new(
+                    webhooks.clone(),
+                    Duration::from_secs(timeout_secs),
+                    RetryPolicy::from_env(),
+                )),
+                SinkLimits::from_env("WEBHOOK", 16),
+            ));
+            Some(webhooks)
+        }
+        false => None,
+    };
+
+    let outbox: bool = std::env::var("OUTBOX_ENABLED")
+        .unwrap_or_else(|_| "false".to_string())
+        .parse()
+        .expect("Invalid OUTBOX_ENABLED");
+
+    // Cursors let consumers without NATS access poll accepted events; they
+    // read from a copy of the events kept for CURSOR_RETENTION_HOURS
+    let cursors_enabled: bool = std::env::var("CURSORS_ENABLED")
+        .unwrap_or_else(|_| "false".to_string())
+        .parse()
+        .expect("Invalid CURSORS_ENABLED");
+    let cursors = match cursors_enabled {
+        true => {
+            let retention_hours: u64 = std::env::var("CURSOR_RETENTION_HOURS")
+                .unwrap_or_else(|_| "168".to_string())
+                .parse()
+                .expect("Invalid CURSOR_RETENTION_HOURS");
+            if !outbox {
+                warn!("Cursors enabled without OUTBOX_ENABLED, events dropped by the feed queue never reach cursors");
+            }
+            let cursors = Arc::new(Cursors::new(Duration::from_secs(retention_hours * 3600)));
+            sinks.push((
+                Arc::new(FeedSink::new(cursors.clone())),
+                SinkLimits::from_env("CURSOR_FEED", 64),
+            ));
+            Some(cursors)
+        }
+        false => None,
+    };
+
+    // Events rejected for their contents are kept in FACTO_REJECTS for
+    // SDK debugging, capped by age and size
+    let rejects_enabled: bool = std::env::var("REJECTS_ENABLED")
+        .unwrap_or_else(|_| "false".to_string())
+        .parse()
+        .expect("Invalid REJECTS_ENABLED");
+    let (rejects, reject_queue) = match rejects_enabled {
+        true => {
+            let retention_hours: u64 = std::env::var("REJECTS_RETENTION_HOURS")
+                .unwrap_or_else(|_| "72".to_string())
+                .parse()
+                .expect("Invalid REJECTS_RETENTION_HOURS");
+            let max_bytes: i64 = std::env::var("REJECTS_MAX_BYTES")
+                .unwrap_or_else(|_| (256 * 1024 * 1024).to_string())
+                .parse()
+                .expect("Invalid REJECTS_MAX_BYTES");
+            let (rejects, queue) =
+                Rejects::new(Duration::from_secs(retention_hours * 3600), max_bytes, 1024);
+            (Some(rejects), Some(queue))
+        }
+        false => (None, None),
+    };
+
+    // In-cluster agents may publish events straight to NATS instead
+    let raw_ingest_enabled: bool = std::env::var("RAW_INGEST_ENABLED")
+        .unwrap_or_else(|_| "false".to_string())
+        .parse()
+        .expect("Invalid RAW_INGEST_ENABLED");
+    let raw_ingest = match raw_ingest_enabled {
+        true => {
+            let concurrency: usize = std::env::var("RAW_INGEST_CONCURRENCY")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse()
+                .expect("Invalid RAW_INGEST_CONCURRENCY");
+            if rejects.is_none() {
+                warn!("Raw ingestion enabled without REJECTS_ENABLED, rejected raw events are only counted");
+            }
+            Some(RawIngest { concurrency })
+        }
+        false => None,
+    };
+
+    let tenants = Tenants::new(std::env::var("TENANTS_PATH").ok().map(Into::into))?;
+    let schemas = SchemaRegistry::new(std::env::var("SCHEMAS_PATH").ok().map(Into::into))?;
+    let models = ModelRegistry::new(std::env::var("MODELS_PATH").ok().map(Into::into))?;
+    let redactions = Redactions::new(
+        std::env::var("REDACTIONS_PATH").ok().map(Into::into),
+        RedactionKeys::from_env()?,
+    )?;
+    let retention = Retention::new(std::env::var("RETENTION_PATH").ok().map(Into::into))?;
+
+    let sandbox_enabled: bool = std::env::var("SANDBOX_ENABLED")
+        .unwrap_or_else(|_| "false".to_string())
+        .parse()
+        .expect("Invalid SANDBOX_ENABLED");
+
+    let sandbox_retention_days: u64 = std::env::var("SANDBOX_RETENTION_DAYS")
+        .unwrap_or_else(|_| "7".to_string())
+        .parse()
+        .expect("Invalid SANDBOX_RETENTION_DAYS");
+
+    let checkpoint_interval_secs: u64 = std::env::var("CHECKPOINT_INTERVAL_SECS")
+        .unwrap_or_else(|_| "60".to_string())
+        .parse()
+        .expect("Invalid CHECKPOINT_INTERVAL_SECS");
+
+    let checkpoint_max_events: usize = std::env::var("CHECKPOINT_MAX_EVENTS")
+        .unwrap_or_else(|_| "10000".to_string())
+        .parse()
+        .expect("Invalid CHECKPOINT_MAX_EVENTS");
+
+    let checkpoint_retention: usize = std::env::var("CHECKPOINT_RETENTION")
+        .unwrap_or_else(|_| "1440".to_string())
+        .parse()
+        .expect("Invalid CHECKPOINT_RETENTION");
+
+    let receipt_retention: usize = std::env::var("RECEIPT_RETENTION")
+        .unwrap_or_else(|_| DEFAULT_RECEIPT_RETENTION.to_string())
+        .parse()
+        .expect("Invalid RECEIPT_RETENTION");
+
+    let anchorer = Anchorer::new(
+        std::env::var("ANCHOR_TSA_URL").ok(),
+        std::env::var("ANCHOR_OTS_CALENDARS")
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|c| !c.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default(),
+    );
+
+    let anchor_interval_secs: u64 = std::env::var("ANCHOR_INTERVAL_SECS")
+        .unwrap_or_else(|_| "3600".to_string())
+        .parse()
+        .expect("Invalid ANCHOR_INTERVAL_SECS");
+
+    let anchors = Anchors::new(std::env::var("ANCHORS_PATH").ok().map(Into::into))?;
+
+    let debug_bundle_ttl_secs: u64 = std::env::var("DEBUG_BUNDLE_TTL_SECS")
+        .unwrap_or_else(|_| "900".to_string())
+        .parse()
+        .expect("Invalid DEBUG_BUNDLE_TTL_SECS");
+
+    let debug_max_bundles: usize = std::env::var("DEBUG_MAX_BUNDLES")
+        .unwrap_or_else(|_| "1000".to_string())
+        .parse()
+        .expect("Invalid DEBUG_MAX_BUNDLES");
+
+    let annotations =
+        SessionAnnotations::new(std::env::var("ANNOTATIONS_PATH").ok().map(Into::into))?;
+
+    let agent_silence_secs: u64 = std::env::var("AGENT_SILENCE_SECS")
+        .unwrap_or_else(|_| DEFAULT_AGENT_SILENCE_SECS.to_string())
+        .parse()
+        .expect("Invalid AGENT_SILENCE_SECS");
+    let agent_silence = Duration::from_secs(agent_silence_secs);
+    let agents = AgentInventory::new(
+        std::env::var("AGENT_INVENTORY_PATH").ok().map(Into::into),
+        agent_silence,
+    )?;
+
+    let usage_retention_days: u32 = std::env::var("USAGE_RETENTION_DAYS")
+        .unwrap_or_else(|_| "400".to_string())
+        .parse()
+        .expect("Invalid USAGE_RETENTION_DAYS");
+    let usage_flush_secs: u64 = std::env::var("USAGE_FLUSH_SECS")
+        .unwrap_or_else(|_| "60".to_string())
+        .parse()
+        .expect("Invalid USAGE_FLUSH_SECS");
+    let usage = UsageLedger::new(
+        std::env::var("USAGE_PATH").ok().map(Into::into),
+        usage_retention_days,
+    )?;
+
+    let spool = match std::env::var("SPOOL_DIR") {
+        Ok(dir) => {
+            let max_bytes: u64 = std::env::var("SPOOL_MAX_BYTES")
+                .unwrap_or_else(|_| "1073741824".to_string())
+                .parse()
+                .expect("Invalid SPOOL_MAX_BYTES");
+            // With the outbox, fan-out sinks read the log through their own
+            // cursors instead of in-memory queues
+            let mut cursors = vec![NATS_CURSOR];
+            if outbox {
+                info!("Appending accepted events to the outbox in {}", dir);
+                cursors.extend(sinks.iter().map(|(sink, _)| sink.name()));
+            } else {
+                info!("Spooling to {} while NATS is unavailable", dir);
+            }
+            Some(Spool::open(dir.into(), max_bytes, &cursors).await?)
+        }
+        Err(_) if outbox => anyhow::bail!("OUTBOX_ENABLED requires SPOOL_DIR"),
+        Err(_) => None,
+    };
+
+    let mut fanout = Fanout::new();
+    let mut outbox_sinks = Vec::new();
+    for (sink, limits) in sinks {
+        if outbox {
+            outbox_sinks.push((sink, limits));
+        } else {
+            fanout.register(sink, limits);
+        }
+    }
+
+    let mut api_keys = ApiKeys::parse(
+        &std::env::var("API_KEYS").unwrap_or_default(),
+        &[Scope::Ingest],
+    )?;
+    api_keys.extend(ApiKeys::parse(
+        &std::env::var("ADMIN_TOKENS").unwrap_or_default(),
+        &[Scope::Admin],
+    )?);
+
+    let jwt = match (std::env::var("JWT_ISSUER"), std::env::var("JWT_JWKS_URL")) {
+        (Ok(issuer), Ok(jwks_url)) => {
+            let jwks_ttl_secs: u64 = std::env::var("JWT_JWKS_TTL_SECS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()
+                .expect("Invalid JWT_JWKS_TTL_SECS");
+            info!("Accepting JWTs issued by {}", issuer);
+            Some(JwtValidator::new(JwtConfig {
+                issuer,
+                jwks_url,
+                audience: std::env::var("JWT_AUDIENCE").ok(),
+                algorithm: std::env::var("JWT_ALGORITHM")
+                    .ok()
+                    .map(|alg| alg.parse().expect("Invalid JWT_ALGORITHM")),
+                jwks_ttl: Duration::from_secs(jwks_ttl_secs),
+            }))
+        }
+        _ => None,
+    };
+
+    let auth = Authenticator::new(api_keys, jwt);
+    if !auth.requires_ingest_auth() {
+        warn!("No API_KEYS or JWT issuer with the ingest scope configured, ingestion is unauthenticated");
+    }
+    if !auth.admin_enabled() {
+        warn!("No admin credentials configured, admin API is disabled");
+    }
+
+    info!("Rate limit per agent: {} req/sec", rate_limit_per_agent);
+    info!("Instance ID: {}", signer.instance_id());
+    info!(
+        "Signature verification: {} concurrent chunks of up to {} events",
+        verify_concurrency, verify_chunk_size
+    );
+    info!(
+        "Batch validation: {} concurrent events, {}ms each",
+        validation_concurrency, validation_timeout_ms
+    );
+    info!("Server public key: {}", signer.public_key_base64());
+
+    let mut endpoints = vec![
+        "GET /health",
+        "GET /ready",
+        "GET /metrics",
+        "GET /v1/capabilities",
+        "GET /openapi.json",
+        "GET /docs",
+        "POST /v1/ingest",
+        "POST /v1/ingest/batch",
+        "POST /v1/ingest/legacy",
+        "POST /v1/agents/register",
+        "POST /v1/agents/:agent_id/heartbeat",
+        "GET /v1/receipts/:facto_id",
+        "GET /.well-known/facto-server-key.json",
+        "GET /v1/checkpoints",
+        "GET /v1/proof/:facto_id",
+        "GET /v1/anchors",
+    ];
+    if auth.debug_enabled() {
+        endpoints.push("GET /v1/debug/:debug_id");
+    }
+    if auth.debug_enabled() && rejects.is_some() {
+        endpoints.push("GET /v1/rejects");
+    }
+    if auth.admin_enabled() {
+        endpoints.extend([
+            "GET /v1/admin/keys/snapshot",
+            "POST /v1/admin/keys/snapshot",
+            "GET /v1/admin/keys/:agent_id",
+            "POST /v1/admin/keys/:agent_id",
+            "POST /v1/admin/keys/:agent_id/revoke",
+            "POST /v1/admin/keys/:agent_id/rotate",
+            "GET /v1/admin/sessions/:session_id/freeze",
+            "POST /v1/admin/sessions/:session_id/freeze",
+            "POST /v1/admin/sessions/:session_id/unfreeze",
+            "GET /v1/admin/tenants",
+            "GET /v1/admin/tenants/:tenant_id",
+            "PUT /v1/admin/tenants/:tenant_id",
+            "DELETE /v1/admin/tenants/:tenant_id",
+            "PUT /v1/admin/tenants/:tenant_id/headers",
+            "PUT /v1/admin/tenants/:tenant_id/enforcement-mode",
+            "DELETE /v1/admin/tenants/:tenant_id/enforcement-mode",
+            "GET /v1/admin/schemas",
+            "GET /v1/admin/schemas/:action_type",
+            "PUT /v1/admin/schemas/:action_type",
+            "DELETE /v1/admin/schemas/:action_type",
+            "GET /v1/admin/models",
+            "GET /v1/admin/models/:tenant_id",
+            "PUT /v1/admin/models/:tenant_id",
+            "DELETE /v1/admin/models/:tenant_id",
+            "GET /v1/admin/retention",
+            "GET /v1/admin/retention/:tenant_id",
+            "PUT /v1/admin/retention/:tenant_id",
+            "DELETE /v1/admin/retention/:tenant_id",
+            "GET /v1/admin/legal-holds",
+            "PUT /v1/admin/legal-holds/:tenant_id/:session_id",
+            "DELETE /v1/admin/legal-holds/:tenant_id/:session_id",
+            "GET /v1/admin/redactions",
+            "GET /v1/admin/redactions/:tenant_id",
+            "PUT /v1/admin/redactions/:tenant_id",
+            "DELETE /v1/admin/redactions/:tenant_id",
+            "GET /v1/admin/shadow",
+            "GET /v1/agents",
+            "GET /v1/usage",
+            "GET /v1/admin/agents",
+            "GET /v1/admin/agents/:agent_id",
+            "PUT /v1/admin/agents/:agent_id/rate-limit",
+            "DELETE /v1/admin/agents/:agent_id/rate-limit",
+            "PUT /v1/admin/agents/:agent_id/byte-quota",
+            "DELETE /v1/admin/agents/:agent_id/byte-quota",
+            "PUT /v1/admin/agents/:agent_id/enforcement-mode",
+            "DELETE /v1/admin/agents/:agent_id/enforcement-mode",
+            "PUT /v1/admin/agents/:agent_id/envelope-trust",
+            "DELETE /v1/admin/agents/:agent_id/envelope-trust",
+            "POST /v1/admin/agents/:agent_id/pause",
+            "POST /v1/admin/agents/:agent_id/resume",
+            "GET /v1/sessions/:session_id/annotations",
+            "POST /v1/sessions/:session_id/annotations",
+        ]);
+    }
+    if auth.admin_enabled() && webhooks.is_some() {
+        endpoints.extend([
+            "GET /v1/admin/webhooks",
+            "POST /v1/admin/webhooks",
+            "GET /v1/admin/webhooks/:webhook_id",
+            "DELETE /v1/admin/webhooks/:webhook_id",
+        ]);
+    }
+    if auth.admin_enabled() && cursors.is_some() {
+        endpoints.extend([
+            "POST /v1/cursors",
+            "GET /v1/cursors/:name",
+            "DELETE /v1/cursors/:name",
+            "GET /v1/cursors/:name/events",
+            "POST /v1/cursors/:name/commit",
+        ]);
+    }
+    let capabilities = Capabilities {
+        server_version: env!("CARGO_PKG_VERSION"),
+        event_versions: versions::SUPPORTED_EVENT_VERSIONS,
+        signature_algorithms: crypto::SIGNATURE_ALGORITHMS,
+        hash_algorithm: crypto::HASH_ALGORITHM,
+        canonical_versions: crypto::CANONICAL_VERSIONS,
+        canonical_v1_until,
+        did_methods: match dids.resolves_web() {
+            true => did::DID_METHODS,
+            false => &did::DID_METHODS[..1],
+        },
+        max_body_bytes,
+        max_batch_events: limits.max_batch_events,
+        max_event_bytes: limits.max_event_bytes,
+        max_tool_calls: limits.max_tool_calls,
+        offload_threshold_bytes: limits.offload_threshold,
+        rate_limit_per_agent,
+        replay_window_secs: replay_window.map(|w| w.as_secs()),
+        replay_max_skew_secs,
+        auth_methods: auth
+            .ingest_methods()
+            .into_iter()
+            .chain(
+                tls.as_ref()
+                    .filter(|t| t.client_certificates())
+                    .map(|_| "mtls"),
+            )
+            .collect(),
+        content_types: encoding::CONTENT_TYPES,
+        content_encodings: decompress::CONTENT_ENCODINGS,
+        endpoints,
+    };
+
+    // Initialize application state
+    let verifier = Verifier::new(
+        signer,
+        VerificationCache::new(
+            verification_cache_size,
+            Duration::from_secs(verification_cache_ttl_secs),
+        ),
+        key_registry.clone(),
+        dids,
+        verify_concurrency,
+        verify_chunk_size,
+        canonical_v1_until.map(|secs| secs * 1_000_000_000),
+    );
+    let sandbox = match sandbox_enabled {
+        true => {
+            info!(
+                "Sandbox mode enabled, events expire after {} days",
+                sandbox_retention_days
+            );
+            Some(Sandbox::new(
+                &verifier,
+                Duration::from_secs(sandbox_retention_days * 24 * 60 * 60),
+            )?)
+        }
+        false => None,
+    };
+    let dedup_ttl = Duration::from_secs(dedup_ttl_secs);
+    let replay_max_skew = Duration::from_secs(replay_max_skew_secs);
+    let mut shared_buckets = Vec::new();
+    if shared_state == SharedStateBackend::Nats {
+        info!("Sharing rate limits, dedup, replay protection and chain heads through NATS");
+    }
+    let rate_limiter: Box<dyn RateLimitStore> = match shared_state {
+        SharedStateBackend::Memory => Box::new(AgentRateLimiter::new()),
+        SharedStateBackend::Nats => {
+            let rate_limiter = KvRateLimiter::new();
+            shared_buckets.push(rate_limiter.bucket());
+            Box::new(rate_limiter)
+        }
+    };
+    let dedup: Box<dyn DedupStore> = match (shared_state, dedup_cache_size) {
+        (SharedStateBackend::Nats, 1..) => {
+            let dedup = KvDedup::new(dedup_cache_size, dedup_ttl);
+            shared_buckets.push(dedup.bucket());
+            Box::new(dedup)
+        }
+        _ => Box::new(DedupCache::new(dedup_cache_size, dedup_ttl)),
+    };
+    let replay: Box<dyn ReplayStore> = match (shared_state, replay_window) {
+        (SharedStateBackend::Nats, Some(window)) => {
+            let replay = KvReplayGuard::new(window, replay_max_skew);
+            shared_buckets.push(replay.bucket());
+            Box::new(replay)
+        }
+        _ => Box::new(ReplayGuard::new(replay_window, replay_max_skew)),
+    };
+    if let Some(ref routing) = chain_routing {
+        info!(
+            "Routing sessions by hash, this is replica {} of {}",
+            routing.index(),
+            routing.count()
+        );
+    }
+    let chain_heads: Box<dyn ChainHeadStore> = match (shared_state, chain_routing) {
+        (SharedStateBackend::Nats, None) => {
+            let chain_heads = KvChainHeads::new(Duration::from_secs(chain_head_ttl_secs));
+            shared_buckets.push(chain_heads.bucket());
+            Box::new(chain_heads)
+        }
+        _ => Box::new(ChainHeads::new()),
+    };
+
+    // SIGTERM drains in-flight requests and flushes publishes before exiting
+    let shutdown = Shutdown::from_env();
+
+    // Probes behind /ready. Events are delivered through the transport or,
+    // while it is down, the spool; with the outbox every event goes through
+    // the spool
+    let health_check_timeout_ms: u64 = std::env::var("HEALTH_CHECK_TIMEOUT_MS")
+        .unwrap_or_else(|_| "2000".to_string())
+        .parse()
+        .expect("Invalid HEALTH_CHECK_TIMEOUT_MS");
+    let mut health = HealthChecks::new(Duration::from_millis(health_check_timeout_ms));
+    match kafka {
+        Some(_) => {
+            health.register(KafkaCheck);
+            health.register(JetStreamCheck {
+                requirement: Requirement::Informational,
+            });
+        }
+        None => health.register(JetStreamCheck {
+            requirement: Requirement::Delivery,
+        }),
+    }
+    if spool.is_some() {
+        health.register(SpoolCheck {
+            requirement: match outbox {
+                true => Requirement::Required,
+                false => Requirement::Delivery,
+            },
+        });
+    }
+    if !shared_buckets.is_empty() {
+        health.register(SharedStateCheck);
+    }
+    health.register(KeyRegistryCheck);
+
+    let state = Arc::new(AppState {
+        nats_client,
+        sink,
+        session_partitions,
+        rate_limiter,
+        rate_limit_per_agent: AtomicU32::new(rate_limit_per_agent),
+        agent_byte_quota: AtomicU64::new(agent_byte_quota),
+        byte_quotas: ByteQuotas::new(Duration::from_secs(agent_byte_quota_window_secs)),
+        agent_controls: AgentControls::new(
+            std::env::var("AGENT_CONTROLS_PATH").ok().map(Into::into),
+        )?,
+        enforcement_mode: std::env::var("VERIFICATION_MODE")
+            .unwrap_or_else(|_| "enforce".to_string())
+            .parse()
+            .expect("Invalid VERIFICATION_MODE"),
+        verifier,
+        key_registry,
+        snapshot_signers,
+        key_rotation_overlap_secs: std::env::var("KEY_ROTATION_OVERLAP_SECS")
+            .unwrap_or_else(|_| "3600".to_string())
+            .parse()
+            .expect("Invalid KEY_ROTATION_OVERLAP_SECS"),
+        auth,
+        dedup,
+        replay,
+        freezes,
+        annotations,
+        agents,
+        usage,
+        chain_heads,
+        chain_routing,
+        shared_buckets,
+        health,
+        checkpoints: Checkpoints::new(checkpoint_max_events, checkpoint_retention),
+        receipts: Receipts::new(receipt_retention),
+        anchors,
+        tenants,
+        schemas,
+        models,
+        policies,
+        redactions,
+        retention,
+        spool,
+        outbox,
+        sink_shaper: Shaper::new(&sink_limits),
+        fanout,
+        capabilities,
+        limits,
+        debug_bundles: DebugBundles::new(
+            Duration::from_secs(debug_bundle_ttl_secs),
+            debug_max_bundles,
+        ),
+        session_locks: SessionLocks::new(),
+        backfill,
+        sandbox,
+        cursors,
+        rejects,
+        raw_ingest,
+        dimensions,
+        shadow,
+        offloader,
+        webhooks,
+        propagation: HeaderPropagation::from_env(),
+        shutdown: shutdown.clone(),
+        metrics: metrics_handle.clone(),
+        validation: Validation::new(
+            validation_concurrency,
+            Duration::from_millis(validation_timeout_ms),
+        ),
+    });
+
+    // Spawn NATS connection task
+    let nats_state = state.clone();
+    tokio::spawn(connect_to_nats(nats_state, nats));
+
+    // Spawn Kafka connection monitor
+    if let Some(kafka) = kafka {
+        tokio::spawn(kafka.run());
+    }
+
+    // Spawn config reloader
+    let config_watch_secs: u64 = std::env::var("CONFIG_WATCH_SECS")
+        .unwrap_or_else(|_| "5".to_string())
+        .parse()
+        .expect("Invalid CONFIG_WATCH_SECS");
+    tokio::spawn(watch_config(
+        state.clone(),
+        config,
+        Duration::from_secs(config_watch_secs),
+    ));
+
+    // Spawn backfill releaser
+    tokio::spawn(run_backfill(state.clone(), Duration::from_secs(1)));
+
+    // Spawn silent agent checks, a few per silence threshold
+    tokio::spawn(agents::run_silence_checks(
+        state.clone(),
+        (agent_silence / 4).clamp(Duration::from_secs(1), Duration::from_secs(60)),
+    ));
+
+    // Spawn usage flusher
+    tokio::spawn(usage::run_usage_flusher(
+        state.clone(),
+        Duration::from_secs(usage_flush_secs.max(1)),
+    ));
+
+    // Spawn metrics pusher
+    if let Some(ref push) = metrics_push {
+        tokio::spawn(push.clone().run(metrics_handle.clone()));
+    }
+
+    // Spawn checkpointer
+    tokio::spawn(run_checkpointer(
+        state.clone(),
+        Duration::from_secs(checkpoint_interval_secs),
+    ));
+
+    // Spawn checkpoint anchoring
+    if let Some(anchorer) = anchorer {
+        info!("Anchoring checkpoints every {}s", anchor_interval_secs);
+        tokio::spawn(run_anchoring(
+            state.clone(),
+            anchorer,
+            Duration::from_secs(anchor_interval_secs),
+        ));
+    }
+
+    // Spawn the dead-letter publisher
+    if let (Some(rejects), Some(queue)) = (state.rejects.clone(), reject_queue) {
+        tokio::spawn(rejects.run(queue));
+    }
+
+    // Spawn classification publisher
+    if let Some(classifications) = classifications {
+        tokio::spawn(publish_classifications(state.clone(), classifications));
+    }
+
+    // Spawn policy alert publisher
+    if let Some((alerts, webhook)) = policy_alerts {
+        tokio::spawn(publish_policy_alerts(state.clone(), alerts, webhook));
+    }
+
+    // Spawn spool drain tasks
+    tokio::spawn(drain_spool(state.clone()));
+    for (sink, limits) in outbox_sinks {
+        tokio::spawn(drain_to_sink(state.clone(), sink, limits));
+    }
+
+    let app = router(state.clone());
+
+    // Start server
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    info!("Listening on {}", addr);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tokio::spawn(shutdown::listen_for_signals(shutdown.clone()));
+    let server = match tls {
+        Some(tls) => {
+            tokio::spawn(tls.clone().watch());
+            tls::serve(listener, app, tls, shutdown.clone()).boxed()
+        }
+        None => axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown.clone().started())
+            .into_future()
+            .boxed(),
+    };
+    tokio::select! {
+        result = server => result?,
+        _ = shutdown.clone().expired() => {
+            warn!(
+                "Shutdown deadline passed, abandoning {} in-flight requests",
+                shutdown.in_flight()
+            );
+            counter!("facto_shutdown_abandoned_requests_total")
+                .increment(shutdown.in_flight() as u64);
+        }
+    }
+    histogram!("facto_shutdown_drain_seconds").record(shutdown.elapsed().as_secs_f64());
+
+    flush_on_shutdown(&state).await;
+    tracing.shutdown(Duration::from_secs(5)).await;
+    if let Some(provider) = meter_provider {
+        let _ = tokio::task::spawn_blocking(move || provider.shutdown()).await;
+    }
+    if let Some(push) = metrics_push {
+        push.push(&metrics_handle).await;
+    }
+    info!("Shut down after {:?}", shutdown.elapsed());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::BatchEnvelope;
+    use crate::testing::{legacy_test_event, sign_test_event, test_event};
+    use crate::verification::TrustBasis;
+    use axum::{async_trait, body::Body, http::Request};
+    use ed25519_dalek::SigningKey;
+    use std::{collections::BTreeSet, sync::Mutex};
+    use tower::ServiceExt;
+    use transport::{PendingAck, SinkError};
+
+    /// A broker that stores what it is sent while connected
+    struct TestSink {
+        connected: bool,
+        published: Mutex<Vec<(FactoEvent, ServerEnvelope)>>,
+    }
+
+    impl TestSink {
+        fn new(connected: bool) -> Arc<Self> {
+            Arc::new(Self {
+                connected,
+                published: Mutex::new(Vec::new()),
+            })
+        }
+
+        fn published(&self) -> Vec<String> {
+            self.messages()
+                .into_iter()
+                .map(|(event, _)| event.facto_id)
+                .collect()
+        }
+
+        fn messages(&self) -> Vec<(FactoEvent, ServerEnvelope)> {
+            self.published.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl Sink for TestSink {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
+        async fn is_connected(&self) -> bool {
+            self.connected
+        }
+
+        async fn send(
+            &self,
+            event: &FactoEvent,
+            envelope: &ServerEnvelope,
+        ) -> Result<PendingAck, SinkError> {
+            if !self.connected {
+                return Err(SinkError::NotConnected("test"));
+            }
+            let mut published = self.published.lock().unwrap();
+            published.push((event.clone(), envelope.clone()));
+            let sequence = published.len() as u64;
+            Ok(async move { Ok(Some(sequence)) }.boxed())
+        }
+
+        async fn flush(&self) -> Result<(), SinkError> {
+            Ok(())
+        }
+    }
+
+    async fn test_spool() -> Spool {
+        let dir = std::env::temp_dir().join(format!("facto-app-{}", uuid::Uuid::new_v4()));
+        Spool::open(dir, 1 << 20, &[NATS_CURSOR]).await.unwrap()
+    }
+
+    /// The state of a server with every optional feature off and no
+    /// credentials configured
+    fn test_state(sink: Arc<TestSink>, spool: Option<Spool>, outbox: bool) -> Arc<AppState> {
+        let key_registry = Arc::new(KeyRegistry::new(None, false).unwrap());
+        let limits = RequestLimits {
+            max_body_bytes: 1 << 20,
+            max_batch_events: None,
+            max_event_bytes: 1 << 20,
+            max_tool_calls: None,
+            offload_threshold: None,
+        };
+        let sink_limits = SinkLimits {
+            rate_per_sec: None,
+            concurrency: 16,
+            queue_capacity: 16,
+        };
+        Arc::new(AppState {
+            nats_client: Arc::new(RwLock::new(None)),
+            sink,
+            session_partitions: 0,
+            rate_limiter: Box::new(AgentRateLimiter::new()),
+            rate_limit_per_agent: AtomicU32::new(0),
+            agent_byte_quota: AtomicU64::new(0),
+            byte_quotas: ByteQuotas::new(Duration::from_secs(60)),
+            agent_controls: AgentControls::new(None).unwrap(),
+            enforcement_mode: EnforcementMode::Enforce,
+            verifier: Verifier::new(
+                ServerSigner::from_seed("ingestion-test".to_string(), None).unwrap(),
+                VerificationCache::new(16, Duration::from_secs(60)),
+                key_registry.clone(),
+                Arc::new(DidResolver::new(Vec::new(), Duration::from_secs(60))),
+                1,
+                64,
+                None,
+            ),
+            key_registry,
+            snapshot_signers: Vec::new(),
+            key_rotation_overlap_secs: 0,
+            auth: Authenticator::new(ApiKeys::parse("", &[Scope::Ingest]).unwrap(), None),
+            dedup: Box::new(DedupCache::new(16, Duration::from_secs(60))),
+            replay: Box::new(ReplayGuard::new(None, Duration::from_secs(30))),
+            freezes: SessionFreezes::new(None).unwrap(),
+            annotations: SessionAnnotations::new(None).unwrap(),
+            agents: AgentInventory::new(None, Duration::from_secs(60)).unwrap(),
+            usage: UsageLedger::new(None, 1).unwrap(),
+            chain_heads: Box::new(ChainHeads::new()),
+            chain_routing: None,
+            shared_buckets: Vec::new(),
+            health: HealthChecks::new(Duration::from_secs(1)),
+            checkpoints: Checkpoints::new(16, 16),
+            receipts: Receipts::new(16),
+            anchors: Anchors::new(None).unwrap(),
+            tenants: Tenants::new(None).unwrap(),
+            schemas: SchemaRegistry::new(None).unwrap(),
+            models: ModelRegistry::new(None).unwrap(),
+            policies: PolicyEngine::new(),
+            redactions: Redactions::new(None, RedactionKeys::new(None, None)).unwrap(),
+            retention: Retention::new(None).unwrap(),
+            spool,
+            outbox,
+            sink_shaper: Shaper::new(&sink_limits),
+            fanout: Fanout::new(),
+            capabilities: Capabilities {
+                server_version: env!("CARGO_PKG_VERSION"),
+                event_versions: versions::SUPPORTED_EVENT_VERSIONS,
+                signature_algorithms: crypto::SIGNATURE_ALGORITHMS,
+                hash_algorithm: crypto::HASH_ALGORITHM,
+                canonical_versions: crypto::CANONICAL_VERSIONS,
+                canonical_v1_until: None,
+                did_methods: &did::DID_METHODS[..1],
+                max_body_bytes: limits.max_body_bytes,
+                max_batch_events: None,
+                max_event_bytes: limits.max_event_bytes,
+                max_tool_calls: None,
+                offload_threshold_bytes: None,
+                rate_limit_per_agent: 0,
+                replay_window_secs: None,
+                replay_max_skew_secs: 30,
+                auth_methods: Vec::new(),
+                content_types: encoding::CONTENT_TYPES,
+                content_encodings: decompress::CONTENT_ENCODINGS,
+                endpoints: Vec::new(),
+            },
+            limits,
+            debug_bundles: DebugBundles::new(Duration::from_secs(60), 16),
+            session_locks: SessionLocks::new(),
+            backfill: BackfillBuffer::new(16, Duration::from_secs(60), Duration::from_secs(60)),
+            sandbox: None,
+            cursors: None,
+            rejects: None,
+            raw_ingest: None,
+            dimensions: None,
+            shadow: None,
+            offloader: None,
+            webhooks: None,
+            propagation: HeaderPropagation::new([]),
+            shutdown: Shutdown::new(Duration::from_secs(1)),
+            metrics: PrometheusBuilder::new().build_recorder().handle(),
+            validation: Validation::new(4, Duration::from_secs(1)),
+        })
+    }
+
+    async fn ingest(state: &Arc<AppState>, event: &FactoEvent) -> (StatusCode, serde_json::Value) {
+        post_json(state, "/v1/ingest", event).await
+    }
+
+    async fn post_json(
+        state: &Arc<AppState>,
+        uri: &str,
+        body: &impl serde::Serialize,
+    ) -> (StatusCode, serde_json::Value) {
+        let request = Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_vec(body).unwrap()))
+            .unwrap();
+        let response = router(state.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn signed_event() -> FactoEvent {
+        sign_test_event(test_event(), &SigningKey::from_bytes(&[7; 32]))
+    }
+
+    #[tokio::test]
+    async fn test_ingest_publishes_accepted_event() {
+        let sink = TestSink::new(true);
+        let state = test_state(sink.clone(), None, false);
+
+        let (status, body) = ingest(&state, &signed_event()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(body["accepted"], true);
+        assert!(body.get("spooled").is_none());
+        assert_eq!(body["receipt"]["sequence"], 1);
+        assert_eq!(sink.published(), vec!["tr-test-123"]);
+
+        // The same event again is a duplicate, not published twice
+        let (status, body) = ingest(&state, &signed_event()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["duplicate"], true);
+        assert_eq!(sink.published().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_rejects_bad_signature() {
+        let sink = TestSink::new(true);
+        let state = test_state(sink.clone(), None, false);
+
+        let mut event = signed_event();
+        event.proof.signature = signed_event().proof.event_hash;
+        let (status, body) = ingest(&state, &event).await;
+        assert!(status.is_client_error(), "{}", status);
+        assert_eq!(body["accepted"], false);
+        assert_eq!(body["error"]["code"], "SIGNATURE_INVALID");
+        assert!(sink.published().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ingest_spools_while_sink_is_down() {
+        let sink = TestSink::new(false);
+        let state = test_state(sink.clone(), Some(test_spool().await), false);
+
+        let (status, body) = ingest(&state, &signed_event()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(body["spooled"], true);
+        assert_eq!(state.spool.as_ref().unwrap().depth(), 1);
+        assert!(sink.published().is_empty());
+
+        // Without a spool the event is refused
+        let state = test_state(sink, None, false);
+        let (status, body) = ingest(&state, &signed_event()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["error"]["code"], "SERVICE_NOT_READY");
+    }
+
+    #[tokio::test]
+    async fn test_ingest_queues_to_outbox() {
+        let sink = TestSink::new(true);
+        let state = test_state(sink.clone(), Some(test_spool().await), true);
+
+        let (status, body) = ingest(&state, &signed_event()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(body["accepted"], true);
+        assert!(body.get("spooled").is_none());
+        assert!(body["receipt"]["sequence"].is_null());
+        assert_eq!(state.spool.as_ref().unwrap().depth(), 1);
+
+        // The sink only sees it once the outbox is drained
+        assert!(sink.published().is_empty());
+        assert_eq!(
+            drain_spool_once(&state, state.spool.as_ref().unwrap()).await,
+            1
+        );
+        assert_eq!(sink.published(), vec!["tr-test-123"]);
+    }
+
+    #[tokio::test]
+    async fn test_batch_envelope_is_published_with_enveloped_events() {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+        use ed25519_dalek::Signer as _;
+
+        let sink = TestSink::new(true);
+        let state = test_state(sink.clone(), None, false);
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let events: Vec<FactoEvent> = (0..2)
+            .map(|i| {
+                let mut event = test_event();
+                event.facto_id = format!("tr-enveloped-{}", i);
+                event.session_id = format!("session-enveloped-{}", i);
+                let mut event = sign_test_event(event, &key);
+                // Only the envelope is signed
+                event.proof.signature = BASE64.encode([0u8; 64]);
+                event
+            })
+            .collect();
+        let batch_hash = crypto::compute_batch_hash(&events);
+        let envelope = BatchEnvelope {
+            signature: BASE64.encode(key.sign(batch_hash.as_bytes()).to_bytes()),
+            public_key: events[0].proof.public_key.clone(),
+            batch_hash,
+            algorithm: None,
+        };
+        let batch = serde_json::json!({"events": events, "envelope": envelope});
+
+        // Until the agent is envelope-trusted, each signature is checked
+        let (_, body) = post_json(&state, "/v1/ingest/batch", &batch).await;
+        assert_eq!(body["accepted_count"], 0);
+        assert_eq!(body["rejected"][0]["error"]["code"], "SIGNATURE_INVALID");
+
+        state
+            .agent_controls
+            .set_envelope_trusted(None, &events[0].agent_id, true)
+            .unwrap();
+        let (status, body) = post_json(&state, "/v1/ingest/batch", &batch).await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+        assert_eq!(body["accepted_count"], 2);
+
+        // Each published event carries what vouches for it
+        let messages = sink.messages();
+        assert_eq!(messages.len(), 2);
+        for (event, server_envelope) in &messages {
+            let verification = &server_envelope.verification;
+            let TrustBasis::BatchEnvelope {
+                envelope: published,
+                event_hashes,
+                ..
+            } = &verification.trust_basis
+            else {
+                panic!("{:?}", verification.trust_basis);
+            };
+            assert_eq!(*published, envelope);
+            let canonical = crypto::build_canonical_form(event).unwrap();
+            assert!(crypto::verify_signature(event, &canonical).is_err());
+            assert!(crypto::verify_envelope_membership(
+                published,
+                event_hashes,
+                &verification.event_hash,
+                &event.proof.public_key
+            )
+            .is_ok());
+
+            // Dropping a member, naming another key or forging the
+            // signature all break it
+            assert!(crypto::verify_envelope_membership(
+                published,
+                &event_hashes[..1],
+                &events[1].proof.event_hash,
+                &event.proof.public_key
+            )
+            .is_err());
+            assert!(crypto::verify_envelope_membership(
+                published,
+                event_hashes,
+                &verification.event_hash,
+                &sign_test_event(test_event(), &SigningKey::from_bytes(&[8; 32]))
+                    .proof
+                    .public_key
+            )
+            .is_err());
+            let mut forged = published.clone();
+            forged.signature = BASE64.encode([0u8; 64]);
+            assert!(crypto::verify_envelope_membership(
+                &forged,
+                event_hashes,
+                &verification.event_hash,
+                &event.proof.public_key
+            )
+            .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_legacy_event_is_translated_and_ingested() {
+        let sink = TestSink::new(true);
+        let state = test_state(sink.clone(), None, false);
+        let event = signed_event();
+
+        let (status, body) =
+            post_json(&state, "/v1/ingest/legacy", &legacy_test_event(&event)).await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+        assert_eq!(body["accepted"], true);
+
+        // Published in the current shape, with the translation on record
+        let messages = sink.messages();
+        assert_eq!(messages.len(), 1);
+        let (published, envelope) = &messages[0];
+        assert_eq!(published.facto_id, event.facto_id);
+        assert_eq!(published.proof.event_hash, event.proof.event_hash);
+        assert_eq!(
+            envelope.translation.as_ref().map(|t| t.from.as_str()),
+            Some(legacy::LEGACY_FORMAT)
+        );
+        assert_eq!(envelope.verification.trust_basis, TrustBasis::EmbeddedKey);
+
+        // The normal pipeline applies: a retry is a duplicate and a tampered
+        // event is refused
+        let (status, body) =
+            post_json(&state, "/v1/ingest/legacy", &legacy_test_event(&event)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["duplicate"], true);
+        let mut tampered = legacy_test_event(&event);
+        tampered["trace_id"] = serde_json::json!("tr-tampered");
+        let (status, body) = post_json(&state, "/v1/ingest/legacy", &tampered).await;
+        assert!(status.is_client_error(), "{}", status);
+        assert_eq!(body["error"]["code"], "HASH_MISMATCH");
+
+        // Current events are not accepted as legacy ones, nor the reverse
+        let (status, _) = post_json(&state, "/v1/ingest/legacy", &event).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (status, _) = post_json(&state, "/v1/ingest", &legacy_test_event(&event)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(sink.messages().len(), 1);
+        assert!(sink.messages()[0].1.translation.is_some());
+
+        // Events sent to /v1/ingest carry no translation
+        let (status, _) = ingest(&state, &{
+            let mut other = test_event();
+            other.facto_id = "tr-current".to_string();
+            other.session_id = "session-current".to_string();
+            sign_test_event(other, &SigningKey::from_bytes(&[7; 32]))
+        })
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(sink.messages()[1].1.translation.is_none());
+    }
+
+    /// Routes registered in [`router`], as (method, OpenAPI path)
+    fn routes() -> BTreeSet<(String, String)> {
+        let source = include_str!("lib.rs");
+        let start = source.find("pub fn router(").unwrap();
+        let end = start + source[start..].find("\n}\n").unwrap();
+        let method = regex::Regex::new(r"\b(get|post|put|delete)\(").unwrap();
+        let mut routes = BTreeSet::new();
+        for route in source[start..end].split(".route(").skip(1) {
+            let route = route.trim_start();
+            let path = match route.strip_prefix('"') {
+                Some(rest) => &rest[..rest.find('"').unwrap()],
+                None if route.starts_with("receipts::SERVER_KEY_PATH") => receipts::SERVER_KEY_PATH,
+                None => panic!("Unrecognized route {}", route),
+            };
+            let path = path
+                .split('/')
+                .map(|segment| match segment.strip_prefix(':') {
+                    Some(name) => format!("{{{}}}", name),
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            for captures in method.captures_iter(route) {
+                routes.insert((captures[1].to_string(), path.clone()));
+            }
+        }
+        routes
+    }
+
+    #[tokio::test]
+    async fn test_openapi_describes_every_route() {
+        let document = openapi::document(env!("CARGO_PKG_VERSION"));
+        let described = document["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, operations)| {
+                operations
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .map(|method| (method.clone(), path.clone()))
+            })
+            .collect::<BTreeSet<_>>();
+        let routes = routes();
+        assert!(routes.len() > 60, "{} routes found", routes.len());
+        assert_eq!(
+            routes.difference(&described).collect::<Vec<_>>(),
+            Vec::<&(String, String)>::new(),
+            "routes not described"
+        );
+        assert_eq!(
+            described.difference(&routes).collect::<Vec<_>>(),
+            Vec::<&(String, String)>::new(),
+            "described but not routed"
+        );
+
+        // The router agrees: every described operation reaches a handler
+        let state = test_state(TestSink::new(true), None, false);
+        for (method, path) in described {
+            let uri = path.replace(['{', '}'], "");
+            let request = Request::builder()
+                .method(method.to_uppercase().as_str())
+                .uri(&uri)
+                .body(Body::empty())
+                .unwrap();
+            let response = router(state.clone()).oneshot(request).await.unwrap();
+            assert_ne!(
+                response.status(),
+                StatusCode::METHOD_NOT_ALLOWED,
+                "{} {}",
+                method,
+                path
+            );
+            let routed = response.status() != StatusCode::NOT_FOUND
+                || !axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap()
+                    .is_empty();
+            assert!(routed, "{} {} is not routed", method, path);
+        }
+    }
+}
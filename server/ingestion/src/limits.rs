@@ -0,0 +1,298 @@
+use crate::encoding::{DecodeError, Encoding};
+use crate::protocol::{BatchIngestRequest, ErrorCode, ErrorResponse};
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{rejection::JsonRejection, FromRequest, Json, Request},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+
+use crate::offload::published_size;
+use crate::{event_size, AppState, FactoEvent};
+
+/// Default for `MAX_EVENT_BYTES`: the default NATS `max_payload`, beyond
+/// which the publish would fail anyway
+pub const DEFAULT_MAX_EVENT_BYTES: u64 = 1024 * 1024;
+
+/// Size limits checked before an event is validated
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    pub max_body_bytes: usize,
+    pub max_batch_events: Option<usize>,
+    /// Size of an event's JSON encoding
+    pub max_event_bytes: u64,
+    pub max_tool_calls: Option<usize>,
+    /// Payloads over this size are offloaded and not counted against
+    /// `max_event_bytes`
+    pub offload_threshold: Option<u64>,
+}
+
+impl RequestLimits {
+    /// Read `MAX_BATCH_EVENTS`, `MAX_EVENT_BYTES` and `MAX_TOOL_CALLS`; the
+    /// batch and tool call limits are off if unset
+    pub fn from_env(max_body_bytes: usize) -> Self {
+        let max_batch_events = std::env::var("MAX_BATCH_EVENTS")
+            .ok()
+            .map(|v| v.parse().expect("Invalid MAX_BATCH_EVENTS"));
+        let max_event_bytes = std::env::var("MAX_EVENT_BYTES")
+            .map_or(DEFAULT_MAX_EVENT_BYTES, |v| {
+                v.parse().expect("Invalid MAX_EVENT_BYTES")
+            });
+        let max_tool_calls = std::env::var("MAX_TOOL_CALLS")
+            .ok()
+            .map(|v| v.parse().expect("Invalid MAX_TOOL_CALLS"));
+
+        Self {
+            max_body_bytes,
+            max_batch_events,
+            max_event_bytes,
+            max_tool_calls,
+            offload_threshold: None,
+        }
+    }
+
+    pub fn check_batch(&self, events: &[FactoEvent]) -> Result<(), LimitError> {
+        if let Some(limit) = self.max_batch_events.filter(|limit| events.len() > *limit) {
+            return Err(LimitError::BatchTooLarge {
+                count: events.len(),
+                limit,
+            });
+        }
+        events.iter().try_for_each(|event| self.check_event(event))
+    }
+
+    pub fn check_event(&self, event: &FactoEvent) -> Result<(), LimitError> {
+        let count = event.execution_meta.tool_calls.len();
+        if let Some(limit) = self.max_tool_calls.filter(|limit| count > *limit) {
+            return Err(LimitError::TooManyToolCalls {
+                facto_id: event.facto_id.clone(),
+                count,
+                limit,
+            });
+        }
+        let bytes = match self.offload_threshold {
+            Some(threshold) => published_size(event, threshold),
+            None => event_size(event),
+        };
+        if bytes > self.max_event_bytes {
+            return Err(LimitError::EventTooLarge {
+                facto_id: event.facto_id.clone(),
+                bytes,
+                limit: self.max_event_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum LimitError {
+    #[error("Request body exceeds {limit} bytes")]
+    BodyTooLarge { limit: usize },
+    #[error("Request body exceeds {limit} bytes once decompressed")]
+    DecompressedTooLarge { limit: usize },
+    #[error("Batch of {count} events exceeds the limit of {limit}")]
+    BatchTooLarge { count: usize, limit: usize },
+    #[error("Event {facto_id} is {bytes} bytes, over the limit of {limit}")]
+    EventTooLarge {
+        facto_id: String,
+        bytes: u64,
+        limit: u64,
+    },
+    #[error("Event {facto_id} has {count} tool calls, over the limit of {limit}")]
+    TooManyToolCalls {
+        facto_id: String,
+        count: usize,
+        limit: usize,
+    },
+}
+
+impl LimitError {
+    /// Machine-readable code returned with the error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            LimitError::BodyTooLarge { .. } | LimitError::DecompressedTooLarge { .. } => {
+                ErrorCode::PayloadTooLarge
+            }
+            LimitError::BatchTooLarge { .. } => ErrorCode::BatchTooLarge,
+            LimitError::EventTooLarge { .. } => ErrorCode::EventTooLarge,
+            LimitError::TooManyToolCalls { .. } => ErrorCode::TooManyToolCalls,
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            LimitError::TooManyToolCalls { .. } => StatusCode::BAD_REQUEST,
+            _ => StatusCode::PAYLOAD_TOO_LARGE,
+        }
+    }
+}
+
+impl IntoResponse for LimitError {
+    fn into_response(self) -> Response {
+        (
+            self.status(),
+            Json(ErrorResponse {
+                error: self.to_string(),
+                code: Some(self.code()),
+            }),
+        )
+            .into_response()
+    }
+}
+
+/// Request bodies whose events keep the JSON they were sent as, so that
+/// exactly those bytes are published
+pub trait JsonBody: DeserializeOwned {
+    fn from_json(body: &Bytes) -> serde_json::Result<Self>;
+}
+
+impl JsonBody for FactoEvent {
+    fn from_json(body: &Bytes) -> serde_json::Result<Self> {
+        FactoEvent::from_json(body)
+    }
+}
+
+impl JsonBody for BatchIngestRequest {
+    fn from_json(body: &Bytes) -> serde_json::Result<Self> {
+        BatchIngestRequest::from_json(body)
+    }
+}
+
+/// A request body in any supported [`Encoding`] whose size limit rejection
+/// carries a [`LimitError`]. `application/json` bodies keep their events'
+/// bytes; other JSON media types are parsed by axum and keep its
+/// rejections. Bodies that do not parse or decode are a 400, and ones of the
+/// wrong shape a 422, as with axum's JSON.
+pub struct LimitedBody<T>(pub T);
+
+#[async_trait]
+impl<T: JsonBody> FromRequest<Arc<AppState>> for LimitedBody<T> {
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &Arc<AppState>) -> Result<Self, Response> {
+        let too_large = || {
+            LimitError::BodyTooLarge {
+                limit: state.limits.max_body_bytes,
+            }
+            .into_response()
+        };
+        let encoding = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Encoding::from_media_type);
+
+        let encoding = match encoding {
+            Some(encoding) => encoding,
+            None => {
+                return match Json::<T>::from_request(request, state).await {
+                    Ok(Json(value)) => Ok(Self(value)),
+                    Err(JsonRejection::BytesRejection(rejection))
+                        if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE =>
+                    {
+                        Err(too_large())
+                    }
+                    Err(rejection) => Err(rejection.into_response()),
+                }
+            }
+        };
+
+        let body = match Bytes::from_request(request, state).await {
+            Ok(body) => body,
+            Err(rejection) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                return Err(too_large())
+            }
+            Err(rejection) => return Err(rejection.into_response()),
+        };
+        let rejection = |status: StatusCode, error: String| {
+            (status, Json(ErrorResponse { error, code: None })).into_response()
+        };
+        if encoding == Encoding::Json {
+            return T::from_json(&body)
+                .map(Self)
+                .map_err(|e| match e.classify() {
+                    serde_json::error::Category::Data => rejection(
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        format!(
+                            "Failed to deserialize the JSON body into the target type: {}",
+                            e
+                        ),
+                    ),
+                    _ => rejection(
+                        StatusCode::BAD_REQUEST,
+                        format!("Failed to parse the request body as JSON: {}", e),
+                    ),
+                });
+        }
+        encoding.decode(&body).map(Self).map_err(|e| match e {
+            DecodeError::Invalid(e) => rejection(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Failed to deserialize the body: {}", e),
+            ),
+            e => rejection(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Failed to decode the {} body: {}",
+                    encoding.content_type(),
+                    e
+                ),
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_event;
+
+    #[test]
+    fn test_limits_checked_in_order() {
+        let limits = RequestLimits {
+            max_body_bytes: 1024,
+            max_batch_events: Some(2),
+            max_event_bytes: 2048,
+            max_tool_calls: Some(1),
+            offload_threshold: None,
+        };
+        assert!(limits.check_batch(&[test_event(), test_event()]).is_ok());
+
+        let err = limits
+            .check_batch(&[test_event(), test_event(), test_event()])
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::BatchTooLarge);
+
+        let mut event = test_event();
+        event.output_data = serde_json::json!("x".repeat(4096));
+        let err = limits.check_batch(&[test_event(), event]).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::EventTooLarge);
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let mut event = test_event();
+        event.execution_meta.tool_calls = vec![serde_json::json!({}); 2];
+        let err = limits.check_event(&event).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::TooManyToolCalls);
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_events_keep_the_bytes_they_were_received_as() {
+        let event = serde_json::to_string_pretty(&test_event())
+            .unwrap()
+            .replace("\"input_data\": {", "\"input_data\": {\"amount\": 1.50, ");
+        let body = Bytes::from(format!("{{\"events\": [{},\n {}]}}", event, event));
+        let batch = <BatchIngestRequest as JsonBody>::from_json(&body).unwrap();
+        assert_eq!(batch.events.len(), 2);
+        for event_json in batch.events.iter().map(FactoEvent::to_json) {
+            assert_eq!(event_json, event.as_bytes());
+        }
+        assert_eq!(event_size(&batch.events[0]), event.len() as u64);
+
+        let single = <FactoEvent as JsonBody>::from_json(&Bytes::from(format!(" {}\n", event)));
+        assert_eq!(single.unwrap().to_json(), event.as_bytes());
+    }
+}
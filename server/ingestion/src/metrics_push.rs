@@ -0,0 +1,302 @@
+//! Push-based metrics export.
+//!
+//! Metrics are always served for scraping on `METRICS_PORT` and at
+//! `/metrics`. Deployments that cannot be scraped can have them pushed as
+//! well:
+//!
+//! - over OTLP, configured by the same `OTEL_*` variables as traces (see
+//!   [`crate::telemetry`]), with `OTEL_METRICS_EXPORTER` and
+//!   `OTEL_METRIC_EXPORT_INTERVAL` as the SDK defines them. [`OtelRecorder`]
+//!   records every metric into OpenTelemetry instruments alongside the
+//!   Prometheus recorder.
+//! - to a Prometheus push gateway at `METRICS_PUSH_URL`, PUT every
+//!   `METRICS_PUSH_INTERVAL_SECS` in the text exposition format as the
+//!   `facto-ingestion` job's instance.
+//!
+//! Both can be set from the config file's `[otel]` and `[metrics.push]`
+//! sections.
+
+use dashmap::DashMap;
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Unit,
+};
+use metrics_exporter_prometheus::PrometheusHandle;
+use opentelemetry::{
+    metrics::{Meter, MeterProvider},
+    KeyValue,
+};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tracing::warn;
+
+/// Job metrics are pushed to a push gateway under, and the name of the
+/// OpenTelemetry meter
+const PUSH_JOB: &str = "facto-ingestion";
+
+// ============================================================================
+// OpenTelemetry
+// ============================================================================
+
+/// Records `metrics` counters, gauges and histograms into the instruments of
+/// an OpenTelemetry meter, with labels as attributes
+pub struct OtelRecorder {
+    meter: Meter,
+    counters: DashMap<Key, Arc<OtelCounter>>,
+    gauges: DashMap<Key, Arc<OtelGauge>>,
+    histograms: DashMap<Key, Arc<OtelHistogram>>,
+}
+
+impl OtelRecorder {
+    pub fn new(provider: &impl MeterProvider) -> Self {
+        Self {
+            meter: provider.meter(PUSH_JOB),
+            counters: DashMap::new(),
+            gauges: DashMap::new(),
+            histograms: DashMap::new(),
+        }
+    }
+}
+
+fn attributes(key: &Key) -> Vec<KeyValue> {
+    key.labels()
+        .map(|label| KeyValue::new(label.key().to_string(), label.value().to_string()))
+        .collect()
+}
+
+struct OtelCounter {
+    counter: opentelemetry::metrics::Counter<u64>,
+    attributes: Vec<KeyValue>,
+}
+
+impl CounterFn for OtelCounter {
+    fn increment(&self, value: u64) {
+        self.counter.add(value, &self.attributes);
+    }
+
+    fn absolute(&self, _value: u64) {
+        // OpenTelemetry counters only add; the service never sets one
+    }
+}
+
+/// Gauges move by increments too, so the current value is kept here and
+/// recorded on every change
+struct OtelGauge {
+    gauge: opentelemetry::metrics::Gauge<f64>,
+    attributes: Vec<KeyValue>,
+    value: AtomicU64,
+}
+
+impl OtelGauge {
+    fn update(&self, f: impl Fn(f64) -> f64) {
+        let bits = self
+            .value
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |bits| {
+                Some(f(f64::from_bits(bits)).to_bits())
+            })
+            .unwrap_or_else(|bits| bits);
+        self.gauge.record(f(f64::from_bits(bits)), &self.attributes);
+    }
+}
+
+impl GaugeFn for OtelGauge {
+    fn increment(&self, value: f64) {
+        self.update(|current| current + value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.update(|current| current - value);
+    }
+
+    fn set(&self, value: f64) {
+        self.update(|_| value);
+    }
+}
+
+struct OtelHistogram {
+    histogram: opentelemetry::metrics::Histogram<f64>,
+    attributes: Vec<KeyValue>,
+}
+
+impl HistogramFn for OtelHistogram {
+    fn record(&self, value: f64) {
+        self.histogram.record(value, &self.attributes);
+    }
+}
+
+impl Recorder for OtelRecorder {
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        let counter = self.counters.entry(key.clone()).or_insert_with(|| {
+            Arc::new(OtelCounter {
+                counter: self.meter.u64_counter(key.name().to_string()).build(),
+                attributes: attributes(key),
+            })
+        });
+        Counter::from_arc(counter.clone())
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        let gauge = self.gauges.entry(key.clone()).or_insert_with(|| {
+            Arc::new(OtelGauge {
+                gauge: self.meter.f64_gauge(key.name().to_string()).build(),
+                attributes: attributes(key),
+                value: AtomicU64::new(0f64.to_bits()),
+            })
+        });
+        Gauge::from_arc(gauge.clone())
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        let histogram = self.histograms.entry(key.clone()).or_insert_with(|| {
+            Arc::new(OtelHistogram {
+                histogram: self.meter.f64_histogram(key.name().to_string()).build(),
+                attributes: attributes(key),
+            })
+        });
+        Histogram::from_arc(histogram.clone())
+    }
+}
+
+// ============================================================================
+// Push Gateway
+// ============================================================================
+
+/// Pushes rendered metrics to a Prometheus push gateway
+pub struct MetricsPush {
+    client: reqwest::Client,
+    url: String,
+    interval: Duration,
+}
+
+impl MetricsPush {
+    /// Read `METRICS_PUSH_URL` and `METRICS_PUSH_INTERVAL_SECS`; nothing is
+    /// pushed without a URL
+    pub fn from_env(instance_id: &str) -> anyhow::Result<Option<Self>> {
+        let Ok(endpoint) = std::env::var("METRICS_PUSH_URL") else {
+            return Ok(None);
+        };
+        let interval_secs: u64 = std::env::var("METRICS_PUSH_INTERVAL_SECS")
+            .unwrap_or_else(|_| "15".to_string())
+            .parse()
+            .expect("Invalid METRICS_PUSH_INTERVAL_SECS");
+
+        let url = format!(
+            "{}/metrics/job/{}/instance/{}",
+            endpoint.trim_end_matches('/'),
+            PUSH_JOB,
+            instance_id
+        );
+        Ok(Some(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()?,
+            url,
+            interval: Duration::from_secs(interval_secs.max(1)),
+        }))
+    }
+
+    pub fn describe(&self) -> String {
+        format!("push gateway {} every {:?}", self.url, self.interval)
+    }
+
+    /// Push every interval
+    pub async fn run(self: Arc<Self>, handle: PrometheusHandle) {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            self.push(&handle).await;
+        }
+    }
+
+    pub async fn push(&self, handle: &PrometheusHandle) {
+        let request = self
+            .client
+            .put(&self.url)
+            .header("content-type", "text/plain; version=0.0.4")
+            .body(handle.render());
+        match request.send().await.and_then(|r| r.error_for_status()) {
+            Ok(_) => metrics::counter!("facto_metrics_pushes_total").increment(1),
+            Err(e) => {
+                warn!("Failed to push metrics to {}: {}", self.url, e);
+                metrics::counter!("facto_metrics_push_failures_total").increment(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_sdk::metrics::{
+        data::{AggregatedMetrics, MetricData},
+        InMemoryMetricExporter, PeriodicReader, SdkMeterProvider,
+    };
+
+    #[test]
+    fn test_metrics_are_recorded_into_instruments() {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let recorder = OtelRecorder::new(&provider);
+
+        metrics::with_local_recorder(&recorder, || {
+            metrics::counter!("facto_events_accepted_total", "tenant" => "acme").increment(2);
+            metrics::counter!("facto_events_accepted_total", "tenant" => "acme").increment(3);
+            metrics::gauge!("facto_spool_depth").increment(5.0);
+            metrics::gauge!("facto_spool_depth").decrement(2.0);
+            metrics::histogram!("facto_publish_seconds").record(0.25);
+        });
+        provider.force_flush().unwrap();
+
+        let exported = exporter.get_finished_metrics().unwrap();
+        let metrics = exported
+            .iter()
+            .flat_map(|resource| resource.scope_metrics())
+            .flat_map(|scope| scope.metrics())
+            .map(|metric| (metric.name().to_string(), metric.data()))
+            .collect::<Vec<_>>();
+        let find = |name: &str| {
+            metrics
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, data)| *data)
+                .unwrap_or_else(|| panic!("{} not exported", name))
+        };
+
+        let AggregatedMetrics::U64(MetricData::Sum(sum)) = find("facto_events_accepted_total")
+        else {
+            panic!("counter is not a u64 sum");
+        };
+        let point = sum.data_points().next().unwrap();
+        assert_eq!(point.value(), 5);
+        assert_eq!(
+            point.attributes().collect::<Vec<_>>(),
+            vec![&KeyValue::new("tenant", "acme")]
+        );
+
+        let AggregatedMetrics::F64(MetricData::Gauge(gauge)) = find("facto_spool_depth") else {
+            panic!("gauge is not an f64 gauge");
+        };
+        assert_eq!(gauge.data_points().next().unwrap().value(), 3.0);
+
+        let AggregatedMetrics::F64(MetricData::Histogram(histogram)) =
+            find("facto_publish_seconds")
+        else {
+            panic!("histogram is not an f64 histogram");
+        };
+        assert_eq!(histogram.data_points().next().unwrap().count(), 1);
+    }
+}
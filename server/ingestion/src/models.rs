@@ -0,0 +1,307 @@
+//! Model attestation against per-tenant lists of approved models.
+//!
+//! Events record the model they ran on in `execution_meta.model_id` and
+//! `model_hash`. A tenant with a model list only trusts events claiming one
+//! of its approved models together with that model's hash. An event naming
+//! an unlisted model, or a listed one without its hash, either is refused
+//! with `MODEL_NOT_APPROVED` or, in audit mode, is accepted with the failure
+//! recorded in its server envelope.
+//!
+//! Events that name no model, and events of tenants without a list, are
+//! not checked.
+
+use crate::protocol::FactoEvent;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, path::PathBuf, sync::RwLock};
+use tracing::info;
+
+use crate::store::JsonFile;
+use crate::tenants::{validate_tenant_id, DEFAULT_TENANT};
+use crate::verification::now_nanos;
+
+// ============================================================================
+// Model Lists
+// ============================================================================
+
+/// What happens to events that fail model attestation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttestationMode {
+    /// Reject the event
+    #[default]
+    Enforce,
+    /// Accept the event and flag the failure in its server envelope
+    Audit,
+}
+
+impl AttestationMode {
+    /// Metrics label for the mode
+    pub fn code(&self) -> &'static str {
+        match self {
+            AttestationMode::Enforce => "enforce",
+            AttestationMode::Audit => "audit",
+        }
+    }
+}
+
+/// Approved models of one tenant
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelListRequest {
+    /// Hash of each approved model, by model_id
+    pub models: BTreeMap<String, String>,
+    #[serde(default)]
+    pub mode: AttestationMode,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelList {
+    /// The tenant, or `default` for events accepted without one
+    pub tenant_id: String,
+    #[serde(flatten)]
+    pub list: ModelListRequest,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ModelError {
+    #[error("invalid model list: {0}")]
+    InvalidList(String),
+    #[error("no model list for tenant: {0}")]
+    Unknown(String),
+    #[error("failed to persist model lists: {0}")]
+    Persistence(String),
+}
+
+// ============================================================================
+// Attestation
+// ============================================================================
+
+/// Why an event's model claim was not trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttestationFailure {
+    /// The model is not on the tenant's list
+    UnapprovedModel,
+    /// The model is listed but the event carries no hash
+    MissingHash,
+    /// The model is listed under a different hash
+    HashMismatch,
+}
+
+impl AttestationFailure {
+    /// Metrics label for the failure
+    pub fn code(&self) -> &'static str {
+        match self {
+            AttestationFailure::UnapprovedModel => "unapproved_model",
+            AttestationFailure::MissingHash => "missing_hash",
+            AttestationFailure::HashMismatch => "hash_mismatch",
+        }
+    }
+}
+
+/// A failed model attestation, recorded in the server envelope of an event
+/// accepted in audit mode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelAttestation {
+    pub model_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_hash: Option<String>,
+    pub failure: AttestationFailure,
+}
+
+/// An event failed its tenant's model attestation
+#[derive(Debug, Clone)]
+pub struct AttestationViolation {
+    pub mode: AttestationMode,
+    pub attestation: ModelAttestation,
+}
+
+impl fmt::Display for AttestationViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let model_id = &self.attestation.model_id;
+        match self.attestation.failure {
+            AttestationFailure::UnapprovedModel => write!(f, "Model {} is not approved", model_id),
+            AttestationFailure::MissingHash => {
+                write!(
+                    f,
+                    "Model {} is approved but no model_hash was given",
+                    model_id
+                )
+            }
+            AttestationFailure::HashMismatch => write!(
+                f,
+                "Model {} is approved under a different model_hash",
+                model_id
+            ),
+        }
+    }
+}
+
+// ============================================================================
+// Model Registry
+// ============================================================================
+
+/// Approved models by tenant
+pub struct ModelRegistry {
+    lists: RwLock<BTreeMap<String, ModelList>>,
+    store: JsonFile,
+}
+
+impl ModelRegistry {
+    pub fn new(path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let store = JsonFile::new(path);
+        let lists: BTreeMap<String, ModelList> = store.load()?;
+        info!("Loaded {} model lists", lists.len());
+        for list in lists.values() {
+            validate(&list.list)?;
+        }
+        Ok(Self {
+            lists: RwLock::new(lists),
+            store,
+        })
+    }
+
+    pub fn list(&self) -> Vec<ModelList> {
+        self.lists.read().unwrap().values().cloned().collect()
+    }
+
+    pub fn get(&self, tenant_id: &str) -> Option<ModelList> {
+        self.lists.read().unwrap().get(tenant_id).cloned()
+    }
+
+    /// Create or replace a tenant's approved models; applies immediately
+    pub fn upsert(
+        &self,
+        tenant_id: &str,
+        request: ModelListRequest,
+    ) -> Result<ModelList, ModelError> {
+        if tenant_id != DEFAULT_TENANT {
+            validate_tenant_id(tenant_id).map_err(|e| ModelError::InvalidList(e.to_string()))?;
+        }
+        validate(&request)?;
+        let list = ModelList {
+            tenant_id: tenant_id.to_string(),
+            list: request,
+            updated_at: now_nanos(),
+        };
+
+        let mut lists = self.lists.write().unwrap();
+        let mut updated = lists.clone();
+        updated.insert(tenant_id.to_string(), list.clone());
+        self.persist(&updated)?;
+        *lists = updated;
+        Ok(list)
+    }
+
+    pub fn remove(&self, tenant_id: &str) -> Result<ModelList, ModelError> {
+        let mut lists = self.lists.write().unwrap();
+        let mut updated = lists.clone();
+        let removed = updated
+            .remove(tenant_id)
+            .ok_or_else(|| ModelError::Unknown(tenant_id.to_string()))?;
+        self.persist(&updated)?;
+        *lists = updated;
+        Ok(removed)
+    }
+
+    /// Check the model an event claims against its tenant's approved models.
+    /// Hashes are compared ignoring case.
+    pub fn check(&self, tenant: &str, event: &FactoEvent) -> Result<(), AttestationViolation> {
+        let Some(model_id) = event.execution_meta.model_id.as_deref() else {
+            return Ok(());
+        };
+        let lists = self.lists.read().unwrap();
+        let Some(list) = lists.get(tenant) else {
+            return Ok(());
+        };
+        let model_hash = event.execution_meta.model_hash.as_deref();
+        let failure = match (list.list.models.get(model_id), model_hash) {
+            (None, _) => AttestationFailure::UnapprovedModel,
+            (Some(_), None) => AttestationFailure::MissingHash,
+            (Some(approved), Some(hash)) if !approved.eq_ignore_ascii_case(hash) => {
+                AttestationFailure::HashMismatch
+            }
+            (Some(_), Some(_)) => return Ok(()),
+        };
+        Err(AttestationViolation {
+            mode: list.list.mode,
+            attestation: ModelAttestation {
+                model_id: model_id.to_string(),
+                model_hash: model_hash.map(str::to_string),
+                failure,
+            },
+        })
+    }
+
+    fn persist(&self, lists: &BTreeMap<String, ModelList>) -> Result<(), ModelError> {
+        self.store
+            .save(lists)
+            .map_err(|e| ModelError::Persistence(e.to_string()))
+    }
+}
+
+fn validate(request: &ModelListRequest) -> Result<(), ModelError> {
+    for (model_id, hash) in &request.models {
+        if model_id.is_empty() {
+            return Err(ModelError::InvalidList(
+                "model_id must not be empty".to_string(),
+            ));
+        }
+        if hash.trim().is_empty() {
+            return Err(ModelError::InvalidList(format!(
+                "hash of model {} must not be empty",
+                model_id
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_event;
+
+    #[test]
+    fn test_events_are_checked_against_the_tenants_models() {
+        let registry = ModelRegistry::new(None).unwrap();
+        registry
+            .upsert(
+                "acme",
+                ModelListRequest {
+                    models: BTreeMap::from([("gpt-4".to_string(), "ABC123".to_string())]),
+                    mode: AttestationMode::Audit,
+                },
+            )
+            .unwrap();
+
+        let mut event = test_event();
+        event.execution_meta.model_id = Some("gpt-4".to_string());
+        event.execution_meta.model_hash = Some("abc123".to_string());
+        assert!(registry.check("acme", &event).is_ok());
+        // Tenants without a list are not checked
+        event.execution_meta.model_hash = None;
+        assert!(registry.check("other", &event).is_ok());
+
+        let failure = |event: &FactoEvent| {
+            let violation = registry.check("acme", event).unwrap_err();
+            assert_eq!(violation.mode, AttestationMode::Audit);
+            violation.attestation.failure
+        };
+        assert_eq!(failure(&event), AttestationFailure::MissingHash);
+        event.execution_meta.model_hash = Some("def456".to_string());
+        assert_eq!(failure(&event), AttestationFailure::HashMismatch);
+        event.execution_meta.model_id = Some("gpt-5".to_string());
+        assert_eq!(failure(&event), AttestationFailure::UnapprovedModel);
+
+        assert!(registry
+            .upsert(
+                "acme",
+                ModelListRequest {
+                    models: BTreeMap::from([("gpt-4".to_string(), " ".to_string())]),
+                    mode: AttestationMode::Enforce,
+                },
+            )
+            .is_err());
+    }
+}
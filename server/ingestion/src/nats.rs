@@ -0,0 +1,184 @@
+use async_nats::ConnectOptions;
+use std::path::PathBuf;
+
+/// How the service authenticates to NATS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NatsAuth {
+    /// Anonymous, or credentials embedded in the URL
+    None,
+    /// A `.creds` file holding a user JWT and its NKey seed, read again on
+    /// every connect so rotated credentials are picked up
+    CredentialsFile(PathBuf),
+    NKey(String),
+    UserPassword {
+        user: String,
+        password: String,
+    },
+    Token(String),
+}
+
+impl NatsAuth {
+    pub fn method(&self) -> &'static str {
+        match self {
+            NatsAuth::None => "none",
+            NatsAuth::CredentialsFile(_) => "credentials file",
+            NatsAuth::NKey(_) => "nkey",
+            NatsAuth::UserPassword { .. } => "user and password",
+            NatsAuth::Token(_) => "token",
+        }
+    }
+}
+
+/// TLS for the NATS connection
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NatsTls {
+    /// Refuse to connect without TLS
+    pub required: bool,
+    /// CA that issued the server certificates, when not a public one
+    pub ca_path: Option<PathBuf>,
+    /// Certificate and key for servers that verify clients
+    pub client_cert: Option<(PathBuf, PathBuf)>,
+}
+
+/// Where NATS is and how to connect to it
+#[derive(Debug, Clone)]
+pub struct NatsConfig {
+    pub url: String,
+    pub auth: NatsAuth,
+    pub tls: NatsTls,
+}
+
+fn path_var(name: &str) -> Option<PathBuf> {
+    std::env::var(name).ok().map(PathBuf::from)
+}
+
+impl NatsConfig {
+    /// Read `NATS_URL` and one of `NATS_CREDS_PATH`, `NATS_NKEY_SEED` (or
+    /// `NATS_NKEY_SEED_PATH`), `NATS_USER` with `NATS_PASSWORD`, or
+    /// `NATS_TOKEN`. TLS is required with `NATS_TLS_REQUIRED=true` or a
+    /// `tls://` URL, verified against `NATS_TLS_CA_PATH` if set, and
+    /// `NATS_TLS_CERT_PATH` with `NATS_TLS_KEY_PATH` present a client
+    /// certificate.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let url = std::env::var("NATS_URL").unwrap_or_else(|_| "nats://localhost:4222".to_string());
+
+        let nkey_seed = match (
+            std::env::var("NATS_NKEY_SEED").ok(),
+            path_var("NATS_NKEY_SEED_PATH"),
+        ) {
+            (Some(_), Some(_)) => {
+                anyhow::bail!("Set only one of NATS_NKEY_SEED and NATS_NKEY_SEED_PATH")
+            }
+            (Some(seed), None) => Some(seed),
+            (None, Some(path)) => Some(std::fs::read_to_string(&path)?.trim().to_string()),
+            (None, None) => None,
+        };
+        let user = match (std::env::var("NATS_USER"), std::env::var("NATS_PASSWORD")) {
+            (Ok(user), Ok(password)) => Some((user, password)),
+            (Err(_), Err(_)) => None,
+            _ => anyhow::bail!("NATS_USER and NATS_PASSWORD must be set together"),
+        };
+        let mut methods = [
+            path_var("NATS_CREDS_PATH").map(NatsAuth::CredentialsFile),
+            nkey_seed.map(NatsAuth::NKey),
+            user.map(|(user, password)| NatsAuth::UserPassword { user, password }),
+            std::env::var("NATS_TOKEN").ok().map(NatsAuth::Token),
+        ]
+        .into_iter()
+        .flatten();
+        let auth = methods.next().unwrap_or(NatsAuth::None);
+        if let Some(other) = methods.next() {
+            anyhow::bail!(
+                "NATS {} and {} authentication are both configured",
+                auth.method(),
+                other.method()
+            );
+        }
+        if let NatsAuth::NKey(ref seed) = auth {
+            nkeys::KeyPair::from_seed(seed)
+                .map_err(|e| anyhow::anyhow!("Invalid NATS NKey seed: {}", e))?;
+        }
+
+        let required = url.starts_with("tls://")
+            || std::env::var("NATS_TLS_REQUIRED")
+                .map(|v| v == "true")
+                .unwrap_or(false);
+        let client_cert = match (
+            path_var("NATS_TLS_CERT_PATH"),
+            path_var("NATS_TLS_KEY_PATH"),
+        ) {
+            (Some(cert), Some(key)) => Some((cert, key)),
+            (None, None) => None,
+            _ => anyhow::bail!("NATS_TLS_CERT_PATH and NATS_TLS_KEY_PATH must be set together"),
+        };
+        Ok(Self {
+            url,
+            auth,
+            tls: NatsTls {
+                required,
+                ca_path: path_var("NATS_TLS_CA_PATH"),
+                client_cert,
+            },
+        })
+    }
+
+    /// Connect options for the configured credentials and TLS
+    pub async fn options(&self) -> std::io::Result<ConnectOptions> {
+        let options = match &self.auth {
+            NatsAuth::None => ConnectOptions::new(),
+            NatsAuth::CredentialsFile(path) => ConnectOptions::with_credentials_file(path).await?,
+            NatsAuth::NKey(seed) => ConnectOptions::with_nkey(seed.clone()),
+            NatsAuth::UserPassword { user, password } => {
+                ConnectOptions::with_user_and_password(user.clone(), password.clone())
+            }
+            NatsAuth::Token(token) => ConnectOptions::with_token(token.clone()),
+        };
+
+        let mut options = options
+            .name(format!("facto-ingestion {}", env!("CARGO_PKG_VERSION")))
+            .require_tls(self.tls.required);
+        if let Some(ref ca_path) = self.tls.ca_path {
+            options = options.add_root_certificates(ca_path.clone());
+        }
+        if let Some((ref cert, ref key)) = self.tls.client_cert {
+            options = options.add_client_certificate(cert.clone(), key.clone());
+        }
+        Ok(options)
+    }
+
+    /// The URL and security settings, without secrets, for logs
+    pub fn describe(&self) -> String {
+        let mut description = format!("{} ({} auth", self.url, self.auth.method());
+        if self.tls.required {
+            description.push_str(", TLS");
+        }
+        if self.tls.client_cert.is_some() {
+            description.push_str(", client certificate");
+        }
+        description.push(')');
+        description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_hides_secrets() {
+        let config = NatsConfig {
+            url: "tls://nats:4222".to_string(),
+            auth: NatsAuth::UserPassword {
+                user: "facto".to_string(),
+                password: "hunter2".to_string(),
+            },
+            tls: NatsTls {
+                required: true,
+                ..Default::default()
+            },
+        };
+        let description = config.describe();
+        assert_eq!(description, "tls://nats:4222 (user and password auth, TLS)");
+        assert!(!description.contains("hunter2"));
+    }
+}
@@ -0,0 +1,115 @@
+//! Choosing the encoding of ingest responses.
+//!
+//! Clients that send CBOR or MessagePack get answers in kind unless their
+//! `Accept` header asks for something else.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use std::convert::Infallible;
+
+use crate::encoding::Encoding;
+
+/// The encoding to answer a request in
+#[derive(Debug, Clone, Copy)]
+pub struct ResponseEncoding(pub Encoding);
+
+/// The supported type with the highest quality in `Accept`, earliest on
+/// ties; otherwise the request's own encoding
+pub fn negotiate(headers: &HeaderMap) -> Encoding {
+    let header_str = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+    let mut best: Option<(Encoding, f32)> = None;
+    for media_range in header_str(header::ACCEPT).unwrap_or_default().split(',') {
+        let Some(encoding) = Encoding::from_media_type(media_range) else {
+            continue;
+        };
+        let quality = media_range
+            .split(';')
+            .skip(1)
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if quality > 0.0 && best.is_none_or(|(_, q)| quality > q) {
+            best = Some((encoding, quality));
+        }
+    }
+
+    best.map(|(encoding, _)| encoding)
+        .or_else(|| header_str(header::CONTENT_TYPE).and_then(Encoding::from_media_type))
+        .unwrap_or(Encoding::Json)
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ResponseEncoding {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Infallible> {
+        Ok(Self(negotiate(&parts.headers)))
+    }
+}
+
+impl ResponseEncoding {
+    /// `body` in the negotiated encoding, with its content type
+    pub fn reply<T: Serialize>(self, status: StatusCode, body: &T) -> Response {
+        match (self.0, serde_json::to_value(body)) {
+            (Encoding::Json, _) | (_, Err(_)) => (status, Json(body)).into_response(),
+            (encoding, Ok(value)) => (
+                status,
+                [(header::CONTENT_TYPE, encoding.content_type())],
+                encoding.encode(&value),
+            )
+                .into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_accept_wins_over_request_encoding() {
+        let headers = |content_type: &'static str, accept: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+            if let Some(accept) = accept {
+                headers.insert(header::ACCEPT, HeaderValue::from_static(accept));
+            }
+            headers
+        };
+
+        assert_eq!(
+            negotiate(&headers("application/cbor", None)),
+            Encoding::Cbor
+        );
+        assert_eq!(
+            negotiate(&headers("application/cbor", Some("*/*"))),
+            Encoding::Cbor
+        );
+        assert_eq!(
+            negotiate(&headers("application/json", Some("application/x-msgpack"))),
+            Encoding::MsgPack
+        );
+        assert_eq!(
+            negotiate(&headers(
+                "application/cbor",
+                Some("application/cbor;q=0.5, application/json")
+            )),
+            Encoding::Json
+        );
+        assert_eq!(
+            negotiate(&headers(
+                "application/msgpack",
+                Some("application/cbor;q=0")
+            )),
+            Encoding::MsgPack
+        );
+        assert_eq!(negotiate(&headers("text/plain", None)), Encoding::Json);
+    }
+}
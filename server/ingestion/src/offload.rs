@@ -0,0 +1,446 @@
+use axum::async_trait;
+use hmac::{Hmac, Mac};
+use metrics::counter;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use sha3::{Digest, Sha3_256};
+use std::{path::PathBuf, time::Duration};
+use tracing::warn;
+
+use crate::{payload_size, FactoEvent};
+
+/// Key of the object that replaces an offloaded payload
+pub const BLOB_KEY: &str = "$facto_blob";
+
+/// Default for `BLOB_OFFLOAD_THRESHOLD_BYTES`
+pub const DEFAULT_OFFLOAD_THRESHOLD_BYTES: u64 = 256 * 1024;
+
+/// Upper bound on the JSON encoding of a reference, used to size events
+/// before their payloads are offloaded
+const MAX_REFERENCE_BYTES: u64 = 512;
+
+/// Where an offloaded payload is stored. `hash` is the SHA3-256 of the
+/// payload's JSON encoding, which is what the blob holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobRef {
+    pub hash: String,
+    pub size: u64,
+    pub uri: String,
+}
+
+impl BlobRef {
+    /// The `{"$facto_blob": {...}}` object published in place of the payload
+    pub fn to_value(&self) -> Value {
+        serde_json::json!({ BLOB_KEY: self })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OffloadError {
+    #[error("Blob store request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Blob store returned {0}")]
+    Status(reqwest::StatusCode),
+    #[error("Blob store I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+// ============================================================================
+// Blob Stores
+// ============================================================================
+
+/// Content-addressed storage for payloads. Writing a blob that is already
+/// stored must succeed without changing it.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// The URI a blob is read back from
+    fn uri(&self, hash: &str) -> String;
+
+    async fn put(&self, hash: &str, bytes: Vec<u8>) -> Result<(), OffloadError>;
+}
+
+/// Blobs as files in a local directory, for development and shared volumes
+pub struct FileBlobStore {
+    dir: PathBuf,
+}
+
+impl FileBlobStore {
+    pub fn new(dir: PathBuf) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir: dir.canonicalize()?,
+        })
+    }
+}
+
+#[async_trait]
+impl BlobStore for FileBlobStore {
+    fn uri(&self, hash: &str) -> String {
+        format!("file://{}", self.dir.join(hash).display())
+    }
+
+    async fn put(&self, hash: &str, bytes: Vec<u8>) -> Result<(), OffloadError> {
+        let path = self.dir.join(hash);
+        if tokio::fs::try_exists(&path).await? {
+            return Ok(());
+        }
+        // Write beside the blob and rename, so readers never see a partial one
+        let partial = self.dir.join(format!(".{}.{}", hash, uuid::Uuid::new_v4()));
+        tokio::fs::write(&partial, bytes).await?;
+        tokio::fs::rename(&partial, &path).await?;
+        Ok(())
+    }
+}
+
+/// Access keys for S3-compatible stores
+#[derive(Debug, Clone)]
+pub struct S3Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+/// Blobs in an S3 bucket, or any store with an S3-compatible API such as
+/// GCS interoperability mode or MinIO. Requests are path-style and signed
+/// with AWS Signature Version 4.
+pub struct S3BlobStore {
+    client: reqwest::Client,
+    scheme: String,
+    endpoint: Url,
+    bucket: String,
+    prefix: String,
+    region: String,
+    credentials: S3Credentials,
+}
+
+impl S3BlobStore {
+    pub fn new(
+        scheme: &str,
+        endpoint: Url,
+        bucket: String,
+        prefix: String,
+        region: String,
+        credentials: S3Credentials,
+    ) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .expect("Failed to build HTTP client");
+        Self {
+            client,
+            scheme: scheme.to_string(),
+            endpoint,
+            bucket,
+            prefix: prefix.trim_matches('/').to_string(),
+            region,
+            credentials,
+        }
+    }
+
+    fn key(&self, hash: &str) -> String {
+        match self.prefix.is_empty() {
+            true => hash.to_string(),
+            false => format!("{}/{}", self.prefix, hash),
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3BlobStore {
+    fn uri(&self, hash: &str) -> String {
+        format!("{}://{}/{}", self.scheme, self.bucket, self.key(hash))
+    }
+
+    async fn put(&self, hash: &str, bytes: Vec<u8>) -> Result<(), OffloadError> {
+        let key: Vec<String> = self.key(hash).split('/').map(uri_encode).collect();
+        let path = format!("/{}/{}", uri_encode(&self.bucket), key.join("/"));
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let payload_hash = hex::encode(Sha256::digest(&bytes));
+        let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(ref token) = self.credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let authorization = sign_v4(
+            &self.credentials,
+            &self.region,
+            "PUT",
+            &path,
+            &headers,
+            &payload_hash,
+            &amz_date,
+        );
+
+        let mut request = self
+            .client
+            .put(url)
+            .header("Authorization", authorization)
+            .header("Content-Type", "application/json");
+        for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+        let response = request.body(bytes).send().await?;
+        if !response.status().is_success() {
+            return Err(OffloadError::Status(response.status()));
+        }
+        Ok(())
+    }
+}
+
+/// Percent-encode a path segment as Signature Version 4 requires
+fn uri_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// The Signature Version 4 key for one day, region and service
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac_sha256(format!("AWS4{}", secret).as_bytes(), date);
+    let key = hmac_sha256(&key, region);
+    let key = hmac_sha256(&key, service);
+    hmac_sha256(&key, "aws4_request")
+}
+
+/// The Authorization header for an S3 request without a query string.
+/// `headers` are signed and must have lowercase names in sorted order.
+fn sign_v4(
+    credentials: &S3Credentials,
+    region: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, String)],
+    payload_hash: &str,
+    amz_date: &str,
+) -> String {
+    let date = &amz_date[..8];
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        method, path, canonical_headers, signed_headers, payload_hash
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = signing_key(&credentials.secret_access_key, date, region, "s3");
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id,
+        scope,
+        signed_headers,
+        hex::encode(hmac_sha256(&key, &string_to_sign))
+    )
+}
+
+// ============================================================================
+// Offloading
+// ============================================================================
+
+/// Moves `input_data` and `output_data` over a size threshold to a blob
+/// store, leaving a reference in the published event.
+///
+/// The event hash still covers the payloads themselves, so readers must
+/// fetch each blob, check it against the reference's hash and size, and
+/// put the payload back before verifying the event.
+pub struct Offloader {
+    store: Box<dyn BlobStore>,
+    threshold: u64,
+}
+
+impl Offloader {
+    pub fn new(store: Box<dyn BlobStore>, threshold: u64) -> Self {
+        Self { store, threshold }
+    }
+
+    /// Read `BLOB_STORE_URL` (`s3://bucket/prefix`, `gs://bucket/prefix` or
+    /// `file:///dir`) and `BLOB_OFFLOAD_THRESHOLD_BYTES`; None if no store is
+    /// configured. S3 and GCS are reached at `BLOB_S3_ENDPOINT` in
+    /// `BLOB_S3_REGION`, with the standard `AWS_*` access key variables.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(store_url) = std::env::var("BLOB_STORE_URL") else {
+            return Ok(None);
+        };
+        let threshold = std::env::var("BLOB_OFFLOAD_THRESHOLD_BYTES")
+            .map_or(DEFAULT_OFFLOAD_THRESHOLD_BYTES, |v| {
+                v.parse().expect("Invalid BLOB_OFFLOAD_THRESHOLD_BYTES")
+            });
+
+        let url = Url::parse(&store_url)?;
+        let store: Box<dyn BlobStore> = match url.scheme() {
+            "file" => Box::new(FileBlobStore::new(PathBuf::from(url.path()))?),
+            scheme @ ("s3" | "gs") => {
+                let bucket = url
+                    .host_str()
+                    .ok_or_else(|| anyhow::anyhow!("BLOB_STORE_URL has no bucket"))?
+                    .to_string();
+                let region = std::env::var("BLOB_S3_REGION")
+                    .or_else(|_| std::env::var("AWS_REGION"))
+                    .unwrap_or_else(|_| match scheme {
+                        "gs" => "auto".to_string(),
+                        _ => "us-east-1".to_string(),
+                    });
+                let endpoint = std::env::var("BLOB_S3_ENDPOINT").unwrap_or_else(|_| match scheme {
+                    "gs" => "https://storage.googleapis.com".to_string(),
+                    _ => format!("https://s3.{}.amazonaws.com", region),
+                });
+                let credentials = S3Credentials {
+                    access_key_id: std::env::var("AWS_ACCESS_KEY_ID")?,
+                    secret_access_key: std::env::var("AWS_SECRET_ACCESS_KEY")?,
+                    session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+                };
+                Box::new(S3BlobStore::new(
+                    scheme,
+                    Url::parse(&endpoint)?,
+                    bucket,
+                    url.path().to_string(),
+                    region,
+                    credentials,
+                ))
+            }
+            other => anyhow::bail!("unsupported BLOB_STORE_URL scheme: {}", other),
+        };
+        Ok(Some(Self::new(store, threshold)))
+    }
+
+    pub fn threshold(&self) -> u64 {
+        self.threshold
+    }
+
+    /// Store the payloads over the threshold and replace them with
+    /// references. The event is left unchanged if any blob fails to store.
+    pub async fn offload(&self, event: &mut FactoEvent) -> Result<(), OffloadError> {
+        let mut references = Vec::new();
+        for (field, payload) in [("input", &event.input_data), ("output", &event.output_data)] {
+            if payload_size(payload) <= self.threshold {
+                continue;
+            }
+            let bytes = serde_json::to_vec(payload).expect("JSON values always serialize");
+            let hash = hex::encode(Sha3_256::digest(&bytes));
+            let reference = BlobRef {
+                size: bytes.len() as u64,
+                uri: self.store.uri(&hash),
+                hash,
+            };
+            if let Err(e) = self.store.put(&reference.hash, bytes).await {
+                warn!("Failed to offload {} of {}: {}", field, event.facto_id, e);
+                counter!("facto_blob_offloads_total", "outcome" => "failed").increment(1);
+                return Err(e);
+            }
+            counter!("facto_blob_offloads_total", "outcome" => "ok").increment(1);
+            counter!("facto_blob_offloaded_bytes_total").increment(reference.size);
+            references.push((field, reference));
+        }
+
+        if !references.is_empty() {
+            event.raw = None;
+        }
+        for (field, reference) in references {
+            let payload = match field {
+                "input" => &mut event.input_data,
+                _ => &mut event.output_data,
+            };
+            *payload = reference.to_value();
+        }
+        Ok(())
+    }
+}
+
+/// Size of an event's JSON encoding once payloads over `threshold` are
+/// replaced with references
+pub fn published_size(event: &FactoEvent, threshold: u64) -> u64 {
+    [&event.input_data, &event.output_data]
+        .into_iter()
+        .map(payload_size)
+        .filter(|bytes| *bytes > threshold)
+        .fold(payload_size(event), |size, bytes| {
+            size - bytes + MAX_REFERENCE_BYTES
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_event;
+
+    #[tokio::test]
+    async fn test_offload_replaces_large_payloads() {
+        let dir = std::env::temp_dir().join(format!("facto-blobs-{}", uuid::Uuid::new_v4()));
+        let offloader = Offloader::new(Box::new(FileBlobStore::new(dir.clone()).unwrap()), 64);
+
+        let mut event = test_event();
+        let output = serde_json::json!({"text": "x".repeat(1000), "tokens": 250});
+        event.output_data = output.clone();
+        let input = event.input_data.clone();
+        assert!(published_size(&event, 64) < payload_size(&event));
+
+        offloader.offload(&mut event).await.unwrap();
+        assert_eq!(event.input_data, input);
+
+        let reference: BlobRef =
+            serde_json::from_value(event.output_data[BLOB_KEY].clone()).unwrap();
+        let stored = std::fs::read(dir.join(&reference.hash)).unwrap();
+        assert_eq!(reference.size, stored.len() as u64);
+        assert_eq!(reference.hash, hex::encode(Sha3_256::digest(&stored)));
+        assert!(reference.uri.starts_with("file://"));
+        assert_eq!(serde_json::from_slice::<Value>(&stored).unwrap(), output);
+
+        // Storing the same payload again is a no-op
+        let mut again = test_event();
+        again.output_data = output;
+        offloader.offload(&mut again).await.unwrap();
+        assert_eq!(again.output_data, event.output_data);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_signing_key_matches_aws_example() {
+        // From the AWS Signature Version 4 documentation
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex::encode(key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+}
@@ -0,0 +1,1444 @@
+//! OpenAPI description of the public ingestion API, served as
+//! `/openapi.json` with Swagger UI at `/docs` so SDK authors can generate
+//! clients instead of reading the Rust structs.
+//!
+//! The document is written out by hand next to the wire types in
+//! `protocol`; the tests check its schemas against their serialized form,
+//! so a field added to a wire type fails them until it is described here
+//! too. Operational, debug, cursor and admin endpoints are described in
+//! brief from [`ENDPOINTS`], with their bodies as plain JSON objects; the
+//! server's tests check every route it serves is described.
+
+use serde_json::{json, Value};
+
+use crate::encoding::CONTENT_TYPES;
+use crate::protocol::ErrorCode;
+
+/// Swagger UI, loaded from a CDN and pointed at `/openapi.json`
+pub const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>Facto Ingestion API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" crossorigin></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
+
+fn schema_ref(name: &str) -> Value {
+    json!({"$ref": format!("#/components/schemas/{}", name)})
+}
+
+fn json_response(description: &str, schema: &str) -> Value {
+    json!({
+        "description": description,
+        "content": {"application/json": {"schema": schema_ref(schema)}},
+    })
+}
+
+const COMPRESSED_BODY: &str = "May be sent with `Content-Encoding` gzip, deflate or zstd; \
+    the body limit applies both before and after decompression.";
+
+/// Content in each body encoding the ingest endpoints accept and answer in
+fn encoded_content(schema: &str) -> Value {
+    CONTENT_TYPES
+        .iter()
+        .map(|content_type| {
+            (
+                content_type.to_string(),
+                json!({"schema": schema_ref(schema)}),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+fn ingest_response(description: &str, schema: &str) -> Value {
+    json!({"description": description, "content": encoded_content(schema)})
+}
+
+fn error_response(description: &str) -> Value {
+    json_response(description, "ErrorResponse")
+}
+
+/// The `backfill` query parameter of the ingest endpoints
+fn backfill_parameter() -> Value {
+    json!({
+        "name": "backfill",
+        "in": "query",
+        "required": false,
+        "schema": {"type": "boolean", "default": false},
+        "description": "Accept events out of chain order: each is held until its \
+            predecessor in the session is published, or the backfill timeout expires",
+    })
+}
+
+fn nullable(kind: &str) -> Value {
+    json!({"type": [kind, "null"]})
+}
+
+/// Schemas of the wire types, keyed by name
+fn schemas() -> Value {
+    let mut schemas = json!({
+        "FactoEvent": {
+            "type": "object",
+            "description": "A signed agent event. `proof.event_hash` is the SHA3-256 of the \
+                event's canonical form and `proof.signature` an Ed25519 signature over it.",
+            "required": [
+                "facto_id", "agent_id", "session_id", "action_type", "status",
+                "input_data", "output_data", "execution_meta", "proof",
+                "started_at", "completed_at"
+            ],
+            "properties": {
+                "event_version": {
+                    "type": "integer",
+                    "description": "Wire version; version 1 when absent",
+                },
+                "facto_id": {"type": "string"},
+                "agent_id": {"type": "string"},
+                "session_id": {"type": "string"},
+                "parent_facto_id": nullable("string"),
+                "action_type": {"type": "string", "examples": ["llm_call", "tool_call"]},
+                "status": {"type": "string", "examples": ["success", "error"]},
+                "input_data": {"description": "Any JSON value"},
+                "output_data": {"description": "Any JSON value"},
+                "execution_meta": schema_ref("ExecutionMeta"),
+                "proof": schema_ref("Proof"),
+                "started_at": {
+                    "type": "integer",
+                    "format": "int64",
+                    "description": "Unix nanoseconds",
+                },
+                "completed_at": {
+                    "type": "integer",
+                    "format": "int64",
+                    "description": "Unix nanoseconds",
+                },
+            },
+        },
+        "ExecutionMeta": {
+            "type": "object",
+            "required": ["tool_calls", "sdk_version", "sdk_language", "tags"],
+            "properties": {
+                "model_id": nullable("string"),
+                "model_hash": nullable("string"),
+                "temperature": nullable("number"),
+                "seed": nullable("integer"),
+                "max_tokens": nullable("integer"),
+                "tool_calls": {"type": "array", "items": {}},
+                "sdk_version": {"type": "string"},
+                "sdk_language": {"type": "string"},
+                "tags": {"type": "object", "additionalProperties": {"type": "string"}},
+            },
+        },
+        "Proof": {
+            "type": "object",
+            "required": ["signature", "public_key", "prev_hash", "event_hash"],
+            "properties": {
+                "signature": {"type": "string", "format": "byte"},
+                "public_key": {"type": "string", "format": "byte"},
+                "prev_hash": {"type": "string"},
+                "event_hash": {"type": "string"},
+                "algorithm": {
+                    "type": "string",
+                    "description": "Signature algorithm; Ed25519 when absent",
+                },
+                "canonical_version": {
+                    "type": "integer",
+                    "description": "Canonical form the hash and signature cover; 1 when absent",
+                },
+                "verification_method": {
+                    "type": "string",
+                    "description": "DID URL resolving to public_key, e.g. did:key:z6Mk...",
+                },
+            },
+        },
+        "BatchIngestRequest": {
+            "type": "object",
+            "required": ["events"],
+            "properties": {
+                "events": {"type": "array", "items": schema_ref("FactoEvent")},
+                "batch_id": nullable("string"),
+                "ordered": {
+                    "type": "boolean",
+                    "default": false,
+                    "description": "Publish each session's events in array order",
+                },
+                "envelope": schema_ref("BatchEnvelope"),
+            },
+        },
+        "BatchEnvelope": {
+            "type": "object",
+            "description": "Signs the batch once for envelope-trusted agents",
+            "required": ["batch_hash", "public_key", "signature"],
+            "properties": {
+                "batch_hash": {
+                    "type": "string",
+                    "description": "SHA3-256 of the event hashes concatenated in array order",
+                },
+                "public_key": {"type": "string", "format": "byte"},
+                "signature": {
+                    "type": "string",
+                    "format": "byte",
+                    "description": "Signature over the UTF-8 bytes of `batch_hash`",
+                },
+                "algorithm": {
+                    "type": "string",
+                    "description": "Signature algorithm; Ed25519 when absent",
+                },
+            },
+        },
+        "SingleIngestResponse": {
+            "type": "object",
+            "required": ["accepted", "facto_id"],
+            "properties": {
+                "accepted": {"type": "boolean"},
+                "facto_id": {"type": "string"},
+                "duplicate": {"type": "boolean", "default": false},
+                "spooled": {"type": "boolean", "default": false},
+                "held": {
+                    "type": "boolean",
+                    "default": false,
+                    "description": "Held until the session's chain is complete",
+                },
+                "reason": {
+                    "type": "string",
+                    "deprecated": true,
+                    "description": "Same as `error.message`",
+                },
+                "error": schema_ref("EventError"),
+                "receipt": schema_ref("Receipt"),
+            },
+        },
+        "BatchIngestResponse": {
+            "type": "object",
+            "required": ["accepted_count", "rejected_count", "rejected"],
+            "properties": {
+                "accepted_count": {"type": "integer"},
+                "rejected_count": {"type": "integer"},
+                "rejected": {"type": "array", "items": schema_ref("RejectedEvent")},
+                "duplicates": {"type": "array", "items": {"type": "string"}},
+                "spooled_count": {"type": "integer", "default": 0},
+                "held_count": {"type": "integer", "default": 0},
+                "ordering": schema_ref("OrderingGuarantee"),
+                "receipts": {
+                    "type": "array",
+                    "items": schema_ref("Receipt"),
+                    "description": "Receipts of the accepted events, duplicates excepted",
+                },
+            },
+        },
+        "RejectedEvent": {
+            "type": "object",
+            "required": ["facto_id", "reason", "error"],
+            "properties": {
+                "facto_id": {"type": "string"},
+                "reason": {
+                    "type": "string",
+                    "deprecated": true,
+                    "description": "Same as `error.message`",
+                },
+                "error": schema_ref("EventError"),
+            },
+        },
+        "EventError": {
+            "type": "object",
+            "required": ["code", "message"],
+            "properties": {
+                "code": schema_ref("ErrorCode"),
+                "message": {"type": "string"},
+                "details": {
+                    "type": "object",
+                    "description": "Structured context for some codes, such as the computed \
+                        and provided hashes of `HASH_MISMATCH`",
+                },
+            },
+        },
+        "ErrorCode": {
+            "type": "string",
+            "description": "Stable reason an event or request was refused. New codes may \
+                be added; clients should treat unknown codes as a generic rejection.",
+            "enum": ErrorCode::ALL.iter().map(|code| code.as_str()).collect::<Vec<_>>(),
+        },
+        "OrderingGuarantee": {
+            "type": "object",
+            "required": ["scope", "guarantee"],
+            "properties": {
+                "scope": {"type": "string"},
+                "guarantee": {"type": "string"},
+            },
+        },
+        "HealthResponse": {
+            "type": "object",
+            "required": ["status", "version"],
+            "properties": {
+                "status": {"type": "string"},
+                "version": {"type": "string"},
+            },
+        },
+        "ReadyResponse": {
+            "type": "object",
+            "required": ["ready", "nats_connected"],
+            "properties": {
+                "ready": {"type": "boolean"},
+                "nats_connected": {"type": "boolean"},
+                "kafka_connected": {"type": "boolean"},
+                "spool_depth": {"type": "integer"},
+                "checks": {
+                    "type": "object",
+                    "additionalProperties": schema_ref("DependencyHealth"),
+                    "description": "Health of each dependency, by name",
+                },
+            },
+        },
+        "DependencyHealth": {
+            "type": "object",
+            "required": ["healthy", "requirement", "latency_ms"],
+            "properties": {
+                "healthy": {"type": "boolean"},
+                "requirement": {
+                    "type": "string",
+                    "enum": ["required", "delivery", "informational"],
+                },
+                "latency_ms": {"type": "integer"},
+                "error": {"type": "string"},
+                "details": {"type": "object"},
+            },
+        },
+        "Capabilities": {
+            "type": "object",
+            "required": [
+                "server_version", "event_versions", "signature_algorithms",
+                "hash_algorithm", "canonical_versions", "did_methods", "max_body_bytes",
+                "max_event_bytes", "rate_limit_per_agent", "replay_max_skew_secs",
+                "auth_methods", "content_types", "content_encodings", "endpoints"
+            ],
+            "properties": {
+                "server_version": {"type": "string"},
+                "event_versions": {"type": "array", "items": {"type": "integer"}},
+                "signature_algorithms": {"type": "array", "items": {"type": "string"}},
+                "hash_algorithm": {"type": "string"},
+                "canonical_versions": {"type": "array", "items": {"type": "integer"}},
+                "canonical_v1_until": {"type": "integer"},
+                "did_methods": {"type": "array", "items": {"type": "string"}},
+                "max_body_bytes": {"type": "integer"},
+                "max_batch_events": {"type": "integer"},
+                "max_event_bytes": {"type": "integer"},
+                "max_tool_calls": {"type": "integer"},
+                "offload_threshold_bytes": {"type": "integer"},
+                "rate_limit_per_agent": {"type": "integer"},
+                "replay_window_secs": {"type": "integer"},
+                "replay_max_skew_secs": {"type": "integer"},
+                "auth_methods": {"type": "array", "items": {"type": "string"}},
+                "content_types": {"type": "array", "items": {"type": "string"}},
+                "content_encodings": {"type": "array", "items": {"type": "string"}},
+                "endpoints": {"type": "array", "items": {"type": "string"}},
+            },
+        },
+        "AgentRegistration": {
+            "type": "object",
+            "required": ["agent_id"],
+            "properties": {
+                "agent_id": {"type": "string"},
+                "sdk_version": {"type": ["string", "null"]},
+                "language": {"type": ["string", "null"]},
+                "models": {"type": "array", "items": {"type": "string"}},
+                "public_keys": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Declared for the inventory; keys are trusted through the key registry only",
+                },
+            },
+        },
+        "Heartbeat": {
+            "type": "object",
+            "properties": {"sdk_version": {"type": ["string", "null"]}},
+        },
+        "AgentRecord": {
+            "type": "object",
+            "required": ["agent_id", "models", "public_keys", "registered_at", "silent"],
+            "properties": {
+                "tenant_id": {"type": "string"},
+                "agent_id": {"type": "string"},
+                "sdk_version": {"type": ["string", "null"]},
+                "language": {"type": ["string", "null"]},
+                "models": {"type": "array", "items": {"type": "string"}},
+                "public_keys": {"type": "array", "items": {"type": "string"}},
+                "registered_at": {"type": "integer"},
+                "last_heartbeat_at": {"type": ["integer", "null"]},
+                "last_event_at": {"type": ["integer", "null"]},
+                "silent": {"type": "boolean"},
+            },
+        },
+        "Receipt": {
+            "type": "object",
+            "required": [
+                "facto_id", "event_hash", "received_at", "stream", "sequence",
+                "signer_id", "signer_public_key", "signature",
+            ],
+            "description": "Signed by the server over the sorted-key JSON of the receipt \
+                without `signature`",
+            "properties": {
+                "facto_id": {"type": "string"},
+                "event_hash": {"type": "string"},
+                "received_at": {"type": "integer"},
+                "stream": {
+                    "type": ["string", "null"],
+                    "description": "Stream the event was stored in; null while spooled or held",
+                },
+                "sequence": {
+                    "type": ["integer", "null"],
+                    "description": "Sequence of the event in `stream`",
+                },
+                "signer_id": {"type": "string"},
+                "signer_public_key": {"type": "string"},
+                "signature": {"type": "string"},
+            },
+        },
+        "ServerKey": {
+            "type": "object",
+            "required": ["signer_id", "algorithm", "public_key"],
+            "properties": {
+                "signer_id": {"type": "string"},
+                "algorithm": {"type": "string", "enum": ["ed25519"]},
+                "public_key": {"type": "string"},
+            },
+        },
+        "ErrorResponse": {
+            "type": "object",
+            "required": ["error"],
+            "properties": {
+                "error": {"type": "string"},
+                "code": schema_ref("ErrorCode"),
+            },
+        },
+    });
+    schemas["LegacyEvent"] = legacy_event_schema();
+    schemas
+}
+
+/// Schema of the pre-1.0 event `/v1/ingest/legacy` accepts
+fn legacy_event_schema() -> Value {
+    json!({
+        "type": "object",
+        "description": "An event as pre-1.0 SDKs send it. Its hash and signature cover \
+            the version 1 canonical form of the event it translates to.",
+        "required": [
+            "trace_id", "agent_id", "session_id", "action_type", "status",
+            "sdk_version", "signature", "public_key", "prev_hash", "event_hash",
+            "started_at", "completed_at"
+        ],
+        "properties": {
+            "trace_id": {"type": "string", "description": "Becomes `facto_id`"},
+            "agent_id": {"type": "string"},
+            "session_id": {"type": "string"},
+            "parent_trace_id": nullable("string"),
+            "action_type": {"type": "string"},
+            "status": {"type": "string"},
+            "input": {"description": "Any JSON value; becomes `input_data`"},
+            "output": {"description": "Any JSON value; becomes `output_data`"},
+            "model_id": nullable("string"),
+            "temperature": nullable("number"),
+            "seed": nullable("integer"),
+            "tool_calls": {"type": "array", "items": {}},
+            "sdk_version": {"type": "string"},
+            "signature": {"type": "string", "format": "byte"},
+            "public_key": {"type": "string", "format": "byte"},
+            "prev_hash": {"type": "string"},
+            "event_hash": {"type": "string"},
+            "started_at": {"type": "integer", "format": "int64", "description": "Unix nanoseconds"},
+            "completed_at": {"type": "integer", "format": "int64", "description": "Unix nanoseconds"},
+        },
+    })
+}
+
+// ============================================================================
+// Endpoints Described in Brief
+// ============================================================================
+
+/// Credentials an endpoint requires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Public,
+    Ingest,
+    Admin,
+    Debug,
+}
+
+/// An endpoint described by its parameters and media types only
+#[derive(Debug, Clone, Copy)]
+pub struct Endpoint {
+    pub method: &'static str,
+    /// In OpenAPI form, with `{name}` path parameters
+    pub path: &'static str,
+    pub operation_id: &'static str,
+    pub summary: &'static str,
+    pub access: Access,
+    /// Query parameters, and whether each is required
+    pub query: &'static [(&'static str, bool)],
+    /// Media types of the request body; empty for none
+    pub consumes: &'static [&'static str],
+    /// Media types of a successful response
+    pub produces: &'static [&'static str],
+}
+
+const JSON: &[&str] = &["application/json"];
+const KEY_FILES: &[&str] = &["application/x-ndjson", "text/csv"];
+const TENANT: &[(&str, bool)] = &[("tenant_id", false)];
+
+const fn endpoint(
+    method: &'static str,
+    path: &'static str,
+    operation_id: &'static str,
+    summary: &'static str,
+) -> Endpoint {
+    Endpoint {
+        method,
+        path,
+        operation_id,
+        summary,
+        access: Access::Public,
+        query: &[],
+        consumes: &[],
+        produces: JSON,
+    }
+}
+
+const fn get(path: &'static str, operation_id: &'static str, summary: &'static str) -> Endpoint {
+    endpoint("get", path, operation_id, summary)
+}
+
+const fn post(path: &'static str, operation_id: &'static str, summary: &'static str) -> Endpoint {
+    endpoint("post", path, operation_id, summary)
+}
+
+const fn put(path: &'static str, operation_id: &'static str, summary: &'static str) -> Endpoint {
+    endpoint("put", path, operation_id, summary)
+}
+
+const fn delete(path: &'static str, operation_id: &'static str, summary: &'static str) -> Endpoint {
+    endpoint("delete", path, operation_id, summary)
+}
+
+impl Endpoint {
+    const fn ingest(self) -> Self {
+        Self {
+            access: Access::Ingest,
+            ..self
+        }
+    }
+
+    const fn admin(self) -> Self {
+        Self {
+            access: Access::Admin,
+            ..self
+        }
+    }
+
+    const fn debug(self) -> Self {
+        Self {
+            access: Access::Debug,
+            ..self
+        }
+    }
+
+    const fn query(self, query: &'static [(&'static str, bool)]) -> Self {
+        Self { query, ..self }
+    }
+
+    const fn consumes(self, consumes: &'static [&'static str]) -> Self {
+        Self { consumes, ..self }
+    }
+
+    const fn produces(self, produces: &'static [&'static str]) -> Self {
+        Self { produces, ..self }
+    }
+}
+
+/// Endpoints beyond ingestion and discovery
+pub const ENDPOINTS: &[Endpoint] = &[
+    get("/metrics", "getMetrics", "Prometheus metrics").produces(&["text/plain"]),
+    get("/openapi.json", "getOpenApi", "This document"),
+    get("/docs", "getDocs", "Swagger UI").produces(&["text/html"]),
+    get(
+        "/v1/checkpoints",
+        "listCheckpoints",
+        "Signed Merkle checkpoints, newest first",
+    )
+    .ingest()
+    .query(&[("limit", false)]),
+    get(
+        "/v1/proof/{facto_id}",
+        "getProof",
+        "Inclusion proof of an event in a checkpoint",
+    )
+    .ingest(),
+    get(
+        "/v1/anchors",
+        "listAnchors",
+        "External timestamp anchors of checkpoints",
+    )
+    .ingest()
+    .query(&[("root", false)]),
+    get(
+        "/v1/debug/{debug_id}",
+        "getDebugBundle",
+        "A captured request debug bundle",
+    )
+    .debug(),
+    get(
+        "/v1/rejects",
+        "listRejects",
+        "Recently rejected events of an agent",
+    )
+    .debug()
+    .query(&[("agent_id", true), ("tenant_id", false), ("limit", false)]),
+    get(
+        "/v1/stream",
+        "streamEvents",
+        "Accepted events as they arrive, over SSE or a WebSocket",
+    )
+    .admin()
+    .query(&[
+        ("agent_id", false),
+        ("session_id", false),
+        ("tenant_id", false),
+        ("buffer", false),
+    ])
+    .produces(&["text/event-stream"]),
+    post(
+        "/v1/cursors",
+        "createCursor",
+        "Create a cursor over accepted events",
+    )
+    .admin()
+    .consumes(JSON),
+    get("/v1/cursors/{name}", "getCursor", "A cursor's position").admin(),
+    delete("/v1/cursors/{name}", "deleteCursor", "Delete a cursor").admin(),
+    get(
+        "/v1/cursors/{name}/events",
+        "fetchCursorEvents",
+        "Events after a cursor's position",
+    )
+    .admin()
+    .query(&[("limit", false), ("wait_ms", false)]),
+    post(
+        "/v1/cursors/{name}/commit",
+        "commitCursor",
+        "Move a cursor past fetched events",
+    )
+    .admin()
+    .consumes(JSON),
+    get(
+        "/v1/admin/keys/export",
+        "exportKeys",
+        "Export registered keys",
+    )
+    .admin()
+    .query(&[("format", false)])
+    .produces(KEY_FILES),
+    post(
+        "/v1/admin/keys/import",
+        "importKeys",
+        "Import registered keys",
+    )
+    .admin()
+    .query(&[("format", false), ("dry_run", false)])
+    .consumes(KEY_FILES),
+    get(
+        "/v1/admin/keys/snapshot",
+        "exportKeySnapshot",
+        "Signed snapshot of the key registry",
+    )
+    .admin()
+    .query(&[("version", false), ("at", false)]),
+    post(
+        "/v1/admin/keys/snapshot",
+        "importKeySnapshot",
+        "Restore a signed key registry snapshot",
+    )
+    .admin()
+    .consumes(JSON),
+    get(
+        "/v1/admin/keys/{agent_id}",
+        "listKeys",
+        "An agent's registered keys",
+    )
+    .admin()
+    .query(TENANT),
+    post(
+        "/v1/admin/keys/{agent_id}",
+        "registerKey",
+        "Register a key for an agent",
+    )
+    .admin()
+    .query(TENANT)
+    .consumes(JSON),
+    post(
+        "/v1/admin/keys/{agent_id}/revoke",
+        "revokeKey",
+        "Revoke an agent's key",
+    )
+    .admin()
+    .query(TENANT)
+    .consumes(JSON),
+    post(
+        "/v1/admin/keys/{agent_id}/rotate",
+        "rotateKey",
+        "Rotate an agent's key",
+    )
+    .admin()
+    .query(TENANT)
+    .consumes(JSON),
+    get(
+        "/v1/admin/sessions/{session_id}/freeze",
+        "getFreeze",
+        "Whether a session is frozen",
+    )
+    .admin()
+    .query(TENANT),
+    post(
+        "/v1/admin/sessions/{session_id}/freeze",
+        "freezeSession",
+        "Freeze a session",
+    )
+    .admin()
+    .query(TENANT)
+    .consumes(JSON),
+    post(
+        "/v1/admin/sessions/{session_id}/unfreeze",
+        "unfreezeSession",
+        "Approve unfreezing a session",
+    )
+    .admin()
+    .query(TENANT),
+    get(
+        "/v1/sessions/{session_id}/annotations",
+        "listAnnotations",
+        "A session's annotations",
+    )
+    .admin()
+    .query(TENANT),
+    post(
+        "/v1/sessions/{session_id}/annotations",
+        "annotateSession",
+        "Annotate a session",
+    )
+    .admin()
+    .query(TENANT)
+    .consumes(JSON),
+    get("/v1/admin/tenants", "listTenants", "Configured tenants").admin(),
+    get(
+        "/v1/admin/tenants/{tenant_id}",
+        "getTenant",
+        "A tenant's limits",
+    )
+    .admin(),
+    put(
+        "/v1/admin/tenants/{tenant_id}",
+        "putTenant",
+        "Create or update a tenant",
+    )
+    .admin()
+    .consumes(JSON),
+    delete(
+        "/v1/admin/tenants/{tenant_id}",
+        "deleteTenant",
+        "Delete a tenant",
+    )
+    .admin(),
+    put(
+        "/v1/admin/tenants/{tenant_id}/headers",
+        "putTenantHeaders",
+        "Headers propagated for a tenant",
+    )
+    .admin()
+    .consumes(JSON),
+    put(
+        "/v1/admin/tenants/{tenant_id}/enforcement-mode",
+        "putTenantEnforcementMode",
+        "Override a tenant's verification mode",
+    )
+    .admin()
+    .consumes(JSON),
+    delete(
+        "/v1/admin/tenants/{tenant_id}/enforcement-mode",
+        "deleteTenantEnforcementMode",
+        "Clear a tenant's verification mode override",
+    )
+    .admin(),
+    get(
+        "/v1/admin/shadow",
+        "getShadowReport",
+        "Results of shadow canonicalization and hashing",
+    )
+    .admin(),
+    get("/v1/agents", "listAgents", "Registered agents")
+        .admin()
+        .query(TENANT),
+    get(
+        "/v1/usage",
+        "getUsage",
+        "Events and bytes by tenant, agent and day",
+    )
+    .admin()
+    .query(&[
+        ("tenant", false),
+        ("from", false),
+        ("to", false),
+        ("format", false),
+    ])
+    .produces(&["application/json", "text/csv"]),
+    get(
+        "/v1/admin/agents",
+        "listAgentControls",
+        "Agents with limits or pauses set",
+    )
+    .admin(),
+    get(
+        "/v1/admin/agents/{agent_id}",
+        "getAgentStatus",
+        "An agent's limits and state",
+    )
+    .admin()
+    .query(TENANT),
+    put(
+        "/v1/admin/agents/{agent_id}/rate-limit",
+        "putAgentRateLimit",
+        "Override an agent's rate limit",
+    )
+    .admin()
+    .query(TENANT)
+    .consumes(JSON),
+    delete(
+        "/v1/admin/agents/{agent_id}/rate-limit",
+        "deleteAgentRateLimit",
+        "Clear an agent's rate limit override",
+    )
+    .admin()
+    .query(TENANT),
+    put(
+        "/v1/admin/agents/{agent_id}/byte-quota",
+        "putAgentByteQuota",
+        "Override an agent's byte quota",
+    )
+    .admin()
+    .query(TENANT)
+    .consumes(JSON),
+    delete(
+        "/v1/admin/agents/{agent_id}/byte-quota",
+        "deleteAgentByteQuota",
+        "Clear an agent's byte quota override",
+    )
+    .admin()
+    .query(TENANT),
+    put(
+        "/v1/admin/agents/{agent_id}/enforcement-mode",
+        "putAgentEnforcementMode",
+        "Override an agent's verification mode",
+    )
+    .admin()
+    .query(TENANT)
+    .consumes(JSON),
+    delete(
+        "/v1/admin/agents/{agent_id}/enforcement-mode",
+        "deleteAgentEnforcementMode",
+        "Clear an agent's verification mode override",
+    )
+    .admin()
+    .query(TENANT),
+    put(
+        "/v1/admin/agents/{agent_id}/envelope-trust",
+        "putAgentEnvelopeTrust",
+        "Trust an agent's batch envelopes",
+    )
+    .admin()
+    .query(TENANT),
+    delete(
+        "/v1/admin/agents/{agent_id}/envelope-trust",
+        "deleteAgentEnvelopeTrust",
+        "Stop trusting an agent's batch envelopes",
+    )
+    .admin()
+    .query(TENANT),
+    post(
+        "/v1/admin/agents/{agent_id}/pause",
+        "pauseAgent",
+        "Refuse an agent's events",
+    )
+    .admin()
+    .query(TENANT)
+    .consumes(JSON),
+    post(
+        "/v1/admin/agents/{agent_id}/resume",
+        "resumeAgent",
+        "Accept a paused agent's events again",
+    )
+    .admin()
+    .query(TENANT),
+    get("/v1/admin/webhooks", "listWebhooks", "Registered webhooks").admin(),
+    post(
+        "/v1/admin/webhooks",
+        "registerWebhook",
+        "Register a webhook",
+    )
+    .admin()
+    .consumes(JSON),
+    get(
+        "/v1/admin/webhooks/{webhook_id}",
+        "getWebhook",
+        "A registered webhook",
+    )
+    .admin(),
+    delete(
+        "/v1/admin/webhooks/{webhook_id}",
+        "deleteWebhook",
+        "Delete a webhook",
+    )
+    .admin(),
+    get(
+        "/v1/admin/redactions",
+        "listRedactions",
+        "Redaction policies by tenant",
+    )
+    .admin(),
+    get(
+        "/v1/admin/redactions/{tenant_id}",
+        "getRedaction",
+        "A tenant's redaction policy",
+    )
+    .admin(),
+    put(
+        "/v1/admin/redactions/{tenant_id}",
+        "putRedaction",
+        "Set a tenant's redaction policy",
+    )
+    .admin()
+    .consumes(JSON),
+    delete(
+        "/v1/admin/redactions/{tenant_id}",
+        "deleteRedaction",
+        "Clear a tenant's redaction policy",
+    )
+    .admin(),
+    get(
+        "/v1/admin/models",
+        "listModels",
+        "Approved models by tenant",
+    )
+    .admin(),
+    get(
+        "/v1/admin/models/{tenant_id}",
+        "getModels",
+        "A tenant's approved models",
+    )
+    .admin(),
+    put(
+        "/v1/admin/models/{tenant_id}",
+        "putModels",
+        "Set a tenant's approved models",
+    )
+    .admin()
+    .consumes(JSON),
+    delete(
+        "/v1/admin/models/{tenant_id}",
+        "deleteModels",
+        "Clear a tenant's approved models",
+    )
+    .admin(),
+    get(
+        "/v1/admin/retention",
+        "listRetention",
+        "Retention policies by tenant",
+    )
+    .admin(),
+    get(
+        "/v1/admin/retention/{tenant_id}",
+        "getRetention",
+        "A tenant's retention policy",
+    )
+    .admin(),
+    put(
+        "/v1/admin/retention/{tenant_id}",
+        "putRetention",
+        "Set a tenant's retention policy",
+    )
+    .admin()
+    .consumes(JSON),
+    delete(
+        "/v1/admin/retention/{tenant_id}",
+        "deleteRetention",
+        "Clear a tenant's retention policy",
+    )
+    .admin(),
+    get(
+        "/v1/admin/legal-holds",
+        "listLegalHolds",
+        "Sessions under legal hold",
+    )
+    .admin()
+    .query(&[("tenant", false)]),
+    put(
+        "/v1/admin/legal-holds/{tenant_id}/{session_id}",
+        "putLegalHold",
+        "Place a session under legal hold",
+    )
+    .admin()
+    .consumes(JSON),
+    delete(
+        "/v1/admin/legal-holds/{tenant_id}/{session_id}",
+        "deleteLegalHold",
+        "Release a session's legal hold",
+    )
+    .admin(),
+    get(
+        "/v1/admin/schemas",
+        "listSchemas",
+        "Payload schemas by action type",
+    )
+    .admin(),
+    get(
+        "/v1/admin/schemas/{action_type}",
+        "getSchema",
+        "An action type's payload schemas",
+    )
+    .admin(),
+    put(
+        "/v1/admin/schemas/{action_type}",
+        "putSchema",
+        "Set an action type's payload schemas",
+    )
+    .admin()
+    .consumes(JSON),
+    delete(
+        "/v1/admin/schemas/{action_type}",
+        "deleteSchema",
+        "Clear an action type's payload schemas",
+    )
+    .admin(),
+];
+
+/// The OpenAPI operation of `endpoint`
+fn operation(endpoint: &Endpoint) -> Value {
+    let string = json!({"type": "string"});
+    let mut parameters: Vec<Value> = endpoint
+        .path
+        .split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .map(|name| json!({"name": name, "in": "path", "required": true, "schema": string}))
+        .collect();
+    parameters.extend(endpoint.query.iter().map(|(name, required)| {
+        json!({"name": name, "in": "query", "required": required, "schema": string})
+    }));
+    let content = |media_types: &[&str], schema: Value| -> Value {
+        media_types
+            .iter()
+            .map(|media_type| (media_type.to_string(), json!({"schema": schema})))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    };
+    let body_schema = |media_type: &str| match media_type {
+        "application/json" => json!({"type": "object"}),
+        _ => string.clone(),
+    };
+
+    let mut responses = json!({
+        "200": {
+            "description": endpoint.summary,
+            "content": content(endpoint.produces, body_schema(endpoint.produces[0])),
+        },
+    });
+    let scope = match endpoint.access {
+        Access::Public => None,
+        Access::Ingest => Some("ingest"),
+        Access::Admin => Some("admin"),
+        Access::Debug => Some("debug"),
+    };
+    if let Some(scope) = scope {
+        responses["401"] = error_response("Missing or invalid credentials");
+        responses["403"] = error_response(&format!("Credentials lack the {} scope", scope));
+    }
+    if endpoint.access == Access::Admin || endpoint.path.contains('{') {
+        responses["404"] = error_response("Not found, or the endpoint is disabled");
+    }
+    if !endpoint.consumes.is_empty() || !endpoint.query.is_empty() {
+        responses["400"] = error_response("Invalid request");
+    }
+
+    let mut operation = json!({
+        "operationId": endpoint.operation_id,
+        "summary": endpoint.summary,
+        "parameters": parameters,
+        "responses": responses,
+    });
+    if let Some(scope) = scope {
+        operation["security"] = json!([{"bearerAuth": []}, {"apiKey": []}]);
+        operation["description"] = format!("Requires the {} scope", scope).into();
+    }
+    if !endpoint.consumes.is_empty() {
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": content(endpoint.consumes, body_schema(endpoint.consumes[0])),
+        });
+    }
+    operation
+}
+
+/// The OpenAPI 3.1 document for the given server version
+pub fn document(server_version: &str) -> Value {
+    let ingest_errors = json!({
+        "400": error_response("Malformed event, or too many tool calls"),
+        "401": error_response("Missing or invalid credentials"),
+        "403": error_response("Credentials lack the ingest scope"),
+        "413": error_response("Request body or event too large, compressed or not"),
+        "415": error_response("Unsupported Content-Type or Content-Encoding"),
+        "503": error_response("No broker is reachable and no spool is configured"),
+    });
+    let mut single_responses = json!({
+        "202": ingest_response("Accepted, or held in the spool when `spooled` is set", "SingleIngestResponse"),
+        "200": ingest_response("Already accepted earlier; `duplicate` is set", "SingleIngestResponse"),
+        "409": ingest_response("`facto_id` was accepted for a different event", "SingleIngestResponse"),
+        "423": ingest_response("Agent paused or session frozen", "SingleIngestResponse"),
+        "429": ingest_response("Rate limit or byte quota exceeded", "SingleIngestResponse"),
+    });
+    let mut batch_responses = json!({
+        "202": ingest_response(
+            "Processed; each event was accepted, rejected with a reason, or a duplicate",
+            "BatchIngestResponse"
+        ),
+    });
+    for (status, response) in ingest_errors.as_object().unwrap() {
+        single_responses[status] = response.clone();
+        batch_responses[status] = response.clone();
+    }
+
+    let mut document = json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "Facto Ingestion API",
+            "version": server_version,
+            "description": "Accepts signed agent events, verifies them and publishes them \
+                to the event stream.",
+        },
+        "paths": {
+            "/v1/ingest": {
+                "post": {
+                    "operationId": "ingestEvent",
+                    "summary": "Ingest one event",
+                    "security": [{"bearerAuth": []}, {"apiKey": []}],
+                    "parameters": [backfill_parameter()],
+                    "requestBody": {
+                        "required": true,
+                        "description": COMPRESSED_BODY,
+                        "content": encoded_content("FactoEvent"),
+                    },
+                    "responses": single_responses.clone(),
+                },
+            },
+            "/v1/ingest/batch": {
+                "post": {
+                    "operationId": "ingestBatch",
+                    "summary": "Ingest a batch of events",
+                    "security": [{"bearerAuth": []}, {"apiKey": []}],
+                    "parameters": [backfill_parameter()],
+                    "requestBody": {
+                        "required": true,
+                        "description": COMPRESSED_BODY,
+                        "content": encoded_content("BatchIngestRequest"),
+                    },
+                    "responses": batch_responses,
+                },
+            },
+            "/v1/ingest/legacy": {
+                "post": {
+                    "operationId": "ingestLegacyEvent",
+                    "summary": "Ingest one event in the pre-1.0 SDK shape",
+                    "description": "The event is translated into a version 1 event and \
+                        ingested like `/v1/ingest`; its server envelope records the \
+                        translation.",
+                    "security": [{"bearerAuth": []}, {"apiKey": []}],
+                    "parameters": [backfill_parameter()],
+                    "requestBody": {
+                        "required": true,
+                        "description": COMPRESSED_BODY,
+                        "content": encoded_content("LegacyEvent"),
+                    },
+                    "responses": single_responses,
+                },
+            },
+            "/v1/agents/register": {
+                "post": {
+                    "operationId": "registerAgent",
+                    "summary": "Register the agent, or update its registration",
+                    "security": [{"bearerAuth": []}, {"apiKey": []}],
+                    "requestBody": {
+                        "required": true,
+                        "content": {"application/json": {"schema": schema_ref("AgentRegistration")}},
+                    },
+                    "responses": {
+                        "200": json_response("Registered", "AgentRecord"),
+                        "400": error_response("Invalid agent_id"),
+                        "401": error_response("Missing or invalid credentials"),
+                        "403": error_response("Credentials lack the ingest scope or are bound to another agent"),
+                    },
+                },
+            },
+            "/v1/agents/{agent_id}/heartbeat": {
+                "post": {
+                    "operationId": "sendHeartbeat",
+                    "summary": "Report a registered agent alive",
+                    "security": [{"bearerAuth": []}, {"apiKey": []}],
+                    "parameters": [{
+                        "name": "agent_id",
+                        "in": "path",
+                        "required": true,
+                        "schema": {"type": "string"},
+                    }],
+                    "requestBody": {
+                        "required": false,
+                        "content": {"application/json": {"schema": schema_ref("Heartbeat")}},
+                    },
+                    "responses": {
+                        "200": json_response("Recorded", "AgentRecord"),
+                        "401": error_response("Missing or invalid credentials"),
+                        "403": error_response("Credentials lack the ingest scope or are bound to another agent"),
+                        "404": error_response("Agent not registered"),
+                    },
+                },
+            },
+            "/v1/receipts/{facto_id}": {
+                "get": {
+                    "operationId": "getReceipt",
+                    "summary": "Receipt of a recently accepted event",
+                    "security": [{"bearerAuth": []}, {"apiKey": []}],
+                    "parameters": [{
+                        "name": "facto_id",
+                        "in": "path",
+                        "required": true,
+                        "schema": {"type": "string"},
+                    }],
+                    "responses": {
+                        "200": json_response("Receipt", "Receipt"),
+                        "401": error_response("Missing or invalid credentials"),
+                        "403": error_response("Credentials lack the ingest scope"),
+                        "404": error_response("No receipt retained for this facto_id"),
+                    },
+                },
+            },
+            "/.well-known/facto-server-key.json": {
+                "get": {
+                    "operationId": "getServerKey",
+                    "summary": "The key receipts are signed with",
+                    "responses": {"200": json_response("Server key", "ServerKey")},
+                },
+            },
+            "/v1/capabilities": {
+                "get": {
+                    "operationId": "getCapabilities",
+                    "summary": "What this server supports",
+                    "responses": {"200": json_response("Capabilities", "Capabilities")},
+                },
+            },
+            "/health": {
+                "get": {
+                    "operationId": "getHealth",
+                    "summary": "Liveness",
+                    "responses": {"200": json_response("Alive", "HealthResponse")},
+                },
+            },
+            "/ready": {
+                "get": {
+                    "operationId": "getReady",
+                    "summary": "Readiness to accept events",
+                    "responses": {
+                        "200": json_response("Ready", "ReadyResponse"),
+                        "503": json_response("Not ready", "ReadyResponse"),
+                    },
+                },
+            },
+        },
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "bearerAuth": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "API key or JWT",
+                },
+                "apiKey": {"type": "apiKey", "in": "header", "name": "x-api-key"},
+            },
+        },
+    });
+    for endpoint in ENDPOINTS {
+        document["paths"][endpoint.path][endpoint.method] = operation(endpoint);
+    }
+    document
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{
+        BatchEnvelope, BatchIngestResponse, Capabilities, DependencyHealth, EventError,
+        OrderingGuarantee, ReadyResponse, Receipt, RejectedEvent, SingleIngestResponse,
+    };
+    use crate::testing::test_event;
+
+    /// Every serialized field is described, and every required one present
+    fn assert_matches(name: &str, value: Value) {
+        let schemas = schemas();
+        let schema = &schemas[name];
+        let properties = schema["properties"].as_object().unwrap();
+        let object = value.as_object().unwrap();
+        for key in object.keys() {
+            assert!(
+                properties.contains_key(key),
+                "{}.{} is not described",
+                name,
+                key
+            );
+        }
+        for key in schema["required"].as_array().unwrap() {
+            let key = key.as_str().unwrap();
+            assert!(
+                object.contains_key(key),
+                "{}.{} is required but missing",
+                name,
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn test_schemas_match_wire_types() {
+        let event = serde_json::to_value(test_event()).unwrap();
+        assert_matches("ExecutionMeta", event["execution_meta"].clone());
+        assert_matches("Proof", event["proof"].clone());
+        assert_matches("FactoEvent", event);
+
+        let receipt = Receipt {
+            facto_id: "f1".to_string(),
+            event_hash: "ab".repeat(32),
+            received_at: 1,
+            stream: Some("FACTO_EVENTS".to_string()),
+            sequence: Some(1),
+            signer_id: "ingestion-1".to_string(),
+            signer_public_key: "a".to_string(),
+            signature: "b".to_string(),
+        };
+        assert_matches("Receipt", serde_json::to_value(&receipt).unwrap());
+        let batch = BatchIngestResponse {
+            accepted_count: 1,
+            rejected_count: 1,
+            rejected: vec![RejectedEvent::new(
+                "f1".to_string(),
+                EventError::new(ErrorCode::RateLimited, "Rate limit exceeded"),
+            )],
+            duplicates: vec!["f2".to_string()],
+            spooled_count: 1,
+            held_count: 1,
+            ordering: Some(OrderingGuarantee::session()),
+            receipts: vec![receipt.clone()],
+        };
+        assert_matches("BatchIngestResponse", serde_json::to_value(batch).unwrap());
+        let envelope = BatchEnvelope {
+            batch_hash: "a".to_string(),
+            public_key: "b".to_string(),
+            signature: "c".to_string(),
+            algorithm: Some("ed25519".to_string()),
+        };
+        assert_matches("BatchEnvelope", serde_json::to_value(envelope).unwrap());
+        let ready = ReadyResponse {
+            ready: true,
+            nats_connected: true,
+            kafka_connected: None,
+            spool_depth: Some(0),
+            checks: [(
+                "jetstream".to_string(),
+                DependencyHealth {
+                    healthy: false,
+                    requirement: "delivery",
+                    latency_ms: 3,
+                    error: Some("stream FACTO_EVENTS is full".to_string()),
+                    details: Some(json!({"messages": 1})),
+                },
+            )]
+            .into(),
+        };
+        let ready = serde_json::to_value(ready).unwrap();
+        assert_matches("DependencyHealth", ready["checks"]["jetstream"].clone());
+        assert_matches("ReadyResponse", ready);
+        let error = EventError::new(ErrorCode::HashMismatch, "Hash mismatch")
+            .with_details(json!({"computed": "a", "provided": "b"}));
+        assert_matches("EventError", serde_json::to_value(&error).unwrap());
+        let single = SingleIngestResponse {
+            duplicate: true,
+            spooled: true,
+            held: true,
+            receipt: Some(receipt),
+            ..SingleIngestResponse::rejected("f1".to_string(), error)
+        };
+        assert_matches(
+            "SingleIngestResponse",
+            serde_json::to_value(single).unwrap(),
+        );
+
+        // Codes serialize as listed in the schema
+        for code in ErrorCode::ALL {
+            assert_eq!(serde_json::to_value(code).unwrap(), code.as_str());
+        }
+        let capabilities = Capabilities {
+            server_version: "0.1.0",
+            event_versions: &[1],
+            signature_algorithms: &["ed25519"],
+            hash_algorithm: "sha3-256",
+            canonical_versions: &[1, 2],
+            canonical_v1_until: Some(0),
+            did_methods: crate::did::DID_METHODS,
+            max_body_bytes: 1,
+            max_batch_events: Some(1),
+            max_event_bytes: 1,
+            max_tool_calls: Some(1),
+            offload_threshold_bytes: Some(1),
+            rate_limit_per_agent: 1,
+            replay_window_secs: Some(1),
+            replay_max_skew_secs: 1,
+            auth_methods: vec![],
+            content_types: CONTENT_TYPES,
+            content_encodings: &["gzip"],
+            endpoints: vec![],
+        };
+        assert_matches("Capabilities", serde_json::to_value(capabilities).unwrap());
+    }
+
+    #[test]
+    fn test_document_references_resolve() {
+        let document = document("0.1.0");
+        let text = document.to_string();
+        for reference in text.split("\"#/components/schemas/").skip(1) {
+            let name = &reference[..reference.find('"').unwrap()];
+            assert!(
+                document["components"]["schemas"].get(name).is_some(),
+                "{} is referenced but not defined",
+                name
+            );
+        }
+        assert_eq!(document["info"]["version"], "0.1.0");
+
+        // Generated clients name their methods by operation id
+        let mut ids = std::collections::HashSet::new();
+        for operations in document["paths"].as_object().unwrap().values() {
+            for operation in operations.as_object().unwrap().values() {
+                let id = operation["operationId"].as_str().unwrap();
+                assert!(ids.insert(id), "{} is not unique", id);
+            }
+        }
+    }
+}
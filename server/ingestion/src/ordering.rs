@@ -0,0 +1,98 @@
+use dashmap::DashMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+/// Per-session delivery locks.
+///
+/// Ordered batches hold the locks of all their sessions while they are
+/// delivered; every other delivery takes its session's lock per event, so
+/// it cannot slip between the events of an ordered batch. Locks are created
+/// on demand and dropped once nobody holds or waits for them.
+#[derive(Default)]
+pub struct SessionLocks {
+    locks: Arc<DashMap<String, Arc<Mutex<()>>>>,
+}
+
+/// Held session locks; released on drop
+pub struct SessionGuard {
+    locks: Arc<DashMap<String, Arc<Mutex<()>>>>,
+    held: Vec<(String, OwnedMutexGuard<()>)>,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        for (key, guard) in self.held.drain(..) {
+            drop(guard);
+            self.locks
+                .remove_if(&key, |_, lock| Arc::strong_count(lock) == 1);
+        }
+    }
+}
+
+impl SessionLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lock every session in `keys`, in sorted order so that concurrent
+    /// callers cannot deadlock
+    pub async fn lock_all<'a>(&self, keys: impl IntoIterator<Item = &'a str>) -> SessionGuard {
+        let mut keys: Vec<&str> = keys.into_iter().collect();
+        keys.sort_unstable();
+        keys.dedup();
+
+        let mut held = Vec::with_capacity(keys.len());
+        for key in keys {
+            let lock = self
+                .locks
+                .entry(key.to_string())
+                .or_insert_with(|| Arc::new(Mutex::new(())))
+                .clone();
+            held.push((key.to_string(), lock.lock_owned().await));
+        }
+        SessionGuard {
+            locks: self.locks.clone(),
+            held,
+        }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.locks.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_ordered_delivery_excludes_other_requests() {
+        let locks = Arc::new(SessionLocks::new());
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let batch = locks.lock_all(["s1", "s2", "s1"]).await;
+        let other = {
+            let (locks, log) = (locks.clone(), log.clone());
+            tokio::spawn(async move {
+                let _session = locks.lock_all(["s1"]).await;
+                log.lock().unwrap().push("other");
+            })
+        };
+
+        for event in ["batch-1", "batch-2", "batch-3"] {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            log.lock().unwrap().push(event);
+        }
+        drop(batch);
+        other.await.unwrap();
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["batch-1", "batch-2", "batch-3", "other"]
+        );
+        // Unused locks are dropped
+        assert_eq!(locks.len(), 0);
+    }
+}
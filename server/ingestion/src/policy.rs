@@ -0,0 +1,455 @@
+//! Runtime guardrails evaluated against each event on the ingest path.
+//!
+//! Policies look at what an agent did rather than whether the record of it
+//! is authentic: a temperature above a threshold, a forbidden tool call,
+//! output matching a pattern, an unusually long action. Each finding names
+//! what the server does about it: `annotate` records it in the event's
+//! server envelope, `alert` also publishes it on [`POLICY_ALERT_SUBJECT`]
+//! and to POLICY_ALERT_WEBHOOK_URL once the event is accepted, and `reject`
+//! refuses the event with `POLICY_VIOLATION`.
+
+use axum::async_trait;
+use metrics::counter;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{path::Path, time::Duration};
+use tokio::sync::mpsc;
+use tracing::info;
+
+use crate::annotations::Severity;
+use crate::sinks::{AcceptedEvent, FanoutSink};
+use crate::verification::{now_nanos, ServerSigner};
+use crate::FactoEvent;
+
+/// NATS subject signed policy alerts are published on
+pub const POLICY_ALERT_SUBJECT: &str = "facto.control.policy_alerts";
+
+/// What the server does when a rule fires
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    /// Record the finding in the server envelope
+    #[default]
+    Annotate,
+    /// Record the finding and alert on it once the event is accepted
+    Alert,
+    /// Refuse the event
+    Reject,
+}
+
+impl PolicyAction {
+    /// Metrics label for the action
+    pub fn code(&self) -> &'static str {
+        match self {
+            PolicyAction::Annotate => "annotate",
+            PolicyAction::Alert => "alert",
+            PolicyAction::Reject => "reject",
+        }
+    }
+}
+
+/// A rule that fired on an event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolicyFinding {
+    pub policy: String,
+    pub rule: String,
+    pub action: PolicyAction,
+    pub severity: Severity,
+    /// What the rule found, one entry per condition
+    pub reasons: Vec<String>,
+}
+
+// ============================================================================
+// Policies
+// ============================================================================
+
+/// Evaluates events before they are verified and accepted. Policies run on
+/// the request path, so they must not block.
+pub trait Policy: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
+    fn evaluate(&self, event: &FactoEvent) -> Vec<PolicyFinding>;
+}
+
+/// A rule as written in a rules file. `action_type` and `agent_id` patterns
+/// select the events the rule applies to; it fires when every condition
+/// given holds. Patterns are regular expressions.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PolicyRuleConfig {
+    pub name: String,
+    #[serde(default)]
+    pub action: PolicyAction,
+    #[serde(default)]
+    pub severity: Severity,
+    pub action_type: Option<String>,
+    pub agent_id: Option<String>,
+    /// `execution_meta.temperature` above this
+    pub max_temperature: Option<f64>,
+    /// A tool call naming one of these tools
+    #[serde(default)]
+    pub forbidden_tools: Vec<String>,
+    /// Pattern for the JSON of the input data
+    pub input_pattern: Option<String>,
+    /// Pattern for the JSON of the output data
+    pub output_pattern: Option<String>,
+    /// `completed_at - started_at` above this
+    pub max_duration_ms: Option<u64>,
+}
+
+struct PolicyRule {
+    name: String,
+    action: PolicyAction,
+    severity: Severity,
+    action_type: Option<Regex>,
+    agent_id: Option<Regex>,
+    max_temperature: Option<f64>,
+    forbidden_tools: Vec<String>,
+    input_pattern: Option<Regex>,
+    output_pattern: Option<Regex>,
+    max_duration: Option<Duration>,
+}
+
+/// The name of the tool a call invokes: `name`, or `function` as a string
+/// or an object with a `name`
+fn tool_name(call: &Value) -> Option<&str> {
+    call.get("name")
+        .and_then(Value::as_str)
+        .or_else(|| match call.get("function") {
+            Some(Value::String(name)) => Some(name.as_str()),
+            Some(function) => function.get("name").and_then(Value::as_str),
+            None => None,
+        })
+}
+
+impl PolicyRule {
+    fn compile(config: PolicyRuleConfig) -> anyhow::Result<Self> {
+        let pattern = |p: Option<String>| -> anyhow::Result<Option<Regex>> {
+            p.map(|p| Regex::new(&p)).transpose().map_err(|e| {
+                anyhow::anyhow!("invalid pattern in policy rule {}: {}", config.name, e)
+            })
+        };
+        let rule = Self {
+            action_type: pattern(config.action_type.clone())?,
+            agent_id: pattern(config.agent_id.clone())?,
+            input_pattern: pattern(config.input_pattern.clone())?,
+            output_pattern: pattern(config.output_pattern.clone())?,
+            max_temperature: config.max_temperature,
+            forbidden_tools: config.forbidden_tools,
+            max_duration: config.max_duration_ms.map(Duration::from_millis),
+            name: config.name,
+            action: config.action,
+            severity: config.severity,
+        };
+        if rule.max_temperature.is_none()
+            && rule.forbidden_tools.is_empty()
+            && rule.input_pattern.is_none()
+            && rule.output_pattern.is_none()
+            && rule.max_duration.is_none()
+        {
+            anyhow::bail!("policy rule {} has no condition", rule.name);
+        }
+        Ok(rule)
+    }
+
+    /// Why the rule fires on the event, or None when it does not
+    fn check(&self, event: &FactoEvent) -> Option<Vec<String>> {
+        let applies = self
+            .action_type
+            .as_ref()
+            .is_none_or(|p| p.is_match(&event.action_type))
+            && self
+                .agent_id
+                .as_ref()
+                .is_none_or(|p| p.is_match(&event.agent_id));
+        if !applies {
+            return None;
+        }
+
+        let mut reasons = Vec::new();
+        if let Some(max) = self.max_temperature {
+            let temperature = event.execution_meta.temperature.filter(|t| *t > max)?;
+            reasons.push(format!("temperature {} is above {}", temperature, max));
+        }
+        if !self.forbidden_tools.is_empty() {
+            let tools: Vec<&str> = event
+                .execution_meta
+                .tool_calls
+                .iter()
+                .filter_map(tool_name)
+                .filter(|name| self.forbidden_tools.iter().any(|t| t == name))
+                .collect();
+            if tools.is_empty() {
+                return None;
+            }
+            reasons.push(format!("forbidden tools called: {}", tools.join(", ")));
+        }
+        if let Some(ref pattern) = self.input_pattern {
+            if !pattern.is_match(&event.input_data.to_string()) {
+                return None;
+            }
+            reasons.push(format!("input_data matches {}", pattern));
+        }
+        if let Some(ref pattern) = self.output_pattern {
+            if !pattern.is_match(&event.output_data.to_string()) {
+                return None;
+            }
+            reasons.push(format!("output_data matches {}", pattern));
+        }
+        if let Some(max) = self.max_duration {
+            let nanos = event.completed_at.saturating_sub(event.started_at);
+            if nanos <= max.as_nanos() as i64 {
+                return None;
+            }
+            reasons.push(format!(
+                "took {}ms, more than {}ms",
+                nanos / 1_000_000,
+                max.as_millis()
+            ));
+        }
+        Some(reasons)
+    }
+}
+
+/// Evaluates events with threshold, tool and pattern rules
+pub struct RulePolicy {
+    rules: Vec<PolicyRule>,
+}
+
+impl RulePolicy {
+    pub fn new(rules: Vec<PolicyRuleConfig>) -> anyhow::Result<Self> {
+        Ok(Self {
+            rules: rules
+                .into_iter()
+                .map(PolicyRule::compile)
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+
+    /// Rules from a JSON file holding an array of rules
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let rules: Vec<PolicyRuleConfig> = serde_json::from_slice(&std::fs::read(path)?)?;
+        info!(
+            "Loaded {} policy rules from {}",
+            rules.len(),
+            path.display()
+        );
+        Self::new(rules)
+    }
+}
+
+impl Policy for RulePolicy {
+    fn name(&self) -> &'static str {
+        "rules"
+    }
+
+    fn evaluate(&self, event: &FactoEvent) -> Vec<PolicyFinding> {
+        self.rules
+            .iter()
+            .filter_map(|rule| {
+                rule.check(event).map(|reasons| PolicyFinding {
+                    policy: self.name().to_string(),
+                    rule: rule.name.clone(),
+                    action: rule.action,
+                    severity: rule.severity,
+                    reasons,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The registered policies
+#[derive(Default)]
+pub struct PolicyEngine {
+    policies: Vec<Box<dyn Policy>>,
+}
+
+impl PolicyEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, policy: impl Policy) {
+        self.policies.push(Box::new(policy));
+    }
+
+    /// Whether no policies are registered
+    pub fn is_empty(&self) -> bool {
+        self.policies.is_empty()
+    }
+
+    /// Every finding of every policy on the event
+    pub fn evaluate(&self, event: &FactoEvent, tenant: &str) -> Vec<PolicyFinding> {
+        let findings: Vec<PolicyFinding> = self
+            .policies
+            .iter()
+            .flat_map(|policy| policy.evaluate(event))
+            .collect();
+        for finding in &findings {
+            counter!(
+                "facto_policy_findings_total",
+                "rule" => finding.rule.clone(),
+                "action" => finding.action.code(),
+                "tenant" => tenant.to_string()
+            )
+            .increment(1);
+        }
+        findings
+    }
+}
+
+// ============================================================================
+// Alerts
+// ============================================================================
+
+/// Alerting findings on an accepted event, signed by the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyAlert {
+    pub facto_id: String,
+    pub agent_id: String,
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub action_type: String,
+    pub event_hash: String,
+    pub findings: Vec<PolicyFinding>,
+    pub alerted_at: i64,
+    #[serde(default)]
+    pub signer_public_key: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub signature: String,
+}
+
+impl PolicyAlert {
+    /// The bytes covered by the alert signature: the sorted-key JSON of the
+    /// alert with `signature` omitted
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let unsigned = Self {
+            signature: String::new(),
+            ..self.clone()
+        };
+        let value = serde_json::to_value(&unsigned).unwrap_or_default();
+        serde_json::to_vec(&value).unwrap_or_default()
+    }
+
+    pub fn sign(&mut self, signer: &ServerSigner) {
+        self.signer_public_key = signer.public_key_base64();
+        self.signature = signer.sign_base64(&self.signing_payload());
+    }
+}
+
+/// Fan-out sink raising alerts for accepted events with alerting findings.
+/// Alerts are handed on for signing and publishing.
+pub struct PolicyAlertSink {
+    sender: mpsc::Sender<PolicyAlert>,
+}
+
+impl PolicyAlertSink {
+    pub fn new(sender: mpsc::Sender<PolicyAlert>) -> Self {
+        Self { sender }
+    }
+}
+
+#[async_trait]
+impl FanoutSink for PolicyAlertSink {
+    fn name(&self) -> &'static str {
+        "policy_alerts"
+    }
+
+    async fn deliver(&self, accepted: &AcceptedEvent) -> anyhow::Result<()> {
+        let (event, envelope) = accepted.as_ref();
+        let findings: Vec<PolicyFinding> = envelope
+            .policy_findings
+            .iter()
+            .filter(|f| f.action == PolicyAction::Alert)
+            .cloned()
+            .collect();
+        if findings.is_empty() {
+            return Ok(());
+        }
+        let alert = PolicyAlert {
+            facto_id: event.facto_id.clone(),
+            agent_id: event.agent_id.clone(),
+            session_id: event.session_id.clone(),
+            tenant_id: envelope.tenant_id.clone(),
+            action_type: event.action_type.clone(),
+            event_hash: envelope.verification.event_hash.clone(),
+            findings,
+            alerted_at: now_nanos(),
+            signer_public_key: String::new(),
+            signature: String::new(),
+        };
+        self.sender
+            .send(alert)
+            .await
+            .map_err(|_| anyhow::anyhow!("policy alert publisher stopped"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_event;
+
+    fn rule(name: &str) -> PolicyRuleConfig {
+        PolicyRuleConfig {
+            name: name.to_string(),
+            action: PolicyAction::Annotate,
+            severity: Severity::Medium,
+            action_type: None,
+            agent_id: None,
+            max_temperature: None,
+            forbidden_tools: Vec::new(),
+            input_pattern: None,
+            output_pattern: None,
+            max_duration_ms: None,
+        }
+    }
+
+    #[test]
+    fn test_rules_fire_when_every_condition_holds() {
+        let policy = RulePolicy::new(vec![
+            PolicyRuleConfig {
+                max_temperature: Some(1.0),
+                ..rule("hot")
+            },
+            PolicyRuleConfig {
+                action: PolicyAction::Reject,
+                forbidden_tools: vec!["shell".to_string()],
+                output_pattern: Some("rm -rf".to_string()),
+                ..rule("destructive_shell")
+            },
+            PolicyRuleConfig {
+                action_type: Some("^other$".to_string()),
+                max_duration_ms: Some(0),
+                ..rule("slow_other")
+            },
+        ])
+        .unwrap();
+
+        let mut event = test_event();
+        assert!(policy.evaluate(&event).is_empty());
+
+        event.execution_meta.temperature = Some(1.5);
+        event.execution_meta.tool_calls = vec![serde_json::json!({"function": {"name": "shell"}})];
+        let findings = policy.evaluate(&event);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, "hot");
+
+        event.output_data = serde_json::json!({"ran": "rm -rf /tmp/x"});
+        let findings = policy.evaluate(&event);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[1].action, PolicyAction::Reject);
+        assert_eq!(findings[1].reasons.len(), 2);
+    }
+
+    #[test]
+    fn test_rules_need_a_condition() {
+        assert!(RulePolicy::new(vec![PolicyRuleConfig {
+            action_type: Some(".*".to_string()),
+            ..rule("empty")
+        }])
+        .is_err());
+    }
+}
@@ -0,0 +1,630 @@
+//! Wire types of the ingestion API, shared by the ingestion server and
+//! `facto-mock-server` so both answer with the same shapes and reasons.
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::collections::BTreeMap;
+
+// ============================================================================
+// Data Models
+// ============================================================================
+
+/// An agent event in the internal model. Events are read in any supported
+/// wire version (see `versions`) and upgraded into this shape.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "serde_json::Value")]
+pub struct FactoEvent {
+    /// Wire version the event was received in
+    pub event_version: u32,
+    pub facto_id: String,
+    pub agent_id: String,
+    pub session_id: String,
+    pub parent_facto_id: Option<String>,
+
+    pub action_type: String,
+    pub status: String,
+
+    pub input_data: serde_json::Value,
+    pub output_data: serde_json::Value,
+
+    pub execution_meta: ExecutionMeta,
+    pub proof: Proof,
+
+    pub started_at: i64,
+    pub completed_at: i64,
+
+    /// The JSON the event was received as, published in place of a
+    /// serialization of the parsed event. Cleared when the server changes
+    /// the event, by redacting or offloading its payloads.
+    #[serde(skip)]
+    pub raw: Option<Bytes>,
+}
+
+impl FactoEvent {
+    /// Read an event from a JSON body, keeping its bytes
+    pub fn from_json(body: &Bytes) -> serde_json::Result<Self> {
+        let mut event: FactoEvent = serde_json::from_slice(body)?;
+        let start = body.iter().position(|b| !b.is_ascii_whitespace());
+        let end = body.iter().rposition(|b| !b.is_ascii_whitespace());
+        if let (Some(start), Some(end)) = (start, end) {
+            event.raw = Some(body.slice(start..end + 1));
+        }
+        Ok(event)
+    }
+
+    /// The event as published: the bytes it was received as when the
+    /// server has them, its serialization otherwise
+    pub fn to_json(&self) -> Bytes {
+        match self.raw {
+            Some(ref raw) => raw.clone(),
+            None => serde_json::to_vec(self)
+                .expect("events always serialize")
+                .into(),
+        }
+    }
+
+    /// Size of the event as published
+    pub fn json_len(&self) -> Option<u64> {
+        self.raw.as_ref().map(|raw| raw.len() as u64)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionMeta {
+    pub model_id: Option<String>,
+    pub model_hash: Option<String>,
+    pub temperature: Option<f64>,
+    pub seed: Option<i64>,
+    pub max_tokens: Option<i32>,
+    pub tool_calls: Vec<serde_json::Value>,
+    pub sdk_version: String,
+    pub sdk_language: String,
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Proof {
+    pub signature: String,
+    pub public_key: String,
+    pub prev_hash: String,
+    pub event_hash: String,
+    /// Signature algorithm, one of `SIGNATURE_ALGORITHMS`; Ed25519 when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+    /// Canonical form the hash and signature cover, one of
+    /// `CANONICAL_VERSIONS`; version 1 when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canonical_version: Option<u32>,
+    /// DID URL of the signing key, e.g. `did:key:z6Mk...`; the key it
+    /// resolves to must be `public_key`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification_method: Option<String>,
+}
+
+/// Query parameters of the ingest endpoints
+#[derive(Debug, Default, Deserialize)]
+pub struct IngestQuery {
+    /// Hold the events until their session's chain is complete, instead of
+    /// publishing them as they arrive
+    #[serde(default)]
+    pub backfill: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchIngestRequest {
+    pub events: Vec<FactoEvent>,
+    pub batch_id: Option<String>,
+    /// Publish each session's events in array order without interleaving
+    /// events of other requests
+    #[serde(default)]
+    pub ordered: bool,
+    /// One signature over the whole batch, checked in place of the event
+    /// signatures of envelope-trusted agents
+    #[serde(default)]
+    pub envelope: Option<BatchEnvelope>,
+}
+
+/// A batch as sent, its events not yet parsed
+#[derive(Deserialize)]
+struct RawBatchIngestRequest<'a> {
+    #[serde(borrow)]
+    events: Vec<&'a RawValue>,
+    batch_id: Option<String>,
+    #[serde(default)]
+    ordered: bool,
+    #[serde(default)]
+    envelope: Option<BatchEnvelope>,
+}
+
+impl BatchIngestRequest {
+    /// Read a batch from a JSON body, each event keeping its own bytes
+    pub fn from_json(body: &Bytes) -> serde_json::Result<Self> {
+        let batch: RawBatchIngestRequest = serde_json::from_slice(body)?;
+        let events = batch
+            .events
+            .into_iter()
+            .map(|raw| {
+                let mut event: FactoEvent = serde_json::from_str(raw.get())?;
+                event.raw = Some(body.slice_ref(raw.get().as_bytes()));
+                Ok(event)
+            })
+            .collect::<serde_json::Result<_>>()?;
+        Ok(Self {
+            events,
+            batch_id: batch.batch_id,
+            ordered: batch.ordered,
+            envelope: batch.envelope,
+        })
+    }
+}
+
+/// A batch signed once: the signature covers the batch hash, and the batch
+/// hash covers each event's `proof.event_hash`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchEnvelope {
+    /// Hex SHA3-256 of the events' `proof.event_hash` values concatenated in
+    /// array order
+    pub batch_hash: String,
+    pub public_key: String,
+    /// Signature over the UTF-8 bytes of `batch_hash`
+    pub signature: String,
+    /// Signature algorithm, one of `SIGNATURE_ALGORITHMS`; Ed25519 when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+}
+
+/// Action type of a key rotation event. The event is signed with the key
+/// being rotated out and its `input_data` is a [`KeyRotation`]; once it is
+/// accepted, the server trusts the new key for the agent.
+pub const KEY_ROTATION_ACTION: &str = "key_rotation";
+
+/// The new key attested to by a key rotation event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyRotation {
+    pub new_public_key: String,
+    /// When the new key takes over, in nanoseconds; defaults to when the
+    /// rotation is accepted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<i64>,
+    /// How long the signing key stays valid after the new key takes over;
+    /// defaults to, and is capped at, the server's KEY_ROTATION_OVERLAP_SECS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlap_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchIngestResponse {
+    pub accepted_count: usize,
+    pub rejected_count: usize,
+    pub rejected: Vec<RejectedEvent>,
+    /// facto_ids that were already accepted earlier (counted in `accepted_count`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<String>,
+    /// Accepted events held in the local spool until NATS is reachable
+    #[serde(skip_serializing_if = "is_zero")]
+    pub spooled_count: usize,
+    /// Accepted events held until their session's chain is complete
+    #[serde(skip_serializing_if = "is_zero")]
+    pub held_count: usize,
+    /// Present when the batch was delivered under an ordering guarantee
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ordering: Option<OrderingGuarantee>,
+    /// Receipts of the events this request accepted, duplicates excepted
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub receipts: Vec<Receipt>,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+#[derive(Debug, Serialize)]
+pub struct RejectedEvent {
+    pub facto_id: String,
+    /// The error's message, kept for clients that predate `error`
+    pub reason: String,
+    pub error: EventError,
+}
+
+impl RejectedEvent {
+    pub fn new(facto_id: String, error: EventError) -> Self {
+        Self {
+            facto_id,
+            reason: error.message.clone(),
+            error,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SingleIngestResponse {
+    pub accepted: bool,
+    pub facto_id: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub duplicate: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub spooled: bool,
+    /// Held until the session's chain is complete
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub held: bool,
+    /// The error's message, kept for clients that predate `error`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<EventError>,
+    /// The server's signed acknowledgement of the accepted event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<Receipt>,
+}
+
+impl SingleIngestResponse {
+    pub fn rejected(facto_id: String, error: EventError) -> Self {
+        Self {
+            accepted: false,
+            facto_id,
+            duplicate: false,
+            spooled: false,
+            held: false,
+            reason: Some(error.message.clone()),
+            error: Some(error),
+            receipt: None,
+        }
+    }
+}
+
+/// A server-signed statement that an event was accepted. `sequence` is the
+/// event's sequence in `stream` once the broker has stored it; receipts of
+/// spooled or held events are issued without one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub facto_id: String,
+    pub event_hash: String,
+    /// When the server received the event, nanoseconds since the epoch
+    pub received_at: i64,
+    pub stream: Option<String>,
+    pub sequence: Option<u64>,
+    pub signer_id: String,
+    pub signer_public_key: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub signature: String,
+}
+
+impl Receipt {
+    /// The bytes covered by the receipt signature: the sorted-key JSON of the
+    /// receipt with `signature` omitted
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let unsigned = Self {
+            signature: String::new(),
+            ..self.clone()
+        };
+        let value = serde_json::to_value(&unsigned).unwrap_or_default();
+        serde_json::to_vec(&value).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    pub status: String,
+    pub version: String,
+}
+
+/// What this server supports, so SDKs can adapt at startup
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    pub server_version: &'static str,
+    /// Event wire versions, each with its own canonical form
+    pub event_versions: &'static [u32],
+    pub signature_algorithms: &'static [&'static str],
+    pub hash_algorithm: &'static str,
+    pub canonical_versions: &'static [u32],
+    /// When canonical form version 1 stops being accepted, in Unix seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canonical_v1_until: Option<i64>,
+    /// DID methods a proof's `verification_method` may use
+    pub did_methods: &'static [&'static str],
+    /// Largest accepted request body
+    pub max_body_bytes: usize,
+    /// Largest accepted batch; unlimited if absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_batch_events: Option<usize>,
+    /// Largest accepted event, measured as its JSON encoding
+    pub max_event_bytes: u64,
+    /// Most tool calls per event; unlimited if absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tool_calls: Option<usize>,
+    /// Payloads larger than this are moved to blob storage and published as
+    /// references; absent when offloading is off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offload_threshold_bytes: Option<u64>,
+    pub rate_limit_per_agent: u32,
+    /// Window for `completed_at`; absent when replay protection is off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replay_window_secs: Option<u64>,
+    pub replay_max_skew_secs: u64,
+    /// Accepted ingestion credentials; empty when ingestion is open
+    pub auth_methods: Vec<&'static str>,
+    /// Request body encodings of the ingest endpoints, which answer in kind
+    pub content_types: &'static [&'static str],
+    /// `Content-Encoding`s accepted on ingest request bodies
+    pub content_encodings: &'static [&'static str],
+    pub endpoints: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadyResponse {
+    pub ready: bool,
+    pub nats_connected: bool,
+    /// Set when events are published to Kafka
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kafka_connected: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spool_depth: Option<u64>,
+    /// Health of each dependency, by name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub checks: BTreeMap<String, DependencyHealth>,
+}
+
+/// Outcome of one dependency's health check
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyHealth {
+    pub healthy: bool,
+    /// How readiness depends on the check: `required`, `delivery` (one
+    /// delivery path must be healthy) or `informational`
+    pub requirement: &'static str,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// What the check observed, such as stream usage or spool depth
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub error: String,
+    /// Machine-readable code, for errors clients are expected to handle
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+}
+
+/// Stable reason an event or request was refused, for clients to branch on
+/// instead of parsing messages. Also the `reason` label of
+/// `facto_ingest_rejected_total`. Codes may be added but are never renamed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    // The event itself
+    MissingField,
+    InvalidEvent,
+    HashMismatch,
+    PublicKeyInvalid,
+    SignatureInvalid,
+    KeyNotRegistered,
+    KeyRevoked,
+    KeyNotYetValid,
+    KeyExpired,
+    VerificationMethodInvalid,
+    UnsupportedAlgorithm,
+    CanonicalVersionRetired,
+    SchemaViolation,
+    PolicyViolation,
+    ModelNotApproved,
+    // Replays and conflicts
+    EventStale,
+    EventFromFuture,
+    EventReplayed,
+    FactoIdConflict,
+    // Admission
+    AgentNotAuthorized,
+    AgentPaused,
+    SessionFrozen,
+    /// The event's session is routed to another replica
+    WrongReplica,
+    RateLimited,
+    AgentByteQuotaExceeded,
+    TenantRateLimited,
+    TenantByteQuotaExceeded,
+    // Request limits and encoding
+    PayloadTooLarge,
+    EventTooLarge,
+    BatchTooLarge,
+    TooManyToolCalls,
+    UnsupportedContentEncoding,
+    InvalidCompressedBody,
+    // Server side; the event may be retried
+    SpoolFull,
+    BackfillFull,
+    QueueFailed,
+    ServiceNotReady,
+    BlobStoreFailed,
+    /// Checking the event took longer than the server allows
+    Timeout,
+}
+
+impl ErrorCode {
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::MissingField,
+        ErrorCode::InvalidEvent,
+        ErrorCode::HashMismatch,
+        ErrorCode::PublicKeyInvalid,
+        ErrorCode::SignatureInvalid,
+        ErrorCode::KeyNotRegistered,
+        ErrorCode::KeyRevoked,
+        ErrorCode::KeyNotYetValid,
+        ErrorCode::KeyExpired,
+        ErrorCode::VerificationMethodInvalid,
+        ErrorCode::UnsupportedAlgorithm,
+        ErrorCode::CanonicalVersionRetired,
+        ErrorCode::SchemaViolation,
+        ErrorCode::PolicyViolation,
+        ErrorCode::ModelNotApproved,
+        ErrorCode::EventStale,
+        ErrorCode::EventFromFuture,
+        ErrorCode::EventReplayed,
+        ErrorCode::FactoIdConflict,
+        ErrorCode::AgentNotAuthorized,
+        ErrorCode::AgentPaused,
+        ErrorCode::SessionFrozen,
+        ErrorCode::WrongReplica,
+        ErrorCode::RateLimited,
+        ErrorCode::AgentByteQuotaExceeded,
+        ErrorCode::TenantRateLimited,
+        ErrorCode::TenantByteQuotaExceeded,
+        ErrorCode::PayloadTooLarge,
+        ErrorCode::EventTooLarge,
+        ErrorCode::BatchTooLarge,
+        ErrorCode::TooManyToolCalls,
+        ErrorCode::UnsupportedContentEncoding,
+        ErrorCode::InvalidCompressedBody,
+        ErrorCode::SpoolFull,
+        ErrorCode::BackfillFull,
+        ErrorCode::QueueFailed,
+        ErrorCode::ServiceNotReady,
+        ErrorCode::BlobStoreFailed,
+        ErrorCode::Timeout,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::MissingField => "MISSING_FIELD",
+            ErrorCode::InvalidEvent => "INVALID_EVENT",
+            ErrorCode::HashMismatch => "HASH_MISMATCH",
+            ErrorCode::PublicKeyInvalid => "PUBLIC_KEY_INVALID",
+            ErrorCode::SignatureInvalid => "SIGNATURE_INVALID",
+            ErrorCode::KeyNotRegistered => "KEY_NOT_REGISTERED",
+            ErrorCode::KeyRevoked => "KEY_REVOKED",
+            ErrorCode::KeyNotYetValid => "KEY_NOT_YET_VALID",
+            ErrorCode::KeyExpired => "KEY_EXPIRED",
+            ErrorCode::VerificationMethodInvalid => "VERIFICATION_METHOD_INVALID",
+            ErrorCode::UnsupportedAlgorithm => "UNSUPPORTED_ALGORITHM",
+            ErrorCode::CanonicalVersionRetired => "CANONICAL_VERSION_RETIRED",
+            ErrorCode::SchemaViolation => "SCHEMA_VIOLATION",
+            ErrorCode::PolicyViolation => "POLICY_VIOLATION",
+            ErrorCode::ModelNotApproved => "MODEL_NOT_APPROVED",
+            ErrorCode::EventStale => "EVENT_STALE",
+            ErrorCode::EventFromFuture => "EVENT_FROM_FUTURE",
+            ErrorCode::EventReplayed => "EVENT_REPLAYED",
+            ErrorCode::FactoIdConflict => "FACTO_ID_CONFLICT",
+            ErrorCode::AgentNotAuthorized => "AGENT_NOT_AUTHORIZED",
+            ErrorCode::AgentPaused => "AGENT_PAUSED",
+            ErrorCode::SessionFrozen => "SESSION_FROZEN",
+            ErrorCode::WrongReplica => "WRONG_REPLICA",
+            ErrorCode::RateLimited => "RATE_LIMITED",
+            ErrorCode::AgentByteQuotaExceeded => "AGENT_BYTE_QUOTA_EXCEEDED",
+            ErrorCode::TenantRateLimited => "TENANT_RATE_LIMITED",
+            ErrorCode::TenantByteQuotaExceeded => "TENANT_BYTE_QUOTA_EXCEEDED",
+            ErrorCode::PayloadTooLarge => "PAYLOAD_TOO_LARGE",
+            ErrorCode::EventTooLarge => "EVENT_TOO_LARGE",
+            ErrorCode::BatchTooLarge => "BATCH_TOO_LARGE",
+            ErrorCode::TooManyToolCalls => "TOO_MANY_TOOL_CALLS",
+            ErrorCode::UnsupportedContentEncoding => "UNSUPPORTED_CONTENT_ENCODING",
+            ErrorCode::InvalidCompressedBody => "INVALID_COMPRESSED_BODY",
+            ErrorCode::SpoolFull => "SPOOL_FULL",
+            ErrorCode::BackfillFull => "BACKFILL_FULL",
+            ErrorCode::QueueFailed => "QUEUE_FAILED",
+            ErrorCode::ServiceNotReady => "SERVICE_NOT_READY",
+            ErrorCode::BlobStoreFailed => "BLOB_STORE_FAILED",
+            ErrorCode::Timeout => "TIMEOUT",
+        }
+    }
+}
+
+/// Why an event was refused: a stable code, a message for people, and for
+/// some codes structured details such as the computed hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl EventError {
+    pub fn new(code: ErrorCode, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+// ============================================================================
+// Rejection Reasons
+// ============================================================================
+
+pub const RATE_LIMITED: &str = "Rate limit exceeded";
+pub const AGENT_BYTE_QUOTA_EXCEEDED: &str = "Agent byte quota exceeded";
+pub const AGENT_PAUSED: &str = "Ingestion is paused for this agent";
+pub const AGENT_NOT_AUTHORIZED: &str = "Credentials are bound to another agent";
+pub const TENANT_RATE_LIMITED: &str = "Tenant rate limit exceeded";
+pub const TENANT_BYTE_QUOTA_EXCEEDED: &str = "Tenant byte quota exceeded";
+pub const SESSION_FROZEN: &str = "Session is frozen";
+pub const WRONG_REPLICA: &str = "Session is routed to another replica";
+pub const FACTO_ID_CONFLICT: &str = "facto_id already accepted for a different event";
+pub const SPOOL_FULL: &str = "Spool is full";
+pub const BACKFILL_FULL: &str = "Backfill holding area is full";
+pub const QUEUE_FAILED: &str = "Failed to queue event";
+pub const SERVICE_NOT_READY: &str = "Service not ready";
+pub const BLOB_STORE_FAILED: &str = "Failed to store payload blob";
+pub const VALIDATION_TIMEOUT: &str = "Event validation timed out";
+
+/// Why an event was refused as a possible replay
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ReplayRejection {
+    #[error("Event completed_at is outside the freshness window")]
+    Stale,
+    #[error("Event completed_at is ahead of server time")]
+    FromFuture,
+    #[error("Event was already accepted (replay)")]
+    Replayed,
+}
+
+impl From<ReplayRejection> for EventError {
+    fn from(rejection: ReplayRejection) -> Self {
+        let code = match rejection {
+            ReplayRejection::Stale => ErrorCode::EventStale,
+            ReplayRejection::FromFuture => ErrorCode::EventFromFuture,
+            ReplayRejection::Replayed => ErrorCode::EventReplayed,
+        };
+        EventError::new(code, rejection)
+    }
+}
+
+// ============================================================================
+// Ordering
+// ============================================================================
+
+/// Scope of the ordering guarantee given to ordered batches
+pub const ORDERING_SCOPE: &str = "session";
+
+/// The guarantee an ordered batch was delivered under
+pub const ORDERING_GUARANTEE: &str = "Accepted events of each session in this batch were \
+     published in array order, with no events of the same session from other requests in \
+     between. Rejected and duplicate events are skipped without breaking the order of the rest.";
+
+/// Returned with an ordered batch so clients know what they can rely on
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderingGuarantee {
+    pub scope: &'static str,
+    pub guarantee: &'static str,
+}
+
+impl OrderingGuarantee {
+    pub fn session() -> Self {
+        Self {
+            scope: ORDERING_SCOPE,
+            guarantee: ORDERING_GUARANTEE,
+        }
+    }
+}
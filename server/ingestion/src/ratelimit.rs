@@ -0,0 +1,269 @@
+use axum::async_trait;
+use dashmap::DashMap;
+use nonzero_ext::nonzero;
+use serde::Serialize;
+use std::{num::NonZeroU32, sync::Arc, time::Duration};
+
+use crate::shared::{self, KvBucket, Swap};
+use crate::verification::now_nanos;
+
+/// Bucket holding each agent's theoretical arrival time
+pub const RATE_LIMIT_BUCKET: &str = "facto_rate_limits";
+
+/// Per-agent request rate limits.
+///
+/// Both stores implement the generic cell rate algorithm: each agent's
+/// theoretical arrival time advances by one emission interval per request,
+/// and a request is refused when that would put it more than a full burst
+/// (one second's worth of requests) ahead of now. The rate is passed with
+/// every check, so limits changed through the admin API apply to the next
+/// request without resetting the agent's state.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Count a request for `key`, returning whether it is within `rate`
+    async fn check(&self, key: &str, rate: NonZeroU32) -> bool;
+
+    /// The theoretical arrival time of `key`, if it has made requests
+    async fn arrival(&self, key: &str) -> Option<i64>;
+}
+
+/// The limiter state of one agent, for the admin API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LimiterState {
+    pub rate_limit_per_sec: u32,
+    /// Requests that would be admitted right now
+    pub remaining: u32,
+    /// Milliseconds until the full burst is available again
+    pub reset_in_ms: u64,
+}
+
+impl LimiterState {
+    pub fn new(arrival: Option<i64>, now: i64, rate: NonZeroU32) -> Self {
+        let interval = interval(rate);
+        let ahead = arrival.map_or(0, |a| (a - now).max(0));
+        let used = (ahead + interval - 1) / interval;
+        Self {
+            rate_limit_per_sec: rate.get(),
+            remaining: rate.get().saturating_sub(used as u32),
+            reset_in_ms: (ahead / 1_000_000) as u64,
+        }
+    }
+}
+
+/// The default per-agent limit for `RATE_LIMIT_PER_AGENT`, where 0 means
+/// practically unlimited
+pub fn default_agent_rate(rate_limit_per_agent: u32) -> NonZeroU32 {
+    NonZeroU32::new(rate_limit_per_agent).unwrap_or(nonzero!(10000u32))
+}
+
+/// Nanoseconds between requests at the sustained rate
+fn interval(rate: NonZeroU32) -> i64 {
+    1_000_000_000 / rate.get() as i64
+}
+
+/// The next theoretical arrival time, or `None` if the request is refused
+fn next_arrival(current: Option<i64>, now: i64, rate: NonZeroU32) -> Option<i64> {
+    let interval = interval(rate);
+    let arrival = current.unwrap_or(now).max(now) + interval;
+    (arrival - now <= interval * rate.get() as i64).then_some(arrival)
+}
+
+/// Rate limits of the agents seen by this replica
+#[derive(Default)]
+pub struct AgentRateLimiter {
+    arrivals: DashMap<String, i64>,
+}
+
+impl AgentRateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for AgentRateLimiter {
+    async fn check(&self, key: &str, rate: NonZeroU32) -> bool {
+        let now = now_nanos();
+        let mut arrival = self.arrivals.entry(key.to_string()).or_insert(now);
+        match next_arrival(Some(*arrival), now, rate) {
+            Some(next) => {
+                *arrival = next;
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn arrival(&self, key: &str) -> Option<i64> {
+        self.arrivals.get(key).map(|a| *a)
+    }
+}
+
+/// Rate limits shared by all replicas. Replica clocks should be kept in
+/// sync.
+pub struct KvRateLimiter {
+    bucket: Arc<KvBucket>,
+    local: AgentRateLimiter,
+}
+
+impl KvRateLimiter {
+    pub fn new() -> Self {
+        Self {
+            // Arrival times are never more than a second ahead of the last
+            // write, so entries expire once they can no longer limit anyone
+            bucket: Arc::new(KvBucket::new(RATE_LIMIT_BUCKET, Duration::from_secs(2))),
+            local: AgentRateLimiter::new(),
+        }
+    }
+
+    pub fn bucket(&self) -> Arc<KvBucket> {
+        self.bucket.clone()
+    }
+}
+
+fn parse_arrival(value: Option<&[u8]>) -> Option<i64> {
+    value
+        .and_then(|v| std::str::from_utf8(v).ok())
+        .and_then(|v| v.parse().ok())
+}
+
+#[async_trait]
+impl RateLimitStore for KvRateLimiter {
+    async fn check(&self, key: &str, rate: NonZeroU32) -> bool {
+        let now = now_nanos();
+        let outcome = self
+            .bucket
+            .swap(key, |current| {
+                match next_arrival(parse_arrival(current), now, rate) {
+                    Some(arrival) => Swap::Write(arrival.to_string().into_bytes(), true),
+                    None => Swap::Keep(false),
+                }
+            })
+            .await;
+        match outcome {
+            Ok(allowed) => allowed,
+            Err(e) => {
+                shared::fallback(e);
+                self.local.check(key, rate).await
+            }
+        }
+    }
+
+    async fn arrival(&self, key: &str) -> Option<i64> {
+        match self.bucket.get(key).await {
+            Ok(value) => parse_arrival(value.as_deref()),
+            Err(e) => {
+                shared::fallback(e);
+                self.local.arrival(key).await
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Byte Quotas
+// ============================================================================
+
+/// Event bytes accepted for an agent in the current quota window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ByteUsage {
+    pub window_start: i64,
+    pub bytes: u64,
+}
+
+/// Per-agent byte quotas over fixed windows of `AGENT_BYTE_QUOTA_WINDOW_SECS`,
+/// so a few large events cannot pass where the request rate allows them.
+/// Usage is tracked by each replica.
+pub struct ByteQuotas {
+    window: Duration,
+    usage: DashMap<String, ByteUsage>,
+}
+
+impl ByteQuotas {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            usage: DashMap::new(),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Count `bytes` for `key` if they fit in `quota` for the current window
+    pub fn admit(&self, key: &str, bytes: u64, quota: u64, now: i64) -> bool {
+        let mut usage = self.usage.entry(key.to_string()).or_default();
+        if now - usage.window_start >= self.window.as_nanos() as i64 {
+            *usage = ByteUsage {
+                window_start: now,
+                bytes: 0,
+            };
+        }
+        if usage.bytes.saturating_add(bytes) > quota {
+            return false;
+        }
+        usage.bytes += bytes;
+        true
+    }
+
+    /// Bytes counted for `key` in the current window
+    pub fn usage(&self, key: &str, now: i64) -> ByteUsage {
+        self.usage
+            .get(key)
+            .map(|u| *u)
+            .filter(|u| now - u.window_start < self.window.as_nanos() as i64)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_sustained_rate() {
+        let rate = NonZeroU32::new(10).unwrap();
+        let interval = interval(rate);
+        let now = 1_000;
+
+        let mut arrival = None;
+        for _ in 0..10 {
+            arrival = next_arrival(arrival, now, rate);
+            assert!(arrival.is_some());
+        }
+        assert_eq!(next_arrival(arrival, now, rate), None);
+        assert_eq!(LimiterState::new(arrival, now, rate).remaining, 0);
+
+        // One interval later there is room for one more request
+        assert!(next_arrival(arrival, now + interval, rate).is_some());
+        assert_eq!(next_arrival(arrival, now + interval - 1, rate), None);
+        assert_eq!(
+            LimiterState::new(arrival, now + interval, rate).remaining,
+            1
+        );
+
+        // A lower limit applies to the state built up under the higher one
+        assert_eq!(
+            next_arrival(arrival, now + interval, NonZeroU32::new(5).unwrap()),
+            None
+        );
+        assert_eq!(LimiterState::new(None, now, rate).remaining, 10);
+    }
+
+    #[test]
+    fn test_byte_quota_window() {
+        let quotas = ByteQuotas::new(Duration::from_secs(60));
+        let minute = 60_000_000_000;
+
+        assert!(quotas.admit("agent-1", 600, 1000, 0));
+        assert!(!quotas.admit("agent-1", 600, 1000, 1));
+        assert!(quotas.admit("agent-1", 400, 1000, 2));
+        assert!(quotas.admit("agent-2", 1000, 1000, 2));
+        assert_eq!(quotas.usage("agent-1", 3).bytes, 1000);
+
+        // A new window starts with the quota unused
+        assert_eq!(quotas.usage("agent-1", minute), ByteUsage::default());
+        assert!(quotas.admit("agent-1", 1000, 1000, minute));
+    }
+}
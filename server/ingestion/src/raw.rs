@@ -0,0 +1,188 @@
+//! Direct NATS ingestion for agents inside the cluster.
+//!
+//! Events published to `facto.raw.{agent_id}`, or
+//! `facto.raw.tenants.{tenant_id}.{agent_id}` for a tenant, go through the
+//! same pipeline as `POST /v1/ingest`: the subject binds the agent and
+//! tenant the way a client certificate and tenant key would over HTTP.
+//! Accepted events are published to `facto.events.>` like any other;
+//! rejected ones are dead-lettered when FACTO_REJECTS is enabled. Bodies
+//! are JSON unless a `Content-Type` header names CBOR or MessagePack.
+//!
+//! Publishers that want the outcome send a request: the reply is the
+//! `/v1/ingest` response in the request's encoding. Plain publishes are
+//! at most once, so in-cluster agents that cannot lose events should use
+//! requests and retry on timeouts.
+
+use crate::encoding::Encoding;
+use crate::protocol::{ErrorCode, ErrorResponse};
+use axum::Extension;
+use futures::StreamExt;
+use metrics::counter;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::auth::{Principal, Scope};
+use crate::limits::LimitError;
+use crate::tenants::validate_tenant_id;
+use crate::{ingest_single, reject_origin, AppState, FactoEvent};
+
+/// Subjects raw events are published on
+pub const RAW_SUBJECT: &str = "facto.raw.>";
+
+const RAW_SUBJECT_PREFIX: &str = "facto.raw.";
+
+/// Queue group of the raw subscription, so each event is ingested by one
+/// replica
+pub const RAW_QUEUE_GROUP: &str = "facto-ingestion";
+
+/// Settings of the raw ingest worker
+#[derive(Debug, Clone, Copy)]
+pub struct RawIngest {
+    /// Events ingested at once. With 1, events are ingested in the order
+    /// they arrive, which keeps each publisher's session chains in order.
+    pub concurrency: usize,
+}
+
+/// The tenant and agent a raw subject binds, `None` when it names neither
+fn subject_origin(subject: &str) -> Option<(Option<String>, String)> {
+    let rest = subject.strip_prefix(RAW_SUBJECT_PREFIX)?;
+    match rest
+        .strip_prefix("tenants.")
+        .and_then(|scoped| scoped.split_once('.'))
+    {
+        Some((tenant_id, agent_id)) if validate_tenant_id(tenant_id).is_ok() => {
+            Some((Some(tenant_id.to_string()), agent_id.to_string()))
+        }
+        Some(_) => None,
+        None if rest.is_empty() => None,
+        None => Some((None, rest.to_string())),
+    }
+}
+
+/// Ingest events from `facto.raw.>` until the subscription ends. The
+/// worker is aborted when the connection is replaced.
+pub async fn run(state: Arc<AppState>, settings: RawIngest, client: async_nats::Client) {
+    let subscriber = match client
+        .queue_subscribe(RAW_SUBJECT, RAW_QUEUE_GROUP.to_string())
+        .await
+    {
+        Ok(subscriber) => subscriber,
+        Err(e) => {
+            warn!("Failed to subscribe to {}: {}", RAW_SUBJECT, e);
+            return;
+        }
+    };
+    info!(
+        "Ingesting events published to {} ({} at once)",
+        RAW_SUBJECT, settings.concurrency
+    );
+
+    subscriber
+        .for_each_concurrent(settings.concurrency.max(1), |message| {
+            let (state, client) = (state.clone(), client.clone());
+            async move {
+                let (outcome, reply) = ingest_message(&state, &message).await;
+                counter!("facto_raw_ingest_messages_total", "outcome" => outcome).increment(1);
+                if let Some(reply_subject) = message.reply {
+                    if let Err(e) = client.publish(reply_subject, reply.into()).await {
+                        warn!("Failed to reply to raw event: {}", e);
+                    }
+                }
+            }
+        })
+        .await;
+}
+
+/// Ingest one raw event, returning the outcome label and the reply body
+async fn ingest_message(
+    state: &Arc<AppState>,
+    message: &async_nats::Message,
+) -> (&'static str, Vec<u8>) {
+    let encoding = message
+        .headers
+        .as_ref()
+        .and_then(|h| h.get("Content-Type"))
+        .and_then(|v| Encoding::from_media_type(v.as_str()))
+        .unwrap_or(Encoding::Json);
+    let invalid = |code: Option<ErrorCode>, error: String| {
+        (
+            "invalid",
+            reply_body(encoding, &ErrorResponse { error, code }),
+        )
+    };
+
+    let Some((tenant_id, agent_id)) = subject_origin(message.subject.as_str()) else {
+        return invalid(None, format!("Invalid raw subject {}", message.subject));
+    };
+    let limit = state.limits.max_body_bytes;
+    if message.payload.len() > limit {
+        let e = LimitError::BodyTooLarge { limit };
+        return invalid(Some(e.code()), e.to_string());
+    }
+    let event = match encoding.decode::<FactoEvent>(&message.payload) {
+        Ok(event) => event,
+        Err(e) => return invalid(None, format!("Invalid event: {}", e)),
+    };
+    if let Err(e) = state.limits.check_event(&event) {
+        return invalid(Some(e.code()), e.to_string());
+    }
+
+    let principal = Some(Extension(Principal {
+        name: "nats".to_string(),
+        scopes: vec![Scope::Ingest],
+        tenant_id,
+        agent_id: Some(agent_id),
+        sandbox: false,
+    }));
+    let origin = reject_origin(&principal);
+    let received = state.rejects.is_some().then(|| event.clone());
+    let (status, axum::Json(response)) = ingest_single(
+        state.clone(),
+        principal,
+        None,
+        BTreeMap::new(),
+        event,
+        false,
+        None,
+    )
+    .await;
+    if let (Some(rejects), Some(event), Some(error)) = (&state.rejects, received, &response.error) {
+        rejects.record(origin.0.as_deref(), origin.1, event, error);
+    }
+
+    let outcome = match status {
+        _ if response.accepted => "accepted",
+        status if status.is_client_error() => "rejected",
+        _ => "failed",
+    };
+    (outcome, reply_body(encoding, &response))
+}
+
+fn reply_body<T: Serialize>(encoding: Encoding, body: &T) -> Vec<u8> {
+    match serde_json::to_value(body) {
+        Ok(value) => encoding.encode(&value),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subject_binds_tenant_and_agent() {
+        assert_eq!(
+            subject_origin("facto.raw.agent-1"),
+            Some((None, "agent-1".to_string()))
+        );
+        assert_eq!(
+            subject_origin("facto.raw.tenants.acme.agent.with.dots"),
+            Some((Some("acme".to_string()), "agent.with.dots".to_string()))
+        );
+        assert_eq!(subject_origin("facto.raw.tenants.Not_Valid!.agent"), None);
+        assert_eq!(subject_origin("facto.raw."), None);
+        assert_eq!(subject_origin("facto.events.agent-1"), None);
+    }
+}
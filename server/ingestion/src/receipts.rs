@@ -0,0 +1,208 @@
+use crate::protocol::Receipt;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use crate::admin::error_response;
+use crate::auth::Principal;
+use crate::sandbox::{sandbox_scope, SANDBOX_STREAM};
+use crate::scoped_id;
+use crate::tenants::EVENT_STREAM;
+use crate::verification::{ServerEnvelope, ServerSigner};
+use crate::AppState;
+
+/// Default for `RECEIPT_RETENTION`
+pub const DEFAULT_RECEIPT_RETENTION: usize = 100_000;
+
+/// Where the server's receipt signing key is published
+pub const SERVER_KEY_PATH: &str = "/.well-known/facto-server-key.json";
+
+/// The receipt of an accepted event, signed by the server
+pub fn sign_receipt(
+    facto_id: &str,
+    envelope: &ServerEnvelope,
+    sequence: Option<u64>,
+    signer: &ServerSigner,
+) -> Receipt {
+    let stream = match envelope.sandbox {
+        true => SANDBOX_STREAM,
+        false => EVENT_STREAM,
+    };
+    let mut receipt = Receipt {
+        facto_id: facto_id.to_string(),
+        event_hash: envelope.verification.event_hash.clone(),
+        received_at: envelope.received_at,
+        stream: sequence.map(|_| stream.to_string()),
+        sequence,
+        signer_id: signer.instance_id().to_string(),
+        signer_public_key: signer.public_key_base64(),
+        signature: String::new(),
+    };
+    receipt.signature = signer.sign_base64(&receipt.signing_payload());
+    receipt
+}
+
+/// Receipts of the last `retention` accepted events, by the facto_id they
+/// were accepted under, scoped like deduplication. Older receipts stay
+/// verifiable from the copies clients were given.
+pub struct Receipts {
+    receipts: DashMap<String, Receipt>,
+    order: Mutex<VecDeque<String>>,
+    retention: usize,
+}
+
+impl Receipts {
+    pub fn new(retention: usize) -> Self {
+        Self {
+            receipts: DashMap::new(),
+            order: Mutex::new(VecDeque::new()),
+            retention: retention.max(1),
+        }
+    }
+
+    /// Sign and keep the receipt of an event accepted under `key`
+    pub fn issue(
+        &self,
+        key: String,
+        facto_id: &str,
+        envelope: &ServerEnvelope,
+        sequence: Option<u64>,
+        signer: &ServerSigner,
+    ) -> Receipt {
+        let receipt = sign_receipt(facto_id, envelope, sequence, signer);
+        self.keep(key, receipt.clone());
+        receipt
+    }
+
+    /// Keep a signed receipt, replacing any earlier one of `key`
+    pub fn keep(&self, key: String, receipt: Receipt) {
+        if self.receipts.insert(key.clone(), receipt).is_none() {
+            let mut order = self.order.lock().unwrap();
+            order.push_back(key);
+            while order.len() > self.retention {
+                if let Some(oldest) = order.pop_front() {
+                    self.receipts.remove(&oldest);
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Receipt> {
+        self.receipts.get(key).map(|receipt| receipt.clone())
+    }
+}
+
+/// Key the receipt of an accepted event is kept under: its facto_id,
+/// scoped like deduplication
+pub fn receipt_key(envelope: &ServerEnvelope, facto_id: &str) -> String {
+    let scope = match envelope.sandbox {
+        true => Some(sandbox_scope(envelope.tenant_id.as_deref())),
+        false => envelope.tenant_id.clone(),
+    };
+    scoped_id(scope.as_deref(), facto_id)
+}
+
+/// `GET /v1/receipts/{facto_id}`
+pub async fn get_receipt_handler(
+    State(state): State<Arc<AppState>>,
+    principal: Option<Extension<Principal>>,
+    Path(facto_id): Path<String>,
+) -> Response {
+    let scope = principal.map(|Extension(p)| match p.sandbox {
+        true => Some(sandbox_scope(p.tenant_id.as_deref())),
+        false => p.tenant_id,
+    });
+    match state
+        .receipts
+        .get(&scoped_id(scope.flatten().as_deref(), &facto_id))
+    {
+        Some(receipt) => (StatusCode::OK, Json(receipt)).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, "No receipt for this facto_id"),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServerKey {
+    pub signer_id: String,
+    pub algorithm: &'static str,
+    pub public_key: String,
+}
+
+/// The key receipts, checkpoints and verification assertions are signed with
+pub async fn server_key_handler(State(state): State<Arc<AppState>>) -> Json<ServerKey> {
+    let signer = state.verifier.signer();
+    Json(ServerKey {
+        signer_id: signer.instance_id().to_string(),
+        algorithm: "ed25519",
+        public_key: signer.public_key_base64(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto;
+    use crate::verification::{TrustBasis, VerificationAssertion};
+    use ed25519_dalek::{SigningKey, Verifier as _};
+
+    fn envelope() -> ServerEnvelope {
+        ServerEnvelope {
+            received_at: 42,
+            verification: VerificationAssertion {
+                facto_id: "f1".to_string(),
+                event_hash: "ab".repeat(32),
+                algorithm: "ed25519".to_string(),
+                signer_public_key: String::new(),
+                trust_basis: TrustBasis::EmbeddedKey,
+                verifier_id: "test".to_string(),
+                verifier_public_key: String::new(),
+                verified_at: 0,
+                signature: String::new(),
+            },
+            verification_status: Default::default(),
+            tenant_id: None,
+            sandbox: false,
+            schema_violations: Vec::new(),
+            policy_findings: Vec::new(),
+            model_attestation: None,
+            headers: Default::default(),
+            redaction: None,
+            data_integrity: None,
+            backfill: None,
+            translation: None,
+        }
+    }
+
+    #[test]
+    fn test_receipts_are_signed_and_retained() {
+        let signer = ServerSigner::new("test".to_string(), SigningKey::from_bytes(&[3u8; 32]));
+        let receipts = Receipts::new(2);
+        let receipt = receipts.issue("f1".to_string(), "f1", &envelope(), Some(7), &signer);
+        assert_eq!(receipt.stream.as_deref(), Some(EVENT_STREAM));
+        assert_eq!(receipt.received_at, 42);
+
+        let key = crypto::decode_public_key(&receipt.signer_public_key).unwrap();
+        let signature = crypto::decode_signature(&receipt.signature).unwrap();
+        assert!(key.verify(&receipt.signing_payload(), &signature).is_ok());
+        let forged = Receipt {
+            sequence: Some(8),
+            ..receipt.clone()
+        };
+        assert!(key.verify(&forged.signing_payload(), &signature).is_err());
+
+        receipts.issue("f2".to_string(), "f2", &envelope(), None, &signer);
+        assert_eq!(receipts.get("f1"), Some(receipt));
+        receipts.issue("f3".to_string(), "f3", &envelope(), None, &signer);
+        assert!(receipts.get("f1").is_none());
+        assert_eq!(receipts.get("f2").unwrap().stream, None);
+    }
+}
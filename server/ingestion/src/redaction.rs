@@ -0,0 +1,521 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use sha3::{Digest, Sha3_256};
+use std::{collections::BTreeMap, path::PathBuf, sync::RwLock};
+use tracing::{info, warn};
+
+use crate::schemas::escape;
+use crate::store::JsonFile;
+use crate::tenants::{validate_tenant_id, DEFAULT_TENANT};
+use crate::verification::now_nanos;
+use crate::FactoEvent;
+
+/// Key of the object that replaces a redacted value
+pub const REDACTED_KEY: &str = "$facto_redacted";
+
+/// Payloads a rule's path may start at
+const PAYLOADS: [&str; 2] = ["input_data", "output_data"];
+
+// ============================================================================
+// Policy Configuration
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionAction {
+    /// Replace the value with its hash, keyed when `REDACTION_HASH_KEY` is
+    /// set, so equal values can still be matched
+    Hash,
+    /// Drop the value, leaving only the marker
+    Tombstone,
+    /// Encrypt the value under a fresh data key, itself encrypted with the
+    /// configured key encryption key
+    Encrypt,
+}
+
+impl RedactionAction {
+    /// Metrics label for the action
+    pub fn code(&self) -> &'static str {
+        match self {
+            RedactionAction::Hash => "hash",
+            RedactionAction::Tombstone => "tombstone",
+            RedactionAction::Encrypt => "encrypt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedactionRule {
+    /// JSON Pointer into the event starting at `/input_data` or
+    /// `/output_data`; a `*` token matches every member or element
+    pub path: String,
+    pub action: RedactionAction,
+}
+
+/// Redaction rules of one tenant
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RedactionRequest {
+    /// Rules for all of the tenant's agents
+    #[serde(default)]
+    pub rules: Vec<RedactionRule>,
+    /// Rules for single agents, applied after the tenant's
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub agents: BTreeMap<String, Vec<RedactionRule>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactionPolicy {
+    /// The tenant, or `default` for events accepted without one
+    pub tenant_id: String,
+    #[serde(flatten)]
+    pub policy: RedactionRequest,
+    pub updated_at: i64,
+}
+
+/// What was redacted from an event before publication, recorded in its
+/// server envelope.
+///
+/// The event hash and signature cover the original payloads. Whoever holds
+/// an original payload can check it against its hash here, put it back and
+/// verify the event as submitted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedactionRecord {
+    /// SHA3-256 of the original `input_data` JSON encoding, if redacted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_hash: Option<String>,
+    /// SHA3-256 of the original `output_data` JSON encoding, if redacted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_hash: Option<String>,
+    /// Pointers of the redacted values
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RedactionError {
+    #[error("invalid redaction policy: {0}")]
+    InvalidPolicy(String),
+    #[error("no redaction policy for tenant: {0}")]
+    Unknown(String),
+    #[error("encrypt rules need REDACTION_KEK to be configured")]
+    EncryptionUnavailable,
+    #[error("failed to persist redaction policies: {0}")]
+    Persistence(String),
+}
+
+// ============================================================================
+// Keys
+// ============================================================================
+
+/// Secrets the hash and encrypt actions use
+pub struct RedactionKeys {
+    hash_key: Option<Vec<u8>>,
+    kek: Option<(String, Aes256Gcm)>,
+}
+
+impl RedactionKeys {
+    pub fn new(hash_key: Option<Vec<u8>>, kek: Option<(String, [u8; 32])>) -> Self {
+        Self {
+            hash_key,
+            kek: kek.map(|(id, key)| (id, Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))),
+        }
+    }
+
+    /// Read `REDACTION_HASH_KEY` and `REDACTION_KEK` (hex, 32 bytes) and
+    /// `REDACTION_KEK_ID`
+    pub fn from_env() -> anyhow::Result<Self> {
+        let hash_key = std::env::var("REDACTION_HASH_KEY")
+            .ok()
+            .map(hex::decode)
+            .transpose()?;
+        let kek = match std::env::var("REDACTION_KEK") {
+            Ok(kek) => {
+                let key: [u8; 32] = hex::decode(kek)?
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("REDACTION_KEK must be 32 bytes"))?;
+                let id =
+                    std::env::var("REDACTION_KEK_ID").unwrap_or_else(|_| "default".to_string());
+                Some((id, key))
+            }
+            Err(_) => None,
+        };
+        if hash_key.is_none() {
+            warn!("REDACTION_HASH_KEY not set, hashed values are unkeyed SHA3-256");
+        }
+        Ok(Self::new(hash_key, kek))
+    }
+
+    fn hash(&self, encoded: &[u8]) -> Value {
+        let (algorithm, digest) = match self.hash_key {
+            Some(ref key) => {
+                let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+                    .expect("HMAC accepts any key length");
+                mac.update(encoded);
+                ("hmac-sha256", hex::encode(mac.finalize().into_bytes()))
+            }
+            None => ("sha3-256", hex::encode(Sha3_256::digest(encoded))),
+        };
+        serde_json::json!({"action": "hash", "algorithm": algorithm, "digest": digest})
+    }
+
+    fn encrypt(&self, encoded: &[u8]) -> Value {
+        let (ref key_id, ref kek) = self.kek.as_ref().expect("encrypt rules require a KEK");
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+        let ciphertext = Aes256Gcm::new(&data_key)
+            .encrypt(&nonce, encoded)
+            .expect("AES-GCM encrypts any payload size");
+
+        let key_nonce = Aes256Gcm::generate_nonce(OsRng);
+        let mut wrapped_key = key_nonce.to_vec();
+        wrapped_key.extend(
+            kek.encrypt(&key_nonce, data_key.as_slice())
+                .expect("AES-GCM encrypts any payload size"),
+        );
+        serde_json::json!({
+            "action": "encrypt",
+            "algorithm": "aes-256-gcm",
+            "key_id": key_id,
+            "wrapped_key": BASE64.encode(wrapped_key),
+            "nonce": BASE64.encode(nonce),
+            "ciphertext": BASE64.encode(ciphertext),
+        })
+    }
+
+    /// The marker a value is replaced with
+    fn redact(&self, value: &Value, action: RedactionAction) -> Value {
+        let encoded = serde_json::to_vec(value).expect("JSON values always serialize");
+        let marker = match action {
+            RedactionAction::Hash => self.hash(&encoded),
+            RedactionAction::Tombstone => serde_json::json!({"action": "tombstone"}),
+            RedactionAction::Encrypt => self.encrypt(&encoded),
+        };
+        serde_json::json!({ REDACTED_KEY: marker })
+    }
+}
+
+// ============================================================================
+// Policy Registry
+// ============================================================================
+
+/// Redaction policies by tenant, applied to accepted events before they are
+/// published anywhere.
+///
+/// Events of tenants without a policy are published as submitted.
+pub struct Redactions {
+    policies: RwLock<BTreeMap<String, RedactionPolicy>>,
+    keys: RedactionKeys,
+    store: JsonFile,
+}
+
+impl Redactions {
+    pub fn new(path: Option<PathBuf>, keys: RedactionKeys) -> anyhow::Result<Self> {
+        let store = JsonFile::new(path);
+        let policies: BTreeMap<String, RedactionPolicy> = store.load()?;
+        info!("Loaded {} redaction policies", policies.len());
+        let redactions = Self {
+            policies: RwLock::new(BTreeMap::new()),
+            keys,
+            store,
+        };
+        for policy in policies.values() {
+            redactions.validate(&policy.policy)?;
+        }
+        *redactions.policies.write().unwrap() = policies;
+        Ok(redactions)
+    }
+
+    /// Read the policies again from `REDACTIONS_PATH`, picking up edits made
+    /// to the file directly. Returns None without a file; nothing changes if
+    /// a policy is invalid.
+    pub fn reload(&self) -> anyhow::Result<Option<usize>> {
+        if self.store.path().is_none() {
+            return Ok(None);
+        }
+        let policies: BTreeMap<String, RedactionPolicy> = self.store.load()?;
+        for policy in policies.values() {
+            self.validate(&policy.policy)?;
+        }
+        let count = policies.len();
+        *self.policies.write().unwrap() = policies;
+        Ok(Some(count))
+    }
+
+    pub fn list(&self) -> Vec<RedactionPolicy> {
+        self.policies.read().unwrap().values().cloned().collect()
+    }
+
+    pub fn get(&self, tenant_id: &str) -> Option<RedactionPolicy> {
+        self.policies.read().unwrap().get(tenant_id).cloned()
+    }
+
+    /// Create or replace a tenant's policy; applies to events accepted from
+    /// now on
+    pub fn upsert(
+        &self,
+        tenant_id: &str,
+        request: RedactionRequest,
+    ) -> Result<RedactionPolicy, RedactionError> {
+        if tenant_id != DEFAULT_TENANT {
+            validate_tenant_id(tenant_id)
+                .map_err(|e| RedactionError::InvalidPolicy(e.to_string()))?;
+        }
+        self.validate(&request)?;
+        let policy = RedactionPolicy {
+            tenant_id: tenant_id.to_string(),
+            policy: request,
+            updated_at: now_nanos(),
+        };
+
+        let mut policies = self.policies.write().unwrap();
+        let mut updated = policies.clone();
+        updated.insert(tenant_id.to_string(), policy.clone());
+        self.persist(&updated)?;
+        *policies = updated;
+        Ok(policy)
+    }
+
+    pub fn remove(&self, tenant_id: &str) -> Result<RedactionPolicy, RedactionError> {
+        let mut policies = self.policies.write().unwrap();
+        let mut updated = policies.clone();
+        let removed = updated
+            .remove(tenant_id)
+            .ok_or_else(|| RedactionError::Unknown(tenant_id.to_string()))?;
+        self.persist(&updated)?;
+        *policies = updated;
+        Ok(removed)
+    }
+
+    fn validate(&self, request: &RedactionRequest) -> Result<(), RedactionError> {
+        for rule in request
+            .rules
+            .iter()
+            .chain(request.agents.values().flatten())
+        {
+            if parse_path(&rule.path).is_none() {
+                return Err(RedactionError::InvalidPolicy(format!(
+                    "path must start at /input_data or /output_data: {}",
+                    rule.path
+                )));
+            }
+            if rule.action == RedactionAction::Encrypt && self.keys.kek.is_none() {
+                return Err(RedactionError::EncryptionUnavailable);
+            }
+        }
+        Ok(())
+    }
+
+    /// Redact an event's payloads under its tenant's policy. Returns what was
+    /// redacted, or None if nothing matched.
+    pub fn redact(&self, tenant: &str, event: &mut FactoEvent) -> Option<RedactionRecord> {
+        let rules: Vec<RedactionRule> = {
+            let policies = self.policies.read().unwrap();
+            let policy = &policies.get(tenant)?.policy;
+            let agent_rules = policy.agents.get(&event.agent_id).into_iter().flatten();
+            policy.rules.iter().chain(agent_rules).cloned().collect()
+        };
+
+        let original_input = event.input_data.clone();
+        let original_output = event.output_data.clone();
+        let mut paths = Vec::new();
+        for rule in &rules {
+            let Some(tokens) = parse_path(&rule.path) else {
+                continue;
+            };
+            let (payload, rest) = match tokens[0].as_str() {
+                "input_data" => (&mut event.input_data, &tokens[1..]),
+                _ => (&mut event.output_data, &tokens[1..]),
+            };
+            let pointer = format!("/{}", tokens[0]);
+            let before = paths.len();
+            self.redact_at(payload, rest, pointer, rule.action, &mut paths);
+            counter!("facto_redacted_values_total", "action" => rule.action.code())
+                .increment((paths.len() - before) as u64);
+        }
+        if paths.is_empty() {
+            return None;
+        }
+        // The event no longer matches the bytes it was received as
+        event.raw = None;
+
+        let hash = |original: &Value, current: &Value| {
+            (original != current).then(|| {
+                let encoded = serde_json::to_vec(original).expect("JSON values always serialize");
+                hex::encode(Sha3_256::digest(encoded))
+            })
+        };
+        Some(RedactionRecord {
+            input_hash: hash(&original_input, &event.input_data),
+            output_hash: hash(&original_output, &event.output_data),
+            paths,
+        })
+    }
+
+    fn redact_at(
+        &self,
+        value: &mut Value,
+        tokens: &[String],
+        pointer: String,
+        action: RedactionAction,
+        paths: &mut Vec<String>,
+    ) {
+        let Some((token, rest)) = tokens.split_first() else {
+            // Values redacted by an earlier rule are left alone
+            if !is_redacted(value) {
+                *value = self.keys.redact(value, action);
+                paths.push(pointer);
+            }
+            return;
+        };
+        match value {
+            Value::Object(members) => {
+                for (name, member) in members.iter_mut() {
+                    if token == "*" || token == name {
+                        let pointer = format!("{}/{}", pointer, escape(name));
+                        self.redact_at(member, rest, pointer, action, paths);
+                    }
+                }
+            }
+            Value::Array(elements) => {
+                for (index, element) in elements.iter_mut().enumerate() {
+                    if token == "*" || *token == index.to_string() {
+                        let pointer = format!("{}/{}", pointer, index);
+                        self.redact_at(element, rest, pointer, action, paths);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn persist(&self, policies: &BTreeMap<String, RedactionPolicy>) -> Result<(), RedactionError> {
+        self.store
+            .save(policies)
+            .map_err(|e| RedactionError::Persistence(e.to_string()))
+    }
+}
+
+/// The unescaped tokens of a rule path, starting with the payload name
+fn parse_path(path: &str) -> Option<Vec<String>> {
+    let tokens: Vec<String> = path
+        .strip_prefix('/')?
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect();
+    PAYLOADS.contains(&tokens[0].as_str()).then_some(tokens)
+}
+
+fn is_redacted(value: &Value) -> bool {
+    value
+        .as_object()
+        .is_some_and(|o| o.len() == 1 && o.contains_key(REDACTED_KEY))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_event;
+
+    fn rule(path: &str, action: RedactionAction) -> RedactionRule {
+        RedactionRule {
+            path: path.to_string(),
+            action,
+        }
+    }
+
+    #[test]
+    fn test_policy_redacts_matching_paths() {
+        let redactions = Redactions::new(
+            None,
+            RedactionKeys::new(Some(b"secret".to_vec()), Some(("k1".to_string(), [7; 32]))),
+        )
+        .unwrap();
+        redactions
+            .upsert(
+                "acme",
+                RedactionRequest {
+                    rules: vec![
+                        rule("/input_data/user/email", RedactionAction::Hash),
+                        rule("/input_data/messages/*/content", RedactionAction::Encrypt),
+                    ],
+                    agents: BTreeMap::from([(
+                        "agent-1".to_string(),
+                        vec![rule("/output_data/ssn", RedactionAction::Tombstone)],
+                    )]),
+                },
+            )
+            .unwrap();
+
+        let mut event = test_event();
+        event.agent_id = "agent-1".to_string();
+        event.input_data = serde_json::json!({
+            "user": {"email": "jane@example.com", "plan": "pro"},
+            "messages": [{"role": "user", "content": "hi"}, {"role": "user", "content": "bye"}]
+        });
+        event.output_data = serde_json::json!({"ssn": "123-45-6789", "ok": true});
+        let original_input = serde_json::to_vec(&event.input_data).unwrap();
+
+        let record = redactions.redact("acme", &mut event).unwrap();
+        assert_eq!(
+            record.paths,
+            vec![
+                "/input_data/user/email",
+                "/input_data/messages/0/content",
+                "/input_data/messages/1/content",
+                "/output_data/ssn",
+            ]
+        );
+        assert_eq!(
+            record.input_hash.unwrap(),
+            hex::encode(Sha3_256::digest(&original_input))
+        );
+        assert!(record.output_hash.is_some());
+
+        assert_eq!(event.input_data["user"]["plan"], "pro");
+        let email = &event.input_data["user"]["email"][REDACTED_KEY];
+        assert_eq!(email["algorithm"], "hmac-sha256");
+        let content = &event.input_data["messages"][0]["content"][REDACTED_KEY];
+        assert_eq!(content["key_id"], "k1");
+        assert!(!content["ciphertext"].as_str().unwrap().is_empty());
+        assert_eq!(
+            event.output_data["ssn"],
+            serde_json::json!({ REDACTED_KEY: {"action": "tombstone"} })
+        );
+
+        // Other tenants and agents only get the rules that apply to them
+        let mut other = test_event();
+        let submitted = other.output_data.clone();
+        assert!(redactions.redact("other", &mut other).is_none());
+        assert_eq!(other.output_data, submitted);
+    }
+
+    #[test]
+    fn test_invalid_policies_rejected() {
+        let redactions = Redactions::new(None, RedactionKeys::new(None, None)).unwrap();
+        let invalid = |rules| {
+            redactions.upsert(
+                "acme",
+                RedactionRequest {
+                    rules,
+                    ..Default::default()
+                },
+            )
+        };
+        assert!(matches!(
+            invalid(vec![rule("/proof/signature", RedactionAction::Tombstone)]),
+            Err(RedactionError::InvalidPolicy(_))
+        ));
+        assert!(matches!(
+            invalid(vec![rule("/input_data/card", RedactionAction::Encrypt)]),
+            Err(RedactionError::EncryptionUnavailable)
+        ));
+        assert!(invalid(vec![rule("/input_data/card", RedactionAction::Hash)]).is_ok());
+    }
+}
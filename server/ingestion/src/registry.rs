@@ -0,0 +1,1117 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::{collections::BTreeMap, path::PathBuf, sync::RwLock};
+use tracing::info;
+
+use crate::crypto::VerificationError;
+use crate::protocol::KeyRotation;
+use crate::scoped_id;
+use crate::store::JsonFile;
+use crate::tenants::validate_tenant_id;
+use crate::verification::{now_nanos, ServerSigner};
+
+// ============================================================================
+// Registry Model
+// ============================================================================
+
+/// A public key trusted for a given agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyEntry {
+    /// Tenant of the agent; unset for agents accepted without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub agent_id: String,
+    pub public_key: String,
+    pub registered_at: i64,
+    /// Events are accepted with this key from this time on
+    #[serde(default)]
+    pub valid_from: i64,
+    /// Events are rejected with this key from this time on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<i64>,
+    /// Events are rejected with this key from this time on, because it was
+    /// rotated out rather than revoked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+}
+
+impl KeyEntry {
+    /// The agent qualified with its tenant, which keys are pinned under
+    fn scope(&self) -> String {
+        scoped_id(self.tenant_id.as_deref(), &self.agent_id)
+    }
+}
+
+/// A single versioned mutation of the registry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryChange {
+    pub version: u64,
+    pub at: i64,
+    #[serde(flatten)]
+    pub op: RegistryOp,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum RegistryOp {
+    /// Trust an additional key for an agent
+    Register { entry: KeyEntry },
+    /// Stop trusting a key from `effective_at` on
+    Revoke {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tenant_id: Option<String>,
+        agent_id: String,
+        public_key: String,
+        effective_at: i64,
+    },
+    /// Replace a key: the old key is revoked when the new one becomes
+    /// valid or, with `old_expires_at`, expires then so both keys are valid
+    /// in between
+    Rotate {
+        old_public_key: String,
+        entry: KeyEntry,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        old_expires_at: Option<i64>,
+    },
+    /// Replace the whole registry with the entries of an imported snapshot
+    Restore { entries: Vec<KeyEntry> },
+    /// Trust many keys at once, from a bulk import
+    Import { entries: Vec<KeyEntry> },
+}
+
+/// Registry contents at a given version, with a server-signed state root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrySnapshot {
+    pub version: u64,
+    pub as_of: i64,
+    pub entries: Vec<KeyEntry>,
+    pub state_root: String,
+    pub signer_id: String,
+    pub signer_public_key: String,
+    pub signature: String,
+}
+
+impl RegistrySnapshot {
+    /// The bytes covered by the snapshot signature
+    pub fn signing_payload(version: u64, as_of: i64, state_root: &str) -> Vec<u8> {
+        serde_json::to_vec(&serde_json::json!({
+            "as_of": as_of,
+            "state_root": state_root,
+            "version": version,
+        }))
+        .unwrap_or_default()
+    }
+}
+
+/// Identifies the registry state a trust decision was made against
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistryRef {
+    pub version: u64,
+    pub state_root: String,
+}
+
+/// Admin request to register a key for an agent
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegisterKeyRequest {
+    pub public_key: String,
+    /// Defaults to now
+    pub valid_from: Option<i64>,
+}
+
+/// One row of a bulk key import
+#[derive(Debug, Clone, Deserialize)]
+pub struct KeyImportRow {
+    /// Tenant of the agent; unset for agents accepted without one
+    #[serde(default)]
+    pub tenant_id: Option<String>,
+    pub agent_id: String,
+    pub public_key: String,
+    /// Defaults to now
+    #[serde(default)]
+    pub valid_from: Option<i64>,
+    /// Imports the key already revoked from this time on
+    #[serde(default)]
+    pub revoked_at: Option<i64>,
+    /// Imports the key expiring at this time
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
+/// Outcome of a bulk key import
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyImportReport {
+    pub dry_run: bool,
+    pub imported: usize,
+    pub rejected: Vec<RejectedKeyRow>,
+    /// Registry state after the import; unset for dry runs and imports that
+    /// change nothing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<RegistryRef>,
+}
+
+/// A row left out of a bulk import, by its line in the imported file
+#[derive(Debug, Clone, Serialize)]
+pub struct RejectedKeyRow {
+    pub line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    pub error: String,
+}
+
+/// Admin request to revoke one of an agent's keys
+#[derive(Debug, Clone, Deserialize)]
+pub struct RevokeKeyRequest {
+    pub public_key: String,
+    /// Defaults to now
+    pub effective_at: Option<i64>,
+}
+
+/// Admin request to rotate an agent's key
+#[derive(Debug, Clone, Deserialize)]
+pub struct RotateKeyRequest {
+    pub old_public_key: String,
+    pub new_public_key: String,
+    /// When the new key takes over from the old one; defaults to now
+    pub effective_at: Option<i64>,
+    /// How long the old key stays valid once the new one takes over, so
+    /// events signed before the switch can still arrive; defaults to none
+    #[serde(default)]
+    pub overlap_secs: Option<u64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RegistryError {
+    #[error("unknown registry version {0}")]
+    UnknownVersion(u64),
+    #[error("invalid public key: {0}")]
+    InvalidKey(String),
+    #[error("key is already registered for agent {0}")]
+    KeyExists(String),
+    #[error("key is not registered for agent {0}")]
+    UnknownKey(String),
+    #[error("key is already revoked for agent {0}")]
+    AlreadyRevoked(String),
+    #[error("key is already rotated out for agent {0}")]
+    AlreadyRotated(String),
+    #[error("snapshot state root does not match its entries")]
+    StateRootMismatch,
+    #[error("snapshot signature is invalid: {0}")]
+    InvalidSignature(String),
+    #[error("snapshot is signed by an untrusted key: {0}")]
+    UntrustedSigner(String),
+    #[error("failed to persist key registry: {0}")]
+    Persistence(String),
+}
+
+/// Compute the state root over a set of entries: SHA3-256 of the JSON array of
+/// entries sorted by (tenant_id, agent_id, public_key)
+pub fn compute_state_root(entries: &[KeyEntry]) -> String {
+    let mut sorted: Vec<&KeyEntry> = entries.iter().collect();
+    sorted.sort_by(|a, b| {
+        (&a.tenant_id, &a.agent_id, &a.public_key).cmp(&(&b.tenant_id, &b.agent_id, &b.public_key))
+    });
+
+    let mut hasher = Sha3_256::new();
+    hasher.update(serde_json::to_vec(&sorted).unwrap_or_default());
+    hex::encode(hasher.finalize())
+}
+
+// ============================================================================
+// Key Registry
+// ============================================================================
+
+#[derive(Default)]
+struct RegistryState {
+    version: u64,
+    changes: Vec<RegistryChange>,
+    /// tenant-scoped agent_id -> public_key -> entry
+    keys: BTreeMap<String, BTreeMap<String, KeyEntry>>,
+    state_root: String,
+}
+
+impl RegistryState {
+    fn apply(&mut self, change: &RegistryChange) {
+        match &change.op {
+            RegistryOp::Register { entry } => {
+                self.keys
+                    .entry(entry.scope())
+                    .or_default()
+                    .insert(entry.public_key.clone(), entry.clone());
+            }
+            RegistryOp::Revoke {
+                tenant_id,
+                agent_id,
+                public_key,
+                effective_at,
+            } => self.revoke(
+                &scoped_id(tenant_id.as_deref(), agent_id),
+                public_key,
+                *effective_at,
+            ),
+            RegistryOp::Rotate {
+                old_public_key,
+                entry,
+                old_expires_at,
+            } => {
+                match old_expires_at {
+                    Some(expires_at) => self.expire(&entry.scope(), old_public_key, *expires_at),
+                    None => self.revoke(&entry.scope(), old_public_key, entry.valid_from),
+                }
+                self.keys
+                    .entry(entry.scope())
+                    .or_default()
+                    .insert(entry.public_key.clone(), entry.clone());
+            }
+            RegistryOp::Restore { entries } => {
+                self.keys.clear();
+                self.insert_all(entries);
+            }
+            RegistryOp::Import { entries } => self.insert_all(entries),
+        }
+        self.version = change.version;
+    }
+
+    fn insert_all(&mut self, entries: &[KeyEntry]) {
+        for entry in entries {
+            self.keys
+                .entry(entry.scope())
+                .or_default()
+                .insert(entry.public_key.clone(), entry.clone());
+        }
+    }
+
+    fn revoke(&mut self, scope: &str, public_key: &str, effective_at: i64) {
+        if let Some(entry) = self
+            .keys
+            .get_mut(scope)
+            .and_then(|keys| keys.get_mut(public_key))
+        {
+            entry.revoked_at = Some(
+                entry
+                    .revoked_at
+                    .map_or(effective_at, |r| r.min(effective_at)),
+            );
+        }
+    }
+
+    fn expire(&mut self, scope: &str, public_key: &str, expires_at: i64) {
+        if let Some(entry) = self
+            .keys
+            .get_mut(scope)
+            .and_then(|keys| keys.get_mut(public_key))
+        {
+            entry.expires_at = Some(entry.expires_at.map_or(expires_at, |e| e.min(expires_at)));
+        }
+    }
+
+    fn entry(&self, scope: &str, public_key: &str) -> Option<&KeyEntry> {
+        self.keys.get(scope)?.get(public_key)
+    }
+
+    fn entries(&self) -> Vec<KeyEntry> {
+        self.keys
+            .values()
+            .flat_map(|keys| keys.values().cloned())
+            .collect()
+    }
+
+    fn replay(changes: &[RegistryChange]) -> Self {
+        let mut state = Self::default();
+        for change in changes {
+            state.apply(change);
+        }
+        state.state_root = compute_state_root(&state.entries());
+        state.changes = changes.to_vec();
+        state
+    }
+}
+
+/// Versioned map of tenant-scoped agent_id to trusted public keys. Every mutation bumps the
+/// version and is kept in an append-only change log, so the registry can be
+/// materialized as of any past version.
+///
+/// Agents with registered keys are pinned to them. Agents without any are
+/// trusted on their embedded key unless registration is required.
+pub struct KeyRegistry {
+    state: RwLock<RegistryState>,
+    store: JsonFile,
+    require_registration: bool,
+}
+
+impl KeyRegistry {
+    pub fn new(path: Option<PathBuf>, require_registration: bool) -> anyhow::Result<Self> {
+        let store = JsonFile::new(path);
+        let changes: Vec<RegistryChange> = store.load()?;
+
+        let state = RegistryState::replay(&changes);
+        info!(
+            "Key registry loaded at version {} ({} agents)",
+            state.version,
+            state.keys.len()
+        );
+
+        Ok(Self {
+            state: RwLock::new(state),
+            store,
+            require_registration,
+        })
+    }
+
+    pub fn current(&self) -> RegistryRef {
+        let state = self.state.read().unwrap();
+        RegistryRef {
+            version: state.version,
+            state_root: state.state_root.clone(),
+        }
+    }
+
+    /// Number of agents with registered keys
+    pub fn agent_count(&self) -> usize {
+        self.state.read().unwrap().keys.len()
+    }
+
+    pub fn requires_registration(&self) -> bool {
+        self.require_registration
+    }
+
+    /// Check that registry changes can be persisted
+    pub fn check_store(&self) -> std::io::Result<()> {
+        self.store.check_writable()
+    }
+
+    /// Decide whether `public_key` may sign for the tenant's `agent_id` at
+    /// time `at`. Returns the registry reference the decision was made
+    /// against, or `None` if the agent is unregistered and trusted on its
+    /// embedded key.
+    pub fn authorize(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+        public_key: &str,
+        at: i64,
+    ) -> Result<Option<RegistryRef>, VerificationError> {
+        let state = self.state.read().unwrap();
+        let Some(keys) = state.keys.get(&scoped_id(tenant_id, agent_id)) else {
+            if self.require_registration {
+                return Err(VerificationError::UnregisteredKey(agent_id.to_string()));
+            }
+            return Ok(None);
+        };
+
+        match keys.get(public_key) {
+            None => Err(VerificationError::UnregisteredKey(agent_id.to_string())),
+            Some(entry) if at < entry.valid_from => {
+                Err(VerificationError::KeyNotYetValid(agent_id.to_string()))
+            }
+            Some(entry) if entry.revoked_at.is_some_and(|revoked_at| at >= revoked_at) => {
+                Err(VerificationError::RevokedKey(agent_id.to_string()))
+            }
+            Some(entry) if entry.expires_at.is_some_and(|expires_at| at >= expires_at) => {
+                Err(VerificationError::ExpiredKey(agent_id.to_string()))
+            }
+            Some(_) => Ok(Some(RegistryRef {
+                version: state.version,
+                state_root: state.state_root.clone(),
+            })),
+        }
+    }
+
+    /// All keys ever registered for an agent, including revoked ones
+    pub fn keys(&self, tenant_id: Option<&str>, agent_id: &str) -> Vec<KeyEntry> {
+        let state = self.state.read().unwrap();
+        state
+            .keys
+            .get(&scoped_id(tenant_id, agent_id))
+            .map(|keys| keys.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Every key of every agent, ordered by (agent_id, public_key)
+    pub fn entries(&self) -> Vec<KeyEntry> {
+        self.state.read().unwrap().entries()
+    }
+
+    /// Register a new key for an agent
+    pub fn register(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+        request: RegisterKeyRequest,
+    ) -> Result<KeyEntry, RegistryError> {
+        crate::crypto::PublicKey::decode_any(&request.public_key)
+            .map_err(|e| RegistryError::InvalidKey(e.to_string()))?;
+
+        let mut state = self.state.write().unwrap();
+        if state
+            .entry(&scoped_id(tenant_id, agent_id), &request.public_key)
+            .is_some()
+        {
+            return Err(RegistryError::KeyExists(agent_id.to_string()));
+        }
+
+        let now = now_nanos();
+        let entry = KeyEntry {
+            tenant_id: tenant_id.map(str::to_string),
+            agent_id: agent_id.to_string(),
+            public_key: request.public_key,
+            registered_at: now,
+            valid_from: request.valid_from.unwrap_or(now),
+            revoked_at: None,
+            expires_at: None,
+        };
+        let change = RegistryChange {
+            version: state.version + 1,
+            at: now,
+            op: RegistryOp::Register {
+                entry: entry.clone(),
+            },
+        };
+        self.commit(&mut state, change)?;
+        Ok(entry)
+    }
+
+    /// Revoke one of an agent's keys
+    pub fn revoke(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+        request: RevokeKeyRequest,
+    ) -> Result<KeyEntry, RegistryError> {
+        let scope = scoped_id(tenant_id, agent_id);
+        let mut state = self.state.write().unwrap();
+        match state.entry(&scope, &request.public_key) {
+            None => return Err(RegistryError::UnknownKey(agent_id.to_string())),
+            Some(entry) if entry.revoked_at.is_some() => {
+                return Err(RegistryError::AlreadyRevoked(agent_id.to_string()))
+            }
+            Some(_) => {}
+        }
+
+        let now = now_nanos();
+        let change = RegistryChange {
+            version: state.version + 1,
+            at: now,
+            op: RegistryOp::Revoke {
+                tenant_id: tenant_id.map(str::to_string),
+                agent_id: agent_id.to_string(),
+                public_key: request.public_key.clone(),
+                effective_at: request.effective_at.unwrap_or(now),
+            },
+        };
+        self.commit(&mut state, change)?;
+        Ok(state.entry(&scope, &request.public_key).cloned().unwrap())
+    }
+
+    /// Replace one of an agent's keys with a new key. The old key stays valid
+    /// until the new one takes effect, and for `overlap_secs` after that.
+    pub fn rotate(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+        request: RotateKeyRequest,
+    ) -> Result<KeyEntry, RegistryError> {
+        crate::crypto::PublicKey::decode_any(&request.new_public_key)
+            .map_err(|e| RegistryError::InvalidKey(e.to_string()))?;
+
+        let scope = scoped_id(tenant_id, agent_id);
+        let mut state = self.state.write().unwrap();
+        match state.entry(&scope, &request.old_public_key) {
+            None => return Err(RegistryError::UnknownKey(agent_id.to_string())),
+            Some(entry) if entry.revoked_at.is_some() => {
+                return Err(RegistryError::AlreadyRevoked(agent_id.to_string()))
+            }
+            Some(entry) if entry.expires_at.is_some() => {
+                return Err(RegistryError::AlreadyRotated(agent_id.to_string()))
+            }
+            Some(_) => {}
+        }
+        if state.entry(&scope, &request.new_public_key).is_some() {
+            return Err(RegistryError::KeyExists(agent_id.to_string()));
+        }
+
+        let now = now_nanos();
+        let entry = KeyEntry {
+            tenant_id: tenant_id.map(str::to_string),
+            agent_id: agent_id.to_string(),
+            public_key: request.new_public_key,
+            registered_at: now,
+            valid_from: request.effective_at.unwrap_or(now),
+            revoked_at: None,
+            expires_at: None,
+        };
+        let old_expires_at = request
+            .overlap_secs
+            .map(|secs| entry.valid_from.saturating_add(secs_to_nanos(secs)));
+        let change = RegistryChange {
+            version: state.version + 1,
+            at: now,
+            op: RegistryOp::Rotate {
+                old_public_key: request.old_public_key,
+                entry: entry.clone(),
+                old_expires_at,
+            },
+        };
+        self.commit(&mut state, change)?;
+        Ok(entry)
+    }
+
+    /// Whether `public_key` is pinned for the agent and can still be rotated
+    /// out: registered, neither revoked nor already rotated
+    pub fn is_pinned(&self, tenant_id: Option<&str>, agent_id: &str, public_key: &str) -> bool {
+        self.state
+            .read()
+            .unwrap()
+            .entry(&scoped_id(tenant_id, agent_id), public_key)
+            .is_some_and(|entry| entry.revoked_at.is_none() && entry.expires_at.is_none())
+    }
+
+    /// Apply a rotation the agent attested to with a key rotation event
+    /// signed by `old_public_key`, which must be pinned for the agent: a
+    /// self-signed rotation cannot claim an agent trusted on its embedded key.
+    pub fn rotate_attested(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+        old_public_key: &str,
+        rotation: &KeyRotation,
+        max_overlap_secs: u64,
+    ) -> Result<KeyEntry, RegistryError> {
+        let overlap_secs = rotation
+            .overlap_secs
+            .unwrap_or(max_overlap_secs)
+            .min(max_overlap_secs);
+        let now = now_nanos();
+        // The new key cannot be backdated past the attestation
+        let effective_at = rotation.valid_from.map_or(now, |at| at.max(now));
+        let request = RotateKeyRequest {
+            old_public_key: old_public_key.to_string(),
+            new_public_key: rotation.new_public_key.clone(),
+            effective_at: Some(effective_at),
+            overlap_secs: Some(overlap_secs),
+        };
+        self.rotate(tenant_id, agent_id, request)
+    }
+
+    /// Register many keys as a single registry version. Rows are validated
+    /// one by one against the registry and each other; the valid ones are
+    /// imported and the others reported with their line. A dry run only
+    /// validates.
+    pub fn import(
+        &self,
+        rows: Vec<(usize, KeyImportRow)>,
+        dry_run: bool,
+    ) -> Result<KeyImportReport, RegistryError> {
+        let mut state = self.state.write().unwrap();
+        let now = now_nanos();
+
+        let mut seen: BTreeMap<(String, String), usize> = BTreeMap::new();
+        let mut entries = Vec::with_capacity(rows.len());
+        let mut rejected = Vec::new();
+        for (line, row) in rows {
+            let valid_from = row.valid_from.unwrap_or(now);
+            let scope = scoped_id(row.tenant_id.as_deref(), &row.agent_id);
+            let error = if row.agent_id.is_empty() {
+                Some("agent_id is required".to_string())
+            } else if let Some(Err(e)) = row.tenant_id.as_deref().map(validate_tenant_id) {
+                Some(e.to_string())
+            } else if let Err(e) = crate::crypto::PublicKey::decode_any(&row.public_key) {
+                Some(RegistryError::InvalidKey(e.to_string()).to_string())
+            } else if state.entry(&scope, &row.public_key).is_some() {
+                Some(RegistryError::KeyExists(row.agent_id.clone()).to_string())
+            } else if let Some(first) = seen.get(&(scope.clone(), row.public_key.clone())) {
+                Some(format!("duplicate of line {}", first))
+            } else if row.revoked_at.is_some_and(|r| r <= valid_from) {
+                Some("revoked_at must be after valid_from".to_string())
+            } else if row.expires_at.is_some_and(|e| e <= valid_from) {
+                Some("expires_at must be after valid_from".to_string())
+            } else {
+                None
+            };
+
+            if let Some(error) = error {
+                rejected.push(RejectedKeyRow {
+                    line,
+                    agent_id: Some(row.agent_id).filter(|a| !a.is_empty()),
+                    error,
+                });
+                continue;
+            }
+
+            seen.insert((scope, row.public_key.clone()), line);
+            entries.push(KeyEntry {
+                tenant_id: row.tenant_id,
+                agent_id: row.agent_id,
+                public_key: row.public_key,
+                registered_at: now,
+                valid_from,
+                revoked_at: row.revoked_at,
+                expires_at: row.expires_at,
+            });
+        }
+
+        let imported = entries.len();
+        let registry = if dry_run || entries.is_empty() {
+            None
+        } else {
+            let change = RegistryChange {
+                version: state.version + 1,
+                at: now,
+                op: RegistryOp::Import { entries },
+            };
+            self.commit(&mut state, change)?;
+            info!(
+                "Imported {} keys into the key registry as version {}",
+                imported, state.version
+            );
+            Some(RegistryRef {
+                version: state.version,
+                state_root: state.state_root.clone(),
+            })
+        };
+
+        Ok(KeyImportReport {
+            dry_run,
+            imported,
+            rejected,
+            registry,
+        })
+    }
+
+    /// Latest version whose change was applied at or before `at`
+    pub fn version_at(&self, at: i64) -> u64 {
+        let state = self.state.read().unwrap();
+        state
+            .changes
+            .iter()
+            .take_while(|c| c.at <= at)
+            .last()
+            .map_or(0, |c| c.version)
+    }
+
+    /// Export a signed snapshot of the registry as of `version` (default: current)
+    pub fn snapshot(
+        &self,
+        version: Option<u64>,
+        signer: &ServerSigner,
+    ) -> Result<RegistrySnapshot, RegistryError> {
+        let state = self.state.read().unwrap();
+        let version = version.unwrap_or(state.version);
+        if version > state.version {
+            return Err(RegistryError::UnknownVersion(version));
+        }
+
+        let applied: Vec<RegistryChange> = state
+            .changes
+            .iter()
+            .take_while(|c| c.version <= version)
+            .cloned()
+            .collect();
+        let as_of = applied.last().map_or(0, |c| c.at);
+        let entries = RegistryState::replay(&applied).entries();
+        let state_root = compute_state_root(&entries);
+        let signature = signer.sign_base64(&RegistrySnapshot::signing_payload(
+            version,
+            as_of,
+            &state_root,
+        ));
+
+        Ok(RegistrySnapshot {
+            version,
+            as_of,
+            entries,
+            state_root,
+            signer_id: signer.instance_id().to_string(),
+            signer_public_key: signer.public_key_base64(),
+            signature,
+        })
+    }
+
+    /// Restore the registry from a snapshot after checking its state root and
+    /// its signature by one of `trusted_signers`, base64 public keys. The
+    /// version becomes the snapshot version, or the next version if the
+    /// registry has already moved past it.
+    pub fn restore(
+        &self,
+        snapshot: &RegistrySnapshot,
+        trusted_signers: &[String],
+    ) -> Result<RegistryRef, RegistryError> {
+        if compute_state_root(&snapshot.entries) != snapshot.state_root {
+            return Err(RegistryError::StateRootMismatch);
+        }
+        verify_snapshot_signature(snapshot, trusted_signers)?;
+
+        let mut state = self.state.write().unwrap();
+        let change = RegistryChange {
+            version: (state.version + 1).max(snapshot.version),
+            at: now_nanos(),
+            op: RegistryOp::Restore {
+                entries: snapshot.entries.clone(),
+            },
+        };
+        self.commit(&mut state, change)?;
+
+        info!(
+            "Key registry restored from snapshot version {} as version {}",
+            snapshot.version, state.version
+        );
+
+        Ok(RegistryRef {
+            version: state.version,
+            state_root: state.state_root.clone(),
+        })
+    }
+
+    /// Append a change, persist the log, and only then apply it in memory
+    fn commit(
+        &self,
+        state: &mut RegistryState,
+        change: RegistryChange,
+    ) -> Result<(), RegistryError> {
+        let mut changes = state.changes.clone();
+        changes.push(change.clone());
+
+        self.store
+            .save(&changes)
+            .map_err(|e| RegistryError::Persistence(e.to_string()))?;
+
+        state.apply(&change);
+        state.changes = changes;
+        state.state_root = compute_state_root(&state.entries());
+        Ok(())
+    }
+}
+
+fn secs_to_nanos(secs: u64) -> i64 {
+    i64::try_from(secs)
+        .unwrap_or(i64::MAX)
+        .saturating_mul(1_000_000_000)
+}
+
+/// The key named in the snapshot is only used once it is known to be trusted
+fn verify_snapshot_signature(
+    snapshot: &RegistrySnapshot,
+    trusted_signers: &[String],
+) -> Result<(), RegistryError> {
+    use ed25519_dalek::Verifier as _;
+
+    if !trusted_signers.contains(&snapshot.signer_public_key) {
+        return Err(RegistryError::UntrustedSigner(snapshot.signer_id.clone()));
+    }
+    let key = crate::crypto::decode_public_key(&snapshot.signer_public_key)
+        .map_err(|e| RegistryError::InvalidSignature(e.to_string()))?;
+    let signature = crate::crypto::decode_signature(&snapshot.signature)
+        .map_err(|e| RegistryError::InvalidSignature(e.to_string()))?;
+    key.verify(
+        &RegistrySnapshot::signing_payload(snapshot.version, snapshot.as_of, &snapshot.state_root),
+        &signature,
+    )
+    .map_err(|e| RegistryError::InvalidSignature(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+
+    fn signer() -> ServerSigner {
+        ServerSigner::new("test".to_string(), SigningKey::from_bytes(&[3u8; 32]))
+    }
+
+    fn entry(agent_id: &str, public_key: &str) -> KeyEntry {
+        KeyEntry {
+            tenant_id: None,
+            agent_id: agent_id.to_string(),
+            public_key: public_key.to_string(),
+            registered_at: 1,
+            valid_from: 0,
+            revoked_at: None,
+            expires_at: None,
+        }
+    }
+
+    fn public_key(seed: u8) -> String {
+        ServerSigner::new("agent".to_string(), SigningKey::from_bytes(&[seed; 32]))
+            .public_key_base64()
+    }
+
+    fn restore_entries(registry: &KeyRegistry, entries: Vec<KeyEntry>) -> RegistryRef {
+        let signer = signer();
+        let state_root = compute_state_root(&entries);
+        let snapshot = RegistrySnapshot {
+            version: 0,
+            as_of: 0,
+            signature: signer.sign_base64(&RegistrySnapshot::signing_payload(0, 0, &state_root)),
+            entries,
+            state_root,
+            signer_id: signer.instance_id().to_string(),
+            signer_public_key: signer.public_key_base64(),
+        };
+        registry.restore(&snapshot, &trusted()).unwrap()
+    }
+
+    fn trusted() -> Vec<String> {
+        vec![signer().public_key_base64()]
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let source = KeyRegistry::new(None, false).unwrap();
+        restore_entries(
+            &source,
+            vec![entry("agent-a", "key-1"), entry("agent-b", "key-2")],
+        );
+
+        let snapshot = source.snapshot(None, &signer()).unwrap();
+        assert_eq!(snapshot.version, 1);
+        assert_eq!(snapshot.entries.len(), 2);
+
+        let target = KeyRegistry::new(None, false).unwrap();
+        let restored = target.restore(&snapshot, &trusted()).unwrap();
+        assert_eq!(restored, source.current());
+        assert!(matches!(
+            target.authorize(None, "agent-a", "key-1", 1),
+            Ok(Some(_))
+        ));
+        assert!(matches!(
+            target.authorize(None, "agent-a", "key-2", 1),
+            Err(VerificationError::UnregisteredKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_past_versions_are_reproducible() {
+        let registry = KeyRegistry::new(None, false).unwrap();
+        let v1 = restore_entries(&registry, vec![entry("agent-a", "key-1")]);
+        restore_entries(&registry, vec![entry("agent-a", "key-2")]);
+
+        let past = registry.snapshot(Some(1), &signer()).unwrap();
+        assert_eq!(past.state_root, v1.state_root);
+        assert_eq!(past.entries, vec![entry("agent-a", "key-1")]);
+        assert!(registry.snapshot(Some(5), &signer()).is_err());
+    }
+
+    #[test]
+    fn test_tampered_snapshot_is_rejected() {
+        let source = KeyRegistry::new(None, false).unwrap();
+        restore_entries(&source, vec![entry("agent-a", "key-1")]);
+
+        let mut snapshot = source.snapshot(None, &signer()).unwrap();
+        snapshot.entries.push(entry("agent-evil", "key-x"));
+        assert!(matches!(
+            KeyRegistry::new(None, false)
+                .unwrap()
+                .restore(&snapshot, &trusted()),
+            Err(RegistryError::StateRootMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_resigned_with_foreign_key_is_rejected() {
+        let source = KeyRegistry::new(None, false).unwrap();
+        restore_entries(&source, vec![entry("agent-a", "key-1")]);
+
+        // A consistent snapshot, signed by a key that is not trusted
+        let foreign = ServerSigner::new("evil".to_string(), SigningKey::from_bytes(&[9u8; 32]));
+        let snapshot = source.snapshot(None, &foreign).unwrap();
+        let target = KeyRegistry::new(None, false).unwrap();
+        assert!(matches!(
+            target.restore(&snapshot, &trusted()),
+            Err(RegistryError::UntrustedSigner(_))
+        ));
+        assert!(target
+            .restore(&snapshot, &[foreign.public_key_base64()])
+            .is_ok());
+    }
+
+    #[test]
+    fn test_unregistered_agents() {
+        let permissive = KeyRegistry::new(None, false).unwrap();
+        assert!(matches!(
+            permissive.authorize(None, "agent-a", "key-1", 1),
+            Ok(None)
+        ));
+
+        let strict = KeyRegistry::new(None, true).unwrap();
+        assert!(matches!(
+            strict.authorize(None, "agent-a", "key-1", 1),
+            Err(VerificationError::UnregisteredKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_bulk_import() {
+        let registry = KeyRegistry::new(None, true).unwrap();
+        registry
+            .register(
+                None,
+                "agent-a",
+                RegisterKeyRequest {
+                    public_key: public_key(1),
+                    valid_from: Some(0),
+                },
+            )
+            .unwrap();
+
+        let row = |agent_id: &str, public_key: String| KeyImportRow {
+            tenant_id: None,
+            agent_id: agent_id.to_string(),
+            public_key,
+            valid_from: Some(0),
+            revoked_at: None,
+            expires_at: None,
+        };
+        let rows = vec![
+            (1, row("agent-a", public_key(1))),
+            (2, row("agent-b", public_key(2))),
+            (3, row("agent-b", public_key(2))),
+            (4, row("agent-c", "not-a-key".to_string())),
+            (5, row("", public_key(3))),
+            (6, row("agent-c", public_key(3))),
+        ];
+
+        let dry_run = registry.import(rows.clone(), true).unwrap();
+        assert_eq!(dry_run.imported, 2);
+        assert!(dry_run.registry.is_none());
+        assert_eq!(registry.current().version, 1);
+
+        let report = registry.import(rows, false).unwrap();
+        assert_eq!(report.imported, 2);
+        let lines: Vec<usize> = report.rejected.iter().map(|r| r.line).collect();
+        assert_eq!(lines, vec![1, 3, 4, 5]);
+        assert_eq!(report.rejected[1].error, "duplicate of line 2");
+
+        // All imported keys land in one version
+        assert_eq!(report.registry, Some(registry.current()));
+        assert_eq!(registry.current().version, 2);
+        assert!(registry
+            .authorize(None, "agent-b", &public_key(2), 1)
+            .is_ok());
+        assert!(registry
+            .authorize(None, "agent-c", &public_key(3), 1)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_rotation_and_revocation() {
+        let registry = KeyRegistry::new(None, true).unwrap();
+        let (old, new) = (public_key(1), public_key(2));
+        registry
+            .register(
+                None,
+                "agent-a",
+                RegisterKeyRequest {
+                    public_key: old.clone(),
+                    valid_from: Some(0),
+                },
+            )
+            .unwrap();
+        registry
+            .rotate(
+                None,
+                "agent-a",
+                RotateKeyRequest {
+                    old_public_key: old.clone(),
+                    new_public_key: new.clone(),
+                    effective_at: Some(100),
+                    overlap_secs: None,
+                },
+            )
+            .unwrap();
+
+        // The old key holds until the new one takes effect
+        assert!(registry.authorize(None, "agent-a", &old, 99).is_ok());
+        assert!(matches!(
+            registry.authorize(None, "agent-a", &new, 99),
+            Err(VerificationError::KeyNotYetValid(_))
+        ));
+        assert!(matches!(
+            registry.authorize(None, "agent-a", &old, 100),
+            Err(VerificationError::RevokedKey(_))
+        ));
+        assert!(registry.authorize(None, "agent-a", &new, 100).is_ok());
+
+        registry
+            .revoke(
+                None,
+                "agent-a",
+                RevokeKeyRequest {
+                    public_key: new.clone(),
+                    effective_at: Some(200),
+                },
+            )
+            .unwrap();
+        assert!(matches!(
+            registry.authorize(None, "agent-a", &new, 200),
+            Err(VerificationError::RevokedKey(_))
+        ));
+        assert_eq!(registry.current().version, 3);
+        assert!(matches!(
+            registry.revoke(
+                None,
+                "agent-a",
+                RevokeKeyRequest {
+                    public_key: new,
+                    effective_at: None,
+                },
+            ),
+            Err(RegistryError::AlreadyRevoked(_))
+        ));
+    }
+
+    #[test]
+    fn test_attested_rotation_overlaps() {
+        let registry = KeyRegistry::new(None, false).unwrap();
+        let (first, second, third) = (public_key(1), public_key(2), public_key(3));
+        let rotation = |new_public_key: &str, overlap_secs| KeyRotation {
+            new_public_key: new_public_key.to_string(),
+            valid_from: None,
+            overlap_secs: Some(overlap_secs),
+        };
+
+        // An agent trusted on its embedded key cannot be claimed by rotating
+        assert!(matches!(
+            registry.rotate_attested(None, "agent-a", &first, &rotation(&second, 60), 30),
+            Err(RegistryError::UnknownKey(_))
+        ));
+        assert_eq!(registry.current().version, 0);
+
+        restore_entries(&registry, vec![entry("agent-a", &first)]);
+        assert!(registry.is_pinned(None, "agent-a", &first));
+        let entry = registry
+            .rotate_attested(None, "agent-a", &first, &rotation(&second, 60), 30)
+            .unwrap();
+        assert_eq!(registry.current().version, 2);
+        assert!(!registry.is_pinned(None, "agent-a", &first));
+        let at = entry.valid_from;
+        let expires_at = at + 30 * 1_000_000_000;
+        assert!(registry.authorize(None, "agent-a", &first, at).is_ok());
+        assert!(registry.authorize(None, "agent-a", &second, at).is_ok());
+        assert!(registry
+            .authorize(None, "agent-a", &first, expires_at - 1)
+            .is_ok());
+        assert!(matches!(
+            registry.authorize(None, "agent-a", &first, expires_at),
+            Err(VerificationError::ExpiredKey(_))
+        ));
+        assert!(matches!(
+            registry.authorize(None, "agent-a", &third, at),
+            Err(VerificationError::UnregisteredKey(_))
+        ));
+
+        // A key already rotated out cannot be rotated again
+        assert!(matches!(
+            registry.rotate_attested(None, "agent-a", &first, &rotation(&third, 0), 30),
+            Err(RegistryError::AlreadyRotated(_))
+        ));
+        let entry = registry
+            .rotate_attested(None, "agent-a", &second, &rotation(&third, 0), 30)
+            .unwrap();
+        assert!(matches!(
+            registry.authorize(None, "agent-a", &second, entry.valid_from),
+            Err(VerificationError::ExpiredKey(_))
+        ));
+        assert!(registry
+            .authorize(None, "agent-a", &third, entry.valid_from)
+            .is_ok());
+    }
+}
@@ -0,0 +1,450 @@
+//! Dead letters for rejected events.
+//!
+//! Events refused for what they contain (a bad hash or signature, an
+//! unknown key, a schema violation, a replay) are published to the
+//! FACTO_REJECTS stream with their rejection and the canonical form and
+//! hash the server computed, so SDK authors can see exactly what the server
+//! disagreed with. Events refused for capacity or admission reasons are
+//! only counted. Publishing happens off the request path through a bounded
+//! queue; dead letters that do not fit are dropped and counted.
+
+use crate::protocol::{ErrorCode, EventError};
+use async_nats::jetstream::{
+    self,
+    consumer::{pull, AckPolicy, DeliverPolicy},
+};
+use axum::{
+    extract::{Json, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::admin::error_response;
+use crate::auth::Scope;
+use crate::crypto::{build_canonical_form, compute_event_hash};
+use crate::tenants::{validate_tenant_id, DEFAULT_TENANT};
+use crate::verification::now_nanos;
+use crate::{AppState, FactoEvent};
+
+/// Stream holding recently rejected events
+pub const REJECTS_STREAM: &str = "FACTO_REJECTS";
+
+const REJECTS_SUBJECT_PREFIX: &str = "facto.rejects";
+
+/// Rejects returned when the client does not ask for fewer
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// Largest page a client may ask for
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// Dead letters read per browse; older rejects of busy agents are skipped
+/// once this many newer ones have been read
+const MAX_SCAN: usize = 10_000;
+
+/// Subject a rejected event is dead-lettered on
+pub fn reject_subject(tenant_id: Option<&str>, agent_id: &str) -> String {
+    format!(
+        "{}.{}.{}",
+        REJECTS_SUBJECT_PREFIX,
+        tenant_id.unwrap_or(DEFAULT_TENANT),
+        agent_id
+    )
+}
+
+/// Whether a rejection is about the event itself, rather than the server's
+/// capacity or the caller's admission, and so worth keeping
+pub fn dead_letters(code: ErrorCode) -> bool {
+    matches!(
+        code,
+        ErrorCode::MissingField
+            | ErrorCode::InvalidEvent
+            | ErrorCode::HashMismatch
+            | ErrorCode::PublicKeyInvalid
+            | ErrorCode::SignatureInvalid
+            | ErrorCode::KeyNotRegistered
+            | ErrorCode::KeyRevoked
+            | ErrorCode::KeyNotYetValid
+            | ErrorCode::KeyExpired
+            | ErrorCode::VerificationMethodInvalid
+            | ErrorCode::UnsupportedAlgorithm
+            | ErrorCode::CanonicalVersionRetired
+            | ErrorCode::SchemaViolation
+            | ErrorCode::PolicyViolation
+            | ErrorCode::EventStale
+            | ErrorCode::EventFromFuture
+            | ErrorCode::EventReplayed
+            | ErrorCode::FactoIdConflict
+    )
+}
+
+/// A rejected event as kept in FACTO_REJECTS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectRecord {
+    pub facto_id: String,
+    pub agent_id: String,
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sandbox: bool,
+    /// Nanoseconds since the epoch
+    pub rejected_at: i64,
+    pub error: EventError,
+    /// The canonical form the server built from the event, absent when it
+    /// could not build one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canonical_form: Option<String>,
+    /// SHA3-256 of `canonical_form`, to compare with `proof.event_hash`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub computed_hash: Option<String>,
+    /// The event as the server received it
+    pub event: FactoEvent,
+}
+
+impl RejectRecord {
+    pub fn new(
+        tenant_id: Option<String>,
+        sandbox: bool,
+        event: FactoEvent,
+        error: EventError,
+    ) -> Self {
+        Self {
+            facto_id: event.facto_id.clone(),
+            agent_id: event.agent_id.clone(),
+            session_id: event.session_id.clone(),
+            tenant_id,
+            sandbox,
+            rejected_at: now_nanos(),
+            error,
+            canonical_form: None,
+            computed_hash: None,
+            event,
+        }
+    }
+
+    /// Add the canonical form and hash the server computes for the event
+    pub fn with_server_hash(mut self) -> Self {
+        self.canonical_form = build_canonical_form(&self.event).ok();
+        self.computed_hash = self.canonical_form.as_deref().map(compute_event_hash);
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StoredReject {
+    /// Sequence in FACTO_REJECTS
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub record: RejectRecord,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RejectsPage {
+    /// Newest first
+    pub rejects: Vec<StoredReject>,
+    /// Set when older rejects were not read
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RejectsError {
+    #[error("invalid agent_id")]
+    InvalidAgent,
+    #[error("NATS is not connected")]
+    Unavailable,
+    #[error("NATS error: {0}")]
+    Nats(String),
+}
+
+impl IntoResponse for RejectsError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            RejectsError::InvalidAgent => StatusCode::BAD_REQUEST,
+            RejectsError::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            RejectsError::Nats(_) => StatusCode::BAD_GATEWAY,
+        };
+        error_response(status, self)
+    }
+}
+
+fn nats_error(e: impl ToString) -> RejectsError {
+    RejectsError::Nats(e.to_string())
+}
+
+// ============================================================================
+// Rejects
+// ============================================================================
+
+/// The dead-letter stream of rejected events
+pub struct Rejects {
+    retention: Duration,
+    max_bytes: i64,
+    sender: mpsc::Sender<RejectRecord>,
+    jetstream: RwLock<Option<jetstream::Context>>,
+}
+
+impl Rejects {
+    /// Rejects kept for `retention` or until the stream holds `max_bytes`,
+    /// with up to `queue_capacity` waiting to be published
+    pub fn new(
+        retention: Duration,
+        max_bytes: i64,
+        queue_capacity: usize,
+    ) -> (Arc<Self>, mpsc::Receiver<RejectRecord>) {
+        let (sender, receiver) = mpsc::channel(queue_capacity.max(1));
+        let rejects = Arc::new(Self {
+            retention,
+            max_bytes,
+            sender,
+            jetstream: RwLock::new(None),
+        });
+        (rejects, receiver)
+    }
+
+    pub fn stream_config(&self) -> jetstream::stream::Config {
+        jetstream::stream::Config {
+            name: REJECTS_STREAM.to_string(),
+            subjects: vec![format!("{}.>", REJECTS_SUBJECT_PREFIX)],
+            storage: jetstream::stream::StorageType::File,
+            max_age: self.retention,
+            max_bytes: self.max_bytes,
+            ..Default::default()
+        }
+    }
+
+    /// Use a newly connected JetStream context
+    pub fn connect(&self, jetstream: jetstream::Context) {
+        *self.jetstream.write().unwrap() = Some(jetstream);
+    }
+
+    fn context(&self) -> Result<jetstream::Context, RejectsError> {
+        self.jetstream
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or(RejectsError::Unavailable)
+    }
+
+    /// Queue a rejected event for the dead-letter stream, if its rejection
+    /// is one worth keeping
+    pub fn record(
+        &self,
+        tenant_id: Option<&str>,
+        sandbox: bool,
+        event: FactoEvent,
+        error: &EventError,
+    ) {
+        if !dead_letters(error.code) {
+            return;
+        }
+        let record =
+            RejectRecord::new(tenant_id.map(str::to_string), sandbox, event, error.clone());
+        if self.sender.try_send(record).is_err() {
+            counter!("facto_rejects_dropped_total", "reason" => "queue_full").increment(1);
+        }
+    }
+
+    /// Publish queued dead letters until the queue closes. Canonical forms
+    /// are built here rather than on the request path.
+    pub async fn run(self: Arc<Self>, mut records: mpsc::Receiver<RejectRecord>) {
+        while let Some(record) = records.recv().await {
+            let record = record.with_server_hash();
+            let Ok(jetstream) = self.context() else {
+                counter!("facto_rejects_dropped_total", "reason" => "unavailable").increment(1);
+                continue;
+            };
+            let subject = reject_subject(record.tenant_id.as_deref(), &record.agent_id);
+            let payload = match serde_json::to_vec(&record) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    warn!("Failed to encode dead letter {}: {}", record.facto_id, e);
+                    counter!("facto_rejects_dropped_total", "reason" => "encode").increment(1);
+                    continue;
+                }
+            };
+            let published = match jetstream.publish(subject, payload.into()).await {
+                Ok(ack) => ack.await.map(|_| ()).map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            match published {
+                Ok(()) => counter!(
+                    "facto_rejects_published_total",
+                    "reason" => record.error.code.as_str()
+                )
+                .increment(1),
+                Err(e) => {
+                    warn!("Failed to publish dead letter {}: {}", record.facto_id, e);
+                    counter!("facto_rejects_dropped_total", "reason" => "publish").increment(1);
+                }
+            }
+        }
+    }
+
+    /// The newest rejects of an agent, newest first
+    pub async fn recent(
+        &self,
+        tenant_id: Option<&str>,
+        agent_id: &str,
+        limit: usize,
+    ) -> Result<RejectsPage, RejectsError> {
+        if agent_id.is_empty() {
+            return Err(RejectsError::InvalidAgent);
+        }
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        let stream = self
+            .context()?
+            .get_stream(REJECTS_STREAM)
+            .await
+            .map_err(nats_error)?;
+        let mut messages = stream
+            .create_consumer(pull::Config {
+                filter_subject: reject_subject(tenant_id, agent_id),
+                deliver_policy: DeliverPolicy::All,
+                ack_policy: AckPolicy::None,
+                inactive_threshold: Duration::from_secs(30),
+                ..Default::default()
+            })
+            .await
+            .map_err(nats_error)?
+            .fetch()
+            .max_messages(MAX_SCAN)
+            .messages()
+            .await
+            .map_err(nats_error)?;
+
+        // Keep the newest `limit` of up to MAX_SCAN rejects, dropping the
+        // oldest as newer ones arrive
+        let mut newest = VecDeque::with_capacity(limit);
+        let mut scanned = 0;
+        let mut pending = 0;
+        while let Some(message) = messages.next().await {
+            let message = message.map_err(nats_error)?;
+            let info = message.info().map_err(nats_error)?;
+            let sequence = info.stream_sequence;
+            pending = info.pending;
+            scanned += 1;
+            let Ok(record) = serde_json::from_slice::<RejectRecord>(&message.payload) else {
+                continue;
+            };
+            if newest.len() == limit {
+                newest.pop_front();
+            }
+            newest.push_back(StoredReject { sequence, record });
+        }
+        Ok(RejectsPage {
+            rejects: newest.into_iter().rev().collect(),
+            truncated: scanned == MAX_SCAN && pending > 0,
+        })
+    }
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct RejectsQuery {
+    pub agent_id: String,
+    /// Tenant to browse, for principals not bound to one
+    pub tenant_id: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Dead letters hold whole events, so browsing them needs the debug scope.
+/// Tenant-bound callers only see their tenant's rejects and agent-bound
+/// callers only their own agent's.
+pub async fn list_rejects_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<RejectsQuery>,
+) -> Response {
+    let Some(ref rejects) = state.rejects else {
+        return error_response(StatusCode::NOT_FOUND, "Dead-lettering is not enabled");
+    };
+    let principal = match state.auth.authorize(&headers, Scope::Debug).await {
+        Ok(principal) => principal,
+        Err(e) => return e.into_response(),
+    };
+    if principal
+        .agent_id
+        .as_ref()
+        .is_some_and(|agent_id| *agent_id != query.agent_id)
+    {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "Credentials are bound to another agent",
+        );
+    }
+    let tenant_id = match (principal.tenant_id, query.tenant_id) {
+        (Some(bound), Some(requested)) if bound != requested => {
+            return error_response(
+                StatusCode::FORBIDDEN,
+                "Credentials are bound to another tenant",
+            )
+        }
+        (Some(bound), _) => Some(bound),
+        (None, Some(requested)) => {
+            if let Err(e) = validate_tenant_id(&requested) {
+                return error_response(StatusCode::BAD_REQUEST, e);
+            }
+            Some(requested)
+        }
+        (None, None) => None,
+    };
+
+    match rejects
+        .recent(
+            tenant_id.as_deref(),
+            &query.agent_id,
+            query.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+        )
+        .await
+    {
+        Ok(page) => (StatusCode::OK, Json(page)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_event;
+
+    #[test]
+    fn test_record_carries_server_hash() {
+        let mut event = test_event();
+        event.proof.event_hash = "f".repeat(64);
+        let canonical = build_canonical_form(&event).unwrap();
+
+        let record = RejectRecord::new(
+            None,
+            false,
+            event,
+            EventError::new(ErrorCode::HashMismatch, "hash mismatch"),
+        )
+        .with_server_hash();
+        assert_eq!(record.canonical_form.as_deref(), Some(canonical.as_str()));
+        assert_eq!(record.computed_hash, Some(compute_event_hash(&canonical)));
+        assert_eq!(
+            reject_subject(None, &record.agent_id),
+            "facto.rejects.default.agent-test"
+        );
+
+        let decoded: RejectRecord =
+            serde_json::from_slice(&serde_json::to_vec(&record).unwrap()).unwrap();
+        assert_eq!(decoded.error, record.error);
+        assert!(dead_letters(ErrorCode::SignatureInvalid));
+        assert!(!dead_letters(ErrorCode::RateLimited));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::protocol::ReplayRejection;
+use axum::async_trait;
+use dashmap::{mapref::entry::Entry, DashMap};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::shared::{self, KvBucket, Swap};
+
+/// Bucket holding the hashes of accepted events until they are stale
+pub const REPLAY_BUCKET: &str = "facto_replay";
+
+/// Claims between sweeps of expired seen-hash entries
+const SWEEP_INTERVAL: usize = 4096;
+
+/// Freshness checks and the record of accepted event hashes
+#[async_trait]
+pub trait ReplayStore: Send + Sync {
+    /// Check that `completed_at` lies within the freshness window
+    fn check_fresh(&self, completed_at: i64, now: i64) -> Result<(), ReplayRejection>;
+
+    /// Record an accepted event hash, refusing hashes already recorded. A
+    /// claim must be released with [`ReplayStore::release`] if the event is
+    /// not accepted after all.
+    async fn claim(&self, key: &str, completed_at: i64, now: i64) -> Result<(), ReplayRejection>;
+
+    /// Forget a claim whose event was not accepted
+    async fn release(&self, key: &str);
+}
+
+/// Rejects captured events that are re-submitted.
+///
+/// An event is fresh while its `completed_at` lies within `max_age` before
+/// server time and at most `max_skew` after it. Hashes of accepted events are
+/// remembered until their event would no longer be fresh, so every replay is
+/// caught either as stale or as seen. Retries within the dedup window are
+/// answered as duplicates before the seen-hash check.
+pub struct ReplayGuard {
+    /// Freshness window in nanoseconds; replay protection is off if unset
+    max_age: Option<i64>,
+    max_skew: i64,
+    /// Event hash -> time after which the event is stale anyway
+    seen: DashMap<String, i64>,
+    claims: AtomicUsize,
+}
+
+impl ReplayGuard {
+    pub fn new(max_age: Option<Duration>, max_skew: Duration) -> Self {
+        Self {
+            max_age: max_age.map(|d| d.as_nanos() as i64),
+            max_skew: max_skew.as_nanos() as i64,
+            seen: DashMap::new(),
+            claims: AtomicUsize::new(0),
+        }
+    }
+
+    /// Check that `completed_at` lies within the freshness window
+    pub fn check_fresh(&self, completed_at: i64, now: i64) -> Result<(), ReplayRejection> {
+        let Some(max_age) = self.max_age else {
+            return Ok(());
+        };
+        if completed_at.saturating_sub(now) > self.max_skew {
+            Err(ReplayRejection::FromFuture)
+        } else if now.saturating_sub(completed_at) > max_age {
+            Err(ReplayRejection::Stale)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Record an accepted event hash, refusing hashes already recorded. A
+    /// claim must be released with [`ReplayGuard::release`] if the event is
+    /// not accepted after all.
+    pub fn claim(&self, key: &str, completed_at: i64, now: i64) -> Result<(), ReplayRejection> {
+        let Some(max_age) = self.max_age else {
+            return Ok(());
+        };
+        if self
+            .claims
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(SWEEP_INTERVAL)
+        {
+            self.seen.retain(|_, expires_at| *expires_at >= now);
+        }
+
+        let expires_at = completed_at.saturating_add(max_age);
+        match self.seen.entry(key.to_string()) {
+            Entry::Occupied(mut entry) => {
+                if *entry.get() >= now {
+                    return Err(ReplayRejection::Replayed);
+                }
+                entry.insert(expires_at);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(expires_at);
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget a claim whose event was not accepted
+    pub fn release(&self, key: &str) {
+        self.seen.remove(key);
+    }
+}
+
+#[async_trait]
+impl ReplayStore for ReplayGuard {
+    fn check_fresh(&self, completed_at: i64, now: i64) -> Result<(), ReplayRejection> {
+        ReplayGuard::check_fresh(self, completed_at, now)
+    }
+
+    async fn claim(&self, key: &str, completed_at: i64, now: i64) -> Result<(), ReplayRejection> {
+        ReplayGuard::claim(self, key, completed_at, now)
+    }
+
+    async fn release(&self, key: &str) {
+        ReplayGuard::release(self, key)
+    }
+}
+
+/// Seen event hashes shared by all replicas, so a replay cannot slip past
+/// by reaching a replica that did not accept the original
+pub struct KvReplayGuard {
+    bucket: Arc<KvBucket>,
+    local: ReplayGuard,
+}
+
+impl KvReplayGuard {
+    pub fn new(max_age: Duration, max_skew: Duration) -> Self {
+        Self {
+            // Hashes are remembered until `completed_at + max_age`, which is
+            // at most `max_age + max_skew` after they were accepted
+            bucket: Arc::new(KvBucket::new(REPLAY_BUCKET, max_age + max_skew)),
+            local: ReplayGuard::new(Some(max_age), max_skew),
+        }
+    }
+
+    pub fn bucket(&self) -> Arc<KvBucket> {
+        self.bucket.clone()
+    }
+}
+
+#[async_trait]
+impl ReplayStore for KvReplayGuard {
+    fn check_fresh(&self, completed_at: i64, now: i64) -> Result<(), ReplayRejection> {
+        self.local.check_fresh(completed_at, now)
+    }
+
+    async fn claim(&self, key: &str, completed_at: i64, now: i64) -> Result<(), ReplayRejection> {
+        let Some(max_age) = self.local.max_age else {
+            return Ok(());
+        };
+        let expires_at = completed_at.saturating_add(max_age);
+        let outcome = self
+            .bucket
+            .swap(key, |current| {
+                let seen_until = current
+                    .and_then(|v| std::str::from_utf8(v).ok())
+                    .and_then(|v| v.parse::<i64>().ok());
+                match seen_until {
+                    Some(seen_until) if seen_until >= now => {
+                        Swap::Keep(Err(ReplayRejection::Replayed))
+                    }
+                    _ => Swap::Write(expires_at.to_string().into_bytes(), Ok(())),
+                }
+            })
+            .await;
+        match outcome {
+            Ok(claimed) => claimed,
+            Err(e) => {
+                shared::fallback(e);
+                self.local.claim(key, completed_at, now)
+            }
+        }
+    }
+
+    async fn release(&self, key: &str) {
+        self.local.release(key);
+        if let Err(e) = self.bucket.delete(key).await {
+            shared::fallback(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: i64 = 1_000_000_000;
+
+    fn guard() -> ReplayGuard {
+        ReplayGuard::new(Some(Duration::from_secs(300)), Duration::from_secs(30))
+    }
+
+    #[test]
+    fn test_freshness_window() {
+        let guard = guard();
+        let now = 1_000_000 * SECOND;
+
+        assert!(guard.check_fresh(now - 299 * SECOND, now).is_ok());
+        assert!(guard.check_fresh(now + 29 * SECOND, now).is_ok());
+        assert_eq!(
+            guard.check_fresh(now - 301 * SECOND, now),
+            Err(ReplayRejection::Stale)
+        );
+        assert_eq!(
+            guard.check_fresh(now + 31 * SECOND, now),
+            Err(ReplayRejection::FromFuture)
+        );
+
+        let disabled = ReplayGuard::new(None, Duration::ZERO);
+        assert!(disabled.check_fresh(0, now).is_ok());
+    }
+
+    #[test]
+    fn test_seen_hashes_expire_with_the_window() {
+        let guard = guard();
+        let completed_at = 1_000_000 * SECOND;
+
+        assert!(guard.claim("hash-a", completed_at, completed_at).is_ok());
+        assert_eq!(
+            guard.claim("hash-a", completed_at, completed_at + 10 * SECOND),
+            Err(ReplayRejection::Replayed)
+        );
+        assert!(guard.claim("hash-b", completed_at, completed_at).is_ok());
+
+        // Once the event is stale the freshness check takes over
+        assert!(guard
+            .claim("hash-a", completed_at, completed_at + 301 * SECOND)
+            .is_ok());
+
+        guard.release("hash-b");
+        assert!(guard.claim("hash-b", completed_at, completed_at).is_ok());
+    }
+}
@@ -0,0 +1,142 @@
+//! Resolution of the DIDs event proofs name as their verification method.
+//!
+//! `did:key` DIDs resolve offline. `did:web` DIDs are only resolved on the
+//! hosts listed in DID_WEB_HOSTS, so agents cannot make the server fetch
+//! arbitrary URLs; their documents are cached for DID_CACHE_TTL_SECS, and
+//! failed fetches for a short while, so a burst of events costs one fetch.
+
+use crate::did::{DidDocument, DidError, DidKey, DidUrl};
+use dashmap::DashMap;
+use metrics::counter;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// How long a failed document fetch is remembered
+const FAILURE_TTL: Duration = Duration::from_secs(30);
+
+/// Most DID documents held at once
+const MAX_CACHED_DOCUMENTS: usize = 10_000;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct DidResolver {
+    http: reqwest::Client,
+    web_hosts: Vec<String>,
+    ttl: Duration,
+    /// DID -> when fetched, and the document or why fetching failed
+    documents: DashMap<String, (Instant, Result<DidDocument, String>)>,
+}
+
+impl DidResolver {
+    pub fn new(web_hosts: Vec<String>, ttl: Duration) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(FETCH_TIMEOUT)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("Failed to build DID resolver HTTP client"),
+            web_hosts,
+            ttl,
+            documents: DashMap::new(),
+        }
+    }
+
+    /// Whether `did:web` DIDs are resolved
+    pub fn resolves_web(&self) -> bool {
+        !self.web_hosts.is_empty()
+    }
+
+    /// The key a verification method names
+    pub async fn resolve(&self, verification_method: &str) -> Result<DidKey, DidError> {
+        let url = DidUrl::parse(verification_method)?;
+        match url.method.as_str() {
+            "key" => url.did_key(),
+            "web" => {
+                let host = url.web_host()?;
+                if !self.web_hosts.contains(&host) {
+                    return Err(DidError::Resolution {
+                        did: url.did.clone(),
+                        reason: format!("{} is not an allowed did:web host", host),
+                    });
+                }
+                self.document(&url).await?.key(&url)
+            }
+            method => Err(DidError::UnsupportedMethod(method.to_string())),
+        }
+    }
+
+    /// The DID document of a `did:web` DID, from the cache while fresh
+    async fn document(&self, url: &DidUrl) -> Result<DidDocument, DidError> {
+        if let Some(entry) = self.documents.get(&url.did) {
+            let (fetched_at, ref outcome) = *entry;
+            let ttl = match outcome {
+                Ok(_) => self.ttl,
+                Err(_) => FAILURE_TTL,
+            };
+            if fetched_at.elapsed() < ttl {
+                counter!("facto_did_resolutions_total", "outcome" => "cached").increment(1);
+                return outcome.clone().map_err(|reason| DidError::Resolution {
+                    did: url.did.clone(),
+                    reason,
+                });
+            }
+        }
+
+        let outcome = self.fetch(url).await;
+        counter!(
+            "facto_did_resolutions_total",
+            "outcome" => if outcome.is_ok() { "fetched" } else { "failed" }
+        )
+        .increment(1);
+        if self.documents.len() >= MAX_CACHED_DOCUMENTS {
+            self.documents
+                .retain(|_, (fetched_at, _)| fetched_at.elapsed() < self.ttl);
+        }
+        if self.documents.len() < MAX_CACHED_DOCUMENTS {
+            self.documents
+                .insert(url.did.clone(), (Instant::now(), outcome.clone()));
+        }
+        outcome.map_err(|reason| DidError::Resolution {
+            did: url.did.clone(),
+            reason,
+        })
+    }
+
+    async fn fetch(&self, url: &DidUrl) -> Result<DidDocument, String> {
+        let location = url.web_document_url().map_err(|e| e.to_string())?;
+        let document: DidDocument = self
+            .http
+            .get(&location)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string())?;
+        info!("Fetched the DID document of {}", url.did);
+        Ok(document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_did_web_hosts_are_allowlisted() {
+        let resolver = DidResolver::new(vec!["example.com".to_string()], Duration::from_secs(60));
+        assert!(matches!(
+            resolver.resolve("did:web:evil.example#key-1").await,
+            Err(DidError::Resolution { .. })
+        ));
+        assert!(matches!(
+            resolver.resolve("did:example:123").await,
+            Err(DidError::UnsupportedMethod(_))
+        ));
+        assert!(resolver
+            .resolve("did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp")
+            .await
+            .is_ok());
+    }
+}
@@ -0,0 +1,300 @@
+//! Retention policies and legal holds for stored events.
+//!
+//! The ingestion server does not store events itself. It keeps the policies
+//! and holds set through the admin API and publishes every change on
+//! [`RETENTION_SUBJECT`], from which the indexer applies them: events are
+//! expired once older than their agent's or tenant's retention, except in
+//! sessions under a legal hold. Each change carries the full policy or hold,
+//! so applying one twice is harmless.
+
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf, sync::RwLock};
+use tracing::info;
+
+use crate::scoped_id;
+use crate::store::JsonFile;
+use crate::tenants::{validate_tenant_id, DEFAULT_TENANT};
+use crate::verification::now_nanos;
+
+/// NATS subject retention policy and legal hold changes are published on
+pub const RETENTION_SUBJECT: &str = "facto.control.retention";
+
+// ============================================================================
+// Records
+// ============================================================================
+
+/// How long a tenant's events are kept, in days from when they completed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionRequest {
+    /// Retention of the tenant's agents without their own; events are kept
+    /// indefinitely when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub days: Option<u32>,
+    /// Retention of single agents, in place of the tenant's
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub agents: BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// The tenant, or `default` for events accepted without one
+    pub tenant_id: String,
+    #[serde(flatten)]
+    pub policy: RetentionRequest,
+    pub updated_at: i64,
+}
+
+/// Admin request to place a legal hold on a session
+#[derive(Debug, Clone, Deserialize)]
+pub struct LegalHoldRequest {
+    pub reason: String,
+}
+
+/// A session whose events are exempt from expiry until the hold is released
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LegalHold {
+    pub tenant_id: String,
+    pub session_id: String,
+    pub reason: String,
+    pub placed_by: String,
+    pub placed_at: i64,
+}
+
+/// A change published on [`RETENTION_SUBJECT`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RetentionControl {
+    Policy(RetentionPolicy),
+    PolicyRemoved {
+        tenant_id: String,
+        at: i64,
+    },
+    LegalHold(LegalHold),
+    LegalHoldReleased {
+        tenant_id: String,
+        session_id: String,
+        released_by: String,
+        at: i64,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RetentionError {
+    #[error("invalid retention policy: {0}")]
+    InvalidPolicy(String),
+    #[error("no retention policy for tenant: {0}")]
+    Unknown(String),
+    #[error("legal hold needs a reason")]
+    EmptyReason,
+    #[error("session {0} is not under a legal hold")]
+    NotHeld(String),
+    #[error("failed to persist retention: {0}")]
+    Persistence(String),
+}
+
+// ============================================================================
+// Registry
+// ============================================================================
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RetentionState {
+    policies: BTreeMap<String, RetentionPolicy>,
+    /// By tenant and session
+    #[serde(default)]
+    holds: BTreeMap<String, LegalHold>,
+}
+
+/// Retention policies by tenant and legal holds by session, persisted
+/// across restarts
+pub struct Retention {
+    state: RwLock<RetentionState>,
+    store: JsonFile,
+}
+
+impl Retention {
+    pub fn new(path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let store = JsonFile::new(path);
+        let state: RetentionState = store.load()?;
+        info!(
+            "Loaded {} retention policies and {} legal holds",
+            state.policies.len(),
+            state.holds.len()
+        );
+        Ok(Self {
+            state: RwLock::new(state),
+            store,
+        })
+    }
+
+    pub fn policies(&self) -> Vec<RetentionPolicy> {
+        self.state
+            .read()
+            .unwrap()
+            .policies
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub fn policy(&self, tenant_id: &str) -> Option<RetentionPolicy> {
+        self.state.read().unwrap().policies.get(tenant_id).cloned()
+    }
+
+    /// Legal holds of one tenant or all
+    pub fn holds(&self, tenant_id: Option<&str>) -> Vec<LegalHold> {
+        self.state
+            .read()
+            .unwrap()
+            .holds
+            .values()
+            .filter(|hold| tenant_id.is_none_or(|t| t == hold.tenant_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Create or replace a tenant's retention policy
+    pub fn set_policy(
+        &self,
+        tenant_id: &str,
+        request: RetentionRequest,
+    ) -> Result<RetentionPolicy, RetentionError> {
+        check_tenant(tenant_id)?;
+        if request.days.is_none() && request.agents.is_empty() {
+            return Err(RetentionError::InvalidPolicy(
+                "set days, agents or both".to_string(),
+            ));
+        }
+        let zero = request
+            .days
+            .into_iter()
+            .chain(request.agents.values().copied())
+            .any(|days| days == 0);
+        if zero {
+            return Err(RetentionError::InvalidPolicy(
+                "retention must be at least one day".to_string(),
+            ));
+        }
+        let policy = RetentionPolicy {
+            tenant_id: tenant_id.to_string(),
+            policy: request,
+            updated_at: now_nanos(),
+        };
+        self.update(|state| {
+            state.policies.insert(tenant_id.to_string(), policy.clone());
+            Ok(())
+        })?;
+        Ok(policy)
+    }
+
+    pub fn remove_policy(&self, tenant_id: &str) -> Result<RetentionPolicy, RetentionError> {
+        self.update(|state| {
+            state
+                .policies
+                .remove(tenant_id)
+                .ok_or_else(|| RetentionError::Unknown(tenant_id.to_string()))
+        })
+    }
+
+    /// Place a legal hold on a session, or replace the reason of its hold
+    pub fn hold(
+        &self,
+        tenant_id: &str,
+        session_id: &str,
+        request: LegalHoldRequest,
+        admin: &str,
+    ) -> Result<LegalHold, RetentionError> {
+        check_tenant(tenant_id)?;
+        if request.reason.trim().is_empty() {
+            return Err(RetentionError::EmptyReason);
+        }
+        let hold = LegalHold {
+            tenant_id: tenant_id.to_string(),
+            session_id: session_id.to_string(),
+            reason: request.reason,
+            placed_by: admin.to_string(),
+            placed_at: now_nanos(),
+        };
+        self.update(|state| {
+            state
+                .holds
+                .insert(scoped_id(Some(tenant_id), session_id), hold.clone());
+            Ok(())
+        })?;
+        Ok(hold)
+    }
+
+    pub fn release(&self, tenant_id: &str, session_id: &str) -> Result<LegalHold, RetentionError> {
+        self.update(|state| {
+            state
+                .holds
+                .remove(&scoped_id(Some(tenant_id), session_id))
+                .ok_or_else(|| RetentionError::NotHeld(session_id.to_string()))
+        })
+    }
+
+    /// Apply a change to a copy of the state, and keep it once saved
+    fn update<T>(
+        &self,
+        change: impl FnOnce(&mut RetentionState) -> Result<T, RetentionError>,
+    ) -> Result<T, RetentionError> {
+        let mut state = self.state.write().unwrap();
+        let mut updated = state.clone();
+        let result = change(&mut updated)?;
+        self.store
+            .save(&updated)
+            .map_err(|e| RetentionError::Persistence(e.to_string()))?;
+        *state = updated;
+        Ok(result)
+    }
+}
+
+fn check_tenant(tenant_id: &str) -> Result<(), RetentionError> {
+    if tenant_id == DEFAULT_TENANT {
+        return Ok(());
+    }
+    validate_tenant_id(tenant_id).map_err(|e| RetentionError::InvalidPolicy(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policies_and_holds_are_kept_per_tenant() {
+        let retention = Retention::new(None).unwrap();
+        let policy = retention
+            .set_policy(
+                "acme",
+                RetentionRequest {
+                    days: Some(90),
+                    agents: BTreeMap::from([("agent-1".to_string(), 30)]),
+                },
+            )
+            .unwrap();
+        assert_eq!(retention.policy("acme"), Some(policy.clone()));
+        assert!(retention
+            .set_policy("acme", RetentionRequest::default())
+            .is_err());
+
+        let control = serde_json::to_value(RetentionControl::Policy(policy)).unwrap();
+        assert_eq!(control["type"], "policy");
+        assert_eq!(control["days"], 90);
+        assert_eq!(control["agents"]["agent-1"], 30);
+
+        let reason = |reason: &str| LegalHoldRequest {
+            reason: reason.to_string(),
+        };
+        retention
+            .hold("acme", "s1", reason("case 7"), "alice")
+            .unwrap();
+        retention
+            .hold(DEFAULT_TENANT, "s1", reason("case 8"), "alice")
+            .unwrap();
+        assert!(retention.hold("acme", "s2", reason(" "), "alice").is_err());
+        assert_eq!(retention.holds(Some("acme")).len(), 1);
+
+        retention.release("acme", "s1").unwrap();
+        assert!(retention.release("acme", "s1").is_err());
+        assert_eq!(retention.holds(None)[0].reason, "case 8");
+    }
+}
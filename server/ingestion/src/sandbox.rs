@@ -0,0 +1,82 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::registry::KeyRegistry;
+use crate::tenants::{self, DEFAULT_TENANT};
+use crate::verification::Verifier;
+
+/// Stream holding sandbox events, apart from FACTO_EVENTS
+pub const SANDBOX_STREAM: &str = "FACTO_SANDBOX";
+
+/// Subjects of the FACTO_SANDBOX stream
+pub const SANDBOX_STREAM_SUBJECTS: [&str; 1] = ["facto.sandbox.>"];
+
+/// NATS subject a sandbox event is published on: the production subject
+/// under `facto.sandbox.`, so production consumers never see it
+pub fn sandbox_subject(tenant_id: Option<&str>, agent_id: &str) -> String {
+    let subject = tenants::event_subject(tenant_id, agent_id);
+    format!(
+        "facto.sandbox.{}",
+        subject.strip_prefix("facto.").unwrap_or(&subject)
+    )
+}
+
+/// Scope for rate limits and deduplication of sandbox events, so SDK tests
+/// never collide with a tenant's production facto_ids
+pub fn sandbox_scope(tenant_id: Option<&str>) -> String {
+    format!("sandbox:{}", tenant_id.unwrap_or(DEFAULT_TENANT))
+}
+
+/// Sandbox mode for callers whose credentials are marked `sandbox`.
+///
+/// Sandbox events are verified against a synthetic, in-memory key registry
+/// that trusts the embedded key, skip freshness, replay and freeze checks,
+/// and are published to their own stream that expires them after
+/// `retention`. They never advance production chain heads, enter
+/// checkpoints or reach fan-out sinks.
+pub struct Sandbox {
+    pub verifier: Verifier,
+    pub retention: Duration,
+}
+
+impl Sandbox {
+    /// Sandbox verifying with `verifier`'s signer and limits
+    pub fn new(verifier: &Verifier, retention: Duration) -> anyhow::Result<Self> {
+        let registry = Arc::new(KeyRegistry::new(None, false)?);
+        Ok(Self {
+            verifier: verifier.with_registry(registry),
+            retention,
+        })
+    }
+
+    pub fn stream_config(&self) -> async_nats::jetstream::stream::Config {
+        async_nats::jetstream::stream::Config {
+            name: SANDBOX_STREAM.to_string(),
+            subjects: SANDBOX_STREAM_SUBJECTS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            storage: async_nats::jetstream::stream::StorageType::File,
+            max_age: self.retention,
+            max_bytes: 1024 * 1024 * 1024, // 1GB
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_subjects() {
+        assert_eq!(
+            sandbox_subject(None, "agent-1"),
+            "facto.sandbox.events.agent-1"
+        );
+        assert_eq!(
+            sandbox_subject(Some("acme"), "agent-1"),
+            "facto.sandbox.tenants.acme.events.agent-1"
+        );
+        assert_ne!(sandbox_scope(Some("acme")), "acme");
+    }
+}
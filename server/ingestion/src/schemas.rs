@@ -0,0 +1,702 @@
+use crate::protocol::FactoEvent;
+use dashmap::DashMap;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+use tracing::info;
+
+use crate::store::JsonFile;
+use crate::verification::now_nanos;
+
+/// Violations reported per payload; the rest are counted but not listed
+pub const MAX_REPORTED_VIOLATIONS: usize = 20;
+
+/// Keywords a schema may not use because the validator does not implement them
+const UNSUPPORTED_KEYWORDS: [&str; 4] = ["$ref", "$dynamicRef", "if", "patternProperties"];
+
+const TYPES: [&str; 7] = [
+    "null", "boolean", "object", "array", "number", "integer", "string",
+];
+
+// ============================================================================
+// Schema Configuration
+// ============================================================================
+
+/// What happens to events whose payloads violate their schema
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaMode {
+    /// Reject the event with the violations
+    #[default]
+    Enforce,
+    /// Accept the event and flag the violations in its server envelope
+    Audit,
+}
+
+impl SchemaMode {
+    /// Metrics label for the mode
+    pub fn code(&self) -> &'static str {
+        match self {
+            SchemaMode::Enforce => "enforce",
+            SchemaMode::Audit => "audit",
+        }
+    }
+}
+
+/// Schemas for the `input_data` and `output_data` of one action type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaRequest {
+    pub input_schema: Option<Value>,
+    pub output_schema: Option<Value>,
+    #[serde(default)]
+    pub mode: SchemaMode,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionSchema {
+    pub action_type: String,
+    #[serde(flatten)]
+    pub schemas: SchemaRequest,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SchemaError {
+    #[error("invalid schema: {0}")]
+    InvalidSchema(String),
+    #[error("no schema registered for action type: {0}")]
+    Unknown(String),
+    #[error("failed to persist schemas: {0}")]
+    Persistence(String),
+}
+
+/// An event's payloads did not conform to the schema of its action type
+#[derive(Debug, Clone)]
+pub struct SchemaViolations {
+    pub action_type: String,
+    pub mode: SchemaMode,
+    /// One `pointer: problem` line per violation, at most
+    /// [`MAX_REPORTED_VIOLATIONS`] of them
+    pub violations: Vec<String>,
+    pub total: usize,
+}
+
+impl fmt::Display for SchemaViolations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Event violates the {} schema: {}",
+            self.action_type,
+            self.violations.join("; ")
+        )?;
+        if self.total > self.violations.len() {
+            write!(f, " (and {} more)", self.total - self.violations.len())?;
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Schema Registry
+// ============================================================================
+
+struct Validators {
+    mode: SchemaMode,
+    input: Option<Validator>,
+    output: Option<Validator>,
+}
+
+/// Registered payload schemas by action type, with their compiled validators.
+///
+/// Events of action types without a schema are not checked.
+pub struct SchemaRegistry {
+    schemas: RwLock<BTreeMap<String, ActionSchema>>,
+    validators: DashMap<String, Arc<Validators>>,
+    store: JsonFile,
+}
+
+impl SchemaRegistry {
+    pub fn new(path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let store = JsonFile::new(path);
+        let schemas: BTreeMap<String, ActionSchema> = store.load()?;
+        info!("Loaded {} action type schemas", schemas.len());
+
+        let validators = DashMap::new();
+        for schema in schemas.values() {
+            validators.insert(
+                schema.action_type.clone(),
+                Arc::new(compile(&schema.schemas)?),
+            );
+        }
+
+        Ok(Self {
+            schemas: RwLock::new(schemas),
+            validators,
+            store,
+        })
+    }
+
+    pub fn list(&self) -> Vec<ActionSchema> {
+        self.schemas.read().unwrap().values().cloned().collect()
+    }
+
+    pub fn get(&self, action_type: &str) -> Option<ActionSchema> {
+        self.schemas.read().unwrap().get(action_type).cloned()
+    }
+
+    /// Create or replace an action type's schemas; applies immediately
+    pub fn upsert(
+        &self,
+        action_type: &str,
+        request: SchemaRequest,
+    ) -> Result<ActionSchema, SchemaError> {
+        if action_type.is_empty() {
+            return Err(SchemaError::InvalidSchema(
+                "action type must not be empty".to_string(),
+            ));
+        }
+        let validators = compile(&request)?;
+        let schema = ActionSchema {
+            action_type: action_type.to_string(),
+            schemas: request,
+            updated_at: now_nanos(),
+        };
+
+        let mut schemas = self.schemas.write().unwrap();
+        let mut updated = schemas.clone();
+        updated.insert(action_type.to_string(), schema.clone());
+        self.persist(&updated)?;
+        *schemas = updated;
+
+        self.validators
+            .insert(action_type.to_string(), Arc::new(validators));
+        Ok(schema)
+    }
+
+    pub fn remove(&self, action_type: &str) -> Result<ActionSchema, SchemaError> {
+        let mut schemas = self.schemas.write().unwrap();
+        let mut updated = schemas.clone();
+        let removed = updated
+            .remove(action_type)
+            .ok_or_else(|| SchemaError::Unknown(action_type.to_string()))?;
+        self.persist(&updated)?;
+        *schemas = updated;
+
+        self.validators.remove(action_type);
+        Ok(removed)
+    }
+
+    /// Check an event's payloads against the schema of its action type
+    pub fn check(&self, event: &FactoEvent) -> Result<(), SchemaViolations> {
+        let Some(validators) = self
+            .validators
+            .get(&event.action_type)
+            .map(|v| Arc::clone(&v))
+        else {
+            return Ok(());
+        };
+
+        let mut violations = Vec::new();
+        if let Some(input) = &validators.input {
+            input.validate(&event.input_data, "/input_data", &mut violations);
+        }
+        if let Some(output) = &validators.output {
+            output.validate(&event.output_data, "/output_data", &mut violations);
+        }
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        let total = violations.len();
+        violations.truncate(MAX_REPORTED_VIOLATIONS);
+        Err(SchemaViolations {
+            action_type: event.action_type.clone(),
+            mode: validators.mode,
+            violations,
+            total,
+        })
+    }
+
+    fn persist(&self, schemas: &BTreeMap<String, ActionSchema>) -> Result<(), SchemaError> {
+        self.store
+            .save(schemas)
+            .map_err(|e| SchemaError::Persistence(e.to_string()))
+    }
+}
+
+fn compile(request: &SchemaRequest) -> Result<Validators, SchemaError> {
+    if request.input_schema.is_none() && request.output_schema.is_none() {
+        return Err(SchemaError::InvalidSchema(
+            "at least one of input_schema and output_schema is required".to_string(),
+        ));
+    }
+    Ok(Validators {
+        mode: request.mode,
+        input: request
+            .input_schema
+            .clone()
+            .map(Validator::new)
+            .transpose()?,
+        output: request
+            .output_schema
+            .clone()
+            .map(Validator::new)
+            .transpose()?,
+    })
+}
+
+// ============================================================================
+// Validator
+// ============================================================================
+
+/// A JSON Schema with its patterns compiled up front.
+///
+/// Supports `type`, `enum`, `const`, `properties`, `required`,
+/// `additionalProperties`, `items`, `minItems`, `maxItems`, `minLength`,
+/// `maxLength`, `pattern`, `minimum`, `maximum`, `exclusiveMinimum`,
+/// `exclusiveMaximum`, `allOf`, `anyOf`, `oneOf` and `not`. Annotations such
+/// as `title` and `description` are ignored. Patterns use the `regex` crate's
+/// syntax, which has no lookaround or backreferences.
+pub struct Validator {
+    schema: Value,
+    patterns: HashMap<String, Regex>,
+}
+
+impl Validator {
+    pub fn new(schema: Value) -> Result<Self, SchemaError> {
+        let mut patterns = HashMap::new();
+        check_schema(&schema, "#", &mut patterns)?;
+        Ok(Self { schema, patterns })
+    }
+
+    /// Append a violation for every way `value` fails the schema
+    pub fn validate(&self, value: &Value, pointer: &str, violations: &mut Vec<String>) {
+        self.validate_at(&self.schema, value, pointer, violations);
+    }
+
+    fn conforms(&self, schema: &Value, value: &Value) -> bool {
+        let mut violations = Vec::new();
+        self.validate_at(schema, value, "", &mut violations);
+        violations.is_empty()
+    }
+
+    fn validate_at(&self, schema: &Value, value: &Value, pointer: &str, out: &mut Vec<String>) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => {
+                out.push(format!("{}: no value is allowed", display(pointer)));
+                return;
+            }
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+        let at = display(pointer);
+
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&str> = match types {
+                Value::String(t) => vec![t.as_str()],
+                Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !types.iter().any(|t| has_type(value, t)) {
+                out.push(format!(
+                    "{}: expected {}, got {}",
+                    at,
+                    types.join(" or "),
+                    type_name(value)
+                ));
+                return;
+            }
+        }
+        if let Some(Value::Array(allowed)) = schema.get("enum") {
+            if !allowed.contains(value) {
+                out.push(format!("{}: value is not one of the allowed values", at));
+            }
+        }
+        if let Some(constant) = schema.get("const") {
+            if constant != value {
+                out.push(format!("{}: value must be {}", at, constant));
+            }
+        }
+
+        match value {
+            Value::String(s) => {
+                let length = s.chars().count() as u64;
+                if let Some(min) = uint(schema, "minLength").filter(|min| length < *min) {
+                    out.push(format!("{}: shorter than {} characters", at, min));
+                }
+                if let Some(max) = uint(schema, "maxLength").filter(|max| length > *max) {
+                    out.push(format!("{}: longer than {} characters", at, max));
+                }
+                if let Some(Value::String(pattern)) = schema.get("pattern") {
+                    if self.patterns.get(pattern).is_some_and(|re| !re.is_match(s)) {
+                        out.push(format!("{}: does not match pattern {}", at, pattern));
+                    }
+                }
+            }
+            Value::Number(n) => {
+                let n = n.as_f64().unwrap_or_default();
+                let bound = |keyword| schema.get(keyword).and_then(Value::as_f64);
+                if let Some(min) = bound("minimum").filter(|min| n < *min) {
+                    out.push(format!("{}: less than the minimum {}", at, min));
+                }
+                if let Some(max) = bound("maximum").filter(|max| n > *max) {
+                    out.push(format!("{}: greater than the maximum {}", at, max));
+                }
+                if let Some(min) = bound("exclusiveMinimum").filter(|min| n <= *min) {
+                    out.push(format!("{}: not greater than {}", at, min));
+                }
+                if let Some(max) = bound("exclusiveMaximum").filter(|max| n >= *max) {
+                    out.push(format!("{}: not less than {}", at, max));
+                }
+            }
+            Value::Array(items) => {
+                let count = items.len() as u64;
+                if let Some(min) = uint(schema, "minItems").filter(|min| count < *min) {
+                    out.push(format!("{}: fewer than {} items", at, min));
+                }
+                if let Some(max) = uint(schema, "maxItems").filter(|max| count > *max) {
+                    out.push(format!("{}: more than {} items", at, max));
+                }
+                if let Some(item_schema) = schema.get("items") {
+                    for (i, item) in items.iter().enumerate() {
+                        self.validate_at(item_schema, item, &format!("{}/{}", pointer, i), out);
+                    }
+                }
+            }
+            Value::Object(members) => self.validate_object(schema, members, pointer, out),
+            _ => {}
+        }
+
+        if let Some(Value::Array(schemas)) = schema.get("allOf") {
+            for s in schemas {
+                self.validate_at(s, value, pointer, out);
+            }
+        }
+        if let Some(Value::Array(schemas)) = schema.get("anyOf") {
+            if !schemas.iter().any(|s| self.conforms(s, value)) {
+                out.push(format!("{}: does not match any anyOf schema", at));
+            }
+        }
+        if let Some(Value::Array(schemas)) = schema.get("oneOf") {
+            let matched = schemas.iter().filter(|s| self.conforms(s, value)).count();
+            if matched != 1 {
+                out.push(format!(
+                    "{}: matches {} oneOf schemas, expected exactly one",
+                    at, matched
+                ));
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            if self.conforms(not, value) {
+                out.push(format!("{}: matches a schema it must not match", at));
+            }
+        }
+    }
+
+    fn validate_object(
+        &self,
+        schema: &Map<String, Value>,
+        members: &Map<String, Value>,
+        pointer: &str,
+        out: &mut Vec<String>,
+    ) {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !members.contains_key(name) {
+                    out.push(format!(
+                        "{}: missing required property {}",
+                        display(pointer),
+                        name
+                    ));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let additional = schema.get("additionalProperties");
+        for (name, member) in members {
+            let member_pointer = format!("{}/{}", pointer, escape(name));
+            match properties.and_then(|p| p.get(name)) {
+                Some(property) => self.validate_at(property, member, &member_pointer, out),
+                None => match additional {
+                    Some(Value::Bool(false)) => {
+                        out.push(format!("{}: unexpected property", member_pointer))
+                    }
+                    Some(additional) => self.validate_at(additional, member, &member_pointer, out),
+                    None => {}
+                },
+            }
+        }
+    }
+}
+
+/// Check that a schema only uses supported keywords with well-formed
+/// values, compiling its patterns along the way
+fn check_schema(
+    schema: &Value,
+    location: &str,
+    patterns: &mut HashMap<String, Regex>,
+) -> Result<(), SchemaError> {
+    let invalid = |problem: &str| {
+        Err(SchemaError::InvalidSchema(format!(
+            "{}: {}",
+            location, problem
+        )))
+    };
+    let schema = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(schema) => schema,
+        _ => return invalid("a schema must be an object or a boolean"),
+    };
+
+    for keyword in UNSUPPORTED_KEYWORDS {
+        if schema.contains_key(keyword) {
+            return invalid(&format!("{} is not supported", keyword));
+        }
+    }
+
+    match schema.get("type") {
+        None => {}
+        Some(Value::String(t)) if TYPES.contains(&t.as_str()) => {}
+        Some(Value::Array(ts))
+            if !ts.is_empty()
+                && ts
+                    .iter()
+                    .all(|t| t.as_str().is_some_and(|t| TYPES.contains(&t))) => {}
+        Some(_) => return invalid("type must be a JSON type name or an array of them"),
+    }
+    if schema.get("enum").is_some_and(|e| !e.is_array()) {
+        return invalid("enum must be an array");
+    }
+    if let Some(required) = schema.get("required") {
+        let names = required
+            .as_array()
+            .filter(|r| r.iter().all(Value::is_string));
+        if names.is_none() {
+            return invalid("required must be an array of property names");
+        }
+    }
+    for keyword in ["minItems", "maxItems", "minLength", "maxLength"] {
+        if schema.contains_key(keyword) && uint(schema, keyword).is_none() {
+            return invalid(&format!("{} must be a non-negative integer", keyword));
+        }
+    }
+    for keyword in ["minimum", "maximum", "exclusiveMinimum", "exclusiveMaximum"] {
+        if schema.get(keyword).is_some_and(|b| !b.is_number()) {
+            return invalid(&format!("{} must be a number", keyword));
+        }
+    }
+    match schema.get("pattern") {
+        None => {}
+        Some(Value::String(pattern)) => {
+            let re = Regex::new(pattern)
+                .map_err(|e| SchemaError::InvalidSchema(format!("{}/pattern: {}", location, e)))?;
+            patterns.insert(pattern.clone(), re);
+        }
+        Some(_) => return invalid("pattern must be a string"),
+    }
+
+    match schema.get("properties") {
+        None => {}
+        Some(Value::Object(properties)) => {
+            for (name, property) in properties {
+                let location = format!("{}/properties/{}", location, escape(name));
+                check_schema(property, &location, patterns)?;
+            }
+        }
+        Some(_) => return invalid("properties must be an object"),
+    }
+    for keyword in ["items", "additionalProperties", "not"] {
+        if let Some(subschema) = schema.get(keyword) {
+            check_schema(subschema, &format!("{}/{}", location, keyword), patterns)?;
+        }
+    }
+    for keyword in ["allOf", "anyOf", "oneOf"] {
+        match schema.get(keyword) {
+            None => {}
+            Some(Value::Array(schemas)) if !schemas.is_empty() => {
+                for (i, subschema) in schemas.iter().enumerate() {
+                    check_schema(
+                        subschema,
+                        &format!("{}/{}/{}", location, keyword, i),
+                        patterns,
+                    )?;
+                }
+            }
+            Some(_) => {
+                return invalid(&format!("{} must be a non-empty array of schemas", keyword))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn uint(schema: &Map<String, Value>, keyword: &str) -> Option<u64> {
+    schema.get(keyword).and_then(Value::as_u64)
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => match value {
+            Value::Number(n) => {
+                n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+            }
+            _ => false,
+        },
+        "number" => value.is_number(),
+        name => type_name(value) == name,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Escape a property name as a JSON Pointer (RFC 6901) token
+pub fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+fn display(pointer: &str) -> &str {
+    if pointer.is_empty() {
+        "/"
+    } else {
+        pointer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_event;
+    use serde_json::json;
+
+    fn violations(schema: Value, value: Value) -> Vec<String> {
+        let mut out = Vec::new();
+        Validator::new(schema)
+            .unwrap()
+            .validate(&value, "/input_data", &mut out);
+        out
+    }
+
+    #[test]
+    fn test_validator_reports_pointers() {
+        let schema = json!({
+            "type": "object",
+            "required": ["model", "messages"],
+            "additionalProperties": false,
+            "properties": {
+                "model": {"type": "string", "pattern": "^gpt-|^claude-"},
+                "temperature": {"type": "number", "minimum": 0, "maximum": 2},
+                "messages": {
+                    "type": "array",
+                    "minItems": 1,
+                    "items": {
+                        "type": "object",
+                        "required": ["role"],
+                        "properties": {"role": {"enum": ["system", "user", "assistant"]}}
+                    }
+                }
+            }
+        });
+
+        assert!(violations(
+            schema.clone(),
+            json!({"model": "claude-3", "temperature": 0.7, "messages": [{"role": "user"}]})
+        )
+        .is_empty());
+
+        assert_eq!(
+            violations(
+                schema,
+                json!({"model": "llama", "temperature": 3, "messages": [{"role": "bot"}, {}], "a/b": 1})
+            ),
+            vec![
+                "/input_data/a~1b: unexpected property",
+                "/input_data/messages/0/role: value is not one of the allowed values",
+                "/input_data/messages/1: missing required property role",
+                "/input_data/model: does not match pattern ^gpt-|^claude-",
+                "/input_data/temperature: greater than the maximum 2",
+            ]
+        );
+
+        assert_eq!(
+            violations(json!({"type": ["string", "null"]}), json!(1)),
+            vec!["/input_data: expected string or null, got number"]
+        );
+        assert_eq!(
+            violations(
+                json!({"oneOf": [{"type": "integer"}, {"type": "number"}]}),
+                json!(1)
+            ),
+            vec!["/input_data: matches 2 oneOf schemas, expected exactly one"]
+        );
+    }
+
+    #[test]
+    fn test_registry_checks_by_action_type() {
+        let registry = SchemaRegistry::new(None).unwrap();
+        assert!(registry
+            .upsert(
+                "tool_call",
+                SchemaRequest {
+                    input_schema: Some(json!({"$ref": "#/$defs/args"})),
+                    output_schema: None,
+                    mode: SchemaMode::Enforce,
+                },
+            )
+            .is_err());
+        assert!(registry
+            .upsert(
+                "tool_call",
+                SchemaRequest {
+                    input_schema: Some(json!({"properties": {"x": {"pattern": "("}}})),
+                    output_schema: None,
+                    mode: SchemaMode::Enforce,
+                },
+            )
+            .is_err());
+
+        registry
+            .upsert(
+                "tool_call",
+                SchemaRequest {
+                    input_schema: None,
+                    output_schema: Some(json!({"type": "object", "required": ["result"]})),
+                    mode: SchemaMode::Audit,
+                },
+            )
+            .unwrap();
+
+        let mut event = test_event();
+        event.action_type = "tool_call".to_string();
+        event.output_data = json!({"error": "timeout"});
+        let violations = registry.check(&event).unwrap_err();
+        assert_eq!(violations.mode, SchemaMode::Audit);
+        assert_eq!(
+            violations.to_string(),
+            "Event violates the tool_call schema: /output_data: missing required property result"
+        );
+
+        event.action_type = "llm_call".to_string();
+        assert!(registry.check(&event).is_ok());
+
+        registry.remove("tool_call").unwrap();
+        event.action_type = "tool_call".to_string();
+        assert!(registry.check(&event).is_ok());
+    }
+}
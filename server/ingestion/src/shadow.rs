@@ -0,0 +1,276 @@
+use crate::crypto::{build_canonical_form, canonical_version, compute_event_hash};
+use crate::jcs;
+use metrics::{counter, histogram};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+use crate::verification::now_nanos;
+use crate::FactoEvent;
+
+/// Divergences kept for the report; older ones are only counted
+pub const MAX_RECORDED_DIVERGENCES: usize = 100;
+
+/// Characters of each output shown on either side of a divergence
+const EXCERPT_CHARS: usize = 40;
+
+// ============================================================================
+// Candidate Hashers
+// ============================================================================
+
+/// A hash function evaluated against the current event hash. Different
+/// algorithms never agree, so candidates are compared on timing only.
+pub trait CandidateHasher: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
+    fn hash(&self, canonical: &str) -> String;
+}
+
+pub struct Sha256Hasher;
+
+impl CandidateHasher for Sha256Hasher {
+    fn name(&self) -> &'static str {
+        "sha256"
+    }
+
+    fn hash(&self, canonical: &str) -> String {
+        hex::encode(Sha256::digest(canonical.as_bytes()))
+    }
+}
+
+/// The candidate hasher configured by name
+pub fn candidate_hasher(name: &str) -> anyhow::Result<Box<dyn CandidateHasher>> {
+    match name {
+        "sha256" => Ok(Box::new(Sha256Hasher)),
+        other => Err(anyhow::anyhow!("unknown candidate hasher: {}", other)),
+    }
+}
+
+// ============================================================================
+// Shadow Evaluation
+// ============================================================================
+
+/// Where a candidate's output first differed from the current one
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    pub facto_id: String,
+    pub stage: &'static str,
+    /// Byte offset of the first difference
+    pub offset: usize,
+    pub current: String,
+    pub candidate: String,
+    pub observed_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct TimingSummary {
+    pub count: u64,
+    pub mean_us: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShadowReport {
+    pub sample_rate: f64,
+    pub evaluated: u64,
+    pub divergences: u64,
+    /// Mean duration per implementation, e.g. `canonicalize/jcs`
+    pub timings: BTreeMap<String, TimingSummary>,
+    /// Most recent divergences, newest last
+    pub recent: Vec<Divergence>,
+}
+
+/// Runs candidate canonicalizers and hashers next to the current ones on a
+/// sample of accepted events, off the request path.
+///
+/// Version 1 canonical forms are compared with the RFC 8785 (JCS)
+/// serialization of the same fields, which differs in key order for
+/// non-BMP characters and in number formatting. The sample is chosen by
+/// event hash, so a retried event is either always or never evaluated.
+pub struct ShadowEvaluator {
+    sample_rate: f64,
+    hasher: Option<Box<dyn CandidateHasher>>,
+    evaluated: AtomicU64,
+    diverged: AtomicU64,
+    timings: Mutex<BTreeMap<String, (u64, Duration)>>,
+    recent: Mutex<VecDeque<Divergence>>,
+}
+
+impl ShadowEvaluator {
+    pub fn new(sample_rate: f64, hasher: Option<Box<dyn CandidateHasher>>) -> Self {
+        Self {
+            sample_rate: sample_rate.clamp(0.0, 1.0),
+            hasher,
+            evaluated: AtomicU64::new(0),
+            diverged: AtomicU64::new(0),
+            timings: Mutex::new(BTreeMap::new()),
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn sampled(&self, event_hash: &str) -> bool {
+        let bucket = event_hash
+            .get(..8)
+            .and_then(|prefix| u32::from_str_radix(prefix, 16).ok())
+            .unwrap_or_default();
+        (bucket as f64) < self.sample_rate * (1u64 << 32) as f64
+    }
+
+    /// Evaluate an accepted event in the background if it is sampled
+    pub fn observe(self: &Arc<Self>, event: &FactoEvent, event_hash: &str) {
+        if !self.sampled(event_hash) {
+            return;
+        }
+        let evaluator = self.clone();
+        let event = event.clone();
+        tokio::task::spawn_blocking(move || evaluator.evaluate(&event));
+    }
+
+    pub fn evaluate(&self, event: &FactoEvent) {
+        let start = Instant::now();
+        let Ok(current) = build_canonical_form(event) else {
+            return;
+        };
+        self.record("canonicalize/current", start.elapsed());
+
+        if canonical_version(event) == 1 {
+            let start = Instant::now();
+            let candidate = serde_json::from_str(&current).map(|value| jcs::to_string(&value));
+            self.record("canonicalize/jcs", start.elapsed());
+            if let Ok(candidate) = candidate {
+                if candidate != current {
+                    self.diverge(event, "canonicalize", &current, &candidate);
+                }
+            }
+        }
+
+        let start = Instant::now();
+        compute_event_hash(&current);
+        self.record("hash/sha3-256", start.elapsed());
+
+        if let Some(ref hasher) = self.hasher {
+            let start = Instant::now();
+            hasher.hash(&current);
+            self.record(&format!("hash/{}", hasher.name()), start.elapsed());
+        }
+
+        self.evaluated.fetch_add(1, Ordering::Relaxed);
+        counter!("facto_shadow_evaluations_total").increment(1);
+    }
+
+    fn record(&self, implementation: &str, elapsed: Duration) {
+        histogram!("facto_shadow_duration_seconds", "implementation" => implementation.to_string())
+            .record(elapsed.as_secs_f64());
+        let mut timings = self.timings.lock().unwrap();
+        let (count, total) = timings.entry(implementation.to_string()).or_default();
+        *count += 1;
+        *total += elapsed;
+    }
+
+    fn diverge(&self, event: &FactoEvent, stage: &'static str, current: &str, candidate: &str) {
+        let offset = first_difference(current, candidate);
+        warn!(
+            "Shadow {} diverged for {} at byte {}",
+            stage, event.facto_id, offset
+        );
+        counter!("facto_shadow_divergences_total", "stage" => stage).increment(1);
+        self.diverged.fetch_add(1, Ordering::Relaxed);
+
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == MAX_RECORDED_DIVERGENCES {
+            recent.pop_front();
+        }
+        recent.push_back(Divergence {
+            facto_id: event.facto_id.clone(),
+            stage,
+            offset,
+            current: excerpt(current, offset),
+            candidate: excerpt(candidate, offset),
+            observed_at: now_nanos(),
+        });
+    }
+
+    pub fn report(&self) -> ShadowReport {
+        let timings = self
+            .timings
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(implementation, (count, total))| {
+                let summary = TimingSummary {
+                    count: *count,
+                    mean_us: total.as_secs_f64() * 1e6 / (*count).max(1) as f64,
+                };
+                (implementation.clone(), summary)
+            })
+            .collect();
+        ShadowReport {
+            sample_rate: self.sample_rate,
+            evaluated: self.evaluated.load(Ordering::Relaxed),
+            divergences: self.diverged.load(Ordering::Relaxed),
+            timings,
+            recent: self.recent.lock().unwrap().iter().cloned().collect(),
+        }
+    }
+}
+
+/// Byte offset of the first difference, on a character boundary of both
+fn first_difference(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map(|((i, _), _)| i)
+        .unwrap_or_else(|| a.len().min(b.len()))
+}
+
+/// The characters around `offset`
+fn excerpt(s: &str, offset: usize) -> String {
+    let before: Vec<char> = s[..offset].chars().rev().take(EXCERPT_CHARS).collect();
+    let after = s[offset..].chars().take(EXCERPT_CHARS);
+    before.into_iter().rev().chain(after).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_event;
+
+    #[test]
+    fn test_sampling_by_event_hash() {
+        let none = ShadowEvaluator::new(0.0, None);
+        let all = ShadowEvaluator::new(1.0, None);
+        let half = ShadowEvaluator::new(0.5, None);
+        assert!(!none.sampled("00000000"));
+        assert!(all.sampled("ffffffff"));
+        assert!(half.sampled("7fffffff"));
+        assert!(!half.sampled("80000001"));
+    }
+
+    #[test]
+    fn test_jcs_divergence_reported() {
+        let evaluator = ShadowEvaluator::new(1.0, Some(Box::new(Sha256Hasher)));
+        evaluator.evaluate(&test_event());
+        assert_eq!(evaluator.report().divergences, 0);
+
+        // serde_json writes 1e20 as 1e+20 where JCS spells it out
+        let mut event = test_event();
+        event.input_data = serde_json::json!({"threshold": 1e20});
+        evaluator.evaluate(&event);
+
+        let report = evaluator.report();
+        assert_eq!(report.evaluated, 2);
+        assert_eq!(report.divergences, 1);
+        assert_eq!(report.recent[0].stage, "canonicalize");
+        assert!(report.recent[0].current.contains("1e+20"));
+        assert!(report.recent[0].candidate.contains("100000000000000000000"));
+        assert!(report.timings.contains_key("canonicalize/jcs"));
+        assert!(report.timings.contains_key("hash/sha256"));
+    }
+}
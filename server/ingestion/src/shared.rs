@@ -0,0 +1,269 @@
+//! State that must agree across ingestion replicas.
+//!
+//! With `SHARED_STATE=memory` (the default) every replica keeps its own
+//! copy, which is only correct for a single replica. With `SHARED_STATE=nats`
+//! the following are kept in JetStream key-value buckets on the event
+//! broker, so any number of replicas behind a load balancer behave as one:
+//!
+//! - per-agent rate limits ([`crate::ratelimit`])
+//! - the facto_id dedup window ([`crate::dedup`])
+//! - seen event hashes of the replay guard ([`crate::replay`])
+//! - session chain heads ([`crate::chain`]), moved by compare-and-swap,
+//!   unless sessions are routed to replicas by hash
+//!
+//! Each distributed store keeps its in-memory counterpart as a fallback for
+//! when the broker is unreachable, so requests are still answered, with
+//! per-replica guarantees, and `facto_shared_state_fallbacks_total` counts
+//! how often that happens.
+//!
+//! Still kept per replica: tenant rate limits and byte quotas and agent
+//! byte quotas (configure each replica with its share), session delivery locks (ordered batches are
+//! ordered per request), the verification cache, debug bundles and the
+//! spool, which are all correct per replica.
+
+use async_nats::{
+    header::NATS_EXPECTED_LAST_SUBJECT_SEQUENCE,
+    jetstream::{self, context::PublishErrorKind, kv},
+    HeaderMap, HeaderValue,
+};
+use metrics::counter;
+use sha2::{Digest, Sha256};
+use std::{sync::RwLock, time::Duration};
+use tracing::{info, warn};
+
+/// Attempts of one compare-and-swap before giving up on the bucket
+const MAX_SWAP_ATTEMPTS: usize = 8;
+
+/// Where state shared by replicas is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedStateBackend {
+    Memory,
+    Nats,
+}
+
+impl SharedStateBackend {
+    /// Read `SHARED_STATE`: `memory` or `nats`
+    pub fn from_env() -> anyhow::Result<Self> {
+        match std::env::var("SHARED_STATE").as_deref() {
+            Err(_) | Ok("memory") => Ok(Self::Memory),
+            Ok("nats") => Ok(Self::Nats),
+            Ok(other) => anyhow::bail!("SHARED_STATE must be memory or nats, not {}", other),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SharedStateError {
+    #[error("bucket {0} is not open")]
+    Unavailable(&'static str),
+    #[error("bucket {bucket}: {message}")]
+    Nats {
+        bucket: &'static str,
+        message: String,
+    },
+    #[error("bucket {0}: too many conflicting updates")]
+    Contended(&'static str),
+}
+
+// ============================================================================
+// Key-Value Buckets
+// ============================================================================
+
+/// What a compare-and-swap does with the current value
+pub enum Swap<T> {
+    /// Leave the value as it is
+    Keep(T),
+    /// Replace the value, unless another replica changed it meanwhile
+    Write(Vec<u8>, T),
+}
+
+/// A JetStream key-value bucket, opened once NATS is connected
+pub struct KvBucket {
+    name: &'static str,
+    max_age: Duration,
+    store: RwLock<Option<(kv::Store, jetstream::Context)>>,
+}
+
+impl KvBucket {
+    /// A bucket whose entries expire `max_age` after they were last written
+    pub fn new(name: &'static str, max_age: Duration) -> Self {
+        Self {
+            name,
+            max_age,
+            store: RwLock::new(None),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Create the bucket if it does not exist yet and start using it
+    pub async fn open(&self, jetstream: &jetstream::Context) -> Result<(), SharedStateError> {
+        let store = match jetstream.get_key_value(self.name).await {
+            Ok(store) => store,
+            Err(_) => jetstream
+                .create_key_value(kv::Config {
+                    bucket: self.name.to_string(),
+                    history: 1,
+                    max_age: self.max_age,
+                    storage: jetstream::stream::StorageType::File,
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| self.error(e))?,
+        };
+        info!("Using shared state bucket {}", self.name);
+        *self.store.write().unwrap() = Some((store, jetstream.clone()));
+        Ok(())
+    }
+
+    fn store(&self) -> Result<kv::Store, SharedStateError> {
+        self.opened().map(|(store, _)| store)
+    }
+
+    fn opened(&self) -> Result<(kv::Store, jetstream::Context), SharedStateError> {
+        self.store
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or(SharedStateError::Unavailable(self.name))
+    }
+
+    fn error(&self, e: impl std::fmt::Display) -> SharedStateError {
+        SharedStateError::Nats {
+            bucket: self.name,
+            message: e.to_string(),
+        }
+    }
+
+    /// Check the bucket answers
+    pub async fn ping(&self) -> Result<(), SharedStateError> {
+        self.store()?
+            .status()
+            .await
+            .map(|_| ())
+            .map_err(|e| self.error(e))
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, SharedStateError> {
+        self.store()?
+            .get(kv_key(key))
+            .await
+            .map(|value| value.map(|v| v.to_vec()))
+            .map_err(|e| self.error(e))
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), SharedStateError> {
+        self.store()?
+            .delete(kv_key(key))
+            .await
+            .map_err(|e| self.error(e))
+    }
+
+    /// Decide on the current value of `key` and apply the decision
+    /// atomically, retrying with the new value when another replica wrote
+    /// the key in between
+    pub async fn swap<T>(
+        &self,
+        key: &str,
+        mut decide: impl FnMut(Option<&[u8]>) -> Swap<T>,
+    ) -> Result<T, SharedStateError> {
+        let (store, jetstream) = self.opened()?;
+        let key = kv_key(key);
+        for _ in 0..MAX_SWAP_ATTEMPTS {
+            // Deleted and expired keys read as absent, at the revision of
+            // their delete marker if there is one
+            let (current, revision) = match store.entry(key.as_str()).await {
+                Ok(Some(entry)) if entry.operation == kv::Operation::Put => {
+                    (Some(entry.value), entry.revision)
+                }
+                Ok(Some(entry)) => (None, entry.revision),
+                Ok(None) => (None, 0),
+                Err(e) => return Err(self.error(e)),
+            };
+            let (value, result) = match decide(current.as_deref()) {
+                Swap::Keep(result) => return Ok(result),
+                Swap::Write(value, result) => (value, result),
+            };
+            match self
+                .update(&store, &jetstream, &key, value, revision)
+                .await?
+            {
+                true => return Ok(result),
+                false => {
+                    counter!("facto_shared_state_conflicts_total", "bucket" => self.name)
+                        .increment(1);
+                }
+            }
+        }
+        Err(SharedStateError::Contended(self.name))
+    }
+
+    /// Write `value` if `revision` is still the last of `key`, or report
+    /// that another replica wrote it first. `kv::Store::update` folds that
+    /// conflict into the same error kind as any other failure, so the
+    /// conditional publish is made here.
+    async fn update(
+        &self,
+        store: &kv::Store,
+        jetstream: &jetstream::Context,
+        key: &str,
+        value: Vec<u8>,
+        revision: u64,
+    ) -> Result<bool, SharedStateError> {
+        let subject = format!(
+            "{}{}",
+            store.put_prefix.as_deref().unwrap_or(&store.prefix),
+            key
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            NATS_EXPECTED_LAST_SUBJECT_SEQUENCE,
+            HeaderValue::from(revision),
+        );
+        let published = match jetstream
+            .publish_with_headers(subject, headers, value.into())
+            .await
+        {
+            Ok(ack) => ack.await,
+            Err(e) => Err(e),
+        };
+        match published {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == PublishErrorKind::WrongLastSequence => Ok(false),
+            Err(e) => Err(self.error(e)),
+        }
+    }
+}
+
+/// Bucket keys are limited to a few characters, so keys are hashed
+fn kv_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Count a bucket failure before falling back to local state. Buckets are
+/// unavailable until NATS is first connected, which is not worth a warning.
+pub fn fallback(e: SharedStateError) {
+    let bucket = match e {
+        SharedStateError::Unavailable(bucket) => bucket,
+        SharedStateError::Nats { bucket, .. } | SharedStateError::Contended(bucket) => {
+            warn!("Shared state unavailable, using local state: {}", e);
+            bucket
+        }
+    };
+    counter!("facto_shared_state_fallbacks_total", "bucket" => bucket).increment(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_are_valid_bucket_keys() {
+        let key = kv_key("tenant:acme/ft-550e8400 e29b");
+        assert_eq!(key.len(), 64);
+        assert!(key.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(key, kv_key("tenant:acme/ft-550e8400"));
+    }
+}
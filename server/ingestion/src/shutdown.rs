@@ -0,0 +1,192 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use metrics::{counter, gauge};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::watch;
+use tracing::info;
+
+use crate::admin::error_response;
+
+/// Shutdown state shared by the server, its middleware and the readiness
+/// probe.
+///
+/// On SIGTERM or Ctrl-C the server stops accepting connections and new
+/// requests on open ones, and waits up to `SHUTDOWN_TIMEOUT_SECS` for
+/// in-flight requests to finish. Whatever time remains is used to hand
+/// spooled events to the broker before the process exits.
+#[derive(Clone)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    timeout: Duration,
+    started: watch::Sender<bool>,
+    started_at: OnceLock<Instant>,
+    in_flight: AtomicUsize,
+}
+
+impl Shutdown {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                timeout,
+                started: watch::Sender::new(false),
+                started_at: OnceLock::new(),
+                in_flight: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Read `SHUTDOWN_TIMEOUT_SECS`
+    pub fn from_env() -> Self {
+        let timeout_secs: u64 = std::env::var("SHUTDOWN_TIMEOUT_SECS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .expect("Invalid SHUTDOWN_TIMEOUT_SECS");
+        Self::new(Duration::from_secs(timeout_secs))
+    }
+
+    /// Start shutting down; later calls have no effect
+    pub fn begin(&self) {
+        if self.inner.started_at.set(Instant::now()).is_err() {
+            return;
+        }
+        info!(
+            "Shutting down, draining {} in-flight requests for up to {:?}",
+            self.in_flight(),
+            self.inner.timeout
+        );
+        gauge!("facto_shutdown_draining").set(1.0);
+        self.inner.started.send_replace(true);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.inner.started_at.get().is_some()
+    }
+
+    /// Requests currently being handled
+    pub fn in_flight(&self) -> usize {
+        self.inner.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Resolves once shutdown has begun
+    pub async fn started(self) {
+        let mut started = self.inner.started.subscribe();
+        let _ = started.wait_for(|started| *started).await;
+    }
+
+    /// Resolves once the shutdown deadline has passed
+    pub async fn expired(self) {
+        self.clone().started().await;
+        tokio::time::sleep(self.remaining()).await;
+    }
+
+    /// Time left until the shutdown deadline
+    pub fn remaining(&self) -> Duration {
+        match self.inner.started_at.get() {
+            Some(started_at) => self.inner.timeout.saturating_sub(started_at.elapsed()),
+            None => self.inner.timeout,
+        }
+    }
+
+    /// Time since shutdown began
+    pub fn elapsed(&self) -> Duration {
+        self.inner
+            .started_at
+            .get()
+            .map_or(Duration::ZERO, Instant::elapsed)
+    }
+}
+
+/// Wait for SIGTERM or Ctrl-C, then begin shutting down
+pub async fn listen_for_signals(shutdown: Shutdown) {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received Ctrl-C"),
+        _ = terminate => info!("Received SIGTERM"),
+    }
+    counter!("facto_shutdown_signals_total").increment(1);
+    shutdown.begin();
+}
+
+/// Middleware counting in-flight requests. Once shutdown has begun, requests
+/// arriving on kept-alive connections are refused so the connection closes;
+/// the liveness probe keeps answering until the process exits.
+pub async fn track_requests(
+    State(shutdown): State<Shutdown>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if shutdown.is_draining() && request.uri().path() != "/health" {
+        counter!("facto_shutdown_refused_requests_total").increment(1);
+        let mut response =
+            error_response(StatusCode::SERVICE_UNAVAILABLE, "Server is shutting down");
+        response
+            .headers_mut()
+            .insert(header::CONNECTION, HeaderValue::from_static("close"));
+        return response;
+    }
+
+    shutdown.inner.in_flight.fetch_add(1, Ordering::Relaxed);
+    gauge!("facto_in_flight_requests").increment(1.0);
+    let _guard = InFlight(&shutdown);
+    next.run(request).await
+}
+
+/// Counts a request as finished even if its handler is dropped
+struct InFlight<'a>(&'a Shutdown);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.inner.in_flight.fetch_sub(1, Ordering::Relaxed);
+        gauge!("facto_in_flight_requests").decrement(1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_starts_once_with_deadline() {
+        let shutdown = Shutdown::new(Duration::from_secs(30));
+        assert!(!shutdown.is_draining());
+        assert_eq!(shutdown.remaining(), Duration::from_secs(30));
+
+        let started = tokio::spawn(shutdown.clone().started());
+        shutdown.begin();
+        shutdown.begin();
+        started.await.unwrap();
+        assert!(shutdown.is_draining());
+        assert!(shutdown.remaining() <= Duration::from_secs(30));
+
+        let expired = Shutdown::new(Duration::ZERO);
+        expired.begin();
+        expired.expired().await;
+    }
+}
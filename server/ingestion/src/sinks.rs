@@ -0,0 +1,294 @@
+use axum::async_trait;
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use metrics::{counter, gauge, histogram};
+use std::{num::NonZeroU32, path::PathBuf, sync::Arc, time::Instant};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::{mpsc, Mutex, OwnedSemaphorePermit, Semaphore},
+};
+use tracing::{info, warn};
+
+use crate::verification::ServerEnvelope;
+use crate::FactoEvent;
+
+/// An accepted event with its server envelope, shared between sinks
+pub type AcceptedEvent = Arc<(FactoEvent, ServerEnvelope)>;
+
+// ============================================================================
+// Rate Shaping
+// ============================================================================
+
+/// Rate and concurrency limits for one downstream sink
+#[derive(Debug, Clone, Copy)]
+pub struct SinkLimits {
+    /// Deliveries started per second; unlimited if unset
+    pub rate_per_sec: Option<NonZeroU32>,
+    /// Deliveries in flight at once
+    pub concurrency: usize,
+    /// Events buffered for the sink before new ones are dropped
+    pub queue_capacity: usize,
+}
+
+impl SinkLimits {
+    /// Read `<PREFIX>_RATE`, `<PREFIX>_CONCURRENCY` and `<PREFIX>_QUEUE`
+    pub fn from_env(prefix: &str, default_concurrency: usize) -> Self {
+        let var = |name: &str| std::env::var(format!("{}_{}", prefix, name)).ok();
+
+        let rate_per_sec = var("RATE").map(|v| {
+            v.parse()
+                .unwrap_or_else(|_| panic!("Invalid {}_RATE", prefix))
+        });
+        let concurrency = var("CONCURRENCY").map_or(default_concurrency, |v| {
+            v.parse()
+                .unwrap_or_else(|_| panic!("Invalid {}_CONCURRENCY", prefix))
+        });
+        let queue_capacity = var("QUEUE").map_or(10_000, |v| {
+            v.parse()
+                .unwrap_or_else(|_| panic!("Invalid {}_QUEUE", prefix))
+        });
+
+        Self {
+            rate_per_sec,
+            concurrency,
+            queue_capacity,
+        }
+    }
+}
+
+/// Admits deliveries to a sink at a bounded rate and concurrency
+pub struct Shaper {
+    rate: Option<DefaultDirectRateLimiter>,
+    permits: Arc<Semaphore>,
+}
+
+impl Shaper {
+    pub fn new(limits: &SinkLimits) -> Self {
+        Self {
+            rate: limits
+                .rate_per_sec
+                .map(|rate| RateLimiter::direct(Quota::per_second(rate))),
+            permits: Arc::new(Semaphore::new(limits.concurrency.max(1))),
+        }
+    }
+
+    /// Wait for a rate slot and a concurrency permit; the delivery may run
+    /// while the permit is held
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        if let Some(ref rate) = self.rate {
+            rate.until_ready().await;
+        }
+        self.permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("sink semaphore closed")
+    }
+}
+
+// ============================================================================
+// Fan-out
+// ============================================================================
+
+/// A secondary destination for accepted events. Fan-out sinks are fed from
+/// their own queue, so a slow sink never delays the primary NATS publish.
+#[async_trait]
+pub trait FanoutSink: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
+    async fn deliver(&self, event: &AcceptedEvent) -> anyhow::Result<()>;
+}
+
+struct SinkQueue {
+    name: &'static str,
+    sender: mpsc::Sender<AcceptedEvent>,
+}
+
+/// Hands accepted events to every registered fan-out sink. Each sink has an
+/// independent bounded queue and worker; when a queue is full the event is
+/// dropped for that sink only and counted.
+#[derive(Default)]
+pub struct Fanout {
+    sinks: Vec<SinkQueue>,
+}
+
+impl Fanout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a worker for `sink` shaped by `limits`
+    pub fn register(&mut self, sink: Arc<dyn FanoutSink>, limits: SinkLimits) {
+        let name = sink.name();
+        let (sender, receiver) = mpsc::channel(limits.queue_capacity.max(1));
+        tokio::spawn(run_sink(sink, receiver, Shaper::new(&limits)));
+        info!(
+            "Fan-out sink {} registered (rate: {:?}/s, concurrency: {}, queue: {})",
+            name, limits.rate_per_sec, limits.concurrency, limits.queue_capacity
+        );
+        self.sinks.push(SinkQueue { name, sender });
+    }
+
+    /// Queue an accepted event for every sink without waiting
+    pub fn dispatch(&self, event: &FactoEvent, envelope: &ServerEnvelope) {
+        if self.sinks.is_empty() {
+            return;
+        }
+
+        let accepted: AcceptedEvent = Arc::new((event.clone(), envelope.clone()));
+        for sink in &self.sinks {
+            if sink.sender.try_send(accepted.clone()).is_err() {
+                counter!("facto_sink_dropped_total", "sink" => sink.name).increment(1);
+            }
+            gauge!("facto_sink_queue_depth", "sink" => sink.name)
+                .set((sink.sender.max_capacity() - sink.sender.capacity()) as f64);
+        }
+    }
+}
+
+async fn run_sink(
+    sink: Arc<dyn FanoutSink>,
+    mut receiver: mpsc::Receiver<AcceptedEvent>,
+    shaper: Shaper,
+) {
+    while let Some(accepted) = receiver.recv().await {
+        let permit = shaper.acquire().await;
+        let sink = sink.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let start = Instant::now();
+            match sink.deliver(&accepted).await {
+                Ok(()) => {
+                    counter!("facto_sink_delivered_total", "sink" => sink.name()).increment(1)
+                }
+                Err(e) => {
+                    warn!(
+                        "Sink {} failed to deliver {}: {}",
+                        sink.name(),
+                        accepted.0.facto_id,
+                        e
+                    );
+                    counter!("facto_sink_failed_total", "sink" => sink.name()).increment(1);
+                }
+            }
+            histogram!("facto_sink_delivery_seconds", "sink" => sink.name())
+                .record(start.elapsed().as_secs_f64());
+        });
+    }
+}
+
+// ============================================================================
+// Warehouse Export Sink
+// ============================================================================
+
+/// Appends accepted events with their envelopes to hourly NDJSON files
+/// (`events-YYYYMMDDHH.ndjson`) for loading into a warehouse
+pub struct ExportSink {
+    dir: PathBuf,
+    current: Mutex<Option<(String, File)>>,
+}
+
+impl ExportSink {
+    pub async fn new(dir: PathBuf) -> anyhow::Result<Self> {
+        tokio::fs::create_dir_all(&dir).await?;
+        Ok(Self {
+            dir,
+            current: Mutex::new(None),
+        })
+    }
+}
+
+#[async_trait]
+impl FanoutSink for ExportSink {
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    async fn deliver(&self, accepted: &AcceptedEvent) -> anyhow::Result<()> {
+        let (event, envelope) = accepted.as_ref();
+        let mut line = serde_json::to_vec(&serde_json::json!({
+            "event": event,
+            "envelope": envelope,
+        }))?;
+        line.push(b'\n');
+
+        let hour = chrono::Utc::now().format("%Y%m%d%H").to_string();
+        let mut current = self.current.lock().await;
+        if current.as_ref().is_none_or(|(h, _)| *h != hour) {
+            let path = self.dir.join(format!("events-{}.ndjson", hour));
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await?;
+            *current = Some((hour, file));
+        }
+
+        let (_, file) = current.as_mut().expect("export file is open");
+        file.write_all(&line).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// A sink that never finishes a delivery
+    struct StuckSink {
+        started: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl FanoutSink for StuckSink {
+        fn name(&self) -> &'static str {
+            "stuck"
+        }
+
+        async fn deliver(&self, _: &AcceptedEvent) -> anyhow::Result<()> {
+            self.started.fetch_add(1, Ordering::SeqCst);
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stuck_sink_never_blocks_dispatch() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let mut fanout = Fanout::new();
+        fanout.register(
+            Arc::new(StuckSink {
+                started: started.clone(),
+            }),
+            SinkLimits {
+                rate_per_sec: None,
+                concurrency: 2,
+                queue_capacity: 4,
+            },
+        );
+
+        let event = crate::testing::test_event();
+        let envelope: ServerEnvelope = serde_json::from_value(serde_json::json!({
+            "received_at": 0,
+            "verification": {
+                "facto_id": "tr-1", "event_hash": "", "algorithm": "ed25519",
+                "signer_public_key": "", "trust_basis": {"type": "embedded_key"},
+                "verifier_id": "test", "verifier_public_key": "", "verified_at": 0
+            }
+        }))
+        .unwrap();
+
+        // Far more events than the sink can take; dispatch must not wait
+        tokio::time::timeout(Duration::from_secs(1), async {
+            for _ in 0..100 {
+                fanout.dispatch(&event, &envelope);
+            }
+        })
+        .await
+        .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+    }
+}
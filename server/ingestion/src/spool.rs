@@ -0,0 +1,649 @@
+use metrics::gauge;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader},
+    sync::{Mutex, Notify},
+};
+use tracing::{info, warn};
+
+use crate::verification::ServerEnvelope;
+use crate::FactoEvent;
+
+/// A validated event waiting in the spool for delivery
+#[derive(Debug, Clone)]
+pub struct SpooledEvent {
+    pub event: FactoEvent,
+    pub envelope: ServerEnvelope,
+}
+
+/// A spool record: the event, and the bytes it was received as escaped
+/// into a string so that each record stays on one line
+#[derive(Serialize, Deserialize)]
+struct SpoolRecord<E, R> {
+    event: E,
+    envelope: ServerEnvelope,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    raw: Option<R>,
+}
+
+impl Serialize for SpooledEvent {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let raw = match self.event.raw {
+            Some(ref raw) => Some(std::str::from_utf8(raw).map_err(serde::ser::Error::custom)?),
+            None => None,
+        };
+        SpoolRecord {
+            event: &self.event,
+            envelope: self.envelope.clone(),
+            raw,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SpooledEvent {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let record = SpoolRecord::<FactoEvent, String>::deserialize(deserializer)?;
+        let mut event = record.event;
+        event.raw = record.raw.map(Into::into);
+        Ok(Self {
+            event,
+            envelope: record.envelope,
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpoolError {
+    #[error("spool is full")]
+    Full,
+    #[error("spool I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Cursor of the primary NATS publisher
+pub const NATS_CURSOR: &str = "nats";
+
+struct SpoolFile {
+    file: File,
+    /// Bytes written to the log
+    len: u64,
+    /// Bytes already delivered, per cursor
+    offsets: HashMap<&'static str, u64>,
+    /// Makes the next append write this many bytes, then fail
+    #[cfg(test)]
+    fail_after: Option<usize>,
+}
+
+/// Delivery progress of one consumer of the log
+struct Cursor {
+    depth: AtomicU64,
+    wake: Notify,
+    /// Held while the cursor is drained, so events are delivered once
+    draining: Mutex<()>,
+}
+
+/// Append-only write-ahead log of validated events. Events are stored as
+/// JSON lines in `spool.log`. Each consumer reads the log through a named
+/// cursor whose position is checkpointed in `<cursor>.offset`, so a restart
+/// resumes where delivery stopped. Once every cursor has caught up the log
+/// is truncated.
+///
+/// Without the outbox only the NATS cursor exists and events are spooled
+/// while NATS is unavailable; new events are appended behind spooled ones
+/// rather than published directly, so NATS receives everything in
+/// acceptance order. With the outbox every accepted event goes through the
+/// log and each fan-out sink has its own cursor.
+///
+/// Delivery is at least once: up to [`CHECKPOINT_INTERVAL`] events per
+/// cursor may be redelivered after a crash. NATS drops the repeats by
+/// message id; sinks must be idempotent on `facto_id`.
+pub struct Spool {
+    dir: PathBuf,
+    log_path: PathBuf,
+    max_bytes: u64,
+    inner: Mutex<SpoolFile>,
+    cursors: HashMap<&'static str, Cursor>,
+}
+
+/// Events delivered between offset checkpoints
+pub const CHECKPOINT_INTERVAL: u64 = 100;
+
+impl Spool {
+    pub async fn open(
+        dir: PathBuf,
+        max_bytes: u64,
+        cursors: &[&'static str],
+    ) -> anyhow::Result<Self> {
+        tokio::fs::create_dir_all(&dir).await?;
+        let log_path = dir.join("spool.log");
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+            .await?;
+        let mut len = file.metadata().await?.len();
+        let complete = complete_len(&log_path, len).await?;
+        if complete < len {
+            warn!(
+                "Dropping {} bytes of an unfinished spool record",
+                len - complete
+            );
+            file.set_len(complete).await?;
+            len = complete;
+        }
+
+        let mut offsets = HashMap::new();
+        for &name in cursors {
+            let offset = match tokio::fs::read_to_string(offset_path(&dir, name)).await {
+                Ok(s) => s.trim().parse::<u64>().unwrap_or(0).min(len),
+                Err(_) => 0,
+            };
+            offsets.insert(name, offset);
+        }
+
+        let mut spool = Self {
+            dir,
+            log_path,
+            max_bytes,
+            inner: Mutex::new(SpoolFile {
+                file,
+                len,
+                offsets: HashMap::new(),
+                #[cfg(test)]
+                fail_after: None,
+            }),
+            cursors: HashMap::new(),
+        };
+
+        for (&name, &offset) in &offsets {
+            let depth = spool.count_pending(offset, len).await?;
+            if depth > 0 {
+                info!("Spool holds {} undelivered events for {}", depth, name);
+            }
+            spool.cursors.insert(
+                name,
+                Cursor {
+                    depth: AtomicU64::new(depth),
+                    wake: Notify::new(),
+                    draining: Mutex::new(()),
+                },
+            );
+        }
+        spool.inner.get_mut().offsets = offsets;
+        spool.report(&*spool.inner.lock().await);
+
+        Ok(spool)
+    }
+
+    /// Number of events waiting for the furthest-behind cursor
+    pub fn depth(&self) -> u64 {
+        self.cursors
+            .values()
+            .map(|c| c.depth.load(Ordering::Relaxed))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Number of events waiting for one cursor
+    pub fn cursor_depth(&self, cursor: &str) -> u64 {
+        self.cursors
+            .get(cursor)
+            .map_or(0, |c| c.depth.load(Ordering::Relaxed))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depth() == 0
+    }
+
+    /// Check the spool can still take events: it has room and its
+    /// directory is writable. Returns the bytes in use.
+    pub async fn probe(&self) -> Result<u64, SpoolError> {
+        let used = {
+            let inner = self.inner.lock().await;
+            inner.len - min_offset(&inner)
+        };
+        if used >= self.max_bytes {
+            return Err(SpoolError::Full);
+        }
+        let probe = self.dir.join("health.probe");
+        tokio::fs::write(&probe, b"ok").await?;
+        tokio::fs::remove_file(&probe).await?;
+        Ok(used)
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Durably append events to the spool, in order
+    pub async fn append(&self, events: &[SpooledEvent]) -> Result<(), SpoolError> {
+        let mut data = Vec::new();
+        for event in events {
+            serde_json::to_writer(&mut data, event).map_err(std::io::Error::from)?;
+            data.push(b'\n');
+        }
+
+        let mut inner = self.inner.lock().await;
+        if inner.len - min_offset(&inner) + data.len() as u64 > self.max_bytes {
+            return Err(SpoolError::Full);
+        }
+
+        if let Err(e) = write_records(&mut inner, &data).await {
+            // A failed write may have left part of the records in the log;
+            // drop them so the next append starts on a record boundary
+            inner.file.set_len(inner.len).await?;
+            return Err(e.into());
+        }
+        inner.len += data.len() as u64;
+
+        for cursor in self.cursors.values() {
+            cursor
+                .depth
+                .fetch_add(events.len() as u64, Ordering::Relaxed);
+            cursor.wake.notify_one();
+        }
+        self.report(&inner);
+        Ok(())
+    }
+
+    /// Wait until events are appended or `timeout` elapses
+    pub async fn wait(&self, cursor: &str, timeout: std::time::Duration) {
+        match self.cursors.get(cursor) {
+            Some(c) => {
+                let _ = tokio::time::timeout(timeout, c.wake.notified()).await;
+            }
+            None => tokio::time::sleep(timeout).await,
+        }
+    }
+
+    /// Deliver the events after `cursor` in order with `publish`, stopping at
+    /// the first failure. Returns the number of events delivered. Concurrent
+    /// drains of one cursor run one after the other.
+    pub async fn drain<F, Fut, E>(&self, cursor: &str, mut publish: F) -> Result<u64, SpoolError>
+    where
+        F: FnMut(SpooledEvent) -> Fut,
+        Fut: std::future::Future<Output = Result<(), E>>,
+        E: std::fmt::Display,
+    {
+        let Some((&name, state)) = self.cursors.get_key_value(cursor) else {
+            return Ok(0);
+        };
+        let _draining = state.draining.lock().await;
+        let mut delivered = 0;
+
+        loop {
+            let (mut offset, end) = {
+                let inner = self.inner.lock().await;
+                (inner.offsets[name], inner.len)
+            };
+
+            if offset == end {
+                let mut inner = self.inner.lock().await;
+                if inner.len != end {
+                    continue;
+                }
+                if inner.offsets.values().all(|&o| o == end) && end > 0 {
+                    // Every cursor caught up: reset the log
+                    inner.file.set_len(0).await?;
+                    inner.len = 0;
+                    for offset in inner.offsets.values_mut() {
+                        *offset = 0;
+                    }
+                    for name in self.cursors.keys() {
+                        let _ = tokio::fs::remove_file(offset_path(&self.dir, name)).await;
+                    }
+                    self.report(&inner);
+                }
+                return Ok(delivered);
+            }
+
+            let mut reader = BufReader::new(File::open(&self.log_path).await?);
+            reader.seek(SeekFrom::Start(offset)).await?;
+
+            let mut line = String::new();
+            while offset < end {
+                line.clear();
+                let read = reader.read_line(&mut line).await? as u64;
+                if read == 0 {
+                    break;
+                }
+
+                match serde_json::from_str::<SpooledEvent>(&line) {
+                    Ok(event) => {
+                        if let Err(e) = publish(event).await {
+                            warn!("Spool drain for {} paused: {}", name, e);
+                            self.checkpoint(name, offset).await?;
+                            return Ok(delivered);
+                        }
+                        delivered += 1;
+                    }
+                    Err(e) => warn!("Skipping corrupt spool record: {}", e),
+                }
+                state.depth.fetch_sub(1, Ordering::Relaxed);
+
+                offset += read;
+                if delivered % CHECKPOINT_INTERVAL == 0 {
+                    self.checkpoint(name, offset).await?;
+                }
+            }
+
+            self.checkpoint(name, offset).await?;
+        }
+    }
+
+    async fn checkpoint(&self, cursor: &'static str, offset: u64) -> Result<(), SpoolError> {
+        let mut inner = self.inner.lock().await;
+        inner.offsets.insert(cursor, offset);
+        // Written aside and renamed over, so a crash never leaves a torn offset
+        let path = offset_path(&self.dir, cursor);
+        let tmp = path.with_extension("offset.tmp");
+        tokio::fs::write(&tmp, offset.to_string()).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        self.report(&inner);
+        Ok(())
+    }
+
+    async fn count_pending(&self, offset: u64, len: u64) -> std::io::Result<u64> {
+        if offset >= len {
+            return Ok(0);
+        }
+        let mut reader = BufReader::new(File::open(&self.log_path).await?);
+        reader.seek(SeekFrom::Start(offset)).await?;
+        let mut count = 0;
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line).await? > 0 {
+            count += 1;
+            line.clear();
+        }
+        Ok(count)
+    }
+
+    fn report(&self, inner: &SpoolFile) {
+        for (&name, cursor) in &self.cursors {
+            gauge!("facto_spool_depth", "cursor" => name)
+                .set(cursor.depth.load(Ordering::Relaxed) as f64);
+        }
+        gauge!("facto_spool_bytes").set((inner.len - min_offset(inner)) as f64);
+    }
+}
+
+async fn write_records(inner: &mut SpoolFile, data: &[u8]) -> std::io::Result<()> {
+    #[cfg(test)]
+    if let Some(written) = inner.fail_after.take() {
+        inner.file.write_all(&data[..written]).await?;
+        return Err(std::io::Error::other("no space left on device"));
+    }
+    inner.file.write_all(data).await?;
+    inner.file.sync_data().await
+}
+
+/// Length of the log up to the end of its last complete record. A crash
+/// during an append can leave part of a record at the end of the log.
+async fn complete_len(path: &Path, len: u64) -> std::io::Result<u64> {
+    let mut file = File::open(path).await?;
+    let mut buf = vec![0; 64 * 1024];
+    let mut end = len;
+    while end > 0 {
+        let start = end.saturating_sub(buf.len() as u64);
+        let chunk = &mut buf[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start)).await?;
+        file.read_exact(chunk).await?;
+        if let Some(newline) = chunk.iter().rposition(|&b| b == b'\n') {
+            return Ok(start + newline as u64 + 1);
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+fn offset_path(dir: &Path, cursor: &str) -> PathBuf {
+    dir.join(format!("{}.offset", cursor))
+}
+
+/// Position of the furthest-behind cursor; bytes before it can be dropped
+fn min_offset(inner: &SpoolFile) -> u64 {
+    inner.offsets.values().copied().min().unwrap_or(inner.len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_event;
+    use crate::verification::{TrustBasis, VerificationAssertion};
+
+    fn spooled(facto_id: &str) -> SpooledEvent {
+        let mut event = test_event();
+        event.facto_id = facto_id.to_string();
+        SpooledEvent {
+            event,
+            envelope: ServerEnvelope {
+                received_at: 0,
+                verification: VerificationAssertion {
+                    facto_id: facto_id.to_string(),
+                    event_hash: String::new(),
+                    algorithm: "ed25519".to_string(),
+                    signer_public_key: String::new(),
+                    trust_basis: TrustBasis::EmbeddedKey,
+                    verifier_id: "test".to_string(),
+                    verifier_public_key: String::new(),
+                    verified_at: 0,
+                    signature: String::new(),
+                },
+                verification_status: Default::default(),
+                tenant_id: None,
+                sandbox: false,
+                schema_violations: Vec::new(),
+                policy_findings: Vec::new(),
+                model_attestation: None,
+                headers: Default::default(),
+                redaction: None,
+                data_integrity: None,
+                backfill: None,
+                translation: None,
+            },
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("facto-spool-{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_drains_in_order_and_resumes() {
+        let dir = temp_dir("order");
+        let spool = Spool::open(dir.clone(), 1 << 20, &[NATS_CURSOR])
+            .await
+            .unwrap();
+        spool
+            .append(&[spooled("tr-1"), spooled("tr-2"), spooled("tr-3")])
+            .await
+            .unwrap();
+        assert_eq!(spool.depth(), 3);
+
+        // Fail on the second event
+        let mut seen = Vec::new();
+        let delivered = spool
+            .drain(NATS_CURSOR, |e| {
+                let fail = e.event.facto_id == "tr-2";
+                seen.push(e.event.facto_id);
+                async move {
+                    if fail {
+                        Err("down")
+                    } else {
+                        Ok(())
+                    }
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(delivered, 1);
+        assert_eq!(spool.depth(), 2);
+
+        // A reopened spool resumes at the failed event
+        drop(spool);
+        let spool = Spool::open(dir.clone(), 1 << 20, &[NATS_CURSOR])
+            .await
+            .unwrap();
+        assert_eq!(spool.depth(), 2);
+        let mut seen = Vec::new();
+        spool
+            .drain(NATS_CURSOR, |e| {
+                seen.push(e.event.facto_id);
+                async { Ok::<(), &str>(()) }
+            })
+            .await
+            .unwrap();
+        assert_eq!(seen, vec!["tr-2", "tr-3"]);
+        assert!(spool.is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_spooled_events_keep_their_bytes() {
+        let dir = temp_dir("raw");
+        let spool = Spool::open(dir.clone(), 1 << 20, &[NATS_CURSOR])
+            .await
+            .unwrap();
+        let mut event = spooled("tr-1");
+        let raw = serde_json::to_string_pretty(&event.event).unwrap();
+        event.event.raw = Some(raw.clone().into());
+        spool.append(&[event, spooled("tr-2")]).await.unwrap();
+
+        let mut seen = Vec::new();
+        spool
+            .drain(NATS_CURSOR, |e| {
+                seen.push(e.event.raw);
+                async { Ok::<(), &str>(()) }
+            })
+            .await
+            .unwrap();
+        assert_eq!(seen, vec![Some(raw.into()), None]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_append_leaves_no_partial_record() {
+        let dir = temp_dir("partial");
+        let spool = Spool::open(dir.clone(), 1 << 20, &[NATS_CURSOR])
+            .await
+            .unwrap();
+        spool.append(&[spooled("tr-1")]).await.unwrap();
+
+        spool.inner.lock().await.fail_after = Some(10);
+        assert!(spool.append(&[spooled("tr-2")]).await.is_err());
+        spool.append(&[spooled("tr-3")]).await.unwrap();
+        assert_eq!(spool.depth(), 2);
+
+        let mut seen = Vec::new();
+        spool
+            .drain(NATS_CURSOR, |e| {
+                seen.push(e.event.facto_id);
+                async { Ok::<(), &str>(()) }
+            })
+            .await
+            .unwrap();
+        assert_eq!(seen, vec!["tr-1", "tr-3"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_open_drops_unfinished_record() {
+        let dir = temp_dir("torn");
+        let spool = Spool::open(dir.clone(), 1 << 20, &[NATS_CURSOR])
+            .await
+            .unwrap();
+        spool.append(&[spooled("tr-1")]).await.unwrap();
+        drop(spool);
+
+        // A crash in the middle of an append
+        let mut log = std::fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join("spool.log"))
+            .unwrap();
+        std::io::Write::write_all(&mut log, br#"{"event":{"facto_id""#).unwrap();
+
+        let spool = Spool::open(dir.clone(), 1 << 20, &[NATS_CURSOR])
+            .await
+            .unwrap();
+        assert_eq!(spool.depth(), 1);
+        spool.append(&[spooled("tr-2")]).await.unwrap();
+
+        let mut seen = Vec::new();
+        spool
+            .drain(NATS_CURSOR, |e| {
+                seen.push(e.event.facto_id);
+                async { Ok::<(), &str>(()) }
+            })
+            .await
+            .unwrap();
+        assert_eq!(seen, vec!["tr-1", "tr-2"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_full_spool_rejects() {
+        let dir = temp_dir("full");
+        let spool = Spool::open(dir.clone(), 10, &[NATS_CURSOR]).await.unwrap();
+        assert!(matches!(
+            spool.append(&[spooled("tr-1")]).await,
+            Err(SpoolError::Full)
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cursors_drain_independently() {
+        let dir = temp_dir("cursors");
+        let cursors = [NATS_CURSOR, "export"];
+        let spool = Spool::open(dir.clone(), 1 << 20, &cursors).await.unwrap();
+        spool
+            .append(&[spooled("tr-1"), spooled("tr-2")])
+            .await
+            .unwrap();
+
+        let delivered = spool
+            .drain(NATS_CURSOR, |_| async { Ok::<(), &str>(()) })
+            .await
+            .unwrap();
+        assert_eq!(delivered, 2);
+        assert_eq!(spool.cursor_depth(NATS_CURSOR), 0);
+        assert_eq!(spool.cursor_depth("export"), 2);
+
+        // The log is kept for the lagging cursor, across restarts
+        drop(spool);
+        let spool = Spool::open(dir.clone(), 1 << 20, &cursors).await.unwrap();
+        assert_eq!(spool.cursor_depth(NATS_CURSOR), 0);
+        let mut seen = Vec::new();
+        spool
+            .drain("export", |e| {
+                seen.push(e.event.facto_id);
+                async { Ok::<(), &str>(()) }
+            })
+            .await
+            .unwrap();
+        assert_eq!(seen, vec!["tr-1", "tr-2"]);
+        assert!(spool.is_empty());
+
+        // Once every cursor caught up the log is truncated
+        let log_len = std::fs::metadata(dir.join("spool.log")).unwrap().len();
+        assert_eq!(log_len, 0);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
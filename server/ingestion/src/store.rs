@@ -0,0 +1,46 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A JSON document persisted to an optional file path. Writes go to a
+/// temporary file first and are renamed into place, so a crash never leaves
+/// a half-written document behind. Without a path the store is memory-only.
+pub struct JsonFile {
+    path: Option<PathBuf>,
+}
+
+impl JsonFile {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Read the document, or the default value if there is no file yet
+    pub fn load<T: DeserializeOwned + Default>(&self) -> anyhow::Result<T> {
+        match &self.path {
+            Some(path) if path.exists() => Ok(serde_json::from_slice(&std::fs::read(path)?)?),
+            _ => Ok(T::default()),
+        }
+    }
+
+    /// Check the document's directory can be written, as saving requires
+    pub fn check_writable(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let probe = path.with_extension("probe");
+        std::fs::write(&probe, b"ok")?;
+        std::fs::remove_file(&probe)
+    }
+
+    pub fn save<T: Serialize>(&self, value: &T) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+        std::fs::rename(&tmp, path)
+    }
+}
@@ -0,0 +1,289 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use futures::StreamExt;
+use metrics::{counter, gauge};
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, sync::Arc};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::admin::error_response;
+use crate::auth::{AuthError, Scope};
+use crate::tenants::{self, validate_tenant_id};
+use crate::transport;
+use crate::verification::{ServerEnvelope, ENVELOPE_HEADER};
+use crate::{AppState, FactoEvent};
+
+/// Events buffered per connection before newer ones are dropped
+pub const DEFAULT_TAIL_BUFFER: usize = 256;
+
+/// Largest buffer a client may ask for
+pub const MAX_TAIL_BUFFER: usize = 10_000;
+
+#[derive(Debug, Deserialize)]
+pub struct TailQuery {
+    /// Only events of this agent; all agents otherwise
+    pub agent_id: Option<String>,
+    /// Only events of this session
+    pub session_id: Option<String>,
+    /// Tenant to watch, for operators not bound to one
+    pub tenant_id: Option<String>,
+    /// Events buffered for a slow client, defaults to [`DEFAULT_TAIL_BUFFER`]
+    pub buffer: Option<usize>,
+}
+
+/// An accepted event with its server envelope
+#[derive(Debug, Clone, Serialize)]
+pub struct TailedEvent {
+    pub event: FactoEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub envelope: Option<ServerEnvelope>,
+}
+
+/// A message relayed to a live tail client
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TailMessage {
+    Event(Box<TailedEvent>),
+    /// The client fell behind and `dropped` events were skipped
+    Lagged {
+        dropped: u64,
+    },
+}
+
+impl TailMessage {
+    fn name(&self) -> &'static str {
+        match self {
+            TailMessage::Event(_) => "event",
+            TailMessage::Lagged { .. } => "lagged",
+        }
+    }
+}
+
+/// Agent ids become a subject token, so they cannot hold separators or
+/// wildcards
+fn valid_subject_token(token: &str) -> bool {
+    !token.is_empty()
+        && !token
+            .chars()
+            .any(|c| c == '.' || c == '*' || c == '>' || c.is_whitespace())
+}
+
+/// Relay events from a NATS subscription to a bounded channel. When the
+/// client falls behind, events are dropped rather than buffered without
+/// bound, and the client is told how many once it catches up.
+async fn relay(
+    mut subscriber: async_nats::Subscriber,
+    session_id: Option<String>,
+    tx: mpsc::Sender<TailMessage>,
+) {
+    let mut dropped: u64 = 0;
+    loop {
+        let message = tokio::select! {
+            message = subscriber.next() => message,
+            _ = tx.closed() => break,
+        };
+        let Some(message) = message else {
+            break;
+        };
+
+        let Ok(event) = serde_json::from_slice::<FactoEvent>(&message.payload) else {
+            continue;
+        };
+        if session_id
+            .as_ref()
+            .is_some_and(|session_id| *session_id != event.session_id)
+        {
+            continue;
+        }
+        let envelope = message
+            .headers
+            .as_ref()
+            .and_then(|h| h.get(ENVELOPE_HEADER))
+            .and_then(|v| serde_json::from_str(v.as_str()).ok());
+
+        if dropped > 0 {
+            match tx.try_send(TailMessage::Lagged { dropped }) {
+                Ok(()) => dropped = 0,
+                Err(mpsc::error::TrySendError::Full(_)) => {}
+                Err(mpsc::error::TrySendError::Closed(_)) => break,
+            }
+        }
+        let message = TailMessage::Event(Box::new(TailedEvent { event, envelope }));
+        match tx.try_send(message) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                dropped += 1;
+                counter!("facto_tail_dropped_total").increment(1);
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => break,
+        }
+    }
+}
+
+/// Authorize a tail request and subscribe to the subject it asks for
+async fn open_tail(
+    state: &AppState,
+    headers: &HeaderMap,
+    query: TailQuery,
+) -> Result<mpsc::Receiver<TailMessage>, Response> {
+    if !state.auth.admin_enabled() {
+        return Err(error_response(
+            StatusCode::FORBIDDEN,
+            "Admin API is disabled",
+        ));
+    }
+    let principal = state
+        .auth
+        .authorize(headers, Scope::Admin)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    // Tenant-bound operators only see their own tenant
+    let tenant_id = match (principal.tenant_id, query.tenant_id) {
+        (Some(own), Some(asked)) if own != asked => {
+            return Err(AuthError::InsufficientScope(Scope::Admin).into_response())
+        }
+        (Some(own), _) => Some(own),
+        (None, Some(asked)) => {
+            validate_tenant_id(&asked).map_err(|e| error_response(StatusCode::BAD_REQUEST, e))?;
+            Some(asked)
+        }
+        (None, None) => None,
+    };
+    let agent = match query.agent_id {
+        Some(agent_id) if !valid_subject_token(&agent_id) => {
+            return Err(error_response(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid agent_id: {}", agent_id),
+            ))
+        }
+        Some(agent_id) => agent_id,
+        None => "*".to_string(),
+    };
+
+    // Events published to Kafka never reach the NATS subjects tailed here
+    if state.sink.name() != "nats" {
+        return Err(error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Live tail requires TRANSPORT=nats",
+        ));
+    }
+    let Some(client) = state.connected_client().await else {
+        return Err(error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "NATS is not connected",
+        ));
+    };
+    // A session's events are all on one partition's subject
+    let subject = tenants::event_subject(tenant_id.as_deref(), &agent);
+    let subject = match (state.session_partitions, query.session_id.as_deref()) {
+        (0, _) => subject,
+        (partitions, Some(session_id)) => {
+            transport::partitioned_subject(subject, session_id, partitions)
+        }
+        (_, None) => format!("{}.*", subject),
+    };
+    let subscriber = client.subscribe(subject.clone()).await.map_err(|e| {
+        warn!("Failed to subscribe to {}: {}", subject, e);
+        error_response(StatusCode::SERVICE_UNAVAILABLE, "Failed to subscribe")
+    })?;
+    info!("{} started a live tail of {}", principal.name, subject);
+
+    let buffer = query
+        .buffer
+        .unwrap_or(DEFAULT_TAIL_BUFFER)
+        .clamp(1, MAX_TAIL_BUFFER);
+    let (tx, rx) = mpsc::channel(buffer);
+    tokio::spawn(async move {
+        gauge!("facto_tail_connections").increment(1.0);
+        relay(subscriber, query.session_id, tx).await;
+        gauge!("facto_tail_connections").decrement(1.0);
+    });
+    Ok(rx)
+}
+
+/// Live tail of accepted events: `GET /v1/stream?agent_id=&session_id=`.
+/// Relayed over Server-Sent Events, or over a WebSocket when the request
+/// asks for an upgrade.
+pub async fn stream_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TailQuery>,
+    headers: HeaderMap,
+    ws: Option<WebSocketUpgrade>,
+) -> Response {
+    let rx = match open_tail(&state, &headers, query).await {
+        Ok(rx) => rx,
+        Err(response) => return response,
+    };
+
+    match ws {
+        Some(ws) => ws.on_upgrade(|socket| relay_websocket(socket, rx)),
+        None => {
+            let events = futures::stream::unfold(rx, |mut rx| async move {
+                let message = rx.recv().await?;
+                let event = Event::default()
+                    .event(message.name())
+                    .json_data(&message)
+                    .unwrap_or_default();
+                Some((Ok::<_, Infallible>(event), rx))
+            });
+            Sse::new(events)
+                .keep_alive(KeepAlive::default())
+                .into_response()
+        }
+    }
+}
+
+async fn relay_websocket(mut socket: WebSocket, mut rx: mpsc::Receiver<TailMessage>) {
+    loop {
+        tokio::select! {
+            message = rx.recv() => {
+                let Some(message) = message else {
+                    break;
+                };
+                let Ok(text) = serde_json::to_string(&message) else {
+                    continue;
+                };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            // Anything but a close from the client is ignored
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_ids_are_single_tokens() {
+        assert!(valid_subject_token("agent-1"));
+        assert!(!valid_subject_token(""));
+        assert!(!valid_subject_token("agent.1"));
+        assert!(!valid_subject_token("*"));
+        assert!(!valid_subject_token(">"));
+    }
+
+    #[test]
+    fn test_messages_are_tagged() {
+        let lagged = serde_json::to_value(TailMessage::Lagged { dropped: 3 }).unwrap();
+        assert_eq!(lagged["type"], "lagged");
+        assert_eq!(lagged["dropped"], 3);
+    }
+}
@@ -0,0 +1,272 @@
+//! Distributed tracing with W3C trace context.
+//!
+//! Spans are OpenTelemetry spans through `tracing-opentelemetry`. A request
+//! carrying a `traceparent` header continues that remote trace, and the
+//! context of the span that accepted an event is attached to the published
+//! message so consumers can correlate the two.
+//!
+//! Export follows the standard `OTEL_*` variables: spans are exported over
+//! OTLP when `OTEL_EXPORTER_OTLP_ENDPOINT` (or its `_TRACES_` form) is set,
+//! with `OTEL_EXPORTER_OTLP_PROTOCOL` choosing `http/protobuf` (the
+//! default), `http/json` or `grpc`. `OTEL_TRACES_SAMPLER`,
+//! `OTEL_SERVICE_NAME` and `OTEL_RESOURCE_ATTRIBUTES` apply as the SDK
+//! defines them. Without an endpoint, spans still carry trace context but
+//! are not exported.
+//!
+//! Span fields become attributes; `otel.kind` sets the span kind and
+//! `otel.status_code` / `otel.status_message` mark a failed span.
+
+use anyhow::bail;
+use opentelemetry::{
+    propagation::TextMapPropagator,
+    trace::{TraceContextExt, TracerProvider as _},
+    KeyValue,
+};
+use opentelemetry_otlp::{MetricExporter, Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    metrics::{PeriodicReader, SdkMeterProvider},
+    propagation::TraceContextPropagator,
+    trace::{SdkTracer, SdkTracerProvider},
+    Resource,
+};
+use std::{collections::HashMap, time::Duration};
+use tracing::{warn, Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// W3C trace context request and message header
+pub const TRACEPARENT: &str = "traceparent";
+
+/// W3C vendor trace state, propagated with `traceparent`
+pub const TRACESTATE: &str = "tracestate";
+
+/// Message header carrying the trace id alone, for consumers that do not
+/// parse `traceparent`
+pub const TRACE_ID_HEADER: &str = "Facto-Trace-Id";
+
+const SERVICE_NAME: &str = "facto-ingestion";
+
+// ============================================================================
+// Trace Context
+// ============================================================================
+
+/// Continue the remote trace named by `traceparent` and `tracestate`, looked
+/// up with `header`, in `span`. Call before the span is first entered;
+/// invalid headers leave it in its own trace.
+pub fn continue_trace<'a>(span: &Span, header: impl Fn(&str) -> Option<&'a str>) {
+    let carrier = [TRACEPARENT, TRACESTATE]
+        .into_iter()
+        .filter_map(|name| header(name).map(|value| (name.to_string(), value.to_string())))
+        .collect::<HashMap<_, _>>();
+    if carrier.is_empty() {
+        return;
+    }
+    let context = TraceContextPropagator::new().extract(&carrier);
+    if context.span().span_context().is_valid() {
+        let _ = span.set_parent(context);
+    }
+}
+
+/// Headers carrying the trace context of `span`: `traceparent`, any
+/// `tracestate`, and the trace id alone. Empty when tracing is not set up.
+pub fn context_headers(span: &Span) -> HashMap<String, String> {
+    let context = span.context();
+    let mut headers = HashMap::new();
+    let trace_id = context.span().span_context().trace_id();
+    if !context.span().span_context().is_valid() {
+        return headers;
+    }
+    TraceContextPropagator::new().inject_context(&context, &mut headers);
+    headers.insert(TRACE_ID_HEADER.to_string(), trace_id.to_string());
+    headers
+}
+
+/// Span of an HTTP request, continuing the caller's trace when the request
+/// carries a `traceparent` header
+pub fn request_span<B>(request: &axum::http::Request<B>) -> Span {
+    let span = tracing::info_span!(
+        "request",
+        otel.kind = "server",
+        http.request.method = %request.method(),
+        url.path = request.uri().path(),
+    );
+    continue_trace(&span, |name| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    });
+    span
+}
+
+// ============================================================================
+// OTLP Export
+// ============================================================================
+
+/// The OTLP protocol exports of `signal` (`TRACES` or `METRICS`) use, from
+/// `OTEL_EXPORTER_OTLP_<signal>_PROTOCOL` or `OTEL_EXPORTER_OTLP_PROTOCOL`.
+/// `None` when no endpoint is set or `OTEL_<signal>_EXPORTER` is `none`.
+pub fn otlp_protocol(signal: &str) -> anyhow::Result<Option<Protocol>> {
+    let var = |name: String| std::env::var(name).ok().filter(|v| !v.is_empty());
+    match var(format!("OTEL_{}_EXPORTER", signal)).as_deref() {
+        None | Some("otlp") => {}
+        Some("none") => return Ok(None),
+        Some(other) => bail!("Unsupported OTEL_{}_EXPORTER {}", signal, other),
+    }
+    let endpoint = var(format!("OTEL_EXPORTER_OTLP_{}_ENDPOINT", signal))
+        .or_else(|| var("OTEL_EXPORTER_OTLP_ENDPOINT".to_string()));
+    if endpoint.is_none() {
+        return Ok(None);
+    }
+    let protocol = var(format!("OTEL_EXPORTER_OTLP_{}_PROTOCOL", signal))
+        .or_else(|| var("OTEL_EXPORTER_OTLP_PROTOCOL".to_string()));
+    match protocol.as_deref() {
+        None | Some("http/protobuf") => Ok(Some(Protocol::HttpBinary)),
+        Some("http/json") => Ok(Some(Protocol::HttpJson)),
+        Some("grpc") => Ok(Some(Protocol::Grpc)),
+        Some(other) => bail!("Unsupported OTLP protocol {}", other),
+    }
+}
+
+/// The service instance exporting telemetry. `OTEL_SERVICE_NAME` and
+/// `OTEL_RESOURCE_ATTRIBUTES` override the defaults.
+pub fn resource(instance_id: &str) -> Resource {
+    let mut builder = Resource::builder()
+        .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")))
+        .with_attribute(KeyValue::new(
+            "service.instance.id",
+            instance_id.to_string(),
+        ));
+    if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+        builder = builder.with_service_name(SERVICE_NAME);
+    }
+    builder.build()
+}
+
+/// The tracer provider of the service
+pub struct Tracing {
+    provider: SdkTracerProvider,
+    exporting: bool,
+}
+
+impl Tracing {
+    /// Build the tracer provider from the `OTEL_*` variables. Must be called
+    /// on the runtime, which a gRPC exporter runs on.
+    pub fn from_env(instance_id: &str) -> anyhow::Result<Self> {
+        let mut builder = SdkTracerProvider::builder().with_resource(resource(instance_id));
+        let protocol = otlp_protocol("TRACES")?;
+        if let Some(protocol) = protocol {
+            let exporter = match protocol {
+                Protocol::Grpc => SpanExporter::builder().with_tonic().build()?,
+                http => SpanExporter::builder()
+                    .with_http()
+                    .with_protocol(http)
+                    .build()?,
+            };
+            builder = builder.with_batch_exporter(exporter);
+        }
+        Ok(Self {
+            provider: builder.build(),
+            exporting: protocol.is_some(),
+        })
+    }
+
+    /// The layer turning `tracing` spans into OpenTelemetry spans
+    pub fn layer<S>(&self) -> OpenTelemetryLayer<S, SdkTracer>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer().with_tracer(self.provider.tracer(SERVICE_NAME))
+    }
+
+    pub fn exporting(&self) -> bool {
+        self.exporting
+    }
+
+    /// Export the spans still queued, waiting at most `timeout`
+    pub async fn shutdown(self, timeout: Duration) {
+        let provider = self.provider;
+        let result =
+            tokio::task::spawn_blocking(move || provider.shutdown_with_timeout(timeout)).await;
+        if let Ok(Err(e)) = result {
+            warn!("Failed to export the remaining spans: {}", e);
+        }
+    }
+}
+
+/// The meter provider exporting metrics over OTLP, configured like traces,
+/// or `None` when metrics are not exported. Must be called on the runtime.
+pub fn meter_provider(instance_id: &str) -> anyhow::Result<Option<SdkMeterProvider>> {
+    let Some(protocol) = otlp_protocol("METRICS")? else {
+        return Ok(None);
+    };
+    let exporter = match protocol {
+        Protocol::Grpc => MetricExporter::builder().with_tonic().build()?,
+        http => MetricExporter::builder()
+            .with_http()
+            .with_protocol(http)
+            .build()?,
+    };
+    Ok(Some(
+        SdkMeterProvider::builder()
+            .with_resource(resource(instance_id))
+            .with_reader(PeriodicReader::builder(exporter).build())
+            .build(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    const HEADER: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    fn traced<T>(f: impl FnOnce() -> T) -> T {
+        let tracing = Tracing {
+            provider: SdkTracerProvider::builder().build(),
+            exporting: false,
+        };
+        let subscriber = tracing_subscriber::registry().with(tracing.layer());
+        tracing::subscriber::with_default(subscriber, f)
+    }
+
+    #[test]
+    fn test_spans_continue_remote_trace() {
+        traced(|| {
+            let request = tracing::info_span!("request");
+            continue_trace(&request, |name| (name == TRACEPARENT).then_some(HEADER));
+            let headers = context_headers(&request);
+            assert_eq!(headers[TRACE_ID_HEADER], "4bf92f3577b34da6a3ce929d0e0e4736");
+            let traceparent = &headers[TRACEPARENT];
+            assert!(traceparent.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+            assert!(traceparent.ends_with("-01"));
+            assert_ne!(traceparent, HEADER);
+
+            let publish = request.in_scope(|| tracing::info_span!("nats.publish"));
+            let child = context_headers(&publish);
+            assert_eq!(child[TRACE_ID_HEADER], headers[TRACE_ID_HEADER]);
+            assert_ne!(child[TRACEPARENT], headers[TRACEPARENT]);
+        });
+    }
+
+    #[test]
+    fn test_invalid_traceparent_starts_a_new_trace() {
+        traced(|| {
+            for header in [
+                "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+                "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+                "00-4bf92f35-00f067aa0ba902b7-01",
+            ] {
+                let span = tracing::info_span!("request");
+                continue_trace(&span, |name| (name == TRACEPARENT).then_some(header));
+                let trace_id = &context_headers(&span)[TRACE_ID_HEADER];
+                assert_eq!(trace_id.len(), 32);
+                assert_ne!(trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+            }
+        });
+
+        // Without the layer there is no context to attach
+        assert!(context_headers(&tracing::info_span!("request")).is_empty());
+    }
+}
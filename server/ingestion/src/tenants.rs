@@ -0,0 +1,403 @@
+use dashmap::DashMap;
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, num::NonZeroU32, path::PathBuf, sync::RwLock};
+use tracing::info;
+
+use crate::headers::{HeaderError, TenantHeaders};
+use crate::store::JsonFile;
+use crate::verification::{now_nanos, EnforcementMode};
+use crate::FactoEvent;
+
+/// Metrics label and display name for requests without a tenant
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Stream accepted events are published to
+pub const EVENT_STREAM: &str = "FACTO_EVENTS";
+
+/// Subjects of the FACTO_EVENTS stream: shared events and per-tenant events
+pub const EVENT_STREAM_SUBJECTS: [&str; 2] = ["facto.events.>", "facto.tenants.>"];
+
+/// NATS subject an agent's events are published on. Events without a
+/// tenant keep the original `facto.events.{agent}` subject.
+pub fn event_subject(tenant_id: Option<&str>, agent_id: &str) -> String {
+    match tenant_id {
+        Some(tenant_id) => format!("facto.tenants.{}.events.{}", tenant_id, agent_id),
+        None => format!("facto.events.{}", agent_id),
+    }
+}
+
+/// Tenant ids are used as subject tokens: lowercase alphanumerics, `-` and
+/// `_`. The metrics label for tenantless requests is reserved.
+pub fn validate_tenant_id(tenant_id: &str) -> Result<(), TenantError> {
+    let valid = !tenant_id.is_empty()
+        && tenant_id.len() <= 64
+        && tenant_id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        && tenant_id != DEFAULT_TENANT;
+    if valid {
+        Ok(())
+    } else {
+        Err(TenantError::InvalidId(tenant_id.to_string()))
+    }
+}
+
+// ============================================================================
+// Tenant Configuration
+// ============================================================================
+
+/// Limits applied to all of a tenant's agents together
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TenantLimits {
+    /// Events per second across the tenant
+    pub rate_limit_per_sec: Option<NonZeroU32>,
+    /// Events per second for each of the tenant's agents, in place of
+    /// `RATE_LIMIT_PER_AGENT`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_rate_limit_per_sec: Option<NonZeroU32>,
+    /// Event bytes each of the tenant's agents may send per agent quota
+    /// window, in place of `AGENT_BYTE_QUOTA`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_byte_quota: Option<u64>,
+    /// Event bytes accepted per quota window
+    pub byte_quota: Option<u64>,
+    /// Length of the byte quota window; defaults to one day
+    #[serde(default = "default_quota_window_secs")]
+    pub quota_window_secs: u64,
+}
+
+fn default_quota_window_secs() -> u64 {
+    86_400
+}
+
+impl Default for TenantLimits {
+    fn default() -> Self {
+        Self {
+            rate_limit_per_sec: None,
+            agent_rate_limit_per_sec: None,
+            agent_byte_quota: None,
+            byte_quota: None,
+            quota_window_secs: default_quota_window_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantConfig {
+    pub tenant_id: String,
+    #[serde(flatten)]
+    pub limits: TenantLimits,
+    /// Headers attached to the NATS messages of the tenant's events
+    #[serde(default, skip_serializing_if = "TenantHeaders::is_empty")]
+    pub headers: TenantHeaders,
+    /// Enforcement mode of the tenant's agents, in place of
+    /// `VERIFICATION_MODE`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enforcement_mode: Option<EnforcementMode>,
+    pub updated_at: i64,
+}
+
+/// Bytes accepted for a tenant in the current quota window
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct TenantUsage {
+    pub window_start: i64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TenantRejection {
+    RateLimited,
+    ByteQuotaExceeded,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TenantError {
+    #[error("invalid tenant id: {0}")]
+    InvalidId(String),
+    #[error("unknown tenant: {0}")]
+    Unknown(String),
+    #[error(transparent)]
+    InvalidHeaders(#[from] HeaderError),
+    #[error("failed to persist tenants: {0}")]
+    Persistence(String),
+}
+
+// ============================================================================
+// Tenant Registry
+// ============================================================================
+
+/// Configured tenants with their live rate limiters and byte usage.
+///
+/// Tenants are resolved from credentials, never from the request body.
+/// Tenants without configuration are unlimited.
+pub struct Tenants {
+    configs: RwLock<BTreeMap<String, TenantConfig>>,
+    limiters: DashMap<String, DefaultDirectRateLimiter>,
+    usage: DashMap<String, TenantUsage>,
+    store: JsonFile,
+}
+
+impl Tenants {
+    pub fn new(path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let store = JsonFile::new(path);
+        let configs: BTreeMap<String, TenantConfig> = store.load()?;
+        info!("Loaded {} tenant configurations", configs.len());
+
+        let limiters = DashMap::new();
+        for config in configs.values() {
+            if let Some(rate) = config.limits.rate_limit_per_sec {
+                limiters.insert(
+                    config.tenant_id.clone(),
+                    RateLimiter::direct(Quota::per_second(rate)),
+                );
+            }
+        }
+
+        Ok(Self {
+            configs: RwLock::new(configs),
+            limiters,
+            usage: DashMap::new(),
+            store,
+        })
+    }
+
+    pub fn list(&self) -> Vec<TenantConfig> {
+        self.configs.read().unwrap().values().cloned().collect()
+    }
+
+    pub fn get(&self, tenant_id: &str) -> Option<TenantConfig> {
+        self.configs.read().unwrap().get(tenant_id).cloned()
+    }
+
+    /// The tenant's per-agent rate limit, if it sets one
+    pub fn agent_rate_limit(&self, tenant_id: &str) -> Option<NonZeroU32> {
+        self.configs
+            .read()
+            .unwrap()
+            .get(tenant_id)
+            .and_then(|c| c.limits.agent_rate_limit_per_sec)
+    }
+
+    /// The tenant's per-agent byte quota, if it sets one
+    pub fn agent_byte_quota(&self, tenant_id: &str) -> Option<u64> {
+        self.configs
+            .read()
+            .unwrap()
+            .get(tenant_id)
+            .and_then(|c| c.limits.agent_byte_quota)
+    }
+
+    pub fn usage(&self, tenant_id: &str) -> TenantUsage {
+        self.usage.get(tenant_id).map(|u| *u).unwrap_or_default()
+    }
+
+    /// The tenant's enforcement mode, if it sets one
+    pub fn enforcement_mode(&self, tenant_id: &str) -> Option<EnforcementMode> {
+        self.configs
+            .read()
+            .unwrap()
+            .get(tenant_id)
+            .and_then(|c| c.enforcement_mode)
+    }
+
+    /// Create or replace a tenant's limits; applies immediately
+    pub fn upsert(
+        &self,
+        tenant_id: &str,
+        limits: TenantLimits,
+    ) -> Result<TenantConfig, TenantError> {
+        let config = self.update(tenant_id, |config| config.limits = limits)?;
+        match config.limits.rate_limit_per_sec {
+            Some(rate) => {
+                self.limiters.insert(
+                    tenant_id.to_string(),
+                    RateLimiter::direct(Quota::per_second(rate)),
+                );
+            }
+            None => {
+                self.limiters.remove(tenant_id);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Create or replace a tenant's message headers; applies to events
+    /// accepted from now on
+    pub fn set_headers(
+        &self,
+        tenant_id: &str,
+        headers: TenantHeaders,
+    ) -> Result<TenantConfig, TenantError> {
+        headers.validate()?;
+        self.update(tenant_id, |config| config.headers = headers)
+    }
+
+    /// Set or clear the enforcement mode of a tenant's agents; applies to
+    /// the next request
+    pub fn set_enforcement_mode(
+        &self,
+        tenant_id: &str,
+        mode: Option<EnforcementMode>,
+    ) -> Result<TenantConfig, TenantError> {
+        self.update(tenant_id, |config| config.enforcement_mode = mode)
+    }
+
+    /// Apply `change` to a tenant's configuration, creating it if needed
+    fn update(
+        &self,
+        tenant_id: &str,
+        change: impl FnOnce(&mut TenantConfig),
+    ) -> Result<TenantConfig, TenantError> {
+        validate_tenant_id(tenant_id)?;
+        let mut configs = self.configs.write().unwrap();
+        let mut config = configs
+            .get(tenant_id)
+            .cloned()
+            .unwrap_or_else(|| TenantConfig {
+                tenant_id: tenant_id.to_string(),
+                limits: TenantLimits::default(),
+                headers: TenantHeaders::default(),
+                enforcement_mode: None,
+                updated_at: 0,
+            });
+        change(&mut config);
+        config.updated_at = now_nanos();
+
+        let mut updated = configs.clone();
+        updated.insert(tenant_id.to_string(), config.clone());
+        self.persist(&updated)?;
+        *configs = updated;
+        Ok(config)
+    }
+
+    /// Add a tenant's configured headers for one of its events
+    pub fn apply_headers(
+        &self,
+        tenant_id: &str,
+        event: &FactoEvent,
+        headers: &mut BTreeMap<String, String>,
+    ) {
+        if let Some(config) = self.configs.read().unwrap().get(tenant_id) {
+            config.headers.apply(event, tenant_id, headers);
+        }
+    }
+
+    pub fn remove(&self, tenant_id: &str) -> Result<TenantConfig, TenantError> {
+        let mut configs = self.configs.write().unwrap();
+        let mut updated = configs.clone();
+        let removed = updated
+            .remove(tenant_id)
+            .ok_or_else(|| TenantError::Unknown(tenant_id.to_string()))?;
+        self.persist(&updated)?;
+        *configs = updated;
+
+        self.limiters.remove(tenant_id);
+        Ok(removed)
+    }
+
+    /// Admit `bytes` of events for a tenant, counting them against its quota
+    pub fn admit(&self, tenant_id: &str, bytes: u64) -> Result<(), TenantRejection> {
+        let limits = match self.configs.read().unwrap().get(tenant_id) {
+            Some(config) => config.limits.clone(),
+            None => return Ok(()),
+        };
+
+        if let Some(limiter) = self.limiters.get(tenant_id) {
+            if limiter.check().is_err() {
+                return Err(TenantRejection::RateLimited);
+            }
+        }
+
+        let Some(byte_quota) = limits.byte_quota else {
+            return Ok(());
+        };
+        let now = now_nanos();
+        let window = (limits.quota_window_secs as i64).saturating_mul(1_000_000_000);
+        let mut usage = self.usage.entry(tenant_id.to_string()).or_default();
+        if now - usage.window_start >= window {
+            *usage = TenantUsage {
+                window_start: now,
+                bytes: 0,
+            };
+        }
+        if usage.bytes + bytes > byte_quota {
+            return Err(TenantRejection::ByteQuotaExceeded);
+        }
+        usage.bytes += bytes;
+        Ok(())
+    }
+
+    fn persist(&self, configs: &BTreeMap<String, TenantConfig>) -> Result<(), TenantError> {
+        self.store
+            .save(configs)
+            .map_err(|e| TenantError::Persistence(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant_subjects() {
+        assert_eq!(event_subject(None, "agent-1"), "facto.events.agent-1");
+        assert_eq!(
+            event_subject(Some("acme"), "agent-1"),
+            "facto.tenants.acme.events.agent-1"
+        );
+        assert!(validate_tenant_id("acme-prod_1").is_ok());
+        assert!(validate_tenant_id("default").is_err());
+        assert!(validate_tenant_id("Acme").is_err());
+        assert!(validate_tenant_id("a.b").is_err());
+    }
+
+    #[test]
+    fn test_byte_quota() {
+        let tenants = Tenants::new(None).unwrap();
+        tenants
+            .upsert(
+                "acme",
+                TenantLimits {
+                    rate_limit_per_sec: None,
+                    agent_rate_limit_per_sec: None,
+                    agent_byte_quota: None,
+                    byte_quota: Some(100),
+                    quota_window_secs: 3600,
+                },
+            )
+            .unwrap();
+
+        assert!(tenants.admit("acme", 60).is_ok());
+        assert_eq!(
+            tenants.admit("acme", 60),
+            Err(TenantRejection::ByteQuotaExceeded)
+        );
+        assert!(tenants.admit("acme", 40).is_ok());
+        assert_eq!(tenants.usage("acme").bytes, 100);
+
+        // Unconfigured tenants are unlimited
+        assert!(tenants.admit("other", u64::MAX).is_ok());
+    }
+
+    #[test]
+    fn test_rate_limit_applies_live() {
+        let tenants = Tenants::new(None).unwrap();
+        tenants
+            .upsert(
+                "acme",
+                TenantLimits {
+                    rate_limit_per_sec: NonZeroU32::new(1),
+                    agent_rate_limit_per_sec: None,
+                    agent_byte_quota: None,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(tenants.admit("acme", 1).is_ok());
+        assert_eq!(tenants.admit("acme", 1), Err(TenantRejection::RateLimited));
+
+        tenants.remove("acme").unwrap();
+        assert!(tenants.admit("acme", 1).is_ok());
+    }
+}
@@ -0,0 +1,81 @@
+//! Events for tests of the ingestion server and its tools.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ed25519_dalek::{Signer, SigningKey};
+use std::collections::BTreeMap;
+
+use crate::crypto::{build_canonical_form, compute_event_hash};
+use crate::{ExecutionMeta, FactoEvent, Proof};
+
+/// An unsigned event with fixed contents
+pub fn test_event() -> FactoEvent {
+    FactoEvent {
+        event_version: 1,
+        facto_id: "tr-test-123".to_string(),
+        agent_id: "agent-test".to_string(),
+        session_id: "session-test".to_string(),
+        parent_facto_id: None,
+        action_type: "llm_call".to_string(),
+        status: "success".to_string(),
+        input_data: serde_json::json!({"prompt": "test"}),
+        output_data: serde_json::json!({"response": "test"}),
+        execution_meta: ExecutionMeta {
+            model_id: Some("gpt-4".to_string()),
+            model_hash: None,
+            temperature: Some(0.7),
+            seed: None,
+            max_tokens: Some(1000),
+            tool_calls: vec![],
+            sdk_version: "0.1.0".to_string(),
+            sdk_language: "python".to_string(),
+            tags: BTreeMap::new(),
+        },
+        proof: Proof {
+            signature: "".to_string(),
+            public_key: "".to_string(),
+            prev_hash: "0".repeat(64),
+            event_hash: "".to_string(),
+            algorithm: None,
+            canonical_version: None,
+            verification_method: None,
+        },
+        started_at: 1000000000,
+        completed_at: 1000000001,
+        raw: None,
+    }
+}
+
+/// Fill in the proof of `event` using `key`
+pub fn sign_test_event(mut event: FactoEvent, key: &SigningKey) -> FactoEvent {
+    event.proof.public_key = BASE64.encode(key.verifying_key().as_bytes());
+    let canonical = build_canonical_form(&event).unwrap();
+    event.proof.event_hash = compute_event_hash(&canonical);
+    event.proof.signature = BASE64.encode(key.sign(canonical.as_bytes()).to_bytes());
+    event
+}
+
+/// The pre-1.0 SDK shape of `event`, as sent to `/v1/ingest/legacy`
+pub fn legacy_test_event(event: &FactoEvent) -> serde_json::Value {
+    let meta = &event.execution_meta;
+    serde_json::json!({
+        "trace_id": event.facto_id,
+        "agent_id": event.agent_id,
+        "session_id": event.session_id,
+        "parent_trace_id": event.parent_facto_id,
+        "action_type": event.action_type,
+        "status": event.status,
+        "input": event.input_data,
+        "output": event.output_data,
+        "model_id": meta.model_id,
+        "temperature": meta.temperature,
+        "seed": meta.seed,
+        "tool_calls": meta.tool_calls,
+        "sdk_version": meta.sdk_version,
+        "signature": event.proof.signature,
+        "public_key": event.proof.public_key,
+        "prev_hash": event.proof.prev_hash,
+        "event_hash": event.proof.event_hash,
+        "started_at": event.started_at,
+        "completed_at": event.completed_at,
+    })
+}
@@ -0,0 +1,466 @@
+use axum::{extract::Request, Router};
+use hyper::{body::Incoming, server::conn::http1};
+use hyper_util::rt::TokioIo;
+use metrics::counter;
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore, ServerConfig,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+use tokio::{net::TcpListener, sync::mpsc};
+use tower::Service;
+use tracing::{debug, error, info, warn};
+use x509_parser::{
+    certificate::X509Certificate,
+    der_parser::asn1_rs::{BmpString, Tag, UniversalString},
+    prelude::FromDer,
+    x509::AttributeTypeAndValue,
+};
+
+use crate::auth::{Principal, Scope};
+use crate::shutdown::Shutdown;
+use crate::tenants::{validate_tenant_id, TenantError};
+
+/// Time a client has to complete the TLS handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether clients must present a certificate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientAuth {
+    /// Clients without a certificate authenticate with API keys or JWTs
+    Optional,
+    /// The handshake fails without a certificate issued by the client CA
+    Required,
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// CA that issues client certificates; enables mTLS
+    pub client_ca_path: Option<PathBuf>,
+    pub client_auth: ClientAuth,
+    /// How often the files are checked for rotation
+    pub reload_interval: Duration,
+}
+
+impl TlsConfig {
+    /// Read `TLS_CERT_PATH`, `TLS_KEY_PATH`, `TLS_CLIENT_CA_PATH`,
+    /// `TLS_CLIENT_AUTH` (required or optional) and `TLS_RELOAD_SECS`. TLS is
+    /// off unless a certificate is configured.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let (cert_path, key_path) = match (
+            std::env::var("TLS_CERT_PATH").ok(),
+            std::env::var("TLS_KEY_PATH").ok(),
+        ) {
+            (Some(cert), Some(key)) => (PathBuf::from(cert), PathBuf::from(key)),
+            (None, None) => return Ok(None),
+            _ => anyhow::bail!("TLS_CERT_PATH and TLS_KEY_PATH must be set together"),
+        };
+        let client_ca_path = std::env::var("TLS_CLIENT_CA_PATH").ok().map(PathBuf::from);
+        let client_auth = match std::env::var("TLS_CLIENT_AUTH").as_deref() {
+            Ok("required") | Err(_) => ClientAuth::Required,
+            Ok("optional") => ClientAuth::Optional,
+            Ok(other) => anyhow::bail!("Invalid TLS_CLIENT_AUTH: {}", other),
+        };
+        let reload_secs: u64 = std::env::var("TLS_RELOAD_SECS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .expect("Invalid TLS_RELOAD_SECS");
+
+        Ok(Some(Self {
+            cert_path,
+            key_path,
+            client_ca_path,
+            client_auth,
+            reload_interval: Duration::from_secs(reload_secs),
+        }))
+    }
+
+    fn paths(&self) -> Vec<&Path> {
+        [
+            Some(&self.cert_path),
+            Some(&self.key_path),
+            self.client_ca_path.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(PathBuf::as_path)
+        .collect()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("failed to read {0}: {1}")]
+    Read(String, rustls::pki_types::pem::Error),
+    #[error("no certificates in {0}")]
+    NoCertificates(String),
+    #[error("invalid client CA: {0}")]
+    ClientCa(String),
+    #[error(transparent)]
+    Rustls(#[from] rustls::Error),
+}
+
+fn read_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let display = || path.display().to_string();
+    let certificates = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| TlsError::Read(display(), e))?;
+    if certificates.is_empty() {
+        return Err(TlsError::NoCertificates(display()));
+    }
+    Ok(certificates)
+}
+
+/// Build the server configuration from the files as they are now
+fn load(config: &TlsConfig) -> Result<Arc<ServerConfig>, TlsError> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let certificates = read_certificates(&config.cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(&config.key_path)
+        .map_err(|e| TlsError::Read(config.key_path.display().to_string(), e))?;
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+    let builder = match &config.client_ca_path {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for certificate in read_certificates(path)? {
+                roots.add(certificate)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = match config.client_auth {
+                ClientAuth::Required => verifier.build(),
+                ClientAuth::Optional => verifier.allow_unauthenticated().build(),
+            }
+            .map_err(|e| TlsError::ClientCa(e.to_string()))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut server_config = builder.with_single_cert(certificates, key)?;
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(server_config))
+}
+
+fn modified(paths: &[&Path]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// TLS termination for the listener. Certificates, keys and the client CA
+/// are read again when their files change, so rotated certificates apply to
+/// new connections without a restart.
+pub struct Tls {
+    config: TlsConfig,
+    current: RwLock<Arc<ServerConfig>>,
+    modified: RwLock<Vec<Option<SystemTime>>>,
+}
+
+impl Tls {
+    pub fn new(config: TlsConfig) -> Result<Self, TlsError> {
+        let current = load(&config)?;
+        let modified = modified(&config.paths());
+        info!(
+            "TLS enabled with {}{}",
+            config.cert_path.display(),
+            match (&config.client_ca_path, config.client_auth) {
+                (Some(_), ClientAuth::Required) => ", client certificates required",
+                (Some(_), ClientAuth::Optional) => ", client certificates accepted",
+                (None, _) => "",
+            }
+        );
+        Ok(Self {
+            config,
+            current: RwLock::new(current),
+            modified: RwLock::new(modified),
+        })
+    }
+
+    /// Whether clients can authenticate with certificates
+    pub fn client_certificates(&self) -> bool {
+        self.config.client_ca_path.is_some()
+    }
+
+    fn acceptor(&self) -> tokio_rustls::TlsAcceptor {
+        tokio_rustls::TlsAcceptor::from(self.current.read().unwrap().clone())
+    }
+
+    /// Reload the files if any of them changed. A failed reload keeps the
+    /// current configuration.
+    fn reload_if_changed(&self) {
+        let modified = modified(&self.config.paths());
+        if *self.modified.read().unwrap() == modified {
+            return;
+        }
+        match load(&self.config) {
+            Ok(config) => {
+                *self.current.write().unwrap() = config;
+                *self.modified.write().unwrap() = modified;
+                info!("Reloaded TLS certificates");
+                counter!("facto_tls_reloads_total", "result" => "ok").increment(1);
+            }
+            Err(e) => {
+                // Files are often replaced one at a time; retry on the next check
+                warn!("Failed to reload TLS certificates: {}", e);
+                counter!("facto_tls_reloads_total", "result" => "error").increment(1);
+            }
+        }
+    }
+
+    /// Watch the certificate files for rotation
+    pub async fn watch(self: Arc<Self>) {
+        if self.config.reload_interval.is_zero() {
+            return;
+        }
+        loop {
+            tokio::time::sleep(self.config.reload_interval).await;
+            self.reload_if_changed();
+        }
+    }
+}
+
+// ============================================================================
+// Client Identity
+// ============================================================================
+
+/// The subject of a verified client certificate: the common name is the
+/// agent id and the organization, if any, the tenant. Added to requests as
+/// an extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientIdentity {
+    pub agent_id: String,
+    pub tenant_id: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IdentityError {
+    #[error("malformed certificate: {0}")]
+    Malformed(String),
+    #[error("subject must have exactly one common name")]
+    CommonName,
+    #[error("subject has more than one organization")]
+    Organization,
+    #[error("unreadable subject attribute")]
+    Attribute,
+    #[error("certificate organization: {0}")]
+    Tenant(#[from] TenantError),
+}
+
+/// Read a subject attribute of any of the directory string types
+fn attribute_value(attribute: &AttributeTypeAndValue) -> Result<String, IdentityError> {
+    let value = attribute.attr_value();
+    match value.tag() {
+        Tag::BmpString => BmpString::try_from(value)
+            .map(|s| s.string())
+            .map_err(|_| IdentityError::Attribute),
+        Tag::UniversalString => UniversalString::try_from(value)
+            .map(|s| s.string())
+            .map_err(|_| IdentityError::Attribute),
+        _ => attribute
+            .as_str()
+            .map(str::to_string)
+            .map_err(|_| IdentityError::Attribute),
+    }
+}
+
+/// The single value of a subject attribute, if present
+fn single_value<'a>(
+    mut attributes: impl Iterator<Item = &'a AttributeTypeAndValue<'a>>,
+    error: IdentityError,
+) -> Result<Option<String>, IdentityError> {
+    let Some(first) = attributes.next() else {
+        return Ok(None);
+    };
+    if attributes.next().is_some() {
+        return Err(error);
+    }
+    attribute_value(first).map(Some)
+}
+
+impl ClientIdentity {
+    /// Read the identity from a certificate's subject. The organization
+    /// becomes part of NATS subjects, so it must be a valid tenant id.
+    pub fn from_certificate(der: &[u8]) -> Result<Self, IdentityError> {
+        let (_, certificate) =
+            X509Certificate::from_der(der).map_err(|e| IdentityError::Malformed(e.to_string()))?;
+        let subject = certificate.subject();
+
+        let agent_id = single_value(subject.iter_common_name(), IdentityError::CommonName)?
+            .filter(|cn| !cn.is_empty())
+            .ok_or(IdentityError::CommonName)?;
+        let tenant_id = single_value(subject.iter_organization(), IdentityError::Organization)?;
+        if let Some(ref tenant_id) = tenant_id {
+            validate_tenant_id(tenant_id)?;
+        }
+        Ok(Self {
+            agent_id,
+            tenant_id,
+        })
+    }
+
+    /// Credentials to ingest for this agent only
+    pub fn principal(&self) -> Principal {
+        Principal {
+            name: format!("cert:{}", self.agent_id),
+            scopes: vec![Scope::Ingest],
+            tenant_id: self.tenant_id.clone(),
+            agent_id: Some(self.agent_id.clone()),
+            sandbox: false,
+        }
+    }
+}
+
+// ============================================================================
+// Server
+// ============================================================================
+
+/// Serve `app` over TLS until shutdown begins, then wait for open
+/// connections to finish
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    tls: Arc<Tls>,
+    shutdown: Shutdown,
+) -> std::io::Result<()> {
+    // Every connection holds a sender, so the channel closes once all of
+    // them have finished
+    let (open, mut closed) = mpsc::channel::<()>(1);
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    error!("Failed to accept connection: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+            _ = shutdown.clone().started() => break,
+        };
+
+        let (acceptor, app, shutdown, open) =
+            (tls.acceptor(), app.clone(), shutdown.clone(), open.clone());
+        tokio::spawn(async move {
+            let _open = open;
+            let stream =
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => stream,
+                    Ok(Err(e)) => {
+                        debug!("TLS handshake with {} failed: {}", peer, e);
+                        counter!("facto_tls_handshake_failures_total").increment(1);
+                        return;
+                    }
+                    Err(_) => {
+                        counter!("facto_tls_handshake_failures_total").increment(1);
+                        return;
+                    }
+                };
+
+            let identity = match stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certificates| certificates.first())
+                .map(|certificate| ClientIdentity::from_certificate(certificate))
+            {
+                Some(Ok(identity)) => Some(identity),
+                Some(Err(e)) => {
+                    warn!("Refusing client certificate of {}: {}", peer, e);
+                    counter!("facto_tls_handshake_failures_total").increment(1);
+                    return;
+                }
+                None => None,
+            };
+            let service = hyper::service::service_fn(move |mut request: Request<Incoming>| {
+                if let Some(ref identity) = identity {
+                    request.extensions_mut().insert(identity.clone());
+                }
+                app.clone().call(request)
+            });
+
+            let connection = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .with_upgrades();
+            tokio::pin!(connection);
+            // Idle connections close at shutdown; busy ones after their response
+            let result = tokio::select! {
+                result = connection.as_mut() => result,
+                _ = shutdown.started() => {
+                    connection.as_mut().graceful_shutdown();
+                    connection.await
+                }
+            };
+            if let Err(e) = result {
+                debug!("Connection from {} closed: {}", peer, e);
+            }
+        });
+    }
+
+    drop((listener, open));
+    let _ = closed.recv().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{CertificateParams, DnType, DnValue, KeyPair};
+
+    fn certificate(subject: &[(DnType, DnValue)]) -> Vec<u8> {
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        for (kind, value) in subject {
+            params.distinguished_name.push(kind.clone(), value.clone());
+        }
+        let key = KeyPair::generate().unwrap();
+        params.self_signed(&key).unwrap().der().to_vec()
+    }
+
+    #[test]
+    fn test_identity_from_subject() {
+        let der = certificate(&[
+            (DnType::OrganizationName, DnValue::Utf8String("acme".into())),
+            (DnType::CommonName, DnValue::Utf8String("agent-1".into())),
+        ]);
+        let identity = ClientIdentity::from_certificate(&der).unwrap();
+        assert_eq!(identity.agent_id, "agent-1");
+        assert_eq!(identity.tenant_id.as_deref(), Some("acme"));
+        assert_eq!(identity.principal().agent_id.as_deref(), Some("agent-1"));
+
+        // Subjects in BMPString, as some older CAs issue them
+        let der = certificate(&[(
+            DnType::CommonName,
+            DnValue::BmpString("agent-2".try_into().unwrap()),
+        )]);
+        let identity = ClientIdentity::from_certificate(&der).unwrap();
+        assert_eq!(identity.agent_id, "agent-2");
+        assert_eq!(identity.tenant_id, None);
+
+        assert!(ClientIdentity::from_certificate(&der[..40]).is_err());
+    }
+
+    #[test]
+    fn test_rejects_organization_that_is_not_a_tenant() {
+        for organization in ["acme.events.>", "acme.*", "other tenant"] {
+            let der = certificate(&[
+                (
+                    DnType::OrganizationName,
+                    DnValue::Utf8String(organization.into()),
+                ),
+                (DnType::CommonName, DnValue::Utf8String("agent-1".into())),
+            ]);
+            assert!(matches!(
+                ClientIdentity::from_certificate(&der),
+                Err(IdentityError::Tenant(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use axum::async_trait;
+use futures::{future::BoxFuture, FutureExt};
+use rdkafka::error::KafkaError;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info_span, Instrument};
+
+use crate::verification::ServerEnvelope;
+use crate::{headers, sandbox, telemetry, tenants, FactoEvent};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SinkError {
+    #[error("{0} is not connected")]
+    NotConnected(&'static str),
+    #[error("NATS publish failed: {0}")]
+    Nats(String),
+    #[error(transparent)]
+    Kafka(#[from] KafkaError),
+}
+
+/// Resolves once the broker has persisted a sent event, with its stream
+/// sequence when the broker reports one
+pub type PendingAck = BoxFuture<'static, Result<Option<u64>, SinkError>>;
+
+/// The transport accepted events are published to.
+///
+/// Deliveries are spooled while the sink is not connected, and the service
+/// is ready as long as it is connected or a spool is configured.
+#[async_trait]
+pub trait Sink: Send + Sync {
+    /// Name used in logs and readiness
+    fn name(&self) -> &'static str;
+
+    async fn is_connected(&self) -> bool;
+
+    /// Hand an event to the broker. Events sent one after another are
+    /// persisted in that order, so acknowledgements can be awaited
+    /// concurrently.
+    async fn send(
+        &self,
+        event: &FactoEvent,
+        envelope: &ServerEnvelope,
+    ) -> Result<PendingAck, SinkError>;
+
+    /// Send an event and wait until the broker has persisted it
+    async fn publish(
+        &self,
+        event: &FactoEvent,
+        envelope: &ServerEnvelope,
+    ) -> Result<Option<u64>, SinkError> {
+        self.send(event, envelope).await?.await
+    }
+
+    /// Wait until published events have been handed to the broker
+    async fn flush(&self) -> Result<(), SinkError>;
+}
+
+/// Which [`Sink`] events are published to, from `TRANSPORT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Nats,
+    Kafka,
+}
+
+impl Transport {
+    pub fn from_env() -> anyhow::Result<Self> {
+        match std::env::var("TRANSPORT").as_deref() {
+            Err(_) | Ok("nats") => Ok(Self::Nats),
+            Ok("kafka") => Ok(Self::Kafka),
+            Ok(other) => anyhow::bail!("TRANSPORT must be nats or kafka, not {}", other),
+        }
+    }
+}
+
+// ============================================================================
+// NATS
+// ============================================================================
+
+/// The partition of `partitions` a session's events are published to,
+/// assigned like Kafka's default partitioner assigns keys, so consumers in
+/// any language can compute it
+pub fn session_partition(session_id: &str, partitions: usize) -> usize {
+    (murmur2(session_id.as_bytes()) & 0x7fff_ffff) as usize % partitions.max(1)
+}
+
+/// MurmurHash2 as implemented by the Kafka clients
+fn murmur2(data: &[u8]) -> i32 {
+    const M: u32 = 0x5bd1_e995;
+    let mut h: u32 = 0x9747_b28c ^ data.len() as u32;
+
+    let chunks = data.chunks_exact(4);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> 24;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M);
+        h ^= k;
+    }
+    if tail.len() == 3 {
+        h ^= (tail[2] as u32) << 16;
+    }
+    if tail.len() >= 2 {
+        h ^= (tail[1] as u32) << 8;
+    }
+    if !tail.is_empty() {
+        h ^= tail[0] as u32;
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^= h >> 15;
+    h as i32
+}
+
+/// NATS subject of an event of `session_id` published on `subject`: with
+/// session partitions, one more token numbering the session's partition
+pub fn partitioned_subject(subject: String, session_id: &str, partitions: usize) -> String {
+    match partitions {
+        0 => subject,
+        partitions => format!("{}.{}", subject, session_partition(session_id, partitions)),
+    }
+}
+
+/// Publishes events to their agent's subject through JetStream on the shared
+/// NATS connection. An event counts as published once the stream has
+/// acknowledged it; the facto_id doubles as the message id, so the stream
+/// drops redeliveries within its duplicate window.
+///
+/// With `NATS_SESSION_PARTITIONS`, each agent's subject is split into that
+/// many subjects, `facto.events.{agent}.{partition}`, and every event of a
+/// session is published on the same one. Consumers filtering on one
+/// partition each, such as `facto.events.*.3`, scale out while every
+/// session's events still reach a single consumer in order.
+pub struct NatsSink {
+    client: Arc<RwLock<Option<async_nats::Client>>>,
+    session_partitions: usize,
+}
+
+impl NatsSink {
+    /// Publish on `session_partitions` subjects per agent, or on the
+    /// agent's subject itself with 0
+    pub fn new(client: Arc<RwLock<Option<async_nats::Client>>>, session_partitions: usize) -> Self {
+        Self {
+            client,
+            session_partitions,
+        }
+    }
+
+    async fn connected_client(&self) -> Option<async_nats::Client> {
+        let client = self.client.read().await;
+        client
+            .as_ref()
+            .filter(|c| c.connection_state() == async_nats::connection::State::Connected)
+            .cloned()
+    }
+}
+
+#[async_trait]
+impl Sink for NatsSink {
+    fn name(&self) -> &'static str {
+        "nats"
+    }
+
+    async fn is_connected(&self) -> bool {
+        self.connected_client().await.is_some()
+    }
+
+    async fn send(
+        &self,
+        event: &FactoEvent,
+        envelope: &ServerEnvelope,
+    ) -> Result<PendingAck, SinkError> {
+        let client = self
+            .connected_client()
+            .await
+            .ok_or(SinkError::NotConnected("NATS"))?;
+        let subject = match envelope.sandbox {
+            true => sandbox::sandbox_subject(envelope.tenant_id.as_deref(), &event.agent_id),
+            false => tenants::event_subject(envelope.tenant_id.as_deref(), &event.agent_id),
+        };
+        let subject = partitioned_subject(subject, &event.session_id, self.session_partitions);
+        // Spooled events are published long after their request, so the
+        // span continues the trace recorded in the envelope
+        let span = info_span!(
+            "nats.publish",
+            otel.kind = "producer",
+            messaging.system = "nats",
+            messaging.destination.name = %subject,
+            facto_id = %event.facto_id,
+            otel.status_code = tracing::field::Empty,
+            otel.status_message = tracing::field::Empty,
+        );
+        telemetry::continue_trace(&span, |name| envelope.headers.get(name).map(String::as_str));
+        let payload = event.to_json();
+        let mut headers = headers::nats_headers(event, envelope).unwrap();
+        for (name, value) in telemetry::context_headers(&span) {
+            headers.insert(name.as_str(), value.as_str());
+        }
+
+        let published = async_nats::jetstream::new(client)
+            .publish_with_headers(subject, headers, payload)
+            .instrument(span.clone())
+            .await;
+        let ack = match published {
+            Ok(ack) => ack,
+            Err(e) => {
+                span.record("otel.status_code", "error");
+                span.record("otel.status_message", e.to_string().as_str());
+                return Err(SinkError::Nats(e.to_string()));
+            }
+        };
+        // The span ends once the broker has acknowledged the event
+        Ok(async move {
+            ack.await.map(|ack| Some(ack.sequence)).map_err(|e| {
+                span.record("otel.status_code", "error");
+                span.record("otel.status_message", e.to_string().as_str());
+                SinkError::Nats(e.to_string())
+            })
+        }
+        .boxed())
+    }
+
+    async fn flush(&self) -> Result<(), SinkError> {
+        if let Some(client) = self.connected_client().await {
+            client
+                .flush()
+                .await
+                .map_err(|e| SinkError::Nats(e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partitioning_matches_java_client() {
+        // Vectors from the Kafka clients' own tests
+        assert_eq!(murmur2(b"21"), -973932308);
+        assert_eq!(murmur2(b"foobar"), -790332482);
+        assert_eq!(murmur2(b"a-little-bit-long-string"), -985981536);
+        assert_eq!(murmur2(b"abc"), 479470107);
+
+        assert_eq!(
+            session_partition("21", 12),
+            (-973932308i32 & 0x7fff_ffff) as usize % 12
+        );
+    }
+
+    #[test]
+    fn test_sessions_keep_their_partition() {
+        assert_eq!(
+            partitioned_subject("facto.events.agent-1".to_string(), "s-1", 0),
+            "facto.events.agent-1"
+        );
+        let subject = partitioned_subject("facto.events.agent-1".to_string(), "s-1", 8);
+        assert_eq!(
+            subject,
+            format!("facto.events.agent-1.{}", session_partition("s-1", 8))
+        );
+        assert_eq!(
+            subject,
+            partitioned_subject("facto.events.agent-1".to_string(), "s-1", 8)
+        );
+        let partitions: std::collections::HashSet<_> = (0..100)
+            .map(|i| session_partition(&format!("s-{}", i), 8))
+            .collect();
+        assert_eq!(partitions.len(), 8);
+    }
+}
@@ -0,0 +1,283 @@
+//! Usage accounting for billing.
+//!
+//! The outcome of every ingested event is counted per tenant, agent and UTC
+//! day: events accepted, bytes accepted, and events rejected by reason.
+//! Duplicates of accepted events and sandbox traffic are not counted. The
+//! counts are kept in memory and saved to `USAGE_PATH` every
+//! `USAGE_FLUSH_SECS` and on shutdown; days older than
+//! `USAGE_RETENTION_DAYS` are dropped when saving.
+//!
+//! `GET /v1/usage` reports the counts as JSON, or with `format=csv` as one
+//! row per count for the billing pipeline.
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use dashmap::DashMap;
+use metrics::gauge;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use crate::admin::error_response;
+use crate::auth::AdminPrincipal;
+use crate::dimensions::Outcome;
+use crate::store::JsonFile;
+use crate::verification::now_nanos;
+use crate::AppState;
+
+const DAY_FORMAT: &str = "%Y-%m-%d";
+
+/// Usage of one agent of a tenant on one UTC day
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub day: String,
+    pub tenant_id: String,
+    pub agent_id: String,
+    /// Events accepted, held or spooled ones included
+    pub events: u64,
+    /// Bytes of the accepted events, as published
+    pub bytes: u64,
+    /// Events rejected, by reason code
+    #[serde(default)]
+    pub rejected: BTreeMap<String, u64>,
+}
+
+/// The UTC day of a time in nanoseconds
+fn day_of(nanos: i64) -> String {
+    chrono::DateTime::from_timestamp_nanos(nanos)
+        .format(DAY_FORMAT)
+        .to_string()
+}
+
+pub struct UsageLedger {
+    /// By day, tenant and agent
+    records: DashMap<(String, String, String), UsageRecord>,
+    store: JsonFile,
+    retention_days: u32,
+    dirty: AtomicBool,
+}
+
+impl UsageLedger {
+    pub fn new(path: Option<PathBuf>, retention_days: u32) -> anyhow::Result<Self> {
+        let store = JsonFile::new(path);
+        let records: Vec<UsageRecord> = store.load()?;
+        info!("Loaded {} usage records", records.len());
+        Ok(Self {
+            records: records
+                .into_iter()
+                .map(|record| {
+                    let key = (
+                        record.day.clone(),
+                        record.tenant_id.clone(),
+                        record.agent_id.clone(),
+                    );
+                    (key, record)
+                })
+                .collect(),
+            store,
+            retention_days,
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    /// Count an event's outcome under its tenant and agent on the day of `now`
+    pub fn record(&self, tenant: &str, agent_id: &str, outcome: Outcome, bytes: u64, now: i64) {
+        if outcome == Outcome::Duplicate {
+            return;
+        }
+        let day = day_of(now);
+        let mut record = self
+            .records
+            .entry((day.clone(), tenant.to_string(), agent_id.to_string()))
+            .or_insert_with(|| UsageRecord {
+                day,
+                tenant_id: tenant.to_string(),
+                agent_id: agent_id.to_string(),
+                ..Default::default()
+            });
+        match outcome {
+            Outcome::Accepted => {
+                record.events += 1;
+                record.bytes += bytes;
+            }
+            Outcome::Rejected(reason) => {
+                *record.rejected.entry(reason.to_string()).or_default() += 1
+            }
+            Outcome::Duplicate => {}
+        }
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Usage from day `from` to day `to`, both included, of one tenant or
+    /// all, by day, tenant and agent
+    pub fn report(
+        &self,
+        tenant: Option<&str>,
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> Vec<UsageRecord> {
+        let mut records: Vec<UsageRecord> = self
+            .records
+            .iter()
+            .filter(|entry| {
+                let (day, tenant_id, _) = entry.key();
+                tenant.is_none_or(|t| t == tenant_id)
+                    && from.is_none_or(|from| day.as_str() >= from)
+                    && to.is_none_or(|to| day.as_str() <= to)
+            })
+            .map(|entry| entry.value().clone())
+            .collect();
+        records.sort_by(|a, b| {
+            (&a.day, &a.tenant_id, &a.agent_id).cmp(&(&b.day, &b.tenant_id, &b.agent_id))
+        });
+        records
+    }
+
+    /// Drop the days past retention and save what changed
+    pub fn flush(&self, now: i64) -> std::io::Result<()> {
+        let oldest = day_of(now - self.retention_days as i64 * 86_400 * 1_000_000_000);
+        let before = self.records.len();
+        self.records.retain(|(day, _, _), _| *day >= oldest);
+        if self.records.len() != before {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        gauge!("facto_usage_records").set(self.records.len() as f64);
+        match self.dirty.swap(false, Ordering::Relaxed) {
+            true => self
+                .store
+                .save(&self.report(None, None, None))
+                .inspect_err(|_| {
+                    self.dirty.store(true, Ordering::Relaxed);
+                }),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Save usage every `interval`
+pub async fn run_usage_flusher(state: Arc<AppState>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        if let Err(e) = state.usage.flush(now_nanos()) {
+            warn!("Failed to save usage: {}", e);
+        }
+    }
+}
+
+/// One row per count: events, bytes, and rejections of each reason
+fn write_csv(records: &[UsageRecord]) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["day", "tenant_id", "agent_id", "metric", "reason", "value"])?;
+    for record in records {
+        let keys = [&record.day, &record.tenant_id, &record.agent_id];
+        let counts = [("events", "", record.events), ("bytes", "", record.bytes)]
+            .into_iter()
+            .chain(
+                record
+                    .rejected
+                    .iter()
+                    .map(|(reason, count)| ("rejected", reason.as_str(), *count)),
+            );
+        for (metric, reason, value) in counts {
+            writer.write_record(keys.iter().map(|key| key.as_str()).chain([
+                metric,
+                reason,
+                &value.to_string(),
+            ]))?;
+        }
+    }
+    writer.into_inner().map_err(|e| e.into_error().into())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    pub tenant: Option<String>,
+    /// First day reported, `YYYY-MM-DD`
+    pub from: Option<String>,
+    /// Last day reported, `YYYY-MM-DD`
+    pub to: Option<String>,
+    /// `json` or `csv`
+    pub format: Option<String>,
+}
+
+/// `GET /v1/usage`
+pub async fn usage_handler(
+    State(state): State<Arc<AppState>>,
+    AdminPrincipal(_): AdminPrincipal,
+    Query(query): Query<UsageQuery>,
+) -> Response {
+    for day in [&query.from, &query.to].into_iter().flatten() {
+        if chrono::NaiveDate::parse_from_str(day, DAY_FORMAT).is_err() {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid day {}, expected YYYY-MM-DD", day),
+            );
+        }
+    }
+    let records = state.usage.report(
+        query.tenant.as_deref(),
+        query.from.as_deref(),
+        query.to.as_deref(),
+    );
+    match query.format.as_deref() {
+        None | Some("json") => (StatusCode::OK, Json(records)).into_response(),
+        Some("csv") => match write_csv(&records) {
+            Ok(body) => {
+                (StatusCode::OK, [(header::CONTENT_TYPE, "text/csv")], body).into_response()
+            }
+            Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+        },
+        Some(other) => error_response(
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported format {}, expected json or csv", other),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_is_counted_per_tenant_agent_and_day() {
+        let day = 86_400 * 1_000_000_000;
+        let ledger = UsageLedger::new(None, 30).unwrap();
+        ledger.record("t1", "a", Outcome::Accepted, 100, 0);
+        ledger.record("t1", "a", Outcome::Accepted, 50, 1);
+        ledger.record("t1", "a", Outcome::Duplicate, 50, 2);
+        ledger.record("t1", "a", Outcome::Rejected("RATE_LIMITED"), 50, 3);
+        ledger.record("t1", "a", Outcome::Accepted, 10, day);
+        ledger.record("t2", "b", Outcome::Accepted, 10, 0);
+
+        let report = ledger.report(Some("t1"), Some("1970-01-01"), Some("1970-01-01"));
+        assert_eq!(report.len(), 1);
+        assert_eq!((report[0].events, report[0].bytes), (2, 150));
+        assert_eq!(report[0].rejected["RATE_LIMITED"], 1);
+        assert_eq!(ledger.report(None, Some("1970-01-02"), None).len(), 1);
+
+        let csv = String::from_utf8(write_csv(&report).unwrap()).unwrap();
+        assert_eq!(
+            csv,
+            "day,tenant_id,agent_id,metric,reason,value\n\
+             1970-01-01,t1,a,events,,2\n\
+             1970-01-01,t1,a,bytes,,150\n\
+             1970-01-01,t1,a,rejected,RATE_LIMITED,1\n"
+        );
+
+        // Days past retention are dropped
+        ledger.flush(31 * day).unwrap();
+        assert_eq!(ledger.report(None, None, None).len(), 1);
+    }
+}
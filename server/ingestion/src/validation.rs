@@ -0,0 +1,96 @@
+//! Concurrent validation of a batch's events.
+//!
+//! Schema and policy checks walk an event's payloads, so one huge or deeply
+//! nested event could hold up every event behind it. The events of a batch
+//! are validated side by side on the blocking pool, at most `concurrency`
+//! at a time across all requests, and an event whose validation outlasts
+//! the timeout is refused on its own while the rest of the batch proceeds.
+//!
+//! A timed out check cannot be interrupted; it runs to completion in the
+//! background, its result dropped, and no longer holds a permit.
+
+use metrics::counter;
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+/// An event's validation outlasted the timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationTimeout;
+
+pub struct Validation {
+    permits: Arc<Semaphore>,
+    timeout: Duration,
+}
+
+impl Validation {
+    pub fn new(concurrency: usize, timeout: Duration) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(concurrency.max(1))),
+            timeout,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Run `check` on every item, returning the outcomes in item order. The
+    /// timeout of each check starts once it holds a permit.
+    pub async fn run<I, T, F>(&self, items: Vec<I>, check: F) -> Vec<Result<T, ValidationTimeout>>
+    where
+        I: Send + 'static,
+        T: Send + 'static,
+        F: Fn(I) -> T + Send + Sync + 'static,
+    {
+        let check = Arc::new(check);
+        let mut tasks = JoinSet::new();
+        for (index, item) in items.into_iter().enumerate() {
+            let permits = self.permits.clone();
+            let check = check.clone();
+            let timeout = self.timeout;
+            tasks.spawn(async move {
+                let _permit = permits
+                    .acquire_owned()
+                    .await
+                    .expect("validation semaphore closed");
+                let task = tokio::task::spawn_blocking(move || check(item));
+                let outcome = match tokio::time::timeout(timeout, task).await {
+                    Ok(outcome) => Ok(outcome.expect("validation task panicked")),
+                    Err(_) => Err(ValidationTimeout),
+                };
+                (index, outcome)
+            });
+        }
+
+        let mut outcomes: Vec<Option<Result<T, ValidationTimeout>>> =
+            std::iter::repeat_with(|| None).take(tasks.len()).collect();
+        while let Some(joined) = tasks.join_next().await {
+            let (index, outcome) = joined.expect("validation task panicked");
+            if outcome.is_err() {
+                counter!("facto_validation_timeouts_total").increment(1);
+            }
+            outcomes[index] = Some(outcome);
+        }
+        outcomes
+            .into_iter()
+            .map(|outcome| outcome.expect("every item has a validation outcome"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_slow_checks_time_out_alone() {
+        let validation = Validation::new(2, Duration::from_millis(100));
+        let outcomes = validation
+            .run(vec![1u64, 500, 2, 3], |millis| {
+                std::thread::sleep(Duration::from_millis(millis));
+                millis * 10
+            })
+            .await;
+        assert_eq!(outcomes, [Ok(10), Err(ValidationTimeout), Ok(20), Ok(30)]);
+    }
+}
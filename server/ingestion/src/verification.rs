@@ -0,0 +1,983 @@
+use crate::did::{self, DataIntegrityProof};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use dashmap::DashMap;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::backfill::BackfillRecord;
+use crate::crypto::{self, ProofSignature, PublicKey, SignatureAlgorithm, VerificationError};
+use crate::legacy::TranslationRecord;
+use crate::models::ModelAttestation;
+use crate::policy::PolicyFinding;
+use crate::protocol::BatchEnvelope;
+use crate::redaction::RedactionRecord;
+use crate::registry::{KeyRegistry, RegistryRef};
+use crate::resolver::DidResolver;
+use crate::FactoEvent;
+
+/// NATS header carrying the JSON-encoded [`ServerEnvelope`] of a published event
+pub const ENVELOPE_HEADER: &str = "Facto-Envelope";
+
+/// Current time as nanoseconds since the Unix epoch (same unit as event timestamps)
+pub fn now_nanos() -> i64 {
+    chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+}
+
+// ============================================================================
+// Server Signing Key
+// ============================================================================
+
+/// The ingestion server's own Ed25519 identity, used to sign assertions
+pub struct ServerSigner {
+    instance_id: String,
+    signing_key: SigningKey,
+}
+
+impl ServerSigner {
+    pub fn new(instance_id: String, signing_key: SigningKey) -> Self {
+        Self {
+            instance_id,
+            signing_key,
+        }
+    }
+
+    /// Load the signing key from a base64-encoded 32-byte seed, or generate an
+    /// ephemeral one if no seed is configured
+    pub fn from_seed(instance_id: String, seed: Option<&str>) -> anyhow::Result<Self> {
+        let signing_key = match seed {
+            Some(seed) => {
+                let bytes: [u8; 32] = BASE64
+                    .decode(seed)?
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("server signing key seed must be 32 bytes"))?;
+                SigningKey::from_bytes(&bytes)
+            }
+            None => {
+                warn!("No server signing key configured, generating an ephemeral key");
+                SigningKey::generate(&mut rand::rngs::OsRng)
+            }
+        };
+
+        Ok(Self::new(instance_id, signing_key))
+    }
+
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    pub fn public_key_base64(&self) -> String {
+        BASE64.encode(self.signing_key.verifying_key().as_bytes())
+    }
+
+    pub fn sign_base64(&self, message: &[u8]) -> String {
+        BASE64.encode(self.signing_key.sign(message).to_bytes())
+    }
+}
+
+// ============================================================================
+// Verification Assertions
+// ============================================================================
+
+/// What the server relied on when it accepted the signer's public key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TrustBasis {
+    /// The public key embedded in the event was taken at face value
+    EmbeddedKey,
+    /// The public key was registered for the agent in this registry state
+    Registry(RegistryRef),
+    /// The agent is unregistered, and the public key is the one the DID
+    /// URL in the proof resolved to
+    Did { verification_method: String },
+    /// The event's hash was checked and its signature skipped: it was
+    /// covered by a batch envelope signed with the same key, accepted for an
+    /// envelope-trusted agent. The envelope and the event hashes it covers
+    /// are carried so consumers can check it without the rest of the batch.
+    BatchEnvelope {
+        envelope: BatchEnvelope,
+        /// `proof.event_hash` of every event in the batch, in batch order
+        event_hashes: Vec<String>,
+        /// Registry state the key was registered in, when keys are registered
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        registry: Option<RegistryRef>,
+    },
+    /// The hash and signature were not verified: the event was let through
+    /// under the `audit` or `off` enforcement mode
+    Unverified,
+}
+
+/// How strictly an agent's event proofs are enforced, set per tenant or
+/// agent to roll out SDK canonicalization changes gradually
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnforcementMode {
+    /// Reject events that fail verification
+    #[default]
+    Enforce,
+    /// Accept events whose hash or signature fails, flagged in the envelope
+    Audit,
+    /// Accept events without checking their hash or signature
+    Off,
+}
+
+impl EnforcementMode {
+    /// Metrics label for the mode
+    pub fn code(&self) -> &'static str {
+        match self {
+            EnforcementMode::Enforce => "enforce",
+            EnforcementMode::Audit => "audit",
+            EnforcementMode::Off => "off",
+        }
+    }
+
+    /// Whether a failed check is let through in this mode. Only hash and
+    /// signature failures are, the ones a canonicalization change causes;
+    /// malformed events and untrusted keys are rejected in every mode.
+    pub fn admits(&self, error: &VerificationError) -> bool {
+        *self != EnforcementMode::Enforce
+            && matches!(
+                error,
+                VerificationError::HashMismatch { .. }
+                    | VerificationError::InvalidSignature(_)
+                    | VerificationError::SignatureMismatch(_)
+            )
+    }
+}
+
+impl std::str::FromStr for EnforcementMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "enforce" => Ok(EnforcementMode::Enforce),
+            "audit" => Ok(EnforcementMode::Audit),
+            "off" => Ok(EnforcementMode::Off),
+            _ => Err(format!("unknown enforcement mode: {}", s)),
+        }
+    }
+}
+
+/// Whether a published event's proof was verified
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum VerificationStatus {
+    #[default]
+    Verified,
+    /// Failed verification and was accepted in audit mode
+    Failed { code: String, message: String },
+    /// Accepted unchecked in `off` mode
+    Skipped,
+}
+
+impl VerificationStatus {
+    pub fn is_verified(&self) -> bool {
+        *self == VerificationStatus::Verified
+    }
+
+    fn failed(error: &VerificationError) -> Self {
+        VerificationStatus::Failed {
+            code: error.code().as_str().to_string(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// A server-signed statement that an event's hash and signature were verified
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerificationAssertion {
+    pub facto_id: String,
+    pub event_hash: String,
+    pub algorithm: String,
+    pub signer_public_key: String,
+    pub trust_basis: TrustBasis,
+    pub verifier_id: String,
+    pub verifier_public_key: String,
+    pub verified_at: i64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub signature: String,
+}
+
+impl VerificationAssertion {
+    /// The bytes covered by the server signature: the sorted-key JSON of the
+    /// assertion with `signature` omitted
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let unsigned = Self {
+            signature: String::new(),
+            ..self.clone()
+        };
+        let value = serde_json::to_value(&unsigned).unwrap_or_default();
+        serde_json::to_vec(&value).unwrap_or_default()
+    }
+}
+
+/// Server-side metadata published alongside each accepted event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerEnvelope {
+    pub received_at: i64,
+    pub verification: VerificationAssertion,
+    /// Tenant the event was accepted for, resolved from the caller's credentials
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    /// Accepted in sandbox mode; published outside the production stream
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sandbox: bool,
+    /// Set when the event's proof was not verified, under the `audit` or
+    /// `off` enforcement mode
+    #[serde(default, skip_serializing_if = "VerificationStatus::is_verified")]
+    pub verification_status: VerificationStatus,
+    /// Payload schema violations of an event accepted in audit mode
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schema_violations: Vec<String>,
+    /// Policy findings that annotate or alert on the event
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub policy_findings: Vec<PolicyFinding>,
+    /// Failed model attestation of an event accepted in audit mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_attestation: Option<ModelAttestation>,
+    /// Headers of the published message: propagated request headers and
+    /// the tenant's configured headers
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Values redacted from the payloads before publication
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redaction: Option<RedactionRecord>,
+    /// The event's proof as a Data Integrity proof, when it was verified
+    /// under a verification method
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_integrity: Option<DataIntegrityProof>,
+    /// How the event was held when it was ingested with `backfill=true`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backfill: Option<BackfillRecord>,
+    /// Set when the event was sent in an older shape and translated, by
+    /// `/v1/ingest/legacy`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation: Option<TranslationRecord>,
+}
+
+// ============================================================================
+// Verification Cache
+// ============================================================================
+
+/// Remembers recent successful verifications so retried events skip the
+/// signature check and receive the same assertion
+pub struct VerificationCache {
+    entries: DashMap<String, (Instant, VerificationAssertion)>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl VerificationCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: DashMap::new(),
+            capacity,
+            ttl,
+        }
+    }
+
+    /// Cache key binding the verified content (via its recomputed hash) to the
+    /// exact key and signature that were checked, and the tenant and registry
+    /// version the trust decision was made against
+    fn key(
+        tenant_id: Option<&str>,
+        event_hash: &str,
+        event: &FactoEvent,
+        registry_version: u64,
+    ) -> String {
+        format!(
+            "{}:{}:{}:{}:{}:{}:{}",
+            tenant_id.unwrap_or_default(),
+            registry_version,
+            event_hash,
+            event.proof.algorithm.as_deref().unwrap_or_default(),
+            event.proof.public_key,
+            event.proof.signature,
+            event
+                .proof
+                .verification_method
+                .as_deref()
+                .unwrap_or_default()
+        )
+    }
+
+    fn get(&self, key: &str) -> Option<VerificationAssertion> {
+        let entry = self.entries.get(key)?;
+        let (inserted, assertion) = entry.value();
+        if inserted.elapsed() > self.ttl {
+            drop(entry);
+            self.entries.remove(key);
+            return None;
+        }
+        Some(assertion.clone())
+    }
+
+    fn insert(&self, key: String, assertion: VerificationAssertion) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            self.entries
+                .retain(|_, (inserted, _)| inserted.elapsed() <= ttl);
+            if self.entries.len() >= self.capacity {
+                return;
+            }
+        }
+        self.entries.insert(key, (Instant::now(), assertion));
+    }
+}
+
+// ============================================================================
+// Verifier
+// ============================================================================
+
+/// A signature check still to be performed for one event of a request
+struct PendingCheck {
+    index: usize,
+    facto_id: String,
+    event_hash: String,
+    public_key_b64: String,
+    cache_key: String,
+    message: Vec<u8>,
+    algorithm: SignatureAlgorithm,
+    public_key: PublicKey,
+    signature: ProofSignature,
+    trust_basis: TrustBasis,
+}
+
+/// Outcome of the inline checks for one event
+enum Prepared {
+    /// A previous verification of the same content, key and signature applies
+    Cached(Box<VerificationAssertion>),
+    /// The signature still has to be checked
+    Pending(Box<PendingCheck>),
+}
+
+/// Verifies event proofs and issues signed assertions for the ones that pass.
+///
+/// Hash checks run inline; signature checks run on the blocking thread pool in
+/// chunks of events sharing a public key, so each chunk can use Ed25519 batch
+/// verification. The number of chunks in flight is bounded across requests.
+pub struct Verifier {
+    signer: Arc<ServerSigner>,
+    cache: VerificationCache,
+    registry: Arc<KeyRegistry>,
+    dids: Arc<DidResolver>,
+    permits: Arc<Semaphore>,
+    chunk_size: usize,
+    /// Events in canonical form version 1 are refused from this time on,
+    /// in nanoseconds
+    canonical_v1_until: Option<i64>,
+}
+
+impl Verifier {
+    pub fn new(
+        signer: ServerSigner,
+        cache: VerificationCache,
+        registry: Arc<KeyRegistry>,
+        dids: Arc<DidResolver>,
+        concurrency: usize,
+        chunk_size: usize,
+        canonical_v1_until: Option<i64>,
+    ) -> Self {
+        Self {
+            signer: Arc::new(signer),
+            cache,
+            registry,
+            dids,
+            permits: Arc::new(Semaphore::new(concurrency.max(1))),
+            chunk_size: chunk_size.max(1),
+            canonical_v1_until,
+        }
+    }
+
+    /// A verifier trusting `registry`, sharing this one's signer and
+    /// concurrency limit but not its cache
+    pub fn with_registry(&self, registry: Arc<KeyRegistry>) -> Self {
+        Self {
+            signer: self.signer.clone(),
+            cache: VerificationCache::new(self.cache.capacity, self.cache.ttl),
+            registry,
+            dids: self.dids.clone(),
+            permits: self.permits.clone(),
+            chunk_size: self.chunk_size,
+            canonical_v1_until: self.canonical_v1_until,
+        }
+    }
+
+    pub fn signer(&self) -> &ServerSigner {
+        &self.signer
+    }
+
+    /// Verify the hash and signature of every event of a tenant under its
+    /// enforcement mode, returning a signed assertion or the failure reason
+    /// for each, in input order. Hashes are always recomputed; only signature checks are
+    /// cached. Events in `off` mode go unchecked, and in `audit` mode hash
+    /// and signature failures are accepted; their assertions name no trust
+    /// basis, and the status says why.
+    pub async fn verify_under(
+        &self,
+        tenant_id: Option<&str>,
+        events: &[FactoEvent],
+        modes: &[EnforcementMode],
+    ) -> Vec<Result<(VerificationAssertion, VerificationStatus), VerificationError>> {
+        self.verify_enveloped(tenant_id, events, modes, None).await
+    }
+
+    /// Like `verify_under`, but the events `enveloped` marks skip their
+    /// signature check: `envelope` was verified against their keys and
+    /// covers `event_hashes`. Their hashes are still recomputed.
+    pub async fn verify_enveloped(
+        &self,
+        tenant_id: Option<&str>,
+        events: &[FactoEvent],
+        modes: &[EnforcementMode],
+        envelope: Option<(&BatchEnvelope, &[String], &[bool])>,
+    ) -> Vec<Result<(VerificationAssertion, VerificationStatus), VerificationError>> {
+        let mut results: Vec<Option<Result<VerificationAssertion, VerificationError>>> =
+            (0..events.len()).map(|_| None).collect();
+        let registry_version = self.registry.current().version;
+
+        // Keys are bound to the DIDs proofs name in every enforcement mode
+        let bindings =
+            futures::future::join_all(events.iter().map(|event| self.bind_did(event))).await;
+
+        // Group outstanding signature checks by public key
+        let mut pending: HashMap<String, Vec<PendingCheck>> = HashMap::new();
+        for ((index, event), binding) in events.iter().enumerate().zip(bindings) {
+            if let Err(e) = binding {
+                results[index] = Some(Err(e));
+                continue;
+            }
+            if modes[index] == EnforcementMode::Off {
+                results[index] = Some(self.issue_unverified(tenant_id, event));
+                continue;
+            }
+            if let Some((envelope, event_hashes, _)) =
+                envelope.filter(|(_, _, enveloped)| enveloped[index])
+            {
+                results[index] =
+                    Some(self.issue_enveloped(tenant_id, event, envelope, event_hashes));
+                continue;
+            }
+            match self.prepare(tenant_id, index, event, registry_version) {
+                Ok(Prepared::Cached(assertion)) => results[index] = Some(Ok(*assertion)),
+                Ok(Prepared::Pending(check)) => pending
+                    .entry(check.public_key_b64.clone())
+                    .or_default()
+                    .push(*check),
+                Err(e) => results[index] = Some(Err(e)),
+            }
+        }
+
+        let mut tasks = Vec::new();
+        for group in pending.into_values() {
+            let mut group = group.into_iter().peekable();
+            while group.peek().is_some() {
+                let chunk: Vec<PendingCheck> = group.by_ref().take(self.chunk_size).collect();
+                let permit = self
+                    .permits
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("verification semaphore closed");
+                let signer = self.signer.clone();
+                tasks.push(tokio::task::spawn_blocking(move || {
+                    let _permit = permit;
+                    verify_chunk(&chunk)
+                        .into_iter()
+                        .zip(chunk)
+                        .map(|(outcome, check)| {
+                            let outcome = outcome.map(|()| issue(&signer, &check));
+                            (check.index, check.cache_key, outcome)
+                        })
+                        .collect::<Vec<_>>()
+                }));
+            }
+        }
+
+        for task in tasks {
+            let outcomes = task.await.expect("signature verification task panicked");
+            for (index, cache_key, outcome) in outcomes {
+                if let Ok(ref assertion) = outcome {
+                    self.cache.insert(cache_key, assertion.clone());
+                }
+                results[index] = Some(outcome);
+            }
+        }
+
+        results
+            .into_iter()
+            .zip(events.iter().zip(modes))
+            .map(|(result, (event, mode))| {
+                match (result.expect("every event has a verification result"), mode) {
+                    (Ok(assertion), EnforcementMode::Off) => {
+                        Ok((assertion, VerificationStatus::Skipped))
+                    }
+                    (Ok(assertion), _) => Ok((assertion, VerificationStatus::Verified)),
+                    (Err(e), mode) if mode.admits(&e) => self
+                        .issue_unverified(tenant_id, event)
+                        .map(|assertion| (assertion, VerificationStatus::failed(&e))),
+                    (Err(e), _) => Err(e),
+                }
+            })
+            .collect()
+    }
+
+    /// Check that the proof's public key is the one its verification
+    /// method, if any, resolves to
+    async fn bind_did(&self, event: &FactoEvent) -> Result<(), VerificationError> {
+        let Some(ref verification_method) = event.proof.verification_method else {
+            return Ok(());
+        };
+        let key = self.dids.resolve(verification_method).await?;
+        did::check_binding(event, &key)
+    }
+
+    /// The checks every event passes whatever its enforcement mode: the
+    /// fields are present and the canonical form can be built in an accepted
+    /// version. Returns the canonical form.
+    fn check_form(
+        &self,
+        event: &FactoEvent,
+    ) -> Result<(SignatureAlgorithm, String), VerificationError> {
+        crypto::check_required_fields(event)?;
+        let algorithm = SignatureAlgorithm::of(&event.proof)?;
+        let canonical_version = crypto::canonical_version(event);
+        if canonical_version == 1
+            && self
+                .canonical_v1_until
+                .is_some_and(|until| now_nanos() >= until)
+        {
+            return Err(VerificationError::RetiredCanonicalVersion(
+                canonical_version,
+            ));
+        }
+        Ok((algorithm, crypto::build_canonical_form(event)?))
+    }
+
+    /// Sign an assertion for an event accepted without verifying its hash or
+    /// signature. The event must still be well-formed, with a valid public
+    /// key the registry trusts when keys are registered.
+    fn issue_unverified(
+        &self,
+        tenant_id: Option<&str>,
+        event: &FactoEvent,
+    ) -> Result<VerificationAssertion, VerificationError> {
+        let (algorithm, canonical) = self.check_form(event)?;
+        PublicKey::decode(algorithm, &event.proof.public_key)?;
+        self.registry.authorize(
+            tenant_id,
+            &event.agent_id,
+            &event.proof.public_key,
+            now_nanos(),
+        )?;
+
+        let mut assertion = VerificationAssertion {
+            facto_id: event.facto_id.clone(),
+            event_hash: crypto::compute_event_hash(&canonical),
+            algorithm: algorithm.name().to_string(),
+            signer_public_key: event.proof.public_key.clone(),
+            trust_basis: TrustBasis::Unverified,
+            verifier_id: self.signer.instance_id().to_string(),
+            verifier_public_key: self.signer.public_key_base64(),
+            verified_at: now_nanos(),
+            signature: String::new(),
+        };
+        assertion.signature = self.signer.sign_base64(&assertion.signing_payload());
+        Ok(assertion)
+    }
+
+    /// Sign an assertion for an event whose hash is verified and whose
+    /// signature is vouched for by a verified batch envelope
+    fn issue_enveloped(
+        &self,
+        tenant_id: Option<&str>,
+        event: &FactoEvent,
+        envelope: &BatchEnvelope,
+        event_hashes: &[String],
+    ) -> Result<VerificationAssertion, VerificationError> {
+        let (algorithm, canonical) = self.check_form(event)?;
+        let event_hash = crypto::verify_hash(event, &canonical)?;
+        PublicKey::decode(algorithm, &event.proof.public_key)?;
+        let registry = self.registry.authorize(
+            tenant_id,
+            &event.agent_id,
+            &event.proof.public_key,
+            now_nanos(),
+        )?;
+        counter!("facto_verification_enveloped_total").increment(1);
+
+        let mut assertion = VerificationAssertion {
+            facto_id: event.facto_id.clone(),
+            event_hash,
+            algorithm: algorithm.name().to_string(),
+            signer_public_key: event.proof.public_key.clone(),
+            trust_basis: TrustBasis::BatchEnvelope {
+                envelope: envelope.clone(),
+                event_hashes: event_hashes.to_vec(),
+                registry,
+            },
+            verifier_id: self.signer.instance_id().to_string(),
+            verifier_public_key: self.signer.public_key_base64(),
+            verified_at: now_nanos(),
+            signature: String::new(),
+        };
+        assertion.signature = self.signer.sign_base64(&assertion.signing_payload());
+        Ok(assertion)
+    }
+
+    /// Run the inline checks for one event
+    fn prepare(
+        &self,
+        tenant_id: Option<&str>,
+        index: usize,
+        event: &FactoEvent,
+        registry_version: u64,
+    ) -> Result<Prepared, VerificationError> {
+        let (algorithm, canonical) = self.check_form(event)?;
+        let event_hash = crypto::verify_hash(event, &canonical)?;
+
+        // Checked before the cache so revocations apply immediately
+        let trust_basis = match self.registry.authorize(
+            tenant_id,
+            &event.agent_id,
+            &event.proof.public_key,
+            now_nanos(),
+        )? {
+            Some(registry) => TrustBasis::Registry(registry),
+            None => match event.proof.verification_method {
+                Some(ref verification_method) => TrustBasis::Did {
+                    verification_method: verification_method.clone(),
+                },
+                None => TrustBasis::EmbeddedKey,
+            },
+        };
+
+        let cache_key = VerificationCache::key(tenant_id, &event_hash, event, registry_version);
+        if let Some(assertion) = self.cache.get(&cache_key) {
+            counter!("facto_verification_cache_hits_total").increment(1);
+            return Ok(Prepared::Cached(Box::new(assertion)));
+        }
+        counter!("facto_verification_cache_misses_total").increment(1);
+
+        let public_key = PublicKey::decode(algorithm, &event.proof.public_key)?;
+        let signature = ProofSignature::decode(algorithm, &event.proof.signature)?;
+
+        Ok(Prepared::Pending(Box::new(PendingCheck {
+            index,
+            facto_id: event.facto_id.clone(),
+            event_hash,
+            public_key_b64: event.proof.public_key.clone(),
+            cache_key,
+            message: canonical.into_bytes(),
+            algorithm,
+            public_key,
+            signature,
+            trust_basis,
+        })))
+    }
+}
+
+/// The Ed25519 key and signature of a check that is safe to batch
+fn batchable(check: &PendingCheck) -> Option<(VerifyingKey, Signature)> {
+    match (&check.public_key, &check.signature) {
+        // Batch verification is cofactorless like `verify`, so screen out the
+        // inputs `verify_strict` would reject before trusting a batch success
+        (PublicKey::Ed25519(key), ProofSignature::Ed25519(signature))
+            if !key.is_weak() && !crypto::has_small_order_r(signature) =>
+        {
+            Some((*key, *signature))
+        }
+        _ => None,
+    }
+}
+
+/// Verify a chunk of signatures. Chunks of more than one Ed25519 event are
+/// first tried as a single batch; if the batch fails, or the chunk holds
+/// ECDSA signatures, each signature is checked individually.
+fn verify_chunk(chunk: &[PendingCheck]) -> Vec<Result<(), VerificationError>> {
+    let batch: Option<Vec<(VerifyingKey, Signature)>> = chunk.iter().map(batchable).collect();
+
+    if let Some(batch) = batch.filter(|b| b.len() > 1) {
+        let messages: Vec<&[u8]> = chunk.iter().map(|c| c.message.as_slice()).collect();
+        let (keys, signatures): (Vec<VerifyingKey>, Vec<Signature>) = batch.into_iter().unzip();
+
+        if ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_ok() {
+            counter!("facto_verification_batches_total", "outcome" => "ok").increment(1);
+            return chunk.iter().map(|_| Ok(())).collect();
+        }
+        counter!("facto_verification_batches_total", "outcome" => "fallback").increment(1);
+    }
+
+    chunk
+        .iter()
+        .map(|c| c.public_key.verify(&c.message, &c.signature))
+        .collect()
+}
+
+/// Build and sign the assertion for a successfully verified event
+fn issue(signer: &ServerSigner, check: &PendingCheck) -> VerificationAssertion {
+    let mut assertion = VerificationAssertion {
+        facto_id: check.facto_id.clone(),
+        event_hash: check.event_hash.clone(),
+        algorithm: check.algorithm.name().to_string(),
+        signer_public_key: check.public_key_b64.clone(),
+        trust_basis: check.trust_basis.clone(),
+        verifier_id: signer.instance_id().to_string(),
+        verifier_public_key: signer.public_key_base64(),
+        verified_at: now_nanos(),
+        signature: String::new(),
+    };
+    assertion.signature = signer.sign_base64(&assertion.signing_payload());
+    assertion
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{sign_test_event, test_event};
+    use ed25519_dalek::Verifier as _;
+
+    /// Check the server signature the way a downstream consumer would
+    fn check_assertion(assertion: &VerificationAssertion) -> Result<(), VerificationError> {
+        let key = crypto::decode_public_key(&assertion.verifier_public_key)?;
+        let signature = crypto::decode_signature(&assertion.signature)?;
+        key.verify(&assertion.signing_payload(), &signature)
+            .map_err(|e| VerificationError::SignatureMismatch(e.to_string()))
+    }
+
+    fn test_verifier() -> Verifier {
+        let signer = ServerSigner::new("test".to_string(), SigningKey::from_bytes(&[1u8; 32]));
+        Verifier::new(
+            signer,
+            VerificationCache::new(16, Duration::from_secs(60)),
+            Arc::new(KeyRegistry::new(None, false).unwrap()),
+            Arc::new(DidResolver::new(Vec::new(), Duration::from_secs(60))),
+            2,
+            4,
+            None,
+        )
+    }
+
+    async fn verify_one(
+        verifier: &Verifier,
+        event: &FactoEvent,
+    ) -> Result<VerificationAssertion, VerificationError> {
+        verifier
+            .verify_under(
+                None,
+                std::slice::from_ref(event),
+                &[EnforcementMode::Enforce],
+            )
+            .await
+            .remove(0)
+            .map(|(assertion, _)| assertion)
+    }
+
+    #[tokio::test]
+    async fn test_assertion_is_signed_and_cached() {
+        let verifier = test_verifier();
+        let event = sign_test_event(test_event(), &SigningKey::from_bytes(&[2u8; 32]));
+
+        let first = verify_one(&verifier, &event).await.unwrap();
+        assert!(check_assertion(&first).is_ok());
+        assert_eq!(first.event_hash, event.proof.event_hash);
+
+        let second = verify_one(&verifier, &event).await.unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn test_tampered_assertion_fails() {
+        let verifier = test_verifier();
+        let event = sign_test_event(test_event(), &SigningKey::from_bytes(&[2u8; 32]));
+
+        let mut assertion = verify_one(&verifier, &event).await.unwrap();
+        assertion.facto_id = "tr-other".to_string();
+        assert!(check_assertion(&assertion).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cache_does_not_skip_hash_check() {
+        let verifier = test_verifier();
+        let event = sign_test_event(test_event(), &SigningKey::from_bytes(&[2u8; 32]));
+        verify_one(&verifier, &event).await.unwrap();
+
+        let mut tampered = event.clone();
+        tampered.input_data = serde_json::json!({"prompt": "other"});
+        assert!(matches!(
+            verify_one(&verifier, &tampered).await,
+            Err(VerificationError::HashMismatch { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_batch_isolates_bad_signature() {
+        let verifier = test_verifier();
+        let key = SigningKey::from_bytes(&[2u8; 32]);
+        let other = SigningKey::from_bytes(&[5u8; 32]);
+
+        let mut events: Vec<FactoEvent> = (0..10)
+            .map(|i| {
+                let mut event = test_event();
+                event.facto_id = format!("tr-{}", i);
+                sign_test_event(event, &key)
+            })
+            .collect();
+        // Re-sign one event's content with a different key but keep the claimed key
+        let forged = sign_test_event(events[6].clone(), &other);
+        events[6].proof.signature = forged.proof.signature;
+
+        let results = verifier
+            .verify_under(None, &events, &[EnforcementMode::Enforce; 10])
+            .await;
+        assert_eq!(results.len(), 10);
+        for (i, result) in results.iter().enumerate() {
+            if i == 6 {
+                assert!(matches!(
+                    result,
+                    Err(VerificationError::SignatureMismatch(_))
+                ));
+            } else {
+                assert_eq!(result.as_ref().unwrap().0.facto_id, format!("tr-{}", i));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_audit_mode_flags_instead_of_rejecting() {
+        let verifier = test_verifier();
+        let event = sign_test_event(test_event(), &SigningKey::from_bytes(&[2u8; 32]));
+        let mut tampered = event.clone();
+        tampered.input_data = serde_json::json!({"prompt": "other"});
+        let mut unsigned = event.clone();
+        unsigned.proof.signature = String::new();
+
+        let events = [event, tampered, unsigned];
+        let modes = [
+            EnforcementMode::Audit,
+            EnforcementMode::Audit,
+            EnforcementMode::Off,
+        ];
+        let results = verifier.verify_under(None, &events, &modes).await;
+
+        let (verified, status) = results[0].as_ref().unwrap();
+        assert_eq!(*status, VerificationStatus::Verified);
+        assert!(matches!(verified.trust_basis, TrustBasis::EmbeddedKey));
+
+        let (flagged, status) = results[1].as_ref().unwrap();
+        assert!(
+            matches!(status, VerificationStatus::Failed { code, .. } if code == "HASH_MISMATCH")
+        );
+        assert_eq!(flagged.trust_basis, TrustBasis::Unverified);
+        assert_ne!(flagged.event_hash, events[1].proof.event_hash);
+        assert!(check_assertion(flagged).is_ok());
+
+        // Missing fields are rejected in every mode
+        assert_eq!(
+            results[2],
+            Err(VerificationError::MissingField("signature"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_envelope_replaces_signature_check() {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+        use ed25519_dalek::Signer as _;
+
+        let verifier = test_verifier();
+        let key = SigningKey::from_bytes(&[2u8; 32]);
+        let mut events: Vec<FactoEvent> = (0..3)
+            .map(|i| {
+                let mut event = test_event();
+                event.facto_id = format!("tr-{}", i);
+                sign_test_event(event, &key)
+            })
+            .collect();
+        // An enveloped event's own signature is not checked, its hash is
+        events[0].proof.signature = BASE64.encode([0u8; 64]);
+        events[1].input_data = serde_json::json!({"prompt": "other"});
+
+        let batch_hash = crypto::compute_batch_hash(&events);
+        let envelope = BatchEnvelope {
+            signature: BASE64.encode(key.sign(batch_hash.as_bytes()).to_bytes()),
+            public_key: events[0].proof.public_key.clone(),
+            batch_hash,
+            algorithm: None,
+        };
+        assert!(crypto::verify_batch_envelope(&envelope, &events).is_ok());
+        assert!(crypto::verify_batch_envelope(&envelope, &events[1..]).is_err());
+
+        let event_hashes: Vec<String> = events
+            .iter()
+            .map(|event| event.proof.event_hash.clone())
+            .collect();
+        let results = verifier
+            .verify_enveloped(
+                None,
+                &events,
+                &[EnforcementMode::Enforce; 3],
+                Some((&envelope, &event_hashes, &[true, true, false])),
+            )
+            .await;
+        let (assertion, _) = results[0].as_ref().unwrap();
+        assert!(matches!(
+            &assertion.trust_basis,
+            TrustBasis::BatchEnvelope { envelope: published, event_hashes: hashes, .. }
+                if *published == envelope && *hashes == event_hashes
+        ));
+        assert!(check_assertion(assertion).is_ok());
+        // The published envelope vouches for the event on its own
+        assert!(crypto::verify_envelope_membership(
+            &envelope,
+            &event_hashes,
+            &assertion.event_hash,
+            &events[0].proof.public_key
+        )
+        .is_ok());
+        assert!(matches!(
+            results[1],
+            Err(VerificationError::HashMismatch { .. })
+        ));
+        assert_eq!(
+            results[2].as_ref().unwrap().0.trust_basis,
+            TrustBasis::EmbeddedKey
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verification_method_must_resolve_to_proof_key() {
+        let verifier = test_verifier();
+        let key = SigningKey::from_bytes(&[2u8; 32]);
+        let did_of = |key: &SigningKey| {
+            did::did_key(&did::DidKey {
+                algorithm: SignatureAlgorithm::Ed25519,
+                bytes: key.verifying_key().to_bytes().to_vec(),
+            })
+        };
+
+        let mut event = test_event();
+        event.proof.verification_method = Some(did_of(&key));
+        let event = sign_test_event(event, &key);
+        let assertion = verify_one(&verifier, &event).await.unwrap();
+        assert!(matches!(assertion.trust_basis, TrustBasis::Did { .. }));
+        assert!(DataIntegrityProof::of(&event).is_some());
+
+        let mut impostor = test_event();
+        impostor.proof.verification_method = Some(did_of(&SigningKey::from_bytes(&[3u8; 32])));
+        let impostor = sign_test_event(impostor, &key);
+        assert!(matches!(
+            verify_one(&verifier, &impostor).await,
+            Err(VerificationError::InvalidVerificationMethod(_))
+        ));
+    }
+}
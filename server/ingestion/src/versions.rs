@@ -0,0 +1,115 @@
+use serde::Deserialize;
+
+use crate::{ExecutionMeta, FactoEvent, Proof};
+
+/// Newest event wire version the server understands
+pub const CURRENT_EVENT_VERSION: u32 = 1;
+
+/// Wire versions accepted, each with its own canonical form
+pub const SUPPORTED_EVENT_VERSIONS: &[u32] = &[1];
+
+/// Wire version of events that carry no `event_version`
+const UNVERSIONED_EVENT_VERSION: u32 = 1;
+
+// ============================================================================
+// Wire Versions
+// ============================================================================
+//
+// Each wire version has its own deserializer and an upgrade into the
+// internal `FactoEvent`. The internal model keeps the version the event was
+// received in, because the signed canonical form is defined per version.
+// To add a version: define `EventVn`, upgrade it in `From<EventVn>`, add it
+// to `FactoEvent::try_from` and to `build_canonical_form`.
+
+/// Version 1: the original SDK event, sent without `event_version`
+#[derive(Debug, Deserialize)]
+struct EventV1 {
+    facto_id: String,
+    agent_id: String,
+    session_id: String,
+    parent_facto_id: Option<String>,
+    action_type: String,
+    status: String,
+    input_data: serde_json::Value,
+    output_data: serde_json::Value,
+    execution_meta: ExecutionMeta,
+    proof: Proof,
+    started_at: i64,
+    completed_at: i64,
+}
+
+impl From<EventV1> for FactoEvent {
+    fn from(v1: EventV1) -> Self {
+        FactoEvent {
+            event_version: 1,
+            facto_id: v1.facto_id,
+            agent_id: v1.agent_id,
+            session_id: v1.session_id,
+            parent_facto_id: v1.parent_facto_id,
+            action_type: v1.action_type,
+            status: v1.status,
+            input_data: v1.input_data,
+            output_data: v1.output_data,
+            execution_meta: v1.execution_meta,
+            proof: v1.proof,
+            started_at: v1.started_at,
+            completed_at: v1.completed_at,
+            raw: None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EventVersionError {
+    #[error("Invalid event_version: {0}")]
+    Invalid(serde_json::Value),
+    #[error("Unsupported event_version {0}, this server supports up to {CURRENT_EVENT_VERSION}")]
+    Unsupported(u64),
+    #[error("{0}")]
+    Malformed(#[from] serde_json::Error),
+}
+
+/// Read an event in any supported wire version
+impl TryFrom<serde_json::Value> for FactoEvent {
+    type Error = EventVersionError;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        let version = match value.get("event_version") {
+            None | Some(serde_json::Value::Null) => UNVERSIONED_EVENT_VERSION as u64,
+            Some(v) => v
+                .as_u64()
+                .ok_or_else(|| EventVersionError::Invalid(v.clone()))?,
+        };
+
+        match version {
+            1 => Ok(serde_json::from_value::<EventV1>(value)?.into()),
+            v => Err(EventVersionError::Unsupported(v)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::test_event;
+    use crate::FactoEvent;
+
+    #[test]
+    fn test_wire_versions() {
+        let mut value = serde_json::to_value(test_event()).unwrap();
+
+        // Deployed SDKs send no version
+        value.as_object_mut().unwrap().remove("event_version");
+        let event: FactoEvent = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(event.event_version, 1);
+
+        value["event_version"] = serde_json::json!(1);
+        assert!(serde_json::from_value::<FactoEvent>(value.clone()).is_ok());
+
+        value["event_version"] = serde_json::json!(99);
+        let err = serde_json::from_value::<FactoEvent>(value.clone()).unwrap_err();
+        assert!(err.to_string().contains("Unsupported event_version 99"));
+
+        value["event_version"] = serde_json::json!("1");
+        assert!(serde_json::from_value::<FactoEvent>(value).is_err());
+    }
+}
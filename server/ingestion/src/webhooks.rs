@@ -0,0 +1,392 @@
+use axum::async_trait;
+use hmac::{Hmac, Mac};
+use metrics::counter;
+use rand::RngCore;
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use crate::sinks::{AcceptedEvent, FanoutSink};
+use crate::store::JsonFile;
+use crate::verification::now_nanos;
+use crate::FactoEvent;
+
+/// Header carrying `t=<unix seconds>,v1=<hex HMAC-SHA256>` of a delivery
+pub const SIGNATURE_HEADER: &str = "Facto-Signature";
+
+/// Header naming the webhook a delivery is for
+pub const WEBHOOK_ID_HEADER: &str = "Facto-Webhook-Id";
+
+// ============================================================================
+// Registrations
+// ============================================================================
+
+/// Which accepted events a webhook receives. An empty list matches every
+/// value of its field.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookFilter {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub agent_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub action_types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub statuses: Vec<String>,
+}
+
+impl WebhookFilter {
+    pub fn matches(&self, event: &FactoEvent) -> bool {
+        let admits =
+            |values: &[String], value: &str| values.is_empty() || values.iter().any(|v| v == value);
+        admits(&self.agent_ids, &event.agent_id)
+            && admits(&self.action_types, &event.action_type)
+            && admits(&self.statuses, &event.status)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookRequest {
+    pub url: String,
+    #[serde(default)]
+    pub filter: WebhookFilter,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    #[serde(default)]
+    pub filter: WebhookFilter,
+    pub created_at: i64,
+}
+
+/// A webhook with the secret its deliveries are signed with. Only returned
+/// when the webhook is registered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisteredWebhook {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    /// Hex-encoded HMAC-SHA256 key
+    pub secret: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookError {
+    #[error("invalid webhook: {0}")]
+    Invalid(String),
+    #[error("unknown webhook: {0}")]
+    Unknown(String),
+    #[error("failed to persist webhooks: {0}")]
+    Persistence(String),
+}
+
+/// Webhook endpoints registered through the admin API, persisted to
+/// `WEBHOOKS_PATH` when set
+pub struct Webhooks {
+    hooks: RwLock<BTreeMap<String, RegisteredWebhook>>,
+    store: JsonFile,
+    /// Accept `http://` endpoints, for local testing
+    allow_http: bool,
+}
+
+impl Webhooks {
+    pub fn new(path: Option<PathBuf>, allow_http: bool) -> anyhow::Result<Self> {
+        let store = JsonFile::new(path);
+        let hooks: BTreeMap<String, RegisteredWebhook> = store.load()?;
+        info!("Loaded {} webhooks", hooks.len());
+        Ok(Self {
+            hooks: RwLock::new(hooks),
+            store,
+            allow_http,
+        })
+    }
+
+    pub fn list(&self) -> Vec<Webhook> {
+        self.hooks
+            .read()
+            .unwrap()
+            .values()
+            .map(|h| h.webhook.clone())
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Webhook> {
+        self.hooks
+            .read()
+            .unwrap()
+            .get(id)
+            .map(|h| h.webhook.clone())
+    }
+
+    /// Register an endpoint under a new id and signing secret
+    pub fn register(&self, request: WebhookRequest) -> Result<RegisteredWebhook, WebhookError> {
+        let url = Url::parse(&request.url)
+            .map_err(|e| WebhookError::Invalid(format!("{}: {}", request.url, e)))?;
+        match url.scheme() {
+            "https" => {}
+            "http" if self.allow_http => {}
+            _ => return Err(WebhookError::Invalid("url must use https".to_string())),
+        }
+
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        let registered = RegisteredWebhook {
+            webhook: Webhook {
+                id: uuid::Uuid::new_v4().to_string(),
+                url: url.to_string(),
+                filter: request.filter,
+                created_at: now_nanos(),
+            },
+            secret: hex::encode(secret),
+        };
+
+        let mut hooks = self.hooks.write().unwrap();
+        let mut updated = hooks.clone();
+        updated.insert(registered.webhook.id.clone(), registered.clone());
+        self.persist(&updated)?;
+        *hooks = updated;
+        Ok(registered)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<Webhook, WebhookError> {
+        let mut hooks = self.hooks.write().unwrap();
+        let mut updated = hooks.clone();
+        let removed = updated
+            .remove(id)
+            .ok_or_else(|| WebhookError::Unknown(id.to_string()))?;
+        self.persist(&updated)?;
+        *hooks = updated;
+        Ok(removed.webhook)
+    }
+
+    /// The webhooks `event` is delivered to
+    fn matching(&self, event: &FactoEvent) -> Vec<RegisteredWebhook> {
+        self.hooks
+            .read()
+            .unwrap()
+            .values()
+            .filter(|h| h.webhook.filter.matches(event))
+            .cloned()
+            .collect()
+    }
+
+    fn persist(&self, hooks: &BTreeMap<String, RegisteredWebhook>) -> Result<(), WebhookError> {
+        self.store
+            .save(hooks)
+            .map_err(|e| WebhookError::Persistence(e.to_string()))
+    }
+}
+
+// ============================================================================
+// Delivery
+// ============================================================================
+
+/// Retry policy for webhook deliveries
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every further one
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Read `WEBHOOK_MAX_ATTEMPTS`, `WEBHOOK_RETRY_BASE_MS` and
+    /// `WEBHOOK_RETRY_MAX_MS`
+    pub fn from_env() -> Self {
+        let var = |name: &str, default: u64| {
+            std::env::var(name)
+                .unwrap_or_else(|_| default.to_string())
+                .parse::<u64>()
+                .unwrap_or_else(|_| panic!("Invalid {}", name))
+        };
+        Self {
+            max_attempts: var("WEBHOOK_MAX_ATTEMPTS", 5).max(1) as u32,
+            base_delay: Duration::from_millis(var("WEBHOOK_RETRY_BASE_MS", 500)),
+            max_delay: Duration::from_millis(var("WEBHOOK_RETRY_MAX_MS", 60_000)),
+        }
+    }
+
+    /// Delay before attempt `attempt + 1`, counting from 1
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_delay)
+    }
+}
+
+/// `t=<unix seconds>,v1=<hex HMAC-SHA256 of "<t>.<body>">`
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!(
+        "t={},v1={}",
+        timestamp,
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+/// Posts accepted events to every registered webhook whose filter matches.
+///
+/// Each delivery is a JSON `{"event", "envelope"}` body signed with the
+/// webhook's secret. Connection errors, 429 and 5xx responses are retried
+/// with exponential backoff; a delivery that exhausts its attempts or is
+/// refused with another status is dead-lettered: counted, logged and
+/// dropped.
+pub struct WebhookSink {
+    webhooks: Arc<Webhooks>,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl WebhookSink {
+    pub fn new(webhooks: Arc<Webhooks>, timeout: Duration, retry: RetryPolicy) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("webhook client builds");
+        Self {
+            webhooks,
+            client,
+            retry,
+        }
+    }
+
+    async fn deliver_to(&self, hook: &RegisteredWebhook, facto_id: &str, body: &[u8]) {
+        let id = hook.webhook.id.as_str();
+        for attempt in 1..=self.retry.max_attempts {
+            let signature = sign(&hook.secret, now_nanos() / 1_000_000_000, body);
+            let outcome = self
+                .client
+                .post(&hook.webhook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, signature)
+                .header(WEBHOOK_ID_HEADER, id)
+                .body(body.to_vec())
+                .send()
+                .await;
+            let retriable = match outcome {
+                Ok(response) if response.status().is_success() => {
+                    counter!("facto_webhook_delivered_total", "webhook" => id.to_string())
+                        .increment(1);
+                    return;
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    warn!("Webhook {} answered {} for {}", id, status, facto_id);
+                    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+                }
+                Err(e) => {
+                    warn!("Webhook {} delivery of {} failed: {}", id, facto_id, e);
+                    true
+                }
+            };
+            if !retriable || attempt == self.retry.max_attempts {
+                break;
+            }
+            counter!("facto_webhook_retries_total", "webhook" => id.to_string()).increment(1);
+            tokio::time::sleep(self.retry.backoff(attempt)).await;
+        }
+
+        warn!("Dead-lettered delivery of {} to webhook {}", facto_id, id);
+        counter!("facto_webhook_dead_letters_total", "webhook" => id.to_string()).increment(1);
+    }
+}
+
+#[async_trait]
+impl FanoutSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        "webhooks"
+    }
+
+    /// Dead-lettered deliveries are not errors of the sink, so events are
+    /// never redelivered to the webhooks that already received them
+    async fn deliver(&self, accepted: &AcceptedEvent) -> anyhow::Result<()> {
+        let (event, envelope) = accepted.as_ref();
+        let hooks = self.webhooks.matching(event);
+        if hooks.is_empty() {
+            return Ok(());
+        }
+        let body = serde_json::to_vec(&serde_json::json!({
+            "event": event,
+            "envelope": envelope,
+        }))?;
+        futures::future::join_all(
+            hooks
+                .iter()
+                .map(|hook| self.deliver_to(hook, &event.facto_id, &body)),
+        )
+        .await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_and_signature() {
+        let event = crate::testing::test_event();
+        let filter = WebhookFilter {
+            agent_ids: vec![],
+            action_types: vec![event.action_type.clone()],
+            statuses: vec!["error".to_string()],
+        };
+        assert_eq!(filter.matches(&event), event.status == "error");
+        assert!(WebhookFilter::default().matches(&event));
+
+        // Receivers recompute the HMAC over "<t>.<body>" with the secret
+        let signature = sign("secret", 1_700_000_000, b"{}");
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(b"1700000000.{}");
+        assert_eq!(
+            signature,
+            format!(
+                "t=1700000000,v1={}",
+                hex::encode(mac.finalize().into_bytes())
+            )
+        );
+    }
+
+    #[test]
+    fn test_registration_requires_https() {
+        let webhooks = Webhooks::new(None, false).unwrap();
+        assert!(matches!(
+            webhooks.register(WebhookRequest {
+                url: "http://example.com/hook".to_string(),
+                filter: WebhookFilter::default(),
+            }),
+            Err(WebhookError::Invalid(_))
+        ));
+
+        let registered = webhooks
+            .register(WebhookRequest {
+                url: "https://example.com/hook".to_string(),
+                filter: WebhookFilter::default(),
+            })
+            .unwrap();
+        assert_eq!(registered.secret.len(), 64);
+        assert_eq!(webhooks.list().len(), 1);
+        webhooks.remove(&registered.webhook.id).unwrap();
+        assert!(webhooks.get(&registered.webhook.id).is_none());
+
+        let retry = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(3),
+        };
+        assert_eq!(retry.backoff(1), Duration::from_millis(500));
+        assert_eq!(retry.backoff(3), Duration::from_secs(2));
+        assert_eq!(retry.backoff(4), Duration::from_secs(3));
+    }
+}
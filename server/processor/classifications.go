@@ -0,0 +1,132 @@
+package main
+
+import (
+	"context"
+	"encoding/json"
+	"time"
+
+	"github.com/gocql/gocql"
+	"github.com/nats-io/nats.go/jetstream"
+	"github.com/prometheus/client_golang/prometheus"
+	"github.com/prometheus/client_golang/prometheus/promauto"
+	"github.com/rs/zerolog/log"
+)
+
+// classificationSubject is where the ingestion service publishes classifier labels
+const classificationSubject = "facto.control.classifications"
+
+var classificationsStored = promauto.NewCounter(prometheus.CounterOpts{
+	Name: "facto_processor_classifications_stored_total",
+	Help: "Total number of event classifications stored",
+})
+
+// Label is a category assigned to an event by a classifier
+type Label struct {
+	Category   string   `json:"category"`
+	Classifier string   `json:"classifier"`
+	Rule       *string  `json:"rule"`
+	Confidence *float64 `json:"confidence"`
+}
+
+// Classification holds the server-signed labels of one event
+type Classification struct {
+	FactoID         string  `json:"facto_id"`
+	AgentID         string  `json:"agent_id"`
+	SessionID       string  `json:"session_id"`
+	TenantID        *string `json:"tenant_id"`
+	ActionType      string  `json:"action_type"`
+	CompletedAt     int64   `json:"completed_at"`
+	Labels          []Label `json:"labels"`
+	ClassifiedAt    int64   `json:"classified_at"`
+	SignerPublicKey string  `json:"signer_public_key"`
+	Signature       string  `json:"signature"`
+}
+
+// StartClassifications consumes classifications from the FACTO_CONTROL stream
+// and stores them, one message at a time
+func (c *Consumer) StartClassifications(ctx context.Context) error {
+	stream, err := c.js.Stream(ctx, "FACTO_CONTROL")
+	if err != nil {
+		return err
+	}
+
+	consumer, err := stream.CreateOrUpdateConsumer(ctx, jetstream.ConsumerConfig{
+		Durable:       "processor-classifications",
+		FilterSubject: classificationSubject,
+		AckPolicy:     jetstream.AckExplicitPolicy,
+		AckWait:       30 * time.Second,
+	})
+	if err != nil {
+		return err
+	}
+
+	log.Info().Msg("Started consuming classifications from FACTO_CONTROL stream")
+
+	consumeCtx, err := consumer.Consume(func(msg jetstream.Msg) {
+		var classification Classification
+		if err := json.Unmarshal(msg.Data(), &classification); err != nil {
+			log.Error().Err(err).Msg("Failed to unmarshal classification")
+			msg.Term()
+			return
+		}
+
+		if err := c.storage.StoreClassification(ctx, &classification); err != nil {
+			log.Error().Err(err).Str("facto_id", classification.FactoID).Msg("Failed to store classification")
+			msg.Nak()
+			return
+		}
+
+		msg.Ack()
+		classificationsStored.Inc()
+	})
+	if err != nil {
+		return err
+	}
+	defer consumeCtx.Stop()
+
+	<-ctx.Done()
+	return ctx.Err()
+}
+
+// StoreClassification stores the labels of an event in event_labels and
+// events_by_label
+func (s *Storage) StoreClassification(ctx context.Context, classification *Classification) error {
+	completedTime := time.Unix(0, classification.CompletedAt)
+	eventDate := completedTime.UTC().Truncate(24 * time.Hour)
+	classifiedTime := time.Unix(0, classification.ClassifiedAt)
+
+	var tenantID string
+	if classification.TenantID != nil {
+		tenantID = *classification.TenantID
+	}
+
+	batch := s.session.NewBatch(gocql.UnloggedBatch).WithContext(ctx)
+	categories := make(map[string]bool)
+	for _, label := range classification.Labels {
+		batch.Query(`
+			INSERT INTO event_labels (
+				facto_id, category, classifier, rule, confidence,
+				classified_at, signer_public_key, signature
+			) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+		`,
+			classification.FactoID, label.Category, label.Classifier, label.Rule, label.Confidence,
+			classifiedTime, classification.SignerPublicKey, classification.Signature,
+		)
+
+		if categories[label.Category] {
+			continue
+		}
+		categories[label.Category] = true
+		batch.Query(`
+			INSERT INTO events_by_label (
+				category, date, completed_at, facto_id,
+				agent_id, session_id, tenant_id, action_type
+			) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+		`,
+			label.Category, eventDate, completedTime, classification.FactoID,
+			classification.AgentID, classification.SessionID, tenantID, classification.ActionType,
+		)
+	}
+
+	return s.session.ExecuteBatch(batch)
+}
@@ -66,6 +66,36 @@ type FactoEvent struct {
 	Proof         Proof                  `json:"proof"`
 	StartedAt     int64                  `json:"started_at"`
 	CompletedAt   int64                  `json:"completed_at"`
+	// TrustBasis is the trust basis JSON from the Facto-Envelope header when
+	// a batch envelope stood in for the signature, kept so the Query API
+	// can check the envelope instead
+	TrustBasis    string                 `json:"-"`
+}
+
+// envelopeHeader carries the ingestion server's envelope for each event
+const envelopeHeader = "Facto-Envelope"
+
+// Envelope is the part of the server envelope the processor stores
+type Envelope struct {
+	Verification struct {
+		TrustBasis json.RawMessage `json:"trust_basis"`
+	} `json:"verification"`
+}
+
+// enveloped returns the trust basis of a server envelope when it names a
+// batch envelope
+func enveloped(raw string) string {
+	var envelope Envelope
+	if err := json.Unmarshal([]byte(raw), &envelope); err != nil {
+		return ""
+	}
+	var basis struct {
+		Type string `json:"type"`
+	}
+	if json.Unmarshal(envelope.Verification.TrustBasis, &basis) != nil || basis.Type != "batch_envelope" {
+		return ""
+	}
+	return string(envelope.Verification.TrustBasis)
 }
 
 // ExecutionMeta contains execution metadata
@@ -83,10 +113,14 @@ type ExecutionMeta struct {
 
 // Proof contains cryptographic proof
 type Proof struct {
-	Signature string `json:"signature"`
-	PublicKey string `json:"public_key"`
-	PrevHash  string `json:"prev_hash"`
-	EventHash string `json:"event_hash"`
+	Signature        string `json:"signature"`
+	PublicKey        string `json:"public_key"`
+	// Algorithm is empty for Ed25519, or "es256" or "secp256k1"
+	Algorithm        string `json:"algorithm,omitempty"`
+	// CanonicalVersion is nil for canonical form version 1
+	CanonicalVersion *int   `json:"canonical_version,omitempty"`
+	PrevHash         string `json:"prev_hash"`
+	EventHash        string `json:"event_hash"`
 }
 
 // Consumer handles NATS message consumption
@@ -138,17 +172,26 @@ func NewConsumer(natsURL string, storage *Storage, batchSize int, flushInterval
 func (c *Consumer) Start(ctx context.Context) error {
 	// Get or create stream
 	stream, err := c.js.Stream(ctx, "FACTO_EVENTS")
-	subject := "facto.events.>"
+	// Shared events and per-tenant events (facto.tenants.{tenant}.events.{agent})
+	subjects := []string{"facto.events.>", "facto.tenants.>"}
+	// With NATS_SESSION_PARTITIONS set on the ingestion service, each
+	// processor can take one partition (SESSION_PARTITION) and still see
+	// every event of its sessions, in order
+	partition := os.Getenv("SESSION_PARTITION")
+	if partition != "" {
+		subjects = []string{"facto.events.*." + partition, "facto.tenants.*.events.*." + partition}
+		log.Info().Str("partition", partition).Msg("Consuming one session partition")
+	}
 	if filter := os.Getenv("FILTER_SUBJECT"); filter != "" {
-		subject = filter
-		log.Info().Str("filter_subject", subject).Msg("Using filtered subject")
+		subjects = []string{filter}
+		log.Info().Str("filter_subject", filter).Msg("Using filtered subject")
 	}
 
 	if err != nil {
 		// Try to create the stream if it doesn't exist
 		stream, err = c.js.CreateStream(ctx, jetstream.StreamConfig{
 			Name:      "FACTO_EVENTS",
-			Subjects:  []string{"facto.events.>"}, // Stream needs full range
+			Subjects:  []string{"facto.events.>", "facto.tenants.>"}, // Stream needs full range
 			Retention: jetstream.WorkQueuePolicy,
 			Storage:   jetstream.FileStorage,
 		})
@@ -161,6 +204,9 @@ func (c *Consumer) Start(ctx context.Context) error {
 	durableName := os.Getenv("DURABLE_NAME")
 	if durableName == "" {
 		durableName = "processor"
+		if partition != "" {
+			durableName = "processor-" + partition
+		}
 	}
 
 	// Delete consumer if requested to reset state
@@ -175,11 +221,11 @@ func (c *Consumer) Start(ctx context.Context) error {
 
 	consumer, err := stream.CreateOrUpdateConsumer(ctx, jetstream.ConsumerConfig{
 		Durable: durableName,
-		// If I change the FilterSubject, I update the consumer.
-		FilterSubject: subject,
-		AckPolicy:     jetstream.AckExplicitPolicy,
-		MaxAckPending: c.batchSize * 2,
-		AckWait:       30 * time.Second,
+		// If I change the FilterSubjects, I update the consumer.
+		FilterSubjects: subjects,
+		AckPolicy:      jetstream.AckExplicitPolicy,
+		MaxAckPending:  c.batchSize * 2,
+		AckWait:        30 * time.Second,
 	})
 	if err != nil {
 		return err
@@ -247,6 +293,9 @@ func (c *Consumer) handleMessage(ctx context.Context, msg jetstream.Msg) {
 		eventsFailedTotal.Inc()
 		return
 	}
+	if raw := msg.Headers().Get(envelopeHeader); raw != "" {
+		event.TrustBasis = enveloped(raw)
+	}
 
 	c.events = append(c.events, event)
 	c.messages = append(c.messages, msg)
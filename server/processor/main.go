@@ -123,6 +123,13 @@ func main() {
 		}
 	}()
 
+	// Store classifier labels published by the ingestion service
+	go func() {
+		if err := consumer.StartClassifications(ctx); err != nil && err != context.Canceled {
+			log.Error().Err(err).Msg("Classification consumer error")
+		}
+	}()
+
 	// Wait for shutdown signal
 	sigChan := make(chan os.Signal, 1)
 	signal.Notify(sigChan, syscall.SIGINT, syscall.SIGTERM)
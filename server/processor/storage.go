@@ -57,11 +57,13 @@ type eventData struct {
 	seed          int64
 	maxTokens     int32
 	parentFactoID string
+	trustBasis    *string
 }
 
 // StoreBatch stores a batch of events using concurrent per-table batches
 // This allows processing 1000 events (3000 total inserts) by splitting into
-// 3 concurrent batches of 1000 inserts each, staying within ScyllaDB limits
+// 3 concurrent batches of 1000 inserts each, staying within ScyllaDB limits.
+// Events with a parent also get a row in events_by_parent.
 func (s *Storage) StoreBatch(ctx context.Context, events []FactoEvent) error {
 	// Pre-process all events once
 	processedEvents := make([]eventData, len(events))
@@ -101,6 +103,11 @@ func (s *Storage) StoreBatch(ctx context.Context, events []FactoEvent) error {
 			parentFactoID = *event.ParentFactoID
 		}
 
+		var trustBasis *string
+		if event.TrustBasis != "" {
+			trustBasis = &event.TrustBasis
+		}
+
 		processedEvents[i] = eventData{
 			event:         event,
 			inputData:     inputData,
@@ -116,10 +123,11 @@ func (s *Storage) StoreBatch(ctx context.Context, events []FactoEvent) error {
 			seed:          seed,
 			maxTokens:     maxTokens,
 			parentFactoID: parentFactoID,
+			trustBasis:    trustBasis,
 		}
 	}
 
-	// Execute 3 table batches concurrently
+	// Execute the table batches concurrently
 	g, ctx := errgroup.WithContext(ctx)
 
 	// Batch 1: Main events table
@@ -137,6 +145,11 @@ func (s *Storage) StoreBatch(ctx context.Context, events []FactoEvent) error {
 		return s.storeBySessionBatch(ctx, processedEvents)
 	})
 
+	// Batch 4: events_by_parent lookup table
+	g.Go(func() error {
+		return s.storeByParentBatch(ctx, processedEvents)
+	})
+
 	if err := g.Wait(); err != nil {
 		log.Error().Err(err).Int("batch_size", len(events)).Msg("Failed to store batch")
 		return err
@@ -163,15 +176,15 @@ func (s *Storage) storeEventsBatch(ctx context.Context, events []eventData) erro
 					action_type, status, input_data, output_data,
 					model_id, model_hash, temperature, seed, max_tokens, tool_calls,
 					sdk_version, sdk_language, tags,
-					signature, public_key, prev_hash, event_hash,
+					signature, public_key, signature_algorithm, canonical_version, trust_basis, prev_hash, event_hash,
 					started_at, completed_at, received_at
-				) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+				) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
 			`,
 				e.event.AgentID, e.eventDate, e.event.FactoID, e.event.SessionID, e.parentFactoID,
 				e.event.ActionType, e.event.Status, e.inputData, e.outputData,
 				e.modelID, e.modelHash, e.temperature, e.seed, e.maxTokens, string(e.toolCalls),
 				e.sdkVersion, e.sdkLanguage, e.event.ExecutionMeta.Tags,
-				[]byte(e.event.Proof.Signature), []byte(e.event.Proof.PublicKey),
+				[]byte(e.event.Proof.Signature), []byte(e.event.Proof.PublicKey), e.event.Proof.Algorithm, e.event.Proof.CanonicalVersion, e.trustBasis,
 				e.event.Proof.PrevHash, e.event.Proof.EventHash,
 				time.Unix(0, e.event.StartedAt), e.completedTime, time.Now(),
 			)
@@ -201,15 +214,15 @@ func (s *Storage) storeByFactoIDBatch(ctx context.Context, events []eventData) e
 					action_type, status, input_data, output_data,
 					model_id, model_hash, temperature, seed, max_tokens, tool_calls,
 					sdk_version, sdk_language, tags,
-					signature, public_key, prev_hash, event_hash,
+					signature, public_key, signature_algorithm, canonical_version, trust_basis, prev_hash, event_hash,
 					parent_facto_id, started_at, received_at
-				) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+				) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
 			`,
 				e.event.FactoID, e.event.AgentID, e.eventDate, e.completedTime, e.event.SessionID,
 				e.event.ActionType, e.event.Status, e.inputData, e.outputData,
 				e.modelID, e.modelHash, e.temperature, e.seed, e.maxTokens, string(e.toolCalls),
 				e.sdkVersion, e.sdkLanguage, e.event.ExecutionMeta.Tags,
-				[]byte(e.event.Proof.Signature), []byte(e.event.Proof.PublicKey),
+				[]byte(e.event.Proof.Signature), []byte(e.event.Proof.PublicKey), e.event.Proof.Algorithm, e.event.Proof.CanonicalVersion, e.trustBasis,
 				e.event.Proof.PrevHash, e.event.Proof.EventHash,
 				e.parentFactoID, time.Unix(0, e.event.StartedAt), time.Now(),
 			)
@@ -240,16 +253,16 @@ func (s *Storage) storeBySessionBatch(ctx context.Context, events []eventData) e
 					input_data, output_data,
 					model_id, temperature,
 					sdk_version, sdk_language, tags,
-					signature, public_key, prev_hash,
+					signature, public_key, signature_algorithm, canonical_version, trust_basis, prev_hash,
 					parent_facto_id, started_at, received_at
-				) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+				) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
 			`,
 				e.event.SessionID, e.completedTime, e.event.FactoID, e.event.AgentID,
 				e.event.ActionType, e.event.Status, e.event.Proof.EventHash,
 				e.inputData, e.outputData,
 				e.modelID, e.temperature,
 				e.sdkVersion, e.sdkLanguage, e.event.ExecutionMeta.Tags,
-				[]byte(e.event.Proof.Signature), []byte(e.event.Proof.PublicKey),
+				[]byte(e.event.Proof.Signature), []byte(e.event.Proof.PublicKey), e.event.Proof.Algorithm, e.event.Proof.CanonicalVersion, e.trustBasis,
 				e.event.Proof.PrevHash,
 				e.parentFactoID, time.Unix(0, e.event.StartedAt), time.Now(),
 			)
@@ -262,6 +275,41 @@ func (s *Storage) storeBySessionBatch(ctx context.Context, events []eventData) e
 	return nil
 }
 
+// storeByParentBatch inserts events that have a parent into the
+// events_by_parent lookup table
+func (s *Storage) storeByParentBatch(ctx context.Context, events []eventData) error {
+	var children []eventData
+	for _, e := range events {
+		if e.parentFactoID != "" {
+			children = append(children, e)
+		}
+	}
+
+	for i := 0; i < len(children); i += maxBatchSize {
+		end := i + maxBatchSize
+		if end > len(children) {
+			end = len(children)
+		}
+		chunk := children[i:end]
+
+		batch := s.session.NewBatch(gocql.UnloggedBatch).WithContext(ctx)
+		for _, e := range chunk {
+			batch.Query(`
+				INSERT INTO events_by_parent (
+					parent_facto_id, completed_at, facto_id, agent_id, session_id
+				) VALUES (?, ?, ?, ?, ?)
+			`,
+				e.parentFactoID, e.completedTime, e.event.FactoID, e.event.AgentID, e.event.SessionID,
+			)
+		}
+
+		if err := s.session.ExecuteBatch(batch); err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
 // StoreMerkleRoot stores a Merkle root entry
 func (s *Storage) StoreMerkleRoot(ctx context.Context, bucketTime time.Time, rootHash string, eventCount int, firstFactoID, lastFactoID string, eventHashes []string) error {
 	date := bucketTime.UTC().Truncate(24 * time.Hour)